@@ -0,0 +1,189 @@
+use crate::analysis::black::BlackFrameScanner;
+use crate::config::{BlackFrameConfig, DebandConfig};
+use crate::utils::{Error, FfmpegWrapper, Result};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Width sample frames are scaled to (nearest neighbour, so steps survive)
+const SAMPLE_WIDTH: usize = 640;
+
+/// Shortest run of identical pixels that counts as a flat band
+const MIN_BAND_RUN: usize = 8;
+
+/// Largest step between neighbouring bands, in 8-bit levels, that still
+/// reads as a quantized gradient rather than an edge
+const MAX_BAND_STEP: u8 = 2;
+
+/// Share of the pixels of an 8-bit grey frame that lie in stepped gradients:
+/// runs of at least [`MIN_BAND_RUN`] identical pixels next to a run that is
+/// at most [`MAX_BAND_STEP`] levels brighter or darker. Flat fills, grain and
+/// hard edges all score close to 0.
+pub fn banding_score(frame: &[u8], width: usize) -> f64 {
+    if width == 0 || frame.len() < width {
+        return 0.0;
+    }
+
+    let mut banded = 0usize;
+    for row in frame.chunks_exact(width) {
+        let mut runs: Vec<(u8, usize)> = Vec::new();
+        for &pixel in row {
+            match runs.last_mut() {
+                Some((value, length)) if *value == pixel => *length += 1,
+                _ => runs.push((pixel, 1)),
+            }
+        }
+        let mut counted = vec![false; runs.len()];
+        for i in 1..runs.len() {
+            let ((previous, previous_length), (value, length)) = (runs[i - 1], runs[i]);
+            if previous_length >= MIN_BAND_RUN
+                && length >= MIN_BAND_RUN
+                && previous.abs_diff(value) <= MAX_BAND_STEP
+            {
+                counted[i - 1] = true;
+                counted[i] = true;
+            }
+        }
+        banded += runs
+            .iter()
+            .zip(&counted)
+            .filter(|(_, &counted)| counted)
+            .map(|((_, length), _)| length)
+            .sum::<usize>();
+    }
+    banded as f64 / (frame.len() / width * width) as f64
+}
+
+/// Estimates how prone a source is to banding from a few frames spread over
+/// its runtime.
+pub struct BandingDetector {
+    ffmpeg: FfmpegWrapper,
+    samples: usize,
+    black_frames: Option<BlackFrameConfig>,
+}
+
+impl BandingDetector {
+    /// Samples the video stream `ffmpeg` selects (`--video-stream`).
+    pub fn new(ffmpeg: &FfmpegWrapper, config: &DebandConfig) -> Self {
+        Self {
+            ffmpeg: ffmpeg.clone(),
+            samples: config.samples,
+            black_frames: None,
+        }
+    }
+
+    /// Moves sample frames off black frames, scanning around each of them.
+    pub fn with_black_frames(mut self, black_frames: BlackFrameConfig) -> Self {
+        self.black_frames = Some(black_frames);
+        self
+    }
+
+    /// Mean [`banding_score`] of the sample frames, taken between 10% and 90%
+    /// of `duration` to stay clear of openings, credits and fades.
+    pub async fn measure(&self, input_path: &Path, duration: f64) -> Result<f64> {
+        let mut timestamps: Vec<f64> = (0..self.samples)
+            .map(|i| duration * (0.1 + 0.8 * (i as f64 + 0.5) / self.samples as f64))
+            .collect();
+        if let Some(black_frames) = &self.black_frames {
+            timestamps = BlackFrameScanner::new(&self.ffmpeg, black_frames.clone())
+                .avoid_black(input_path, &timestamps, duration)
+                .await;
+        }
+
+        let mut scores = Vec::with_capacity(self.samples);
+        for timestamp in timestamps {
+            let frame = self.grab_frame(input_path, timestamp).await?;
+            let score = banding_score(&frame, SAMPLE_WIDTH);
+            debug!("Banding at {:.1}s: {:.3}", timestamp, score);
+            scores.push(score);
+        }
+        if scores.is_empty() {
+            return Ok(0.0);
+        }
+        let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+        info!(
+            "Banding check: score {:.3} over {} sample frames",
+            mean,
+            scores.len()
+        );
+        Ok(mean)
+    }
+
+    /// FFmpeg arguments writing the frame at `timestamp` as 8-bit grey
+    /// pixels, [`SAMPLE_WIDTH`] wide.
+    fn frame_args(&self, input_path: &Path, timestamp: f64) -> Vec<String> {
+        let mut args: Vec<String> = ["-hide_banner", "-nostats", "-loglevel", "error"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(self.ffmpeg.input_options().iter().cloned());
+        args.extend([
+            "-ss".to_string(),
+            format!("{:.3}", timestamp),
+            "-i".to_string(),
+            input_path.to_string_lossy().to_string(),
+            "-map".to_string(),
+            self.ffmpeg.video_map_specifier(),
+            "-frames:v".to_string(),
+            "1".to_string(),
+            "-vf".to_string(),
+            format!("scale={}:-2:flags=neighbor,format=gray", SAMPLE_WIDTH),
+            "-f".to_string(),
+            "rawvideo".to_string(),
+            "-".to_string(),
+        ]);
+        args
+    }
+
+    async fn grab_frame(&self, input_path: &Path, timestamp: f64) -> Result<Vec<u8>> {
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(self.frame_args(input_path, timestamp))
+            .output()
+            .await?;
+        if !output.status.success() || output.stdout.len() < SAMPLE_WIDTH {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "Grabbing a frame for the banding check at {:.1}s failed: {}",
+                timestamp,
+                stderr.lines().last().unwrap_or("no frame decoded")
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_banding_score() {
+        let rows = 4;
+        // 8-bit sky gradient: 20 levels across the frame, 32 pixels each
+        let gradient: Vec<u8> = (0..rows)
+            .flat_map(|_| (0..SAMPLE_WIDTH).map(|x| 100 + (x / 32) as u8))
+            .collect();
+        assert!(banding_score(&gradient, SAMPLE_WIDTH) > 0.99);
+
+        // A flat cel and hard cel edges are not banding
+        let flat = vec![128u8; SAMPLE_WIDTH * rows];
+        assert_eq!(banding_score(&flat, SAMPLE_WIDTH), 0.0);
+        let cels: Vec<u8> = (0..rows)
+            .flat_map(|_| (0..SAMPLE_WIDTH).map(|x| [40, 200][x / 64 % 2]))
+            .collect();
+        assert_eq!(banding_score(&cels, SAMPLE_WIDTH), 0.0);
+
+        // Dithered or grainy gradients break the runs up
+        let grain: Vec<u8> = (0..SAMPLE_WIDTH * rows)
+            .map(|i| 100 + ((i % SAMPLE_WIDTH) / 32) as u8 + (i * 7 % 3) as u8)
+            .collect();
+        assert!(banding_score(&grain, SAMPLE_WIDTH) < 0.05);
+
+        // Frames come from the stream --video-stream picked
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(1));
+        let detector = BandingDetector::new(&ffmpeg, &DebandConfig::default());
+        let args = detector.frame_args(Path::new("in.mkv"), 60.0).join(" ");
+        assert!(args.contains("-ss 60.000 -i in.mkv -map 0:v:1 -frames:v 1"));
+    }
+}
@@ -0,0 +1,163 @@
+//! Bits per pixel of the source's video stream (`analysis.starved_source`),
+//! from a histogram of its bitrate second by second. A source that already
+//! spends very few bits per pixel for its resolution carries compression
+//! artifacts that a re-encode at the profile's CRF would smear further.
+
+use crate::utils::{Error, FfmpegWrapper, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::info;
+
+/// Bitrate of the source's video stream, in bits per pixel.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceBitrate {
+    /// Video codec of the source (as named by ffprobe)
+    pub codec: String,
+    /// Median of the per-second bitrates
+    pub median_bpp: f64,
+    /// 10th percentile of the per-second bitrates: the starved stretches
+    pub low_bpp: f64,
+}
+
+impl SourceBitrate {
+    /// Median bits per pixel scaled to what H.264 would need for the same
+    /// picture, so thresholds hold across source codecs.
+    pub fn h264_equivalent_bpp(&self) -> f64 {
+        self.median_bpp / codec_efficiency(&self.codec)
+    }
+
+    /// Whether the source spends fewer bits per pixel than `min_bits_per_pixel`
+    /// (H.264 equivalent).
+    pub fn is_starved(&self, min_bits_per_pixel: f64) -> bool {
+        self.h264_equivalent_bpp() < min_bits_per_pixel
+    }
+}
+
+/// Bits a codec needs for a picture relative to H.264.
+pub fn codec_efficiency(codec: &str) -> f64 {
+    match codec {
+        "hevc" | "vp9" => 0.6,
+        "av1" | "vvc" => 0.5,
+        "mpeg2video" | "mpeg4" | "msmpeg4v3" => 1.5,
+        _ => 1.0,
+    }
+}
+
+/// `pts_time,size` lines as printed by ffprobe; packets without a time are
+/// left out.
+fn parse_packets(output: &str) -> Vec<(f64, u64)> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (time, size) = line.trim().split_once(',')?;
+            Some((time.parse().ok()?, size.trim_end_matches(',').parse().ok()?))
+        })
+        .collect()
+}
+
+/// Bits of every whole second of the stream, in order. Seconds without
+/// packets count as empty.
+pub fn bitrate_histogram(packets: &[(f64, u64)]) -> Vec<u64> {
+    let mut seconds: Vec<u64> = Vec::new();
+    for &(time, size) in packets {
+        let second = time.max(0.0) as usize;
+        if second >= seconds.len() {
+            seconds.resize(second + 1, 0);
+        }
+        seconds[second] += size * 8;
+    }
+    seconds
+}
+
+/// The `share` quantile (0.0-1.0) of the per-second bitrates.
+fn quantile(seconds: &[u64], share: f64) -> Option<u64> {
+    let mut sorted = seconds.to_vec();
+    sorted.sort_unstable();
+    let position = ((sorted.len() as f64 - 1.0) * share).round() as usize;
+    sorted.get(position).copied()
+}
+
+/// Bits per pixel of `bits_per_second` for the frame size and rate.
+pub fn bits_per_pixel(bits_per_second: u64, width: u32, height: u32, fps: f32) -> f64 {
+    let pixels_per_second = width as f64 * height as f64 * fps as f64;
+    if pixels_per_second <= 0.0 {
+        return 0.0;
+    }
+    bits_per_second as f64 / pixels_per_second
+}
+
+/// Reads the size of every packet of the selected video stream (the whole
+/// file, without decoding) and sums them up per second.
+pub async fn measure_source_bitrate(
+    ffmpeg: &FfmpegWrapper,
+    input_path: &Path,
+    codec: &str,
+    (width, height, fps): (u32, u32, f32),
+) -> Result<SourceBitrate> {
+    let output = ffmpeg
+        .run_ffprobe(&[
+            "-v",
+            "error",
+            "-select_streams",
+            &ffmpeg.video_stream_specifier(),
+            "-show_entries",
+            "packet=pts_time,size",
+            "-of",
+            "csv=p=0",
+            &input_path.to_string_lossy(),
+        ])
+        .await?;
+    let mut seconds = bitrate_histogram(&parse_packets(&output));
+    // The last second is usually cut short
+    if seconds.len() > 1 {
+        seconds.pop();
+    }
+    let bpp =
+        |share| quantile(&seconds, share).map(|bits| bits_per_pixel(bits, width, height, fps));
+    let (Some(median_bpp), Some(low_bpp)) = (bpp(0.5), bpp(0.1)) else {
+        return Err(Error::parse("ffprobe listed no video packets"));
+    };
+    info!(
+        "Source bitrate: {:.3} bits per pixel (median), {:.3} (10th percentile) over {}s",
+        median_bpp,
+        low_bpp,
+        seconds.len()
+    );
+    Ok(SourceBitrate {
+        codec: codec.to_string(),
+        median_bpp,
+        low_bpp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_bits_per_pixel() {
+        let packets =
+            parse_packets("0.000,120000\n0.042,30000\nN/A,500\n1.001,25000,\n3.5,50000\n");
+        assert_eq!(packets.len(), 4);
+        let seconds = bitrate_histogram(&packets);
+        assert_eq!(seconds, vec![1_200_000, 200_000, 0, 400_000]);
+        assert_eq!(quantile(&seconds, 0.5), Some(400_000));
+        assert_eq!(quantile(&seconds, 0.1), Some(0));
+
+        // 2 Mbps of 1080p24 H.264 is starved, 20 Mbps is not
+        let bpp = bits_per_pixel(2_000_000, 1920, 1080, 24.0);
+        assert!((bpp - 0.040).abs() < 0.001);
+        assert!(bits_per_pixel(20_000_000, 1920, 1080, 24.0) > 0.4);
+        assert_eq!(bits_per_pixel(1_000, 0, 0, 0.0), 0.0);
+
+        // HEVC gets by on fewer bits for the same picture
+        let source = SourceBitrate {
+            codec: "hevc".to_string(),
+            median_bpp: 0.03,
+            low_bpp: 0.01,
+        };
+        assert!((source.h264_equivalent_bpp() - 0.05).abs() < 1e-9);
+        assert!(source.is_starved(0.06));
+        assert!(!source.is_starved(0.04));
+    }
+}
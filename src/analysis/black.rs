@@ -0,0 +1,283 @@
+use crate::config::BlackFrameConfig;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+static BLACKDETECT_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"black_start:\s*(-?[\d.]+)\s+black_end:\s*(-?[\d.]+)").unwrap());
+
+/// Distance kept from the edge of a black range when moving a sample out of it.
+const EDGE_MARGIN_SECONDS: f64 = 1.0;
+
+/// Seconds scanned around each sample point; the rest of the file is never decoded.
+pub const BLACK_SCAN_WINDOW: f64 = 20.0;
+
+/// A stretch of black or near-black frames, in seconds from the start of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BlackRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl BlackRange {
+    pub fn contains(&self, timestamp: f64) -> bool {
+        self.start <= timestamp && timestamp <= self.end
+    }
+}
+
+/// Parses `blackdetect` lines from FFmpeg's stderr, shifting them by `offset`
+/// when the scan started at a seek point.
+pub fn parse_blackdetect(stderr: &str, offset: f64) -> Vec<BlackRange> {
+    BLACKDETECT_REGEX
+        .captures_iter(stderr)
+        .filter_map(|caps| {
+            let start: f64 = caps[1].parse().ok()?;
+            let end: f64 = caps[2].parse().ok()?;
+            Some(BlackRange {
+                start: start.max(0.0) + offset,
+                end: end + offset,
+            })
+        })
+        .collect()
+}
+
+/// Moves every timestamp that falls inside a black range to the nearest
+/// non-black point within `[0, duration]`, so sampling sees representative
+/// picture instead of fades, slates or studio logos.
+pub fn exclude_black_ranges(timestamps: &[f64], ranges: &[BlackRange], duration: f64) -> Vec<f64> {
+    let is_black = |t: f64| ranges.iter().any(|range| range.contains(t));
+
+    timestamps
+        .iter()
+        .map(|&timestamp| {
+            let Some(range) = ranges.iter().find(|range| range.contains(timestamp)) else {
+                return timestamp;
+            };
+
+            let after = range.end + EDGE_MARGIN_SECONDS;
+            let before = range.start - EDGE_MARGIN_SECONDS;
+            let candidates = [after, before]
+                .into_iter()
+                .filter(|&t| (0.0..=duration).contains(&t) && !is_black(t));
+            let replacement = candidates.min_by(|a, b| {
+                (a - timestamp)
+                    .abs()
+                    .partial_cmp(&(b - timestamp).abs())
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+            match replacement {
+                Some(moved) => {
+                    debug!(
+                        "Sample at {:.2}s falls in black range {:.2}-{:.2}s, using {:.2}s",
+                        timestamp, range.start, range.end, moved
+                    );
+                    moved
+                }
+                // Nothing usable nearby (e.g. a mostly black file): keep the original point
+                None => timestamp,
+            }
+        })
+        .collect()
+}
+
+/// Seconds of `ranges` that fall within `start..end`.
+pub fn black_seconds(ranges: &[BlackRange], start: f64, end: f64) -> f64 {
+    ranges
+        .iter()
+        .map(|range| (range.end.min(end) - range.start.max(start)).max(0.0))
+        .sum()
+}
+
+/// Runs FFmpeg's `blackdetect` around sample points to find ranges that
+/// analysis sampling should avoid.
+pub struct BlackFrameScanner {
+    ffmpeg: FfmpegWrapper,
+    config: BlackFrameConfig,
+}
+
+impl BlackFrameScanner {
+    /// Scans the video stream `ffmpeg` selects (`--video-stream`).
+    pub fn new(ffmpeg: &FfmpegWrapper, config: BlackFrameConfig) -> Self {
+        Self {
+            ffmpeg: ffmpeg.clone(),
+            config,
+        }
+    }
+
+    /// FFmpeg arguments running blackdetect over keyframes of `length`
+    /// seconds of the input from `start`.
+    fn scan_args(&self, input_path: &Path, start: f64, length: f64) -> Vec<String> {
+        let filter = format!(
+            "scale=320:-2,blackdetect=d={}:pic_th={}:pix_th={}",
+            self.config.min_duration, self.config.picture_threshold, self.config.pixel_threshold
+        );
+
+        let mut args: Vec<String> = ["-hide_banner", "-nostats", "-loglevel", "info"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(self.ffmpeg.input_options().iter().cloned());
+        args.extend(["-skip_frame".to_string(), "nokey".to_string()]);
+        args.extend(["-ss".to_string(), start.to_string()]);
+        args.extend(["-i".to_string(), input_path.to_string_lossy().to_string()]);
+        args.extend(["-t".to_string(), length.to_string()]);
+        args.extend(["-map".to_string(), self.ffmpeg.video_map_specifier()]);
+        args.extend(
+            ["-an", "-sn", "-dn", "-vf", &filter, "-f", "null", "-"]
+                .iter()
+                .map(|arg| arg.to_string()),
+        );
+        args
+    }
+
+    /// Scans `length` seconds of the file from `start`. Only keyframes are
+    /// decoded, at low resolution.
+    pub async fn scan(
+        &self,
+        input_path: &Path,
+        start: f64,
+        length: f64,
+    ) -> Result<Vec<BlackRange>> {
+        if !self.config.enabled {
+            return Ok(Vec::new());
+        }
+
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(self.scan_args(input_path, start, length))
+            .output()
+            .await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "Black frame scan failed: {}",
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+
+        let ranges = parse_blackdetect(&stderr, start);
+        debug!(
+            "Black frame scan of {:.1}-{:.1}s: {} black range(s)",
+            start,
+            start + length,
+            ranges.len()
+        );
+        Ok(ranges)
+    }
+
+    /// Black ranges within [`BLACK_SCAN_WINDOW`] of each of `timestamps`.
+    pub async fn scan_around(
+        &self,
+        input_path: &Path,
+        timestamps: &[f64],
+    ) -> Result<Vec<BlackRange>> {
+        let mut ranges = Vec::new();
+        for &timestamp in timestamps {
+            let start = (timestamp - BLACK_SCAN_WINDOW / 2.0).max(0.0);
+            ranges.extend(self.scan(input_path, start, BLACK_SCAN_WINDOW).await?);
+        }
+        Ok(ranges)
+    }
+
+    /// Moves the automatically chosen `timestamps` off black frames (see
+    /// [`exclude_black_ranges`]). If the scan fails they are kept as they are.
+    pub async fn avoid_black(
+        &self,
+        input_path: &Path,
+        timestamps: &[f64],
+        duration: f64,
+    ) -> Vec<f64> {
+        let ranges = match self.scan_around(input_path, timestamps).await {
+            Ok(ranges) => ranges,
+            Err(e) => {
+                warn!("Black frame scan failed, sampling without it: {}", e);
+                return timestamps.to_vec();
+            }
+        };
+        let adjusted = exclude_black_ranges(timestamps, &ranges, duration);
+        let moved = adjusted
+            .iter()
+            .zip(timestamps)
+            .filter(|(adjusted, timestamp)| adjusted != timestamp)
+            .count();
+        if moved > 0 {
+            info!(
+                "Black frame scan: moved {} of {} sample point(s) off black frames",
+                moved,
+                timestamps.len()
+            );
+        }
+        adjusted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_blackdetect() {
+        let stderr = "\
+[blackdetect @ 0x5581] black_start:0 black_end:4.504 black_duration:4.504
+frame=  120 fps=0.0 q=-0.0 size=N/A
+[blackdetect @ 0x5581] black_start:5390.89 black_end:5412.2 black_duration:21.31
+";
+        let ranges = parse_blackdetect(stderr, 0.0);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(
+            ranges[0],
+            BlackRange {
+                start: 0.0,
+                end: 4.504
+            }
+        );
+        assert_eq!(ranges[1].start, 5390.89);
+
+        let shifted = parse_blackdetect(stderr, 100.0);
+        assert_eq!(shifted[0].start, 100.0);
+    }
+
+    #[test]
+    fn test_exclude_black_ranges() {
+        let ranges = [
+            BlackRange {
+                start: 0.0,
+                end: 10.0,
+            },
+            BlackRange {
+                start: 50.0,
+                end: 52.0,
+            },
+            BlackRange {
+                start: 95.0,
+                end: 100.0,
+            },
+        ];
+        let adjusted = exclude_black_ranges(&[2.0, 30.0, 51.5, 98.0], &ranges, 100.0);
+        assert_eq!(adjusted, vec![11.0, 30.0, 53.0, 94.0]);
+
+        // Entirely black: nothing better to pick
+        let all_black = [BlackRange {
+            start: 0.0,
+            end: 100.0,
+        }];
+        assert_eq!(exclude_black_ranges(&[40.0], &all_black, 100.0), vec![40.0]);
+
+        // Overlap with a preview segment
+        assert_eq!(black_seconds(&ranges, 5.0, 51.0), 6.0);
+        assert_eq!(black_seconds(&ranges, 20.0, 40.0), 0.0);
+    }
+
+    #[test]
+    fn test_scan_args_follow_video_stream() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(2));
+        let scanner = BlackFrameScanner::new(&ffmpeg, BlackFrameConfig::default());
+        let args = scanner.scan_args(Path::new("in.mkv"), 30.0, 10.0).join(" ");
+        assert!(args.contains("-ss 30 -i in.mkv -t 10 -map 0:v:2 -an"));
+    }
+}
@@ -0,0 +1,335 @@
+use crate::analysis::{
+    ContentClassification, CropAnalysisResult, DolbyVisionInfo, SceneCuts, SourceBitrate,
+    VideoFingerprint,
+};
+use crate::config::types::AnalysisCacheConfig;
+use crate::hdr::types::HdrAnalysisResult;
+use crate::utils::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, warn};
+
+/// Bumped whenever the cached structures change shape.
+const CACHE_VERSION: u32 = 2;
+
+/// Identity of a source file; any change to path, size or mtime invalidates the entry.
+/// The analyzed video stream and the input options it was read with are part of
+/// the key, so each selection gets its own entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceKey {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mtime_secs: u64,
+    pub mtime_nanos: u32,
+    /// Position of the analyzed video stream (`--video-stream`)
+    pub video_stream: usize,
+    /// Whether the source was read with `--error-resilience`
+    pub error_resilience: bool,
+}
+
+impl SourceKey {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        let path = path.canonicalize()?;
+        let metadata = std::fs::metadata(&path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        Ok(Self {
+            path,
+            size: metadata.len(),
+            mtime_secs: mtime.as_secs(),
+            mtime_nanos: mtime.subsec_nanos(),
+            video_stream: 0,
+            error_resilience: false,
+        })
+    }
+
+    fn file_name(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        self.video_stream.hash(&mut hasher);
+        self.error_resilience.hash(&mut hasher);
+        format!("{:016x}.json", hasher.finish())
+    }
+}
+
+/// Crop detection result together with the settings it was produced with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedCrop {
+    pub settings: String,
+    pub result: CropAnalysisResult,
+}
+
+/// Everything the analysis phase computes for one source.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CachedAnalysis {
+    pub hdr: Option<HdrAnalysisResult>,
+    pub dolby_vision: Option<DolbyVisionInfo>,
+    pub crop: Option<CachedCrop>,
+    pub classification: Option<ContentClassification>,
+    /// Frames of the primary video stream, counted packet by packet
+    pub frame_count: Option<u32>,
+    /// Mean banding score of the banding check's sample frames
+    pub banding_score: Option<f64>,
+    /// Perceptual fingerprint (`analysis.fingerprint`)
+    pub fingerprint: Option<VideoFingerprint>,
+    /// Scene cuts (`analysis.scene_detection`)
+    pub scene_cuts: Option<SceneCuts>,
+    /// Bits per pixel of the video stream (`analysis.starved_source`)
+    pub source_bitrate: Option<SourceBitrate>,
+}
+
+impl CachedAnalysis {
+    /// Cached crop result, if it was computed with the same settings.
+    pub fn crop_for(&self, settings: &str) -> Option<&CropAnalysisResult> {
+        self.crop
+            .as_ref()
+            .filter(|crop| crop.settings == settings)
+            .map(|crop| &crop.result)
+    }
+
+    /// Cached scene cuts, if they were detected at the same threshold.
+    pub fn scene_cuts_for(&self, threshold: f64) -> Option<&[f64]> {
+        self.scene_cuts
+            .as_ref()
+            .filter(|cuts| cuts.threshold == threshold)
+            .map(|cuts| cuts.times.as_slice())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    version: u32,
+    key: SourceKey,
+    analysis: CachedAnalysis,
+}
+
+/// On-disk cache of analysis results, one JSON file per source.
+#[derive(Debug, Clone)]
+pub struct AnalysisCache {
+    dir: Option<PathBuf>,
+    video_stream: usize,
+    error_resilience: bool,
+}
+
+impl AnalysisCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            video_stream: 0,
+            error_resilience: false,
+        }
+    }
+
+    /// A cache that never stores or returns anything.
+    pub fn disabled() -> Self {
+        Self {
+            dir: None,
+            video_stream: 0,
+            error_resilience: false,
+        }
+    }
+
+    /// Keys entries by the analyzed video stream (`--video-stream`) and
+    /// `--error-resilience` as well, since both change what is analyzed.
+    pub fn with_selection(mut self, video_stream: Option<usize>, error_resilience: bool) -> Self {
+        self.video_stream = video_stream.unwrap_or(0);
+        self.error_resilience = error_resilience;
+        self
+    }
+
+    pub fn from_config(config: &AnalysisCacheConfig, bypass: bool) -> Self {
+        if bypass || !config.enabled {
+            return Self::disabled();
+        }
+
+        let dir = config
+            .dir
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| dirs::cache_dir().map(|dir| dir.join("ven").join("analysis")));
+
+        match dir {
+            Some(dir) => Self::new(dir),
+            None => {
+                warn!("No cache directory available, analysis cache disabled");
+                Self::disabled()
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.dir.is_some()
+    }
+
+    fn key(&self, source: &Path) -> Result<SourceKey> {
+        Ok(SourceKey {
+            video_stream: self.video_stream,
+            error_resilience: self.error_resilience,
+            ..SourceKey::from_path(source)?
+        })
+    }
+
+    /// Returns the cached analysis for `source` if its size and mtime still match.
+    pub fn load(&self, source: &Path) -> Option<CachedAnalysis> {
+        let dir = self.dir.as_ref()?;
+        let key = self.key(source).ok()?;
+        let entry_path = dir.join(key.file_name());
+
+        let content = std::fs::read_to_string(&entry_path).ok()?;
+        let entry: CacheEntry = match serde_json::from_str(&content) {
+            Ok(entry) => entry,
+            Err(e) => {
+                debug!(
+                    "Ignoring unreadable cache entry {}: {}",
+                    entry_path.display(),
+                    e
+                );
+                return None;
+            }
+        };
+
+        if entry.version != CACHE_VERSION || entry.key != key {
+            debug!("Cache entry for {} is stale", source.display());
+            return None;
+        }
+
+        Some(entry.analysis)
+    }
+
+    /// Stores the analysis for `source`. Failures are logged, never fatal.
+    pub fn store(&self, source: &Path, analysis: &CachedAnalysis) {
+        let Some(dir) = self.dir.as_ref() else {
+            return;
+        };
+
+        let result = (|| -> Result<PathBuf> {
+            let key = self.key(source)?;
+            std::fs::create_dir_all(dir)?;
+            let entry_path = dir.join(key.file_name());
+            let entry = CacheEntry {
+                version: CACHE_VERSION,
+                key,
+                analysis: analysis.clone(),
+            };
+            std::fs::write(&entry_path, serde_json::to_string_pretty(&entry)?)?;
+            Ok(entry_path)
+        })();
+
+        match result {
+            Ok(path) => debug!("Stored analysis cache entry: {}", path.display()),
+            Err(e) => warn!(
+                "Failed to write analysis cache for {}: {}",
+                source.display(),
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::CropValues;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("ven_cache_test_{}_{}", name, uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn sample_crop() -> CachedCrop {
+        CachedCrop {
+            settings: "limits=24/64".to_string(),
+            result: CropAnalysisResult {
+                crop_values: Some(CropValues::new(1920, 800, 0, 140)),
+                detection_method: "sampling".to_string(),
+                confidence: 0.9,
+                pixel_change_percent: 25.9,
+                sample_results: Vec::new(),
+                aspect_variation: 0.0,
+            },
+        }
+    }
+
+    #[test]
+    fn test_store_and_load_roundtrip() {
+        let dir = temp_dir("roundtrip");
+        let source = dir.join("source.mkv");
+        std::fs::write(&source, b"video").unwrap();
+
+        let cache = AnalysisCache::new(dir.join("cache"));
+        assert!(cache.load(&source).is_none());
+
+        let analysis = CachedAnalysis {
+            crop: Some(sample_crop()),
+            ..CachedAnalysis::default()
+        };
+        cache.store(&source, &analysis);
+
+        let loaded = cache.load(&source).unwrap();
+        let crop = loaded.crop_for("limits=24/64").unwrap();
+        assert_eq!(crop.crop_values, Some(CropValues::new(1920, 800, 0, 140)));
+        assert!(loaded.crop_for("limits=16/64").is_none());
+
+        // Changing the file size invalidates the entry
+        std::fs::write(&source, b"re-muxed video").unwrap();
+        assert!(cache.load(&source).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_video_stream_selection_keys_entries() {
+        let dir = temp_dir("selection");
+        let source = dir.join("source.mkv");
+        std::fs::write(&source, b"video").unwrap();
+
+        let first = AnalysisCache::new(dir.join("cache"));
+        let second = AnalysisCache::new(dir.join("cache")).with_selection(Some(1), false);
+        first.store(
+            &source,
+            &CachedAnalysis {
+                frame_count: Some(1000),
+                ..CachedAnalysis::default()
+            },
+        );
+        assert!(second.load(&source).is_none());
+
+        second.store(
+            &source,
+            &CachedAnalysis {
+                frame_count: Some(2000),
+                ..CachedAnalysis::default()
+            },
+        );
+        assert_eq!(first.load(&source).unwrap().frame_count, Some(1000));
+        assert_eq!(second.load(&source).unwrap().frame_count, Some(2000));
+
+        // Reading the source with --error-resilience is another entry again
+        let resilient = AnalysisCache::new(dir.join("cache")).with_selection(Some(1), true);
+        assert!(resilient.load(&source).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_disabled_cache() {
+        let dir = temp_dir("disabled");
+        let source = dir.join("source.mkv");
+        std::fs::write(&source, b"video").unwrap();
+
+        let cache = AnalysisCache::from_config(&AnalysisCacheConfig::default(), true);
+        assert!(!cache.is_enabled());
+        cache.store(&source, &CachedAnalysis::default());
+        assert!(cache.load(&source).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
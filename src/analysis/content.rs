@@ -1,7 +1,13 @@
 use crate::config::ContentType;
+use crate::utils::ffmpeg::VideoMetadata;
 use crate::utils::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, warn};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContentClassification {
     pub content_type: ContentType,
     pub confidence: f32,
@@ -49,3 +55,179 @@ impl Default for ContentAnalyzer {
         Self::new()
     }
 }
+
+/// Classification logic for profile auto-selection, e.g. a local ML model.
+/// Register implementations with
+/// [`VenEngine::with_classifier`](crate::VenEngine::with_classifier).
+pub trait ContentClassifier: Send + Sync {
+    /// Name in logs
+    fn name(&self) -> &str;
+
+    /// Classifies the source, or returns `None` to leave it to the next
+    /// classifier.
+    fn classify<'a>(
+        &'a self,
+        input_path: &'a Path,
+        metadata: &'a VideoMetadata,
+    ) -> BoxFuture<'a, Result<Option<ContentClassification>>>;
+}
+
+impl ContentClassifier for ContentAnalyzer {
+    fn name(&self) -> &str {
+        "technical_analysis"
+    }
+
+    fn classify<'a>(
+        &'a self,
+        _input_path: &'a Path,
+        metadata: &'a VideoMetadata,
+    ) -> BoxFuture<'a, Result<Option<ContentClassification>>> {
+        Box::pin(async move { self.classify_content(metadata).await.map(Some) })
+    }
+}
+
+/// Classifiers asked in registration order; the first answer wins. The
+/// built-in [`ContentAnalyzer`] answers last unless it was left out.
+#[derive(Clone)]
+pub struct ClassifierChain {
+    classifiers: Vec<Arc<dyn ContentClassifier>>,
+    builtin: bool,
+}
+
+impl ClassifierChain {
+    pub fn new() -> Self {
+        Self {
+            classifiers: Vec::new(),
+            builtin: true,
+        }
+    }
+
+    pub fn register(&mut self, classifier: Arc<dyn ContentClassifier>) {
+        self.classifiers.push(classifier);
+    }
+
+    /// Uses only the registered classifiers, not the bitrate heuristic.
+    pub fn without_builtin(&mut self) {
+        self.builtin = false;
+    }
+
+    /// The first classification a classifier gives. A classifier that fails
+    /// is logged and skipped.
+    pub async fn classify(
+        &self,
+        input_path: &Path,
+        metadata: &VideoMetadata,
+    ) -> Option<ContentClassification> {
+        let builtin = ContentAnalyzer::new();
+        let builtin: Option<&dyn ContentClassifier> = self.builtin.then_some(&builtin);
+        let classifiers = self
+            .classifiers
+            .iter()
+            .map(|classifier| classifier.as_ref())
+            .chain(builtin);
+        for classifier in classifiers {
+            match classifier.classify(input_path, metadata).await {
+                Ok(Some(classification)) => return Some(classification),
+                Ok(None) => debug!("Classifier '{}' left the source open", classifier.name()),
+                Err(e) => warn!("Classifier '{}' failed: {}", classifier.name(), e),
+            }
+        }
+        None
+    }
+}
+
+impl Default for ClassifierChain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Error;
+
+    struct Fixed(Option<ContentType>);
+
+    impl ContentClassifier for Fixed {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn classify<'a>(
+            &'a self,
+            _input_path: &'a Path,
+            _metadata: &'a VideoMetadata,
+        ) -> BoxFuture<'a, Result<Option<ContentClassification>>> {
+            Box::pin(async move {
+                Ok(self.0.map(|content_type| ContentClassification {
+                    content_type,
+                    confidence: 0.95,
+                    method: "model".to_string(),
+                }))
+            })
+        }
+    }
+
+    struct Failing;
+
+    impl ContentClassifier for Failing {
+        fn name(&self) -> &str {
+            "failing"
+        }
+
+        fn classify<'a>(
+            &'a self,
+            _input_path: &'a Path,
+            _metadata: &'a VideoMetadata,
+        ) -> BoxFuture<'a, Result<Option<ContentClassification>>> {
+            Box::pin(async { Err(Error::tool("model not loaded")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_classifier_chain() {
+        let metadata = VideoMetadata {
+            width: 1920,
+            height: 1080,
+            duration: 1440.0,
+            fps: 23.976,
+            bitrate: Some(10_000_000),
+            codec: Some("h264".to_string()),
+            pix_fmt: Some("yuv420p".to_string()),
+            field_order: None,
+            is_hdr: false,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+        let input = Path::new("/media/show.mkv");
+
+        let mut chain = ClassifierChain::new();
+        assert_eq!(
+            chain.classify(input, &metadata).await.unwrap().method,
+            "technical_analysis"
+        );
+
+        // Registered classifiers come first; undecided or failing ones fall through
+        chain.register(Arc::new(Failing));
+        chain.register(Arc::new(Fixed(None)));
+        assert_eq!(
+            chain.classify(input, &metadata).await.unwrap().method,
+            "technical_analysis"
+        );
+        chain.register(Arc::new(Fixed(Some(ContentType::Anime))));
+        let classification = chain.classify(input, &metadata).await.unwrap();
+        assert_eq!(classification.content_type, ContentType::Anime);
+
+        let mut only_custom = ClassifierChain::new();
+        only_custom.register(Arc::new(Fixed(None)));
+        only_custom.without_builtin();
+        assert!(only_custom.classify(input, &metadata).await.is_none());
+    }
+}
@@ -1,6 +1,9 @@
-use crate::config::CropDetectionConfig;
-use crate::utils::Result;
+use crate::analysis::black::BlackFrameScanner;
+use crate::config::{BlackFrameConfig, CropDetectionConfig};
+use crate::log_stage;
+use crate::utils::{FfmpegWrapper, Result};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::LazyLock;
@@ -10,7 +13,7 @@ use tracing::{debug, info};
 static CROP_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"crop=(\d+):(\d+):(\d+):(\d+)").unwrap());
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CropValues {
     pub width: u32,
     pub height: u32,
@@ -53,6 +56,30 @@ impl CropValues {
     ) -> bool {
         self.calculate_pixel_change(original_width, original_height) >= min_change_percent
     }
+
+    /// Width over height of the cropped picture
+    #[must_use]
+    pub fn aspect_ratio(&self) -> f64 {
+        f64::from(self.width) / f64::from(self.height.max(1))
+    }
+}
+
+/// How far apart the aspect ratios of the samples' crops are, in percent of
+/// the narrowest: 0 for a constant aspect ratio, about 34 for a film
+/// switching between 2.39:1 and 1.78:1.
+fn aspect_variation(sample_results: &[CropSampleResult]) -> f32 {
+    let ratios = sample_results
+        .iter()
+        .filter_map(|sample| sample.crop_values.as_ref())
+        .map(CropValues::aspect_ratio);
+    let (min, max) = ratios.fold((f64::INFINITY, 0.0_f64), |(min, max), ratio| {
+        (min.min(ratio), max.max(ratio))
+    });
+    if max > 0.0 {
+        ((max - min) / min * 100.0) as f32
+    } else {
+        0.0
+    }
 }
 
 impl CropDetectionConfig {
@@ -86,30 +113,47 @@ impl CropDetectionConfig {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CropAnalysisResult {
     pub crop_values: Option<CropValues>,
     pub detection_method: String,
     pub confidence: f32,
     pub pixel_change_percent: f32,
     pub sample_results: Vec<CropSampleResult>,
+    /// Spread of the samples' aspect ratios in percent, see `max_aspect_variation`
+    #[serde(default)]
+    pub aspect_variation: f32,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CropSampleResult {
     pub sample_point: String,
     pub timestamp: f64,
     pub crop_values: Option<CropValues>,
+    #[serde(skip)]
     pub raw_output: String,
 }
 
 pub struct CropDetector {
+    ffmpeg: FfmpegWrapper,
     config: CropDetectionConfig,
+    black_frames: Option<BlackFrameConfig>,
 }
 
 impl CropDetector {
-    pub fn new(config: CropDetectionConfig) -> Self {
-        Self { config }
+    /// Samples the video stream `ffmpeg` selects (`--video-stream`).
+    pub fn new(ffmpeg: &FfmpegWrapper, config: CropDetectionConfig) -> Self {
+        Self {
+            ffmpeg: ffmpeg.clone(),
+            config,
+            black_frames: None,
+        }
+    }
+
+    /// Moves sample points off black frames, scanning around each of them.
+    pub fn with_black_frames(mut self, black_frames: BlackFrameConfig) -> Self {
+        self.black_frames = Some(black_frames);
+        self
     }
 
     pub async fn detect_crop_values<P: AsRef<Path>>(
@@ -127,11 +171,17 @@ impl CropDetector {
                 confidence: 0.0,
                 pixel_change_percent: 0.0,
                 sample_results: vec![],
+                aspect_variation: 0.0,
             });
         }
 
-        let sample_timestamps = self.config.get_sample_timestamps(duration);
-        info!(
+        let mut sample_timestamps = self.config.get_sample_timestamps(duration);
+        if let Some(black_frames) = &self.black_frames {
+            sample_timestamps = BlackFrameScanner::new(&self.ffmpeg, black_frames.clone())
+                .avoid_black(input_path.as_ref(), &sample_timestamps, duration)
+                .await;
+        }
+        log_stage!(
             "Starting crop detection analysis with {} sample points",
             sample_timestamps.len()
         );
@@ -156,9 +206,13 @@ impl CropDetector {
                 let size_reduction_percent =
                     ((original_pixels - new_pixels) as f32 / original_pixels as f32) * 100.0;
 
-                info!(
+                log_stage!(
                     "Crop detection completed: {}x{} -> {}x{} (reduced by {:.1}%)",
-                    width, height, crop_values.width, crop_values.height, size_reduction_percent
+                    width,
+                    height,
+                    crop_values.width,
+                    crop_values.height,
+                    size_reduction_percent
                 );
             }
             None => {
@@ -191,23 +245,8 @@ impl CropDetector {
             timestamp, crop_limit, is_hdr
         );
 
-        let output = Command::new("ffmpeg")
-            .args([
-                "-loglevel",
-                "info",         // Need info level for cropdetect filter output
-                "-hide_banner", // Hide FFmpeg banner
-                "-ss",
-                &timestamp.to_string(),
-                "-i",
-                &input_path_str,
-                "-t",
-                "2", // Analyze 2 seconds for better accuracy
-                "-vf",
-                &format!("cropdetect=limit={}:round=2", crop_limit),
-                "-f",
-                "null",
-                "-",
-            ])
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(self.sample_args(&input_path_str, timestamp, crop_limit))
             .output()
             .await?;
 
@@ -222,6 +261,32 @@ impl CropDetector {
         })
     }
 
+    /// FFmpeg arguments running cropdetect over 2 seconds from `timestamp`.
+    fn sample_args(&self, input_path: &str, timestamp: f64, crop_limit: u32) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "-loglevel".to_string(),
+            "info".to_string(), // Need info level for cropdetect filter output
+            "-hide_banner".to_string(),
+        ];
+        args.extend(self.ffmpeg.input_options().iter().cloned());
+        args.extend([
+            "-ss".to_string(),
+            timestamp.to_string(),
+            "-i".to_string(),
+            input_path.to_string(),
+            "-map".to_string(),
+            self.ffmpeg.video_map_specifier(),
+            "-t".to_string(),
+            "2".to_string(), // Analyze 2 seconds for better accuracy
+            "-vf".to_string(),
+            format!("cropdetect=limit={}:round=2", crop_limit),
+            "-f".to_string(),
+            "null".to_string(),
+            "-".to_string(),
+        ]);
+        args
+    }
+
     fn extract_crop_from_output(&self, output: &str) -> Option<CropValues> {
         // Find the last (most recent) crop detection line
         let mut best_crop: Option<CropValues> = None;
@@ -264,6 +329,7 @@ impl CropDetector {
                 confidence: 0.0,
                 pixel_change_percent: 0.0,
                 sample_results: sample_results.to_vec(),
+                aspect_variation: 0.0,
             };
         }
 
@@ -285,7 +351,24 @@ impl CropDetector {
             self.config.min_pixel_change_percent,
         );
 
-        let detection_method = if should_apply_crop {
+        let confident = confidence >= self.config.min_confidence;
+        // Samples far apart in aspect ratio mean the framing changes (IMAX
+        // sequences in a scope film); any single crop would cut into some of it
+        let aspect_variation = aspect_variation(sample_results);
+        let varying = self
+            .config
+            .max_aspect_variation
+            .is_some_and(|limit| aspect_variation > limit);
+        let should_apply_crop = should_apply_crop && confident && !varying;
+
+        let detection_method = if varying {
+            format!(
+                "varying_aspect_ratio_{}%_variation",
+                (aspect_variation as u32)
+            )
+        } else if !confident {
+            format!("low_confidence_{}%_agreement", (confidence as u32))
+        } else if should_apply_crop {
             format!("frequency_analysis_{}%_agreement", (confidence as u32))
         } else {
             "insufficient_change".to_string()
@@ -306,6 +389,7 @@ impl CropDetector {
             confidence,
             pixel_change_percent: pixel_change,
             sample_results: sample_results.to_vec(),
+            aspect_variation,
         }
     }
 
@@ -403,16 +487,15 @@ impl CropDetector {
     }
 }
 
-impl Default for CropDetector {
-    fn default() -> Self {
-        Self::new(CropDetectionConfig::default())
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn detector(config: CropDetectionConfig) -> CropDetector {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        CropDetector::new(&ffmpeg, config)
+    }
+
     #[test]
     fn test_crop_values_creation() {
         let crop = CropValues::new(1920, 800, 0, 140);
@@ -432,7 +515,7 @@ mod tests {
 
     #[test]
     fn test_sample_point_parsing() {
-        let detector = CropDetector::default();
+        let detector = detector(CropDetectionConfig::default());
         let duration = 3600.0; // 1 hour
 
         assert_eq!(
@@ -447,6 +530,16 @@ mod tests {
         assert_eq!(detector.parse_sample_point("300", duration).unwrap(), 300.0);
     }
 
+    #[test]
+    fn test_sample_args_follow_video_stream() {
+        let ffmpeg = FfmpegWrapper::new("/opt/ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(1));
+        let detector = CropDetector::new(&ffmpeg, CropDetectionConfig::default());
+        let args = detector.sample_args("in.mkv", 60.0, 24).join(" ");
+        assert!(args.contains("-ss 60 -i in.mkv -map 0:v:1 -t 2 -vf cropdetect=limit=24:round=2"));
+        assert_eq!(detector.ffmpeg.get_ffmpeg_path(), "/opt/ffmpeg");
+    }
+
     #[test]
     fn test_crop_regex() {
         let output = "[Parsed_cropdetect_0 @ 0x7f8b8c000940] crop=1920:800:0:140";
@@ -460,7 +553,7 @@ mod tests {
 
     #[test]
     fn test_crops_match_tolerance() {
-        let detector = CropDetector::default();
+        let detector = detector(CropDetectionConfig::default());
         let crop1 = CropValues::new(1920, 800, 0, 140);
         let crop2 = CropValues::new(1918, 802, 2, 138); // Within 4 pixel tolerance
         let crop3 = CropValues::new(1900, 780, 10, 150); // Outside tolerance
@@ -469,6 +562,67 @@ mod tests {
         assert!(!detector.crops_match_with_tolerance(&crop1, &crop3, 4));
     }
 
+    #[test]
+    fn test_crop_min_confidence() {
+        let sample = |crop: CropValues| CropSampleResult {
+            sample_point: String::new(),
+            timestamp: 0.0,
+            crop_values: Some(crop),
+            raw_output: String::new(),
+        };
+        let samples = vec![
+            sample(CropValues::new(1920, 800, 0, 140)),
+            sample(CropValues::new(1920, 800, 0, 140)),
+            sample(CropValues::new(1920, 1040, 0, 20)),
+        ];
+
+        let result =
+            detector(CropDetectionConfig::default()).analyze_crop_frequency(&samples, 1920, 1080);
+        assert_eq!(result.crop_values, Some(CropValues::new(1920, 800, 0, 140)));
+
+        let strict = detector(CropDetectionConfig {
+            min_confidence: 80.0,
+            ..Default::default()
+        });
+        let result = strict.analyze_crop_frequency(&samples, 1920, 1080);
+        assert_eq!(result.crop_values, None);
+        assert_eq!(result.detection_method, "low_confidence_66%_agreement");
+    }
+
+    #[test]
+    fn test_varying_aspect_ratio() {
+        let sample = |crop: CropValues| CropSampleResult {
+            sample_point: String::new(),
+            timestamp: 0.0,
+            crop_values: Some(crop),
+            raw_output: String::new(),
+        };
+        // 2.40:1 scope with 1.78:1 IMAX sequences
+        let samples = vec![
+            sample(CropValues::new(1920, 800, 0, 140)),
+            sample(CropValues::new(1920, 800, 0, 140)),
+            sample(CropValues::new(1920, 1080, 0, 0)),
+        ];
+
+        let result =
+            detector(CropDetectionConfig::default()).analyze_crop_frequency(&samples, 1920, 1080);
+        assert_eq!(result.crop_values, Some(CropValues::new(1920, 800, 0, 140)));
+        assert!((result.aspect_variation - 35.0).abs() < 0.1);
+
+        let zoned = detector(CropDetectionConfig {
+            max_aspect_variation: Some(10.0),
+            ..Default::default()
+        });
+        let result = zoned.analyze_crop_frequency(&samples, 1920, 1080);
+        assert_eq!(result.crop_values, None);
+        assert_eq!(
+            result.detection_method,
+            "varying_aspect_ratio_35%_variation"
+        );
+        let result = zoned.analyze_crop_frequency(&samples[..2], 1920, 1080);
+        assert!(result.crop_values.is_some());
+    }
+
     #[test]
     fn test_dynamic_sample_timestamps() {
         let config = CropDetectionConfig {
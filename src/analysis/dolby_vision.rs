@@ -114,7 +114,7 @@ impl DolbyVisionDetector {
         // Use ffprobe to extract detailed video stream information
         let output = ffmpeg.run_ffprobe(&[
             "-v", "quiet",
-            "-select_streams", "v:0",
+            "-select_streams", &ffmpeg.video_stream_specifier(),
             "-show_entries", 
             "stream=codec_name,profile,codec_tag_string,color_space,color_transfer,color_primaries:stream_side_data",
             "-print_format", "json",
@@ -0,0 +1,180 @@
+//! Perceptual fingerprint of a source (`analysis.fingerprint`): a 64-bit
+//! difference hash (dHash) of each of a few grey frames sampled over the
+//! runtime. Scaling, re-encoding and renaming leave the hashes close; other
+//! content does not, which lets an `encode` run notice a source that was
+//! encoded before under another name or at another resolution.
+
+use crate::config::FingerprintConfig;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Sample frames are scaled to 9x8, giving 8 comparisons per row.
+const HASH_WIDTH: usize = 9;
+const HASH_HEIGHT: usize = 8;
+
+/// Runtimes further apart than this many seconds (or 0.5% of the longer one)
+/// are different cuts, or different content.
+const DURATION_TOLERANCE: f64 = 2.0;
+
+/// Bit per neighbouring pixel pair of a 9x8 grey frame, set where the left
+/// pixel is darker than the right one.
+pub fn difference_hash(frame: &[u8]) -> u64 {
+    let mut hash = 0u64;
+    for row in frame.chunks_exact(HASH_WIDTH).take(HASH_HEIGHT) {
+        for pair in row.windows(2) {
+            hash = (hash << 1) | u64::from(pair[0] < pair[1]);
+        }
+    }
+    hash
+}
+
+/// Fingerprint of one source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoFingerprint {
+    /// Runtime in seconds
+    pub duration: f64,
+    /// [`difference_hash`] of every sample frame, in order
+    pub hashes: Vec<u64>,
+}
+
+impl VideoFingerprint {
+    /// Mean share of differing bits between the hashes of both fingerprints:
+    /// close to 0 for the same content, around 0.5 for unrelated frames.
+    /// `None` when the runtimes or the number of samples differ.
+    pub fn distance(&self, other: &Self) -> Option<f64> {
+        let tolerance = DURATION_TOLERANCE.max(self.duration.max(other.duration) * 0.005);
+        if self.hashes.is_empty()
+            || self.hashes.len() != other.hashes.len()
+            || (self.duration - other.duration).abs() > tolerance
+        {
+            return None;
+        }
+        let differing: u32 = self
+            .hashes
+            .iter()
+            .zip(&other.hashes)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum();
+        Some(differing as f64 / (self.hashes.len() * 64) as f64)
+    }
+}
+
+/// Computes [`VideoFingerprint`]s with FFmpeg.
+pub struct Fingerprinter {
+    ffmpeg: FfmpegWrapper,
+    samples: usize,
+}
+
+impl Fingerprinter {
+    /// Samples the video stream `ffmpeg` selects (`--video-stream`).
+    pub fn new(ffmpeg: &FfmpegWrapper, config: &FingerprintConfig) -> Self {
+        Self {
+            ffmpeg: ffmpeg.clone(),
+            samples: config.samples,
+        }
+    }
+
+    /// Hashes frames taken between 10% and 90% of `duration`, clear of
+    /// openings and credits, which differ between releases most.
+    pub async fn compute(&self, input_path: &Path, duration: f64) -> Result<VideoFingerprint> {
+        let mut hashes = Vec::with_capacity(self.samples);
+        for i in 0..self.samples {
+            let timestamp = duration * (0.1 + 0.8 * (i as f64 + 0.5) / self.samples as f64);
+            let hash = difference_hash(&self.grab_frame(input_path, timestamp).await?);
+            debug!("Fingerprint at {:.1}s: {:016x}", timestamp, hash);
+            hashes.push(hash);
+        }
+        Ok(VideoFingerprint { duration, hashes })
+    }
+
+    /// FFmpeg arguments writing the frame at `timestamp` as 9x8 grey pixels.
+    fn frame_args(&self, input_path: &Path, timestamp: f64) -> Vec<String> {
+        let mut args: Vec<String> = ["-hide_banner", "-nostats", "-loglevel", "error"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(self.ffmpeg.input_options().iter().cloned());
+        args.extend([
+            "-ss".to_string(),
+            format!("{:.3}", timestamp),
+            "-i".to_string(),
+            input_path.to_string_lossy().to_string(),
+            "-map".to_string(),
+            self.ffmpeg.video_map_specifier(),
+            "-frames:v".to_string(),
+            "1".to_string(),
+            "-vf".to_string(),
+            format!(
+                "scale={}:{}:flags=area,format=gray",
+                HASH_WIDTH, HASH_HEIGHT
+            ),
+            "-f".to_string(),
+            "rawvideo".to_string(),
+            "-".to_string(),
+        ]);
+        args
+    }
+
+    async fn grab_frame(&self, input_path: &Path, timestamp: f64) -> Result<Vec<u8>> {
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(self.frame_args(input_path, timestamp))
+            .output()
+            .await?;
+        if !output.status.success() || output.stdout.len() < HASH_WIDTH * HASH_HEIGHT {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "Grabbing a frame for the fingerprint at {:.1}s failed: {}",
+                timestamp,
+                stderr.lines().last().unwrap_or("no frame decoded")
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_distance() {
+        // Brightening left to right sets every bit, the mirror image none
+        let ramp: Vec<u8> = (0..HASH_WIDTH * HASH_HEIGHT)
+            .map(|i| (i % HASH_WIDTH * 20) as u8)
+            .collect();
+        assert_eq!(difference_hash(&ramp), u64::MAX);
+        let mirrored: Vec<u8> = ramp.iter().map(|pixel| 255 - pixel).collect();
+        assert_eq!(difference_hash(&mirrored), 0);
+
+        let source = VideoFingerprint {
+            duration: 5400.0,
+            hashes: vec![u64::MAX, 0x0f0f_0f0f_0f0f_0f0f],
+        };
+        // Another release: a few bits off and a second shorter
+        let release = VideoFingerprint {
+            duration: 5399.0,
+            hashes: vec![u64::MAX >> 4, 0x0f0f_0f0f_0f0f_0f0f],
+        };
+        assert_eq!(source.distance(&release), Some(4.0 / 128.0));
+        assert_eq!(source.distance(&source), Some(0.0));
+
+        // A different cut is not compared at all
+        let extended = VideoFingerprint {
+            duration: 5700.0,
+            ..source.clone()
+        };
+        assert_eq!(source.distance(&extended), None);
+
+        // Frames come from the stream --video-stream picked
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(1));
+        let fingerprinter = Fingerprinter::new(&ffmpeg, &FingerprintConfig::default());
+        let args = fingerprinter
+            .frame_args(Path::new("in.mkv"), 60.0)
+            .join(" ");
+        assert!(args.contains("-ss 60.000 -i in.mkv -map 0:v:1 -frames:v 1"));
+    }
+}
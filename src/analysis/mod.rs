@@ -1,10 +1,24 @@
+pub mod banding;
+pub mod bitrate;
+pub mod black;
+pub mod cache;
 pub mod content;
 pub mod crop;
 pub mod dolby_vision;
+pub mod fingerprint;
+pub mod policy;
+pub mod scenes;
 pub mod video;
 
 pub use crate::config::CropDetectionConfig;
-pub use content::{ContentAnalyzer, ContentClassification};
+pub use banding::BandingDetector;
+pub use bitrate::SourceBitrate;
+pub use black::{BlackFrameScanner, BlackRange};
+pub use cache::{AnalysisCache, CachedAnalysis};
+pub use content::{ClassifierChain, ContentAnalyzer, ContentClassification, ContentClassifier};
 pub use crop::{CropAnalysisResult, CropDetector, CropValues};
 pub use dolby_vision::{DolbyVisionDetector, DolbyVisionInfo, DolbyVisionProfile};
+pub use fingerprint::{Fingerprinter, VideoFingerprint};
+pub use policy::policy_violations;
+pub use scenes::{SceneCuts, SceneDetector};
 pub use video::VideoAnalysis;
@@ -0,0 +1,138 @@
+//! The `check:` policy behind `analyze --check`, which turns an analysis into
+//! a pass/fail gate for media pipelines.
+
+use crate::analysis::{DolbyVisionInfo, DolbyVisionProfile};
+use crate::config::CheckPolicy;
+use crate::stream::preservation::{language_matches, StreamInfo};
+use crate::utils::ffmpeg::VideoMetadata;
+
+/// Every rule of `policy` the source breaks, one line each; empty when it passes.
+pub fn policy_violations(
+    policy: &CheckPolicy,
+    metadata: &VideoMetadata,
+    dolby_vision: &DolbyVisionInfo,
+    audio_streams: &[StreamInfo],
+) -> Vec<String> {
+    let mut violations = Vec::new();
+
+    if !policy.video_codecs.is_empty() {
+        let codec = metadata.codec.as_deref().unwrap_or("unknown");
+        if !policy
+            .video_codecs
+            .iter()
+            .any(|allowed| allowed.eq_ignore_ascii_case(codec))
+        {
+            violations.push(format!(
+                "video is {}, not {}",
+                codec,
+                policy.video_codecs.join(" or ")
+            ));
+        }
+    }
+
+    if let Some(max_bitrate) = policy.max_bitrate {
+        match metadata.bitrate.map(|bps| bps / 1000) {
+            Some(kbps) if kbps <= max_bitrate => {}
+            Some(kbps) => {
+                violations.push(format!("bitrate {}kbps exceeds {}kbps", kbps, max_bitrate))
+            }
+            None => violations.push("bitrate unknown".to_string()),
+        }
+    }
+
+    for language in &policy.audio_languages {
+        if !audio_streams
+            .iter()
+            .any(|stream| language_matches(stream.language.as_deref(), language))
+        {
+            violations.push(format!("no {} audio stream", language));
+        }
+    }
+
+    if policy.reject_interlaced && metadata.is_interlaced() {
+        violations.push(format!(
+            "video is interlaced (field order {})",
+            metadata.field_order.as_deref().unwrap_or("unknown")
+        ));
+    }
+
+    if policy.require_hdr_fallback && dolby_vision.profile == DolbyVisionProfile::Profile5 {
+        violations.push(
+            "Dolby Vision profile 5 has no HDR10 or SDR base layer to fall back to".to_string(),
+        );
+    }
+
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    #[test]
+    fn test_policy_violations() {
+        let metadata = VideoMetadata {
+            width: 1920,
+            height: 1080,
+            duration: 60.0,
+            fps: 25.0,
+            bitrate: Some(8_000_000),
+            codec: Some("hevc".to_string()),
+            pix_fmt: Some("yuv420p10le".to_string()),
+            field_order: Some("progressive".to_string()),
+            is_hdr: false,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+        let audio = vec![StreamInfo {
+            index: 1,
+            codec_type: "audio".to_string(),
+            codec_name: "eac3".to_string(),
+            channels: Some(6),
+            language: Some("eng".to_string()),
+            title: None,
+            disposition: StreamDisposition::default(),
+            closed_captions: false,
+        }];
+        let policy = CheckPolicy {
+            video_codecs: vec!["hevc".to_string()],
+            max_bitrate: Some(10000),
+            audio_languages: vec!["en".to_string()],
+            reject_interlaced: true,
+            require_hdr_fallback: true,
+        };
+        let dolby_vision = DolbyVisionInfo::none();
+        assert!(policy_violations(&policy, &metadata, &dolby_vision, &audio).is_empty());
+        assert!(
+            policy_violations(&CheckPolicy::default(), &metadata, &dolby_vision, &[]).is_empty()
+        );
+
+        let broadcast = VideoMetadata {
+            codec: Some("mpeg2video".to_string()),
+            bitrate: Some(15_000_000),
+            field_order: Some("tt".to_string()),
+            ..metadata
+        };
+        let profile5 = DolbyVisionInfo {
+            profile: DolbyVisionProfile::Profile5,
+            ..DolbyVisionInfo::none()
+        };
+        assert_eq!(
+            policy_violations(&policy, &broadcast, &profile5, &[]),
+            vec![
+                "video is mpeg2video, not hevc",
+                "bitrate 15000kbps exceeds 10000kbps",
+                "no en audio stream",
+                "video is interlaced (field order tt)",
+                "Dolby Vision profile 5 has no HDR10 or SDR base layer to fall back to",
+            ]
+        );
+    }
+}
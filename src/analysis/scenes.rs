@@ -0,0 +1,170 @@
+//! Scene cut detection (`analysis.scene_detection`): FFmpeg's scene change
+//! score over the whole source, at low resolution. The encoders force a
+//! keyframe on every cut (`-force_key_frames`), so GOPs start where the
+//! picture changes anyway: seeking lands on scene starts and no I-frame is
+//! spent a few frames before a cut.
+
+use crate::config::SceneDetectionConfig;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::LazyLock;
+use tokio::process::Command;
+use tracing::info;
+
+static SHOWINFO_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"Parsed_showinfo.*\bpts_time:\s*(-?[\d.]+)").unwrap());
+
+/// Scene cuts of a source, with the threshold they were detected at.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneCuts {
+    pub threshold: f64,
+    /// Seconds from the start of the file, in order
+    pub times: Vec<f64>,
+}
+
+/// Times of the frames `showinfo` printed after the scene `select` filter.
+pub fn parse_scene_cuts(stderr: &str) -> Vec<f64> {
+    SHOWINFO_REGEX
+        .captures_iter(stderr)
+        .filter_map(|caps| caps[1].parse().ok())
+        .filter(|&time: &f64| time > 0.0)
+        .collect()
+}
+
+/// The cuts that get a keyframe: at least `min_interval` seconds after the
+/// start and the previous one kept, so flashes and rapid cutting do not
+/// fill the stream with I-frames.
+pub fn keyframe_times(cuts: &[f64], min_interval: f64) -> Vec<f64> {
+    let mut times: Vec<f64> = Vec::new();
+    for &cut in cuts {
+        if cut - times.last().copied().unwrap_or(0.0) >= min_interval {
+            times.push(cut);
+        }
+    }
+    times
+}
+
+/// Arguments forcing an IDR frame at each of `times` in the encoded video
+/// stream; nothing without times.
+pub fn force_key_frames_args(times: &[f64]) -> Vec<String> {
+    if times.is_empty() {
+        return Vec::new();
+    }
+    let times: Vec<String> = times.iter().map(|time| format!("{:.3}", time)).collect();
+    vec![
+        "-force_key_frames:v:0".to_string(),
+        times.join(","),
+        "-forced-idr:v:0".to_string(),
+        "1".to_string(),
+    ]
+}
+
+/// Runs FFmpeg's scene change detection over the source.
+pub struct SceneDetector {
+    ffmpeg: FfmpegWrapper,
+    threshold: f64,
+}
+
+impl SceneDetector {
+    /// Detects cuts in the video stream `ffmpeg` selects (`--video-stream`).
+    pub fn new(ffmpeg: &FfmpegWrapper, config: &SceneDetectionConfig) -> Self {
+        Self {
+            ffmpeg: ffmpeg.clone(),
+            threshold: config.threshold,
+        }
+    }
+
+    fn detect_args(&self, input_path: &Path) -> Vec<String> {
+        let filter = format!(
+            "scale=320:-2,select='gt(scene,{})',showinfo",
+            self.threshold
+        );
+        let mut args: Vec<String> = ["-hide_banner", "-nostats", "-loglevel", "info"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(self.ffmpeg.input_options().iter().cloned());
+        args.extend([
+            "-i".to_string(),
+            input_path.to_string_lossy().to_string(),
+            "-map".to_string(),
+            self.ffmpeg.video_map_specifier(),
+        ]);
+        args.extend(
+            ["-an", "-sn", "-dn", "-vf", &filter, "-f", "null", "-"]
+                .iter()
+                .map(|arg| arg.to_string()),
+        );
+        args
+    }
+
+    /// Decodes every frame of the selected video stream, scaled down, and
+    /// returns the frames whose scene score reaches the threshold.
+    pub async fn detect(&self, input_path: &Path) -> Result<SceneCuts> {
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(self.detect_args(input_path))
+            .output()
+            .await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "Scene detection failed: {}",
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+
+        let times = parse_scene_cuts(&stderr);
+        info!(
+            "Scene detection: {} cuts (threshold {})",
+            times.len(),
+            self.threshold
+        );
+        Ok(SceneCuts {
+            threshold: self.threshold,
+            times,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scene_keyframes() {
+        let stderr = "\
+[Parsed_showinfo_2 @ 0x5581] config in time_base: 1/1000, frame_rate: 24000/1001
+[Parsed_showinfo_2 @ 0x5581] n:   0 pts:   4046 pts_time:4.046   duration: 42 pos: 1 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x5581] n:   1 pts:   4421 pts_time:4.421   duration: 42 pos: 2 fmt:yuv420p
+[Parsed_showinfo_2 @ 0x5581] n:   2 pts:  61228 pts_time:61.228  duration: 42 pos: 3 fmt:yuv420p
+[out#0/null @ 0x5590] video:0KiB audio:0KiB";
+        let cuts = parse_scene_cuts(stderr);
+        assert_eq!(cuts, vec![4.046, 4.421, 61.228]);
+
+        // The flash 0.4s after the first cut is left to x265
+        let times = keyframe_times(&cuts, 1.0);
+        assert_eq!(times, vec![4.046, 61.228]);
+        assert_eq!(keyframe_times(&[0.5, 3.0], 1.0), vec![3.0]);
+
+        assert_eq!(
+            force_key_frames_args(&times),
+            [
+                "-force_key_frames:v:0",
+                "4.046,61.228",
+                "-forced-idr:v:0",
+                "1"
+            ]
+        );
+        assert!(force_key_frames_args(&[]).is_empty());
+
+        // Detection reads the stream --video-stream picked
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(1))
+            .with_input_options(["-err_detect", "ignore_err"]);
+        let detector = SceneDetector::new(&ffmpeg, &SceneDetectionConfig::default());
+        let args = detector.detect_args(Path::new("in.mkv")).join(" ");
+        assert!(args.contains("-err_detect ignore_err -i in.mkv -map 0:v:1 -an"));
+    }
+}
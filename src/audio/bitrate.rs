@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Channel count assumed when ffprobe does not report one.
+const DEFAULT_CHANNELS: u32 = 2;
+
+/// Maps an FFmpeg encoder name onto the codec key used by the ladder
+/// (`libopus` -> `opus`, `libfdk_aac` -> `aac`).
+pub(crate) fn codec_key(codec: &str) -> &str {
+    match codec {
+        "libopus" => "opus",
+        "libfdk_aac" => "aac",
+        "libmp3lame" => "mp3",
+        "libvorbis" => "vorbis",
+        other => other,
+    }
+}
+
+/// Most channels FFmpeg's encoder for a codec accepts; wider sources are
+/// downmixed to it.
+pub(crate) fn max_channels(codec: &str) -> Option<u32> {
+    match codec_key(codec) {
+        // FFmpeg's (E-)AC-3 encoders stop at 5.1
+        "ac3" | "eac3" => Some(6),
+        "mp3" => Some(2),
+        _ => None,
+    }
+}
+
+/// Built-in kbps per channel and optional ceiling for a codec.
+fn default_rung(codec: &str) -> (u32, Option<u32>) {
+    match codec {
+        "opus" => (64, Some(510)),
+        "aac" => (96, None),
+        // AC-3 tops out at 640 kbps regardless of channel count
+        "ac3" => (128, Some(640)),
+        "eac3" => (128, Some(1536)),
+        "mp3" => (160, Some(320)),
+        "vorbis" => (80, None),
+        _ => (96, None),
+    }
+}
+
+/// Audio bitrate chosen from the channel count, so a 5.1 track gets more than
+/// a stereo commentary instead of both sharing one fixed number.
+#[derive(Debug, Clone, Default)]
+pub struct BitrateLadder {
+    per_channel: HashMap<String, u32>,
+}
+
+impl BitrateLadder {
+    /// Ladder with per-codec overrides (kbps per channel), e.g. from a stream
+    /// selection profile's `audio.bitrate_per_channel`.
+    pub fn with_overrides(overrides: Option<&HashMap<String, u32>>) -> Self {
+        let per_channel = overrides
+            .map(|overrides| {
+                overrides
+                    .iter()
+                    .map(|(codec, kbps)| (codec_key(&codec.to_lowercase()).to_string(), *kbps))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { per_channel }
+    }
+
+    /// Bitrate in kbps for one stream encoded with `codec`.
+    pub fn bitrate_kbps(&self, codec: &str, channels: Option<u32>) -> u32 {
+        let key = codec_key(codec);
+        let (default_per_channel, ceiling) = default_rung(key);
+        let per_channel = self
+            .per_channel
+            .get(key)
+            .copied()
+            .unwrap_or(default_per_channel);
+
+        let channels = channels.filter(|&c| c > 0).unwrap_or(DEFAULT_CHANNELS);
+        let kbps = per_channel * channels;
+        ceiling.map_or(kbps, |ceiling| kbps.min(ceiling))
+    }
+}
+
+/// The bitrate picked for one transcoded stream, kept for the encode log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioBitrate {
+    pub stream_index: u32,
+    pub channels: Option<u32>,
+    pub codec: String,
+    pub bitrate: String,
+}
+
+impl fmt::Display for AudioBitrate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let channels = self
+            .channels
+            .map(|c| format!("{} ch", c))
+            .unwrap_or_else(|| "unknown channels".to_string());
+        write!(
+            f,
+            "audio stream #{} ({}): {} @ {}",
+            self.stream_index, channels, self.codec, self.bitrate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ladder_defaults_and_overrides() {
+        let ladder = BitrateLadder::default();
+        assert_eq!(ladder.bitrate_kbps("aac", Some(2)), 192);
+        assert_eq!(ladder.bitrate_kbps("libopus", Some(6)), 384);
+        assert_eq!(ladder.bitrate_kbps("opus", None), 128);
+        assert_eq!(ladder.bitrate_kbps("ac3", Some(6)), 640);
+
+        let overrides = HashMap::from([("Opus".to_string(), 80), ("aac".to_string(), 64)]);
+        let ladder = BitrateLadder::with_overrides(Some(&overrides));
+        assert_eq!(ladder.bitrate_kbps("libopus", Some(2)), 160);
+        assert_eq!(ladder.bitrate_kbps("aac", Some(1)), 64);
+        assert_eq!(ladder.bitrate_kbps("eac3", Some(2)), 256);
+    }
+}
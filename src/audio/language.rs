@@ -0,0 +1,232 @@
+//! Language detection for untagged audio tracks.
+//!
+//! Rips often carry audio tracks tagged `und`, which language-based stream
+//! selection can only keep or drop blindly. With `audio.language_detection`
+//! enabled, a short sample of each such track is extracted as 16 kHz mono WAV
+//! and handed to an external command (e.g. whisper.cpp), whose answer is used
+//! for selection and written to the output as the track's language tag.
+
+use crate::config::types::LanguageDetectionConfig;
+use crate::stream::preservation::LANGUAGE_ALIASES;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::debug;
+
+/// whisper.cpp reports `auto-detected language: en (p = 0.97)` on stderr
+static WHISPER_LANGUAGE_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"auto-detected language:\s*([A-Za-z]{2,3})\b").unwrap());
+
+/// Size of one second of 16 kHz mono 16-bit PCM; shorter samples are retried
+/// from the start of the track
+const MIN_SAMPLE_BYTES: u64 = 16_000 * 2;
+
+/// Whether a stream's language tag says nothing about its language.
+pub fn is_untagged(language: Option<&str>) -> bool {
+    language
+        .is_none_or(|language| language.trim().is_empty() || language.eq_ignore_ascii_case("und"))
+}
+
+/// Reads the language out of a detection command's output: whisper.cpp's
+/// `auto-detected language:` line if present, otherwise the first word of the
+/// last non-empty line of stdout. Returns the ISO 639-2 code Matroska and MP4
+/// tags use, or `None` for `und` and unknown two-letter codes.
+pub fn parse_detected_language(stdout: &str, stderr: &str) -> Option<String> {
+    let code = [stdout, stderr]
+        .iter()
+        .find_map(|output| WHISPER_LANGUAGE_REGEX.captures(output))
+        .map(|captures| captures[1].to_string())
+        .or_else(|| {
+            stdout
+                .lines()
+                .rev()
+                .find(|line| !line.trim().is_empty())
+                .and_then(|line| line.split_whitespace().next())
+                .map(str::to_string)
+        })?
+        .to_lowercase();
+
+    if !code.chars().all(|c| c.is_ascii_lowercase()) || code == "und" {
+        return None;
+    }
+    match code.len() {
+        2 => LANGUAGE_ALIASES
+            .iter()
+            .find(|(short, _)| *short == code)
+            .map(|(_, codes)| codes[0].to_string()),
+        3 => Some(code),
+        _ => None,
+    }
+}
+
+/// Runs the configured detection command on samples of audio streams.
+pub struct LanguageDetector {
+    ffmpeg: FfmpegWrapper,
+    config: LanguageDetectionConfig,
+    temp_dir: PathBuf,
+}
+
+impl LanguageDetector {
+    pub fn new(
+        ffmpeg: FfmpegWrapper,
+        config: LanguageDetectionConfig,
+        temp_dir: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            ffmpeg,
+            config,
+            temp_dir: temp_dir.into(),
+        }
+    }
+
+    /// Detects the language of one input stream (by absolute index).
+    pub async fn detect<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        stream_index: u32,
+    ) -> Result<Option<String>> {
+        std::fs::create_dir_all(&self.temp_dir)?;
+        let sample = self
+            .temp_dir
+            .join(format!("lang_sample_{}.wav", uuid::Uuid::new_v4().simple()));
+
+        let result = self
+            .detect_with_sample(input_path.as_ref(), stream_index, &sample)
+            .await;
+        let _ = std::fs::remove_file(&sample);
+        result
+    }
+
+    async fn detect_with_sample(
+        &self,
+        input_path: &Path,
+        stream_index: u32,
+        sample: &Path,
+    ) -> Result<Option<String>> {
+        self.extract_sample(input_path, stream_index, self.config.offset_seconds, sample)
+            .await?;
+        let too_short = std::fs::metadata(sample).map_or(true, |m| m.len() < MIN_SAMPLE_BYTES);
+        if too_short && self.config.offset_seconds > 0 {
+            // Track shorter than the offset
+            self.extract_sample(input_path, stream_index, 0, sample)
+                .await?;
+        }
+
+        let command = self.config.command.as_deref().ok_or_else(|| {
+            Error::validation("Audio language detection requires audio.language_detection.command")
+        })?;
+        // The sample path is passed as $1 so it never needs shell quoting
+        let script = command.replace("{sample}", "\"$1\"");
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(&script)
+            .arg("sh")
+            .arg(sample)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+        let output = tokio::time::timeout(
+            Duration::from_secs(self.config.timeout_seconds.max(1)),
+            child.wait_with_output(),
+        )
+        .await
+        .map_err(|_| {
+            Error::tool(format!(
+                "Language detection command timed out after {}s",
+                self.config.timeout_seconds
+            ))
+        })??;
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::tool(format!(
+                "Language detection command exited with {}: {}",
+                output.status,
+                stderr.lines().last().unwrap_or("no output")
+            )));
+        }
+        debug!(
+            "Language detection output for stream #{}: {}",
+            stream_index,
+            stdout.trim()
+        );
+        Ok(parse_detected_language(&stdout, &stderr))
+    }
+
+    async fn extract_sample(
+        &self,
+        input_path: &Path,
+        stream_index: u32,
+        offset_seconds: u32,
+        sample: &Path,
+    ) -> Result<()> {
+        let map = format!("0:{}", stream_index);
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"])
+            .args(["-ss", &offset_seconds.to_string(), "-i"])
+            .arg(input_path)
+            .args([
+                "-map",
+                &map,
+                "-t",
+                &self.config.sample_seconds.to_string(),
+                "-vn",
+                "-sn",
+                "-dn",
+                "-ac",
+                "1",
+                "-ar",
+                "16000",
+                "-c:a",
+                "pcm_s16le",
+            ])
+            .arg(sample)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "Audio sample extraction failed for stream #{}: {}",
+                stream_index,
+                String::from_utf8_lossy(&output.stderr)
+                    .lines()
+                    .last()
+                    .unwrap_or("unknown error")
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_detected_language() {
+        let whisper_stderr = "whisper_full_with_state: auto-detected language: ja (p = 0.981)\n";
+        assert_eq!(
+            parse_detected_language("", whisper_stderr),
+            Some("jpn".to_string())
+        );
+        assert_eq!(
+            parse_detected_language("loading model\nger 0.93\n\n", ""),
+            Some("ger".to_string())
+        );
+        assert_eq!(parse_detected_language("de\n", ""), Some("ger".to_string()));
+        assert_eq!(parse_detected_language("und\n", ""), None);
+        assert_eq!(parse_detected_language("xx\n", ""), None);
+        assert_eq!(parse_detected_language("", ""), None);
+
+        assert!(is_untagged(None));
+        assert!(is_untagged(Some("UND")));
+        assert!(!is_untagged(Some("eng")));
+    }
+}
@@ -0,0 +1,326 @@
+use crate::audio::bitrate::{AudioBitrate, BitrateLadder};
+use crate::config::types::LoudnormConfig;
+use crate::stream::preservation::StreamMapping;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Values reported by the first (measurement) pass of `loudnorm`.
+/// FFmpeg prints them as strings in its JSON summary.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LoudnormMeasurement {
+    #[serde(deserialize_with = "de_f64")]
+    pub input_i: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub input_tp: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub input_lra: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub input_thresh: f64,
+    #[serde(deserialize_with = "de_f64")]
+    pub target_offset: f64,
+}
+
+fn de_f64<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<f64, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let value = value.trim();
+    match value {
+        // Digital silence measures as -inf; clamp so the second pass still gets a number
+        "-inf" => Ok(-99.0),
+        "inf" | "+inf" => Ok(99.0),
+        _ => value.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+impl LoudnormMeasurement {
+    /// Extracts the JSON block that `loudnorm=print_format=json` writes to stderr.
+    pub fn parse(stderr: &str) -> Result<Self> {
+        let start = stderr
+            .rfind("[Parsed_loudnorm")
+            .and_then(|pos| stderr[pos..].find('{').map(|offset| pos + offset))
+            .or_else(|| stderr.rfind('{'))
+            .ok_or_else(|| Error::parse("No loudnorm measurement found in FFmpeg output"))?;
+        let end = stderr[start..]
+            .find('}')
+            .map(|offset| start + offset + 1)
+            .ok_or_else(|| Error::parse("Truncated loudnorm measurement in FFmpeg output"))?;
+
+        Ok(serde_json::from_str(&stderr[start..end])?)
+    }
+}
+
+/// Two-pass EBU R128 normalization of the audio streams in a mapping.
+pub struct LoudnormNormalizer {
+    ffmpeg: FfmpegWrapper,
+    config: LoudnormConfig,
+    ladder: BitrateLadder,
+}
+
+impl LoudnormNormalizer {
+    pub fn new(ffmpeg: FfmpegWrapper, config: LoudnormConfig) -> Self {
+        Self {
+            ffmpeg,
+            config,
+            ladder: BitrateLadder::default(),
+        }
+    }
+
+    /// Bitrate ladder used when `bitrate` is not fixed in the config.
+    pub fn with_bitrate_ladder(mut self, ladder: BitrateLadder) -> Self {
+        self.ladder = ladder;
+        self
+    }
+
+    fn target_filter(&self) -> String {
+        format!(
+            "loudnorm=I={}:TP={}:LRA={}",
+            self.config.target_lufs, self.config.true_peak, self.config.loudness_range
+        )
+    }
+
+    /// First pass: measures the loudness of one input stream (by absolute index).
+    pub async fn measure<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        stream_index: u32,
+    ) -> Result<LoudnormMeasurement> {
+        let input_path = input_path.as_ref();
+        let filter = format!("{}:print_format=json", self.target_filter());
+        let map = format!("0:{}", stream_index);
+
+        debug!(
+            "Measuring loudness of stream #{} with {}",
+            stream_index, filter
+        );
+
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(["-hide_banner", "-nostats", "-loglevel", "info", "-i"])
+            .arg(input_path)
+            .args([
+                "-map", &map, "-af", &filter, "-vn", "-sn", "-dn", "-f", "null", "-",
+            ])
+            .output()
+            .await?;
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "Loudness measurement failed for stream #{}: {}",
+                stream_index,
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+
+        LoudnormMeasurement::parse(&stderr)
+    }
+
+    /// Second-pass filter using the measured values (linear normalization).
+    pub fn build_filter(&self, measurement: &LoudnormMeasurement) -> String {
+        format!(
+            "{}:measured_I={:.2}:measured_TP={:.2}:measured_LRA={:.2}:measured_thresh={:.2}:offset={:.2}:linear=true:print_format=summary",
+            self.target_filter(),
+            measurement.input_i,
+            measurement.input_tp,
+            measurement.input_lra,
+            measurement.input_thresh,
+            measurement.target_offset
+        )
+    }
+
+    /// Measures every mapped audio stream and rewrites the mapping so audio is
+    /// re-encoded through the second `loudnorm` pass instead of being copied.
+    /// Returns the bitrate chosen for each stream.
+    pub async fn apply_to_mapping<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        mapping: &mut StreamMapping,
+    ) -> Result<Vec<AudioBitrate>> {
+        if mapping.audio_streams.is_empty() {
+            info!("No audio streams to normalize");
+            return Ok(Vec::new());
+        }
+
+        let mut measurements = Vec::with_capacity(mapping.audio_streams.len());
+        for stream in &mapping.audio_streams {
+            let measurement = self.measure(input_path.as_ref(), stream.index).await?;
+            info!(
+                "Audio stream #{}: {:.1} LUFS, {:.1} dBTP, LRA {:.1} LU -> target {} LUFS",
+                stream.index,
+                measurement.input_i,
+                measurement.input_tp,
+                measurement.input_lra,
+                self.config.target_lufs
+            );
+            measurements.push(measurement);
+        }
+
+        let bitrates = self.rewrite_mapping_args(mapping, &measurements);
+        for bitrate in &bitrates {
+            info!("Transcoding {}", bitrate);
+        }
+        Ok(bitrates)
+    }
+
+    /// Bitrate for each mapped audio stream: the fixed config value if set,
+    /// otherwise the ladder's value for the stream's channel count.
+    fn stream_bitrates(&self, mapping: &StreamMapping) -> Vec<AudioBitrate> {
+        mapping
+            .audio_streams
+            .iter()
+            .map(|stream| AudioBitrate {
+                stream_index: stream.index,
+                channels: stream.channels,
+                codec: self.config.codec.clone(),
+                bitrate: self.config.bitrate.clone().unwrap_or_else(|| {
+                    format!(
+                        "{}k",
+                        self.ladder
+                            .bitrate_kbps(&self.config.codec, stream.channels)
+                    )
+                }),
+            })
+            .collect()
+    }
+
+    fn rewrite_mapping_args(
+        &self,
+        mapping: &mut StreamMapping,
+        measurements: &[LoudnormMeasurement],
+    ) -> Vec<AudioBitrate> {
+        let mut args = Vec::with_capacity(mapping.mapping_args.len());
+        let mut iter = mapping.mapping_args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "-c:a" {
+                iter.next();
+                continue;
+            }
+            args.push(arg.clone());
+        }
+
+        let bitrates = self.stream_bitrates(mapping);
+        args.extend(vec![
+            "-c:a".to_string(),
+            self.config.codec.clone(),
+            "-ar".to_string(),
+            self.config.sample_rate.to_string(),
+        ]);
+        for (output_index, measurement) in measurements.iter().enumerate() {
+            if let Some(bitrate) = bitrates.get(output_index) {
+                args.push(format!("-b:a:{}", output_index));
+                args.push(bitrate.bitrate.clone());
+            }
+            args.push(format!("-filter:a:{}", output_index));
+            args.push(self.build_filter(measurement));
+        }
+
+        mapping.mapping_args = args;
+        bitrates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_OUTPUT: &str = r#"
+[Parsed_loudnorm_0 @ 0x55d0c8a0] 
+{
+	"input_i" : "-31.42",
+	"input_tp" : "-9.87",
+	"input_lra" : "6.20",
+	"input_thresh" : "-41.71",
+	"output_i" : "-23.05",
+	"output_tp" : "-1.42",
+	"output_lra" : "5.10",
+	"output_thresh" : "-33.30",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.05"
+}
+"#;
+
+    fn normalizer() -> LoudnormNormalizer {
+        LoudnormNormalizer::new(
+            FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string()),
+            LoudnormConfig::default(),
+        )
+    }
+
+    #[test]
+    fn test_parse_measurement() {
+        let measurement = LoudnormMeasurement::parse(SAMPLE_OUTPUT).unwrap();
+        assert_eq!(measurement.input_i, -31.42);
+        assert_eq!(measurement.input_tp, -9.87);
+        assert_eq!(measurement.target_offset, 0.05);
+
+        assert!(LoudnormMeasurement::parse("no json here").is_err());
+    }
+
+    #[test]
+    fn test_second_pass_filter_and_mapping() {
+        let normalizer = normalizer();
+        let measurement = LoudnormMeasurement::parse(SAMPLE_OUTPUT).unwrap();
+
+        let filter = normalizer.build_filter(&measurement);
+        assert!(filter.starts_with("loudnorm=I=-23:TP=-1:LRA=11:measured_I=-31.42"));
+        assert!(filter.contains("linear=true"));
+
+        let surround = crate::stream::preservation::StreamInfo {
+            index: 1,
+            codec_type: "audio".to_string(),
+            codec_name: "dts".to_string(),
+            channels: Some(6),
+            language: None,
+            title: None,
+            disposition: crate::stream::preservation::StreamDisposition {
+                default: true,
+                forced: false,
+                comment: false,
+                lyrics: false,
+                karaoke: false,
+                original: false,
+                dub: false,
+                visual_impaired: false,
+                hearing_impaired: false,
+                attached_pic: false,
+            },
+            closed_captions: false,
+        };
+        let mut stereo = surround.clone();
+        stereo.index = 2;
+        stereo.channels = Some(2);
+
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: vec![surround, stereo],
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            copied_video_args: Vec::new(),
+            mapping_args: [
+                "-map", "0:v:0", "-map", "0:a", "-c:a", "copy", "-c:s", "copy",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        let bitrates =
+            normalizer.rewrite_mapping_args(&mut mapping, &[measurement.clone(), measurement]);
+
+        let joined = mapping.mapping_args.join(" ");
+        assert!(!joined.contains("-c:a copy"));
+        assert!(joined.contains("-c:s copy"));
+        // Bitrate follows the channel count: 96 kbps per channel for AAC
+        assert!(joined.contains("-c:a aac -ar 48000 -b:a:0 576k -filter:a:0 loudnorm="));
+        assert!(joined.contains("-b:a:1 192k -filter:a:1 loudnorm="));
+        assert_eq!(
+            bitrates[0].to_string(),
+            "audio stream #1 (6 ch): aac @ 576k"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+pub mod bitrate;
+pub mod language;
+pub mod loudnorm;
+
+pub use crate::config::types::{LanguageDetectionConfig, LoudnormConfig};
+pub use bitrate::{AudioBitrate, BitrateLadder};
+pub use language::LanguageDetector;
+pub use loudnorm::{LoudnormMeasurement, LoudnormNormalizer};
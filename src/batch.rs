@@ -0,0 +1,434 @@
+//! Per-file results of an `encode` run over several inputs.
+//!
+//! [`BatchSummary`] collects what happened to each file (sizes, time, speed,
+//! profile) and reports it with totals at the end of the batch: on the console
+//! and, with `--summary`, as a CSV or JSON file.
+
+use crate::encoding::Provenance;
+use crate::engine::EncodeOutcome;
+use crate::progress::format_duration;
+use crate::utils::filesystem::format_file_size;
+use crate::utils::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::info;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Encoded,
+    Failed,
+    /// Abandoned because the output would not have saved space
+    Inefficient,
+    /// Already an output of VEN (carries its provenance tags)
+    Skipped,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Encoded => "encoded",
+            Self::Failed => "failed",
+            Self::Inefficient => "inefficient",
+            Self::Skipped => "skipped",
+        }
+    }
+}
+
+/// What happened to one input file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileSummary {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub status: FileStatus,
+    pub profile: Option<String>,
+    pub original_size: Option<u64>,
+    pub new_size: Option<u64>,
+    /// Negative when the output is larger than the source
+    pub saved_percent: Option<f64>,
+    pub duration_seconds: f64,
+    pub average_fps: Option<f64>,
+    pub error: Option<String>,
+}
+
+/// Totals over a batch; sizes and savings only count encoded files.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchTotals {
+    pub files: usize,
+    pub encoded: usize,
+    pub failed: usize,
+    pub inefficient: usize,
+    pub skipped: usize,
+    pub original_size: u64,
+    pub new_size: u64,
+    pub saved_percent: Option<f64>,
+    pub duration_seconds: f64,
+}
+
+impl BatchTotals {
+    /// `N encoded, N failed, N inefficient`, plus skipped files if any.
+    pub fn status_counts(&self) -> String {
+        let mut text = format!(
+            "{} encoded, {} failed, {} inefficient",
+            self.encoded, self.failed, self.inefficient
+        );
+        if self.skipped > 0 {
+            text.push_str(&format!(", {} skipped", self.skipped));
+        }
+        text
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BatchSummary {
+    files: Vec<FileSummary>,
+}
+
+fn saved_percent(original: u64, new: u64) -> Option<f64> {
+    (original > 0).then(|| (1.0 - new as f64 / original as f64) * 100.0)
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    std::fs::metadata(path)
+        .ok()
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+}
+
+impl BatchSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn files(&self) -> &[FileSummary] {
+        &self.files
+    }
+
+    pub fn add(&mut self, file: FileSummary) {
+        self.files.push(file);
+    }
+
+    /// Records a finished encode of `input`, whose size is `original_size`.
+    pub fn record_success(
+        &mut self,
+        input: &Path,
+        original_size: Option<u64>,
+        outcome: &EncodeOutcome,
+    ) {
+        let new_size = file_size(&outcome.output_path);
+        self.add(FileSummary {
+            input: input.to_path_buf(),
+            output: Some(outcome.output_path.clone()),
+            status: FileStatus::Encoded,
+            profile: outcome.profile.clone(),
+            original_size,
+            new_size,
+            saved_percent: original_size
+                .zip(new_size)
+                .and_then(|(original, new)| saved_percent(original, new)),
+            duration_seconds: outcome.elapsed.as_secs_f64(),
+            average_fps: outcome.average_fps(),
+            error: None,
+        });
+    }
+
+    /// Records a file that was not encoded because of `error`.
+    pub fn record_failure(&mut self, input: &Path, error: &Error, elapsed: Duration) {
+        let (status, message) = match error {
+            Error::Inefficient(reason) => (FileStatus::Inefficient, reason.clone()),
+            Error::Duplicate(reason) => (FileStatus::Skipped, reason.clone()),
+            e => (FileStatus::Failed, e.to_string()),
+        };
+        self.add(FileSummary {
+            input: input.to_path_buf(),
+            output: None,
+            status,
+            profile: None,
+            original_size: file_size(input),
+            new_size: None,
+            saved_percent: None,
+            duration_seconds: elapsed.as_secs_f64(),
+            average_fps: None,
+            error: Some(message),
+        });
+    }
+
+    /// Records a file left alone because VEN already produced it.
+    pub fn record_skip(&mut self, input: &Path, provenance: &Provenance) {
+        self.add(FileSummary {
+            input: input.to_path_buf(),
+            output: None,
+            status: FileStatus::Skipped,
+            profile: provenance.profile.clone(),
+            original_size: file_size(input),
+            new_size: None,
+            saved_percent: None,
+            duration_seconds: 0.0,
+            average_fps: None,
+            error: None,
+        });
+    }
+
+    pub fn count(&self, status: FileStatus) -> usize {
+        self.files.iter().filter(|f| f.status == status).count()
+    }
+
+    pub fn totals(&self) -> BatchTotals {
+        let encoded = self
+            .files
+            .iter()
+            .filter(|f| f.status == FileStatus::Encoded);
+        let (original_size, new_size) = encoded
+            .filter_map(|f| f.original_size.zip(f.new_size))
+            .fold((0, 0), |(original, new), (o, n)| (original + o, new + n));
+
+        BatchTotals {
+            files: self.files.len(),
+            encoded: self.count(FileStatus::Encoded),
+            failed: self.count(FileStatus::Failed),
+            inefficient: self.count(FileStatus::Inefficient),
+            skipped: self.count(FileStatus::Skipped),
+            original_size,
+            new_size,
+            saved_percent: saved_percent(original_size, new_size),
+            duration_seconds: self.files.iter().map(|f| f.duration_seconds).sum(),
+        }
+    }
+
+    /// Prints one line per file and the totals.
+    pub fn log(&self) {
+        let totals = self.totals();
+        info!("Processing complete: {}", totals.status_counts());
+
+        for file in &self.files {
+            let name = file
+                .input
+                .file_name()
+                .unwrap_or(file.input.as_os_str())
+                .to_string_lossy();
+            let duration = format_duration(Duration::from_secs_f64(file.duration_seconds));
+            match file.status {
+                FileStatus::Encoded => info!(
+                    "  ✓ {}: {}, {}{}{}",
+                    name,
+                    size_change(file.original_size, file.new_size),
+                    duration,
+                    file.average_fps
+                        .map(|fps| format!(", {:.1}fps", fps))
+                        .unwrap_or_default(),
+                    file.profile
+                        .as_ref()
+                        .map(|profile| format!(", profile {}", profile))
+                        .unwrap_or_default()
+                ),
+                FileStatus::Failed | FileStatus::Inefficient => info!(
+                    "  ✗ {} ({}): {}",
+                    name,
+                    file.status.as_str(),
+                    file.error.as_deref().unwrap_or_default()
+                ),
+                FileStatus::Skipped => info!(
+                    "  - {}: skipped, {}",
+                    name,
+                    file.error.as_deref().unwrap_or("already encoded")
+                ),
+            }
+        }
+
+        info!(
+            "Total: {}, {}",
+            size_change(Some(totals.original_size), Some(totals.new_size)),
+            format_duration(Duration::from_secs_f64(totals.duration_seconds))
+        );
+    }
+
+    /// Writes the summary as CSV or JSON, picked by the extension of `path`.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = match SummaryFormat::from_path(path)? {
+            SummaryFormat::Csv => self.to_csv(),
+            SummaryFormat::Json => serde_json::to_string_pretty(&serde_json::json!({
+                "files": self.files,
+                "totals": self.totals(),
+            }))?,
+        };
+        std::fs::write(path, contents)?;
+        info!("Batch summary written to {}", path.display());
+        Ok(())
+    }
+
+    /// One row per file, then a `TOTAL` row.
+    pub fn to_csv(&self) -> String {
+        fn opt<T: ToString>(value: Option<T>) -> String {
+            value.map(|v| v.to_string()).unwrap_or_default()
+        }
+        fn percent(value: Option<f64>) -> String {
+            opt(value.map(|v| format!("{:.2}", v)))
+        }
+
+        let mut rows = vec![[
+            "input",
+            "output",
+            "status",
+            "profile",
+            "original_size",
+            "new_size",
+            "saved_percent",
+            "duration_seconds",
+            "average_fps",
+            "error",
+        ]
+        .map(String::from)];
+        for file in &self.files {
+            rows.push([
+                file.input.display().to_string(),
+                opt(file.output.as_ref().map(|p| p.display())),
+                file.status.as_str().to_string(),
+                opt(file.profile.as_ref()),
+                opt(file.original_size),
+                opt(file.new_size),
+                percent(file.saved_percent),
+                format!("{:.1}", file.duration_seconds),
+                percent(file.average_fps),
+                opt(file.error.as_ref()),
+            ]);
+        }
+
+        let totals = self.totals();
+        rows.push([
+            "TOTAL".to_string(),
+            String::new(),
+            totals.status_counts(),
+            String::new(),
+            totals.original_size.to_string(),
+            totals.new_size.to_string(),
+            percent(totals.saved_percent),
+            format!("{:.1}", totals.duration_seconds),
+            String::new(),
+            String::new(),
+        ]);
+
+        rows.iter()
+            .map(|row| {
+                let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                fields.join(",") + "\n"
+            })
+            .collect()
+    }
+}
+
+/// `original > new (-N%)`, or `+N%` when the output grew.
+fn size_change(original: Option<u64>, new: Option<u64>) -> String {
+    match (original, new) {
+        (Some(original), Some(new)) => {
+            let change = match saved_percent(original, new) {
+                Some(saved) if saved >= 0.0 => format!(" (-{:.0}%)", saved),
+                Some(saved) => format!(" (+{:.0}%)", -saved),
+                None => String::new(),
+            };
+            format!(
+                "{} > {}{}",
+                format_file_size(original),
+                format_file_size(new),
+                change
+            )
+        }
+        (_, Some(new)) => format_file_size(new),
+        _ => "size unknown".to_string(),
+    }
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryFormat {
+    Csv,
+    Json,
+}
+
+impl SummaryFormat {
+    pub fn from_path(path: &Path) -> Result<Self> {
+        match path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .as_deref()
+        {
+            Some("csv") => Ok(Self::Csv),
+            Some("json") => Ok(Self::Json),
+            _ => Err(Error::validation(format!(
+                "Summary file must end in .csv or .json: {}",
+                path.display()
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(input: &str, original: u64, new: u64) -> FileSummary {
+        FileSummary {
+            input: PathBuf::from(input),
+            output: Some(PathBuf::from(format!("out/{}", input))),
+            status: FileStatus::Encoded,
+            profile: Some("anime".to_string()),
+            original_size: Some(original),
+            new_size: Some(new),
+            saved_percent: saved_percent(original, new),
+            duration_seconds: 60.0,
+            average_fps: Some(24.0),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_totals_and_csv() {
+        let mut summary = BatchSummary::new();
+        summary.add(encoded("a.mkv", 1000, 400));
+        summary.add(encoded("b, c.mkv", 3000, 1600));
+        summary.record_failure(
+            Path::new("missing.mkv"),
+            &Error::inefficient("projected output is 1.20x the source"),
+            Duration::from_secs(30),
+        );
+
+        let totals = summary.totals();
+        assert_eq!(
+            (totals.encoded, totals.failed, totals.inefficient),
+            (2, 0, 1)
+        );
+        assert_eq!((totals.original_size, totals.new_size), (4000, 2000));
+        assert_eq!(totals.saved_percent, Some(50.0));
+        assert_eq!(totals.duration_seconds, 150.0);
+
+        let csv = summary.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[0].starts_with("input,output,status,"));
+        assert_eq!(
+            lines[2],
+            "\"b, c.mkv\",\"out/b, c.mkv\",encoded,anime,3000,1600,46.67,60.0,24.00,"
+        );
+        assert!(lines[3].starts_with("missing.mkv,,inefficient,,,,,30.0,,"));
+        assert_eq!(
+            lines[4],
+            "TOTAL,,\"2 encoded, 0 failed, 1 inefficient\",,4000,2000,50.00,150.0,,"
+        );
+
+        summary.record_skip(Path::new("done.mkv"), &Provenance::default());
+        assert_eq!(
+            summary.totals().status_counts(),
+            "2 encoded, 0 failed, 1 inefficient, 1 skipped"
+        );
+
+        assert!(SummaryFormat::from_path(Path::new("batch.JSON")).is_ok());
+        assert!(SummaryFormat::from_path(Path::new("batch.txt")).is_err());
+    }
+}
@@ -1,6 +1,13 @@
-use crate::utils::Result;
-use clap::Parser;
-use std::path::PathBuf;
+use crate::config::CropDetectionOverrides;
+use crate::encoding::EncodingOptions;
+use crate::preview::ScreenshotPosition;
+use crate::reports::{parse_date, ReportCondition};
+use crate::schedule::TimeWindow;
+use crate::utils::{FileDiscovery, Result};
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -10,29 +17,340 @@ use std::path::PathBuf;
 )]
 #[command(long_about = "
 A professional-grade Rust implementation of automated video encoding using FFmpeg with x265/HEVC codec.
-Provides multi-mode encoding support (CRF/ABR/CBR), intelligent content analysis with automatic profile 
+Provides multi-mode encoding support (CRF/ABR/CBR), intelligent content analysis with automatic profile
 selection, and comprehensive batch processing capabilities.
 
 EXAMPLES:
   # Auto-selection with UUID output
-  ffmpeg-encoder -i input.mkv -p auto -m crf
+  ffmpeg-encoder encode -i input.mkv -p auto -m crf
 
-  # Specific profile with custom output  
-  ffmpeg-encoder -i input.mkv -o output.mkv -p anime -m crf
+  # Specific profile with custom output
+  ffmpeg-encoder encode -i input.mkv -o output.mkv -p anime -m crf
 
   # With denoising
-  ffmpeg-encoder -i input.mkv -p 4k_heavy_grain -m crf --denoise
+  ffmpeg-encoder encode -i input.mkv -p 4k_heavy_grain -m crf --denoise
 
   # Legacy interlaced footage with neural network deinterlacing
-  ffmpeg-encoder -i legacy_footage.mkv -p classic_anime -m crf --deinterlace
+  ffmpeg-encoder encode -i legacy_footage.mkv -p classic_anime -m crf --deinterlace
 
   # Batch processing directory
-  ffmpeg-encoder -i ~/Videos/Raw/ -p auto -m abr
+  ffmpeg-encoder encode -i ~/Videos/Raw/ -p auto -m abr
 
-  # With automatic crop detection
-  ffmpeg-encoder -i input.mkv -p movie -m abr
+  # Preview a single frame with every profile of a preview group
+  ffmpeg-encoder preview -i input.mkv --time 60 --group anime_comparison
+
+  # Inspect a file without encoding it
+  ffmpeg-encoder analyze -i input.mkv
+
+  # Configuration and tooling
+  ffmpeg-encoder config list-profiles
+  ffmpeg-encoder tools check
+
+  # Check that this installation can run the whole pipeline
+  ffmpeg-encoder selftest --hdr
+
+The pre-subcommand flags (e.g. `ffmpeg-encoder -i input.mkv --preview-time 60`) are still
+accepted for this release but are deprecated.
 ")]
 pub struct CliArgs {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(flatten)]
+    pub legacy: LegacyArgs,
+}
+
+/// Options shared by every subcommand
+#[derive(Args, Debug, Clone, Default)]
+pub struct GlobalArgs {
+    /// Configuration file path (optional, auto-discovers if not specified)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Enable verbose logging
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Enable debug logging
+    #[arg(long, global = true)]
+    pub debug: bool,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Encode video files (the default workflow)
+    Encode(EncodeArgs),
+
+    /// Generate preview images or segments to compare profiles
+    Preview(PreviewArgs),
+
+    /// Analyze files (HDR, Dolby Vision, crop, streams) without encoding
+    Analyze(AnalyzeArgs),
+
+    /// Take lossless PNG screenshots of a source at set positions for comparisons
+    Screenshots(ScreenshotsArgs),
+
+    /// Encode the same segments with several profiles and compare size, quality and time
+    Shootout(ShootoutArgs),
+
+    /// Inspect the external tools used by the pipeline
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
+
+    /// Inspect and validate the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Manage the persistent encoding queue
+    Queue {
+        #[command(subcommand)]
+        command: QueueCommand,
+    },
+
+    /// Browse the reports of past encodes
+    Logs {
+        #[command(subcommand)]
+        command: LogsCommand,
+    },
+
+    /// Summarize past encodes per profile: savings, VMAF, speed and time spent
+    Stats {
+        /// Only count encodes finished on or after this date (YYYY-MM-DD)
+        #[arg(long, value_name = "DATE")]
+        since: Option<String>,
+
+        /// One row per profile and month, to follow a profile over time
+        #[arg(long)]
+        monthly: bool,
+    },
+
+    /// Remove temporary files left behind by crashed or killed runs
+    Clean {
+        /// Also look in these directories (e.g. output folders) besides app.temp_dir
+        #[arg(value_name = "DIR")]
+        dirs: Vec<PathBuf>,
+
+        /// Remove files without a job marker once they are this old (defaults to cleanup.min_age_hours)
+        #[arg(long, value_name = "HOURS")]
+        older_than: Option<u64>,
+
+        /// Only list what would be removed
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Encode a synthetic clip end-to-end and report which subsystems and tools work
+    Selftest {
+        /// Tag the synthetic source as HDR10 to exercise the HDR metadata path
+        #[arg(long)]
+        hdr: bool,
+
+        /// Add HDR10+ dynamic metadata to the synthetic source (needs hdr10plus_tool)
+        #[arg(long)]
+        hdr10plus: bool,
+
+        /// Add a Dolby Vision profile 8.1 RPU to the synthetic source (needs dovi_tool)
+        #[arg(long)]
+        dv81: bool,
+
+        /// Duration of the synthetic source in seconds
+        #[arg(long, default_value_t = 10.0, value_name = "SECONDS")]
+        duration: f64,
+
+        /// Keep the generated source, output and log instead of deleting them
+        #[arg(long)]
+        keep: bool,
+
+        /// Encoding profile to use for the encode step (defaults to 'default' or the first profile)
+        #[arg(short, long, value_name = "PROFILE")]
+        profile: Option<String>,
+    },
+
+    /// Write a synthetic source with chosen HDR10/HDR10+/Dolby Vision characteristics
+    GenerateTest {
+        /// BT.2020/PQ with mastering display and content light levels
+        #[arg(long)]
+        hdr10: bool,
+
+        /// HDR10+ dynamic metadata on an HDR10 base (needs hdr10plus_tool)
+        #[arg(long)]
+        hdr10plus: bool,
+
+        /// Dolby Vision profile 8.1 RPU on an HDR10 base (needs dovi_tool)
+        #[arg(long)]
+        dv81: bool,
+
+        /// Duration in seconds
+        #[arg(long, default_value_t = 30.0, value_name = "SECONDS")]
+        duration: f64,
+
+        /// Output file (defaults to e.g. test_hdr10_dv81.mkv in the current directory)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+#[allow(clippy::large_enum_variant)] // parsed once per run
+pub enum QueueCommand {
+    /// Add files (or directories) to the queue with the given encode options
+    Add(EncodeArgs),
+
+    /// List queued, running and finished jobs
+    List,
+
+    /// Remove a job by its ID
+    Remove {
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+
+    /// Remove jobs from the queue (all of them, or only finished ones)
+    Clear {
+        /// Only remove completed and failed jobs
+        #[arg(long)]
+        finished: bool,
+    },
+
+    /// Process pending jobs until the queue is empty
+    Run {
+        /// Number of encodes to run in parallel
+        #[arg(short, long, default_value_t = 1, value_name = "N")]
+        jobs: usize,
+
+        /// Only start jobs inside this daily window (HH:MM-HH:MM, may wrap past midnight)
+        #[arg(long, value_name = "HH:MM-HH:MM")]
+        schedule: Option<String>,
+
+        /// Also pause running jobs while outside the --schedule window
+        #[arg(long, requires = "schedule")]
+        pause_outside_schedule: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum LogsCommand {
+    /// List the most recent encodes, newest first
+    List {
+        /// Number of encodes to list
+        #[arg(short = 'n', long, default_value_t = 20, value_name = "N")]
+        limit: usize,
+    },
+
+    /// Show the report and log of one encode
+    Show {
+        /// Number from `logs list` (1 = the most recent), or an input or output path
+        #[arg(value_name = "ENCODE")]
+        encode: String,
+    },
+
+    /// List encodes matching every condition, e.g. 'vmaf<93' 'savings<20%' 'profile=anime'
+    Grep {
+        /// FIELD OP VALUE with OP one of < <= > >= = != ~ (contains); fields: vmaf, ssim,
+        /// xpsnr, butteraugli, savings, fps, duration, status, profile, input, output, error
+        #[arg(value_name = "CONDITION", required = true)]
+        conditions: Vec<String>,
+    },
+
+    /// Follow the log of a running encode, e.g. from another terminal
+    Tail {
+        /// Output path (or its .log); for a directory, the newest log in it, switching to
+        /// the next file's log as a batch moves on
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+
+        /// Lines of the log to print before following
+        #[arg(short = 'n', long, default_value_t = 20, value_name = "N")]
+        lines: usize,
+
+        /// Stop following a single file's log after this many minutes without new
+        /// lines (the encode was killed before writing its result); 0 never stops
+        #[arg(long, default_value_t = 30, value_name = "MINUTES")]
+        idle_timeout: u64,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ToolsCommand {
+    /// Check availability of ffmpeg, ffprobe, dovi_tool, hdr10plus_tool and mkvmerge
+    Check,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// List available encoding profiles
+    ListProfiles,
+
+    /// Show detailed information about a specific profile
+    ShowProfile {
+        #[arg(value_name = "PROFILE")]
+        name: String,
+    },
+
+    /// List all available stream selection profiles
+    ListStreamProfiles,
+
+    /// Show detailed information about a specific stream selection profile
+    ShowStreamProfile {
+        #[arg(value_name = "PROFILE")]
+        name: String,
+    },
+
+    /// List available preview profile groups
+    ListPreviewProfiles,
+
+    /// Validate configuration file
+    Validate,
+
+    /// Print the effective configuration after merging all config layers
+    Show {
+        /// Print every value with the file it came from
+        #[arg(long)]
+        origin: bool,
+    },
+
+    /// Write profiles (and the stream selection profiles they use) to a shareable bundle
+    Export {
+        /// Comma-separated profile names
+        #[arg(long, value_name = "PROFILES")]
+        profiles: String,
+
+        /// Write the bundle to this file instead of printing it
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+
+    /// Merge a profile bundle into the user config (or the file given with --config)
+    Import {
+        #[arg(value_name = "BUNDLE")]
+        bundle: PathBuf,
+
+        /// What to do with profiles whose name is already taken
+        #[arg(long, default_value = "ask", value_parser = ["ask", "overwrite", "skip", "rename"])]
+        on_conflict: String,
+    },
+
+    /// Convert HandBrake presets (a presets.json export) into VEN profiles
+    ImportHandbrake {
+        #[arg(value_name = "PRESETS")]
+        presets: PathBuf,
+
+        /// Write the profiles to this file instead of printing them
+        #[arg(short, long, value_name = "PATH")]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodeArgs {
     /// Input video file or directory (can be specified multiple times)
     #[arg(short, long, value_name = "PATH", action = clap::ArgAction::Append)]
     pub input: Vec<PathBuf>,
@@ -41,7 +359,11 @@ pub struct CliArgs {
     #[arg(short, long, value_name = "PATH")]
     pub output: Option<PathBuf>,
 
-    /// Encoding profile to use (use --list-profiles to see available profiles, or 'auto' for automatic selection)
+    /// Replace an existing output file that is not an earlier VEN encode of the same source
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Encoding profile to use (use `config list-profiles` to see available profiles, or 'auto' for automatic selection)
     #[arg(short, long, default_value = "auto", value_name = "PROFILE")]
     pub profile: String,
 
@@ -61,62 +383,335 @@ pub struct CliArgs {
     #[arg(long)]
     pub deinterlace: bool,
 
-    /// Configuration file path (optional, auto-discovers if not specified)
+    /// Stream selection profile to use (use `config list-stream-profiles` to see available profiles)
+    #[arg(short = 's', long = "stream-selection-profile", value_name = "PROFILE")]
+    pub stream_selection_profile: Option<String>,
+
+    /// Video handling: encode (x265) or copy (remux only, keeping stream selection, chapters and metadata)
+    #[arg(long, default_value = "encode", value_parser = ["encode", "copy"])]
+    pub video: String,
+
+    /// Encode the N-th video stream (0:v:N) instead of the first, e.g. when stream 0 is a menu or preview track
+    #[arg(long, value_name = "N")]
+    pub video_stream: Option<usize>,
+
+    /// Encode only from this many seconds into the source (Dolby Vision RPU and HDR10+ metadata are cut to match)
+    #[arg(long, value_name = "SECONDS")]
+    pub start: Option<f64>,
+
+    /// Encode only up to this many seconds into the source
+    #[arg(long, value_name = "SECONDS")]
+    pub end: Option<f64>,
+
+    /// Normalize audio loudness (two-pass loudnorm to the configured LUFS target); implies --video copy
+    #[arg(long)]
+    pub normalize_audio: bool,
+
+    /// Verify copied audio/subtitle streams are bit-exact (per-stream MD5 of source vs output)
+    #[arg(long)]
+    pub verify_streams: bool,
+
+    /// Salvage damaged sources: ignore decode errors and discard corrupt frames instead of aborting
+    #[arg(long)]
+    pub error_resilience: bool,
+
+    /// Re-run HDR, Dolby Vision, crop and content analysis instead of reusing cached results
+    #[arg(long)]
+    pub no_analysis_cache: bool,
+
+    /// For Dolby Vision sources, also write an HDR10-only copy (<name>.hdr10.mkv) without the RPU
+    #[arg(long)]
+    pub dv_hdr10_fallback: bool,
+
+    /// Fail the file when Dolby Vision/HDR10+ metadata cannot be extracted, converted or injected, instead of writing it without
+    #[arg(long)]
+    pub strict_metadata: bool,
+
+    /// Record every automated decision (profile, crop, rate, filters, metadata, streams) with its reason and config key in the encode report
+    #[arg(long)]
+    pub explain: bool,
+
+    /// Encode Dolby Vision profile 5 sources by reshaping them to HDR10 (ffmpeg with libplacebo) and converting the RPU to profile 8.1
+    #[arg(long)]
+    pub dv5_convert: bool,
+
+    /// Apply a playback device's limits (from `devices` in the config, e.g. chromecast, appletv, shield, webos)
+    #[arg(long, value_name = "DEVICE")]
+    pub device: Option<String>,
+
+    /// Encode every rendition of a ladder (from `ladders` in the config, e.g. 2160p HDR + 1080p SDR) instead of a single output
+    #[arg(long, value_name = "LADDER")]
+    pub ladder: Option<String>,
+
+    /// Write a thumbnail sprite with WebVTT track (or a strip, see `thumbnails` in the config) next to each output
+    #[arg(long)]
+    pub thumbnails: bool,
+
+    /// Score each output against its source with the metrics of `quality` in the config (VMAF, SSIM, XPSNR, butteraugli)
+    #[arg(long)]
+    pub quality_check: bool,
+
+    /// Pick per file the slowest preset of `planning.presets` that lets the batch finish within this time (e.g. 8h, 1h30m)
+    #[arg(long, value_name = "DURATION")]
+    pub time_budget: Option<String>,
+
+    /// Only start new encodes inside this daily window (HH:MM-HH:MM, may wrap past midnight)
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    pub schedule: Option<String>,
+
+    /// Also pause a running encode while outside the --schedule window
+    #[arg(long, requires = "schedule")]
+    pub pause_outside_schedule: bool,
+
+    /// Remux instead of re-encoding when the source is already HEVC 10-bit within the profile's bitrate ceiling and needs no crop or filters
+    #[arg(long)]
+    pub smart: bool,
+
+    /// Write the batch summary (sizes, savings, time, speed per file) to a .csv or .json file
     #[arg(long, value_name = "FILE")]
-    pub config: Option<PathBuf>,
+    pub summary: Option<PathBuf>,
 
-    /// Enable verbose logging
-    #[arg(short, long)]
-    pub verbose: bool,
+    /// Append every encode event (stages, progress, results) to FILE as JSON Lines, for other programs to follow
+    #[arg(long, value_name = "FILE")]
+    pub events_json: Option<PathBuf>,
 
-    /// Enable debug logging
+    /// Batches: analyze the next file (probe, HDR/Dolby Vision, crop detection) while the current one encodes
     #[arg(long)]
-    pub debug: bool,
+    pub pipeline: bool,
 
-    /// List available encoding profiles
+    /// Blu-ray inputs: encode this playlist (e.g. 00800.mpls) instead of the longest one
+    #[arg(long, value_name = "MPLS")]
+    pub playlist: Option<String>,
+
+    /// Descend into symlinked directories when searching input folders
+    #[arg(long)]
+    pub follow_symlinks: bool,
+
+    /// Encode a file again when it is reached twice (through hardlinks, symlinks or overlapping inputs)
+    #[arg(long)]
+    pub allow_duplicates: bool,
+
+    /// Encode inputs VEN produced itself (recognized by their provenance tags) instead of skipping them
+    #[arg(long)]
+    pub re_encode_own_output: bool,
+
+    /// On failure, pack the log, error, source probe, resolved config and tool versions into <output>.debug.tar.gz
+    #[arg(long)]
+    pub debug_bundle: bool,
+
+    /// Keep the temporary encode, extracted RPU, HDR10+ JSON and two-pass stats files for debugging (their paths are logged)
+    #[arg(long)]
+    pub keep_temp: bool,
+
+    /// Crop detection: number of samples, for this run (overrides analysis.crop_detection.sample_count)
+    #[arg(long, value_name = "N")]
+    pub crop_samples: Option<u32>,
+
+    /// Crop detection: cropdetect limit for SDR and HDR sources, for this run (0-255)
+    #[arg(long, value_name = "LIMIT")]
+    pub crop_threshold: Option<u32>,
+
+    /// Crop detection: percentage of samples that must agree on the crop, or the source is left uncropped
+    #[arg(long, value_name = "PERCENT")]
+    pub crop_min_confidence: Option<f32>,
+
+    /// When a detected crop is applied, write a before/after report (<output>.crop.html and .crop.jpg) next to the log
+    #[arg(long)]
+    pub crop_report: bool,
+}
+
+impl Default for EncodeArgs {
+    fn default() -> Self {
+        Self {
+            input: Vec::new(),
+            output: None,
+            overwrite: false,
+            profile: "auto".to_string(),
+            title: None,
+            mode: "abr".to_string(),
+            denoise: false,
+            deinterlace: false,
+            stream_selection_profile: None,
+            video: "encode".to_string(),
+            video_stream: None,
+            start: None,
+            end: None,
+            normalize_audio: false,
+            verify_streams: false,
+            error_resilience: false,
+            no_analysis_cache: false,
+            dv_hdr10_fallback: false,
+            strict_metadata: false,
+            explain: false,
+            dv5_convert: false,
+            device: None,
+            ladder: None,
+            thumbnails: false,
+            quality_check: false,
+            time_budget: None,
+            schedule: None,
+            pause_outside_schedule: false,
+            smart: false,
+            summary: None,
+            events_json: None,
+            pipeline: false,
+            playlist: None,
+            follow_symlinks: false,
+            allow_duplicates: false,
+            re_encode_own_output: false,
+            debug_bundle: false,
+            keep_temp: false,
+            crop_samples: None,
+            crop_threshold: None,
+            crop_min_confidence: None,
+            crop_report: false,
+        }
+    }
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct PreviewArgs {
+    /// Input video file
+    #[arg(short, long, value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Output directory (or a file path whose directory is used)
+    #[arg(short, long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+
+    /// Encoding profile to preview ('auto' previews every profile)
+    #[arg(short, long, default_value = "auto", value_name = "PROFILE")]
+    pub profile: String,
+
+    /// Preview timestamp in seconds (for single frame image generation); several
+    /// (comma-separated or repeated) also write a montage of all frames
+    #[arg(
+        long = "time",
+        visible_alias = "preview-time",
+        value_name = "SECONDS",
+        value_delimiter = ','
+    )]
+    pub time: Vec<f64>,
+
+    /// Preview time range in format "START-END" (for video segment encoding, e.g., "10-20")
+    #[arg(
+        long = "range",
+        visible_alias = "preview-range",
+        value_name = "START-END"
+    )]
+    pub range: Option<String>,
+
+    /// Preview profile group to use (from config's preview_profiles section)
+    #[arg(long = "group", visible_alias = "preview-profile", value_name = "NAME")]
+    pub group: Option<String>,
+
+    /// Seek mode: accurate (start at an earlier keyframe and trim, clean on open-GOP
+    /// sources) or fast (seek straight to the start, may show corrupted first frames)
+    #[arg(long, default_value = "accurate", value_parser = ["accurate", "fast"])]
+    pub seek: String,
+
+    /// Preview the N-th video stream (0:v:N) instead of the first
+    #[arg(long, value_name = "N")]
+    pub video_stream: Option<usize>,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct AnalyzeArgs {
+    /// Input video file or directory (can be specified multiple times)
+    #[arg(short, long, value_name = "PATH", action = clap::ArgAction::Append, required = true)]
+    pub input: Vec<PathBuf>,
+
+    /// Check each file against the `check:` policy of the configuration and
+    /// exit non-zero if any violates it
+    #[arg(long)]
+    pub check: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ScreenshotsArgs {
+    /// Input video file
+    #[arg(value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Comma-separated positions, as percentages of the duration or seconds
+    #[arg(long, default_value = "10%,30%,50%,70%,90%", value_name = "POSITIONS")]
+    pub at: String,
+
+    /// Output directory (defaults to the input's directory)
+    #[arg(short, long, value_name = "DIR")]
+    pub output: Option<PathBuf>,
+
+    /// Seek mode: accurate (decode from an earlier keyframe, exact frame) or fast
+    #[arg(long, default_value = "accurate", value_parser = ["accurate", "fast"])]
+    pub seek: String,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct ShootoutArgs {
+    /// Input video file
+    #[arg(value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Comma-separated encoding profiles to compare
+    #[arg(long, value_delimiter = ',', required = true, value_name = "PROFILES")]
+    pub profiles: Vec<String>,
+
+    /// Number of segments, spread between 10% and 90% of the runtime
+    #[arg(long, default_value_t = 5, value_name = "N")]
+    pub segments: usize,
+
+    /// Length of each segment in seconds
+    #[arg(long, default_value_t = 20.0, value_name = "SECONDS")]
+    pub length: f64,
+}
+
+/// Pre-subcommand flat flags, kept as deprecated aliases for one release
+#[derive(Args, Debug, Clone, Default)]
+#[command(next_help_heading = "Legacy options (deprecated, use subcommands)")]
+pub struct LegacyArgs {
+    #[command(flatten)]
+    pub encode: EncodeArgs,
+
+    /// List available encoding profiles (use `config list-profiles`)
     #[arg(long)]
     pub list_profiles: bool,
 
-    /// Show detailed information about a specific profile
+    /// Show detailed information about a specific profile (use `config show-profile`)
     #[arg(long, value_name = "PROFILE")]
     pub show_profile: Option<String>,
 
-    /// Validate configuration file
+    /// Validate configuration file (use `config validate`)
     #[arg(long)]
     pub validate_config: bool,
 
-    /// Stream selection profile to use (use --list-stream-profiles to see available profiles)
-    #[arg(short = 's', long = "stream-selection-profile", value_name = "PROFILE")]
-    pub stream_selection_profile: Option<String>,
-
-    /// List all available stream selection profiles
+    /// List all available stream selection profiles (use `config list-stream-profiles`)
     #[arg(long)]
     pub list_stream_profiles: bool,
 
-    /// Show detailed information about a specific stream selection profile
+    /// Show detailed information about a specific stream selection profile (use `config show-stream-profile`)
     #[arg(long, value_name = "PROFILE")]
     pub show_stream_profile: Option<String>,
 
-    /// Preview timestamp in seconds (for single frame image generation)
+    /// Preview timestamp in seconds (use `preview --time`)
     #[arg(long, value_name = "SECONDS")]
     pub preview_time: Option<f64>,
 
-    /// Preview time range in format "START-END" (for video segment encoding, e.g., "10-20")
+    /// Preview time range in format "START-END" (use `preview --range`)
     #[arg(long, value_name = "START-END")]
     pub preview_range: Option<String>,
 
-    /// Preview profile group to use (from config's preview_profiles section)
+    /// Preview profile group to use (use `preview --group`)
     #[arg(long, value_name = "NAME")]
     pub preview_profile: Option<String>,
 
-    /// List available preview profile groups
+    /// List available preview profile groups (use `config list-preview-profiles`)
     #[arg(long)]
     pub list_preview_profiles: bool,
 }
 
 impl CliArgs {
     pub fn get_log_level<'a>(&self, config_level: &'a str) -> &'a str {
-        if self.debug {
+        if self.global.debug {
             "debug"
         } else {
             // Use config level if debug flag is not set
@@ -124,86 +719,226 @@ impl CliArgs {
         }
     }
 
-    pub fn is_info_command(&self) -> bool {
-        self.list_profiles
+    /// Resolve the command to run, translating deprecated flat flags into
+    /// their subcommand equivalent.
+    ///
+    /// Returns `None` when nothing was requested (the caller prints help), and
+    /// a deprecation hint alongside commands that came from legacy flags.
+    pub fn resolve_command(&self) -> Result<Option<(Command, Option<&'static str>)>> {
+        let legacy = &self.legacy;
+
+        if let Some(command) = &self.command {
+            if legacy.is_used() {
+                return Err(crate::utils::Error::validation(
+                    "Deprecated flat flags cannot be combined with a subcommand; pass them after the subcommand instead"
+                        .to_string(),
+                ));
+            }
+            return Ok(Some((command.clone(), None)));
+        }
+
+        let config_command = if legacy.list_profiles {
+            Some((ConfigCommand::ListProfiles, "config list-profiles"))
+        } else if let Some(name) = &legacy.show_profile {
+            Some((
+                ConfigCommand::ShowProfile { name: name.clone() },
+                "config show-profile <PROFILE>",
+            ))
+        } else if legacy.list_stream_profiles {
+            Some((
+                ConfigCommand::ListStreamProfiles,
+                "config list-stream-profiles",
+            ))
+        } else if let Some(name) = &legacy.show_stream_profile {
+            Some((
+                ConfigCommand::ShowStreamProfile { name: name.clone() },
+                "config show-stream-profile <PROFILE>",
+            ))
+        } else if legacy.list_preview_profiles {
+            Some((
+                ConfigCommand::ListPreviewProfiles,
+                "config list-preview-profiles",
+            ))
+        } else if legacy.validate_config {
+            Some((ConfigCommand::Validate, "config validate"))
+        } else {
+            None
+        };
+
+        if let Some((command, replacement)) = config_command {
+            return Ok(Some((Command::Config { command }, Some(replacement))));
+        }
+
+        if legacy.preview_time.is_some() || legacy.preview_range.is_some() {
+            let input = legacy.encode.input.first().cloned().ok_or_else(|| {
+                crate::utils::Error::validation(
+                    "At least one input path is required for preview mode".to_string(),
+                )
+            })?;
+            let preview = PreviewArgs {
+                input,
+                output: legacy.encode.output.clone(),
+                profile: legacy.encode.profile.clone(),
+                time: legacy.preview_time.into_iter().collect(),
+                range: legacy.preview_range.clone(),
+                group: legacy.preview_profile.clone(),
+                seek: "accurate".to_string(),
+                video_stream: legacy.encode.video_stream,
+            };
+            return Ok(Some((Command::Preview(preview), Some("preview"))));
+        }
+
+        if !legacy.encode.input.is_empty() {
+            return Ok(Some((
+                Command::Encode(legacy.encode.clone()),
+                Some("encode"),
+            )));
+        }
+
+        Ok(None)
+    }
+}
+
+impl LegacyArgs {
+    /// Whether any legacy flag that selects or feeds a command was given.
+    fn is_used(&self) -> bool {
+        !self.encode.input.is_empty()
+            || self.encode.output.is_some()
+            || self.list_profiles
             || self.show_profile.is_some()
+            || self.validate_config
             || self.list_stream_profiles
             || self.show_stream_profile.is_some()
+            || self.preview_time.is_some()
+            || self.preview_range.is_some()
+            || self.preview_profile.is_some()
             || self.list_preview_profiles
-            || self.validate_config
-    }
-
-    pub fn should_encode(&self) -> bool {
-        !self.is_info_command() && !self.input.is_empty() && !self.should_preview()
-    }
-
-    pub fn should_preview(&self) -> bool {
-        !self.input.is_empty() && (self.preview_time.is_some() || self.preview_range.is_some())
     }
+}
 
+impl Command {
     pub fn validate(&self) -> Result<()> {
-        // Validate preview mode parameters
-        if self.preview_time.is_some() || self.preview_range.is_some() {
-            if self.input.is_empty() {
-                return Err(crate::utils::Error::validation(
-                    "At least one input path is required for preview mode".to_string(),
-                ));
-            }
-
-            // Cannot specify both
-            if self.preview_time.is_some() && self.preview_range.is_some() {
-                return Err(crate::utils::Error::validation(
-                    "Cannot use both --preview-time and --preview-range simultaneously".to_string(),
-                ));
+        match self {
+            Self::Encode(args) => args.validate(),
+            Self::Preview(args) => args.validate(),
+            Self::Analyze(args) => validate_inputs_exist(&args.input),
+            Self::Screenshots(args) => {
+                if !args.input.is_file() {
+                    return Err(crate::utils::Error::validation(format!(
+                        "Screenshots require a single video file as input: {}",
+                        args.input.display()
+                    )));
+                }
+                ScreenshotPosition::parse_list(&args.at).map(|_| ())
             }
-
-            // Validate preview_time is positive
-            if let Some(time) = self.preview_time {
-                if time < 0.0 {
+            Self::Shootout(args) => {
+                if !args.input.is_file() {
+                    return Err(crate::utils::Error::validation(format!(
+                        "A shootout needs a single video file as input: {}",
+                        args.input.display()
+                    )));
+                }
+                if args.profiles.len() < 2 {
                     return Err(crate::utils::Error::validation(
-                        "Preview time must be a positive number".to_string(),
+                        "A shootout needs at least two --profiles".to_string(),
                     ));
                 }
+                if args.segments == 0 || args.length <= 0.0 {
+                    return Err(crate::utils::Error::validation(
+                        "--segments and --length must be greater than 0".to_string(),
+                    ));
+                }
+                Ok(())
             }
-
-            // Validate preview_range format
-            if let Some(range) = &self.preview_range {
-                self.validate_preview_range(range)?;
-            }
-
-            // Validate all input paths exist
-            for input in &self.input {
-                if !input.exists() {
+            Self::Clean { dirs, .. } => {
+                if let Some(dir) = dirs.iter().find(|dir| !dir.is_dir()) {
                     return Err(crate::utils::Error::validation(format!(
-                        "Input path does not exist: {}",
-                        input.display()
+                        "Not a directory: {}",
+                        dir.display()
                     )));
                 }
+                Ok(())
             }
-        }
-
-        // Only validate input if we're encoding
-        if self.should_encode() {
-            if self.input.is_empty() {
-                return Err(crate::utils::Error::validation(
-                    "At least one input path is required for encoding".to_string(),
-                ));
+            Self::GenerateTest {
+                duration, output, ..
+            } => {
+                if *duration <= 0.0 {
+                    return Err(crate::utils::Error::validation(
+                        "Test source duration must be greater than 0".to_string(),
+                    ));
+                }
+                if output
+                    .as_ref()
+                    .is_some_and(|output| output.extension().is_none_or(|ext| ext != "mkv"))
+                {
+                    return Err(crate::utils::Error::validation(
+                        "Test sources are written as Matroska (.mkv)".to_string(),
+                    ));
+                }
+                Ok(())
             }
-
-            // Validate all input paths exist
-            for input in &self.input {
-                if !input.exists() {
-                    return Err(crate::utils::Error::validation(format!(
-                        "Input path does not exist: {}",
-                        input.display()
-                    )));
+            Self::Selftest { duration, .. } => {
+                if *duration < 4.0 {
+                    return Err(crate::utils::Error::validation(
+                        "Self-test duration must be at least 4 seconds".to_string(),
+                    ));
                 }
+                Ok(())
             }
+            Self::Queue {
+                command: QueueCommand::Add(args),
+            } => {
+                if args.summary.is_some() {
+                    // Every job runs in its own worker, which would overwrite the file
+                    return Err(crate::utils::Error::validation(
+                        "--summary cannot be used with queued jobs".to_string(),
+                    ));
+                }
+                if args.time_budget.is_some() {
+                    // Every job is a batch of one, so there is nothing to plan across
+                    return Err(crate::utils::Error::validation(
+                        "--time-budget cannot be used with queued jobs".to_string(),
+                    ));
+                }
+                args.validate()
+            }
+            Self::Queue {
+                command: QueueCommand::Run { jobs, schedule, .. },
+            } => {
+                if *jobs == 0 {
+                    return Err(crate::utils::Error::validation(
+                        "--jobs must be at least 1".to_string(),
+                    ));
+                }
+                if let Some(schedule) = schedule {
+                    TimeWindow::parse(schedule)?;
+                }
+                Ok(())
+            }
+            Self::Logs {
+                command: LogsCommand::Grep { conditions },
+            } => conditions
+                .iter()
+                .try_for_each(|condition| ReportCondition::parse(condition).map(|_| ())),
+            Self::Stats { since, .. } => since
+                .as_deref()
+                .map_or(Ok(()), |date| parse_date(date).map(|_| ())),
+            Self::Tools { .. } | Self::Config { .. } | Self::Queue { .. } | Self::Logs { .. } => {
+                Ok(())
+            }
+        }
+    }
+}
+
+impl EncodeArgs {
+    pub fn validate(&self) -> Result<()> {
+        if self.input.is_empty() {
+            return Err(crate::utils::Error::validation(
+                "At least one input path is required for encoding".to_string(),
+            ));
         }
 
-        // For validate-config command, we don't need to check if files exist
-        // since the loading logic will handle fallbacks and discovery appropriately
-        // Config validation is now handled by the discovery mechanism
+        validate_inputs_exist(&self.input)?;
 
         // Validate encoding mode
         if !["crf", "abr", "cbr"].contains(&self.mode.as_str()) {
@@ -213,12 +948,298 @@ impl CliArgs {
             )));
         }
 
+        if self.is_video_copy() && (self.denoise || self.deinterlace) {
+            return Err(crate::utils::Error::validation(
+                "--denoise and --deinterlace require re-encoding and cannot be used with --video copy or --normalize-audio"
+                    .to_string(),
+            ));
+        }
+
+        if self.is_video_copy() && self.dv_hdr10_fallback {
+            return Err(crate::utils::Error::validation(
+                "--dv-hdr10-fallback requires re-encoding and cannot be used with --video copy or --normalize-audio"
+                    .to_string(),
+            ));
+        }
+
+        if self.is_video_copy() && self.dv5_convert {
+            return Err(crate::utils::Error::validation(
+                "--dv5-convert requires re-encoding and cannot be used with --video copy or --normalize-audio"
+                    .to_string(),
+            ));
+        }
+
+        if self.start.is_some() || self.end.is_some() {
+            if self.is_video_copy() {
+                return Err(crate::utils::Error::validation(
+                    "--start and --end require re-encoding and cannot be used with --video copy or --normalize-audio"
+                        .to_string(),
+                ));
+            }
+            if self.verify_streams {
+                return Err(crate::utils::Error::validation(
+                    "--verify-streams compares whole streams and cannot be used with --start or --end"
+                        .to_string(),
+                ));
+            }
+        }
+
+        self.schedule_window()?;
+
+        if self.time_budget.is_some() && (self.is_video_copy() || self.ladder.is_some()) {
+            return Err(crate::utils::Error::validation(
+                "--time-budget cannot be used with --video copy, --normalize-audio or --ladder"
+                    .to_string(),
+            ));
+        }
+        self.time_budget()?;
+
+        if let Some(summary) = &self.summary {
+            crate::batch::SummaryFormat::from_path(summary)?;
+        }
+
+        if self.crop_samples == Some(0) {
+            return Err(crate::utils::Error::validation(
+                "--crop-samples must be at least 1".to_string(),
+            ));
+        }
+        if self.crop_threshold.is_some_and(|limit| limit > 255) {
+            return Err(crate::utils::Error::validation(
+                "--crop-threshold must be between 0 and 255".to_string(),
+            ));
+        }
+        if self
+            .crop_min_confidence
+            .is_some_and(|confidence| !(0.0..=100.0).contains(&confidence))
+        {
+            return Err(crate::utils::Error::validation(
+                "--crop-min-confidence must be between 0 and 100".to_string(),
+            ));
+        }
+
         // Note: Profile validation is performed later after config is loaded
         // since profiles are defined dynamically in the configuration file
 
         Ok(())
     }
 
+    /// File discovery configured by `--follow-symlinks` and `--allow-duplicates`.
+    pub fn file_discovery(&self) -> FileDiscovery {
+        FileDiscovery::new()
+            .with_follow_symlinks(self.follow_symlinks)
+            .with_allow_duplicates(self.allow_duplicates)
+    }
+
+    /// Whether the video stream is copied instead of re-encoded.
+    pub fn is_video_copy(&self) -> bool {
+        self.video == "copy" || self.normalize_audio
+    }
+
+    /// The parsed `--time-budget`, if one was given.
+    pub fn time_budget(&self) -> Result<Option<std::time::Duration>> {
+        self.time_budget
+            .as_deref()
+            .map(crate::planning::parse_budget)
+            .transpose()
+    }
+
+    /// The parsed `--schedule` window, if one was given.
+    pub fn schedule_window(&self) -> Result<Option<TimeWindow>> {
+        self.schedule.as_deref().map(TimeWindow::parse).transpose()
+    }
+
+    /// Rebuilds the `encode` command line for these options, e.g. to run a
+    /// queued job in a worker process.
+    pub fn to_cli_args(&self) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        for input in &self.input {
+            args.push("--input".into());
+            args.push(input.into());
+        }
+        if let Some(output) = &self.output {
+            args.push("--output".into());
+            args.push(output.into());
+        }
+        args.push("--profile".into());
+        args.push((&self.profile).into());
+        if let Some(title) = &self.title {
+            args.push("--title".into());
+            args.push(title.into());
+        }
+        args.push("--mode".into());
+        args.push((&self.mode).into());
+        if let Some(profile) = &self.stream_selection_profile {
+            args.push("--stream-selection-profile".into());
+            args.push(profile.into());
+        }
+        args.push("--video".into());
+        args.push((&self.video).into());
+        if let Some(position) = self.video_stream {
+            args.push("--video-stream".into());
+            args.push(position.to_string().into());
+        }
+        if let Some(start) = self.start {
+            args.push("--start".into());
+            args.push(start.to_string().into());
+        }
+        if let Some(end) = self.end {
+            args.push("--end".into());
+            args.push(end.to_string().into());
+        }
+        if let Some(device) = &self.device {
+            args.push("--device".into());
+            args.push(device.into());
+        }
+        if let Some(ladder) = &self.ladder {
+            args.push("--ladder".into());
+            args.push(ladder.into());
+        }
+        if let Some(budget) = &self.time_budget {
+            args.push("--time-budget".into());
+            args.push(budget.into());
+        }
+        if let Some(schedule) = &self.schedule {
+            args.push("--schedule".into());
+            args.push(schedule.into());
+        }
+        if let Some(summary) = &self.summary {
+            args.push("--summary".into());
+            args.push(summary.into());
+        }
+        if let Some(events_json) = &self.events_json {
+            args.push("--events-json".into());
+            args.push(events_json.into());
+        }
+        if let Some(playlist) = &self.playlist {
+            args.push("--playlist".into());
+            args.push(playlist.into());
+        }
+        if let Some(samples) = self.crop_samples {
+            args.push("--crop-samples".into());
+            args.push(samples.to_string().into());
+        }
+        if let Some(threshold) = self.crop_threshold {
+            args.push("--crop-threshold".into());
+            args.push(threshold.to_string().into());
+        }
+        if let Some(confidence) = self.crop_min_confidence {
+            args.push("--crop-min-confidence".into());
+            args.push(confidence.to_string().into());
+        }
+
+        let flags = [
+            ("--overwrite", self.overwrite),
+            ("--denoise", self.denoise),
+            ("--deinterlace", self.deinterlace),
+            ("--normalize-audio", self.normalize_audio),
+            ("--verify-streams", self.verify_streams),
+            ("--error-resilience", self.error_resilience),
+            ("--no-analysis-cache", self.no_analysis_cache),
+            ("--dv-hdr10-fallback", self.dv_hdr10_fallback),
+            ("--strict-metadata", self.strict_metadata),
+            ("--explain", self.explain),
+            ("--dv5-convert", self.dv5_convert),
+            ("--pause-outside-schedule", self.pause_outside_schedule),
+            ("--smart", self.smart),
+            ("--pipeline", self.pipeline),
+            ("--follow-symlinks", self.follow_symlinks),
+            ("--allow-duplicates", self.allow_duplicates),
+            ("--re-encode-own-output", self.re_encode_own_output),
+            ("--debug-bundle", self.debug_bundle),
+            ("--keep-temp", self.keep_temp),
+            ("--crop-report", self.crop_report),
+            ("--thumbnails", self.thumbnails),
+            ("--quality-check", self.quality_check),
+        ];
+        for (flag, enabled) in flags {
+            if enabled {
+                args.push(flag.into());
+            }
+        }
+
+        args
+    }
+
+    /// Options for encoding one input file. `output_path` is the path picked
+    /// for this file, which only counts as explicit if it is the `-o` path itself.
+    pub fn to_encoding_options(&self, input_path: &Path, output_path: &Path) -> EncodingOptions {
+        EncodingOptions {
+            input_path: input_path.to_path_buf(),
+            output_path: output_path.to_path_buf(),
+            profile_name: self.profile.clone(),
+            title: self.title.clone(),
+            mode: self.mode.clone(),
+            crop: None,
+            denoise: self.denoise,
+            deinterlace: self.deinterlace,
+            stream_selection_profile: self.stream_selection_profile.clone(),
+            video_copy: self.video == "copy",
+            video_stream: self.video_stream,
+            normalize_audio: self.normalize_audio,
+            smart: self.smart,
+            verify_streams: self.verify_streams,
+            error_resilience: self.error_resilience,
+            use_analysis_cache: !self.no_analysis_cache,
+            dv_hdr10_fallback: self.dv_hdr10_fallback,
+            strict_metadata: self.strict_metadata,
+            explain: self.explain,
+            dv5_convert: self.dv5_convert,
+            device: self.device.clone(),
+            pause_window: self
+                .schedule
+                .clone()
+                .filter(|_| self.pause_outside_schedule),
+            scale_height: None,
+            tonemap: false,
+            thumbnails: self.thumbnails,
+            quality_check: self.quality_check,
+            preset: None,
+            crop_detection: CropDetectionOverrides {
+                sample_count: self.crop_samples,
+                limit: self.crop_threshold,
+                min_confidence: self.crop_min_confidence,
+                report: self.crop_report,
+            },
+            explicit_output: self.output.as_deref() == Some(output_path),
+            overwrite: self.overwrite,
+            keep_temp: self.keep_temp,
+            start: self.start,
+            end: self.end,
+        }
+    }
+}
+
+impl PreviewArgs {
+    pub fn validate(&self) -> Result<()> {
+        // Must specify exactly one of time/range
+        if !self.time.is_empty() && self.range.is_some() {
+            return Err(crate::utils::Error::validation(
+                "Cannot use both --time and --range simultaneously".to_string(),
+            ));
+        }
+        if self.time.is_empty() && self.range.is_none() {
+            return Err(crate::utils::Error::validation(
+                "Preview mode requires either --time or --range".to_string(),
+            ));
+        }
+
+        // Validate time is positive
+        for &time in &self.time {
+            if time < 0.0 {
+                return Err(crate::utils::Error::validation(
+                    "Preview time must be a positive number".to_string(),
+                ));
+            }
+        }
+
+        // Validate range format
+        if let Some(range) = &self.range {
+            self.validate_preview_range(range)?;
+        }
+
+        validate_inputs_exist(std::slice::from_ref(&self.input))
+    }
+
     fn validate_preview_range(&self, range: &str) -> Result<()> {
         let parts: Vec<&str> = range.split('-').collect();
         if parts.len() != 2 {
@@ -257,7 +1278,7 @@ impl CliArgs {
     }
 
     pub fn parse_preview_range(&self) -> Option<(f64, f64)> {
-        self.preview_range.as_ref().and_then(|range| {
+        self.range.as_ref().and_then(|range| {
             let parts: Vec<&str> = range.split('-').collect();
             if parts.len() == 2 {
                 if let (Ok(start), Ok(end)) = (parts[0].parse::<f64>(), parts[1].parse::<f64>()) {
@@ -268,3 +1289,194 @@ impl CliArgs {
         })
     }
 }
+
+fn validate_inputs_exist(inputs: &[PathBuf]) -> Result<()> {
+    for input in inputs {
+        if !input.exists() {
+            return Err(crate::utils::Error::validation(format!(
+                "Input path does not exist: {}",
+                input.display()
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(argv: &[&str]) -> Option<(Command, Option<&'static str>)> {
+        CliArgs::try_parse_from(argv)
+            .unwrap()
+            .resolve_command()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_encode_subcommand() {
+        let (command, deprecation) = resolve(&[
+            "ffmpeg-encoder",
+            "encode",
+            "-i",
+            "a.mkv",
+            "-p",
+            "anime",
+            "-m",
+            "crf",
+        ])
+        .unwrap();
+        assert!(deprecation.is_none());
+        match command {
+            Command::Encode(args) => {
+                assert_eq!(args.input, vec![PathBuf::from("a.mkv")]);
+                assert_eq!(args.profile, "anime");
+                assert_eq!(args.mode, "crf");
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_legacy_flags_map_to_subcommands() {
+        let (command, deprecation) = resolve(&["ffmpeg-encoder", "-i", "a.mkv"]).unwrap();
+        assert!(matches!(command, Command::Encode(_)));
+        assert_eq!(deprecation, Some("encode"));
+
+        let (command, _) =
+            resolve(&["ffmpeg-encoder", "-i", "a.mkv", "--preview-time", "60"]).unwrap();
+        match command {
+            Command::Preview(args) => {
+                assert_eq!(args.input, PathBuf::from("a.mkv"));
+                assert_eq!(args.time, vec![60.0]);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let (command, deprecation) = resolve(&["ffmpeg-encoder", "--list-profiles"]).unwrap();
+        assert!(matches!(
+            command,
+            Command::Config {
+                command: ConfigCommand::ListProfiles
+            }
+        ));
+        assert_eq!(deprecation, Some("config list-profiles"));
+    }
+
+    #[test]
+    fn test_no_command_resolves_to_none() {
+        assert!(resolve(&["ffmpeg-encoder"]).is_none());
+        assert!(resolve(&["ffmpeg-encoder", "--debug"]).is_none());
+    }
+
+    #[test]
+    fn test_global_options_after_subcommand() {
+        let args = CliArgs::try_parse_from([
+            "ffmpeg-encoder",
+            "config",
+            "validate",
+            "--config",
+            "custom.yaml",
+            "--debug",
+        ])
+        .unwrap();
+        assert_eq!(args.global.config, Some(PathBuf::from("custom.yaml")));
+        assert_eq!(args.get_log_level("info"), "debug");
+
+        let args = CliArgs::try_parse_from([
+            "ffmpeg-encoder",
+            "--config",
+            "custom.yaml",
+            "config",
+            "validate",
+        ])
+        .unwrap();
+        assert_eq!(args.global.config, Some(PathBuf::from("custom.yaml")));
+        assert!(args.resolve_command().unwrap().is_some());
+
+        let args = CliArgs::try_parse_from(["ffmpeg-encoder", "-i", "a.mkv", "config", "validate"])
+            .unwrap();
+        assert!(args.resolve_command().is_err());
+    }
+
+    #[test]
+    fn test_preview_args_validation() {
+        let preview = PreviewArgs {
+            input: PathBuf::from("Cargo.toml"),
+            output: None,
+            profile: "auto".to_string(),
+            time: Vec::new(),
+            range: Some("10-20".to_string()),
+            group: None,
+            seek: "accurate".to_string(),
+            video_stream: None,
+        };
+        assert!(preview.validate().is_ok());
+        assert_eq!(preview.parse_preview_range(), Some((10.0, 20.0)));
+
+        let invalid = PreviewArgs {
+            range: Some("20-10".to_string()),
+            ..preview.clone()
+        };
+        assert!(invalid.validate().is_err());
+
+        let both = PreviewArgs {
+            time: vec![5.0],
+            ..preview
+        };
+        assert!(both.validate().is_err());
+
+        let args = CliArgs::try_parse_from([
+            "ffmpeg-encoder",
+            "preview",
+            "-i",
+            "a.mkv",
+            "--time",
+            "60,300",
+            "--time",
+            "900",
+        ])
+        .unwrap();
+        match args.command {
+            Some(Command::Preview(preview)) => assert_eq!(preview.time, [60.0, 300.0, 900.0]),
+            other => panic!("unexpected command: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_encode_args_roundtrip_through_cli_args() {
+        let original = EncodeArgs {
+            input: vec![PathBuf::from("a b.mkv")],
+            output: Some(PathBuf::from("out.mkv")),
+            profile: "anime".to_string(),
+            title: Some("Title".to_string()),
+            mode: "crf".to_string(),
+            denoise: true,
+            stream_selection_profile: Some("english_only".to_string()),
+            verify_streams: true,
+            schedule: Some("23:00-07:00".to_string()),
+            pause_outside_schedule: true,
+            smart: true,
+            summary: Some(PathBuf::from("batch.csv")),
+            events_json: Some(PathBuf::from("events.jsonl")),
+            pipeline: true,
+            playlist: Some("00800.mpls".to_string()),
+            follow_symlinks: true,
+            ..EncodeArgs::default()
+        };
+
+        let mut argv: Vec<OsString> = vec!["ffmpeg-encoder".into(), "encode".into()];
+        argv.extend(original.to_cli_args());
+        let parsed = match CliArgs::try_parse_from(argv).unwrap().command {
+            Some(Command::Encode(args)) => args,
+            other => panic!("unexpected command: {:?}", other),
+        };
+
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", original));
+
+        let options = parsed.to_encoding_options(Path::new("a b.mkv"), Path::new("out.mkv"));
+        assert!(options.explicit_output && options.verify_streams);
+        assert_eq!(options.pause_window.as_deref(), Some("23:00-07:00"));
+        assert!(options.pause_window().unwrap().is_some());
+    }
+}
@@ -1,43 +1,794 @@
 use crate::{
-    cli::CliArgs,
-    config::{Config, PreviewProfileManager, ProfileManager, StreamSelectionProfileManager},
-    utils::Result,
+    analysis::policy_violations,
+    cli::{
+        AnalyzeArgs, CliArgs, Command, ConfigCommand, EncodeArgs, LogsCommand, QueueCommand,
+        ScreenshotsArgs, ShootoutArgs, ToolsCommand,
+    },
+    config::{
+        layers::ConfigLayer, BundleEntry, Config, ConflictResolution, HandBrakeImport,
+        PreviewProfileManager, ProfileBundle, ProfileManager, StreamSelectionProfileManager,
+    },
+    input::discover_inputs,
+    preview::{generate_screenshots, PreviewSeek, ScreenshotPosition},
+    progress::format_duration,
+    queue::{EncodingQueue, QueueRunner},
+    reports::{parse_date, EncodeReport, ProfileStats, ReportCondition, ReportLog},
+    schedule::TimeWindow,
+    selftest::{check_tools, SelfTest, SelfTestOptions},
+    shootout::Shootout,
+    testsignal::{TestSignal, TestSignalGenerator},
+    utils::{
+        filesystem::format_file_size,
+        find_video_files, generate_uuid_filename,
+        logging::{find_log, newest_log, LogTail},
+        sweep_on_startup, Error, FfmpegWrapper, Result, TempSweep,
+    },
+    ContentEncodingApproach, VenEngine,
 };
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use uuid::Uuid;
 
-pub async fn handle_commands(args: &CliArgs, config: &Config) -> Result<bool> {
-    // Handle info commands
-    if args.list_profiles {
-        list_profiles(config).await?;
-        return Ok(true);
+/// Run non-encoding commands. Returns `false` for `encode`/`preview`, which
+/// are handled by the binary.
+pub async fn handle_commands(command: &Command, args: &CliArgs, config: &Config) -> Result<bool> {
+    match command {
+        Command::Encode(_) | Command::Preview(_) => Ok(false),
+        Command::Analyze(analyze_args) => {
+            analyze_files(config, analyze_args).await?;
+            Ok(true)
+        }
+        Command::Screenshots(screenshots_args) => {
+            take_screenshots(config, screenshots_args).await?;
+            Ok(true)
+        }
+        Command::Shootout(shootout_args) => {
+            run_shootout(config, shootout_args).await?;
+            Ok(true)
+        }
+        Command::Tools {
+            command: ToolsCommand::Check,
+        } => {
+            tools_check(config).await?;
+            Ok(true)
+        }
+        Command::Config { command } => {
+            handle_config_command(command, args, config).await?;
+            Ok(true)
+        }
+        Command::Queue { command } => {
+            handle_queue_command(command, args, config).await?;
+            Ok(true)
+        }
+        Command::Logs { command } => {
+            handle_logs_command(command)?;
+            Ok(true)
+        }
+        Command::Stats { since, monthly } => {
+            print_profile_stats(since.as_deref(), *monthly)?;
+            Ok(true)
+        }
+        Command::Clean {
+            dirs,
+            older_than,
+            dry_run,
+        } => {
+            clean_temp_files(config, dirs, *older_than, *dry_run)?;
+            Ok(true)
+        }
+        Command::Selftest {
+            hdr,
+            hdr10plus,
+            dv81,
+            duration,
+            keep,
+            profile,
+        } => {
+            let options = SelfTestOptions {
+                hdr: *hdr,
+                hdr10plus: *hdr10plus,
+                dv81: *dv81,
+                duration: *duration,
+                keep_files: *keep,
+                profile: profile.clone(),
+            };
+            run_selftest(config, options).await?;
+            Ok(true)
+        }
+        Command::GenerateTest {
+            hdr10,
+            hdr10plus,
+            dv81,
+            duration,
+            output,
+        } => {
+            let signal = TestSignal {
+                hdr10: *hdr10,
+                hdr10plus: *hdr10plus,
+                dv81: *dv81,
+                duration: *duration,
+            };
+            generate_test_source(config, &signal, output.as_deref()).await?;
+            Ok(true)
+        }
+    }
+}
+
+async fn handle_config_command(
+    command: &ConfigCommand,
+    args: &CliArgs,
+    config: &Config,
+) -> Result<()> {
+    match command {
+        ConfigCommand::ListProfiles => list_profiles(config).await,
+        ConfigCommand::ShowProfile { name } => show_profile(config, name).await,
+        ConfigCommand::ListStreamProfiles => list_stream_profiles(config).await,
+        ConfigCommand::ShowStreamProfile { name } => show_stream_profile(config, name).await,
+        ConfigCommand::ListPreviewProfiles => list_preview_profiles(config).await,
+        ConfigCommand::Validate => validate_config(args.global.config.as_deref()).await,
+        ConfigCommand::Show { origin } => show_config(args.global.config.as_deref(), *origin),
+        ConfigCommand::Export { profiles, output } => {
+            export_profiles(config, profiles, output.as_deref())
+        }
+        ConfigCommand::Import {
+            bundle,
+            on_conflict,
+        } => import_profiles(config, bundle, args.global.config.as_deref(), on_conflict),
+        ConfigCommand::ImportHandbrake { presets, output } => {
+            import_handbrake(presets, output.as_deref())
+        }
+    }
+}
+
+async fn handle_queue_command(
+    command: &QueueCommand,
+    args: &CliArgs,
+    config: &Config,
+) -> Result<()> {
+    let mut queue = EncodingQueue::open_default()?;
+
+    match command {
+        QueueCommand::Add(encode_args) => queue_add(&mut queue, encode_args, config),
+        QueueCommand::List => {
+            queue_list(&queue);
+            Ok(())
+        }
+        QueueCommand::Remove { id } => {
+            let job = queue.remove(id)?;
+            queue.save()?;
+            println!(
+                "Removed job {} ({})",
+                job.id,
+                job.input()
+                    .map(|p| p.display().to_string())
+                    .unwrap_or_default()
+            );
+            Ok(())
+        }
+        QueueCommand::Clear { finished } => {
+            let removed = queue.clear(*finished);
+            queue.save()?;
+            println!("Removed {} job(s) from the queue", removed);
+            Ok(())
+        }
+        QueueCommand::Run {
+            jobs,
+            schedule,
+            pause_outside_schedule,
+        } => {
+            sweep_on_startup(config);
+            let mut runner = QueueRunner::new(args.global.config.clone(), *jobs)?;
+            if let Some(schedule) = schedule {
+                runner =
+                    runner.with_schedule(TimeWindow::parse(schedule)?, *pause_outside_schedule);
+            }
+            let (completed, failed) = runner.run(&mut queue).await?;
+            println!("Queue finished: {} completed, {} failed", completed, failed);
+            if failed > 0 && completed == 0 {
+                return Err(Error::encoding("All queued jobs failed".to_string()));
+            }
+            Ok(())
+        }
+    }
+}
+
+fn queue_add(queue: &mut EncodingQueue, encode_args: &EncodeArgs, config: &Config) -> Result<()> {
+    if encode_args.profile != "auto" && !config.profiles.contains_key(&encode_args.profile) {
+        return Err(Error::profile(format!(
+            "Profile '{}' not found",
+            encode_args.profile
+        )));
+    }
+
+    // A Blu-ray folder becomes one job; its playlist is picked when the job runs
+    let mut discovery = encode_args.file_discovery();
+    let mut files = Vec::new();
+    for input in &encode_args.input {
+        files.extend(
+            discover_inputs(input, encode_args.playlist.as_deref(), &mut discovery)?
+                .iter()
+                .map(|source| source.path().to_path_buf()),
+        );
+    }
+
+    // Jobs may run from another working directory, so store absolute paths
+    let cwd = std::env::current_dir()?;
+    let output = encode_args.output.as_ref().map(|output| cwd.join(output));
+    let events_json = encode_args.events_json.as_ref().map(|path| cwd.join(path));
+
+    // One job per file; an explicit output for several files only names the directory
+    let file_count = files.len();
+    for file in files {
+        let file = file.canonicalize()?;
+        let output = match &output {
+            Some(output) if file_count > 1 => Some(generate_uuid_filename(
+                &file,
+                Some(output.parent().unwrap_or(output)),
+            )),
+            other => other.clone(),
+        };
+        let job_args = EncodeArgs {
+            input: vec![file],
+            output,
+            events_json: events_json.clone(),
+            ..encode_args.clone()
+        };
+        let job = queue.add(job_args);
+        println!(
+            "Queued {}: {}",
+            job.id,
+            job.input()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        );
+    }
+
+    queue.save()?;
+    println!(
+        "{} job(s) added ({} in queue at {})",
+        file_count,
+        queue.jobs().len(),
+        queue.path().display()
+    );
+    Ok(())
+}
+
+fn queue_list(queue: &EncodingQueue) {
+    if queue.jobs().is_empty() {
+        println!("The queue is empty ({})", queue.path().display());
+        return;
+    }
+
+    println!("Encoding queue ({}):", queue.path().display());
+    println!("{:-<80}", "");
+    println!("{:<10} {:<10} {:<16} INPUT", "ID", "STATUS", "PROFILE");
+    println!("{:-<80}", "");
+    for job in queue.jobs() {
+        println!(
+            "{:<10} {:<10} {:<16} {}",
+            job.id,
+            job.status.as_str(),
+            job.args.profile,
+            job.input()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        );
+        if let Some(error) = &job.error {
+            println!("{:<10} {}", "", error);
+        }
+    }
+}
+
+fn handle_logs_command(command: &LogsCommand) -> Result<()> {
+    let reports = ReportLog::open_default()?;
+
+    match command {
+        LogsCommand::List { limit } => {
+            print_reports(
+                &reports,
+                reports.newest_first().take(*limit),
+                "No encode reports found",
+            );
+            Ok(())
+        }
+        LogsCommand::Show { encode } => {
+            let report = reports.find(encode).ok_or_else(|| {
+                Error::validation(format!(
+                    "No encode report matches '{}' (see `logs list`)",
+                    encode
+                ))
+            })?;
+            show_report(report);
+            Ok(())
+        }
+        LogsCommand::Grep { conditions } => {
+            let conditions = conditions
+                .iter()
+                .map(|condition| ReportCondition::parse(condition))
+                .collect::<Result<Vec<_>>>()?;
+            print_reports(
+                &reports,
+                reports.grep(&conditions),
+                "No encode matches the conditions",
+            );
+            Ok(())
+        }
+        LogsCommand::Tail {
+            output,
+            lines,
+            idle_timeout,
+        } => tail_log(output, *lines, Duration::from_secs(idle_timeout * 60)),
+    }
+}
+
+/// Prints the last `lines` lines of the log of `target`, then what is appended
+/// to it until the encode finishes (successfully or not), or nothing was
+/// appended for `idle_timeout` (zero waits forever). For a directory this goes
+/// on until interrupted, moving to each newer log that appears.
+fn tail_log(target: &Path, lines: usize, idle_timeout: Duration) -> Result<()> {
+    const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+    let open = |log: &Path, lines: usize| -> Result<LogTail> {
+        let (tail, last) = LogTail::open(log, lines)?;
+        println!("==> {} <==", log.display());
+        last.iter().for_each(|line| println!("{}", line));
+        Ok(tail)
+    };
+
+    let mut tail = open(&find_log(target)?, lines)?;
+    loop {
+        for line in tail.poll()? {
+            println!("{}", line);
+        }
+        if target.is_dir() {
+            if let Some(newer) = newest_log(target)?.filter(|log| log != tail.path()) {
+                println!();
+                tail = open(&newer, usize::MAX)?;
+                continue;
+            }
+        } else if tail.finished() {
+            return Ok(());
+        } else if !idle_timeout.is_zero() && tail.idle() >= idle_timeout {
+            println!(
+                "==> No new lines for {}, stopping (the encode may have been killed) <==",
+                format_duration(idle_timeout)
+            );
+            return Ok(());
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn print_reports<'a>(
+    reports: &ReportLog,
+    listed: impl Iterator<Item = (usize, &'a EncodeReport)>,
+    none_found: &str,
+) {
+    let mut listed = listed.peekable();
+    if listed.peek().is_none() {
+        println!("{} ({})", none_found, reports.path().display());
+        return;
+    }
+
+    println!("Encode reports ({}):", reports.path().display());
+    println!("{:-<100}", "");
+    println!(
+        "{:<5} {:<17} {:<12} {:<16} {:>7}  {:<12} INPUT",
+        "#", "FINISHED", "STATUS", "PROFILE", "SAVED", "QUALITY"
+    );
+    println!("{:-<100}", "");
+    for (number, report) in listed {
+        let file = &report.file;
+        println!(
+            "{:<5} {:<17} {:<12} {:<16} {:>7}  {:<12} {}",
+            number,
+            report.finished(),
+            file.status.as_str(),
+            file.profile.as_deref().unwrap_or("-"),
+            file.saved_percent
+                .map(|saved| format!("{:.1}%", saved))
+                .unwrap_or_else(|| "-".to_string()),
+            report
+                .quality
+                .first()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "-".to_string()),
+            file.input.display()
+        );
+    }
+}
+
+fn print_profile_stats(since: Option<&str>, monthly: bool) -> Result<()> {
+    let since = since.map(parse_date).transpose()?;
+    let reports = ReportLog::open_default()?;
+    let stats = reports.profile_stats(since, monthly);
+    if stats.is_empty() {
+        println!("No encode reports found ({})", reports.path().display());
+        return Ok(());
+    }
+
+    let average = |value: Option<f64>, precision: usize, unit: &str| {
+        value
+            .map(|value| format!("{:.*}{}", precision, value, unit))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let row = |stats: &ProfileStats| {
+        let profile = match &stats.month {
+            Some(month) => format!("{:<20} {:<8}", stats.profile, month),
+            None => format!("{:<20}", stats.profile),
+        };
+        format!(
+            "{} {:>7} {:>6} {:>8} {:>7} {:>7} {:>8.1}",
+            profile,
+            stats.encodes,
+            stats.failed,
+            average(stats.saved_percent, 1, "%"),
+            average(stats.vmaf, 2, ""),
+            average(stats.fps, 1, ""),
+            stats.encoding_seconds / 3600.0
+        )
+    };
+
+    println!(
+        "Encode statistics per profile ({}):",
+        reports.path().display()
+    );
+    println!("{:-<90}", "");
+    let profile = if monthly {
+        format!("{:<20} {:<8}", "PROFILE", "MONTH")
+    } else {
+        format!("{:<20}", "PROFILE")
+    };
+    println!(
+        "{} {:>7} {:>6} {:>8} {:>7} {:>7} {:>8}",
+        profile, "ENCODES", "FAILED", "SAVED", "VMAF", "FPS", "HOURS"
+    );
+    println!("{:-<90}", "");
+    for stats in &stats {
+        println!("{}", row(stats));
+    }
+    Ok(())
+}
+
+fn show_report(report: &EncodeReport) {
+    let file = &report.file;
+    println!("Encode report: {}", file.input.display());
+    println!("{:-<80}", "");
+    println!("Finished: {}", report.finished());
+    println!("Status: {}", file.status.as_str());
+    if let Some(output) = &file.output {
+        println!("Output: {}", output.display());
+    }
+    if let Some(profile) = &file.profile {
+        println!("Profile: {}", profile);
+    }
+    if let (Some(original), Some(new)) = (file.original_size, file.new_size) {
+        println!(
+            "Size: {} -> {}{}",
+            format_file_size(original),
+            format_file_size(new),
+            file.saved_percent
+                .map(|saved| format!(" ({:.1}% saved)", saved))
+                .unwrap_or_default()
+        );
+    }
+    println!(
+        "Time: {}{}",
+        format_duration(Duration::from_secs_f64(file.duration_seconds)),
+        file.average_fps
+            .map(|fps| format!(" ({:.1} fps)", fps))
+            .unwrap_or_default()
+    );
+    if !report.quality.is_empty() {
+        println!("Quality: {}", report.quality_summary());
+    }
+    if let Some(profile) = report.hdr10plus_profile {
+        println!(
+            "HDR10+: {}{}",
+            profile,
+            if profile.needs_dhdr10_opt() {
+                " (--dhdr10-opt)"
+            } else {
+                ""
+            }
+        );
+    }
+    if let Some(error) = &file.error {
+        println!("Error: {}", error);
+    }
+    if !report.decisions.is_empty() {
+        println!();
+        println!("Decisions:");
+        for decision in &report.decisions {
+            println!("  {}", decision);
+        }
+    }
+
+    let Some(log) = &report.log else {
+        return;
+    };
+    println!();
+    match std::fs::read_to_string(log) {
+        Ok(contents) => {
+            println!("Log ({}):", log.display());
+            println!("{:-<80}", "");
+            print!("{}", contents);
+        }
+        Err(e) => println!("Log {} is not readable: {}", log.display(), e),
+    }
+}
+
+async fn analyze_files(config: &Config, args: &AnalyzeArgs) -> Result<()> {
+    let engine = VenEngine::new(config.clone());
+
+    let mut files = Vec::new();
+    for input in &args.input {
+        files.extend(find_video_files(input)?);
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        let report = engine.analyze(file).await?;
+        let metadata = &report.metadata;
+        let analysis = &report.content;
+
+        println!("Analysis: {}", file.display());
+        println!("{:-<80}", "");
+        println!(
+            "Video: {} {}x{} @ {:.3} fps, {:.1}s",
+            metadata.codec.as_deref().unwrap_or("unknown"),
+            metadata.width,
+            metadata.height,
+            metadata.fps,
+            metadata.duration
+        );
+        if let Some(bitrate) = metadata.bitrate {
+            println!("Bitrate: {} kbps", bitrate);
+        }
+        println!(
+            "Approach: {}",
+            match &analysis.recommended_approach {
+                ContentEncodingApproach::SDR => "SDR".to_string(),
+                ContentEncodingApproach::HDR(hdr) => format!("HDR ({:?})", hdr.metadata.format),
+                ContentEncodingApproach::DolbyVision(dv) => {
+                    format!("Dolby Vision (profile {})", dv.profile.as_str())
+                }
+                ContentEncodingApproach::DolbyVisionWithHDR10Plus(dv, _) => {
+                    format!("Dolby Vision (profile {}) + HDR10+", dv.profile.as_str())
+                }
+            }
+        );
+        println!(
+            "Adjustments: CRF {:+.1}, bitrate x{:.2}",
+            analysis.encoding_adjustments.crf_adjustment,
+            analysis.encoding_adjustments.bitrate_multiplier
+        );
+
+        match &report.crop {
+            Some(crop) => match &crop.crop_values {
+                Some(values) => println!(
+                    "Crop: {} ({:.1}% pixels removed)",
+                    values.to_ffmpeg_string(),
+                    crop.pixel_change_percent
+                ),
+                None => println!("Crop: none ({})", crop.detection_method),
+            },
+            None => println!("Crop: detection disabled"),
+        }
+
+        let mapping = &report.streams;
+        println!(
+            "Streams: {} video, {} audio, {} subtitle, {} data, {} chapters",
+            mapping.video_streams.len(),
+            mapping.audio_streams.len(),
+            mapping.subtitle_streams.len(),
+            mapping.data_streams.len(),
+            mapping.chapters.len()
+        );
+        for stream in &mapping.audio_streams {
+            println!(
+                "  Audio #{:<3} {:<4} {:<6} {:>2} ch  {:<17} {}",
+                stream.index,
+                stream.language.as_deref().unwrap_or("und"),
+                stream.codec_name,
+                stream
+                    .channels
+                    .map_or_else(|| "?".to_string(), |c| c.to_string()),
+                stream
+                    .audio_role()
+                    .map(|r| r.to_string())
+                    .unwrap_or_default(),
+                stream.title.as_deref().unwrap_or("")
+            );
+        }
+        if mapping.has_closed_captions() {
+            println!(
+                "  Closed captions: EIA-608/708 in the video stream (streams.closed_captions: {})",
+                config.streams.closed_captions
+            );
+        }
+        if args.check {
+            let violations = policy_violations(
+                &config.check,
+                metadata,
+                &analysis.dolby_vision,
+                &mapping.audio_streams,
+            );
+            if violations.is_empty() {
+                println!("Check: passed");
+            } else {
+                println!("Check: FAILED");
+                for violation in &violations {
+                    println!("  - {}", violation);
+                }
+                failed += 1;
+            }
+        }
+        println!();
+    }
+
+    if failed > 0 {
+        return Err(Error::validation(format!(
+            "{} of {} file(s) failed the check policy",
+            failed,
+            files.len()
+        )));
+    }
+    Ok(())
+}
+
+async fn take_screenshots(config: &Config, args: &ScreenshotsArgs) -> Result<()> {
+    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+    ffmpeg
+        .check_availability()
+        .await
+        .map_err(|e| Error::ffmpeg(format!("FFmpeg tools not available: {}", e)))?;
+
+    let positions = ScreenshotPosition::parse_list(&args.at)?;
+    let screenshots = generate_screenshots(
+        &ffmpeg,
+        config,
+        &args.input,
+        args.output.as_deref(),
+        &positions,
+        PreviewSeek::from_string(&args.seek).unwrap_or_default(),
+    )
+    .await?;
+
+    for screenshot in &screenshots {
+        println!("{}", screenshot.display());
     }
+    Ok(())
+}
+
+async fn run_shootout(config: &Config, args: &ShootoutArgs) -> Result<()> {
+    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+    ffmpeg
+        .check_availability()
+        .await
+        .map_err(|e| Error::ffmpeg(format!("FFmpeg tools not available: {}", e)))?;
 
-    if let Some(profile_name) = &args.show_profile {
-        show_profile(config, profile_name).await?;
-        return Ok(true);
+    let mut profile_manager = ProfileManager::new();
+    profile_manager.load_profiles(config.profiles.clone())?;
+    let profiles = args
+        .profiles
+        .iter()
+        .map(|name| {
+            profile_manager
+                .get_profile(name)
+                .ok_or_else(|| Error::profile(format!("Profile '{}' not found", name)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let report = Shootout::new(
+        &ffmpeg,
+        config,
+        &args.input,
+        profiles,
+        args.segments,
+        args.length,
+    )
+    .run()
+    .await?;
+    println!("{}", report.format_table());
+    Ok(())
+}
+
+async fn tools_check(config: &Config) -> Result<()> {
+    let report = check_tools(config).await;
+
+    println!("External tools:");
+    println!("{}", report.format_table());
+
+    if report.all_passed() {
+        Ok(())
+    } else {
+        Err(Error::Tool(format!(
+            "{} configured tool(s) are not working",
+            report.failed_count()
+        )))
     }
+}
 
-    if args.list_stream_profiles {
-        list_stream_profiles(config).await?;
-        return Ok(true);
+fn clean_temp_files(
+    config: &Config,
+    dirs: &[PathBuf],
+    older_than: Option<u64>,
+    dry_run: bool,
+) -> Result<()> {
+    let mut sweep = TempSweep::from_config(config)
+        .with_dirs(dirs.iter().cloned())
+        .with_dry_run(dry_run);
+    if let Some(hours) = older_than {
+        sweep = sweep.with_min_age(Duration::from_secs(hours * 3600));
     }
+    let report = sweep.run();
 
-    if let Some(profile_name) = &args.show_stream_profile {
-        show_stream_profile(config, profile_name).await?;
-        return Ok(true);
+    for path in &report.removed {
+        println!("  {}", path.display());
     }
+    if report.active_jobs > 0 {
+        println!("Skipped {} running job(s)", report.active_jobs);
+    }
+    println!("{}", report.summary());
 
-    if args.list_preview_profiles {
-        list_preview_profiles(config).await?;
-        return Ok(true);
+    if report.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::validation(format!(
+            "{} item(s) could not be removed",
+            report.errors.len()
+        )))
     }
+}
+
+async fn generate_test_source(
+    config: &Config,
+    signal: &TestSignal,
+    output: Option<&Path>,
+) -> Result<()> {
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from(signal.default_file_name()));
+    let work_dir =
+        PathBuf::from(&config.app.temp_dir).join(format!("ven_testsignal_{}", Uuid::new_v4()));
+    tokio::fs::create_dir_all(&work_dir).await?;
 
-    if args.validate_config {
-        validate_config(args.config.as_deref()).await?;
-        return Ok(true);
+    let result = TestSignalGenerator::new(config)
+        .generate(signal, &output, &work_dir)
+        .await;
+    if let Err(e) = tokio::fs::remove_dir_all(&work_dir).await {
+        tracing::warn!("Failed to remove {}: {}", work_dir.display(), e);
     }
+    result?;
+
+    println!(
+        "✓ {:.0}s {} test source written to {}",
+        signal.duration,
+        signal.describe(),
+        output.display()
+    );
+    Ok(())
+}
+
+async fn run_selftest(config: &Config, options: SelfTestOptions) -> Result<()> {
+    let selftest = SelfTest::new(config, options);
+    let report = selftest.run().await?;
+
+    println!();
+    println!("Self-test results:");
+    println!("{}", report.format_table());
 
-    // No info commands executed
-    Ok(false)
+    if report.all_passed() {
+        println!("✓ All self-test checks passed");
+        Ok(())
+    } else {
+        println!("✗ {} self-test check(s) failed", report.failed_count());
+        Err(Error::validation(format!(
+            "Self-test failed: {} check(s) did not pass",
+            report.failed_count()
+        )))
+    }
 }
 
 async fn list_profiles(config: &Config) -> Result<()> {
@@ -72,7 +823,9 @@ async fn list_profiles(config: &Config) -> Result<()> {
     }
 
     println!("{:-<80}", "");
-    println!("Use 'show-profile <name>' to see detailed information about a specific profile.");
+    println!(
+        "Use 'config show-profile <name>' to see detailed information about a specific profile."
+    );
 
     Ok(())
 }
@@ -87,7 +840,31 @@ async fn show_profile(config: &Config, name: &str) -> Result<()> {
         println!("Title: {}", profile.title);
         println!("Base CRF: {}", profile.base_crf);
         println!("Bitrate: {}kbps", profile.bitrate);
+        if let Some(max_bitrate) = profile.max_bitrate {
+            println!("Max Bitrate: {}kbps", max_bitrate);
+        }
         println!("Content Type: {}", profile.content_type.as_str());
+        if let Some(tune) = profile.tune {
+            match profile.get_tune() {
+                Some(resolved) if resolved != tune.as_str() => {
+                    println!("Tune: {} ({})", tune.as_str(), resolved)
+                }
+                Some(resolved) => println!("Tune: {}", resolved),
+                None => println!("Tune: {} (none for this content type)", tune.as_str()),
+            }
+        }
+        if let Some(selection) = &profile.stream_selection_profile {
+            println!("Stream Selection: {}", selection);
+        }
+        if let Some(extension) = profile.output_extension() {
+            println!(
+                "Output: {} (.{})",
+                profile
+                    .output_container()
+                    .map_or("unknown container", |c| c.as_str()),
+                extension
+            );
+        }
         println!();
 
         println!("HDR Adjustments:");
@@ -114,13 +891,15 @@ async fn show_profile(config: &Config, name: &str) -> Result<()> {
         println!("  Base Bitrate: {}kbps", profile.bitrate);
         println!(
             "  HDR Bitrate: {}kbps ({}kbps × {:.1}x)",
-            (profile.bitrate as f32
-                * config
-                    .analysis
-                    .hdr
-                    .as_ref()
-                    .unwrap_or(&crate::config::UnifiedHdrConfig::default())
-                    .bitrate_multiplier) as u32,
+            profile.cap_bitrate(
+                (profile.bitrate as f32
+                    * config
+                        .analysis
+                        .hdr
+                        .as_ref()
+                        .unwrap_or(&crate::config::UnifiedHdrConfig::default())
+                        .bitrate_multiplier) as u32
+            ),
             profile.bitrate,
             config
                 .analysis
@@ -133,10 +912,7 @@ async fn show_profile(config: &Config, name: &str) -> Result<()> {
 
         println!("x265 Parameters:");
         println!("{:-<40}", "");
-        let mut params: Vec<_> = profile.x265_params.iter().collect();
-        params.sort_by_key(|(k, _)| *k);
-
-        for (key, value) in params {
+        for (key, value) in &profile.x265_params {
             if value.is_empty() || value == "true" || value == "1" {
                 println!("  {}", key);
             } else {
@@ -155,6 +931,189 @@ async fn show_profile(config: &Config, name: &str) -> Result<()> {
     Ok(())
 }
 
+fn show_config(config_path: Option<&std::path::Path>, origin: bool) -> Result<()> {
+    let layered = Config::load_layered(config_path)?;
+
+    println!("Configuration layers (lowest priority first):");
+    for (index, source) in layered.sources.iter().enumerate() {
+        println!("  {}. {}", index + 1, source);
+    }
+    println!();
+
+    if origin {
+        let key_width = layered.origins.keys().map(String::len).max().unwrap_or(0);
+
+        println!("{:<width$} Value / Source", "Key", width = key_width);
+        println!("{:-<80}", "");
+        for (key, value_origin) in &layered.origins {
+            println!(
+                "{:<width$} {}  [{}]",
+                key,
+                value_origin.value,
+                value_origin.source,
+                width = key_width
+            );
+        }
+    } else {
+        print!("{}", serde_yaml::to_string(&layered.config)?);
+    }
+
+    Ok(())
+}
+
+fn export_profiles(
+    config: &Config,
+    profiles: &str,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    let names: Vec<String> = profiles
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(str::to_string)
+        .collect();
+    if names.is_empty() {
+        return Err(Error::validation(
+            "--profiles needs at least one profile name".to_string(),
+        ));
+    }
+
+    let bundle = ProfileBundle::export(config, &names)?;
+    let yaml = format!(
+        "# VEN profile bundle (ven {}); add it with `config import`\n{}",
+        env!("CARGO_PKG_VERSION"),
+        bundle.to_yaml()?
+    );
+    match output {
+        Some(path) => {
+            std::fs::write(path, yaml)?;
+            println!(
+                "Exported {} profile(s) and {} stream selection profile(s) to {}",
+                bundle.profiles.len(),
+                bundle.stream_selection_profiles.len(),
+                path.display()
+            );
+        }
+        None => print!("{}", yaml),
+    }
+    Ok(())
+}
+
+/// Merges a bundle into the `--config` file, or the user config when none is
+/// given. The file is rewritten (comments in it are lost) and put back if the
+/// configuration no longer loads afterwards.
+fn import_profiles(
+    config: &Config,
+    bundle_path: &std::path::Path,
+    config_path: Option<&std::path::Path>,
+    on_conflict: &str,
+) -> Result<()> {
+    let bundle = ProfileBundle::from_yaml(&std::fs::read_to_string(bundle_path)?)?;
+    let target = match config_path {
+        Some(path) => path.to_path_buf(),
+        None => dirs::config_dir()
+            .ok_or_else(|| {
+                Error::validation("No user config directory; pass --config".to_string())
+            })?
+            .join("ven")
+            .join("config.yaml"),
+    };
+
+    let original = std::fs::read_to_string(&target).ok();
+    let mut document = match &original {
+        Some(yaml) => ConfigLayer::parse(target.display().to_string(), yaml)?.value,
+        None => serde_yaml::Value::Null,
+    };
+    let report = bundle.merge_into(&mut document, config, |kind, name| {
+        resolve_conflict(on_conflict, kind, name)
+    })?;
+
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&target, serde_yaml::to_string(&document)?)?;
+    if let Err(e) = Config::load_with_discovery(config_path) {
+        match &original {
+            Some(yaml) => std::fs::write(&target, yaml)?,
+            None => std::fs::remove_file(&target)?,
+        }
+        return Err(Error::validation(format!(
+            "The imported profiles do not load, {} left unchanged: {}",
+            target.display(),
+            e
+        )));
+    }
+
+    for line in &report {
+        println!("  {}", line);
+    }
+    println!("✓ Imported into {}", target.display());
+    Ok(())
+}
+
+fn resolve_conflict(
+    on_conflict: &str,
+    kind: BundleEntry,
+    name: &str,
+) -> Result<ConflictResolution> {
+    use std::io::{BufRead, IsTerminal, Write};
+
+    match on_conflict {
+        "overwrite" => return Ok(ConflictResolution::Overwrite),
+        "skip" => return Ok(ConflictResolution::Skip),
+        "rename" => return Ok(ConflictResolution::Rename),
+        _ => {}
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::validation(format!(
+            "A different {} '{}' already exists; pass --on-conflict overwrite, skip or rename",
+            kind, name
+        )));
+    }
+
+    loop {
+        print!(
+            "A different {} '{}' already exists: [o]verwrite, [s]kip or [r]ename? ",
+            kind, name
+        );
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().lock().read_line(&mut answer)? == 0 {
+            return Err(Error::cancelled("no answer to the conflict prompt"));
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => return Ok(ConflictResolution::Overwrite),
+            "s" | "skip" => return Ok(ConflictResolution::Skip),
+            "r" | "rename" => return Ok(ConflictResolution::Rename),
+            _ => {}
+        }
+    }
+}
+
+fn import_handbrake(presets: &std::path::Path, output: Option<&std::path::Path>) -> Result<()> {
+    let import = HandBrakeImport::from_json(&std::fs::read_to_string(presets)?)?;
+    let yaml = import.to_yaml()?;
+
+    let Some(output) = output else {
+        print!("{}", yaml);
+        return Ok(());
+    };
+    std::fs::write(output, yaml)?;
+    println!(
+        "Wrote {} profile(s) to {}; merge them into your configuration",
+        import.profiles.len(),
+        output.display()
+    );
+    if !import.unmapped.is_empty() {
+        println!();
+        println!("Not mapped:");
+        for (profile, note) in &import.unmapped {
+            println!("  {}: {}", profile, note);
+        }
+    }
+    Ok(())
+}
+
 async fn validate_config(config_path: Option<&std::path::Path>) -> Result<()> {
     match Config::load_with_discovery(config_path) {
         Ok(config) => {
@@ -218,7 +1177,7 @@ async fn list_stream_profiles(config: &Config) -> Result<()> {
     }
 
     println!();
-    println!("Use --show-stream-profile <PROFILE> for detailed information");
+    println!("Use 'config show-stream-profile <PROFILE>' for detailed information");
     println!("Use -s/--stream-selection-profile <PROFILE> to select a profile");
 
     Ok(())
@@ -347,8 +1306,8 @@ preview_profiles:
         println!();
     }
 
-    println!("Use --preview-profile <NAME> to use a preview profile group");
-    println!("Example: --preview-time 30 --preview-profile anime_comparison");
+    println!("Use 'preview --group <NAME>' to use a preview profile group");
+    println!("Example: preview -i input.mkv --time 30 --group anime_comparison");
 
     Ok(())
 }
@@ -373,6 +1332,11 @@ mod tests {
                 bitrate: 10000,
                 content_type: "film".to_string(),
                 x265_params: HashMap::new(),
+                container: None,
+                extension: None,
+                max_bitrate: None,
+                stream_selection_profile: None,
+                tune: None,
             },
         );
 
@@ -1,5 +1,8 @@
 pub mod args;
 pub mod commands;
 
-pub use args::CliArgs;
+pub use args::{
+    AnalyzeArgs, CliArgs, Command, ConfigCommand, EncodeArgs, GlobalArgs, LogsCommand, PreviewArgs,
+    QueueCommand, ScreenshotsArgs, ShootoutArgs, ToolsCommand,
+};
 pub use commands::handle_commands;
@@ -135,13 +135,13 @@ impl ColorManager {
 
         // HDR-specific validation
         match transfer_function {
-            TransferFunction::Smpte2084 | TransferFunction::AribStdB67 => {
-                if !matches!(color_space, ColorSpace::Bt2020) {
-                    return Err(format!(
-                        "HDR transfer function {:?} should use Bt2020 color space",
-                        transfer_function
-                    ));
-                }
+            TransferFunction::Smpte2084 | TransferFunction::AribStdB67
+                if !matches!(color_space, ColorSpace::Bt2020) =>
+            {
+                return Err(format!(
+                    "HDR transfer function {:?} should use Bt2020 color space",
+                    transfer_function
+                ));
             }
             _ => {}
         }
@@ -0,0 +1,324 @@
+//! Profile bundles: a set of encoding profiles, plus the stream selection
+//! profiles they reference, as a partial configuration document that can be
+//! shared and merged into another configuration.
+
+use crate::config::types::{RawProfile, RawStreamSelectionProfile};
+use crate::config::Config;
+use crate::utils::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// What kind of entry a bundle conflict is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleEntry {
+    Profile,
+    StreamSelectionProfile,
+}
+
+impl BundleEntry {
+    fn section(&self) -> &'static str {
+        match self {
+            Self::Profile => "profiles",
+            Self::StreamSelectionProfile => "stream_selection_profiles",
+        }
+    }
+}
+
+impl fmt::Display for BundleEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Profile => write!(f, "profile"),
+            Self::StreamSelectionProfile => write!(f, "stream selection profile"),
+        }
+    }
+}
+
+/// How to import an entry whose name is taken by a different one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    Overwrite,
+    Skip,
+    /// Import under the first free `<name>_imported[_N]`
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileBundle {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, RawProfile>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub stream_selection_profiles: BTreeMap<String, RawStreamSelectionProfile>,
+}
+
+impl ProfileBundle {
+    /// The named profiles of `config` and the stream selection profiles they use.
+    pub fn export(config: &Config, names: &[String]) -> Result<Self> {
+        let missing: Vec<&str> = names
+            .iter()
+            .filter(|name| !config.profiles.contains_key(*name))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            let mut available: Vec<&String> = config.profiles.keys().collect();
+            available.sort();
+            return Err(Error::validation(format!(
+                "Unknown profile(s): {} (available: {})",
+                missing.join(", "),
+                available
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let mut bundle = Self::default();
+        for name in names {
+            let profile = config.profiles[name].clone();
+            if let Some(selection) = &profile.stream_selection_profile {
+                if let Some(raw) = config.stream_selection_profiles.get(selection) {
+                    bundle
+                        .stream_selection_profiles
+                        .insert(selection.clone(), raw.clone());
+                }
+            }
+            bundle.profiles.insert(name.clone(), profile);
+        }
+        Ok(bundle)
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        let bundle: Self = serde_yaml::from_str(yaml)?;
+        if bundle.profiles.is_empty() {
+            return Err(Error::parse("The bundle contains no profiles".to_string()));
+        }
+        Ok(bundle)
+    }
+
+    /// The bundle as YAML, leaving out unset fields.
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(&tidy(serde_yaml::to_value(self)?))?)
+    }
+
+    /// Adds the bundle's entries to the configuration `document` (one config
+    /// layer, as parsed YAML). Entries identical to what `existing` already
+    /// has are left out; for a name `existing` uses for something else,
+    /// `resolve` decides. Returns one line per entry describing what was done.
+    pub fn merge_into(
+        self,
+        document: &mut Value,
+        existing: &Config,
+        mut resolve: impl FnMut(BundleEntry, &str) -> Result<ConflictResolution>,
+    ) -> Result<Vec<String>> {
+        if !document.is_mapping() {
+            *document = Value::Mapping(Mapping::new());
+        }
+        let mut report = Vec::new();
+
+        let mut renamed = HashMap::new();
+        for (name, selection) in self.stream_selection_profiles {
+            let target = merge_entry(
+                document,
+                BundleEntry::StreamSelectionProfile,
+                &name,
+                &selection,
+                &existing.stream_selection_profiles,
+                &mut resolve,
+                &mut report,
+            )?;
+            if let Some(target) = target.filter(|target| *target != name) {
+                renamed.insert(name, target);
+            }
+        }
+
+        for (name, mut profile) in self.profiles {
+            if let Some(selection) = &mut profile.stream_selection_profile {
+                if let Some(target) = renamed.get(selection) {
+                    *selection = target.clone();
+                }
+            }
+            merge_entry(
+                document,
+                BundleEntry::Profile,
+                &name,
+                &profile,
+                &existing.profiles,
+                &mut resolve,
+                &mut report,
+            )?;
+        }
+        Ok(report)
+    }
+}
+
+/// Writes one entry into `document` and returns the name it ended up under,
+/// `None` if it was skipped.
+fn merge_entry<T: Serialize + PartialEq>(
+    document: &mut Value,
+    kind: BundleEntry,
+    name: &str,
+    entry: &T,
+    existing: &HashMap<String, T>,
+    resolve: &mut impl FnMut(BundleEntry, &str) -> Result<ConflictResolution>,
+    report: &mut Vec<String>,
+) -> Result<Option<String>> {
+    let taken = |candidate: &str| {
+        existing.contains_key(candidate)
+            || document
+                .get(kind.section())
+                .and_then(Value::as_mapping)
+                .is_some_and(|section| section.contains_key(candidate))
+    };
+
+    let target = match existing.get(name) {
+        None => {
+            report.push(format!("added {} '{}'", kind, name));
+            name.to_string()
+        }
+        Some(current) if current == entry => {
+            report.push(format!("{} '{}' is already present", kind, name));
+            return Ok(Some(name.to_string()));
+        }
+        Some(_) => match resolve(kind, name)? {
+            ConflictResolution::Overwrite => {
+                report.push(format!("replaced {} '{}'", kind, name));
+                name.to_string()
+            }
+            ConflictResolution::Skip => {
+                report.push(format!(
+                    "skipped {} '{}', keeping the existing one",
+                    kind, name
+                ));
+                return Ok(None);
+            }
+            ConflictResolution::Rename => {
+                let mut target = format!("{}_imported", name);
+                let mut suffix = 2;
+                while taken(&target) {
+                    target = format!("{}_imported_{}", name, suffix);
+                    suffix += 1;
+                }
+                report.push(format!(
+                    "added {} '{}' (renamed from '{}')",
+                    kind, target, name
+                ));
+                target
+            }
+        },
+    };
+
+    let section = document
+        .as_mapping_mut()
+        .expect("document is a mapping")
+        .entry(kind.section().into())
+        .or_insert_with(|| Value::Mapping(Mapping::new()));
+    if !section.is_mapping() {
+        *section = Value::Mapping(Mapping::new());
+    }
+    if let Value::Mapping(section) = section {
+        section.insert(target.as_str().into(), tidy(serde_yaml::to_value(entry)?));
+    }
+    Ok(Some(target))
+}
+
+/// Drops unset (`null`) fields and sorts x265 params, so written profiles
+/// read like hand-written ones.
+pub(crate) fn tidy(value: Value) -> Value {
+    match value {
+        Value::Mapping(mapping) => {
+            let mut entries: Vec<(Value, Value)> = mapping
+                .into_iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key, tidy(value)))
+                .collect();
+            if let Some((_, Value::Mapping(params))) =
+                entries.iter_mut().find(|(key, _)| key == "x265_params")
+            {
+                let mut sorted: Vec<(Value, Value)> = std::mem::take(params).into_iter().collect();
+                sorted.sort_by(|a, b| a.0.as_str().cmp(&b.0.as_str()));
+                *params = sorted.into_iter().collect();
+            }
+            Value::Mapping(entries.into_iter().collect())
+        }
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_and_merge_bundle() {
+        let mut config = Config::load_default().unwrap();
+        let selection = "jpn_eng".to_string();
+        config.stream_selection_profiles.insert(
+            selection.clone(),
+            RawStreamSelectionProfile {
+                title: "Japanese and English".to_string(),
+                audio: None,
+                subtitle: None,
+            },
+        );
+        let mut anime = config.profiles["default"].clone();
+        anime.stream_selection_profile = Some(selection.clone());
+        config.profiles.insert("anime".to_string(), anime);
+        assert!(ProfileBundle::export(&config, &["nope".to_string()]).is_err());
+
+        let bundle = ProfileBundle::export(&config, &["anime".to_string()]).unwrap();
+        assert_eq!(
+            bundle.stream_selection_profiles.keys().collect::<Vec<_>>(),
+            [&selection]
+        );
+        let yaml = bundle.to_yaml().unwrap();
+        assert!(!yaml.contains("null"));
+        let mut bundle = ProfileBundle::from_yaml(&yaml).unwrap();
+        assert_eq!(
+            bundle.profiles,
+            ProfileBundle::export(&config, &["anime".to_string()])
+                .unwrap()
+                .profiles
+        );
+
+        // Unchanged selection profile, tuned anime profile and a new one
+        bundle.profiles.get_mut("anime").unwrap().base_crf = 17.0;
+        let shared = bundle.profiles["anime"].clone();
+        bundle.profiles.insert("shared".to_string(), shared);
+
+        let mut document = Value::Null;
+        let mut conflicts = Vec::new();
+        let report = bundle
+            .clone()
+            .merge_into(&mut document, &config, |kind, name| {
+                conflicts.push(format!("{} {}", kind, name));
+                Ok(ConflictResolution::Rename)
+            })
+            .unwrap();
+        assert_eq!(conflicts, ["profile anime"]);
+        assert_eq!(
+            report,
+            [
+                format!(
+                    "stream selection profile '{}' is already present",
+                    selection
+                ),
+                "added profile 'anime_imported' (renamed from 'anime')".to_string(),
+                "added profile 'shared'".to_string(),
+            ]
+        );
+        assert_eq!(
+            document["profiles"]["anime_imported"]["base_crf"],
+            Value::from(17.0)
+        );
+        assert!(document.get("stream_selection_profiles").is_none());
+
+        let mut document = Value::Null;
+        bundle
+            .merge_into(&mut document, &config, |_, _| Ok(ConflictResolution::Skip))
+            .unwrap();
+        assert!(document["profiles"].get("anime").is_none());
+        assert!(document["profiles"].get("shared").is_some());
+    }
+}
@@ -0,0 +1,506 @@
+//! Import of HandBrake presets as VEN profiles.
+//!
+//! Translates the fields of a HandBrake preset export (`presets.json`) that
+//! have a VEN equivalent: encoder preset, tune, profile, level and extra
+//! options become x265 params, the quality slider `base_crf`, the average
+//! bitrate `bitrate`, and the audio and subtitle language rules a stream
+//! selection profile of the same name. Whatever has no equivalent is listed
+//! in the import's report instead of being dropped silently.
+
+use crate::config::bundle::ProfileBundle;
+use crate::config::types::{
+    AudioSelectionConfig, EncoderTune, OutputContainer, RawProfile, RawStreamSelectionProfile,
+    SubtitleSelectionConfig,
+};
+use crate::encoding::x265_params::is_known_param;
+use crate::utils::{Error, Result};
+use serde_json::Value as Json;
+use serde_yaml::Value as Yaml;
+use std::collections::{BTreeMap, HashMap};
+
+/// Used when a preset has no quality slider or average bitrate
+const DEFAULT_CRF: f32 = 22.0;
+const DEFAULT_BITRATE: u32 = 10000;
+
+const X265_PRESETS: &[&str] = &[
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+    "placebo",
+];
+
+/// x265 options the encoding mode sets from `base_crf`/`bitrate`
+const RATE_CONTROL_OPTIONS: &[&str] = &["crf", "bitrate", "qp", "pass", "stats", "vbv-maxrate"];
+
+/// Profiles converted from a HandBrake preset file.
+#[derive(Debug, Clone, Default)]
+pub struct HandBrakeImport {
+    pub profiles: BTreeMap<String, RawProfile>,
+    pub stream_selection_profiles: BTreeMap<String, RawStreamSelectionProfile>,
+    /// `(profile, what was not mapped)`
+    pub unmapped: Vec<(String, String)>,
+}
+
+impl HandBrakeImport {
+    /// Converts every preset of a HandBrake export, including those in folders.
+    pub fn from_json(json: &str) -> Result<Self> {
+        let root: Json = serde_json::from_str(json)?;
+        let presets = root["PresetList"].as_array().ok_or_else(|| {
+            Error::parse("Not a HandBrake preset export: no PresetList found".to_string())
+        })?;
+
+        let mut import = Self::default();
+        import.add_presets(presets);
+        if import.profiles.is_empty() {
+            return Err(Error::parse(
+                "The HandBrake preset file contains no presets".to_string(),
+            ));
+        }
+        Ok(import)
+    }
+
+    fn add_presets(&mut self, presets: &[Json]) {
+        for preset in presets {
+            if preset["Folder"].as_bool() == Some(true) {
+                if let Some(children) = preset["ChildrenArray"].as_array() {
+                    self.add_presets(children);
+                }
+                continue;
+            }
+            let title = preset["PresetName"].as_str().unwrap_or("HandBrake preset");
+            let name = self.unique_name(&profile_name(title));
+            let mut notes = Vec::new();
+            let mut profile = convert_video(preset, title, &mut notes);
+            if let Some(selection) = convert_streams(preset, title, &mut notes) {
+                profile.stream_selection_profile = Some(name.clone());
+                self.stream_selection_profiles
+                    .insert(name.clone(), selection);
+            }
+            self.unmapped
+                .extend(notes.into_iter().map(|note| (name.clone(), note)));
+            self.profiles.insert(name, profile);
+        }
+    }
+
+    fn unique_name(&self, base: &str) -> String {
+        let mut name = base.to_string();
+        let mut suffix = 2;
+        while self.profiles.contains_key(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        name
+    }
+
+    /// The profiles as a config file fragment, headed by the report as comments.
+    pub fn to_yaml(&self) -> Result<String> {
+        let mut yaml = String::from("# Imported from HandBrake presets\n");
+        if !self.unmapped.is_empty() {
+            yaml.push_str("# Not mapped:\n");
+            for (profile, note) in &self.unmapped {
+                yaml.push_str(&format!("#   {}: {}\n", profile, note));
+            }
+        }
+
+        let bundle = ProfileBundle {
+            profiles: self.profiles.clone(),
+            stream_selection_profiles: self.stream_selection_profiles.clone(),
+        };
+        yaml.push_str(&bundle.to_yaml()?);
+        Ok(yaml)
+    }
+}
+
+/// `Fast 1080p30` -> `fast_1080p30`
+fn profile_name(title: &str) -> String {
+    let mut name = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if !name.is_empty() && !name.ends_with('_') {
+            name.push('_');
+        }
+    }
+    let name = name.trim_end_matches('_');
+    if name.is_empty() {
+        "handbrake".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// A setting other than HandBrake's "leave it alone" values.
+fn setting<'a>(preset: &'a Json, key: &str) -> Option<&'a str> {
+    preset[key]
+        .as_str()
+        .map(str::trim)
+        .filter(|value| !value.is_empty() && !["auto", "off", "none"].contains(value))
+}
+
+/// An x265 option value from HandBrake's text, typed like hand-written profiles.
+fn yaml_value(value: &str) -> Yaml {
+    if let Ok(int) = value.parse::<i64>() {
+        Yaml::from(int)
+    } else if let Ok(float) = value.parse::<f64>() {
+        Yaml::from(float)
+    } else {
+        Yaml::from(value)
+    }
+}
+
+fn convert_video(preset: &Json, title: &str, notes: &mut Vec<String>) -> RawProfile {
+    let mut params: HashMap<String, Yaml> = HashMap::new();
+    params.insert("pix_fmt".into(), "yuv420p10le".into());
+    params.insert("profile".into(), "main10".into());
+
+    match preset["VideoEncoder"].as_str().unwrap_or("x265_10bit") {
+        "x265_10bit" => {}
+        "x265_12bit" => {
+            params.insert("pix_fmt".into(), "yuv420p12le".into());
+            params.insert("profile".into(), "main12".into());
+        }
+        "x265" | "x265_8bit" => {
+            notes.push("VideoEncoder x265 (8-bit): the profile encodes 10-bit".to_string())
+        }
+        other => notes.push(format!(
+            "VideoEncoder {}: VEN encodes with x265, the other video settings are kept",
+            other
+        )),
+    }
+
+    if let Some(preset_name) = setting(preset, "VideoPreset") {
+        if X265_PRESETS.contains(&preset_name) {
+            params.insert("preset".into(), preset_name.into());
+        } else {
+            notes.push(format!("VideoPreset {}: not an x265 preset", preset_name));
+        }
+    }
+
+    let mut tune = None;
+    for name in setting(preset, "VideoTune").unwrap_or_default().split(',') {
+        match name.trim() {
+            "" => {}
+            "animation" if tune.is_none() => tune = Some(EncoderTune::Animation),
+            "grain" if tune.is_none() => tune = Some(EncoderTune::Grain),
+            "fastdecode" if tune.is_none() => tune = Some(EncoderTune::Fastdecode),
+            other => notes.push(format!("VideoTune {}: no x265 equivalent", other)),
+        }
+    }
+    let content_type = match tune {
+        Some(EncoderTune::Animation) => "anime",
+        Some(EncoderTune::Grain) => "heavy_grain",
+        _ => "film",
+    };
+
+    if let Some(profile) = setting(preset, "VideoProfile") {
+        params.insert("profile".into(), profile.into());
+    }
+    if let Some(level) = setting(preset, "VideoLevel") {
+        params.insert("level-idc".into(), yaml_value(level));
+    }
+
+    for option in setting(preset, "VideoOptionExtra")
+        .unwrap_or_default()
+        .split(':')
+        .map(str::trim)
+        .filter(|option| !option.is_empty())
+    {
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) => (key, yaml_value(value)),
+            None => (option, Yaml::from(true)),
+        };
+        if RATE_CONTROL_OPTIONS.contains(&key) {
+            notes.push(format!(
+                "x265 option {}: rate control comes from base_crf/bitrate",
+                option
+            ));
+        } else if !is_known_param(key) {
+            notes.push(format!("x265 option {}: unknown to x265", option));
+        } else {
+            params.insert(key.to_string(), value);
+        }
+    }
+
+    let avg_bitrate = preset["VideoAvgBitrate"].as_u64().filter(|&kbps| kbps > 0);
+    let mut base_crf = DEFAULT_CRF;
+    match preset["VideoQualityType"].as_u64() {
+        Some(2) | None => match preset["VideoQualitySlider"].as_f64() {
+            Some(quality) => base_crf = quality as f32,
+            None => notes.push(format!("no quality set: base_crf {}", DEFAULT_CRF)),
+        },
+        Some(1) => notes.push(format!(
+            "average bitrate{}: encode with --mode abr",
+            if preset["VideoMultiPass"].as_bool() == Some(true) {
+                " (multi-pass)"
+            } else {
+                ""
+            }
+        )),
+        Some(other) => notes.push(format!("VideoQualityType {}: unsupported", other)),
+    }
+    if avg_bitrate.is_none() {
+        notes.push(format!(
+            "no average bitrate set: bitrate {}",
+            DEFAULT_BITRATE
+        ));
+    }
+
+    let container = match preset["FileFormat"].as_str() {
+        Some("av_mkv") => Some(OutputContainer::Mkv),
+        Some("av_mp4") => Some(OutputContainer::Mp4),
+        None => None,
+        Some(other) => {
+            notes.push(format!("FileFormat {}: VEN writes MKV or MP4", other));
+            None
+        }
+    };
+
+    if let Some(rate) = setting(preset, "VideoFramerate") {
+        notes.push(format!(
+            "VideoFramerate {}: VEN keeps the source frame rate",
+            rate
+        ));
+    }
+    for (key, hint) in [
+        ("PictureDeinterlaceFilter", "filters.deinterlace"),
+        ("PictureDenoiseFilter", "filters.denoise"),
+        ("PictureDetelecine", "filters.deinterlace"),
+        ("PictureSharpenFilter", "no sharpening in VEN"),
+        ("PictureDeblockPreset", "no deblock filter in VEN"),
+    ] {
+        if let Some(value) = setting(preset, key) {
+            notes.push(format!("{} {}: see {}", key, value, hint));
+        }
+    }
+    for key in ["PictureWidth", "PictureHeight"] {
+        if let Some(size) = preset[key].as_u64().filter(|&size| size > 0) {
+            notes.push(format!("{} {}: VEN keeps the source resolution", key, size));
+        }
+    }
+
+    RawProfile {
+        title: title.to_string(),
+        base_crf,
+        bitrate: avg_bitrate.map_or(DEFAULT_BITRATE, |kbps| kbps as u32),
+        content_type: content_type.to_string(),
+        x265_params: params,
+        container,
+        extension: None,
+        max_bitrate: None,
+        stream_selection_profile: None,
+        tune,
+    }
+}
+
+/// HandBrake's language list and track behavior for audio or subtitles:
+/// the languages to keep (HandBrake's `any` keeps all) and the track limit.
+fn language_rules(
+    preset: &Json,
+    prefix: &str,
+    notes: &mut Vec<String>,
+) -> (Option<Vec<String>>, Option<usize>) {
+    let languages: Vec<String> = preset[format!("{}LanguageList", prefix)]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Json::as_str)
+        .filter(|language| *language != "any")
+        .map(str::to_string)
+        .collect();
+    let behavior_key = format!("{}TrackSelectionBehavior", prefix);
+    let max_streams = match preset[behavior_key.as_str()].as_str() {
+        Some("first") => Some(1),
+        Some("none") => {
+            notes.push(format!(
+                "{} none: VEN always keeps at least the matching tracks",
+                behavior_key
+            ));
+            None
+        }
+        _ => None,
+    };
+    ((!languages.is_empty()).then_some(languages), max_streams)
+}
+
+fn convert_streams(
+    preset: &Json,
+    title: &str,
+    notes: &mut Vec<String>,
+) -> Option<RawStreamSelectionProfile> {
+    let (audio_languages, audio_max) = language_rules(preset, "Audio", notes);
+    let (subtitle_languages, subtitle_max) = language_rules(preset, "Subtitle", notes);
+
+    let mut bitrate_per_channel = HashMap::new();
+    for track in preset["AudioList"].as_array().into_iter().flatten() {
+        let encoder = track["AudioEncoder"].as_str().unwrap_or_default();
+        if encoder.starts_with("copy") {
+            continue;
+        }
+        let codec = match encoder {
+            "av_aac" | "ca_aac" | "fdk_aac" | "ca_haac" | "fdk_haac" => "aac",
+            "opus" => "opus",
+            "ac3" => "ac3",
+            "eac3" => "eac3",
+            "mp3" => "mp3",
+            "vorbis" => "vorbis",
+            _ => {
+                notes.push(format!("AudioEncoder {}: no VEN equivalent", encoder));
+                continue;
+            }
+        };
+        let channels = match track["AudioMixdown"].as_str().unwrap_or("stereo") {
+            "mono" => 1,
+            "5point1" => 6,
+            "6point1" => 7,
+            "7point1" => 8,
+            _ => 2,
+        };
+        notes.push(format!(
+            "AudioEncoder {}: VEN copies audio and transcodes only what the container cannot hold",
+            encoder
+        ));
+        if let Some(kbps) = track["AudioBitrate"].as_u64().filter(|&kbps| kbps > 0) {
+            bitrate_per_channel
+                .entry(codec.to_string())
+                .or_insert((kbps / channels) as u32);
+        }
+    }
+
+    if preset["SubtitleBurnBehavior"]
+        .as_str()
+        .is_some_and(|burn| burn != "none")
+    {
+        notes.push("SubtitleBurnBehavior: VEN does not burn in subtitles".to_string());
+    }
+
+    let audio = AudioSelectionConfig {
+        languages: audio_languages,
+        max_streams: audio_max,
+        bitrate_per_channel: (!bitrate_per_channel.is_empty()).then_some(bitrate_per_channel),
+        ..Default::default()
+    };
+    let subtitle = SubtitleSelectionConfig {
+        languages: subtitle_languages,
+        max_streams: subtitle_max,
+        ..Default::default()
+    };
+    let has_audio_rules = audio != AudioSelectionConfig::default();
+    let has_subtitle_rules = subtitle != SubtitleSelectionConfig::default();
+    (has_audio_rules || has_subtitle_rules).then(|| RawStreamSelectionProfile {
+        title: title.to_string(),
+        audio: has_audio_rules.then_some(audio),
+        subtitle: has_subtitle_rules.then_some(subtitle),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::LanguageSelectionMode;
+
+    const PRESETS: &str = r#"{
+        "PresetList": [{
+            "Folder": true,
+            "PresetName": "My Presets",
+            "ChildrenArray": [{
+                "PresetName": "Anime 1080p (x265)",
+                "FileFormat": "av_mkv",
+                "VideoEncoder": "x265_10bit",
+                "VideoPreset": "slow",
+                "VideoTune": "animation",
+                "VideoProfile": "auto",
+                "VideoLevel": "auto",
+                "VideoOptionExtra": "aq-mode=3:crf=18:no-sao:fancy=1",
+                "VideoQualityType": 2,
+                "VideoQualitySlider": 20.5,
+                "VideoAvgBitrate": 6000,
+                "VideoFramerate": "auto",
+                "PictureDeinterlaceFilter": "off",
+                "PictureDenoiseFilter": "nlmeans",
+                "AudioLanguageList": ["jpn", "eng"],
+                "AudioTrackSelectionBehavior": "first",
+                "AudioList": [
+                    {"AudioEncoder": "copy:flac"},
+                    {"AudioEncoder": "opus", "AudioMixdown": "5point1", "AudioBitrate": 384}
+                ],
+                "SubtitleLanguageList": ["eng"],
+                "SubtitleTrackSelectionBehavior": "all",
+                "SubtitleBurnBehavior": "none"
+            }]
+        }, {
+            "PresetName": "Anime 1080p (x265)",
+            "VideoEncoder": "x264",
+            "VideoQualityType": 1,
+            "VideoAvgBitrate": 4000,
+            "VideoMultiPass": true
+        }]
+    }"#;
+
+    #[test]
+    fn test_import_handbrake_presets() {
+        let import = HandBrakeImport::from_json(PRESETS).unwrap();
+        assert_eq!(
+            import.profiles.keys().collect::<Vec<_>>(),
+            ["anime_1080p_x265", "anime_1080p_x265_2"]
+        );
+
+        let anime = &import.profiles["anime_1080p_x265"];
+        assert_eq!(anime.title, "Anime 1080p (x265)");
+        assert_eq!(anime.base_crf, 20.5);
+        assert_eq!(anime.bitrate, 6000);
+        assert_eq!(anime.content_type, "anime");
+        assert_eq!(anime.tune, Some(EncoderTune::Animation));
+        assert_eq!(anime.container, Some(OutputContainer::Mkv));
+        assert_eq!(anime.x265_params["preset"], Yaml::from("slow"));
+        assert_eq!(anime.x265_params["profile"], Yaml::from("main10"));
+        assert_eq!(anime.x265_params["aq-mode"], Yaml::from(3));
+        assert_eq!(anime.x265_params["no-sao"], Yaml::from(true));
+        assert!(!anime.x265_params.contains_key("crf"));
+        assert!(!anime.x265_params.contains_key("fancy"));
+
+        let selection = &import.stream_selection_profiles["anime_1080p_x265"];
+        assert_eq!(
+            anime.stream_selection_profile.as_deref(),
+            Some("anime_1080p_x265")
+        );
+        let audio = selection.audio.as_ref().unwrap();
+        assert_eq!(
+            audio.languages.as_deref(),
+            Some(&["jpn".to_string(), "eng".to_string()][..])
+        );
+        assert_eq!(audio.max_streams, Some(1));
+        assert_eq!(audio.bitrate_per_channel.as_ref().unwrap()["opus"], 64);
+        assert_eq!(audio.selection_mode, LanguageSelectionMode::AllMatches);
+        assert_eq!(selection.subtitle.as_ref().unwrap().max_streams, None);
+
+        let notes: Vec<&str> = import
+            .unmapped
+            .iter()
+            .map(|(_, note)| note.as_str())
+            .collect();
+        assert!(notes.contains(&"x265 option crf=18: rate control comes from base_crf/bitrate"));
+        assert!(notes.contains(&"x265 option fancy=1: unknown to x265"));
+        assert!(notes.contains(&"PictureDenoiseFilter nlmeans: see filters.denoise"));
+        assert!(notes.contains(&"average bitrate (multi-pass): encode with --mode abr"));
+
+        let abr = &import.profiles["anime_1080p_x265_2"];
+        assert_eq!(abr.bitrate, 4000);
+        assert_eq!(abr.base_crf, DEFAULT_CRF);
+        assert!(abr.stream_selection_profile.is_none());
+
+        // The fragment loads back as profiles
+        let yaml = import.to_yaml().unwrap();
+        assert!(yaml.starts_with("# Imported from HandBrake presets\n# Not mapped:\n"));
+        let fragment: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+        let profiles: BTreeMap<String, RawProfile> =
+            serde_yaml::from_value(fragment["profiles"].clone()).unwrap();
+        assert_eq!(profiles, import.profiles);
+        assert!(HandBrakeImport::from_json("{}").is_err());
+    }
+}
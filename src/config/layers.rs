@@ -0,0 +1,232 @@
+use crate::utils::{Error, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Label used for values that come from the embedded default configuration.
+pub const BUILTIN_LAYER: &str = "built-in defaults";
+
+/// A single parsed configuration source, prior to merging.
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    pub source: String,
+    pub value: Value,
+}
+
+impl ConfigLayer {
+    pub fn parse(source: impl Into<String>, yaml: &str) -> Result<Self> {
+        let source = source.into();
+        let value: Value = serde_yaml::from_str(yaml)
+            .map_err(|e| Error::parse(format!("Failed to parse {}: {}", source, e)))?;
+
+        // An empty file parses as null; treat it as "no overrides"
+        let value = match value {
+            Value::Null => Value::Mapping(Mapping::new()),
+            Value::Mapping(_) => value,
+            _ => {
+                return Err(Error::parse(format!(
+                    "Configuration in {} must be a mapping at the top level",
+                    source
+                )))
+            }
+        };
+
+        Ok(Self { source, value })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let yaml = std::fs::read_to_string(path)?;
+        Self::parse(path.display().to_string(), &yaml)
+    }
+}
+
+/// Where a merged configuration value was last set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueOrigin {
+    pub value: String,
+    pub source: String,
+}
+
+/// Lists the override layers that exist on disk, lowest priority first:
+/// 1. `/etc/ven/config.yaml` (system-wide)
+/// 2. `config/config.yaml` next to the binary
+/// 3. User config directory, legacy `ffmpeg-encoder/config.yaml` then `ven/config.yaml`
+/// 4. `./ven.yaml` in the working directory (project)
+/// 5. The path passed via `--config`
+pub fn discover_layer_paths(explicit_path: Option<&Path>) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::from("/etc/ven/config.yaml")];
+
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(exe_dir) = exe_path.parent() {
+            candidates.push(exe_dir.join("config").join("config.yaml"));
+        }
+    }
+
+    if let Some(config_dir) = dirs::config_dir() {
+        candidates.push(config_dir.join("ffmpeg-encoder").join("config.yaml"));
+        candidates.push(config_dir.join("ven").join("config.yaml"));
+    }
+
+    candidates.push(PathBuf::from("ven.yaml"));
+
+    let mut paths = Vec::new();
+    for candidate in candidates {
+        if candidate.is_file() {
+            tracing::debug!("Found config layer: {}", candidate.display());
+            paths.push(candidate);
+        } else {
+            tracing::debug!("No config found at: {}", candidate.display());
+        }
+    }
+
+    if let Some(path) = explicit_path {
+        if path.exists() {
+            tracing::debug!("Using explicit config path: {}", path.display());
+            paths.retain(|p| p != path);
+            paths.push(path.to_path_buf());
+        } else {
+            tracing::debug!("Explicit config path does not exist: {}", path.display());
+        }
+    }
+
+    paths
+}
+
+/// Deep-merges `overlay` into `base`. Mappings are merged key by key; any other
+/// value (including sequences) replaces the base value wholesale.
+pub fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base_map), Value::Mapping(overlay_map)) => {
+            for (key, overlay_value) in overlay_map {
+                match base_map.get_mut(&key) {
+                    Some(base_value) => merge_values(base_value, overlay_value),
+                    None => {
+                        base_map.insert(key, overlay_value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// Merges all layers in order and records which layer supplied each leaf value
+/// of the result, keyed by dotted path (e.g. `analysis.crop_detection.enabled`).
+pub fn merge_layers(layers: &[ConfigLayer]) -> (Value, BTreeMap<String, ValueOrigin>) {
+    let mut merged = Value::Mapping(Mapping::new());
+    for layer in layers {
+        merge_values(&mut merged, layer.value.clone());
+    }
+
+    let layer_leaves: Vec<BTreeMap<String, &Value>> = layers
+        .iter()
+        .map(|layer| {
+            let mut leaves = BTreeMap::new();
+            collect_leaves(&layer.value, "", &mut leaves);
+            leaves
+        })
+        .collect();
+
+    let mut merged_leaves = BTreeMap::new();
+    collect_leaves(&merged, "", &mut merged_leaves);
+
+    let origins = merged_leaves
+        .into_iter()
+        .map(|(path, value)| {
+            let source = layers
+                .iter()
+                .zip(&layer_leaves)
+                .rev()
+                .find(|(_, leaves)| leaves.contains_key(&path))
+                .map(|(layer, _)| layer.source.clone())
+                .unwrap_or_else(|| BUILTIN_LAYER.to_string());
+            let origin = ValueOrigin {
+                value: format_leaf(value),
+                source,
+            };
+            (path, origin)
+        })
+        .collect();
+
+    (merged, origins)
+}
+
+fn collect_leaves<'a>(value: &'a Value, prefix: &str, out: &mut BTreeMap<String, &'a Value>) {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, child) in map {
+                let key = match key {
+                    Value::String(s) => s.clone(),
+                    other => format_leaf(other),
+                };
+                let path = if prefix.is_empty() {
+                    key
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                collect_leaves(child, &path, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), value);
+        }
+    }
+}
+
+fn format_leaf(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(source: &str, yaml: &str) -> ConfigLayer {
+        ConfigLayer::parse(source, yaml).unwrap()
+    }
+
+    #[test]
+    fn test_later_layers_override_per_key() {
+        let layers = vec![
+            layer(
+                "system",
+                "logging:\n  level: info\n  colored_output: true\ntools:\n  ffmpeg: ffmpeg\n",
+            ),
+            layer("user", "logging:\n  level: debug\n"),
+            layer("project", "tools:\n  ffmpeg: /opt/ffmpeg/bin/ffmpeg\n"),
+        ];
+
+        let (merged, origins) = merge_layers(&layers);
+
+        assert_eq!(merged["logging"]["level"], Value::from("debug"));
+        assert_eq!(merged["logging"]["colored_output"], Value::from(true));
+        assert_eq!(
+            merged["tools"]["ffmpeg"],
+            Value::from("/opt/ffmpeg/bin/ffmpeg")
+        );
+
+        assert_eq!(origins["logging.level"].source, "user");
+        assert_eq!(origins["logging.colored_output"].source, "system");
+        assert_eq!(origins["tools.ffmpeg"].source, "project");
+        assert_eq!(origins["tools.ffmpeg"].value, "\"/opt/ffmpeg/bin/ffmpeg\"");
+    }
+
+    #[test]
+    fn test_sequences_replace_instead_of_appending() {
+        let layers = vec![
+            layer("base", "langs: [eng, jpn]\n"),
+            layer("override", "langs: [ger]\n"),
+        ];
+
+        let (merged, origins) = merge_layers(&layers);
+
+        assert_eq!(merged["langs"].as_sequence().unwrap().len(), 1);
+        assert_eq!(origins["langs"].source, "override");
+    }
+
+    #[test]
+    fn test_empty_layer_is_accepted() {
+        assert!(ConfigLayer::parse("empty", "").is_ok());
+        assert!(ConfigLayer::parse("scalar", "42").is_err());
+    }
+}
@@ -1,52 +1,19 @@
+use super::layers::{discover_layer_paths, merge_layers, ConfigLayer, ValueOrigin, BUILTIN_LAYER};
 use super::types::*;
+use crate::encoding::filters::{validate_custom_filter, FilterStage};
+use crate::encoding::x265_params;
 use crate::utils::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 
-/// Discovers the config file location in the following priority order:
-/// 1. If explicit_path is Some and exists, use it
-/// 2. Check for config/config.yaml next to the binary
-/// 3. Check user config directory (~/.config/ffmpeg-encoder/ on Linux,
-///    ~/Library/Application Support/ffmpeg-encoder/ on macOS,
-///    %APPDATA%\ffmpeg-encoder\ on Windows)
-/// 4. Fall back to default config
-pub fn discover_config_path(explicit_path: Option<&Path>) -> Option<PathBuf> {
-    // Priority 1: Explicit path provided via CLI argument
-    if let Some(path) = explicit_path {
-        if path.exists() {
-            tracing::debug!("Using explicit config path: {}", path.display());
-            return Some(path.to_path_buf());
-        } else {
-            tracing::debug!("Explicit config path does not exist: {}", path.display());
-        }
-    }
-
-    // Priority 2: Check for config/config.yaml next to the binary
-    if let Ok(exe_path) = std::env::current_exe() {
-        if let Some(exe_dir) = exe_path.parent() {
-            let binary_config = exe_dir.join("config").join("config.yaml");
-            if binary_config.exists() {
-                tracing::debug!("Found config next to binary: {}", binary_config.display());
-                return Some(binary_config);
-            }
-            tracing::debug!("No config found at: {}", binary_config.display());
-        }
-    }
-
-    // Priority 3: Check user config directory (cross-platform)
-    if let Some(config_dir) = dirs::config_dir() {
-        let user_config = config_dir.join("ffmpeg-encoder").join("config.yaml");
-        if user_config.exists() {
-            tracing::debug!("Found config in user directory: {}", user_config.display());
-            return Some(user_config);
-        }
-        tracing::debug!("No config found at: {}", user_config.display());
-    }
-
-    // Priority 4: No config found, will fall back to default
-    tracing::debug!("No config file found, will use default configuration");
-    None
+/// A merged configuration together with the layers it was built from and the
+/// source of every leaf value (used by `config show --origin`).
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    pub config: Config,
+    pub sources: Vec<String>,
+    pub origins: BTreeMap<String, ValueOrigin>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -56,11 +23,43 @@ pub struct Config {
     pub logging: LoggingConfig,
     pub analysis: AnalysisConfig,
     pub profiles: HashMap<String, RawProfile>,
+    /// Fail loading on `x265_params` keys that are not known x265 options,
+    /// instead of passing them through with a warning
+    #[serde(default)]
+    pub reject_unknown_x265_params: bool,
     pub filters: FiltersConfig,
     #[serde(default)]
     pub stream_selection_profiles: HashMap<String, RawStreamSelectionProfile>,
     #[serde(default)]
     pub preview_profiles: HashMap<String, RawPreviewProfile>,
+    #[serde(default)]
+    pub audio: AudioConfig,
+    #[serde(default)]
+    pub throttle: ThrottleConfig,
+    #[serde(default)]
+    pub disk_space: DiskSpaceConfig,
+    #[serde(default)]
+    pub encoding: EncodingConfig,
+    #[serde(default)]
+    pub cleanup: CleanupConfig,
+    #[serde(default)]
+    pub streams: StreamsConfig,
+    #[serde(default)]
+    pub devices: HashMap<String, DevicePreset>,
+    #[serde(default)]
+    pub check: CheckPolicy,
+    #[serde(default)]
+    pub ladders: HashMap<String, LadderConfig>,
+    #[serde(default)]
+    pub thumbnails: ThumbnailsConfig,
+    #[serde(default)]
+    pub quality: QualityConfig,
+    #[serde(default)]
+    pub planning: PlanningConfig,
+    #[serde(default)]
+    pub output_files: OutputFilesConfig,
+    #[serde(default)]
+    pub notifications: NotificationsConfig,
 }
 
 impl Config {
@@ -78,17 +77,52 @@ impl Config {
         }
     }
 
-    /// Loads config with automatic discovery.
-    /// If explicit_path is provided and exists, it takes priority.
-    /// Otherwise, searches in: binary dir, user config dir, then falls back to default.
+    /// Loads config with automatic discovery, layering every config file found
+    /// on top of the defaults (see [`discover_layer_paths`] for the order).
     pub fn load_with_discovery(explicit_path: Option<&Path>) -> Result<Self> {
-        if let Some(config_path) = discover_config_path(explicit_path) {
-            tracing::info!("Loading configuration from: {}", config_path.display());
-            Self::load(&config_path)
-        } else {
+        Self::load_layered(explicit_path).map(|layered| layered.config)
+    }
+
+    /// Like [`Config::load_with_discovery`], but keeps track of where each value came from.
+    pub fn load_layered(explicit_path: Option<&Path>) -> Result<LayeredConfig> {
+        let mut layers = vec![Self::default_layer()?];
+
+        let paths = discover_layer_paths(explicit_path);
+        if paths.is_empty() {
             tracing::info!("No config file found, using default configuration");
-            Self::load_default()
         }
+        for path in &paths {
+            tracing::info!("Loading configuration from: {}", path.display());
+            layers.push(ConfigLayer::from_file(path)?);
+        }
+
+        let (merged, origins) = merge_layers(&layers);
+        let config: Config = serde_yaml::from_value(merged)?;
+        config.validate()?;
+
+        Ok(LayeredConfig {
+            config,
+            sources: layers.into_iter().map(|layer| layer.source).collect(),
+            origins,
+        })
+    }
+
+    fn default_layer() -> Result<ConfigLayer> {
+        let default_paths = ["config.default.yaml", "./config/config.default.yaml"];
+
+        for path in &default_paths {
+            let path = Path::new(path);
+            if path.exists() {
+                if let Ok(layer) = ConfigLayer::from_file(path) {
+                    return Ok(layer);
+                }
+            }
+        }
+
+        ConfigLayer::parse(
+            BUILTIN_LAYER,
+            include_str!("../../config/config.default.yaml"),
+        )
     }
 
     pub fn load_default() -> Result<Self> {
@@ -109,6 +143,26 @@ impl Config {
         Ok(config)
     }
 
+    /// Problems that do not stop loading, to log once logging is set up:
+    /// unknown `x265_params` keys, which newer x265 builds may know.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut names: Vec<&String> = self.profiles.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let unknown = x265_params::unknown_params(self.profiles[name].x265_params.keys());
+                (!unknown.is_empty()).then(|| {
+                    format!(
+                        "Unknown x265 parameter(s) in profile '{}': {} (passed through; x265 may reject them)",
+                        name,
+                        unknown.join(", ")
+                    )
+                })
+            })
+            .collect()
+    }
+
     fn validate(&self) -> Result<()> {
         if self.profiles.is_empty() {
             return Err(Error::validation("At least one profile must be defined"));
@@ -129,14 +183,263 @@ impl Config {
                 )));
             }
 
+            if profile.max_bitrate == Some(0) {
+                return Err(Error::validation(format!(
+                    "Invalid max_bitrate for profile '{}': must be greater than 0",
+                    name
+                )));
+            }
+
             if ContentType::from_string(&profile.content_type).is_none() {
                 return Err(Error::validation(format!(
                     "Invalid content_type for profile '{}': {}",
                     name, profile.content_type
                 )));
             }
+
+            if let Some(selection) = &profile.stream_selection_profile {
+                if !self.stream_selection_profiles.contains_key(selection) {
+                    return Err(Error::validation(format!(
+                        "Profile '{}' references unknown stream selection profile '{}'",
+                        name, selection
+                    )));
+                }
+            }
+
+            // x265 takes a single tune; ffmpeg's -tune and a `tune` in
+            // x265_params would silently override each other
+            if profile.tune.is_some() && profile.x265_params.contains_key("tune") {
+                return Err(Error::validation(format!(
+                    "Profile '{}' sets both tune and x265_params.tune; keep only one",
+                    name
+                )));
+            }
+
+            let unknown = x265_params::unknown_params(profile.x265_params.keys());
+            if !unknown.is_empty() && self.reject_unknown_x265_params {
+                return Err(Error::validation(format!(
+                    "Unknown x265 parameter(s) in profile '{}': {} (reject_unknown_x265_params is set)",
+                    name,
+                    unknown.join(", ")
+                )));
+            }
+        }
+
+        if self.throttle.enabled {
+            if self.throttle.resume_below > self.throttle.pause_above {
+                return Err(Error::validation(format!(
+                    "Invalid throttle thresholds: resume_below ({}) must not exceed pause_above ({})",
+                    self.throttle.resume_below, self.throttle.pause_above
+                )));
+            }
+
+            if self.throttle.source == ThrottleSource::Command && self.throttle.command.is_none() {
+                return Err(Error::validation(
+                    "Throttle source 'command' requires throttle.command to be set",
+                ));
+            }
+        }
+
+        let detection = &self.audio.language_detection;
+        if detection.enabled {
+            if detection.command.is_none() {
+                return Err(Error::validation(
+                    "Audio language detection requires audio.language_detection.command to be set",
+                ));
+            }
+            if detection.sample_seconds == 0 {
+                return Err(Error::validation(
+                    "Invalid audio.language_detection.sample_seconds: must be greater than 0",
+                ));
+            }
+        }
+
+        if let Some(ratio) = self.encoding.abort_if_projected_ratio {
+            if ratio <= 0.0 {
+                return Err(Error::validation(format!(
+                    "Invalid encoding.abort_if_projected_ratio: {} (must be greater than 0)",
+                    ratio
+                )));
+            }
+        }
+        if !(0.0..=100.0).contains(&self.encoding.abort_min_percent) {
+            return Err(Error::validation(format!(
+                "Invalid encoding.abort_min_percent: {} (must be between 0 and 100)",
+                self.encoding.abort_min_percent
+            )));
+        }
+
+        let thumbnails = &self.thumbnails;
+        if thumbnails.interval <= 0.0
+            || thumbnails.count == 0
+            || thumbnails.width == 0
+            || thumbnails.columns == 0
+        {
+            return Err(Error::validation(
+                "Invalid thumbnails settings: interval, count, width and columns must be greater than 0",
+            ));
+        }
+
+        if let Some(mode) = &self.output_files.mode {
+            crate::utils::filesystem::parse_mode(mode)?;
+        }
+
+        let quality = &self.quality;
+        if quality.metrics.is_empty()
+            || quality.vmaf_subsample == 0
+            || quality.butteraugli_frames == 0
+        {
+            return Err(Error::validation(
+                "Invalid quality settings: metrics must not be empty, vmaf_subsample and butteraugli_frames must be greater than 0",
+            ));
+        }
+
+        let planning = &self.planning;
+        if planning.presets.is_empty() || planning.probe_seconds <= 0.0 {
+            return Err(Error::validation(
+                "Invalid planning settings: presets must not be empty and probe_seconds must be greater than 0",
+            ));
+        }
+        if let Some(preset) = planning
+            .presets
+            .iter()
+            .find(|preset| crate::planning::typical_speed(preset).is_none())
+        {
+            return Err(Error::validation(format!(
+                "Unknown x265 preset in planning.presets: {}",
+                preset
+            )));
+        }
+
+        let deband = &self.filters.deband;
+        if deband.samples == 0 || deband.threshold <= 0.0 || deband.threshold > 1.0 {
+            return Err(Error::validation(
+                "Invalid filters.deband settings: samples must be greater than 0 and threshold between 0 and 1",
+            ));
+        }
+
+        let fingerprint = &self.analysis.fingerprint;
+        if fingerprint.samples == 0
+            || fingerprint.max_distance < 0.0
+            || fingerprint.max_distance > 0.5
+        {
+            return Err(Error::validation(
+                "Invalid analysis.fingerprint settings: samples must be greater than 0 and max_distance between 0 and 0.5",
+            ));
+        }
+
+        let scenes = &self.analysis.scene_detection;
+        if scenes.threshold <= 0.0 || scenes.threshold > 1.0 || scenes.min_interval < 0.0 {
+            return Err(Error::validation(
+                "Invalid analysis.scene_detection settings: threshold must be between 0 and 1 and min_interval not negative",
+            ));
+        }
+
+        let starved = &self.analysis.starved_source;
+        if starved.min_bits_per_pixel <= 0.0 || !(0.0..=51.0).contains(&starved.crf_reduction) {
+            return Err(Error::validation(
+                "Invalid analysis.starved_source settings: min_bits_per_pixel must be positive and crf_reduction between 0 and 51",
+            ));
+        }
+
+        for stage in FilterStage::ALL {
+            if let Some(filter) = self.filters.custom_filters.get(stage) {
+                validate_custom_filter(stage, filter)?;
+            }
+        }
+
+        let min_confidence = self.analysis.crop_detection.min_confidence;
+        if !(0.0..=100.0).contains(&min_confidence) {
+            return Err(Error::validation(
+                "Invalid analysis.crop_detection.min_confidence: must be between 0 and 100",
+            ));
+        }
+        if self
+            .analysis
+            .crop_detection
+            .max_aspect_variation
+            .is_some_and(|variation| variation < 0.0)
+        {
+            return Err(Error::validation(
+                "Invalid analysis.crop_detection.max_aspect_variation: must not be negative",
+            ));
+        }
+
+        let chain = &self.analysis.fallback_chain;
+        for (i, step) in chain.iter().enumerate() {
+            if chain[..i].contains(step) {
+                return Err(Error::validation(format!(
+                    "analysis.fallback_chain lists {} twice",
+                    step
+                )));
+            }
+            if *step == MetadataFallback::Fail && i + 1 < chain.len() {
+                return Err(Error::validation(
+                    "analysis.fallback_chain: fail must be the last step",
+                ));
+            }
         }
 
+        let ratio = self.streams.forced_subtitles.max_event_ratio;
+        if ratio <= 0.0 || ratio >= 1.0 {
+            return Err(Error::validation(
+                "Invalid streams.forced_subtitles.max_event_ratio: must be between 0 and 1",
+            ));
+        }
+
+        for (name, ladder) in &self.ladders {
+            self.validate_ladder(name, ladder)?;
+        }
+
+        Ok(())
+    }
+
+    fn validate_ladder(&self, name: &str, ladder: &LadderConfig) -> Result<()> {
+        if ladder.renditions.is_empty() {
+            return Err(Error::validation(format!(
+                "Ladder '{}' has no renditions",
+                name
+            )));
+        }
+        if ladder.parallel == 0 {
+            return Err(Error::validation(format!(
+                "Invalid parallel for ladder '{}': must be greater than 0",
+                name
+            )));
+        }
+        // Without the rendition name every rendition would write the same file
+        if !ladder.output.contains("{name}") {
+            return Err(Error::validation(format!(
+                "Output of ladder '{}' must contain {{name}}: {}",
+                name, ladder.output
+            )));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for rendition in &ladder.renditions {
+            if rendition.name.is_empty() || !seen.insert(rendition.name.as_str()) {
+                return Err(Error::validation(format!(
+                    "Ladder '{}' needs a distinct name for every rendition ('{}')",
+                    name, rendition.name
+                )));
+            }
+            if let Some(profile) = &rendition.profile {
+                if profile != "auto" && !self.profiles.contains_key(profile) {
+                    return Err(Error::validation(format!(
+                        "Rendition '{}' of ladder '{}' references unknown profile '{}'",
+                        rendition.name, name, profile
+                    )));
+                }
+            }
+            if let Some(height) = rendition.height {
+                if height == 0 || height % 2 != 0 {
+                    return Err(Error::validation(format!(
+                        "Invalid height for rendition '{}' of ladder '{}': {} (must be even and greater than 0)",
+                        rendition.name, name, height
+                    )));
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -184,6 +487,7 @@ analysis:
     sdr_crop_limit: 24
     hdr_crop_limit: 64
     min_pixel_change_percent: 1.0
+    dolby_vision: rpu-aware
   hdr:
     enabled: true
     crf_adjustment: 2.0
@@ -219,5 +523,84 @@ filters:
         let config: Config = serde_yaml::from_str(yaml).unwrap();
         assert_eq!(config.logging.level, "debug");
         assert!(!config.logging.show_timestamps);
+        assert_eq!(
+            config.analysis.crop_detection.dolby_vision,
+            DolbyVisionCropPolicy::RpuAware
+        );
+    }
+
+    #[test]
+    fn test_streams_muxer() {
+        assert_eq!(Config::default().streams.muxer, Muxer::Ffmpeg);
+
+        let streams: StreamsConfig = serde_yaml::from_str("muxer: mkvmerge").unwrap();
+        assert_eq!(streams.muxer, Muxer::Mkvmerge);
+        assert_eq!(streams.video_policy, VideoStreamPolicy::First);
+        assert!(serde_yaml::from_str::<StreamsConfig>("muxer: mp4box").is_err());
+    }
+
+    #[test]
+    fn test_unknown_x265_params_rejected() {
+        let mut config = Config::default();
+        assert!(config.warnings().is_empty());
+        let profile = config.profiles.values_mut().next().unwrap();
+        profile.x265_params.insert(
+            "aq_strenght".to_string(),
+            serde_yaml::Value::Number(1.into()),
+        );
+        assert!(config.validate().is_ok());
+        assert_eq!(config.warnings().len(), 1);
+        assert!(config.warnings()[0].contains("aq_strenght"));
+
+        config.reject_unknown_x265_params = true;
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_profile_stream_selection_must_exist() {
+        let mut config = Config::default();
+        let profile = config.profiles.values_mut().next().unwrap();
+        profile.stream_selection_profile = Some("jpn_eng".to_string());
+        assert!(config.validate().is_err());
+
+        config.stream_selection_profiles.insert(
+            "jpn_eng".to_string(),
+            RawStreamSelectionProfile {
+                title: "Japanese and English".to_string(),
+                audio: None,
+                subtitle: None,
+            },
+        );
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_profile_tune() {
+        let mut config = Config::default();
+        let profile = config.profiles.values_mut().next().unwrap();
+        profile.tune = Some(EncoderTune::Auto);
+        assert!(config.validate().is_ok());
+
+        let profile = config.profiles.values_mut().next().unwrap();
+        profile.x265_params.insert(
+            "tune".to_string(),
+            serde_yaml::Value::String("grain".to_string()),
+        );
+        assert!(config.validate().is_err());
+
+        assert_eq!(
+            EncoderTune::Auto.resolve(ContentType::ClassicAnime),
+            Some(EncoderTune::Animation)
+        );
+        assert_eq!(
+            EncoderTune::Auto.resolve(ContentType::HeavyGrain),
+            Some(EncoderTune::Grain)
+        );
+        assert_eq!(EncoderTune::Auto.resolve(ContentType::Film), None);
+        assert_eq!(
+            EncoderTune::Fastdecode.resolve(ContentType::Film),
+            Some(EncoderTune::Fastdecode)
+        );
+        assert!(serde_yaml::from_str::<EncoderTune>("zerolatency").is_err());
     }
 }
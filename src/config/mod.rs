@@ -1,10 +1,15 @@
+pub mod bundle;
+pub mod handbrake;
+pub mod layers;
 pub mod loader;
 pub mod preview_profiles;
 pub mod profiles;
 pub mod stream_profiles;
 pub mod types;
 
-pub use loader::Config;
+pub use bundle::{BundleEntry, ConflictResolution, ProfileBundle};
+pub use handbrake::HandBrakeImport;
+pub use loader::{Config, LayeredConfig};
 pub use preview_profiles::PreviewProfileManager;
 pub use profiles::{EncodingProfile, ProfileManager};
 pub use stream_profiles::StreamSelectionProfileManager;
@@ -1,9 +1,10 @@
-use super::types::{ContentType, RawProfile};
+use super::types::{ContentType, EncoderTune, OutputContainer, RawProfile};
 use crate::analysis::dolby_vision::{DolbyVisionInfo, DolbyVisionProfile};
 use crate::dolby_vision::RpuMetadata;
+use crate::encoding::x265_params;
 use crate::utils::{Error, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EncodingProfile {
@@ -12,7 +13,12 @@ pub struct EncodingProfile {
     pub base_crf: f32,
     pub bitrate: u32,
     pub content_type: ContentType,
-    pub x265_params: HashMap<String, String>,
+    pub x265_params: BTreeMap<String, String>,
+    pub container: Option<OutputContainer>,
+    pub extension: Option<String>,
+    pub max_bitrate: Option<u32>,
+    pub stream_selection_profile: Option<String>,
+    pub tune: Option<EncoderTune>,
 }
 
 impl EncodingProfile {
@@ -43,7 +49,7 @@ impl EncodingProfile {
                 };
                 Ok((k, value_str))
             })
-            .collect::<Result<HashMap<String, String>>>()?;
+            .collect::<Result<BTreeMap<String, String>>>()?;
 
         Ok(EncodingProfile {
             name,
@@ -52,6 +58,30 @@ impl EncodingProfile {
             bitrate: raw.bitrate,
             content_type,
             x265_params,
+            container: raw.container,
+            extension: raw
+                .extension
+                .map(|ext| ext.trim_start_matches('.').to_string()),
+            max_bitrate: raw.max_bitrate,
+            stream_selection_profile: raw.stream_selection_profile,
+            tune: raw.tune,
+        })
+    }
+
+    /// Extension that outputs of this profile should use, if the profile pins
+    /// a container or extension. `None` keeps the source file's extension.
+    pub fn output_extension(&self) -> Option<&str> {
+        self.extension
+            .as_deref()
+            .or_else(|| self.container.map(|c| c.extension()))
+    }
+
+    /// Container implied by the profile settings, falling back to the extension.
+    pub fn output_container(&self) -> Option<OutputContainer> {
+        self.container.or_else(|| {
+            self.extension
+                .as_deref()
+                .and_then(OutputContainer::from_extension)
         })
     }
 
@@ -68,9 +98,36 @@ impl EncodingProfile {
         crf.clamp(1.0, 51.0)
     }
 
+    /// Clamps an adaptive bitrate (kbps) to the profile's `max_bitrate`.
+    pub fn cap_bitrate(&self, bitrate: u32) -> u32 {
+        self.max_bitrate.map_or(bitrate, |max| bitrate.min(max))
+    }
+
+    /// Enforces `max_bitrate` through x265's VBV, so rate control with a
+    /// variable target (CRF, ABR) never peaks above it. A `vbv-maxrate` set in
+    /// the profile is kept when it is already lower.
+    pub fn apply_max_bitrate_vbv(&mut self) {
+        let Some(max) = self.max_bitrate else {
+            return;
+        };
+
+        let maxrate = self
+            .x265_params
+            .get("vbv-maxrate")
+            .and_then(|v| v.parse::<u32>().ok())
+            .map_or(max, |configured| configured.min(max));
+        self.x265_params
+            .insert("vbv-maxrate".to_string(), maxrate.to_string());
+        self.x265_params
+            .entry("vbv-bufsize".to_string())
+            .or_insert_with(|| (maxrate * 15 / 10).to_string());
+    }
+
+    /// Builds the `-x265-params` value. Keys are emitted in sorted order, so the
+    /// same settings always produce the same string (and diffable logs).
     pub fn build_x265_params_string(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
     ) -> String {
         self.build_x265_params_string_with_hdr(
             mode_specific_params,
@@ -91,6 +148,13 @@ impl EncodingProfile {
         self.x265_params.get("preset").cloned()
     }
 
+    /// Value for ffmpeg's `-tune`, with `auto` resolved from the content type.
+    pub fn get_tune(&self) -> Option<&'static str> {
+        self.tune
+            .and_then(|tune| tune.resolve(self.content_type))
+            .map(|tune| tune.as_str())
+    }
+
     pub fn get_profile(&self) -> Option<String> {
         self.x265_params.get("profile").cloned()
     }
@@ -98,7 +162,7 @@ impl EncodingProfile {
     #[allow(clippy::too_many_arguments)]
     pub fn build_x265_params_string_with_hdr(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
         is_hdr: Option<bool>,
         color_space: Option<&String>,
         transfer_function: Option<&String>,
@@ -121,7 +185,7 @@ impl EncodingProfile {
     #[allow(clippy::too_many_arguments)]
     pub fn build_x265_params_string_with_hdr_passthrough(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
         is_hdr: Option<bool>,
         color_space: Option<&String>,
         transfer_function: Option<&String>,
@@ -146,7 +210,7 @@ impl EncodingProfile {
     #[allow(clippy::too_many_arguments)]
     pub fn build_x265_params_string_with_external_metadata(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
         is_hdr: Option<bool>,
         color_space: Option<&String>,
         transfer_function: Option<&String>,
@@ -164,7 +228,10 @@ impl EncodingProfile {
         }
 
         if is_hdr.unwrap_or(false) {
-            params.insert("hdr10_opt".to_string(), "1".to_string());
+            // A profile's own hdr10-opt setting wins
+            if !x265_params::is_set(&params, "hdr10-opt") {
+                params.insert("hdr10_opt".to_string(), "1".to_string());
+            }
 
             if let Some(cs) = color_space {
                 if cs.contains("bt2020") || cs.contains("rec2020") {
@@ -177,6 +244,8 @@ impl EncodingProfile {
                     params.insert("transfer".to_string(), "smpte2084".to_string());
                 } else if tf.contains("arib-std-b67") {
                     params.insert("transfer".to_string(), "arib-std-b67".to_string());
+                    // hdr10-opt tunes QP for PQ and works against HLG
+                    params.remove("hdr10_opt");
                 }
             }
 
@@ -222,7 +291,7 @@ impl EncodingProfile {
     #[allow(clippy::too_many_arguments)]
     pub fn build_x265_params_string_with_external_metadata_passthrough(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
         is_hdr: Option<bool>,
         color_space: Option<&String>,
         transfer_function: Option<&String>,
@@ -243,7 +312,10 @@ impl EncodingProfile {
         params.insert("log-level".to_string(), "error".to_string());
 
         if is_hdr.unwrap_or(false) {
-            params.insert("hdr10_opt".to_string(), "1".to_string());
+            // A profile's own hdr10-opt setting wins
+            if !x265_params::is_set(&params, "hdr10-opt") {
+                params.insert("hdr10_opt".to_string(), "1".to_string());
+            }
 
             if let Some(cs) = color_space {
                 if cs.contains("bt2020") || cs.contains("rec2020") {
@@ -256,6 +328,8 @@ impl EncodingProfile {
                     params.insert("transfer".to_string(), "smpte2084".to_string());
                 } else if tf.contains("arib-std-b67") {
                     params.insert("transfer".to_string(), "arib-std-b67".to_string());
+                    // hdr10-opt tunes QP for PQ and works against HLG
+                    params.remove("hdr10_opt");
                 }
             }
 
@@ -303,7 +377,7 @@ impl EncodingProfile {
     #[allow(clippy::too_many_arguments)]
     pub fn build_x265_params_string_with_dolby_vision(
         &self,
-        mode_specific_params: Option<&HashMap<String, String>>,
+        mode_specific_params: Option<&BTreeMap<String, String>>,
         is_hdr: Option<bool>,
         color_space: Option<&String>,
         transfer_function: Option<&String>,
@@ -324,7 +398,10 @@ impl EncodingProfile {
         params.insert("log-level".to_string(), "error".to_string());
 
         if is_hdr.unwrap_or(false) {
-            params.insert("hdr10_opt".to_string(), "1".to_string());
+            // A profile's own hdr10-opt setting wins
+            if !x265_params::is_set(&params, "hdr10-opt") {
+                params.insert("hdr10_opt".to_string(), "1".to_string());
+            }
 
             if let Some(cs) = color_space {
                 if cs.contains("bt2020") || cs.contains("rec2020") {
@@ -337,6 +414,8 @@ impl EncodingProfile {
                     params.insert("transfer".to_string(), "smpte2084".to_string());
                 } else if tf.contains("arib-std-b67") {
                     params.insert("transfer".to_string(), "arib-std-b67".to_string());
+                    // hdr10-opt tunes QP for PQ and works against HLG
+                    params.remove("hdr10_opt");
                 }
             }
 
@@ -381,7 +460,13 @@ impl EncodingProfile {
                 params.insert("output-depth".to_string(), "10".to_string());
 
                 params.insert("colorprim".to_string(), "bt2020".to_string());
-                params.insert("transfer".to_string(), "smpte2084".to_string());
+                // Profile 8.4 sits on an HLG base layer, every other profile on PQ
+                if rpu_meta.profile == DolbyVisionProfile::Profile84 {
+                    params.insert("transfer".to_string(), "arib-std-b67".to_string());
+                    params.remove("hdr10_opt");
+                } else {
+                    params.insert("transfer".to_string(), "smpte2084".to_string());
+                }
                 params.insert("colormatrix".to_string(), "bt2020nc".to_string());
             }
         }
@@ -546,9 +631,34 @@ mod tests {
             bitrate: 10000,
             content_type: "film".to_string(),
             x265_params,
+            container: None,
+            extension: None,
+            max_bitrate: None,
+            stream_selection_profile: None,
+            tune: None,
         }
     }
 
+    #[test]
+    fn test_output_extension_from_container() {
+        let mut raw = create_test_raw_profile();
+        assert_eq!(
+            EncodingProfile::from_raw("test".to_string(), raw.clone())
+                .unwrap()
+                .output_extension(),
+            None
+        );
+
+        raw.container = Some(OutputContainer::Mp4);
+        let profile = EncodingProfile::from_raw("test".to_string(), raw.clone()).unwrap();
+        assert_eq!(profile.output_extension(), Some("mp4"));
+
+        raw.extension = Some(".m4v".to_string());
+        let profile = EncodingProfile::from_raw("test".to_string(), raw).unwrap();
+        assert_eq!(profile.output_extension(), Some("m4v"));
+        assert_eq!(profile.output_container(), Some(OutputContainer::Mp4));
+    }
+
     #[test]
     fn test_encoding_profile_from_raw() {
         let raw = create_test_raw_profile();
@@ -575,6 +685,33 @@ mod tests {
         assert_eq!(profile.calculate_adaptive_crf(0.5, true, 2.0), 24.5);
     }
 
+    #[test]
+    fn test_max_bitrate_cap() {
+        let mut raw = create_test_raw_profile();
+        raw.max_bitrate = Some(40000);
+        let mut profile = EncodingProfile::from_raw("test".to_string(), raw).unwrap();
+        let param = |profile: &EncodingProfile, key: &str| profile.x265_params[key].clone();
+
+        assert_eq!(profile.cap_bitrate(18000), 18000);
+        assert_eq!(profile.cap_bitrate(52000), 40000);
+
+        profile.apply_max_bitrate_vbv();
+        assert_eq!(param(&profile, "vbv-maxrate"), "40000");
+        assert_eq!(param(&profile, "vbv-bufsize"), "60000");
+
+        // A stricter profile VBV setting wins, a looser one is clamped
+        profile
+            .x265_params
+            .insert("vbv-maxrate".into(), "30000".into());
+        profile.apply_max_bitrate_vbv();
+        assert_eq!(param(&profile, "vbv-maxrate"), "30000");
+        profile
+            .x265_params
+            .insert("vbv-maxrate".into(), "80000".into());
+        profile.apply_max_bitrate_vbv();
+        assert_eq!(param(&profile, "vbv-maxrate"), "40000");
+    }
+
     #[test]
     fn test_build_x265_params_string() {
         let raw = create_test_raw_profile();
@@ -588,6 +725,40 @@ mod tests {
         assert!(params_str.contains("no-sao=0"));
     }
 
+    #[test]
+    fn test_x265_params_order_is_stable() {
+        let raw = create_test_raw_profile();
+        let profile = EncodingProfile::from_raw("test".to_string(), raw).unwrap();
+
+        let mode_params = BTreeMap::from([
+            ("vbv-maxrate".to_string(), "20000".to_string()),
+            ("crf".to_string(), "20".to_string()),
+            ("aq-mode".to_string(), "3".to_string()),
+        ]);
+        let expected = "aq-mode=3:crf=20:log-level=error:no-sao=0:vbv-maxrate=20000:weightb=1";
+        for _ in 0..10 {
+            assert_eq!(
+                profile.build_x265_params_string(Some(&mode_params)),
+                expected
+            );
+        }
+
+        let hdr_params = profile.build_x265_params_string_with_hdr(
+            None,
+            Some(true),
+            Some(&"bt2020nc".to_string()),
+            Some(&"smpte2084".to_string()),
+            Some(&"bt2020".to_string()),
+            None,
+            Some(&"1000".to_string()),
+        );
+        assert_eq!(
+            hdr_params,
+            "colormatrix=bt2020nc:colorprim=bt2020:crf=22:hdr10_opt=1:log-level=error:\
+             max-cll=1000,400:no-sao=0:transfer=smpte2084:weightb=1"
+        );
+    }
+
     #[test]
     fn test_hdr_parameter_injection() {
         let raw = create_test_raw_profile();
@@ -619,6 +790,24 @@ mod tests {
             "master-display=G(0.17,0.797)B(0.131,0.046)R(0.708,0.292)WP(0.3127,0.329)L(1000,0.01)"
         ));
         assert!(params_str.contains("max-cll=1000,400"));
+        assert!(params_str.contains("hdr10_opt=1"));
+
+        // A profile turning hdr10-opt off keeps it off
+        let mut no_opt = profile.clone();
+        no_opt
+            .x265_params
+            .insert("hdr10-opt".to_string(), "0".to_string());
+        let params_str = no_opt.build_x265_params_string_with_hdr(
+            None,
+            Some(true),
+            None,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(params_str.contains("hdr10-opt=0"));
+        assert!(!params_str.contains("hdr10_opt"));
     }
 
     #[test]
@@ -1,5 +1,5 @@
 use super::types::{
-    AudioSelectionConfig, RawStreamSelectionProfile, StreamSelectionProfile,
+    AudioSelectionConfig, LanguageSelectionMode, RawStreamSelectionProfile, StreamSelectionProfile,
     SubtitleSelectionConfig,
 };
 use crate::utils::{Error, Result};
@@ -95,6 +95,10 @@ impl StreamSelectionProfileManager {
                     title_patterns: None,
                     exclude_commentary: true,
                     max_streams: Some(2),
+                    bitrate_per_channel: None,
+                    roles: None,
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    title_template: None,
                 },
                 subtitle: SubtitleSelectionConfig {
                     languages: Some(vec!["eng".to_string()]),
@@ -104,6 +108,8 @@ impl StreamSelectionProfileManager {
                     exclude_commentary: true,
                     include_forced_only: false,
                     max_streams: Some(2),
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    convert_pgs_to_text: false,
                 },
             },
         );
@@ -127,6 +133,10 @@ impl StreamSelectionProfileManager {
                     ]),
                     exclude_commentary: true,
                     max_streams: Some(3),
+                    bitrate_per_channel: None,
+                    roles: None,
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    title_template: None,
                 },
                 subtitle: SubtitleSelectionConfig {
                     languages: Some(vec!["eng".to_string(), "jpn".to_string()]),
@@ -138,6 +148,8 @@ impl StreamSelectionProfileManager {
                     exclude_commentary: true,
                     include_forced_only: false,
                     max_streams: Some(4),
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    convert_pgs_to_text: false,
                 },
             },
         );
@@ -155,6 +167,10 @@ impl StreamSelectionProfileManager {
                     title_patterns: None,
                     exclude_commentary: true,
                     max_streams: None,
+                    bitrate_per_channel: None,
+                    roles: None,
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    title_template: None,
                 },
                 subtitle: SubtitleSelectionConfig {
                     languages: None,
@@ -164,6 +180,8 @@ impl StreamSelectionProfileManager {
                     exclude_commentary: true,
                     include_forced_only: true,
                     max_streams: Some(2),
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    convert_pgs_to_text: false,
                 },
             },
         );
@@ -181,6 +199,10 @@ impl StreamSelectionProfileManager {
                     title_patterns: None,
                     exclude_commentary: true,
                     max_streams: Some(1),
+                    bitrate_per_channel: None,
+                    roles: None,
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    title_template: None,
                 },
                 subtitle: SubtitleSelectionConfig {
                     languages: None,
@@ -190,6 +212,8 @@ impl StreamSelectionProfileManager {
                     exclude_commentary: true,
                     include_forced_only: true,
                     max_streams: Some(1),
+                    selection_mode: LanguageSelectionMode::AllMatches,
+                    convert_pgs_to_text: false,
                 },
             },
         );
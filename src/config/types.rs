@@ -47,6 +47,80 @@ impl ContentType {
     }
 }
 
+/// Output container a profile writes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputContainer {
+    #[default]
+    Mkv,
+    Mp4,
+}
+
+impl OutputContainer {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Mkv => "mkv",
+            Self::Mp4 => "mp4",
+        }
+    }
+
+    /// Default file extension for this container (without the dot).
+    pub fn extension(&self) -> &'static str {
+        self.as_str()
+    }
+
+    pub fn from_extension(ext: &str) -> Option<Self> {
+        match ext.to_lowercase().as_str() {
+            "mkv" | "mka" | "mk3d" => Some(Self::Mkv),
+            "mp4" | "m4v" | "mov" => Some(Self::Mp4),
+            _ => None,
+        }
+    }
+
+    /// Container implied by a path's extension, if it is one we know.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(Self::from_extension)
+    }
+}
+
+/// x265 `--tune` of a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderTune {
+    Animation,
+    Grain,
+    Fastdecode,
+    /// Picked from the profile's content type
+    Auto,
+}
+
+impl EncoderTune {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Animation => "animation",
+            Self::Grain => "grain",
+            Self::Fastdecode => "fastdecode",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// The tune x265 gets for `content_type`: `auto` becomes `animation` for
+    /// animated content, `grain` for heavy grain and no tune otherwise.
+    pub fn resolve(self, content_type: ContentType) -> Option<Self> {
+        match (self, content_type) {
+            (
+                Self::Auto,
+                ContentType::Anime | ContentType::ClassicAnime | ContentType::Animation3D,
+            ) => Some(Self::Animation),
+            (Self::Auto, ContentType::HeavyGrain) => Some(Self::Grain),
+            (Self::Auto, _) => None,
+            (tune, _) => Some(tune),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AppConfig {
     pub temp_dir: String,
@@ -59,6 +133,12 @@ pub struct DoviToolConfig {
     pub timeout_seconds: u64,
     pub extract_args: Option<Vec<String>>,
     pub inject_args: Option<Vec<String>>,
+    /// Extra attempts after a timeout or crash (rejected input is not retried)
+    #[serde(default)]
+    pub retries: u32,
+    /// Pause before the first retry, doubled for each further one
+    #[serde(default = "crate::utils::tool_runner::default_retry_backoff_seconds")]
+    pub retry_backoff_seconds: u64,
 }
 
 impl Default for DoviToolConfig {
@@ -68,6 +148,21 @@ impl Default for DoviToolConfig {
             timeout_seconds: 300,
             extract_args: None,
             inject_args: None,
+            retries: 0,
+            retry_backoff_seconds: crate::utils::tool_runner::default_retry_backoff_seconds(),
+        }
+    }
+}
+
+impl DoviToolConfig {
+    pub fn to_tool_config(&self) -> crate::utils::ToolConfig {
+        crate::utils::ToolConfig {
+            path: self.path.clone(),
+            timeout_seconds: self.timeout_seconds,
+            extract_args: self.extract_args.clone(),
+            inject_args: self.inject_args.clone(),
+            retries: self.retries,
+            retry_backoff_seconds: self.retry_backoff_seconds,
         }
     }
 }
@@ -95,6 +190,28 @@ pub struct ToolsConfig {
     pub dovi_tool: Option<DoviToolConfig>,
     pub hdr10plus_tool: Option<crate::hdr10plus::Hdr10PlusToolConfig>,
     pub mkvmerge: Option<MkvMergeConfig>,
+    /// Only checked by `tools check` so far
+    #[serde(default)]
+    pub mkvpropedit: Option<MkvMergeConfig>,
+    /// `butteraugli_main` from libjxl, for the butteraugli quality metric
+    #[serde(default)]
+    pub butteraugli: Option<String>,
+    /// OCR tool for stream selection profiles with `convert_pgs_to_text`
+    #[serde(default)]
+    pub subtitle_ocr: Option<SubtitleOcrConfig>,
+}
+
+/// External OCR tool converting PGS subtitles to SRT (e.g. pgsrip or
+/// SubtitleEdit's command line)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SubtitleOcrConfig {
+    pub path: String,
+    /// `{input}` is the extracted PGS (.sup) file, `{output}` the SRT to write,
+    /// `{output_dir}` its directory and `{language}` the track's language
+    /// (`und` when untagged). A tool that names its output itself may write
+    /// any `.srt` file into `{output_dir}`.
+    pub args: Vec<String>,
+    pub timeout_seconds: u64,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -102,6 +219,51 @@ pub struct LoggingConfig {
     pub level: String,
     pub show_timestamps: bool,
     pub colored_output: bool,
+    /// Where log events go: the terminal, or syslog/journald for daemons
+    #[serde(default)]
+    pub backend: LogBackend,
+    /// Keep printing to the terminal when `backend` is syslog or journald
+    #[serde(default = "default_logging_console")]
+    pub console: bool,
+}
+
+fn default_logging_console() -> bool {
+    true
+}
+
+/// Destination of log events besides the per-file encode logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogBackend {
+    /// The terminal only
+    #[default]
+    Stdout,
+    /// The local syslog daemon (`/dev/log`), facility daemon
+    Syslog,
+    /// The systemd journal, with the event fields as journal fields
+    Journald,
+}
+
+impl std::fmt::Display for LogBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Syslog => "syslog",
+            Self::Journald => "journald",
+        })
+    }
+}
+
+/// How crop detection treats Dolby Vision sources, whose RPU carries the
+/// active area (L5) of the uncropped frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DolbyVisionCropPolicy {
+    /// Never crop Dolby Vision sources
+    #[default]
+    Off,
+    /// Crop, and reset the RPU's L5 active area offsets to match the cropped frame
+    RpuAware,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -111,6 +273,21 @@ pub struct CropDetectionConfig {
     pub sdr_crop_limit: u32,
     pub hdr_crop_limit: u32,
     pub min_pixel_change_percent: f32,
+    /// Share of the samples (percent) that must agree on the crop; below it
+    /// the source is left uncropped
+    #[serde(default)]
+    pub min_confidence: f32,
+    /// Leave the source uncropped when the aspect ratios of the samples'
+    /// crops differ by more than this many percent (films alternating
+    /// between framings); unset picks the most common crop regardless
+    #[serde(default)]
+    pub max_aspect_variation: Option<f32>,
+    #[serde(default)]
+    pub dolby_vision: DolbyVisionCropPolicy,
+    /// Write a before/after crop report next to the log when a detected crop
+    /// is applied
+    #[serde(default)]
+    pub report: bool,
 }
 
 impl Default for CropDetectionConfig {
@@ -121,6 +298,382 @@ impl Default for CropDetectionConfig {
             sdr_crop_limit: 24,
             hdr_crop_limit: 64,
             min_pixel_change_percent: 1.0,
+            min_confidence: 0.0,
+            max_aspect_variation: None,
+            dolby_vision: DolbyVisionCropPolicy::Off,
+            report: false,
+        }
+    }
+}
+
+/// Crop detection settings given for a single run (`--crop-samples`,
+/// `--crop-threshold`, `--crop-min-confidence`, `--crop-report`), taking
+/// precedence over `analysis.crop_detection`
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct CropDetectionOverrides {
+    pub sample_count: Option<u32>,
+    /// cropdetect limit, for SDR and HDR sources alike
+    pub limit: Option<u32>,
+    pub min_confidence: Option<f32>,
+    /// Write the crop report even when `report` is off
+    #[serde(default)]
+    pub report: bool,
+}
+
+impl CropDetectionConfig {
+    /// This configuration with `overrides` applied.
+    pub fn with_overrides(&self, overrides: &CropDetectionOverrides) -> Self {
+        let mut config = self.clone();
+        if let Some(sample_count) = overrides.sample_count {
+            config.sample_count = sample_count;
+        }
+        if let Some(limit) = overrides.limit {
+            config.sdr_crop_limit = limit;
+            config.hdr_crop_limit = limit;
+        }
+        if let Some(min_confidence) = overrides.min_confidence {
+            config.min_confidence = min_confidence;
+        }
+        config.report |= overrides.report;
+        config
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct AudioConfig {
+    #[serde(default)]
+    pub normalization: LoudnormConfig,
+    #[serde(default)]
+    pub language_detection: LanguageDetectionConfig,
+}
+
+/// Language detection for audio tracks tagged `und` (or not tagged at all),
+/// run by an external command on a short sample of the track
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LanguageDetectionConfig {
+    pub enabled: bool,
+    /// Shell command printing the language of the sample; `{sample}` is
+    /// replaced by the path of a 16 kHz mono WAV file
+    pub command: Option<String>,
+    pub sample_seconds: u32,
+    /// Where the sample starts, skipping intros that are often music only
+    pub offset_seconds: u32,
+    pub timeout_seconds: u64,
+}
+
+impl Default for LanguageDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            command: None,
+            sample_seconds: 30,
+            offset_seconds: 300,
+            timeout_seconds: 120,
+        }
+    }
+}
+
+/// EBU R128 loudness normalization settings (FFmpeg `loudnorm`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoudnormConfig {
+    pub target_lufs: f32,
+    pub true_peak: f32,
+    pub loudness_range: f32,
+    pub codec: String,
+    /// Fixed bitrate for every stream (e.g. `256k`); unset uses the per-channel ladder
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bitrate: Option<String>,
+    pub sample_rate: u32,
+}
+
+impl Default for LoudnormConfig {
+    fn default() -> Self {
+        Self {
+            target_lufs: -23.0,
+            true_peak: -1.0,
+            loudness_range: 11.0,
+            codec: "aac".to_string(),
+            bitrate: None,
+            sample_rate: 48000,
+        }
+    }
+}
+
+/// What to do when a source has more than one (non-cover) video stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoStreamPolicy {
+    /// Encode the first video stream and drop the others with a warning
+    #[default]
+    First,
+    /// Keep every video stream: the first is encoded, the others are copied
+    All,
+    /// Refuse to process the file
+    Error,
+}
+
+/// What to do with closed captions (EIA-608/708) carried inside the video stream,
+/// which the x265 encode cannot pass through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ClosedCaptionPolicy {
+    /// Drop them with a warning
+    #[default]
+    Warn,
+    /// Extract them into a subtitle track of the output
+    Extract,
+    /// Drop them silently
+    Drop,
+}
+
+impl std::fmt::Display for ClosedCaptionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ClosedCaptionPolicy::Warn => "warn",
+            ClosedCaptionPolicy::Extract => "extract",
+            ClosedCaptionPolicy::Drop => "drop",
+        })
+    }
+}
+
+/// Playback limits of a device, layered onto the selected profile by `--device`
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DevicePreset {
+    pub title: String,
+    /// Highest HEVC level the decoder supports (x265 `level-idc`), e.g. "5.1"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<String>,
+    /// Whether the decoder handles HEVC High tier at that level
+    pub high_tier: bool,
+    /// Video bitrate ceiling in kbps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bitrate: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<OutputContainer>,
+    /// Audio codecs the device decodes; other audio streams are transcoded to
+    /// `audio_fallback`. Unset accepts everything.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_codecs: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_fallback: Option<String>,
+    /// Dolby Vision profiles the device plays (e.g. ["8.1"]); other profiles
+    /// are encoded as HDR10. Unset accepts every profile.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dolby_vision_profiles: Option<Vec<String>>,
+    /// The player only renders text subtitles: bitmap tracks are dropped, or
+    /// converted where the stream selection profile sets `convert_pgs_to_text`
+    pub text_subtitles_only: bool,
+}
+
+/// Renditions `--ladder` encodes from one source, e.g. 2160p HDR, 1080p SDR
+/// and 720p SDR
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LadderConfig {
+    #[serde(default)]
+    pub title: String,
+    /// Output name of each rendition, without extension: `{stem}` is the stem
+    /// of the output a plain encode would write, `{name}` and `{height}` come
+    /// from the rendition
+    #[serde(default = "default_ladder_output")]
+    pub output: String,
+    /// Renditions encoded at once after the first one has analyzed the source
+    #[serde(default = "default_ladder_parallel")]
+    pub parallel: usize,
+    pub renditions: Vec<Rendition>,
+}
+
+fn default_ladder_output() -> String {
+    "{stem}.{name}".to_string()
+}
+
+fn default_ladder_parallel() -> usize {
+    1
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rendition {
+    pub name: String,
+    /// Encoding profile; `--profile` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Scale down to this height, keeping the aspect ratio; sources that are
+    /// not taller are left as they are
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// Tone map HDR and Dolby Vision sources to SDR (BT.709)
+    #[serde(default)]
+    pub tonemap: bool,
+}
+
+/// Which tool writes the final Matroska output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Muxer {
+    /// FFmpeg writes the output directly
+    #[default]
+    Ffmpeg,
+    /// FFmpeg writes the video as raw HEVC and the other streams to a
+    /// temporary file, which mkvmerge muxes into the output in one pass,
+    /// adding track statistics tags and mkvmerge's track flag handling
+    Mkvmerge,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StreamsConfig {
+    pub video_policy: VideoStreamPolicy,
+    pub closed_captions: ClosedCaptionPolicy,
+    pub muxer: Muxer,
+    pub forced_subtitles: ForcedSubtitleConfig,
+}
+
+/// Finding the forced (narrative) subtitle track of sources that do not flag it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForcedSubtitleConfig {
+    pub enabled: bool,
+    /// A track with at most this share of the events of the full subtitles in
+    /// the same language counts as forced
+    pub max_event_ratio: f64,
+    /// Also make the forced track the default subtitle track
+    pub set_default: bool,
+}
+
+impl Default for ForcedSubtitleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_event_ratio: 0.2,
+            set_default: false,
+        }
+    }
+}
+
+/// Policy `analyze --check` holds sources to; rules left unset are not checked
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckPolicy {
+    /// Accepted video codecs, as ffprobe names them (`hevc`, `av1`); empty: any
+    pub video_codecs: Vec<String>,
+    /// Highest overall bitrate in kbps
+    pub max_bitrate: Option<u32>,
+    /// Languages that each need at least one audio stream
+    pub audio_languages: Vec<String>,
+    pub reject_interlaced: bool,
+    /// Dolby Vision sources need a base layer that plays without Dolby Vision,
+    /// which profile 5 lacks
+    pub require_hdr_fallback: bool,
+}
+
+/// Free-space guard for the output and temp volumes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiskSpaceConfig {
+    pub enabled: bool,
+    /// Refuse to start, or abort a running encode, below this much free space
+    pub min_free_mb: u64,
+    pub check_interval: u64,
+}
+
+impl Default for DiskSpaceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_free_mb: 1024,
+            check_interval: 10,
+        }
+    }
+}
+
+/// Checks on a running encode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodingConfig {
+    /// Abandon an encode whose projected output exceeds this multiple of the
+    /// source size (e.g. 1.0: any growth); disabled when unset
+    pub abort_if_projected_ratio: Option<f64>,
+    /// Percentage that must be encoded before the projection is trusted
+    pub abort_min_percent: f64,
+    /// Encode HDR sources in 8-bit when a profile pins it, instead of failing
+    pub allow_8bit_hdr: bool,
+    /// Add `hdr10`, `hdr10-opt` and `repeat-headers` to HDR encodes whose
+    /// profile leaves them out
+    pub hdr_signaling: bool,
+    /// Encode a few seconds with the final arguments before each encode, so
+    /// invalid x265 params or missing filters fail right away
+    pub preflight_probe: bool,
+}
+
+impl Default for EncodingConfig {
+    fn default() -> Self {
+        Self {
+            abort_if_projected_ratio: None,
+            abort_min_percent: 10.0,
+            allow_8bit_hdr: false,
+            hdr_signaling: true,
+            preflight_probe: false,
+        }
+    }
+}
+
+/// Removal of temporary files left behind by crashed or killed runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupConfig {
+    /// Sweep before encoding or running the queue
+    pub on_startup: bool,
+    /// Files without a job marker are only removed once they are this old
+    pub min_age_hours: u64,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            on_startup: true,
+            min_age_hours: 24,
+        }
+    }
+}
+
+/// What the throttle measures before pausing an encode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThrottleSource {
+    /// A sysfs temperature file in millidegrees Celsius
+    #[default]
+    Temperature,
+    /// The 1-minute load average from /proc/loadavg
+    Load,
+    /// A shell command that prints a single number
+    Command,
+}
+
+/// Pause/resume encoding when the machine runs hot or busy
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleConfig {
+    pub enabled: bool,
+    pub source: ThrottleSource,
+    pub temperature_path: String,
+    pub command: Option<String>,
+    pub pause_above: f64,
+    pub resume_below: f64,
+    pub check_interval: u64,
+}
+
+impl Default for ThrottleConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: ThrottleSource::Temperature,
+            temperature_path: "/sys/class/thermal/thermal_zone0/temp".to_string(),
+            command: None,
+            pause_above: 85.0,
+            resume_below: 75.0,
+            check_interval: 10,
         }
     }
 }
@@ -168,6 +721,10 @@ pub struct DolbyVisionConfig {
     pub vbv_abr_bufsize: u32,
     pub vbv_abr_maxrate: u32,
     pub profile_specific_adjustments: bool,
+    /// Encode Profile 8.1 sources as HDR10+ (converted from the RPU's L1
+    /// metadata) instead of injecting the RPU
+    #[serde(default)]
+    pub convert_to_hdr10plus: bool,
 }
 
 impl Default for DolbyVisionConfig {
@@ -188,6 +745,7 @@ impl Default for DolbyVisionConfig {
             vbv_abr_bufsize: 120_000,
             vbv_abr_maxrate: 100_000,
             profile_specific_adjustments: true,
+            convert_to_hdr10plus: false,
         }
     }
 }
@@ -207,6 +765,15 @@ pub struct Hdr10PlusConfig {
     pub encoding_complexity: f32,
 
     pub validate_curves: bool,
+
+    /// Extracted metadata files larger than this (in MB) are only read for
+    /// a summary, frame by frame, instead of being loaded whole (0: always)
+    #[serde(default = "default_hdr10plus_summary_threshold_mb")]
+    pub summary_threshold_mb: u64,
+}
+
+fn default_hdr10plus_summary_threshold_mb() -> u64 {
+    crate::hdr10plus::DEFAULT_SUMMARY_THRESHOLD_MB
 }
 
 impl Default for Hdr10PlusConfig {
@@ -220,6 +787,7 @@ impl Default for Hdr10PlusConfig {
             bitrate_multiplier: 1.4,
             encoding_complexity: 1.4,
             validate_curves: true,
+            summary_threshold_mb: default_hdr10plus_summary_threshold_mb(),
         }
     }
 }
@@ -230,6 +798,212 @@ pub struct AnalysisConfig {
     pub hdr: Option<UnifiedHdrConfig>,
     pub dolby_vision: Option<DolbyVisionConfig>,
     pub hdr10_plus: Option<Hdr10PlusConfig>,
+    #[serde(default)]
+    pub cache: AnalysisCacheConfig,
+    #[serde(default)]
+    pub black_frames: BlackFrameConfig,
+    #[serde(default)]
+    pub fingerprint: FingerprintConfig,
+    #[serde(default)]
+    pub scene_detection: SceneDetectionConfig,
+    #[serde(default)]
+    pub starved_source: StarvedSourceConfig,
+    /// Count the source's frames exactly (`ffprobe -count_packets`, cached)
+    /// for progress and frame reports, instead of duration × frame rate
+    #[serde(default)]
+    pub count_frames: bool,
+    /// Degrade order when the source's Dolby Vision RPU or HDR10+ metadata
+    /// cannot be carried over (tool missing, extraction or injection failed)
+    #[serde(default = "default_fallback_chain")]
+    pub fallback_chain: Vec<MetadataFallback>,
+}
+
+/// A step of `analysis.fallback_chain`: what the output carries when the
+/// steps before it could not be delivered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataFallback {
+    /// The source's Dolby Vision RPU
+    DolbyVision,
+    /// HDR10+ dynamic metadata (of an HDR10+ or dual-format source)
+    Hdr10Plus,
+    /// Static HDR10 metadata only
+    Hdr10,
+    /// Tone mapped to SDR (BT.709), as with `--tonemap`
+    Sdr,
+    /// Fail the encode
+    Fail,
+}
+
+impl MetadataFallback {
+    /// Position in the fixed order DV > HDR10+ > HDR10 > SDR > fail: a chain
+    /// only ever degrades to steps ranked below the lost metadata
+    pub fn rank(self) -> u8 {
+        match self {
+            Self::DolbyVision => 0,
+            Self::Hdr10Plus => 1,
+            Self::Hdr10 => 2,
+            Self::Sdr => 3,
+            Self::Fail => 4,
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataFallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::DolbyVision => "Dolby Vision",
+            Self::Hdr10Plus => "HDR10+",
+            Self::Hdr10 => "HDR10",
+            Self::Sdr => "SDR (tone mapped)",
+            Self::Fail => "fail",
+        })
+    }
+}
+
+/// DV falls back to the HDR10+ of dual-format sources, then to HDR10
+fn default_fallback_chain() -> Vec<MetadataFallback> {
+    vec![
+        MetadataFallback::DolbyVision,
+        MetadataFallback::Hdr10Plus,
+        MetadataFallback::Hdr10,
+    ]
+}
+
+/// Persistent per-source analysis cache (HDR/DV detection, crop detection, classification)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisCacheConfig {
+    pub enabled: bool,
+    /// Cache directory (defaults to the user cache dir, e.g. ~/.cache/ven/analysis)
+    pub dir: Option<String>,
+}
+
+impl Default for AnalysisCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: None,
+        }
+    }
+}
+
+/// `blackdetect` pre-scan that keeps crop and preview sampling off black frames
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BlackFrameConfig {
+    pub enabled: bool,
+    /// Shortest black stretch (seconds) that is excluded
+    pub min_duration: f64,
+    /// Share of pixels that must be black for a frame to count as black
+    pub picture_threshold: f64,
+    /// Luma threshold (0.0-1.0) below which a pixel counts as black
+    pub pixel_threshold: f64,
+}
+
+impl Default for BlackFrameConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_duration: 0.5,
+            picture_threshold: 0.98,
+            pixel_threshold: 0.10,
+        }
+    }
+}
+
+/// Perceptual fingerprint of every source, kept with the encode reports to
+/// spot a source that was encoded before under another name or resolution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FingerprintConfig {
+    pub enabled: bool,
+    /// Sample frames hashed over the runtime
+    pub samples: usize,
+    /// Largest share of differing hash bits (0.0-1.0) at which two sources
+    /// count as the same content
+    pub max_distance: f64,
+    /// What happens to a source matching an earlier encode
+    pub on_duplicate: DuplicateAction,
+}
+
+impl Default for FingerprintConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            samples: 16,
+            max_distance: 0.1,
+            on_duplicate: DuplicateAction::Warn,
+        }
+    }
+}
+
+/// Scene cut detection forcing keyframes on the cuts of the source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneDetectionConfig {
+    pub enabled: bool,
+    /// Scene change score (0.0-1.0, FFmpeg's `scene`) from which a frame is a cut
+    pub threshold: f64,
+    /// Shortest gap in seconds between forced keyframes; closer cuts are
+    /// left to x265
+    pub min_interval: f64,
+}
+
+impl Default for SceneDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.3,
+            min_interval: 1.0,
+        }
+    }
+}
+
+/// What happens to a source that is already starved of bits
+/// (`analysis.starved_source.action`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StarvedSourceAction {
+    /// Log a warning and encode at the profile's CRF
+    #[default]
+    Warn,
+    /// Lower the CRF by `crf_reduction`, so the encode keeps what detail is left
+    LowerCrf,
+}
+
+/// Gate for sources with very few bits per pixel for their resolution
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StarvedSourceConfig {
+    pub enabled: bool,
+    /// Median bits per pixel (H.264 equivalent) below which a source is starved
+    pub min_bits_per_pixel: f64,
+    pub action: StarvedSourceAction,
+    /// CRF points taken off with `action: lower_crf`
+    pub crf_reduction: f32,
+}
+
+impl Default for StarvedSourceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_bits_per_pixel: 0.05,
+            action: StarvedSourceAction::Warn,
+            crf_reduction: 2.0,
+        }
+    }
+}
+
+/// `analysis.fingerprint.on_duplicate`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DuplicateAction {
+    /// Log a warning and encode anyway
+    #[default]
+    Warn,
+    /// Leave the source alone, listed as skipped in the summary
+    Skip,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -250,6 +1024,50 @@ pub struct DenoiseConfig {
     pub params: String,
 }
 
+/// Banding check and deband filter for sources encoded with an anime profile
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebandConfig {
+    pub enabled: bool,
+    /// Sample frames the banding check looks at
+    pub samples: usize,
+    /// Banding score (share of the picture in stepped gradients) from which
+    /// the source is debanded
+    pub threshold: f64,
+    pub filter: String,
+    pub params: String,
+    /// Debanded encodes use aq-mode 3 and at least this many B-frames
+    pub min_bframes: u32,
+}
+
+impl Default for DebandConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            samples: 6,
+            threshold: 0.15,
+            filter: "gradfun".to_string(),
+            params: "strength=1.2:radius=16".to_string(),
+            min_bframes: 8,
+        }
+    }
+}
+
+/// Extra ffmpeg filters inserted at fixed points of the video filter chain
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct CustomFilters {
+    /// After deinterlace/denoise/deband, before cropping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pre_crop: Option<String>,
+    /// After cropping and scaling, before tone mapping
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_scale: Option<String>,
+    /// Last in the chain, after tone mapping
+    #[serde(rename = "final", skip_serializing_if = "Option::is_none")]
+    pub final_stage: Option<String>,
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct AudioSelectionConfig {
     #[serde(default)]
@@ -264,6 +1082,52 @@ pub struct AudioSelectionConfig {
     pub exclude_commentary: bool,
     #[serde(default)]
     pub max_streams: Option<usize>,
+    /// Per-codec overrides of the transcode bitrate ladder, in kbps per channel
+    /// (e.g. `opus: 80`); keys are codec names such as `aac`, `opus`, `ac3`
+    #[serde(default)]
+    pub bitrate_per_channel: Option<HashMap<String, u32>>,
+    /// Keep only tracks classified as one of these roles
+    #[serde(default)]
+    pub roles: Option<Vec<AudioRole>>,
+    #[serde(default)]
+    pub selection_mode: LanguageSelectionMode,
+    /// Title set on every output audio track, with `{language}`, `{codec}`,
+    /// `{channels}` and `{title}` (the source title) filled in per track
+    #[serde(default)]
+    pub title_template: Option<String>,
+}
+
+/// How the `languages` list of a stream selection profile is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LanguageSelectionMode {
+    /// Keep every stream whose language is in the list
+    #[default]
+    AllMatches,
+    /// Treat the list as a fallback chain: keep only the streams of the first
+    /// language that is present (`und` matches untagged streams)
+    FirstMatch,
+}
+
+/// What an audio track is for, as classified from disposition, title and channel layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioRole {
+    Main,
+    Commentary,
+    AudioDescription,
+    Karaoke,
+}
+
+impl std::fmt::Display for AudioRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AudioRole::Main => "main",
+            AudioRole::Commentary => "commentary",
+            AudioRole::AudioDescription => "audio-description",
+            AudioRole::Karaoke => "karaoke",
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
@@ -282,12 +1146,22 @@ pub struct SubtitleSelectionConfig {
     pub include_forced_only: bool,
     #[serde(default)]
     pub max_streams: Option<usize>,
+    #[serde(default)]
+    pub selection_mode: LanguageSelectionMode,
+    /// OCR PGS tracks to SRT with `tools.subtitle_ocr` when the container
+    /// (MP4) or `--device` needs text subtitles, instead of dropping them
+    #[serde(default)]
+    pub convert_pgs_to_text: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FiltersConfig {
     pub deinterlace: DeinterlaceConfig,
     pub denoise: DenoiseConfig,
+    #[serde(default)]
+    pub deband: DebandConfig,
+    #[serde(default)]
+    pub custom_filters: CustomFilters,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -297,6 +1171,20 @@ pub struct RawProfile {
     pub bitrate: u32,
     pub content_type: String,
     pub x265_params: HashMap<String, serde_yaml::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub container: Option<OutputContainer>,
+    /// File extension for outputs (defaults to the container's extension)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extension: Option<String>,
+    /// Hard ceiling for the video bitrate in kbps (e.g. a player's decoder limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bitrate: Option<u32>,
+    /// Stream selection profile applied when `--stream-selection-profile` is not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stream_selection_profile: Option<String>,
+    /// x265 `--tune`: animation, grain, fastdecode, or auto (from content_type)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tune: Option<EncoderTune>,
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -347,3 +1235,154 @@ pub struct RawPreviewProfile {
     pub title: String,
     pub profiles: Vec<String>,
 }
+
+/// Layout of the thumbnails written after an encode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailKind {
+    /// A grid of frames every `interval` seconds plus a WebVTT file mapping
+    /// each time range to its tile
+    #[default]
+    Sprite,
+    /// `count` frames spread over the video in a single row
+    Strip,
+}
+
+/// Shell commands run when an encode ends, e.g. to notify a media server or
+/// send a message; see [`CommandHook`](crate::events::CommandHook)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationsConfig {
+    /// Run after an output is complete
+    pub on_finished: Option<String>,
+    /// Run after an encode failed or was cancelled
+    pub on_failed: Option<String>,
+}
+
+impl NotificationsConfig {
+    pub fn is_enabled(&self) -> bool {
+        self.on_finished.is_some() || self.on_failed.is_some()
+    }
+}
+
+/// Permissions and ownership given to finished outputs, e.g. when VEN runs
+/// as a service user but the library belongs to another
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputFilesConfig {
+    /// Octal permissions such as `"0644"`; unset keeps the umask's
+    pub mode: Option<String>,
+    /// User name or uid to hand the files to
+    pub owner: Option<String>,
+    /// Group name or gid to hand the files to
+    pub group: Option<String>,
+}
+
+/// Thumbnail images generated next to each output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThumbnailsConfig {
+    /// Generate them after every encode (`--thumbnails` does it for one run)
+    pub enabled: bool,
+    pub kind: ThumbnailKind,
+    /// Seconds between sprite thumbnails
+    pub interval: f64,
+    /// Thumbnails in a strip
+    pub count: u32,
+    /// Width of one thumbnail in pixels; the height follows the aspect ratio
+    pub width: u32,
+    /// Thumbnails per sprite row
+    pub columns: u32,
+}
+
+impl Default for ThumbnailsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: ThumbnailKind::Sprite,
+            interval: 10.0,
+            count: 8,
+            width: 320,
+            columns: 10,
+        }
+    }
+}
+
+/// A full-reference metric comparing an output with its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityMetric {
+    /// Netflix VMAF (0-100), needs an FFmpeg built with libvmaf
+    Vmaf,
+    /// Structural similarity (0-1)
+    Ssim,
+    /// Extended perceptually weighted PSNR in dB (FFmpeg 7.1+)
+    Xpsnr,
+    /// libjxl's butteraugli distance on sampled frames (lower is better),
+    /// needs `tools.butteraugli`
+    Butteraugli,
+}
+
+/// How HDR outputs are compared with their source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityHdrMode {
+    /// Compare the PQ/HLG signals directly, with `hdr_vmaf_model` for VMAF
+    #[default]
+    Pq,
+    /// Tone map both sides to SDR the same way and use the SDR models
+    Tonemap,
+}
+
+/// Quality measurement after an encode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualityConfig {
+    /// Measure after every encode (`--quality-check` does it for one run)
+    pub enabled: bool,
+    pub metrics: Vec<QualityMetric>,
+    /// VMAF scores every Nth frame
+    pub vmaf_subsample: u32,
+    /// Frames compared with butteraugli, spread over the video
+    pub butteraugli_frames: u32,
+    /// Butteraugli always compares tone mapped frames of HDR outputs
+    pub hdr_mode: QualityHdrMode,
+    /// VMAF model for HDR in `pq` mode; the NEG model does not reward the
+    /// contrast boosts the default model overrates on PQ signals
+    pub hdr_vmaf_model: String,
+}
+
+impl Default for QualityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            metrics: vec![QualityMetric::Vmaf, QualityMetric::Ssim],
+            vmaf_subsample: 5,
+            butteraugli_frames: 5,
+            hdr_mode: QualityHdrMode::Pq,
+            hdr_vmaf_model: "vmaf_v0.6.1neg".to_string(),
+        }
+    }
+}
+
+/// Preset planning for `encode --time-budget`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlanningConfig {
+    /// x265 presets to choose from, slowest first
+    pub presets: Vec<String>,
+    /// Seconds of each file encoded to measure its speed
+    pub probe_seconds: f64,
+}
+
+impl Default for PlanningConfig {
+    fn default() -> Self {
+        Self {
+            presets: ["veryslow", "slower", "slow", "medium", "fast", "faster"]
+                .iter()
+                .map(|preset| preset.to_string())
+                .collect(),
+            probe_seconds: 10.0,
+        }
+    }
+}
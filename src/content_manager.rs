@@ -3,6 +3,7 @@ use crate::config::DolbyVisionConfig;
 use crate::config::UnifiedHdrConfig;
 use crate::hdr::{HdrAnalysisResult, HdrFormat, HdrManager};
 use crate::hdr10plus::{Hdr10PlusManager, Hdr10PlusProcessingResult};
+use crate::log_stage;
 use crate::utils::{FfmpegWrapper, Result};
 use std::path::Path;
 use tracing::{debug, info, warn};
@@ -81,6 +82,15 @@ impl UnifiedContentManager {
         }
     }
 
+    /// Directory for the HDR10+ metadata extracted during analysis
+    /// (`/tmp` by default).
+    pub fn with_temp_dir(mut self, temp_dir: &Path) -> Self {
+        self.hdr10plus_manager = self
+            .hdr10plus_manager
+            .map(|manager| manager.with_temp_dir(temp_dir));
+        self
+    }
+
     pub async fn analyze_hdr_only<P: AsRef<Path>>(
         &self,
         ffmpeg: &FfmpegWrapper,
@@ -105,7 +115,20 @@ impl UnifiedContentManager {
         input_path: P,
         existing_hdr_analysis: Option<HdrAnalysisResult>,
     ) -> Result<ContentAnalysisResult> {
-        info!(
+        self.analyze_content_with_reuse(ffmpeg, input_path, existing_hdr_analysis, None)
+            .await
+    }
+
+    /// Like [`Self::analyze_content_with_hdr_reuse`], but also skips Dolby Vision
+    /// detection when a previous result is supplied (e.g. from the analysis cache).
+    pub async fn analyze_content_with_reuse<P: AsRef<Path>>(
+        &self,
+        ffmpeg: &FfmpegWrapper,
+        input_path: P,
+        existing_hdr_analysis: Option<HdrAnalysisResult>,
+        existing_dv_info: Option<DolbyVisionInfo>,
+    ) -> Result<ContentAnalysisResult> {
+        log_stage!(
             "Starting unified content analysis for: {}",
             input_path.as_ref().display()
         );
@@ -125,7 +148,10 @@ impl UnifiedContentManager {
             result
         };
 
-        let dv_info = if let Some(ref detector) = self.dv_detector {
+        let dv_info = if let Some(dv_info) = existing_dv_info {
+            debug!("Reusing existing Dolby Vision analysis");
+            dv_info
+        } else if let Some(ref detector) = self.dv_detector {
             detector.analyze(ffmpeg, &input_path).await?
         } else {
             DolbyVisionInfo::none()
@@ -136,9 +162,10 @@ impl UnifiedContentManager {
         );
 
         let hdr10plus_result = if let Some(ref manager) = self.hdr10plus_manager {
+            // HDR10+ is PQ-only, so an HLG stream (including DV 8.4) never carries usable metadata
             if hdr_analysis.metadata.format == HdrFormat::HDR10Plus
                 || hdr_analysis.metadata.format == HdrFormat::HDR10
-                || dv_info.is_dolby_vision()
+                || (dv_info.is_dolby_vision() && hdr_analysis.metadata.format != HdrFormat::HLG)
             {
                 info!("Checking for HDR10+ dynamic metadata");
                 if dv_info.is_dolby_vision() {
@@ -168,7 +195,7 @@ impl UnifiedContentManager {
 
         let approach =
             self.determine_encoding_approach(&hdr_analysis, &dv_info, hdr10plus_result.as_ref());
-        info!("Recommended encoding approach: {:?}", approach);
+        log_stage!("Recommended encoding approach: {:?}", approach);
 
         let adjustments = self.calculate_encoding_adjustments(&approach, &hdr_analysis, &dv_info);
 
@@ -187,14 +214,30 @@ impl UnifiedContentManager {
         dv: &DolbyVisionInfo,
         hdr10plus_result: Option<&Hdr10PlusProcessingResult>,
     ) -> ContentEncodingApproach {
+        // HDR10+ dynamic metadata is only defined for PQ streams
+        let is_pq = matches!(hdr.metadata.format, HdrFormat::HDR10 | HdrFormat::HDR10Plus);
+        if hdr10plus_result.is_some() && !is_pq {
+            warn!(
+                "HDR10+ metadata found on a {:?} stream; ignoring it (HDR10+ requires PQ)",
+                hdr.metadata.format
+            );
+        }
+        let has_hdr10plus =
+            hdr.metadata.format == HdrFormat::HDR10Plus || (hdr10plus_result.is_some() && is_pq);
+
         if dv.is_dolby_vision() {
             if let Some(ref config) = self.dv_config {
                 if config.enabled {
                     if let Some(ref detector) = self.dv_detector {
-                        if detector.should_preserve_dolby_vision(dv) {
-                            let has_hdr10plus = hdr.metadata.format == HdrFormat::HDR10Plus
-                                || hdr10plus_result.is_some();
-
+                        let conflict = base_layer_conflict(dv.profile, hdr.metadata.format);
+                        if let Some(reason) = conflict {
+                            warn!(
+                                "Dolby Vision profile {} does not match the stream: {}",
+                                dv.profile.as_str(),
+                                reason
+                            );
+                        }
+                        if conflict.is_none() && detector.should_preserve_dolby_vision(dv) {
                             if has_hdr10plus {
                                 info!("Dual format detected: Dolby Vision + HDR10+");
                                 return ContentEncodingApproach::DolbyVisionWithHDR10Plus(
@@ -383,10 +426,125 @@ impl UnifiedContentManager {
     }
 }
 
+/// Why a Dolby Vision profile contradicts the base layer's transfer function.
+///
+/// Profile 8.4 is the only one with an HLG base layer; every other profile
+/// expects PQ (or, for 8.2, SDR), so a mismatch means the signaling is broken
+/// and the stream is encoded by its base layer alone.
+fn base_layer_conflict(profile: DolbyVisionProfile, format: HdrFormat) -> Option<&'static str> {
+    match (profile, format) {
+        (DolbyVisionProfile::Profile84, HdrFormat::HDR10 | HdrFormat::HDR10Plus) => {
+            Some("profile 8.4 needs an HLG base layer, but the stream is PQ")
+        }
+        (
+            DolbyVisionProfile::Profile5
+            | DolbyVisionProfile::Profile7
+            | DolbyVisionProfile::Profile81
+            | DolbyVisionProfile::Profile82,
+            HdrFormat::HLG,
+        ) => Some("only profile 8.4 has an HLG base layer"),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::hdr::HdrMetadata;
+    use crate::hdr::{HdrMetadata, HdrMetadataExtractor};
+    use crate::hdr10plus::Hdr10PlusMetadata;
+
+    fn analysis_for(format: HdrFormat) -> HdrAnalysisResult {
+        HdrAnalysisResult {
+            metadata: HdrMetadata {
+                format,
+                ..HdrMetadataExtractor::get_default_metadata_for_format(format)
+            },
+            confidence_score: 1.0,
+            requires_tone_mapping: false,
+            encoding_complexity: 1.0,
+        }
+    }
+
+    fn approach_label(approach: &ContentEncodingApproach) -> &'static str {
+        match approach {
+            ContentEncodingApproach::SDR => "SDR",
+            ContentEncodingApproach::HDR(_) => "HDR",
+            ContentEncodingApproach::DolbyVision(_) => "DV",
+            ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => "DV+HDR10+",
+        }
+    }
+
+    #[test]
+    fn test_format_combination_matrix() {
+        use DolbyVisionProfile::{Profile5, Profile7, Profile81, Profile82, Profile84};
+        const NO_DV: DolbyVisionProfile = DolbyVisionProfile::None;
+        use HdrFormat::{HDR10Plus, None as Sdr, HDR10, HLG};
+
+        let manager = UnifiedContentManager::new(
+            UnifiedHdrConfig::default(),
+            Some(DolbyVisionConfig::default()),
+            None,
+        );
+        let hdr10plus = Hdr10PlusProcessingResult::new(
+            std::path::PathBuf::from("/tmp/hdr10plus.json"),
+            Hdr10PlusMetadata::default(),
+            true,
+        );
+
+        // (DV profile, base layer, expected without / with extracted HDR10+ metadata)
+        let matrix = [
+            (NO_DV, Sdr, "SDR", "SDR"),
+            (NO_DV, HDR10, "HDR", "HDR"),
+            (NO_DV, HDR10Plus, "HDR", "HDR"),
+            (NO_DV, HLG, "HDR", "HDR"),
+            (Profile5, Sdr, "DV", "DV"),
+            (Profile5, HDR10, "DV", "DV+HDR10+"),
+            (Profile5, HDR10Plus, "DV+HDR10+", "DV+HDR10+"),
+            (Profile5, HLG, "HDR", "HDR"),
+            (Profile7, Sdr, "DV", "DV"),
+            (Profile7, HDR10, "DV", "DV+HDR10+"),
+            (Profile7, HDR10Plus, "DV+HDR10+", "DV+HDR10+"),
+            (Profile7, HLG, "HDR", "HDR"),
+            (Profile81, Sdr, "DV", "DV"),
+            (Profile81, HDR10, "DV", "DV+HDR10+"),
+            (Profile81, HDR10Plus, "DV+HDR10+", "DV+HDR10+"),
+            (Profile81, HLG, "HDR", "HDR"),
+            (Profile82, Sdr, "DV", "DV"),
+            (Profile82, HDR10, "DV", "DV+HDR10+"),
+            (Profile82, HDR10Plus, "DV+HDR10+", "DV+HDR10+"),
+            (Profile82, HLG, "HDR", "HDR"),
+            (Profile84, Sdr, "DV", "DV"),
+            (Profile84, HDR10, "HDR", "HDR"),
+            (Profile84, HDR10Plus, "HDR", "HDR"),
+            (Profile84, HLG, "DV", "DV"),
+        ];
+
+        for (profile, format, expected, expected_with_hdr10plus) in matrix {
+            let hdr = analysis_for(format);
+            let dv = DolbyVisionInfo {
+                profile,
+                has_rpu: profile != NO_DV,
+                ..Default::default()
+            };
+            for (result, expected) in [
+                (None, expected),
+                (Some(&hdr10plus), expected_with_hdr10plus),
+            ] {
+                let approach = manager.determine_encoding_approach(&hdr, &dv, result);
+                assert_eq!(
+                    approach_label(&approach),
+                    expected,
+                    "profile {} on {:?}, HDR10+ metadata: {}",
+                    profile.as_str(),
+                    format,
+                    result.is_some()
+                );
+                let adjustments = manager.calculate_encoding_adjustments(&approach, &hdr, &dv);
+                let (min_crf, max_crf) = adjustments.recommended_crf_range;
+                assert!(min_crf <= max_crf);
+            }
+        }
+    }
 
     #[test]
     fn test_sdr_content_adjustments() {
@@ -0,0 +1,210 @@
+//! Dolby Vision -> HDR10+ conversion
+//!
+//! Builds HDR10+ dynamic metadata (hdr10plus_tool JSON layout, consumed by
+//! x265 `--dhdr10-info`) from the per-shot L1 analysis in a Dolby Vision RPU.
+//! L1 only carries min/avg/max luminance, so the result is an approximation:
+//! the luminance distribution is interpolated, no Bezier curves are produced
+//! and the L2/L8 trims that steer DV tone mapping are lost.
+
+use crate::hdr10plus::metadata::{
+    BezierCurveData, Hdr10PlusMetadata, JsonInfo, LuminanceDistributions, LuminanceParameters,
+    SceneMetadata, ToolInfo,
+};
+use crate::utils::{Error, Result};
+use serde_json::Value;
+
+/// Percentiles written to `LuminanceDistributions`, as produced by hdr10plus_tool.
+const DISTRIBUTION_INDEX: [u32; 9] = [1, 5, 10, 25, 50, 75, 90, 95, 99];
+
+/// L1 metadata of one frame: 12-bit PQ codes of the minimum, average and maximum luminance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct L1Metadata {
+    pub min_pq: u16,
+    pub max_pq: u16,
+    pub avg_pq: u16,
+}
+
+impl L1Metadata {
+    fn from_value(value: &Value) -> Option<Self> {
+        let field = |name: &str| value.get(name)?.as_u64().map(|v| v.min(4095) as u16);
+        Some(Self {
+            min_pq: field("min_pq")?,
+            max_pq: field("max_pq")?,
+            avg_pq: field("avg_pq")?,
+        })
+    }
+
+    /// Finds the first L1 block anywhere inside one exported RPU.
+    fn find(value: &Value) -> Option<Self> {
+        match value {
+            Value::Object(map) => {
+                Self::from_value(value).or_else(|| map.values().find_map(Self::find))
+            }
+            Value::Array(items) => items.iter().find_map(Self::find),
+            _ => None,
+        }
+    }
+}
+
+/// SMPTE ST 2084 EOTF for a 12-bit PQ code, in cd/m².
+pub fn pq_to_nits(code: u16) -> f64 {
+    const M1: f64 = 2610.0 / 16384.0;
+    const M2: f64 = 2523.0 / 4096.0 * 128.0;
+    const C1: f64 = 3424.0 / 4096.0;
+    const C2: f64 = 2413.0 / 4096.0 * 32.0;
+    const C3: f64 = 2392.0 / 4096.0 * 32.0;
+
+    let e = (f64::from(code) / 4095.0).powf(1.0 / M2);
+    let linear = ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1);
+    linear * 10000.0
+}
+
+/// HDR10+ luminance values are linear light in units of 0.1 cd/m².
+fn hdr10plus_units(code: u16) -> u32 {
+    (pq_to_nits(code) * 10.0).round() as u32
+}
+
+/// Parses `dovi_tool export -d all=...` output (a JSON array with one entry per
+/// frame) into per-frame L1 metadata. Frames without an L1 block inherit the
+/// previous frame's values, as DV players do.
+pub fn parse_l1_export(json: &str) -> Result<Vec<L1Metadata>> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|e| Error::parse(format!("Failed to parse dovi_tool export: {}", e)))?;
+    let frames = value
+        .as_array()
+        .ok_or_else(|| Error::parse("dovi_tool export is not a JSON array of RPUs"))?;
+
+    let per_frame: Vec<Option<L1Metadata>> = frames.iter().map(L1Metadata::find).collect();
+    let first = per_frame.iter().flatten().next().copied().ok_or_else(|| {
+        Error::DolbyVision("RPU contains no L1 metadata to convert to HDR10+".to_string())
+    })?;
+
+    let mut current = first;
+    Ok(per_frame
+        .into_iter()
+        .map(|l1| {
+            if let Some(l1) = l1 {
+                current = l1;
+            }
+            current
+        })
+        .collect())
+}
+
+fn distribution_values(l1: &L1Metadata) -> Vec<u32> {
+    let (min, avg, max) = (
+        f64::from(l1.min_pq),
+        f64::from(l1.avg_pq),
+        f64::from(l1.max_pq),
+    );
+    DISTRIBUTION_INDEX
+        .iter()
+        .map(|&percentile| {
+            // Interpolate in PQ space: min -> avg over the lower half, avg -> max above
+            let p = f64::from(percentile);
+            let code = if p <= 50.0 {
+                min + (avg - min) * p / 50.0
+            } else {
+                avg + (max - avg) * (p - 50.0) / 50.0
+            };
+            hdr10plus_units(code.round() as u16)
+        })
+        .collect()
+}
+
+/// Converts per-frame L1 metadata into HDR10+ (profile A) metadata. A new scene
+/// starts whenever the L1 values change, matching DV's per-shot analysis.
+pub fn l1_to_hdr10plus(frames: &[L1Metadata]) -> Hdr10PlusMetadata {
+    let mut scene_info = Vec::with_capacity(frames.len());
+    let mut scene_id = 0;
+    let mut scene_frame_index = 0;
+
+    for (index, l1) in frames.iter().enumerate() {
+        if index > 0 {
+            if frames[index - 1] != *l1 {
+                scene_id += 1;
+                scene_frame_index = 0;
+            } else {
+                scene_frame_index += 1;
+            }
+        }
+
+        let max = hdr10plus_units(l1.max_pq);
+        scene_info.push(SceneMetadata {
+            scene_id,
+            scene_frame_index,
+            sequence_frame_index: index as u32,
+            number_of_windows: 1,
+            targeted_system_display_maximum_luminance: 0,
+            bezier_curve_data: BezierCurveData {
+                knee_point_x: 0,
+                knee_point_y: 0,
+                anchors: Vec::new(),
+            },
+            luminance_parameters: LuminanceParameters {
+                average_rgb: hdr10plus_units(l1.avg_pq),
+                max_scl: vec![max, max, max],
+                luminance_distributions: Some(LuminanceDistributions {
+                    distribution_index: DISTRIBUTION_INDEX.to_vec(),
+                    distribution_values: distribution_values(l1),
+                }),
+            },
+        });
+    }
+
+    Hdr10PlusMetadata {
+        json_info: JsonInfo {
+            hdr10plus_profile: "A".to_string(),
+            version: "1.0".to_string(),
+        },
+        scene_info,
+        tool_info: Some(ToolInfo {
+            tool: "ven (converted from Dolby Vision L1)".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pq_to_nits() {
+        assert!((pq_to_nits(4095) - 10000.0).abs() < 0.5);
+        assert_eq!(pq_to_nits(0), 0.0);
+        // ~1000 cd/m² sits at roughly 75% of the PQ range
+        assert!((pq_to_nits(3079) - 1000.0).abs() < 5.0);
+    }
+
+    #[test]
+    fn test_export_to_hdr10plus() {
+        let export = r#"[
+            {"vdr_dm_data": {"cmv29_metadata": {"ext_metadata_blocks": [
+                {"Level1": {"min_pq": 0, "max_pq": 3079, "avg_pq": 1500}}]}}},
+            {"vdr_dm_data": {"cmv29_metadata": {"ext_metadata_blocks": []}}},
+            {"vdr_dm_data": {"cmv29_metadata": {"ext_metadata_blocks": [
+                {"Level1": {"min_pq": 10, "max_pq": 2081, "avg_pq": 900}}]}}}
+        ]"#;
+
+        let frames = parse_l1_export(export).unwrap();
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1], frames[0]);
+
+        let metadata = l1_to_hdr10plus(&frames);
+        metadata.validate().unwrap();
+        assert_eq!(metadata.get_scene_count(), 2);
+        assert_eq!(metadata.scene_info[1].scene_frame_index, 1);
+
+        let first = &metadata.scene_info[0].luminance_parameters;
+        assert!((9950..=10050).contains(&first.max_scl[0]));
+        let distribution = &first
+            .luminance_distributions
+            .as_ref()
+            .unwrap()
+            .distribution_values;
+        assert!(distribution.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert!(parse_l1_export("[{}]").is_err());
+    }
+}
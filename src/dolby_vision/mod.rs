@@ -1,3 +1,4 @@
+pub mod hdr10plus;
 pub mod rpu;
 pub mod tools;
 
@@ -5,7 +5,10 @@ use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use crate::analysis::dolby_vision::{DolbyVisionInfo, DolbyVisionProfile};
+use crate::dolby_vision::hdr10plus::{l1_to_hdr10plus, parse_l1_export};
 use crate::dolby_vision::tools::DoviTool;
+use crate::hdr10plus::metadata::Hdr10PlusProcessingResult;
+use crate::log_step;
 use crate::mkvmerge::MkvMergeTool;
 use crate::utils::{Error, Result};
 
@@ -56,10 +59,25 @@ impl RpuMetadata {
     }
 }
 
+/// `dovi_tool editor` ranges (inclusive) removing every frame of a
+/// `total`-frame RPU outside `frames`.
+pub fn remove_ranges(frames: std::ops::Range<u64>, total: u64) -> Vec<String> {
+    let mut ranges = Vec::new();
+    if frames.start > 0 {
+        ranges.push(format!("0-{}", frames.start - 1));
+    }
+    if frames.end < total {
+        ranges.push(format!("{}-{}", frames.end, total - 1));
+    }
+    ranges
+}
+
 pub struct RpuManager {
     temp_dir: PathBuf,
     dovi_tool: Option<DoviTool>,
     mkvmerge_tool: Option<MkvMergeTool>,
+    /// Leave the intermediate files of the injection in place (`--keep-temp`)
+    keep_temp: bool,
 }
 
 impl RpuManager {
@@ -68,6 +86,30 @@ impl RpuManager {
             temp_dir,
             dovi_tool,
             mkvmerge_tool,
+            keep_temp: false,
+        }
+    }
+
+    pub fn with_keep_temp(mut self, keep: bool) -> Self {
+        self.keep_temp = keep;
+        self
+    }
+
+    /// Removes an intermediate file of the injection, or logs it with `keep_temp`.
+    async fn remove_temp(&self, path: &Path) {
+        if !path.exists() {
+            return;
+        }
+        if self.keep_temp {
+            info!("Kept temporary file: {}", path.display());
+        } else if let Err(e) = fs::remove_file(path).await {
+            warn!(
+                "Failed to clean up temporary file {}: {}",
+                path.display(),
+                e
+            );
+        } else {
+            debug!("Cleaned up temporary file: {}", path.display());
         }
     }
 
@@ -96,21 +138,10 @@ impl RpuManager {
             )
         })?;
 
-        // Generate RPU file alongside the source video
-        let input_path_ref = input_path.as_ref();
-        let rpu_filename = if let Some(stem) = input_path_ref.file_stem() {
-            format!("{}_rpu_{}.bin", stem.to_string_lossy(), Uuid::new_v4())
-        } else {
-            format!("rpu_{}.bin", Uuid::new_v4())
-        };
-
-        let rpu_path = if let Some(parent) = input_path_ref.parent() {
-            parent.join(rpu_filename)
-        } else {
-            PathBuf::from(rpu_filename)
-        };
+        self.ensure_temp_dir().await?;
+        let rpu_path = self.temp_dir.join(format!("rpu_{}.bin", Uuid::new_v4()));
 
-        info!(
+        log_step!(
             "Extracting RPU metadata from: {}",
             input_path.as_ref().display()
         );
@@ -184,10 +215,7 @@ impl RpuManager {
         let encoded_mkv = encoded_mkv_path.as_ref();
         let final_output = final_output_path.as_ref();
 
-        info!(
-            "Injecting RPU metadata into: {}",
-            encoded_mkv.display()
-        );
+        log_step!("Injecting RPU metadata into: {}", encoded_mkv.display());
 
         // Step 1: Extract raw HEVC bitstream from MKV
         let temp_hevc = if let Some(parent) = encoded_mkv.parent() {
@@ -216,7 +244,7 @@ impl RpuManager {
 
         if !extract_status.status.success() {
             let stderr = String::from_utf8_lossy(&extract_status.stderr);
-            let _ = fs::remove_file(&temp_hevc).await;
+            self.remove_temp(&temp_hevc).await;
             return Err(Error::Ffmpeg {
                 message: format!("Failed to extract HEVC from MKV: {}", stderr),
             });
@@ -232,7 +260,7 @@ impl RpuManager {
             PathBuf::from(format!("temp_hevc_rpu_{}.hevc", Uuid::new_v4()))
         };
 
-        info!("  Step 2/3: Injecting RPU metadata into HEVC bitstream...");
+        log_step!("  Step 2/3: Injecting RPU metadata into HEVC bitstream...");
         debug!("    Input HEVC: {}", temp_hevc.display());
         debug!("    RPU file: {}", rpu_metadata.temp_file.display());
         debug!("    Output HEVC+RPU: {}", hevc_with_rpu.display());
@@ -245,14 +273,14 @@ impl RpuManager {
                 info!("    RPU injection successful!");
             }
             Err(e) => {
-                let _ = fs::remove_file(&temp_hevc).await;
-                let _ = fs::remove_file(&hevc_with_rpu).await;
+                self.remove_temp(&temp_hevc).await;
+                self.remove_temp(&hevc_with_rpu).await;
                 return Err(e);
             }
         }
 
         // Clean up intermediate HEVC file
-        let _ = fs::remove_file(&temp_hevc).await;
+        self.remove_temp(&temp_hevc).await;
 
         // Step 3: Remux HEVC+RPU back into MKV with all streams using mkvmerge
         info!("  Step 3/3: Remuxing HEVC+RPU back into MKV with all streams...");
@@ -273,23 +301,9 @@ impl RpuManager {
             .await
         {
             Ok(_) => {
-                // Clean up HEVC+RPU file
-                let _ = fs::remove_file(&hevc_with_rpu).await;
-
-                // Clean up the temporary encoded MKV file (no longer needed)
-                if encoded_mkv.exists() {
-                    match fs::remove_file(encoded_mkv).await {
-                        Ok(_) => debug!(
-                            "Cleaned up temporary encoded file: {}",
-                            encoded_mkv.display()
-                        ),
-                        Err(e) => warn!(
-                            "Failed to clean up temporary encoded file {}: {}",
-                            encoded_mkv.display(),
-                            e
-                        ),
-                    }
-                }
+                // Clean up HEVC+RPU file and the temporary encoded MKV (no longer needed)
+                self.remove_temp(&hevc_with_rpu).await;
+                self.remove_temp(encoded_mkv).await;
 
                 info!("Successfully injected Dolby Vision RPU metadata!");
                 info!("  Profile: {}", rpu_metadata.profile.as_str());
@@ -299,12 +313,151 @@ impl RpuManager {
             }
             Err(e) => {
                 // Clean up HEVC+RPU file on failure
-                let _ = fs::remove_file(&hevc_with_rpu).await;
+                self.remove_temp(&hevc_with_rpu).await;
                 Err(e)
             }
         }
     }
 
+    /// Rewrites the RPU for a cropped encode: `active_area.crop` zeroes the L5
+    /// letterbox offsets, since the black bars are no longer part of the frame.
+    pub async fn reset_active_area(&self, rpu_metadata: &mut RpuMetadata) -> Result<()> {
+        let dovi_tool = self.dovi_tool.as_ref().ok_or_else(|| {
+            Error::DolbyVision("dovi_tool not configured but required for RPU editing".to_string())
+        })?;
+
+        let edit_path = rpu_metadata.temp_file.with_extension("crop.json");
+        let edited_path = rpu_metadata.temp_file.with_extension("cropped.bin");
+        fs::write(&edit_path, r#"{"active_area": {"crop": true}}"#).await?;
+
+        let result = dovi_tool
+            .edit_rpu(&rpu_metadata.temp_file, &edit_path, &edited_path)
+            .await;
+        let _ = fs::remove_file(&edit_path).await;
+        if let Err(e) = result {
+            let _ = fs::remove_file(&edited_path).await;
+            return Err(e);
+        }
+
+        fs::rename(&edited_path, &rpu_metadata.temp_file).await?;
+        rpu_metadata.validate().await?;
+        info!("Reset Dolby Vision L5 active area for the cropped frame");
+        Ok(())
+    }
+
+    /// Cuts the RPU of a `total`-frame source down to `frames`, for an encode
+    /// of only that range.
+    pub async fn trim(
+        &self,
+        rpu_metadata: &mut RpuMetadata,
+        frames: std::ops::Range<u64>,
+        total: u64,
+    ) -> Result<()> {
+        let dovi_tool = self.dovi_tool.as_ref().ok_or_else(|| {
+            Error::DolbyVision("dovi_tool not configured but required for RPU editing".to_string())
+        })?;
+
+        let edit_path = rpu_metadata.temp_file.with_extension("trim.json");
+        let trimmed_path = rpu_metadata.temp_file.with_extension("trimmed.bin");
+        let edit = serde_json::json!({ "remove": remove_ranges(frames.clone(), total) });
+        fs::write(&edit_path, edit.to_string()).await?;
+
+        let result = dovi_tool
+            .edit_rpu(&rpu_metadata.temp_file, &edit_path, &trimmed_path)
+            .await;
+        let _ = fs::remove_file(&edit_path).await;
+        if let Err(e) = result {
+            let _ = fs::remove_file(&trimmed_path).await;
+            return Err(e);
+        }
+
+        fs::rename(&trimmed_path, &rpu_metadata.temp_file).await?;
+        rpu_metadata.frame_count = Some(frames.end.min(total) - frames.start);
+        rpu_metadata.validate().await?;
+        info!(
+            "Trimmed the Dolby Vision RPU to frames {}-{}",
+            frames.start,
+            frames.end.min(total) - 1
+        );
+        Ok(())
+    }
+
+    /// Rewrites a profile 5 RPU as profile 8.1 (`dovi_tool` mode 3), for a
+    /// base layer that was reshaped to HDR10 before encoding.
+    pub async fn convert_profile5_to_81(&self, rpu_metadata: &mut RpuMetadata) -> Result<()> {
+        let dovi_tool = self.dovi_tool.as_ref().ok_or_else(|| {
+            Error::DolbyVision(
+                "dovi_tool not configured but required for RPU conversion".to_string(),
+            )
+        })?;
+
+        let edit_path = rpu_metadata.temp_file.with_extension("p81.json");
+        let converted_path = rpu_metadata.temp_file.with_extension("p81.bin");
+        fs::write(&edit_path, r#"{"mode": 3}"#).await?;
+
+        let result = dovi_tool
+            .edit_rpu(&rpu_metadata.temp_file, &edit_path, &converted_path)
+            .await;
+        let _ = fs::remove_file(&edit_path).await;
+        if let Err(e) = result {
+            let _ = fs::remove_file(&converted_path).await;
+            return Err(e);
+        }
+
+        fs::rename(&converted_path, &rpu_metadata.temp_file).await?;
+        rpu_metadata.profile = DolbyVisionProfile::Profile81;
+        rpu_metadata.validate().await?;
+        info!("Converted the Dolby Vision profile 5 RPU to profile 8.1");
+        Ok(())
+    }
+
+    /// Converts the RPU's L1 metadata into an HDR10+ JSON file for x265 `--dhdr10-info`.
+    /// Lossy: see [`crate::dolby_vision::hdr10plus`] for what does not survive.
+    pub async fn convert_to_hdr10plus(
+        &self,
+        rpu_metadata: &RpuMetadata,
+    ) -> Result<Hdr10PlusProcessingResult> {
+        let dovi_tool = self.dovi_tool.as_ref().ok_or_else(|| {
+            Error::DolbyVision(
+                "dovi_tool not configured but required for HDR10+ conversion".to_string(),
+            )
+        })?;
+
+        let export_path = rpu_metadata.temp_file.with_extension("export.json");
+        let hdr10plus_path = rpu_metadata.temp_file.with_extension("hdr10plus.json");
+
+        info!("Exporting RPU L1 metadata for HDR10+ conversion...");
+        let exported = dovi_tool
+            .export_rpu(&rpu_metadata.temp_file, &export_path)
+            .await;
+        let json = match exported {
+            Ok(_) => fs::read_to_string(&export_path).await,
+            Err(e) => {
+                let _ = fs::remove_file(&export_path).await;
+                return Err(e);
+            }
+        };
+        let _ = fs::remove_file(&export_path).await;
+
+        let frames = parse_l1_export(&json?)?;
+        let metadata = l1_to_hdr10plus(&frames);
+        metadata.validate()?;
+        metadata.to_json_file(&hdr10plus_path).await?;
+
+        let mut result = Hdr10PlusProcessingResult::new(hdr10plus_path, metadata, true);
+        result.file_size = fs::metadata(&result.metadata_file)
+            .await
+            .ok()
+            .map(|m| m.len());
+
+        info!(
+            "Converted {} frames ({} scenes) of Dolby Vision L1 metadata to HDR10+",
+            frames.len(),
+            result.scene_count
+        );
+        Ok(result)
+    }
+
     /// Clean up temporary RPU file
     pub fn cleanup_rpu(&self, rpu_metadata: &RpuMetadata) {
         if rpu_metadata.temp_file.exists() {
@@ -375,37 +528,6 @@ impl RpuManager {
     }
 }
 
-impl Drop for RpuManager {
-    fn drop(&mut self) {
-        // Best effort cleanup on drop
-        if self.temp_dir.exists() {
-            let _ = std::thread::spawn({
-                let temp_dir = self.temp_dir.clone();
-                move || {
-                    let rt = tokio::runtime::Handle::try_current();
-                    if let Ok(handle) = rt {
-                        handle.block_on(async {
-                            let mut dir = match tokio::fs::read_dir(&temp_dir).await {
-                                Ok(dir) => dir,
-                                Err(_) => return,
-                            };
-
-                            while let Ok(Some(entry)) = dir.next_entry().await {
-                                let path = entry.path();
-                                if let Some(filename) = path.file_name() {
-                                    if filename.to_string_lossy().starts_with("rpu_") {
-                                        let _ = tokio::fs::remove_file(&path).await;
-                                    }
-                                }
-                            }
-                        });
-                    }
-                }
-            });
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -422,6 +544,14 @@ mod tests {
         assert_eq!(metadata.frame_count, None);
     }
 
+    #[test]
+    fn test_remove_ranges() {
+        assert_eq!(remove_ranges(240..1200, 2000), ["0-239", "1200-1999"]);
+        assert_eq!(remove_ranges(0..1200, 2000), ["1200-1999"]);
+        assert_eq!(remove_ranges(240..2000, 2000), ["0-239"]);
+        assert!(remove_ranges(0..2000, 2000).is_empty());
+    }
+
     #[tokio::test]
     async fn test_rpu_manager_temp_dir_creation() {
         let temp_dir = tempdir().unwrap();
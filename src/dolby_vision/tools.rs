@@ -1,4 +1,7 @@
-use crate::utils::{Result, ToolConfig, ToolRunner};
+use crate::tools::{ExternalTool, Version};
+use crate::utils::{
+    CancelToken, FfmpegWrapper, Result, ToolConfig, ToolInput, ToolProgress, ToolRunner,
+};
 use std::path::Path;
 use tracing::{debug, info};
 
@@ -6,17 +9,49 @@ pub type DoviToolConfig = ToolConfig;
 
 pub struct DoviTool {
     tool: ToolRunner,
+    ffmpeg_pipe: Option<FfmpegWrapper>,
 }
 
 impl DoviTool {
     pub fn new(config: DoviToolConfig) -> Self {
         Self {
             tool: ToolRunner::new(config),
+            ffmpeg_pipe: None,
         }
     }
 
+    /// Feeds extraction from `ffmpeg` (the HEVC elementary stream of the video
+    /// stream it selects, on stdin) instead of letting the tool open the
+    /// source, which it can only do for raw HEVC files.
+    pub fn with_ffmpeg_pipe(mut self, ffmpeg: FfmpegWrapper) -> Self {
+        self.ffmpeg_pipe = Some(ffmpeg);
+        self
+    }
+
+    fn source<'a>(&'a self, path: &'a Path) -> ToolInput<'a> {
+        match &self.ffmpeg_pipe {
+            Some(ffmpeg) => ToolInput::HevcPipe {
+                ffmpeg,
+                source: path,
+            },
+            None => ToolInput::File(path),
+        }
+    }
+
+    /// Lets `cancel` stop a running RPU extraction.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.tool.set_cancel_token(cancel);
+        self
+    }
+
+    /// Reports the tool's progress through `progress`.
+    pub fn with_progress(mut self, progress: ToolProgress) -> Self {
+        self.tool.set_progress(progress);
+        self
+    }
+
     pub async fn check_availability(&self) -> Result<()> {
-        self.tool.check_availability("--help", "extract-rpu").await
+        crate::tools::probe(self).await.into_result().map(|_| ())
     }
 
     pub async fn extract_rpu<P1: AsRef<Path>, P2: AsRef<Path>>(
@@ -30,19 +65,19 @@ impl DoviTool {
         info!("Extracting RPU: {} -> {}", input_str, output_str);
         debug!("Running dovi_tool (this may take a moment)...");
 
-        let base_args = vec![
+        let input = self.source(input_path.as_ref());
+        let mut args = vec![
             "extract-rpu".to_string(),
-            input_str.to_string(),
+            input.arg(),
             "-o".to_string(),
             output_str.to_string(),
         ];
+        if let Some(custom) = &self.tool.config().extract_args {
+            args.extend(custom.iter().cloned());
+        }
 
         self.tool
-            .run_with_custom_args(
-                &base_args,
-                &self.tool.config().extract_args,
-                Some(output_rpu),
-            )
+            .run_monitored(&args, input, output_rpu.as_ref(), "Extracting RPU", true)
             .await
             .map(|_| ())
     }
@@ -63,7 +98,7 @@ impl DoviTool {
         );
         debug!("Running dovi_tool (this may take a moment)...");
 
-        let base_args = vec![
+        let mut args = vec![
             "inject-rpu".to_string(),
             "-i".to_string(),
             input_str.to_string(),
@@ -72,17 +107,91 @@ impl DoviTool {
             "-o".to_string(),
             output_str.to_string(),
         ];
+        if let Some(custom) = &self.tool.config().inject_args {
+            args.extend(custom.iter().cloned());
+        }
 
         self.tool
-            .run_with_custom_args(
-                &base_args,
-                &self.tool.config().inject_args,
-                Some(output_path),
+            .run_monitored(
+                &args,
+                ToolInput::File(input_hevc.as_ref()),
+                output_path.as_ref(),
+                "Injecting RPU",
+                true,
             )
             .await
             .map(|_| ())
     }
 
+    /// Applies a `dovi_tool editor` JSON edit file to an RPU.
+    pub async fn edit_rpu<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+        &self,
+        rpu_file: P1,
+        edit_json: P2,
+        output_rpu: P3,
+    ) -> Result<()> {
+        let args = vec![
+            "editor".to_string(),
+            "-i".to_string(),
+            rpu_file.as_ref().to_string_lossy().to_string(),
+            "-j".to_string(),
+            edit_json.as_ref().to_string_lossy().to_string(),
+            "-o".to_string(),
+            output_rpu.as_ref().to_string_lossy().to_string(),
+        ];
+
+        self.tool
+            .run_with_custom_args(&args, &None, Some(output_rpu))
+            .await
+            .map(|_| ())
+    }
+
+    /// Writes an RPU from a `dovi_tool generate` JSON config (profile 8.1
+    /// unless the config says otherwise).
+    pub async fn generate_rpu<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        config_json: P1,
+        output_rpu: P2,
+    ) -> Result<()> {
+        let args = vec![
+            "generate".to_string(),
+            "-j".to_string(),
+            config_json.as_ref().to_string_lossy().to_string(),
+            "-o".to_string(),
+            output_rpu.as_ref().to_string_lossy().to_string(),
+        ];
+
+        self.tool
+            .run_with_custom_args(&args, &None, Some(output_rpu))
+            .await
+            .map(|_| ())
+    }
+
+    /// Exports every RPU as JSON (`dovi_tool export -d all=...`).
+    pub async fn export_rpu<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        rpu_file: P1,
+        output_json: P2,
+    ) -> Result<()> {
+        let rpu_str = rpu_file.as_ref().to_string_lossy();
+        let output_str = output_json.as_ref().to_string_lossy();
+
+        debug!("Exporting RPU: {} -> {}", rpu_str, output_str);
+
+        let args = vec![
+            "export".to_string(),
+            "-i".to_string(),
+            rpu_str.to_string(),
+            "-d".to_string(),
+            format!("all={}", output_str),
+        ];
+
+        self.tool
+            .run_with_custom_args(&args, &None, Some(output_json))
+            .await
+            .map(|_| ())
+    }
+
     pub async fn convert_profile<P1: AsRef<Path>, P2: AsRef<Path>>(
         &self,
         input_path: P1,
@@ -116,3 +225,18 @@ impl DoviTool {
         self.tool.get_version().await
     }
 }
+
+impl ExternalTool for DoviTool {
+    fn name(&self) -> &str {
+        "dovi_tool"
+    }
+
+    fn path(&self) -> &str {
+        &self.tool.config().path
+    }
+
+    /// RPU editing and reading HEVC from stdin as used here
+    fn min_version(&self) -> Option<Version> {
+        Some(Version::new(2, 0, 0))
+    }
+}
@@ -73,6 +73,8 @@ mod tests {
             timeout_seconds: 600,
             extract_args: Some(vec!["--verbose".to_string()]),
             inject_args: Some(vec!["--force".to_string()]),
+            retries: 2,
+            retry_backoff_seconds: 10,
         };
 
         let tool = DoviTool::new(custom_config.clone());
@@ -126,6 +128,11 @@ mod tests {
             bitrate: 10000,
             content_type: "film".to_string(),
             x265_params,
+            container: None,
+            extension: None,
+            max_bitrate: None,
+            stream_selection_profile: None,
+            tune: None,
         };
 
         let profile = EncodingProfile::from_raw("dv_test".to_string(), raw).unwrap();
@@ -151,6 +158,11 @@ mod tests {
             bitrate: 10000,
             content_type: "film".to_string(),
             x265_params,
+            container: None,
+            extension: None,
+            max_bitrate: None,
+            stream_selection_profile: None,
+            tune: None,
         };
 
         let profile = EncodingProfile::from_raw("dv_test".to_string(), raw).unwrap();
@@ -205,6 +217,53 @@ mod tests {
         assert!(params_str.contains("max-cll=1000,400"));
     }
 
+    #[test]
+    fn test_dolby_vision_84_keeps_hlg_transfer() {
+        let raw = RawProfile {
+            title: "DV 8.4 Test Profile".to_string(),
+            base_crf: 22.0,
+            bitrate: 10000,
+            content_type: "film".to_string(),
+            x265_params: HashMap::new(),
+            container: None,
+            extension: None,
+            max_bitrate: None,
+            stream_selection_profile: None,
+            tune: None,
+        };
+        let profile = EncodingProfile::from_raw("dv84_test".to_string(), raw).unwrap();
+
+        let dv_info = DolbyVisionInfo {
+            profile: DolbyVisionProfile::Profile84,
+            has_rpu: true,
+            ..Default::default()
+        };
+        let rpu_metadata = RpuMetadata {
+            temp_file: PathBuf::from("/tmp/test.rpu"),
+            profile: DolbyVisionProfile::Profile84,
+            frame_count: Some(1000),
+            extracted_successfully: true,
+            file_size: Some(1024),
+        };
+
+        let params_str = profile.build_x265_params_string_with_dolby_vision(
+            None,
+            Some(true),
+            Some(&"bt2020nc".to_string()),
+            Some(&"arib-std-b67".to_string()),
+            Some(&"bt2020".to_string()),
+            None,
+            None,
+            Some(&dv_info),
+            Some(&rpu_metadata),
+        );
+
+        assert!(params_str.contains("dolby-vision-profile=8.4"));
+        assert!(params_str.contains("transfer=arib-std-b67"));
+        assert!(!params_str.contains("smpte2084"));
+        assert!(!params_str.contains("hdr10_opt"));
+    }
+
     #[test]
     fn test_dolby_vision_profile_conversion_logic() {
         let config = DolbyVisionConfig {
@@ -342,6 +401,11 @@ pub async fn demo_dolby_vision_workflow() -> Result<(), Box<dyn std::error::Erro
         bitrate: 12000,
         content_type: "film".to_string(),
         x265_params,
+        container: None,
+        extension: None,
+        max_bitrate: None,
+        stream_selection_profile: None,
+        tune: None,
     };
 
     let profile = EncodingProfile::from_raw("dv_movie".to_string(), raw_profile)?;
@@ -403,4 +467,29 @@ mod integration_tests {
         // This test runs the complete demo workflow
         assert!(demo_dolby_vision_workflow().await.is_ok());
     }
+
+    #[test]
+    fn test_synthetic_source_metadata() {
+        use crate::testsignal::{hdr10plus_metadata, rpu_generate_config, TestSignal};
+
+        let signal = TestSignal {
+            hdr10plus: true,
+            dv81: true,
+            duration: 2.0,
+            ..Default::default()
+        };
+        let frames = signal.frame_count();
+
+        // HDR10+ metadata reads back like hdr10plus_tool's extraction output
+        let metadata: crate::Hdr10PlusMetadata =
+            serde_json::from_value(hdr10plus_metadata(frames)).unwrap();
+        assert!(metadata.validate().is_ok());
+        assert_eq!(metadata.get_frame_count(), frames);
+        assert!(metadata.has_tone_mapping_curves());
+
+        // One RPU per frame, L6 matching the HDR10 base layer
+        let rpu = rpu_generate_config(frames);
+        assert_eq!(rpu["length"], frames);
+        assert_eq!(rpu["level6"]["max_content_light_level"], 1000);
+    }
 }
@@ -0,0 +1,246 @@
+use crate::analysis::dolby_vision::DolbyVisionProfile;
+use crate::audio::bitrate::{codec_key, max_channels};
+use crate::audio::{AudioBitrate, BitrateLadder};
+use crate::config::{Config, DevicePreset, EncodingProfile};
+use crate::stream::preservation::StreamMapping;
+use crate::utils::{Error, Result};
+
+/// Codec for audio a device cannot decode when its preset names no fallback
+const DEFAULT_AUDIO_FALLBACK: &str = "aac";
+
+/// Constraints of a `--device` preset, applied on top of whatever profile was
+/// selected before any encoder arguments are built.
+#[derive(Debug, Clone)]
+pub struct DeviceConstraints {
+    name: String,
+    preset: DevicePreset,
+}
+
+impl DeviceConstraints {
+    pub fn from_config(config: &Config, name: &str) -> Result<Self> {
+        let preset = config.devices.get(name).cloned().ok_or_else(|| {
+            let mut available: Vec<&str> = config.devices.keys().map(String::as_str).collect();
+            available.sort_unstable();
+            Error::validation(format!(
+                "Unknown device '{}'. Available devices: {}",
+                name,
+                available.join(", ")
+            ))
+        })?;
+
+        Ok(Self {
+            name: name.to_string(),
+            preset,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn title(&self) -> &str {
+        &self.preset.title
+    }
+
+    /// Tightens the profile to the device: bitrate ceiling, HEVC level/tier and container.
+    pub fn apply_to_profile(&self, profile: &mut EncodingProfile) {
+        if let Some(max) = self.preset.max_bitrate {
+            profile.max_bitrate = Some(profile.max_bitrate.map_or(max, |own| own.min(max)));
+        }
+
+        if let Some(level) = &self.preset.level {
+            let (tier, other) = if self.preset.high_tier {
+                ("high-tier", "no-high-tier")
+            } else {
+                ("no-high-tier", "high-tier")
+            };
+            profile
+                .x265_params
+                .insert("level-idc".to_string(), level.clone());
+            profile.x265_params.remove(other);
+            profile
+                .x265_params
+                .insert(tier.to_string(), "1".to_string());
+        }
+
+        if let Some(container) = self.preset.container {
+            if profile.output_container() != Some(container) {
+                profile.container = Some(container);
+                profile.extension = None;
+            }
+        }
+    }
+
+    /// Whether the device plays this Dolby Vision profile.
+    pub fn supports_dolby_vision(&self, profile: DolbyVisionProfile) -> bool {
+        self.preset
+            .dolby_vision_profiles
+            .as_ref()
+            .is_none_or(|profiles| profiles.iter().any(|p| p == profile.as_str()))
+    }
+
+    /// Whether the device only renders text subtitles.
+    pub fn text_subtitles_only(&self) -> bool {
+        self.preset.text_subtitles_only
+    }
+
+    /// Transcodes the audio streams whose (output) codec the device cannot
+    /// decode. Returns the bitrate picked for each transcoded stream.
+    pub fn apply_to_mapping(
+        &self,
+        mapping: &mut StreamMapping,
+        ladder: &BitrateLadder,
+    ) -> Vec<AudioBitrate> {
+        let Some(allowed) = &self.preset.audio_codecs else {
+            return Vec::new();
+        };
+        let fallback = self
+            .preset
+            .audio_fallback
+            .as_deref()
+            .unwrap_or(DEFAULT_AUDIO_FALLBACK);
+
+        // Audio may already be re-encoded (e.g. by loudness normalization)
+        let global_codec = mapping
+            .mapping_args
+            .windows(2)
+            .rev()
+            .find(|pair| pair[0] == "-c:a")
+            .map(|pair| pair[1].clone())
+            .filter(|codec| codec != "copy");
+
+        let mut transcoded = Vec::new();
+        for (output_index, stream) in mapping.audio_streams.iter().enumerate() {
+            let codec = global_codec.as_deref().unwrap_or(&stream.codec_name);
+            if allowed.iter().any(|c| codec_key(c) == codec_key(codec)) {
+                continue;
+            }
+
+            // Downmix what the fallback encoder cannot take, e.g. 7.1 to 5.1 for E-AC-3
+            let limit = max_channels(fallback);
+            let channels = match (stream.channels, limit) {
+                (Some(channels), Some(limit)) if channels > limit => {
+                    mapping
+                        .mapping_args
+                        .extend([format!("-ac:a:{}", output_index), limit.to_string()]);
+                    Some(limit)
+                }
+                (channels, _) => channels,
+            };
+            let bitrate = format!("{}k", ladder.bitrate_kbps(fallback, channels));
+            mapping.mapping_args.extend([
+                format!("-c:a:{}", output_index),
+                fallback.to_string(),
+                format!("-b:a:{}", output_index),
+                bitrate.clone(),
+            ]);
+            transcoded.push(AudioBitrate {
+                stream_index: stream.index,
+                channels,
+                codec: fallback.to_string(),
+                bitrate,
+            });
+        }
+        transcoded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::types::OutputContainer;
+    use crate::stream::preservation::{StreamDisposition, StreamInfo};
+    use std::collections::BTreeMap;
+
+    fn constraints() -> DeviceConstraints {
+        DeviceConstraints {
+            name: "webos".to_string(),
+            preset: DevicePreset {
+                title: "LG webOS TV".to_string(),
+                level: Some("5.1".to_string()),
+                high_tier: false,
+                max_bitrate: Some(60000),
+                container: Some(OutputContainer::Mp4),
+                audio_codecs: Some(vec!["aac".to_string(), "eac3".to_string()]),
+                audio_fallback: Some("eac3".to_string()),
+                dolby_vision_profiles: Some(vec!["8.1".to_string()]),
+                text_subtitles_only: false,
+            },
+        }
+    }
+
+    fn audio(index: u32, codec_name: &str, channels: u32) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: "audio".to_string(),
+            codec_name: codec_name.to_string(),
+            channels: Some(channels),
+            language: None,
+            title: None,
+            disposition: StreamDisposition {
+                default: false,
+                forced: false,
+                comment: false,
+                lyrics: false,
+                karaoke: false,
+                original: false,
+                dub: false,
+                visual_impaired: false,
+                hearing_impaired: false,
+                attached_pic: false,
+            },
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_device_constraints() {
+        let device = constraints();
+        let mut profile = EncodingProfile {
+            name: "movie".to_string(),
+            title: "Movie".to_string(),
+            base_crf: 22.0,
+            bitrate: 10000,
+            content_type: crate::config::ContentType::Film,
+            x265_params: BTreeMap::from([("high-tier".to_string(), "1".to_string())]),
+            container: None,
+            extension: Some("mkv".to_string()),
+            max_bitrate: Some(80000),
+            stream_selection_profile: None,
+            tune: None,
+        };
+        device.apply_to_profile(&mut profile);
+        assert_eq!(profile.max_bitrate, Some(60000));
+        assert_eq!(profile.x265_params["level-idc"], "5.1");
+        assert!(profile.x265_params.contains_key("no-high-tier"));
+        assert!(!profile.x265_params.contains_key("high-tier"));
+        assert_eq!(profile.output_extension(), Some("mp4"));
+
+        assert!(device.supports_dolby_vision(DolbyVisionProfile::Profile81));
+        assert!(!device.supports_dolby_vision(DolbyVisionProfile::Profile5));
+
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: vec![audio(1, "truehd", 8), audio(2, "aac", 2)],
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            copied_video_args: Vec::new(),
+            mapping_args: ["-map", "0:a", "-c:a", "copy"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        let transcoded = device.apply_to_mapping(&mut mapping, &BitrateLadder::default());
+        assert_eq!(transcoded.len(), 1);
+        assert_eq!(
+            mapping.mapping_args.join(" "),
+            "-map 0:a -c:a copy -ac:a:0 6 -c:a:0 eac3 -b:a:0 768k"
+        );
+        assert_eq!(transcoded[0].channels, Some(6));
+    }
+}
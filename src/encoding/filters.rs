@@ -1,6 +1,53 @@
+use crate::config::types::CustomFilters;
 use crate::config::Config;
 use crate::utils::{Error, Result};
 
+/// Applies a Dolby Vision profile 5 RPU's reshaping on the GPU, turning the
+/// IPTPQc2 base layer into BT.2020 PQ (HDR10) in 10-bit. Needs an ffmpeg
+/// built with libplacebo and a Vulkan device.
+pub const DV5_RESHAPE_FILTER: &str = "libplacebo=apply_dolbyvision=1:colorspace=bt2020nc:\
+color_primaries=bt2020:color_trc=smpte2084:range=tv:format=yuv420p10le";
+
+/// Points of the chain where `filters.custom_filters` are inserted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStage {
+    /// After deinterlacing, denoising and debanding, before the crop
+    PreCrop,
+    /// After crop and scale, before tone mapping
+    PostScale,
+    /// Last in the chain
+    Final,
+}
+
+impl FilterStage {
+    pub const ALL: [FilterStage; 3] = [Self::PreCrop, Self::PostScale, Self::Final];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::PreCrop => "pre_crop",
+            Self::PostScale => "post_scale",
+            Self::Final => "final",
+        }
+    }
+}
+
+impl std::fmt::Display for FilterStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl CustomFilters {
+    /// The filter configured for `stage`, if any.
+    pub fn get(&self, stage: FilterStage) -> Option<&str> {
+        match stage {
+            FilterStage::PreCrop => self.pre_crop.as_deref(),
+            FilterStage::PostScale => self.post_scale.as_deref(),
+            FilterStage::Final => self.final_stage.as_deref(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct FilterChain {
     filters: Vec<String>,
@@ -18,12 +65,18 @@ impl FilterChain {
     }
 
     pub fn build_ffmpeg_args(&self) -> Vec<String> {
+        self.build_ffmpeg_args_for_input("0:v")
+    }
+
+    /// Like [`Self::build_ffmpeg_args`], with the filtergraph reading `input`
+    /// (e.g. `0:v:1` when the first video stream is cover art).
+    pub fn build_ffmpeg_args_for_input(&self, input: &str) -> Vec<String> {
         if self.filters.is_empty() {
             Vec::new()
         } else {
             let has_crop = self.filters.iter().any(|f| f.starts_with("crop="));
             if has_crop {
-                let filter_spec = format!("[0:v]{}[v]", self.filters.join(","));
+                let filter_spec = format!("[{}]{}[v]", input, self.filters.join(","));
                 vec!["-filter_complex".to_string(), filter_spec]
             } else {
                 vec!["-vf".to_string(), self.filters.join(",")]
@@ -34,6 +87,45 @@ impl FilterChain {
     pub fn is_empty(&self) -> bool {
         self.filters.is_empty()
     }
+
+    /// Picture size the chain outputs for a `width`x`height` input, following
+    /// its `crop=` and `scale=` filters (`-1`/`-2` keep the aspect ratio, the
+    /// latter at an even size).
+    pub fn output_dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        self.filters
+            .iter()
+            .fold((width, height), |(width, height), filter| {
+                let parse = |value: Option<&str>| value.and_then(|v| v.parse::<i64>().ok());
+                if let Some(crop) = filter.strip_prefix("crop=") {
+                    let mut parts = crop.split(':');
+                    match (parse(parts.next()), parse(parts.next())) {
+                        (Some(w), Some(h)) if w > 0 && h > 0 => (w as u32, h as u32),
+                        _ => (width, height),
+                    }
+                } else if let Some(scale) = filter.strip_prefix("scale=") {
+                    let mut parts = scale.split(':');
+                    // Rounded to a multiple of 1 (-1) or 2 (-2), as FFmpeg does
+                    let keep_aspect = |size: f64, factor: i64| {
+                        let factor = factor.unsigned_abs() as f64;
+                        ((size / factor).round() * factor) as u32
+                    };
+                    match (parse(parts.next()), parse(parts.next())) {
+                        (Some(w), Some(h)) if w > 0 && h > 0 => (w as u32, h as u32),
+                        (Some(w @ (-1 | -2)), Some(h)) if h > 0 && height > 0 => (
+                            keep_aspect(width as f64 * h as f64 / height as f64, w),
+                            h as u32,
+                        ),
+                        (Some(w), Some(h @ (-1 | -2))) if w > 0 && width > 0 => (
+                            w as u32,
+                            keep_aspect(height as f64 * w as f64 / width as f64, h),
+                        ),
+                        _ => (width, height),
+                    }
+                } else {
+                    (width, height)
+                }
+            })
+    }
 }
 
 impl std::fmt::Display for FilterChain {
@@ -87,6 +179,15 @@ impl<'a> FilterBuilder<'a> {
         Ok(self.chain)
     }
 
+    /// Reshapes a Dolby Vision profile 5 source to HDR10 before any other
+    /// filter sees it; see [`DV5_RESHAPE_FILTER`].
+    pub fn with_dv5_reshape(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.chain.add_filter(DV5_RESHAPE_FILTER.to_string());
+        }
+        self
+    }
+
     pub fn with_deinterlace(mut self, enabled: bool) -> Result<Self> {
         if enabled {
             let filter = self.build_deinterlace_filter()?;
@@ -103,6 +204,17 @@ impl<'a> FilterBuilder<'a> {
         self
     }
 
+    /// Smooths banded gradients with `filters.deband` (gradfun by default),
+    /// before cropping and scaling see the picture.
+    pub fn with_deband(mut self, enabled: bool) -> Self {
+        if enabled {
+            let deband_config = &self.config.filters.deband;
+            self.chain
+                .add_filter(format!("{}={}", deband_config.filter, deband_config.params));
+        }
+        self
+    }
+
     pub fn with_crop(mut self, crop: Option<&str>) -> Result<Self> {
         if let Some(crop_value) = crop {
             let filter = format!("crop={}", crop_value);
@@ -111,6 +223,50 @@ impl<'a> FilterBuilder<'a> {
         Ok(self)
     }
 
+    /// Scales to `height` (after cropping), keeping the aspect ratio at an
+    /// even width.
+    pub fn with_scale(mut self, height: Option<u32>) -> Self {
+        if let Some(height) = height {
+            self.chain
+                .add_filter(format!("scale=-2:{}:flags=lanczos", height));
+        }
+        self
+    }
+
+    /// Tone maps HDR to SDR BT.709 in 10-bit with zscale, using the
+    /// algorithm and peak of `analysis.hdr.tone_mapping` (hable at 100 nits
+    /// when unset).
+    pub fn with_tonemap(mut self, enabled: bool) -> Self {
+        if enabled {
+            let filter = self.build_tonemap_filter(None);
+            self.chain.add_filter(filter);
+        }
+        self
+    }
+
+    /// Like [`with_tonemap`](Self::with_tonemap), for input whose colour tags
+    /// may be missing (e.g. an intermediate encode): the source is taken to
+    /// be BT.2020 with the given transfer (PQ unless it is `arib-std-b67`).
+    pub fn with_tonemap_from(mut self, transfer: Option<&str>) -> Self {
+        let transfer = match transfer {
+            Some("arib-std-b67") => "arib-std-b67",
+            _ => "smpte2084",
+        };
+        let filter = self.build_tonemap_filter(Some(transfer));
+        self.chain.add_filter(filter);
+        self
+    }
+
+    /// Inserts the `filters.custom_filters` entry for `stage`, if any. The
+    /// caller places the insertion points; [`build`](Self::build) does not
+    /// add them on its own.
+    pub fn with_custom(mut self, stage: FilterStage) -> Self {
+        if let Some(filter) = self.config.filters.custom_filters.get(stage) {
+            self.chain.add_filter(filter.trim().to_string());
+        }
+        self
+    }
+
     pub fn build(self) -> FilterChain {
         self.chain
     }
@@ -172,6 +328,26 @@ impl<'a> FilterBuilder<'a> {
         }
     }
 
+    fn build_tonemap_filter(&self, source_transfer: Option<&str>) -> String {
+        let tone_mapping = self
+            .config
+            .analysis
+            .hdr
+            .as_ref()
+            .and_then(|hdr| hdr.tone_mapping.as_ref());
+        let (algorithm, peak) = tone_mapping
+            .map(|tm| (tm.algorithm.as_str(), tm.target_max_nits))
+            .unwrap_or(("hable", 100));
+        let input = source_transfer
+            .map(|transfer| format!("tin={}:min=bt2020nc:pin=bt2020:", transfer))
+            .unwrap_or_default();
+        format!(
+            "zscale={}t=linear:npl={},format=gbrpf32le,zscale=p=bt709,tonemap=tonemap={}:desat=0,\
+             zscale=t=bt709:m=bt709:r=tv,format=yuv420p10le",
+            input, peak, algorithm
+        )
+    }
+
     fn build_denoise_filter(&self) -> String {
         let denoise_config = &self.config.filters.denoise;
         format!("{}={}", denoise_config.filter, denoise_config.params)
@@ -193,6 +369,58 @@ pub fn validate_crop_format(crop: &str) -> Result<()> {
     Ok(())
 }
 
+/// Checks a `filters.custom_filters` entry: a plain comma-separated list of
+/// `name[=options]` filters. Labels and `;` are rejected since the entry is
+/// spliced into a single linear chain.
+pub fn validate_custom_filter(stage: FilterStage, filter: &str) -> Result<()> {
+    let invalid = |reason: &str| {
+        Error::validation(format!(
+            "Invalid filters.custom_filters.{} '{}': {}",
+            stage, filter, reason
+        ))
+    };
+
+    let mut filters = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = filter.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let current = filters.last_mut().unwrap();
+                current.push(c);
+                current.extend(chars.next());
+            }
+            '\'' => {
+                quoted = !quoted;
+                filters.last_mut().unwrap().push(c);
+            }
+            ',' if !quoted => filters.push(String::new()),
+            ';' | '[' | ']' if !quoted => {
+                return Err(invalid(
+                    "filtergraph labels and ';' are not allowed, only a filter chain",
+                ))
+            }
+            _ => filters.last_mut().unwrap().push(c),
+        }
+    }
+    if quoted {
+        return Err(invalid("unbalanced quotes"));
+    }
+
+    for entry in &filters {
+        let entry = entry.trim();
+        let name = entry.split_once('=').map_or(entry, |(name, _)| name);
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(invalid(if entry.is_empty() {
+                "empty filter"
+            } else {
+                "expected name[=options] for every filter"
+            }));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -212,11 +440,16 @@ mod tests {
                 dovi_tool: None,
                 hdr10plus_tool: None,
                 mkvmerge: None,
+                mkvpropedit: None,
+                butteraugli: None,
+                subtitle_ocr: None,
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
                 show_timestamps: true,
                 colored_output: true,
+                backend: Default::default(),
+                console: true,
             },
             analysis: AnalysisConfig {
                 crop_detection: CropDetectionConfig::default(),
@@ -228,8 +461,20 @@ mod tests {
                 }),
                 dolby_vision: Some(crate::config::DolbyVisionConfig::default()),
                 hdr10_plus: Some(crate::config::Hdr10PlusConfig::default()),
+                cache: AnalysisCacheConfig::default(),
+                black_frames: BlackFrameConfig::default(),
+                fingerprint: Default::default(),
+                scene_detection: Default::default(),
+                starved_source: Default::default(),
+                count_frames: false,
+                fallback_chain: vec![
+                    crate::config::MetadataFallback::DolbyVision,
+                    crate::config::MetadataFallback::Hdr10Plus,
+                    crate::config::MetadataFallback::Hdr10,
+                ],
             },
             profiles: HashMap::new(),
+            reject_unknown_x265_params: false,
             filters: FiltersConfig {
                 deinterlace: DeinterlaceConfig {
                     primary_method: "nnedi".to_string(),
@@ -242,9 +487,25 @@ mod tests {
                     filter: "hqdn3d".to_string(),
                     params: "1:1:2:2".to_string(),
                 },
+                deband: DebandConfig::default(),
+                custom_filters: CustomFilters::default(),
             },
             stream_selection_profiles: HashMap::new(),
             preview_profiles: HashMap::new(),
+            audio: AudioConfig::default(),
+            throttle: ThrottleConfig::default(),
+            disk_space: DiskSpaceConfig::default(),
+            encoding: EncodingConfig::default(),
+            cleanup: CleanupConfig::default(),
+            streams: StreamsConfig::default(),
+            devices: HashMap::new(),
+            check: CheckPolicy::default(),
+            ladders: HashMap::new(),
+            thumbnails: ThumbnailsConfig::default(),
+            quality: QualityConfig::default(),
+            planning: PlanningConfig::default(),
+            output_files: OutputFilesConfig::default(),
+            notifications: NotificationsConfig::default(),
         }
     }
 
@@ -271,6 +532,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_custom_filter_stages() {
+        let mut config = create_test_config();
+        config.filters.custom_filters = CustomFilters {
+            pre_crop: Some("eq=gamma=1.05".to_string()),
+            post_scale: Some("cas=0.2".to_string()),
+            final_stage: None,
+        };
+        let chain = FilterBuilder::new(&config)
+            .with_denoise(true)
+            .with_custom(FilterStage::PreCrop)
+            .with_crop(Some("1920:800:0:140"))
+            .unwrap()
+            .with_scale(Some(720))
+            .with_custom(FilterStage::PostScale)
+            .with_custom(FilterStage::Final)
+            .build();
+        assert_eq!(
+            chain.to_string(),
+            "hqdn3d=1:1:2:2,eq=gamma=1.05,crop=1920:800:0:140,scale=-2:720:flags=lanczos,cas=0.2"
+        );
+
+        let stage = FilterStage::PostScale;
+        assert!(validate_custom_filter(stage, "cas=0.2").is_ok());
+        assert!(validate_custom_filter(stage, "unsharp=5:5:0.8, hqdn3d").is_ok());
+        assert!(validate_custom_filter(stage, "drawtext=text='a, b; [c]'").is_ok());
+        assert!(validate_custom_filter(stage, "").is_err());
+        assert!(validate_custom_filter(stage, "cas=0.2,").is_err());
+        assert!(validate_custom_filter(stage, "[in]cas[out]").is_err());
+        assert!(validate_custom_filter(stage, "cas;hqdn3d").is_err());
+        assert!(validate_custom_filter(stage, "drawtext=text='open").is_err());
+        assert!(validate_custom_filter(stage, "-vf cas").is_err());
+    }
+
     #[test]
     fn test_validate_crop_format() {
         assert!(validate_crop_format("1920:800:0:140").is_ok());
@@ -291,6 +586,50 @@ mod tests {
         assert!(!chain.is_empty());
     }
 
+    #[test]
+    fn test_dv5_reshape_runs_first() {
+        let config = create_test_config();
+        let chain = FilterBuilder::new(&config)
+            .with_dv5_reshape(true)
+            .with_denoise(true)
+            .with_crop(Some("3840:1600:0:280"))
+            .unwrap()
+            .build();
+
+        let args = chain.build_ffmpeg_args();
+        assert_eq!(args[0], "-filter_complex");
+        assert!(args[1].starts_with("[0:v]libplacebo=apply_dolbyvision=1:"));
+        assert!(args[1].ends_with(",hqdn3d=1:1:2:2,crop=3840:1600:0:280[v]"));
+    }
+
+    #[test]
+    fn test_scale_and_tonemap_follow_crop() {
+        let config = create_test_config();
+        let chain = FilterBuilder::new(&config)
+            .with_crop(Some("3840:1600:0:280"))
+            .unwrap()
+            .with_scale(Some(1080))
+            .with_tonemap(true)
+            .build();
+
+        let filters = chain.to_string();
+        assert!(filters.starts_with("crop=3840:1600:0:280,scale=-2:1080:flags=lanczos,"));
+        assert!(filters.contains("tonemap=tonemap=hable:desat=0"));
+        assert!(filters.ends_with("zscale=t=bt709:m=bt709:r=tv,format=yuv420p10le"));
+        assert_eq!(chain.output_dimensions(3840, 2160), (2592, 1080));
+        assert_eq!(
+            FilterChain::new().output_dimensions(1920, 1080),
+            (1920, 1080)
+        );
+
+        let hinted = FilterBuilder::new(&config)
+            .with_tonemap_from(Some("arib-std-b67"))
+            .build();
+        assert!(hinted
+            .to_string()
+            .starts_with("zscale=tin=arib-std-b67:min=bt2020nc:pin=bt2020:t=linear:npl=100,"));
+    }
+
     #[test]
     fn test_nnedi_filter_construction() {
         let mut config = create_test_config();
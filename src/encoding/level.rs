@@ -0,0 +1,249 @@
+use crate::config::EncodingProfile;
+use crate::encoding::FilterChain;
+use crate::utils::ffmpeg::VideoMetadata;
+use std::collections::BTreeMap;
+use tracing::{debug, warn};
+
+/// One row of the HEVC level limits (ITU-T H.265 Table A.8, Main/Main 10 profiles).
+struct LevelLimits {
+    name: &'static str,
+    max_luma_picture_size: u64,
+    max_luma_sample_rate: u64,
+    /// Maximum VCL bitrate in kbps for the Main and (if defined) High tier
+    max_bitrate_main: u32,
+    max_bitrate_high: Option<u32>,
+}
+
+const fn limits(
+    name: &'static str,
+    max_luma_picture_size: u64,
+    max_luma_sample_rate: u64,
+    max_bitrate_main: u32,
+    max_bitrate_high: Option<u32>,
+) -> LevelLimits {
+    LevelLimits {
+        name,
+        max_luma_picture_size,
+        max_luma_sample_rate,
+        max_bitrate_main,
+        max_bitrate_high,
+    }
+}
+
+const LEVELS: &[LevelLimits] = &[
+    limits("1", 36_864, 552_960, 128, None),
+    limits("2", 122_880, 3_686_400, 1_500, None),
+    limits("2.1", 245_760, 7_372_800, 3_000, None),
+    limits("3", 552_960, 16_588_800, 6_000, None),
+    limits("3.1", 983_040, 33_177_600, 10_000, None),
+    limits("4", 2_228_224, 66_846_720, 12_000, Some(30_000)),
+    limits("4.1", 2_228_224, 133_693_440, 20_000, Some(50_000)),
+    limits("5", 8_912_896, 267_386_880, 25_000, Some(100_000)),
+    limits("5.1", 8_912_896, 534_773_760, 40_000, Some(160_000)),
+    limits("5.2", 8_912_896, 1_069_547_520, 60_000, Some(240_000)),
+    limits("6", 35_651_584, 1_069_547_520, 60_000, Some(240_000)),
+    limits("6.1", 35_651_584, 2_139_095_040, 120_000, Some(480_000)),
+    limits("6.2", 35_651_584, 4_278_190_080, 240_000, Some(800_000)),
+];
+
+/// The lowest HEVC level (and tier) that fits a stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HevcLevel {
+    pub level: &'static str,
+    pub high_tier: bool,
+}
+
+impl std::fmt::Display for HevcLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tier = if self.high_tier { "High" } else { "Main" };
+        write!(f, "{}@{}", tier, self.level)
+    }
+}
+
+fn level_index(name: &str) -> Option<usize> {
+    // x265 also accepts levels written without the dot (e.g. "51")
+    let normalized = match name.trim() {
+        digits if digits.len() == 2 && !digits.contains('.') => {
+            format!("{}.{}", &digits[..1], &digits[1..])
+        }
+        other => other.trim_end_matches(".0").to_string(),
+    };
+    LEVELS.iter().position(|l| l.name == normalized)
+}
+
+/// Lowest level/tier whose picture size, sample rate and bitrate limits cover
+/// the stream. `peak_bitrate` (kbps) is `None` when rate control has no bound (CRF without VBV).
+pub fn required_level(width: u32, height: u32, fps: f32, peak_bitrate: Option<u32>) -> HevcLevel {
+    let picture_size = u64::from(width) * u64::from(height);
+    let sample_rate = (picture_size as f64 * f64::from(fps.max(1.0))).ceil() as u64;
+    let bitrate = peak_bitrate.unwrap_or(0);
+
+    for limits in LEVELS {
+        // Neither dimension may exceed sqrt(8 * MaxLumaPs)
+        let max_dimension = ((limits.max_luma_picture_size * 8) as f64).sqrt() as u32;
+        if picture_size > limits.max_luma_picture_size
+            || width.max(height) > max_dimension
+            || sample_rate > limits.max_luma_sample_rate
+        {
+            continue;
+        }
+        if bitrate <= limits.max_bitrate_main {
+            return HevcLevel {
+                level: limits.name,
+                high_tier: false,
+            };
+        }
+        if limits.max_bitrate_high.is_some_and(|max| bitrate <= max) {
+            return HevcLevel {
+                level: limits.name,
+                high_tier: true,
+            };
+        }
+    }
+
+    HevcLevel {
+        level: "6.2",
+        high_tier: true,
+    }
+}
+
+/// Signals the HEVC level/tier for an encode by adding `level-idc` and
+/// `high-tier`/`no-high-tier` to the mode parameters, for the picture size
+/// left after `filters` crop and scale. A level already set in the profile
+/// (e.g. by a `--device` preset) is kept, with a warning if the stream needs
+/// more than it allows.
+pub fn apply_level_params(
+    profile: &EncodingProfile,
+    metadata: &VideoMetadata,
+    filters: &FilterChain,
+    mode_params: &mut BTreeMap<String, String>,
+) {
+    let param = |key: &str| {
+        mode_params
+            .get(key)
+            .or_else(|| profile.x265_params.get(key))
+            .and_then(|v| v.parse::<u32>().ok())
+    };
+    let peak_bitrate = match (param("vbv-maxrate"), param("bitrate")) {
+        (Some(maxrate), Some(bitrate)) => Some(maxrate.max(bitrate)),
+        (maxrate, bitrate) => maxrate.or(bitrate),
+    };
+    let (width, height) = filters.output_dimensions(metadata.width, metadata.height);
+    let required = required_level(width, height, metadata.fps, peak_bitrate);
+
+    if let Some(configured) = profile.x265_params.get("level-idc") {
+        let exceeds = match (level_index(configured), level_index(required.level)) {
+            (Some(allowed), Some(needed)) => needed > allowed,
+            _ => false,
+        };
+        if exceeds {
+            warn!(
+                "{}x{} @ {:.3} fps{} needs HEVC level {}, above the configured level {}; players limited to that level may not decode it",
+                width,
+                height,
+                metadata.fps,
+                peak_bitrate.map(|b| format!(", {} kbps", b)).unwrap_or_default(),
+                required,
+                configured
+            );
+        }
+        return;
+    }
+
+    debug!("Signaling HEVC level {}", required);
+    mode_params.insert("level-idc".to_string(), required.level.to_string());
+    let tier = if required.high_tier {
+        "high-tier"
+    } else {
+        "no-high-tier"
+    };
+    mode_params.insert(tier.to_string(), "1".to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_required_level() {
+        assert_eq!(
+            required_level(1920, 1080, 23.976, Some(10_000)).to_string(),
+            "Main@4"
+        );
+        assert_eq!(
+            required_level(1920, 1080, 60.0, None).to_string(),
+            "Main@4.1"
+        );
+        assert_eq!(
+            required_level(3840, 2160, 23.976, Some(25_000)).to_string(),
+            "Main@5"
+        );
+        // Like x265: the lowest level wins, moving to High tier before the next level
+        assert_eq!(
+            required_level(3840, 2160, 23.976, Some(40_000)).to_string(),
+            "High@5"
+        );
+        assert_eq!(
+            required_level(3840, 2160, 60.0, Some(80_000)).to_string(),
+            "High@5.1"
+        );
+        assert_eq!(required_level(7680, 4320, 30.0, None).level, "6");
+
+        assert!(level_index("51") == level_index("5.1") && level_index("5.1").is_some());
+        assert_eq!(level_index("4.0"), level_index("4"));
+    }
+
+    #[test]
+    fn test_level_follows_crop_and_scale() {
+        let profile = EncodingProfile {
+            name: "movie".to_string(),
+            title: "Movie".to_string(),
+            base_crf: 22.0,
+            bitrate: 10000,
+            content_type: crate::config::ContentType::Film,
+            x265_params: BTreeMap::new(),
+            container: None,
+            extension: None,
+            max_bitrate: None,
+            stream_selection_profile: None,
+            tune: None,
+        };
+        let metadata = VideoMetadata {
+            width: 3840,
+            height: 2160,
+            duration: 60.0,
+            fps: 23.976,
+            bitrate: None,
+            codec: Some("hevc".to_string()),
+            pix_fmt: Some("yuv420p10le".to_string()),
+            field_order: None,
+            is_hdr: false,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+
+        let mut mode_params = BTreeMap::new();
+        apply_level_params(&profile, &metadata, &FilterChain::new(), &mut mode_params);
+        assert_eq!(mode_params["level-idc"], "5");
+
+        // A 2160p source scaled down to 1080p (e.g. a ladder rendition) only needs level 4
+        let mut scaled = FilterChain::new();
+        scaled.add_filter("scale=-2:1080:flags=lanczos".to_string());
+        let mut mode_params = BTreeMap::new();
+        apply_level_params(&profile, &metadata, &scaled, &mut mode_params);
+        assert_eq!(mode_params["level-idc"], "4");
+        assert!(mode_params.contains_key("no-high-tier"));
+
+        let mut cropped = FilterChain::new();
+        cropped.add_filter("crop=1920:1080:960:540".to_string());
+        let mut mode_params = BTreeMap::new();
+        apply_level_params(&profile, &metadata, &cropped, &mut mode_params);
+        assert_eq!(mode_params["level-idc"], "4");
+    }
+}
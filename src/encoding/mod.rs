@@ -1,7 +1,14 @@
+pub mod device;
 pub mod filters;
+pub mod level;
 pub mod modes;
 pub mod options;
+pub mod provenance;
+pub mod remux;
+pub mod x265_params;
 
-pub use filters::{FilterBuilder, FilterChain};
+pub use device::DeviceConstraints;
+pub use filters::{FilterBuilder, FilterChain, FilterStage};
 pub use modes::{AbrEncoder, CbrEncoder, CrfEncoder, EncodingMode};
 pub use options::EncodingOptions;
+pub use provenance::Provenance;
@@ -1,10 +1,12 @@
-use crate::config::EncodingProfile;
+use crate::analysis::scenes::force_key_frames_args;
+use crate::config::{EncodingProfile, OutputContainer};
+use crate::encoding::level::apply_level_params;
 use crate::encoding::FilterChain;
 use crate::stream::preservation::StreamMapping;
 use crate::utils::ffmpeg::VideoMetadata;
 use crate::utils::{Error, FfmpegWrapper, Result};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EncodingMode {
@@ -32,6 +34,88 @@ impl EncodingMode {
     }
 }
 
+/// Muxer-specific arguments for the container implied by the output path,
+/// for an output whose first video stream is `video_codec`.
+pub(crate) fn container_args(output_path: &Path, video_codec: Option<&str>) -> Vec<String> {
+    match OutputContainer::from_path(output_path).unwrap_or_default() {
+        OutputContainer::Mkv => vec!["-default_mode".to_string(), "infer_no_subs".to_string()],
+        // hvc1 tagging is what Apple/TV players expect for HEVC in MP4. Only
+        // the first video stream is tagged: copied cover art and other video
+        // streams cannot be hvc1. The movflags are options of the mov/mp4 muxer only
+        OutputContainer::Mp4 => {
+            let mut args = Vec::new();
+            if video_codec == Some("hevc") {
+                args.extend(["-tag:v:0".to_string(), "hvc1".to_string()]);
+            }
+            args.extend([
+                "-movflags".to_string(),
+                "+faststart+use_metadata_tags".to_string(),
+            ]);
+            args
+        }
+    }
+}
+
+/// Filter arguments for the encoded video stream. Crop filtergraphs read the primary
+/// video stream, and `-vf` is narrowed to it so copied video streams (cover art,
+/// the others under `video_policy: all`) are not filtered.
+fn video_filter_args(filters: &FilterChain, stream_mapping: &StreamMapping) -> Vec<String> {
+    let mut args = filters.build_ffmpeg_args_for_input(&stream_mapping.primary_video_specifier());
+    if !stream_mapping.copied_video_args.is_empty() {
+        if let Some(flag) = args.first_mut().filter(|flag| *flag == "-vf") {
+            *flag = "-filter:v:0".to_string();
+        }
+    }
+    args
+}
+
+/// Stream mapping and video encoder arguments of an encode. With a separate
+/// `video_output` (`streams.muxer: mkvmerge`), the video mapped from
+/// `video_map` is written there as a raw HEVC stream and the arguments that
+/// follow apply to a second output with every other stream, so mkvmerge
+/// muxes the final file from the two in a single pass.
+pub(crate) fn output_stream_args(
+    mapping_args: &[String],
+    video_map: &str,
+    video_args: &[String],
+    copied_video_args: &[String],
+    video_output: Option<&Path>,
+) -> Vec<String> {
+    let Some(video_output) = video_output else {
+        return [mapping_args, video_args, copied_video_args].concat();
+    };
+
+    let mut args = vec!["-map".to_string(), video_map.to_string()];
+    args.extend_from_slice(video_args);
+    args.extend([
+        "-f".to_string(),
+        "hevc".to_string(),
+        video_output.to_string_lossy().to_string(),
+        "-max_muxing_queue_size".to_string(),
+        "1024".to_string(),
+    ]);
+
+    let mut stream_args = mapping_args.to_vec();
+    if let Some(position) = stream_args
+        .windows(2)
+        .position(|pair| pair[0] == "-map" && pair[1] == video_map)
+    {
+        stream_args.drain(position..position + 2);
+    }
+    args.extend(stream_args);
+
+    // Without the encoded stream, the copied video streams move up one index
+    args.extend(copied_video_args.iter().map(|arg| {
+        arg.rsplit_once(":v:")
+            .and_then(|(option, index)| Some((option, index.parse::<usize>().ok()?)))
+            .map_or_else(
+                || arg.clone(),
+                |(option, index)| format!("{}:v:{}", option, index.saturating_sub(1)),
+            )
+    }));
+    args
+}
+
 pub trait Encoder {
     #[allow(async_fn_in_trait)]
     #[allow(clippy::too_many_arguments)]
@@ -53,7 +137,25 @@ pub trait Encoder {
     ) -> Result<tokio::process::Child>;
 }
 
-pub struct CrfEncoder;
+#[derive(Default)]
+pub struct CrfEncoder {
+    /// Times (seconds) of forced keyframes, e.g. the source's scene cuts
+    keyframes: Vec<f64>,
+    /// Raw HEVC file for the encoded video; see [`output_stream_args`]
+    video_output: Option<PathBuf>,
+}
+
+impl CrfEncoder {
+    pub fn with_keyframes(mut self, times: Vec<f64>) -> Self {
+        self.keyframes = times;
+        self
+    }
+
+    pub fn with_video_output(mut self, path: Option<PathBuf>) -> Self {
+        self.video_output = path;
+        self
+    }
+}
 
 impl Encoder for CrfEncoder {
     async fn encode<P: AsRef<Path>>(
@@ -75,8 +177,9 @@ impl Encoder for CrfEncoder {
         let input_path_str = input_path.as_ref().to_string_lossy();
         let output_path_str = output_path.as_ref().to_string_lossy();
 
-        let mut mode_params = HashMap::new();
+        let mut mode_params = BTreeMap::new();
         mode_params.insert("crf".to_string(), adaptive_crf.to_string());
+        apply_level_params(profile, metadata, filters, &mut mode_params);
 
         let x265_params = profile.build_x265_params_string_with_external_metadata_passthrough(
             Some(&mode_params),
@@ -91,13 +194,15 @@ impl Encoder for CrfEncoder {
         );
 
         let mut args = vec!["-i".to_string(), input_path_str.to_string()];
+        args.extend(stream_mapping.input_args.iter().cloned());
 
         args.extend(vec![
             "-max_muxing_queue_size".to_string(),
             "1024".to_string(),
         ]);
 
-        let filter_args = filters.build_ffmpeg_args();
+        let video_specifier = stream_mapping.primary_video_specifier();
+        let filter_args = video_filter_args(filters, stream_mapping);
         let uses_filter_complex = filter_args.contains(&"-filter_complex".to_string());
         args.extend(filter_args);
 
@@ -105,56 +210,59 @@ impl Encoder for CrfEncoder {
 
         if uses_filter_complex {
             for i in 0..mapping_args.len() - 1 {
-                if mapping_args[i] == "-map" && mapping_args[i + 1] == "0:v:0" {
+                if mapping_args[i] == "-map" && mapping_args[i + 1] == video_specifier {
                     mapping_args[i + 1] = "[v]".to_string();
                     break;
                 }
             }
         }
 
-        args.extend(mapping_args);
-
-        args.extend(vec!["-c:v".to_string(), "libx265".to_string()]);
+        let mut video_args = vec!["-c:v".to_string(), "libx265".to_string()];
 
         if let Some(preset) = profile.get_preset() {
-            args.extend(vec!["-preset".to_string(), preset]);
+            video_args.extend(vec!["-preset".to_string(), preset]);
+        }
+
+        if let Some(tune) = profile.get_tune() {
+            video_args.extend(vec!["-tune".to_string(), tune.to_string()]);
         }
 
         if let Some(profile_name) = profile.get_profile() {
-            args.extend(vec!["-profile:v".to_string(), profile_name]);
+            video_args.extend(vec!["-profile:v".to_string(), profile_name]);
         }
 
         if let Some(pix_fmt) = profile.get_pixel_format() {
-            args.extend(vec!["-pix_fmt".to_string(), pix_fmt]);
+            video_args.extend(vec!["-pix_fmt".to_string(), pix_fmt]);
         }
 
-        args.extend(vec!["-x265-params".to_string(), x265_params]);
+        video_args.extend(force_key_frames_args(&self.keyframes));
+        video_args.extend(vec!["-x265-params".to_string(), x265_params]);
 
-        args.extend(vec![
-            "-default_mode".to_string(),
-            "infer_no_subs".to_string(),
-        ]);
+        let video_map = if uses_filter_complex {
+            "[v]"
+        } else {
+            video_specifier.as_str()
+        };
+        args.extend(output_stream_args(
+            &mapping_args,
+            video_map,
+            &video_args,
+            &stream_mapping.copied_video_args,
+            self.video_output.as_deref(),
+        ));
+
+        args.extend(container_args(output_path.as_ref(), Some("hevc")));
 
         let stream_preservation =
             crate::stream::preservation::StreamPreservation::new(ffmpeg.clone());
         args.extend(stream_preservation.get_metadata_args(stream_mapping, custom_title));
 
-        let progress_file = format!("/tmp/ffmpeg_progress_{}.txt", std::process::id());
-        args.extend(vec![
-            "-progress".to_string(),
-            progress_file,
-            "-nostats".to_string(),
-            "-stats_period".to_string(),
-            "1.0".to_string(),
-        ]);
+        args.extend(FfmpegWrapper::progress_args());
 
-        args.extend(vec![
-            "-movflags".to_string(),
-            "+faststart".to_string(),
-            output_path_str.to_string(),
-        ]);
+        args.push(output_path_str.to_string());
 
         tracing::debug!(
+            tree = "stage",
             "Starting CRF encoding with CRF={} ({} streams)",
             adaptive_crf,
             stream_mapping.video_streams.len()
@@ -173,7 +281,15 @@ impl Encoder for CrfEncoder {
     }
 }
 
-pub struct AbrEncoder;
+#[derive(Default)]
+pub struct AbrEncoder {
+    /// Leave the two-pass stats files in place (`--keep-temp`)
+    keep_stats: bool,
+    /// Times (seconds) of forced keyframes, e.g. the source's scene cuts
+    keyframes: Vec<f64>,
+    /// Raw HEVC file for the encoded video; see [`output_stream_args`]
+    video_output: Option<PathBuf>,
+}
 
 impl Encoder for AbrEncoder {
     async fn encode<P: AsRef<Path>>(
@@ -213,6 +329,21 @@ impl Encoder for AbrEncoder {
 }
 
 impl AbrEncoder {
+    pub fn with_keep_stats(mut self, keep: bool) -> Self {
+        self.keep_stats = keep;
+        self
+    }
+
+    pub fn with_keyframes(mut self, times: Vec<f64>) -> Self {
+        self.keyframes = times;
+        self
+    }
+
+    pub fn with_video_output(mut self, path: Option<PathBuf>) -> Self {
+        self.video_output = path;
+        self
+    }
+
     #[allow(clippy::too_many_arguments)]
     async fn run_two_pass_encoding<P: AsRef<Path>>(
         &self,
@@ -236,6 +367,7 @@ impl AbrEncoder {
         let stats_file = format!("/tmp/ffmpeg2pass_{}", uuid::Uuid::new_v4());
 
         tracing::debug!(
+            tree = "stage",
             "Starting two-pass {} encoding (bitrate={}kbps)",
             if is_cbr { "CBR" } else { "ABR" },
             adaptive_bitrate
@@ -247,6 +379,7 @@ impl AbrEncoder {
                 &input_path_str,
                 profile,
                 filters,
+                &stream_mapping.primary_video_specifier(),
                 metadata,
                 adaptive_bitrate,
                 external_metadata_params,
@@ -292,6 +425,7 @@ impl AbrEncoder {
         input_path: &str,
         profile: &EncodingProfile,
         filters: &FilterChain,
+        video_specifier: &str,
         metadata: &VideoMetadata,
         adaptive_bitrate: u32,
         external_metadata_params: Option<&[(String, String)]>,
@@ -299,7 +433,7 @@ impl AbrEncoder {
         hdr_passthrough_mode: bool,
         is_cbr: bool,
     ) -> Result<()> {
-        let mut mode_params = HashMap::new();
+        let mut mode_params = BTreeMap::new();
         mode_params.insert("pass".to_string(), "1".to_string());
         mode_params.insert("bitrate".to_string(), adaptive_bitrate.to_string());
         mode_params.insert("stats".to_string(), stats_file.to_string());
@@ -318,6 +452,7 @@ impl AbrEncoder {
             }
             mode_params.insert("nal-hrd".to_string(), "cbr".to_string());
         }
+        apply_level_params(profile, metadata, filters, &mut mode_params);
 
         let x265_params = profile.build_x265_params_string_with_external_metadata_passthrough(
             Some(&mode_params),
@@ -338,23 +473,31 @@ impl AbrEncoder {
             "1024".to_string(),
         ]);
 
-        let filter_args = filters.build_ffmpeg_args();
+        let filter_args = filters.build_ffmpeg_args_for_input(video_specifier);
         let uses_filter_complex = filter_args.contains(&"-filter_complex".to_string());
         args.extend(filter_args);
 
-        // For filter_complex, we need to map the output
+        // For filter_complex, we need to map the output; the analysis pass only
+        // needs the stream that gets encoded
         if uses_filter_complex {
             args.extend(vec!["-map".to_string(), "[v]".to_string()]);
         } else {
-            args.extend(vec!["-map".to_string(), "0:v".to_string()]);
+            args.extend(vec!["-map".to_string(), video_specifier.to_string()]);
         }
 
         args.extend(vec!["-c:v".to_string(), "libx265".to_string()]);
 
+        // The analysis pass must see the same tune as the encode
+        if let Some(tune) = profile.get_tune() {
+            args.extend(vec!["-tune".to_string(), tune.to_string()]);
+        }
+
         if let Some(pix_fmt) = profile.get_pixel_format() {
             args.extend(vec!["-pix_fmt".to_string(), pix_fmt]);
         }
 
+        // The analysis pass must place the same keyframes as the encode
+        args.extend(force_key_frames_args(&self.keyframes));
         args.extend(vec![
             "-x265-params".to_string(),
             x265_params,
@@ -366,11 +509,15 @@ impl AbrEncoder {
         ]);
 
         tracing::debug!("Running pass 1/2...");
-        let mut child = ffmpeg.start_encoding(input_path, "/dev/null", args).await?;
-        let status = child.wait().await?;
-
-        if !status.success() {
-            return Err(Error::encoding("First pass encoding failed"));
+        let child = ffmpeg.start_encoding(input_path, "/dev/null", args).await?;
+        let output = child.wait_with_output().await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::encoding(match stderr.lines().last() {
+                Some(error) => format!("First pass encoding failed: {}", error),
+                None => "First pass encoding failed".to_string(),
+            }));
         }
 
         Ok(())
@@ -395,7 +542,7 @@ impl AbrEncoder {
         hdr_passthrough_mode: bool,
         is_cbr: bool,
     ) -> Result<tokio::process::Child> {
-        let mut mode_params = HashMap::new();
+        let mut mode_params = BTreeMap::new();
         mode_params.insert("pass".to_string(), "2".to_string());
         mode_params.insert("bitrate".to_string(), adaptive_bitrate.to_string());
         mode_params.insert("stats".to_string(), stats_file.to_string());
@@ -410,6 +557,7 @@ impl AbrEncoder {
             }
             mode_params.insert("nal-hrd".to_string(), "cbr".to_string());
         }
+        apply_level_params(profile, metadata, filters, &mut mode_params);
 
         let x265_params = profile.build_x265_params_string_with_external_metadata_passthrough(
             Some(&mode_params),
@@ -424,13 +572,15 @@ impl AbrEncoder {
         );
 
         let mut args = vec!["-i".to_string(), input_path.to_string()];
+        args.extend(stream_mapping.input_args.iter().cloned());
 
         args.extend(vec![
             "-max_muxing_queue_size".to_string(),
             "1024".to_string(),
         ]);
 
-        let filter_args = filters.build_ffmpeg_args();
+        let video_specifier = stream_mapping.primary_video_specifier();
+        let filter_args = video_filter_args(filters, stream_mapping);
         let uses_filter_complex = filter_args.contains(&"-filter_complex".to_string());
         args.extend(filter_args);
 
@@ -438,54 +588,56 @@ impl AbrEncoder {
 
         if uses_filter_complex {
             for i in 0..mapping_args.len() - 1 {
-                if mapping_args[i] == "-map" && mapping_args[i + 1] == "0:v:0" {
+                if mapping_args[i] == "-map" && mapping_args[i + 1] == video_specifier {
                     mapping_args[i + 1] = "[v]".to_string();
                     break;
                 }
             }
         }
 
-        args.extend(mapping_args);
-
-        args.extend(vec!["-c:v".to_string(), "libx265".to_string()]);
+        let mut video_args = vec!["-c:v".to_string(), "libx265".to_string()];
 
         if let Some(preset) = profile.get_preset() {
-            args.extend(vec!["-preset".to_string(), preset]);
+            video_args.extend(vec!["-preset".to_string(), preset]);
+        }
+
+        if let Some(tune) = profile.get_tune() {
+            video_args.extend(vec!["-tune".to_string(), tune.to_string()]);
         }
 
         if let Some(profile_name) = profile.get_profile() {
-            args.extend(vec!["-profile:v".to_string(), profile_name]);
+            video_args.extend(vec!["-profile:v".to_string(), profile_name]);
         }
 
         if let Some(pix_fmt) = profile.get_pixel_format() {
-            args.extend(vec!["-pix_fmt".to_string(), pix_fmt]);
+            video_args.extend(vec!["-pix_fmt".to_string(), pix_fmt]);
         }
 
-        args.extend(vec!["-x265-params".to_string(), x265_params]);
+        video_args.extend(force_key_frames_args(&self.keyframes));
+        video_args.extend(vec!["-x265-params".to_string(), x265_params]);
 
-        args.extend(vec![
-            "-default_mode".to_string(),
-            "infer_no_subs".to_string(),
-        ]);
+        let video_map = if uses_filter_complex {
+            "[v]"
+        } else {
+            video_specifier.as_str()
+        };
+        args.extend(output_stream_args(
+            &mapping_args,
+            video_map,
+            &video_args,
+            &stream_mapping.copied_video_args,
+            self.video_output.as_deref(),
+        ));
+
+        args.extend(container_args(Path::new(output_path), Some("hevc")));
 
         let stream_preservation =
             crate::stream::preservation::StreamPreservation::new(ffmpeg.clone());
         args.extend(stream_preservation.get_metadata_args(stream_mapping, custom_title));
 
-        let progress_file = format!("/tmp/ffmpeg_progress_{}.txt", std::process::id());
-        args.extend(vec![
-            "-progress".to_string(),
-            progress_file,
-            "-nostats".to_string(),
-            "-stats_period".to_string(),
-            "1.0".to_string(),
-        ]);
+        args.extend(FfmpegWrapper::progress_args());
 
-        args.extend(vec![
-            "-movflags".to_string(),
-            "+faststart".to_string(),
-            output_path.to_string(),
-        ]);
+        args.push(output_path.to_string());
 
         tracing::debug!("Running pass 2/2...");
 
@@ -499,6 +651,10 @@ impl AbrEncoder {
     }
 
     fn cleanup_stats_files(&self, stats_prefix: &str) {
+        if self.keep_stats {
+            tracing::info!("Kept two-pass stats files: {}-0.log*", stats_prefix);
+            return;
+        }
         let stats_files = [
             format!("{}-0.log", stats_prefix),
             format!("{}-0.log.mbtree", stats_prefix),
@@ -520,9 +676,24 @@ pub struct CbrEncoder {
 impl CbrEncoder {
     pub fn new() -> Self {
         Self {
-            abr_encoder: AbrEncoder,
+            abr_encoder: AbrEncoder::default(),
         }
     }
+
+    pub fn with_keep_stats(mut self, keep: bool) -> Self {
+        self.abr_encoder = self.abr_encoder.with_keep_stats(keep);
+        self
+    }
+
+    pub fn with_keyframes(mut self, times: Vec<f64>) -> Self {
+        self.abr_encoder = self.abr_encoder.with_keyframes(times);
+        self
+    }
+
+    pub fn with_video_output(mut self, path: Option<PathBuf>) -> Self {
+        self.abr_encoder = self.abr_encoder.with_video_output(path);
+        self
+    }
 }
 
 impl Default for CbrEncoder {
@@ -549,6 +720,7 @@ impl Encoder for CbrEncoder {
         hdr_passthrough_mode: bool,
     ) -> Result<tokio::process::Child> {
         tracing::debug!(
+            tree = "stage",
             "Starting CBR encoding (constant bitrate={}kbps)",
             adaptive_bitrate
         );
@@ -593,4 +765,100 @@ mod tests {
         assert_eq!(EncodingMode::ABR.as_str(), "abr");
         assert_eq!(EncodingMode::CBR.as_str(), "cbr");
     }
+
+    #[test]
+    fn test_filters_skip_copied_video_streams() {
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: Vec::new(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        let mut filters = FilterChain::new();
+        filters.add_filter("hqdn3d".to_string());
+        assert_eq!(video_filter_args(&filters, &mapping), ["-vf", "hqdn3d"]);
+
+        // A second video stream copied under `video_policy: all`
+        mapping.copied_video_args = ["-c:v:1", "copy"].map(String::from).to_vec();
+        assert_eq!(
+            video_filter_args(&filters, &mapping),
+            ["-filter:v:0", "hqdn3d"]
+        );
+    }
+
+    #[test]
+    fn test_container_args_tag_only_hevc_video() {
+        let mp4 = Path::new("out.mp4");
+        assert_eq!(
+            container_args(mp4, Some("hevc")),
+            [
+                "-tag:v:0",
+                "hvc1",
+                "-movflags",
+                "+faststart+use_metadata_tags"
+            ]
+        );
+        assert_eq!(
+            container_args(mp4, Some("h264")),
+            ["-movflags", "+faststart+use_metadata_tags"]
+        );
+        assert_eq!(
+            container_args(Path::new("out.mkv"), Some("hevc")),
+            ["-default_mode", "infer_no_subs"]
+        );
+    }
+
+    #[test]
+    fn test_raw_video_output() {
+        let strings = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let mapping_args = strings(&[
+            "-map", "[v]", "-map", "0:v:1", "-map", "0:a", "-c:a", "copy",
+        ]);
+        let video_args = strings(&["-c:v", "libx265"]);
+        let copied = strings(&["-c:v:1", "copy", "-disposition:v:1", "attached_pic"]);
+
+        assert_eq!(
+            output_stream_args(&mapping_args, "[v]", &video_args, &copied, None),
+            [mapping_args.clone(), video_args.clone(), copied.clone()].concat()
+        );
+
+        // The cover art becomes the first video stream of the streams output
+        assert_eq!(
+            output_stream_args(
+                &mapping_args,
+                "[v]",
+                &video_args,
+                &copied,
+                Some(Path::new("video.hevc"))
+            ),
+            strings(&[
+                "-map",
+                "[v]",
+                "-c:v",
+                "libx265",
+                "-f",
+                "hevc",
+                "video.hevc",
+                "-max_muxing_queue_size",
+                "1024",
+                "-map",
+                "0:v:1",
+                "-map",
+                "0:a",
+                "-c:a",
+                "copy",
+                "-c:v:0",
+                "copy",
+                "-disposition:v:0",
+                "attached_pic",
+            ])
+        );
+    }
 }
@@ -1,6 +1,11 @@
+use crate::config::CropDetectionOverrides;
+use crate::schedule::TimeWindow;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::path::PathBuf;
 
+/// Everything that controls one encode, independent of the command line.
+/// `encode` builds these from its flags; library users build them directly.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EncodingOptions {
     pub input_path: PathBuf,
@@ -8,9 +13,88 @@ pub struct EncodingOptions {
     pub profile_name: String,
     pub title: Option<String>,
     pub mode: String,
+    /// Fixed crop (`width:height:x:y`) instead of crop detection
     pub crop: Option<String>,
     pub denoise: bool,
     pub deinterlace: bool,
+    #[serde(default)]
+    pub stream_selection_profile: Option<String>,
+    /// Copy the video stream instead of encoding it
+    #[serde(default)]
+    pub video_copy: bool,
+    /// Position of the video stream to encode among the source's video
+    /// streams (`0:v:N`); `None` takes the first that is not cover art
+    #[serde(default)]
+    pub video_stream: Option<usize>,
+    /// Normalize audio loudness; implies `video_copy`
+    #[serde(default)]
+    pub normalize_audio: bool,
+    /// Copy the video stream when the source already meets the profile
+    /// (HEVC 10-bit within its bitrate ceiling) and needs no filters
+    #[serde(default)]
+    pub smart: bool,
+    #[serde(default)]
+    pub verify_streams: bool,
+    #[serde(default)]
+    pub error_resilience: bool,
+    #[serde(default = "default_true")]
+    pub use_analysis_cache: bool,
+    #[serde(default)]
+    pub dv_hdr10_fallback: bool,
+    /// Fail instead of writing an output without the source's Dolby
+    /// Vision/HDR10+ metadata when the metadata workflow fails
+    #[serde(default)]
+    pub strict_metadata: bool,
+    /// Record the automated decisions of the encode (`--explain`)
+    #[serde(default)]
+    pub explain: bool,
+    /// Reshape Dolby Vision profile 5 sources to HDR10 instead of refusing them
+    #[serde(default)]
+    pub dv5_convert: bool,
+    #[serde(default)]
+    pub device: Option<String>,
+    /// Daily window (`HH:MM-HH:MM`) outside which a running encode is suspended
+    #[serde(default)]
+    pub pause_window: Option<String>,
+    /// Scale the video down to this height (ladder renditions)
+    #[serde(default)]
+    pub scale_height: Option<u32>,
+    /// Tone map HDR and Dolby Vision sources to SDR (ladder renditions)
+    #[serde(default)]
+    pub tonemap: bool,
+    /// Write thumbnails next to the output (`thumbnails` in the config)
+    #[serde(default)]
+    pub thumbnails: bool,
+    /// Score the output against the source (`quality` in the config)
+    #[serde(default)]
+    pub quality_check: bool,
+    /// x265 preset replacing the profile's (planned by `--time-budget`)
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Crop detection settings for this encode only, over
+    /// `analysis.crop_detection`
+    #[serde(default)]
+    pub crop_detection: CropDetectionOverrides,
+    /// `output_path` was chosen explicitly: keep its extension instead of
+    /// switching to the profile's container
+    #[serde(default = "default_true")]
+    pub explicit_output: bool,
+    /// Replace an existing output that is not an earlier encode of this source
+    #[serde(default)]
+    pub overwrite: bool,
+    /// Leave the intermediate files of the encode in place for debugging
+    #[serde(default)]
+    pub keep_temp: bool,
+    /// Encode from this many seconds into the source (`--start`)
+    #[serde(default)]
+    pub start: Option<f64>,
+    /// Encode up to this many seconds into the source (`--end`)
+    #[serde(default)]
+    pub end: Option<f64>,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 impl EncodingOptions {
@@ -24,6 +108,31 @@ impl EncodingOptions {
             crop: None,
             denoise: false,
             deinterlace: false,
+            stream_selection_profile: None,
+            video_copy: false,
+            normalize_audio: false,
+            smart: false,
+            verify_streams: false,
+            error_resilience: false,
+            use_analysis_cache: true,
+            dv_hdr10_fallback: false,
+            strict_metadata: false,
+            explain: false,
+            dv5_convert: false,
+            device: None,
+            pause_window: None,
+            scale_height: None,
+            tonemap: false,
+            thumbnails: false,
+            quality_check: false,
+            preset: None,
+            crop_detection: CropDetectionOverrides::default(),
+            video_stream: None,
+            explicit_output: true,
+            overwrite: false,
+            keep_temp: false,
+            start: None,
+            end: None,
         }
     }
 
@@ -47,6 +156,26 @@ impl EncodingOptions {
         self
     }
 
+    pub fn with_crop_detection(mut self, overrides: CropDetectionOverrides) -> Self {
+        self.crop_detection = overrides;
+        self
+    }
+
+    pub fn with_video_stream(mut self, position: usize) -> Self {
+        self.video_stream = Some(position);
+        self
+    }
+
+    pub fn with_overwrite(mut self, enabled: bool) -> Self {
+        self.overwrite = enabled;
+        self
+    }
+
+    pub fn with_keep_temp(mut self, enabled: bool) -> Self {
+        self.keep_temp = enabled;
+        self
+    }
+
     pub fn with_denoise(mut self, enabled: bool) -> Self {
         self.denoise = enabled;
         self
@@ -57,10 +186,99 @@ impl EncodingOptions {
         self
     }
 
+    pub fn with_stream_selection_profile(mut self, profile: String) -> Self {
+        self.stream_selection_profile = Some(profile);
+        self
+    }
+
+    pub fn with_video_copy(mut self, enabled: bool) -> Self {
+        self.video_copy = enabled;
+        self
+    }
+
+    pub fn with_normalize_audio(mut self, enabled: bool) -> Self {
+        self.normalize_audio = enabled;
+        self
+    }
+
+    pub fn with_smart(mut self, enabled: bool) -> Self {
+        self.smart = enabled;
+        self
+    }
+
+    pub fn with_strict_metadata(mut self, enabled: bool) -> Self {
+        self.strict_metadata = enabled;
+        self
+    }
+
+    pub fn with_explain(mut self, enabled: bool) -> Self {
+        self.explain = enabled;
+        self
+    }
+
+    pub fn with_device(mut self, device: String) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    pub fn with_pause_window(mut self, window: String) -> Self {
+        self.pause_window = Some(window);
+        self
+    }
+
+    /// Encodes only the part of the source from `start` to `end` seconds.
+    pub fn with_trim(mut self, start: Option<f64>, end: Option<f64>) -> Self {
+        self.start = start;
+        self.end = end;
+        self
+    }
+
     pub fn is_auto_profile(&self) -> bool {
         self.profile_name == "auto"
     }
 
+    pub fn is_video_copy(&self) -> bool {
+        self.video_copy || self.normalize_audio
+    }
+
+    /// Whether only part of the source is encoded (`--start`/`--end`).
+    pub fn is_trimmed(&self) -> bool {
+        self.start.is_some() || self.end.is_some()
+    }
+
+    /// Seconds of a `duration`-second source the encode covers.
+    pub fn trimmed_duration(&self, duration: f64) -> f64 {
+        let end = self.end.map_or(duration, |end| end.min(duration));
+        (end - self.start.unwrap_or(0.0)).max(0.0)
+    }
+
+    /// `seconds` into the source on the timeline of the output, which starts
+    /// at `--start`; `None` outside the encoded part.
+    pub fn trimmed_time(&self, seconds: f64) -> Option<f64> {
+        let start = self.start.unwrap_or(0.0);
+        (seconds >= start && self.end.is_none_or(|end| seconds < end)).then_some(seconds - start)
+    }
+
+    /// Frames of a `total`-frame source at `fps` that a trimmed encode
+    /// covers; `None` when the whole source is encoded.
+    pub fn trimmed_frames(&self, fps: f32, total: u32) -> Option<Range<u32>> {
+        if !self.is_trimmed() {
+            return None;
+        }
+        let frame = |seconds: f64| ((seconds * fps as f64).round() as u32).min(total);
+        let start = frame(self.start.unwrap_or(0.0));
+        let end = self.end.map_or(total, frame);
+        Some(start..end)
+    }
+
+    /// The parsed pause window, if one was given.
+    pub fn pause_window(&self) -> crate::utils::Result<Option<TimeWindow>> {
+        self.pause_window
+            .as_deref()
+            .map(TimeWindow::parse)
+            .transpose()
+    }
+
     pub fn validate(&self) -> crate::utils::Result<()> {
         if !self.input_path.exists() {
             return Err(crate::utils::Error::validation(format!(
@@ -85,6 +303,24 @@ impl EncodingOptions {
             }
         }
 
+        if self.start.is_some_and(|start| start < 0.0) {
+            return Err(crate::utils::Error::validation(
+                "--start must not be negative".to_string(),
+            ));
+        }
+        if let Some(end) = self.end {
+            if end <= self.start.unwrap_or(0.0) {
+                return Err(crate::utils::Error::validation(format!(
+                    "--end {} must be after --start {}",
+                    end,
+                    self.start.unwrap_or(0.0)
+                )));
+            }
+        }
+
+        self.pause_window()?;
+        crate::utils::filesystem::ensure_not_source(&self.input_path, &self.output_path)?;
+
         Ok(())
     }
 
@@ -137,4 +373,48 @@ mod tests {
         assert!(!options.is_valid_crop_format("1920:800:0:abc"));
         assert!(!options.is_valid_crop_format("invalid"));
     }
+
+    #[test]
+    fn test_validate_output_is_not_source() {
+        let temp_input = NamedTempFile::new().unwrap();
+        let options = EncodingOptions::new(temp_input.path(), temp_input.path());
+        assert!(options.validate().is_err());
+
+        let output = temp_input.path().with_extension("x265.mkv");
+        let options = EncodingOptions::new(temp_input.path(), output.as_path());
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn test_trim_range() {
+        let temp_input = NamedTempFile::new().unwrap();
+        let output = temp_input.path().with_extension("x265.mkv");
+        let options = EncodingOptions::new(temp_input.path(), output.as_path());
+        assert!(!options.is_trimmed());
+        assert_eq!(options.trimmed_frames(24.0, 2400), None);
+        assert_eq!(options.trimmed_time(42.0), Some(42.0));
+
+        let options = options.with_trim(Some(10.0), Some(40.0));
+        assert!(options.validate().is_ok());
+        assert_eq!(options.trimmed_duration(100.0), 30.0);
+        assert_eq!(options.trimmed_frames(24.0, 2400), Some(240..960));
+        assert_eq!(options.trimmed_time(12.5), Some(2.5));
+        assert_eq!(options.trimmed_time(5.0), None);
+        assert_eq!(options.trimmed_time(40.0), None);
+
+        // --end past the end of the source stops at its last frame
+        let tail = options.clone().with_trim(Some(90.0), Some(200.0));
+        assert_eq!(tail.trimmed_duration(100.0), 10.0);
+        assert_eq!(tail.trimmed_frames(24.0, 2400), Some(2160..2400));
+
+        assert!(options
+            .clone()
+            .with_trim(Some(-1.0), None)
+            .validate()
+            .is_err());
+        assert!(options
+            .with_trim(Some(40.0), Some(10.0))
+            .validate()
+            .is_err());
+    }
 }
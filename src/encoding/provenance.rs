@@ -0,0 +1,231 @@
+//! Provenance tags VEN writes into its outputs.
+//!
+//! Every encode and remux gets global `VEN_*` tags naming the version, profile
+//! and rate control that produced it, plus a fingerprint of the source. A
+//! library can be queried for them later (`ffprobe -show_format`), and batch
+//! runs use them to skip files VEN wrote itself.
+
+use crate::config::EncodingProfile;
+use crate::encoding::EncodingMode;
+use crate::utils::{FfmpegWrapper, Result};
+use serde_json::Value;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const TAG_VERSION: &str = "VEN_VERSION";
+pub const TAG_PROFILE: &str = "VEN_PROFILE";
+pub const TAG_MODE: &str = "VEN_MODE";
+pub const TAG_CRF: &str = "VEN_CRF";
+pub const TAG_BITRATE: &str = "VEN_BITRATE";
+pub const TAG_SOURCE_HASH: &str = "VEN_SOURCE_HASH";
+
+/// Bytes hashed at the start, middle and end of a source
+const FINGERPRINT_CHUNK: u64 = 1024 * 1024;
+
+/// Settings that produced an output.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Provenance {
+    pub version: String,
+    pub profile: Option<String>,
+    /// `crf`, `abr`, `cbr`, or `copy` for remuxes
+    pub mode: String,
+    pub crf: Option<f32>,
+    /// Target video bitrate in kbps (ABR/CBR)
+    pub bitrate: Option<u32>,
+    pub source_hash: Option<String>,
+}
+
+impl Provenance {
+    /// Provenance of an x265 encode of `input_path`.
+    pub fn for_encode(
+        input_path: &Path,
+        profile: &EncodingProfile,
+        mode: EncodingMode,
+        crf: f32,
+        bitrate: u32,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            profile: Some(profile.name.clone()),
+            mode: mode.as_str().to_string(),
+            crf: (mode == EncodingMode::CRF).then_some(crf),
+            bitrate: (mode != EncodingMode::CRF).then_some(bitrate),
+            source_hash: source_fingerprint(input_path).ok(),
+        }
+    }
+
+    /// Provenance of a remux that copies the video of `input_path`.
+    pub fn for_remux(input_path: &Path, profile: Option<&str>) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            profile: profile.map(str::to_string),
+            mode: "copy".to_string(),
+            crf: None,
+            bitrate: None,
+            source_hash: source_fingerprint(input_path).ok(),
+        }
+    }
+
+    pub fn tags(&self) -> Vec<(&'static str, String)> {
+        let mut tags = vec![
+            (TAG_VERSION, self.version.clone()),
+            (TAG_MODE, self.mode.clone()),
+        ];
+        if let Some(profile) = &self.profile {
+            tags.push((TAG_PROFILE, profile.clone()));
+        }
+        if let Some(crf) = self.crf {
+            tags.push((TAG_CRF, format!("{:.1}", crf)));
+        }
+        if let Some(bitrate) = self.bitrate {
+            tags.push((TAG_BITRATE, bitrate.to_string()));
+        }
+        if let Some(hash) = &self.source_hash {
+            tags.push((TAG_SOURCE_HASH, hash.clone()));
+        }
+        tags
+    }
+
+    /// `-metadata KEY=value` pairs for the output's global tags.
+    pub fn metadata_args(&self) -> Vec<String> {
+        self.tags()
+            .into_iter()
+            .flat_map(|(key, value)| ["-metadata".to_string(), format!("{}={}", key, value)])
+            .collect()
+    }
+
+    /// Reads provenance back from a file's global tags; `None` unless the
+    /// file carries `VEN_VERSION`. Tag names are matched case-insensitively,
+    /// as containers differ in how they store them.
+    pub fn from_tags<'a>(tags: impl IntoIterator<Item = (&'a str, &'a str)>) -> Option<Self> {
+        let mut provenance = Self::default();
+        let mut has_version = false;
+        for (key, value) in tags {
+            match key.to_uppercase().as_str() {
+                TAG_VERSION => {
+                    provenance.version = value.to_string();
+                    has_version = true;
+                }
+                TAG_PROFILE => provenance.profile = Some(value.to_string()),
+                TAG_MODE => provenance.mode = value.to_string(),
+                TAG_CRF => provenance.crf = value.parse().ok(),
+                TAG_BITRATE => provenance.bitrate = value.parse().ok(),
+                TAG_SOURCE_HASH => provenance.source_hash = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        has_version.then_some(provenance)
+    }
+
+    /// Whether this output was encoded from `source`, by its fingerprint.
+    pub fn is_encode_of(&self, source: &Path) -> bool {
+        self.source_hash.is_some() && self.source_hash == source_fingerprint(source).ok()
+    }
+
+    /// Provenance of `path`, if VEN wrote it.
+    pub async fn read<P: AsRef<Path>>(ffmpeg: &FfmpegWrapper, path: P) -> Option<Self> {
+        let output = ffmpeg
+            .run_ffprobe(&[
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_format",
+                &path.as_ref().to_string_lossy(),
+            ])
+            .await
+            .ok()?;
+        let json: Value = serde_json::from_str(&output).ok()?;
+        let tags = json["format"]["tags"].as_object()?;
+        Self::from_tags(
+            tags.iter()
+                .filter_map(|(key, value)| Some((key.as_str(), value.as_str()?))),
+        )
+    }
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ven {}, {}", self.version, self.mode)?;
+        if let Some(profile) = &self.profile {
+            write!(f, ", profile {}", profile)?;
+        }
+        Ok(())
+    }
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Quick fingerprint of a source: its size and 1 MiB each from the start,
+/// middle and end, so even very large files are identified in milliseconds.
+pub fn source_fingerprint(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    let mut hash = fnv1a(0xcbf2_9ce4_8422_2325, &size.to_le_bytes());
+    let middle = (size / 2).saturating_sub(FINGERPRINT_CHUNK / 2);
+    let end = size.saturating_sub(FINGERPRINT_CHUNK);
+    let mut buffer = Vec::with_capacity(FINGERPRINT_CHUNK as usize);
+    for offset in [0, middle, end] {
+        buffer.clear();
+        file.seek(SeekFrom::Start(offset))?;
+        (&mut file)
+            .take(FINGERPRINT_CHUNK)
+            .read_to_end(&mut buffer)?;
+        hash = fnv1a(hash, &buffer);
+    }
+    Ok(format!("{:016x}", hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provenance_tags_roundtrip() {
+        let provenance = Provenance {
+            version: "1.2.0".to_string(),
+            profile: Some("anime".to_string()),
+            mode: "crf".to_string(),
+            crf: Some(21.5),
+            bitrate: None,
+            source_hash: Some("00ff00ff00ff00ff".to_string()),
+        };
+        let args = provenance.metadata_args();
+        assert_eq!(&args[..2], ["-metadata", "VEN_VERSION=1.2.0"]);
+        assert!(args.contains(&"VEN_CRF=21.5".to_string()));
+
+        // Containers may hand the keys back in another case
+        let tags: Vec<(String, String)> = provenance
+            .tags()
+            .into_iter()
+            .map(|(key, value)| (key.to_lowercase(), value))
+            .chain([("title".to_string(), "Movie".to_string())])
+            .collect();
+        let read =
+            Provenance::from_tags(tags.iter().map(|(k, v)| (k.as_str(), v.as_str()))).unwrap();
+        assert_eq!(read, provenance);
+        assert!(Provenance::from_tags([("title", "Movie")]).is_none());
+
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source.mkv");
+        std::fs::write(&source, vec![7u8; 3 * 1024 * 1024]).unwrap();
+        let fingerprint = source_fingerprint(&source).unwrap();
+        assert_eq!(fingerprint.len(), 16);
+        assert_eq!(source_fingerprint(&source).unwrap(), fingerprint);
+        let earlier = Provenance {
+            source_hash: Some(fingerprint.clone()),
+            ..provenance.clone()
+        };
+        assert!(earlier.is_encode_of(&source));
+        std::fs::write(&source, vec![7u8; 3 * 1024 * 1024 + 1]).unwrap();
+        assert_ne!(source_fingerprint(&source).unwrap(), fingerprint);
+        assert!(!earlier.is_encode_of(&source));
+        assert!(!Provenance::default().is_encode_of(&source));
+    }
+}
@@ -0,0 +1,166 @@
+use crate::encoding::modes::{container_args, output_stream_args};
+use crate::stream::preservation::{StreamMapping, StreamPreservation};
+use crate::utils::{FfmpegWrapper, FileLogger, Result};
+use std::path::Path;
+
+/// Builds the FFmpeg arguments for a remux: the selected streams are copied
+/// unchanged (video included) with the usual chapter/metadata/title handling.
+/// `video_codec` is the codec of the copied primary video stream, which
+/// decides whether an MP4 output tags it `hvc1`. With a `video_output`, the primary video is copied there as a raw HEVC
+/// stream for mkvmerge to mux; see [`output_stream_args`].
+pub fn build_remux_args(
+    ffmpeg: &FfmpegWrapper,
+    input_path: &Path,
+    output_path: &Path,
+    stream_mapping: &StreamMapping,
+    custom_title: Option<&str>,
+    video_codec: Option<&str>,
+    video_output: Option<&Path>,
+) -> Vec<String> {
+    let mut args = vec!["-i".to_string(), input_path.to_string_lossy().to_string()];
+    args.extend(stream_mapping.input_args.iter().cloned());
+    args.extend(["-max_muxing_queue_size".to_string(), "1024".to_string()]);
+
+    args.extend(output_stream_args(
+        &stream_mapping.mapping_args,
+        &stream_mapping.primary_video_specifier(),
+        &["-c:v".to_string(), "copy".to_string()],
+        &stream_mapping.copied_video_args,
+        video_output,
+    ));
+    args.extend(container_args(output_path, video_codec));
+
+    let stream_preservation = StreamPreservation::new(ffmpeg.clone());
+    args.extend(stream_preservation.get_metadata_args(stream_mapping, custom_title));
+
+    args.extend(FfmpegWrapper::progress_args());
+    args.push(output_path.to_string_lossy().to_string());
+
+    args
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn start_remux(
+    ffmpeg: &FfmpegWrapper,
+    input_path: &Path,
+    output_path: &Path,
+    stream_mapping: &StreamMapping,
+    custom_title: Option<&str>,
+    video_codec: Option<&str>,
+    video_output: Option<&Path>,
+    file_logger: Option<&FileLogger>,
+) -> Result<tokio::process::Child> {
+    let args = build_remux_args(
+        ffmpeg,
+        input_path,
+        output_path,
+        stream_mapping,
+        custom_title,
+        video_codec,
+        video_output,
+    );
+
+    tracing::debug!(
+        "Starting remux ({} audio, {} subtitle streams)",
+        stream_mapping.audio_streams.len(),
+        stream_mapping.subtitle_streams.len()
+    );
+
+    if let Some(logger) = file_logger {
+        if let Err(e) = logger.log_ffmpeg_command(ffmpeg.get_ffmpeg_path(), &args) {
+            tracing::warn!("Failed to log ffmpeg command: {}", e);
+        }
+    }
+
+    ffmpeg.start_encoding(input_path, output_path, args).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remux_args_copy_video_and_keep_mapping() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            copied_video_args: Vec::new(),
+            mapping_args: vec![
+                "-map".to_string(),
+                "0:v:0".to_string(),
+                "-map".to_string(),
+                "0:2".to_string(),
+                "-c:a".to_string(),
+                "copy".to_string(),
+            ],
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        let args = build_remux_args(
+            &ffmpeg,
+            Path::new("in.mkv"),
+            Path::new("out.mkv"),
+            &mapping,
+            Some("Title"),
+            Some("hevc"),
+            None,
+        );
+
+        let joined = args.join(" ");
+        assert!(joined.contains("-map 0:v:0 -map 0:2 -c:a copy -c:v copy"));
+        assert!(joined.contains("-default_mode infer_no_subs"));
+        assert!(joined.contains("title=Title"));
+        assert!(!joined.contains("libx265"));
+        assert!(!joined.contains("-movflags"));
+        assert_eq!(args.last().unwrap(), "out.mkv");
+
+        let args = build_remux_args(
+            &ffmpeg,
+            Path::new("in.mkv"),
+            Path::new("out.mp4"),
+            &mapping,
+            None,
+            Some("hevc"),
+            None,
+        );
+        assert!(args
+            .join(" ")
+            .contains("-tag:v:0 hvc1 -movflags +faststart+use_metadata_tags"));
+
+        // An H.264 source is copied into the MP4 untagged
+        let args = build_remux_args(
+            &ffmpeg,
+            Path::new("in.mkv"),
+            Path::new("out.mp4"),
+            &mapping,
+            None,
+            Some("h264"),
+            None,
+        );
+        let joined = args.join(" ");
+        assert!(!joined.contains("hvc1"));
+        assert!(joined.contains("-c:v copy -movflags +faststart+use_metadata_tags"));
+
+        // mkvmerge muxes the raw video with the other streams
+        let args = build_remux_args(
+            &ffmpeg,
+            Path::new("in.mkv"),
+            Path::new("streams.mkv"),
+            &mapping,
+            None,
+            Some("hevc"),
+            Some(Path::new("video.hevc")),
+        );
+        assert!(args.join(" ").contains(
+            "-map 0:v:0 -c:v copy -f hevc video.hevc -max_muxing_queue_size 1024 -map 0:2 -c:a copy -default_mode"
+        ));
+        assert_eq!(args.last().unwrap(), "streams.mkv");
+    }
+}
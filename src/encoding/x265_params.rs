@@ -0,0 +1,624 @@
+//! Checks for the `x265_params` of encoding profiles.
+//!
+//! Profiles are validated against the options x265 knows when the config is
+//! loaded, and right before an encode [`sanitize_rate_control`] drops the
+//! rate-control keys that the selected [`EncodingMode`] sets itself,
+//! [`enforce_hdr_bit_depth`] makes sure HDR sources are encoded in 10-bit,
+//! [`add_hdr_signaling`] adds the options players rely on to find HDR10 and
+//! [`enforce_dolby_vision_gop`] sets the GOP structure Dolby Vision needs.
+
+use super::EncodingMode;
+use crate::utils::{Error, Result};
+use std::collections::BTreeMap;
+
+/// x265 options (`x265 --fullhelp`) accepted in profiles; boolean options
+/// may also be given with a `no-` prefix.
+const KNOWN_PARAMS: &[&str] = &[
+    "allow-non-conformance",
+    "amp",
+    "analysis-load",
+    "analysis-load-reuse-level",
+    "analysis-reuse-level",
+    "analysis-save",
+    "analysis-save-reuse-level",
+    "analyze-src-pics",
+    "annexb",
+    "aq-mode",
+    "aq-motion",
+    "aq-strength",
+    "asm",
+    "atc-sei",
+    "aud",
+    "b-adapt",
+    "b-intra",
+    "b-pyramid",
+    "bframe-bias",
+    "bframes",
+    "bitrate",
+    "cbqpoffs",
+    "chromaloc",
+    "cll",
+    "colormatrix",
+    "colorprim",
+    "const-vbv",
+    "constrained-intra",
+    "cplxblur",
+    "crf",
+    "crf-max",
+    "crf-min",
+    "crqpoffs",
+    "csv",
+    "csv-log-level",
+    "ctu",
+    "ctu-info",
+    "cu-lossless",
+    "cutree",
+    "deblock",
+    "dhdr10-info",
+    "dhdr10-opt",
+    "display-window",
+    "dither",
+    "dolby-vision-profile",
+    "dolby-vision-rpu",
+    "dynamic-rd",
+    "dynamic-refine",
+    "early-skip",
+    "fades",
+    "fast-intra",
+    "field",
+    "film-grain",
+    "force-flush",
+    "fps",
+    "frame-dup",
+    "frame-threads",
+    "gop-lookahead",
+    "hash",
+    "hdr",
+    "hdr-opt",
+    "hdr10",
+    "hdr10-opt",
+    "hevc-aq",
+    "high-tier",
+    "hist-scenecut",
+    "hist-threshold",
+    "hme",
+    "hme-range",
+    "hme-search",
+    "hrd",
+    "hrd-concat",
+    "idr-recovery-sei",
+    "info",
+    "input-csp",
+    "input-depth",
+    "interlace",
+    "intra-refresh",
+    "ipratio",
+    "keyint",
+    "lambda-file",
+    "level-idc",
+    "limit-modes",
+    "limit-refs",
+    "limit-sao",
+    "limit-tu",
+    "log-level",
+    "log2-max-poc-lsb",
+    "lookahead-slices",
+    "lookahead-threads",
+    "lossless",
+    "lowpass-dct",
+    "master-display",
+    "max-ausize-factor",
+    "max-cll",
+    "max-luma",
+    "max-merge",
+    "max-tu-size",
+    "max-vbv-fullness",
+    "mcstf",
+    "me",
+    "merange",
+    "min-cu-size",
+    "min-keyint",
+    "min-luma",
+    "min-vbv-fullness",
+    "multi-pass-opt-analysis",
+    "multi-pass-opt-distortion",
+    "multi-pass-opt-rps",
+    "nalu-file",
+    "nr-inter",
+    "nr-intra",
+    "numa-pools",
+    "open-gop",
+    "opt-cu-delta-qp",
+    "opt-qp-pps",
+    "opt-ref-list-length-pps",
+    "output-depth",
+    "overscan",
+    "pass",
+    "pbratio",
+    "pic-struct",
+    "pme",
+    "pmode",
+    "pools",
+    "preset",
+    "profile",
+    "psnr",
+    "psy-rd",
+    "psy-rdoq",
+    "qblur",
+    "qcomp",
+    "qg-size",
+    "qp",
+    "qp-adaptation-range",
+    "qpfile",
+    "qpmax",
+    "qpmin",
+    "qpstep",
+    "radl",
+    "range",
+    "rc-grain",
+    "rc-lookahead",
+    "rd",
+    "rd-refine",
+    "rdoq-level",
+    "rdpenalty",
+    "rect",
+    "recursion-skip",
+    "ref",
+    "refine-ctu-distortion",
+    "refine-inter",
+    "refine-intra",
+    "refine-mv",
+    "refine-mv-type",
+    "repeat-headers",
+    "rskip",
+    "rskip-edge-threshold",
+    "sao",
+    "sao-non-deblock",
+    "sar",
+    "sbrc",
+    "scale-factor",
+    "scaling-list",
+    "scenecut",
+    "scenecut-bias",
+    "selective-sao",
+    "signhide",
+    "single-sei",
+    "slices",
+    "slow-firstpass",
+    "splitrd-skip",
+    "ssim",
+    "ssim-rd",
+    "stats",
+    "strict-cbr",
+    "strong-intra-smoothing",
+    "subme",
+    "temporal-layers",
+    "temporal-mvp",
+    "transfer",
+    "tskip",
+    "tskip-fast",
+    "tu-inter-depth",
+    "tu-intra-depth",
+    "tune",
+    "uhd-bd",
+    "vbv-bufsize",
+    "vbv-end",
+    "vbv-end-fr-adj",
+    "vbv-init",
+    "vbv-maxrate",
+    "videoformat",
+    "vui-hrd-info",
+    "vui-timing-info",
+    "weightb",
+    "weightp",
+    "wpp",
+    "zonefile",
+    "zones",
+];
+
+/// Profile keys that are FFmpeg options rather than x265 ones
+const FFMPEG_PARAMS: &[&str] = &["pix_fmt"];
+
+/// x265 accepts `_` for `-` in option names (`hdr10_opt`)
+fn normalize(key: &str) -> String {
+    key.replace('_', "-")
+}
+
+pub fn is_known_param(key: &str) -> bool {
+    if FFMPEG_PARAMS.contains(&key) {
+        return true;
+    }
+    let key = normalize(key);
+    let known = |name: &str| KNOWN_PARAMS.binary_search(&name).is_ok();
+    known(&key) || key.strip_prefix("no-").is_some_and(known)
+}
+
+/// Keys x265 would reject, sorted.
+pub fn unknown_params<'a>(keys: impl IntoIterator<Item = &'a String>) -> Vec<&'a str> {
+    let mut unknown: Vec<&str> = keys
+        .into_iter()
+        .map(String::as_str)
+        .filter(|key| !is_known_param(key))
+        .collect();
+    unknown.sort_unstable();
+    unknown
+}
+
+/// Removes the rate-control keys that contradict or duplicate what `mode`
+/// passes to x265 (e.g. `crf` in ABR mode, `bitrate` in CRF mode) and returns
+/// a warning for each.
+pub fn sanitize_rate_control(
+    params: &mut BTreeMap<String, String>,
+    mode: EncodingMode,
+) -> Vec<String> {
+    let mode_name = mode.as_str().to_uppercase();
+    let keys: Vec<String> = params.keys().cloned().collect();
+
+    let mut warnings = Vec::new();
+    for key in keys {
+        let reason = match (normalize(&key).as_str(), mode) {
+            ("crf", EncodingMode::CRF) => {
+                "is replaced by the adaptive CRF (set base_crf instead)".to_string()
+            }
+            ("bitrate", EncodingMode::ABR | EncodingMode::CBR) => {
+                "is replaced by the adaptive bitrate (set the profile's bitrate instead)"
+                    .to_string()
+            }
+            // CRF bounds only mean something in CRF mode
+            ("crf-max" | "crf-min", EncodingMode::CRF) => continue,
+            ("crf" | "crf-max" | "crf-min" | "bitrate" | "qp" | "lossless", _) => {
+                format!("conflicts with {} mode", mode_name)
+            }
+            ("pass" | "stats", _) => format!("is set by the encoder in {} mode", mode_name),
+            _ => continue,
+        };
+        if let Some(value) = params.remove(&key) {
+            warnings.push(format!("x265 param {}={} {}; removed", key, value, reason));
+        }
+    }
+    warnings
+}
+
+/// The setting among `output-depth`, `pix_fmt` and `profile` that limits
+/// the output to 8-bit, as `key=value`.
+fn eight_bit_pin(params: &BTreeMap<String, String>) -> Option<String> {
+    let high_depth = |value: &str| ["10", "12", "16"].iter().any(|d| value.contains(d));
+    params.iter().find_map(|(key, value)| {
+        let pins_8bit = match normalize(key).as_str() {
+            "output-depth" => value.trim().parse::<u32>().is_ok_and(|depth| depth < 10),
+            "pix-fmt" | "profile" => !high_depth(value),
+            _ => false,
+        };
+        pins_8bit.then(|| format!("{}={}", key, value))
+    })
+}
+
+/// Makes an HDR encode 10-bit: adds `output-depth=10` and
+/// `pix_fmt=yuv420p10le` where the profile leaves them out, and returns what
+/// was added. A profile that pins 8-bit is an error (8-bit PQ bands visibly)
+/// unless `allow_8bit` is set, in which case it is kept with a warning.
+pub fn enforce_hdr_bit_depth(
+    params: &mut BTreeMap<String, String>,
+    allow_8bit: bool,
+) -> Result<Vec<String>> {
+    if let Some(pin) = eight_bit_pin(params) {
+        if allow_8bit {
+            return Ok(vec![format!(
+                "x265 param {} keeps this HDR encode 8-bit (encoding.allow_8bit_hdr); expect banding",
+                pin
+            )]);
+        }
+        return Err(Error::validation(format!(
+            "x265 param {} would encode this HDR source in 8-bit, which bands visibly; \
+             remove it, or set encoding.allow_8bit_hdr: true to encode 8-bit anyway",
+            pin
+        )));
+    }
+
+    let mut added = Vec::new();
+    for (key, value) in [("output-depth", "10"), ("pix_fmt", "yuv420p10le")] {
+        if !params.keys().any(|k| normalize(k) == normalize(key)) {
+            params.insert(key.to_string(), value.to_string());
+            added.push(format!(
+                "x265 param {}={} added for 10-bit HDR output",
+                key, value
+            ));
+        }
+    }
+    Ok(added)
+}
+
+/// Whether `key` (any spelling, or its `no-` form) is set in `params`, and
+/// if so whether it is enabled.
+fn flag_setting(params: &BTreeMap<String, String>, key: &str) -> Option<(String, bool)> {
+    params.iter().find_map(|(k, value)| {
+        let normalized = normalize(k);
+        if normalized == key {
+            let disabled = ["0", "false", "no", "off"].contains(&value.trim());
+            let setting = if value.is_empty() {
+                k.clone()
+            } else {
+                format!("{}={}", k, value)
+            };
+            Some((setting, !disabled))
+        } else if normalized.strip_prefix("no-") == Some(key) {
+            Some((k.clone(), false))
+        } else {
+            None
+        }
+    })
+}
+
+/// Whether the profile sets the boolean option `key` in any spelling.
+pub fn is_set(params: &BTreeMap<String, String>, key: &str) -> bool {
+    flag_setting(params, &normalize(key)).is_some()
+}
+
+/// Adds `hdr10=1`, `hdr10_opt=1` (both only for PQ) and `repeat-headers=1`
+/// to an HDR encode where the profile leaves them out, so the HDR10 SEI is
+/// written, QP is tuned for PQ and players joining mid-stream find the
+/// headers. Returns what was added, and a warning for every option the
+/// profile disables, which is kept.
+pub fn add_hdr_signaling(
+    params: &mut BTreeMap<String, String>,
+    pq: bool,
+) -> (Vec<String>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut conflicts = Vec::new();
+    for (key, pq_only) in [
+        ("hdr10", true),
+        ("hdr10_opt", true),
+        ("repeat-headers", false),
+    ] {
+        if pq_only && !pq {
+            continue;
+        }
+        match flag_setting(params, &normalize(key)) {
+            None => {
+                params.insert(key.to_string(), "1".to_string());
+                added.push(format!("{}=1", key));
+            }
+            Some((setting, false)) => conflicts.push(format!(
+                "x265 param {} overrides {}=1 for HDR (encoding.hdr_signaling); kept",
+                setting, key
+            )),
+            Some((_, true)) => {}
+        }
+    }
+    (added, conflicts)
+}
+
+/// Sets `key` to `value`, replacing it in any spelling, with a warning for
+/// each value replaced.
+fn replace_param(
+    params: &mut BTreeMap<String, String>,
+    key: &str,
+    value: String,
+    warnings: &mut Vec<String>,
+) {
+    let existing: Vec<String> = params
+        .keys()
+        .filter(|k| normalize(k) == key)
+        .cloned()
+        .collect();
+    for k in existing {
+        if let Some(old) = params.remove(&k) {
+            warnings.push(format!(
+                "x265 param {}={} replaced by {}={} for Dolby Vision",
+                k, old, key, value
+            ));
+        }
+    }
+    params.insert(key.to_string(), value);
+}
+
+/// Longest GOP of a Dolby Vision encode, in seconds
+const DOLBY_VISION_MAX_GOP_SECONDS: f32 = 2.0;
+
+/// Makes the GOPs of a Dolby Vision encode closed (`no-open-gop`) and at
+/// most two seconds long at `fps` (`keyint`), with `min-keyint` at most half
+/// of that, as DV players need to start decoding at every keyframe. Profile
+/// values that go against it are replaced; returns a warning for each.
+pub fn enforce_dolby_vision_gop(params: &mut BTreeMap<String, String>, fps: f32) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let Some((setting, true)) = flag_setting(params, "open-gop") {
+        params.retain(|k, _| normalize(k) != "open-gop");
+        warnings.push(format!(
+            "x265 param {} replaced by no-open-gop for Dolby Vision",
+            setting
+        ));
+    }
+    if !is_set(params, "open-gop") {
+        params.insert("no-open-gop".to_string(), String::new());
+    }
+
+    let fps = if fps > 0.0 { fps } else { 24.0 };
+    let max_keyint = ((fps * DOLBY_VISION_MAX_GOP_SECONDS).round() as u32).max(2);
+    let param = |params: &BTreeMap<String, String>, key: &str| {
+        params
+            .iter()
+            .find(|(k, _)| normalize(k) == key)
+            .map(|(_, value)| value.trim().parse::<i64>().ok())
+    };
+    let keyint = match param(params, "keyint") {
+        Some(Some(keyint)) if keyint > 0 && keyint <= max_keyint as i64 => keyint as u32,
+        _ => {
+            replace_param(params, "keyint", max_keyint.to_string(), &mut warnings);
+            max_keyint
+        }
+    };
+    let max_min_keyint = (keyint / 2).max(1);
+    match param(params, "min-keyint") {
+        Some(Some(min_keyint)) if min_keyint > 0 && min_keyint <= max_min_keyint as i64 => {}
+        _ => replace_param(
+            params,
+            "min-keyint",
+            (fps.round() as u32).clamp(1, max_min_keyint).to_string(),
+            &mut warnings,
+        ),
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_params() {
+        assert!(KNOWN_PARAMS.windows(2).all(|pair| pair[0] < pair[1]));
+
+        assert!(is_known_param("aq-mode"));
+        assert!(is_known_param("no-sao"));
+        assert!(is_known_param("hdr10_opt"));
+        assert!(is_known_param("pix_fmt"));
+
+        let keys = ["psy-rd", "crf", "no-such-option", "bframe"].map(String::from);
+        assert_eq!(unknown_params(&keys), vec!["bframe", "no-such-option"]);
+    }
+
+    #[test]
+    fn test_sanitize_rate_control() {
+        let params = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let mut crf_params = params(&[("crf", "20"), ("bitrate", "8000"), ("aq-mode", "3")]);
+        let warnings = sanitize_rate_control(&mut crf_params, EncodingMode::CRF);
+        assert_eq!(crf_params, params(&[("aq-mode", "3")]));
+        assert_eq!(
+            warnings,
+            vec![
+                "x265 param bitrate=8000 conflicts with CRF mode; removed",
+                "x265 param crf=20 is replaced by the adaptive CRF (set base_crf instead); removed",
+            ]
+        );
+
+        // A profile capping the adaptive CRF keeps its cap
+        let mut capped = params(&[("crf-max", "26"), ("crf-min", "18")]);
+        assert!(sanitize_rate_control(&mut capped, EncodingMode::CRF).is_empty());
+        assert_eq!(capped.len(), 2);
+
+        let mut abr_params = params(&[("crf", "20"), ("crf-max", "24"), ("pass", "2")]);
+        let warnings = sanitize_rate_control(&mut abr_params, EncodingMode::ABR);
+        assert!(abr_params.is_empty());
+        assert_eq!(warnings.len(), 3);
+
+        let mut clean = params(&[("vbv-maxrate", "20000")]);
+        assert!(sanitize_rate_control(&mut clean, EncodingMode::CBR).is_empty());
+        assert_eq!(clean.len(), 1);
+    }
+
+    #[test]
+    fn test_enforce_hdr_bit_depth() {
+        let params = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let mut missing = params(&[("preset", "slow"), ("profile", "main10")]);
+        assert_eq!(enforce_hdr_bit_depth(&mut missing, false).unwrap().len(), 2);
+        assert_eq!(missing["output-depth"], "10");
+        assert_eq!(missing["pix_fmt"], "yuv420p10le");
+
+        let mut ten_bit = params(&[("output_depth", "10"), ("pix_fmt", "p010le")]);
+        assert!(enforce_hdr_bit_depth(&mut ten_bit, false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(ten_bit.len(), 2);
+
+        for pin in [
+            ("pix_fmt", "yuv420p"),
+            ("output-depth", "8"),
+            ("profile", "main"),
+        ] {
+            let mut pinned = params(&[pin]);
+            let error = enforce_hdr_bit_depth(&mut pinned, false).unwrap_err();
+            assert!(error.to_string().contains(&format!("{}={}", pin.0, pin.1)));
+            assert_eq!(enforce_hdr_bit_depth(&mut pinned, true).unwrap().len(), 1);
+            assert_eq!(pinned.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_add_hdr_signaling() {
+        let params = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let mut missing = params(&[("preset", "slow")]);
+        let (added, conflicts) = add_hdr_signaling(&mut missing, true);
+        assert_eq!(added, ["hdr10=1", "hdr10_opt=1", "repeat-headers=1"]);
+        assert!(conflicts.is_empty());
+        assert_eq!(missing.len(), 4);
+
+        // HLG gets no PQ signaling
+        let mut hlg = params(&[]);
+        assert_eq!(add_hdr_signaling(&mut hlg, false).0, ["repeat-headers=1"]);
+
+        // Explicit profile values are kept, whatever their spelling
+        let mut explicit = params(&[
+            ("hdr10-opt", "1"),
+            ("no-hdr10", ""),
+            ("repeat_headers", "false"),
+        ]);
+        let (added, conflicts) = add_hdr_signaling(&mut explicit, true);
+        assert!(added.is_empty());
+        assert_eq!(
+            conflicts,
+            [
+                "x265 param no-hdr10 overrides hdr10=1 for HDR (encoding.hdr_signaling); kept",
+                "x265 param repeat_headers=false overrides repeat-headers=1 for HDR (encoding.hdr_signaling); kept",
+            ]
+        );
+        assert_eq!(explicit.len(), 3);
+    }
+
+    #[test]
+    fn test_enforce_dolby_vision_gop() {
+        let params = |pairs: &[(&str, &str)]| -> BTreeMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let mut missing = params(&[("preset", "slow")]);
+        assert!(enforce_dolby_vision_gop(&mut missing, 23.976).is_empty());
+        assert_eq!(
+            missing,
+            params(&[
+                ("keyint", "48"),
+                ("min-keyint", "24"),
+                ("no-open-gop", ""),
+                ("preset", "slow"),
+            ])
+        );
+
+        let mut conflicting = params(&[("open-gop", ""), ("keyint", "250"), ("min_keyint", "60")]);
+        let warnings = enforce_dolby_vision_gop(&mut conflicting, 50.0);
+        assert_eq!(
+            warnings,
+            [
+                "x265 param open-gop replaced by no-open-gop for Dolby Vision",
+                "x265 param keyint=250 replaced by keyint=100 for Dolby Vision",
+                "x265 param min_keyint=60 replaced by min-keyint=50 for Dolby Vision",
+            ]
+        );
+        assert_eq!(conflicting["keyint"], "100");
+        assert!(conflicting.contains_key("no-open-gop"));
+
+        // Shorter GOPs and a closed GOP set by the profile are kept
+        let mut closed = params(&[("no-open-gop", ""), ("keyint", "24"), ("min-keyint", "12")]);
+        assert!(enforce_dolby_vision_gop(&mut closed, 23.976).is_empty());
+        assert_eq!(closed.len(), 3);
+    }
+}
@@ -0,0 +1,443 @@
+//! High-level API for embedding VEN in other Rust programs.
+//!
+//! [`VenEngine`] wraps configuration, tool lookup and the processing pipeline
+//! behind two calls, [`VenEngine::analyze`] and [`VenEngine::encode_with`].
+//! Progress is reported as typed [`EncodeEvent`](crate::EncodeEvent)s on a
+//! channel instead of the terminal progress bar:
+//!
+//! ```no_run
+//! # async fn run() -> ven::Result<()> {
+//! use ven::{EncodingOptions, VenEngine};
+//!
+//! let (events, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+//! let engine = VenEngine::from_config_file(None)?.with_events(events);
+//! tokio::spawn(async move {
+//!     while let Some(event) = receiver.recv().await {
+//!         println!("{:?}", event);
+//!     }
+//! });
+//!
+//! let options = EncodingOptions::new("input.mkv", "output.mkv").with_mode("crf".to_string());
+//! let outcome = engine.encode_with(&options).await?;
+//! println!("Wrote {}", outcome.output_path.display());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Profile auto-selection classifies the source with a bitrate heuristic;
+//! [`VenEngine::with_classifier`] puts a [`ContentClassifier`] of your own
+//! (e.g. a local ML model) in front of it.
+
+use crate::analysis::{
+    CachedAnalysis, ClassifierChain, ContentClassifier, CropAnalysisResult, CropDetector,
+    VideoFingerprint,
+};
+use crate::audio::LanguageDetector;
+use crate::config::{Config, ProfileManager};
+use crate::content_manager::{
+    ContentAnalysisResult, ContentEncodingApproach, UnifiedContentManager,
+};
+use crate::encoding::{EncodingOptions, Provenance};
+use crate::events::{EventSender, EventSink};
+use crate::explain::Decision;
+use crate::hdr10plus::Hdr10PlusProfile;
+use crate::processing::{ladder, lookahead, VideoProcessor};
+use crate::progress::BatchProgress;
+use crate::quality::QualityScore;
+use crate::stream::preservation::{StreamMapping, StreamPreservation};
+use crate::utils::ffmpeg::VideoMetadata;
+use crate::utils::{CancelToken, Error, FfmpegWrapper, Result};
+use futures::stream::{self, StreamExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Result of [`VenEngine::analyze`].
+#[derive(Debug, Clone)]
+pub struct AnalysisReport {
+    pub metadata: VideoMetadata,
+    pub content: ContentAnalysisResult,
+    /// `None` when crop detection is disabled in the configuration
+    pub crop: Option<CropAnalysisResult>,
+    pub streams: StreamMapping,
+}
+
+/// One rendition of [`VenEngine::encode_ladder`].
+#[derive(Debug)]
+pub struct RenditionResult {
+    pub name: String,
+    /// Output the rendition was started with; see [`EncodeOutcome::output_path`]
+    /// for where it ended up
+    pub output_path: PathBuf,
+    pub result: Result<EncodeOutcome>,
+}
+
+/// Result of [`VenEngine::encode_with`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeOutcome {
+    pub output_path: PathBuf,
+    pub elapsed: Duration,
+    /// Profile the video was encoded with; `None` for a stream copy
+    pub profile: Option<String>,
+    /// Frames written, as reported by FFmpeg
+    pub frames: Option<u32>,
+    /// Scores of the quality check; empty when none ran
+    pub quality: Vec<QualityScore>,
+    /// Automated decisions of the encode; empty without `--explain`
+    pub decisions: Vec<Decision>,
+    /// Profile of the HDR10+ metadata carried into the output
+    pub hdr10plus_profile: Option<Hdr10PlusProfile>,
+    /// Perceptual fingerprint of the source (`analysis.fingerprint`)
+    pub fingerprint: Option<VideoFingerprint>,
+}
+
+impl EncodeOutcome {
+    /// Frames per second over the whole run, including analysis and muxing.
+    pub fn average_fps(&self) -> Option<f64> {
+        let seconds = self.elapsed.as_secs_f64();
+        self.frames
+            .filter(|_| seconds > 0.0)
+            .map(|frames| frames as f64 / seconds)
+    }
+}
+
+/// Entry point for using VEN as a library.
+pub struct VenEngine {
+    config: Config,
+    ffmpeg: FfmpegWrapper,
+    events: Option<EventSender>,
+    sinks: Vec<Arc<dyn EventSink>>,
+    cancel: CancelToken,
+    classifiers: ClassifierChain,
+    batch_progress: Option<BatchProgress>,
+}
+
+impl VenEngine {
+    pub fn new(config: Config) -> Self {
+        let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+        Self {
+            config,
+            ffmpeg,
+            events: None,
+            sinks: Vec::new(),
+            cancel: CancelToken::new(),
+            classifiers: ClassifierChain::new(),
+            batch_progress: None,
+        }
+    }
+
+    /// Loads the configuration like the command line does: the given file, or
+    /// the discovered layers when `None`.
+    pub fn from_config_file(path: Option<&Path>) -> Result<Self> {
+        Config::load_with_discovery(path).map(Self::new)
+    }
+
+    /// Sends [`EncodeEvent`](crate::EncodeEvent)s to `sender` and hides the
+    /// terminal progress bar.
+    pub fn with_events(mut self, sender: EventSender) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Hands every [`EncodeEvent`](crate::EncodeEvent) to `sink` as well,
+    /// keeping the terminal progress bar (e.g. a
+    /// [`JsonReporter`](crate::JsonReporter) writing to a file).
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Cancelling `cancel` stops the running encode: a Dolby Vision/HDR10+
+    /// extraction is killed and its partial output removed, and
+    /// [`encode_with`](Self::encode_with) returns
+    /// [`Error::Cancelled`](crate::utils::Error::Cancelled) or an aborted
+    /// encoding error.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Draws each encode's progress bar above the overall bar of `batch`.
+    pub fn with_batch_progress(mut self, batch: BatchProgress) -> Self {
+        self.batch_progress = Some(batch);
+        self
+    }
+
+    /// Asks `classifier` to classify sources for profile auto-selection
+    /// (`--profile auto`). Classifiers are asked in the order they were added
+    /// and the first answer wins; the built-in heuristic answers when none does.
+    pub fn with_classifier(mut self, classifier: Arc<dyn ContentClassifier>) -> Self {
+        self.classifiers.register(classifier);
+        self
+    }
+
+    /// Leaves the built-in heuristic out of profile auto-selection, so only
+    /// the added classifiers decide; unclassified sources get the `movie`
+    /// profile.
+    pub fn without_builtin_classifier(mut self) -> Self {
+        self.classifiers.without_builtin();
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    async fn check_tools(&self) -> Result<()> {
+        self.ffmpeg
+            .check_availability()
+            .await
+            .map_err(|e| Error::ffmpeg(format!("FFmpeg tools not available: {}", e)))
+    }
+
+    /// Probes a source: video properties, HDR/Dolby Vision content analysis,
+    /// crop detection and the streams an encode would keep.
+    pub async fn analyze(&self, input_path: impl AsRef<Path>) -> Result<AnalysisReport> {
+        let input_path = input_path.as_ref();
+        self.check_tools().await?;
+
+        let metadata = self.ffmpeg.get_video_metadata(input_path).await?;
+        let content = UnifiedContentManager::new(
+            self.config.analysis.hdr.clone().unwrap_or_default(),
+            self.config.analysis.dolby_vision.clone(),
+            self.config.tools.hdr10plus_tool.clone(),
+        )
+        .analyze_content(&self.ffmpeg, input_path)
+        .await?;
+
+        let crop = if self.config.analysis.crop_detection.enabled {
+            let is_advanced = !matches!(content.recommended_approach, ContentEncodingApproach::SDR);
+            let result =
+                CropDetector::new(&self.ffmpeg, self.config.analysis.crop_detection.clone())
+                    .with_black_frames(self.config.analysis.black_frames.clone())
+                    .detect_crop_values(
+                        input_path,
+                        metadata.duration,
+                        metadata.width,
+                        metadata.height,
+                        is_advanced,
+                    )
+                    .await?;
+            Some(result)
+        } else {
+            None
+        };
+
+        let streams = StreamPreservation::new(self.ffmpeg.clone())
+            .with_video_policy(self.config.streams.video_policy)
+            .analyze_streams(input_path)
+            .await?;
+
+        Ok(AnalysisReport {
+            metadata,
+            content,
+            crop,
+            streams,
+        })
+    }
+
+    /// Encodes (or remuxes) one file. The returned path differs from
+    /// `options.output_path` when a non-explicit output was retargeted to the
+    /// profile's container.
+    pub async fn encode_with(&self, options: &EncodingOptions) -> Result<EncodeOutcome> {
+        self.encode_seeded(options, CachedAnalysis::default(), true)
+            .await
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Runs the analysis phase of an encode with `options` (HDR/Dolby Vision
+    /// analysis and crop detection) without encoding, e.g. for the next file
+    /// of a batch while the current one encodes. Hand the result to
+    /// [`encode_with_analysis`](Self::encode_with_analysis).
+    pub async fn analyze_ahead(&self, options: &EncodingOptions) -> Result<CachedAnalysis> {
+        lookahead::analyze_ahead(&self.ffmpeg_for(options), &self.config, options).await
+    }
+
+    /// Like [`encode_with`](Self::encode_with), but takes the results of
+    /// [`analyze_ahead`](Self::analyze_ahead) instead of analyzing the source
+    /// again.
+    pub async fn encode_with_analysis(
+        &self,
+        options: &EncodingOptions,
+        analysis: CachedAnalysis,
+    ) -> Result<EncodeOutcome> {
+        self.encode_seeded(options, analysis, true)
+            .await
+            .map(|(outcome, _)| outcome)
+    }
+
+    /// Encodes every rendition of the ladder `name` (see
+    /// [`ladder`](crate::processing::ladder)) from `options.input_path`. The
+    /// first rendition analyzes the source; the others reuse that analysis and
+    /// run up to the ladder's `parallel` at a time, without progress bars when
+    /// more than one. Returns the renditions in ladder order.
+    pub async fn encode_ladder(
+        &self,
+        options: &EncodingOptions,
+        name: &str,
+    ) -> Result<Vec<RenditionResult>> {
+        let ladder = ladder::find_ladder(&self.config, name)?;
+        if options.is_video_copy() {
+            return Err(Error::validation(
+                "A ladder encodes every rendition; it cannot be combined with a video copy",
+            ));
+        }
+
+        let renditions: Vec<(String, EncodingOptions)> = ladder
+            .renditions
+            .iter()
+            .map(|rendition| {
+                (
+                    rendition.name.clone(),
+                    ladder::rendition_options(options, ladder, rendition),
+                )
+            })
+            .collect();
+        for (_, rendition) in &renditions {
+            if let Some(parent) = rendition.output_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+
+        let mut renditions = renditions.into_iter();
+        let Some((first_name, first)) = renditions.next() else {
+            return Ok(Vec::new());
+        };
+        tracing::info!("Ladder '{}': rendition {}", name, first_name);
+        let (first_result, analysis) = match self
+            .encode_seeded(&first, CachedAnalysis::default(), true)
+            .await
+        {
+            Ok((outcome, analysis)) => (Ok(outcome), analysis),
+            Err(e) => (Err(e), CachedAnalysis::default()),
+        };
+        let mut results = vec![RenditionResult {
+            name: first_name,
+            output_path: first.output_path,
+            result: first_result,
+        }];
+
+        let show_progress_bar = ladder.parallel == 1;
+        let rest: Vec<RenditionResult> = stream::iter(renditions)
+            .map(|(rendition_name, rendition)| {
+                let analysis = analysis.clone();
+                async move {
+                    tracing::info!("Ladder '{}': rendition {}", name, rendition_name);
+                    let result = self
+                        .encode_seeded(&rendition, analysis, show_progress_bar)
+                        .await
+                        .map(|(outcome, _)| outcome);
+                    RenditionResult {
+                        name: rendition_name,
+                        output_path: rendition.output_path,
+                        result,
+                    }
+                }
+            })
+            .buffered(ladder.parallel)
+            .collect()
+            .await;
+        results.extend(rest);
+        Ok(results)
+    }
+
+    fn ffmpeg_for(&self, options: &EncodingOptions) -> FfmpegWrapper {
+        self.ffmpeg
+            .clone()
+            .with_video_stream(options.video_stream)
+            .with_error_resilience(options.error_resilience)
+            .with_trim(options.start, options.end)
+    }
+
+    /// [`encode_with`](Self::encode_with), starting from `analysis` of the
+    /// source; also returns the analysis as the run completed it.
+    async fn encode_seeded(
+        &self,
+        options: &EncodingOptions,
+        analysis: CachedAnalysis,
+        show_progress_bar: bool,
+    ) -> Result<(EncodeOutcome, CachedAnalysis)> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::cancelled(format!(
+                "{} not encoded",
+                options.input_path.display()
+            )));
+        }
+        options.validate()?;
+        self.check_tools().await?;
+
+        let ffmpeg = self.ffmpeg_for(options);
+        if !options.overwrite {
+            check_existing_output(&ffmpeg, &options.input_path, &options.output_path).await?;
+        }
+        let mut stream_preservation = StreamPreservation::new(ffmpeg.clone())
+            .with_video_policy(self.config.streams.video_policy)
+            .with_video_stream(options.video_stream);
+        let detection = &self.config.audio.language_detection;
+        if detection.enabled {
+            stream_preservation = stream_preservation.with_language_detector(
+                LanguageDetector::new(ffmpeg.clone(), detection.clone(), &self.config.app.temp_dir),
+            );
+        }
+        let mut profile_manager = ProfileManager::new();
+        profile_manager.load_profiles(self.config.profiles.clone())?;
+
+        let start = std::time::Instant::now();
+        let mut processor = VideoProcessor::new(
+            &ffmpeg,
+            &stream_preservation,
+            options,
+            &self.config,
+            &mut profile_manager,
+        )?
+        .with_cancel_token(self.cancel.clone())
+        .with_classifiers(self.classifiers.clone())
+        .with_cached_analysis(analysis);
+        for sink in &self.sinks {
+            processor = processor.with_event_sink(sink.clone());
+        }
+        if let Some(events) = &self.events {
+            processor = processor.with_events(events.clone());
+        } else if !show_progress_bar {
+            processor = processor.without_progress_bar();
+        } else if let Some(batch) = &self.batch_progress {
+            processor = processor.with_batch_progress(batch.clone());
+        }
+        processor.run().await?;
+
+        let outcome = EncodeOutcome {
+            output_path: processor.output_path().to_path_buf(),
+            elapsed: start.elapsed(),
+            profile: processor.profile_used().map(str::to_string),
+            frames: processor.frames_encoded(),
+            quality: processor.quality_scores().to_vec(),
+            decisions: processor.decisions().to_vec(),
+            hdr10plus_profile: processor.hdr10plus_profile(),
+            fingerprint: processor.cached_analysis().fingerprint.clone(),
+        };
+        Ok((outcome, processor.cached_analysis().clone()))
+    }
+}
+
+/// Refuses to replace an existing `output` unless VEN wrote it from `input`
+/// before (a rerun); anything else needs `--overwrite`.
+async fn check_existing_output(ffmpeg: &FfmpegWrapper, input: &Path, output: &Path) -> Result<()> {
+    if !output.exists() {
+        return Ok(());
+    }
+    if Provenance::read(ffmpeg, output)
+        .await
+        .is_some_and(|provenance| provenance.is_encode_of(input))
+    {
+        tracing::info!(
+            "Replacing the earlier encode of this source: {}",
+            output.display()
+        );
+        return Ok(());
+    }
+    Err(Error::validation(format!(
+        "{} already exists and is not an earlier encode of {}; pass --overwrite to replace it",
+        output.display(),
+        input.display()
+    )))
+}
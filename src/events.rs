@@ -0,0 +1,454 @@
+//! Typed events for the stages of an encode.
+//!
+//! [`VideoProcessor`](crate::processing::VideoProcessor) reports what happens
+//! as [`EncodeEvent`]s on an [`EventBus`]. The bus renders each event once on
+//! the console (at the tree level the event belongs to) and hands it to every
+//! subscribed [`EventSink`]: the per-file log, a channel for library callers,
+//! the [`JsonReporter`] (`--events-json`) and the `notifications:` commands
+//! ([`CommandHook`]).
+
+use crate::analysis::dolby_vision::DolbyVisionProfile;
+use crate::config::types::NotificationsConfig;
+use crate::progress::format_duration;
+use crate::utils::filesystem::format_file_size;
+use crate::utils::logging::ProcessingLevel;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Sending half of the channel that receives [`EncodeEvent`]s.
+pub type EventSender = UnboundedSender<EncodeEvent>;
+
+/// Progress of a running FFmpeg process.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EncodeProgress {
+    /// 0-100, never moving backwards
+    pub percent: f64,
+    pub frame: Option<u32>,
+    /// Encoding speed in frames per second
+    pub fps: Option<f32>,
+    /// Encoding speed relative to real time
+    pub speed: Option<f32>,
+    #[serde(serialize_with = "optional_seconds")]
+    pub eta: Option<Duration>,
+    /// Bytes written so far
+    pub size: Option<u64>,
+    /// Final size extrapolated from `size` and `percent`, once 1% is done
+    pub projected_size: Option<u64>,
+    /// Space the projected output saves over the source, in percent;
+    /// negative when it ends up larger
+    pub savings_percent: Option<f64>,
+}
+
+/// What happens during an encode, in order.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EncodeEvent {
+    Started {
+        input: PathBuf,
+        output: PathBuf,
+    },
+    /// The source is being probed
+    AnalysisStarted {
+        input: PathBuf,
+    },
+    /// Crop detection finished (or a fixed crop was given); `crop` is `None`
+    /// when the full frame is kept
+    CropDetected {
+        crop: Option<String>,
+        method: String,
+    },
+    /// The profile was chosen, with the CRF/bitrate after content adjustments
+    ProfileSelected {
+        profile: String,
+        crf: f32,
+        bitrate: u32,
+    },
+    /// Reported about once a second while FFmpeg runs (pass 2 of two-pass modes)
+    Progress(EncodeProgress),
+    /// Progress at every further 10% of the encode, for the per-file log
+    ProgressSnapshot(EncodeProgress),
+    /// The encode was suspended by the schedule window or the thermal throttle
+    Paused {
+        reason: String,
+    },
+    Resumed,
+    /// Progress of a tool run after the encode (RPU injection, mkvmerge
+    /// mux), reported whenever another whole percent is done
+    ToolProgress {
+        tool: String,
+        percent: f64,
+    },
+    /// The Dolby Vision RPU was injected into the encoded stream
+    MetadataInjected {
+        profile: DolbyVisionProfile,
+    },
+    /// The output (possibly retargeted to the profile's container) is complete
+    Finished {
+        output: PathBuf,
+        #[serde(serialize_with = "seconds")]
+        elapsed: Duration,
+    },
+    /// The encode stopped with an error, or was cancelled
+    Failed {
+        output: PathBuf,
+        error: String,
+        cancelled: bool,
+    },
+}
+
+impl EncodeEvent {
+    /// Where the event sits in the console tree; `None` for events the
+    /// progress bar (or, for failures, the caller's error report) already shows.
+    pub fn level(&self) -> Option<ProcessingLevel> {
+        match self {
+            EncodeEvent::Started { .. }
+            | EncodeEvent::Progress(_)
+            | EncodeEvent::ProgressSnapshot(_)
+            | EncodeEvent::ToolProgress { .. }
+            | EncodeEvent::Failed { .. } => None,
+            EncodeEvent::AnalysisStarted { .. }
+            | EncodeEvent::CropDetected { .. }
+            | EncodeEvent::ProfileSelected { .. } => Some(ProcessingLevel::Step),
+            EncodeEvent::Paused { .. } | EncodeEvent::Resumed => Some(ProcessingLevel::Detail),
+            EncodeEvent::MetadataInjected { .. } => Some(ProcessingLevel::Stage),
+            EncodeEvent::Finished { .. } => Some(ProcessingLevel::Root),
+        }
+    }
+}
+
+impl fmt::Display for EncodeEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeEvent::Started { input, output } => {
+                write!(f, "Started: {} -> {}", input.display(), output.display())
+            }
+            EncodeEvent::AnalysisStarted { .. } => write!(f, "Analyzing video metadata"),
+            EncodeEvent::CropDetected { crop, method } => match crop {
+                Some(crop) => write!(f, "Crop: {} ({})", crop, method),
+                None => write!(f, "Crop: none ({})", method),
+            },
+            EncodeEvent::ProfileSelected {
+                profile,
+                crf,
+                bitrate,
+            } => write!(f, "Profile '{}': CRF {:.1}, {} kbps", profile, crf, bitrate),
+            EncodeEvent::Progress(progress) => write!(f, "Progress: {:.1}%", progress.percent),
+            EncodeEvent::ProgressSnapshot(progress) => {
+                write!(f, "Progress: {:.0}%", progress.percent)?;
+                if let Some(size) = progress.size {
+                    write!(f, ", {} written", format_file_size(size))?;
+                }
+                if let Some(projected) = progress.projected_size {
+                    write!(f, ", projected ~{}", format_file_size(projected))?;
+                }
+                match progress.savings_percent {
+                    Some(savings) if savings >= 0.0 => write!(f, " (saves {:.0}%)", savings)?,
+                    Some(savings) => write!(f, " ({:.0}% larger than the source)", -savings)?,
+                    None => {}
+                }
+                if let Some(fps) = progress.fps {
+                    write!(f, ", {:.1}fps", fps)?;
+                }
+                if let Some(eta) = progress.eta {
+                    write!(f, ", ETA {}", format_duration(eta))?;
+                }
+                Ok(())
+            }
+            EncodeEvent::Paused { reason } => write!(f, "Pausing encode: {}", reason),
+            EncodeEvent::Resumed => write!(f, "Resuming encode"),
+            EncodeEvent::ToolProgress { tool, percent } => write!(f, "{}: {:.0}%", tool, percent),
+            EncodeEvent::MetadataInjected { profile } => write!(
+                f,
+                "Metadata injection completed: Dolby Vision profile {}",
+                profile.as_str()
+            ),
+            EncodeEvent::Finished { output, elapsed } => write!(
+                f,
+                "Finished {} in {:.2}s",
+                output.display(),
+                elapsed.as_secs_f64()
+            ),
+            EncodeEvent::Failed {
+                output,
+                error,
+                cancelled,
+            } => {
+                let outcome = if *cancelled { "Cancelled" } else { "Failed" };
+                write!(f, "{} {}: {}", outcome, output.display(), error)
+            }
+        }
+    }
+}
+
+/// Durations in JSON: seconds as a number.
+fn seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+fn optional_seconds<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => seconds(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Consumer of [`EncodeEvent`]s. Sinks run inline, so they must not block.
+pub trait EventSink: Send + Sync {
+    fn handle(&self, event: &EncodeEvent);
+}
+
+impl EventSink for EventSender {
+    fn handle(&self, event: &EncodeEvent) {
+        // A receiver that went away just stops listening
+        let _ = self.send(event.clone());
+    }
+}
+
+/// Writes every event as a JSON object on its own line (JSON Lines), tagged
+/// with its kind in `event`, for programs following the encodes.
+pub struct JsonReporter {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonReporter {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    /// Appends the events to `path`, which is created if needed.
+    pub fn append_to(path: &Path) -> std::io::Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        Ok(Self::new(file))
+    }
+}
+
+impl EventSink for JsonReporter {
+    fn handle(&self, event: &EncodeEvent) {
+        let Ok(line) = serde_json::to_string(event) else {
+            return;
+        };
+        if let Ok(mut writer) = self.writer.lock() {
+            if let Err(e) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+                tracing::debug!("Failed to write event: {}", e);
+            }
+        }
+    }
+}
+
+/// Runs the `notifications:` command for an encode that finished or failed,
+/// through `sh -c` with `VEN_EVENT` (`finished`, `failed` or `cancelled`),
+/// `VEN_OUTPUT` and, for failures, `VEN_ERROR` set. The command runs in the
+/// background; a failing one is only logged.
+pub struct CommandHook {
+    config: NotificationsConfig,
+}
+
+impl CommandHook {
+    pub fn new(config: NotificationsConfig) -> Self {
+        Self { config }
+    }
+
+    /// The command for `event` and its environment, if one is configured.
+    fn command_for(&self, event: &EncodeEvent) -> Option<(&str, Vec<(&'static str, String)>)> {
+        match event {
+            EncodeEvent::Finished { output, .. } => Some((
+                self.config.on_finished.as_deref()?,
+                vec![
+                    ("VEN_EVENT", "finished".to_string()),
+                    ("VEN_OUTPUT", output.display().to_string()),
+                ],
+            )),
+            EncodeEvent::Failed {
+                output,
+                error,
+                cancelled,
+            } => Some((
+                self.config.on_failed.as_deref()?,
+                vec![
+                    (
+                        "VEN_EVENT",
+                        if *cancelled { "cancelled" } else { "failed" }.to_string(),
+                    ),
+                    ("VEN_OUTPUT", output.display().to_string()),
+                    ("VEN_ERROR", error.clone()),
+                ],
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl EventSink for CommandHook {
+    fn handle(&self, event: &EncodeEvent) {
+        let Some((command, env)) = self.command_for(event) else {
+            return;
+        };
+        let child = tokio::process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(env)
+            .stdin(std::process::Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                tracing::warn!("Failed to run notification command '{}': {}", command, e);
+                return;
+            }
+        };
+        let command = command.to_string();
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) if !status.success() => {
+                    tracing::warn!("Notification command '{}' exited with {}", command, status)
+                }
+                Err(e) => tracing::warn!("Notification command '{}' failed: {}", command, e),
+                Ok(_) => {}
+            }
+        });
+    }
+}
+
+/// Fans events out to the console and every subscribed sink.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    sinks: Vec<Arc<dyn EventSink>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn subscribe(&mut self, sink: Arc<dyn EventSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn emit(&self, event: EncodeEvent) {
+        if let Some(level) = event.level() {
+            tracing::info!(tree = level.as_str(), "{}", event);
+        }
+        for sink in &self.sinks {
+            sink.handle(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_bus_fans_out() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut bus = EventBus::new();
+        bus.subscribe(Arc::new(sender.clone()));
+        bus.subscribe(Arc::new(sender));
+
+        let event = EncodeEvent::CropDetected {
+            crop: Some("1920:800:0:140".to_string()),
+            method: "frequency_analysis".to_string(),
+        };
+        bus.emit(event.clone());
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        assert_eq!(receiver.try_recv().unwrap(), event);
+        assert!(receiver.try_recv().is_err());
+
+        assert_eq!(
+            event.to_string(),
+            "Crop: 1920:800:0:140 (frequency_analysis)"
+        );
+        assert_eq!(event.level(), Some(ProcessingLevel::Step));
+        assert_eq!(
+            EncodeEvent::Progress(EncodeProgress {
+                percent: 50.0,
+                frame: None,
+                fps: None,
+                speed: None,
+                eta: None,
+                size: None,
+                projected_size: None,
+                savings_percent: None,
+            })
+            .level(),
+            None
+        );
+
+        let snapshot = EncodeEvent::ProgressSnapshot(EncodeProgress {
+            percent: 30.0,
+            frame: Some(7200),
+            fps: Some(24.5),
+            speed: None,
+            eta: Some(Duration::from_secs(600)),
+            size: Some(1_073_741_824),
+            projected_size: Some(3_579_139_413),
+            savings_percent: Some(-25.0),
+        });
+        assert_eq!(
+            snapshot.to_string(),
+            "Progress: 30%, 1.00 GB written, projected ~3.33 GB (25% larger than the source), 24.5fps, ETA 10:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_json_reporter_and_command_hook() {
+        let dir = tempfile::tempdir().unwrap();
+        let events_path = dir.path().join("events.jsonl");
+        let marker = dir.path().join("notified");
+        let mut bus = EventBus::new();
+        bus.subscribe(Arc::new(JsonReporter::append_to(&events_path).unwrap()));
+        bus.subscribe(Arc::new(CommandHook::new(NotificationsConfig {
+            on_finished: None,
+            on_failed: Some(format!(
+                "echo \"$VEN_EVENT $VEN_ERROR\" > '{}'",
+                marker.display()
+            )),
+        })));
+
+        bus.emit(EncodeEvent::Finished {
+            output: PathBuf::from("out.mkv"),
+            elapsed: Duration::from_millis(1500),
+        });
+        bus.emit(EncodeEvent::Failed {
+            output: PathBuf::from("out.mkv"),
+            error: "disk full".to_string(),
+            cancelled: false,
+        });
+
+        let lines: Vec<serde_json::Value> = std::fs::read_to_string(&events_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines[0],
+            serde_json::json!({"event": "finished", "output": "out.mkv", "elapsed": 1.5})
+        );
+        assert_eq!(lines[1]["event"], "failed");
+        assert_eq!(lines[1]["error"], "disk full");
+
+        // Only the failure has a command; it runs in the background
+        let mut notified = String::new();
+        for _ in 0..100 {
+            notified = std::fs::read_to_string(&marker).unwrap_or_default();
+            if !notified.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(notified.trim(), "failed disk full");
+    }
+}
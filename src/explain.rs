@@ -0,0 +1,81 @@
+//! `--explain`: the automated decisions of an encode, each with the reason
+//! and the config key or flag behind it.
+//!
+//! [`VideoProcessor`](crate::processing::VideoProcessor) records a
+//! [`Decision`] wherever it picks something on its own (profile, crop, rate
+//! adjustments, streams, metadata handling). They end up in the
+//! [`EncodeOutcome`](crate::EncodeOutcome) and the `decisions` section of the
+//! encode report, shown by `logs show`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What a [`Decision`] is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecisionTopic {
+    Profile,
+    Crop,
+    Rate,
+    Filter,
+    Metadata,
+    Stream,
+}
+
+impl DecisionTopic {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Profile => "profile",
+            Self::Crop => "crop",
+            Self::Rate => "rate",
+            Self::Filter => "filter",
+            Self::Metadata => "metadata",
+            Self::Stream => "stream",
+        }
+    }
+}
+
+/// One automated decision.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Decision {
+    pub topic: DecisionTopic,
+    /// What was decided, e.g. `CRF 23.0` or `drop audio #2`
+    pub choice: String,
+    /// Why
+    pub reason: String,
+    /// Config key or flag that triggered it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+}
+
+impl Decision {
+    pub fn new(topic: DecisionTopic, choice: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self {
+            topic,
+            choice: choice.into(),
+            reason: reason.into(),
+            rule: None,
+        }
+    }
+
+    pub fn with_rule(mut self, rule: impl Into<String>) -> Self {
+        self.rule = Some(rule.into());
+        self
+    }
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {}: {}",
+            self.topic.as_str(),
+            self.choice,
+            self.reason
+        )?;
+        if let Some(rule) = &self.rule {
+            write!(f, " ({})", rule)?;
+        }
+        Ok(())
+    }
+}
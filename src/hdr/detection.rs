@@ -55,7 +55,7 @@ impl HdrDetector {
     ) -> Result<EnhancedVideoMetadata> {
         let output = ffmpeg.run_ffprobe(&[
             "-v", "quiet",
-            "-select_streams", "v:0",
+            "-select_streams", &ffmpeg.video_stream_specifier(),
             "-show_entries",
             "stream=color_space,color_transfer,color_primaries,bits_per_raw_sample,chroma_location:stream_side_data",
             "-show_frames",
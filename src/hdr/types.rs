@@ -56,7 +56,7 @@ pub struct HdrMetadata {
     pub raw_primaries: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HdrAnalysisResult {
     pub metadata: HdrMetadata,
     pub confidence_score: f32, // Detection confidence (0.0-1.0)
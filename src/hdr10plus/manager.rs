@@ -1,17 +1,23 @@
-use super::metadata::{Hdr10PlusMetadata, Hdr10PlusProcessingResult};
+use super::metadata::{Hdr10PlusMetadata, Hdr10PlusProcessingResult, Hdr10PlusSummary};
 use super::tools::{Hdr10PlusTool, Hdr10PlusToolConfig};
 use crate::analysis::dolby_vision::DolbyVisionInfo;
 use crate::hdr::types::{HdrAnalysisResult, HdrFormat};
-use crate::utils::{Error, Result};
+use crate::log_step;
+use crate::utils::{CancelToken, Error, FfmpegWrapper, Result, ToolProgress};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+/// Metadata files up to this size are loaded whole; larger ones only summarized
+pub const DEFAULT_SUMMARY_THRESHOLD_MB: u64 = 64;
+
 /// High-level manager for HDR10+ dynamic metadata processing
 pub struct Hdr10PlusManager {
     tool: Option<Hdr10PlusTool>,
     temp_dir: PathBuf,
     _tool_config: Hdr10PlusToolConfig,
+    fallback_to_hdr10: bool,
+    summary_threshold_bytes: u64,
 }
 
 impl Hdr10PlusManager {
@@ -24,9 +30,51 @@ impl Hdr10PlusManager {
             tool,
             temp_dir,
             _tool_config: tool_cfg,
+            fallback_to_hdr10: true,
+            summary_threshold_bytes: DEFAULT_SUMMARY_THRESHOLD_MB * 1024 * 1024,
         }
     }
 
+    /// Extracted metadata files larger than `megabytes` are read frame by
+    /// frame into a [`Hdr10PlusSummary`] instead of being loaded whole
+    /// (0: always).
+    pub fn with_summary_threshold(mut self, megabytes: u64) -> Self {
+        self.summary_threshold_bytes = megabytes * 1024 * 1024;
+        self
+    }
+
+    /// Extracts through an FFmpeg pipe instead of handing the source to
+    /// hdr10plus_tool directly (see [`Hdr10PlusTool::with_ffmpeg_pipe`]).
+    pub fn with_ffmpeg_pipe(mut self, ffmpeg: &FfmpegWrapper) -> Self {
+        self.tool = self.tool.map(|tool| tool.with_ffmpeg_pipe(ffmpeg.clone()));
+        self
+    }
+
+    /// Whether a failed extraction degrades to plain HDR10 (the default) or
+    /// is returned as an error.
+    pub fn with_hdr10_fallback(mut self, fallback_to_hdr10: bool) -> Self {
+        self.fallback_to_hdr10 = fallback_to_hdr10;
+        self
+    }
+
+    /// Directory the extracted metadata is written to.
+    pub fn with_temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = temp_dir.into();
+        self
+    }
+
+    /// Lets `cancel` stop a running metadata extraction.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.tool = self.tool.map(|tool| tool.with_cancel_token(cancel));
+        self
+    }
+
+    /// Reports the extraction's progress through `progress`.
+    pub fn with_progress(mut self, progress: ToolProgress) -> Self {
+        self.tool = self.tool.map(|tool| tool.with_progress(progress));
+        self
+    }
+
     /// Check if HDR10+ processing capability is available
     pub async fn check_hdr10plus_capability(&self) -> Result<bool> {
         if let Some(ref tool) = self.tool {
@@ -67,66 +115,33 @@ impl Hdr10PlusManager {
         }
 
         let input_path = input_video.as_ref();
-        info!(
+        log_step!(
             "Extracting HDR10+ dynamic metadata from: {}",
             input_path.display()
         );
 
-        // Generate HDR10+ metadata file alongside the source video
-        let input_stem = input_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("video");
-        let metadata_id = Uuid::new_v4().to_string();
-        let metadata_filename = format!("{}_hdr10plus_metadata_{}.json", input_stem, metadata_id);
-
-        let metadata_file = if let Some(parent) = input_path.parent() {
-            parent.join(metadata_filename)
-        } else {
-            PathBuf::from(metadata_filename)
-        };
+        tokio::fs::create_dir_all(&self.temp_dir).await?;
+        let metadata_file = self
+            .temp_dir
+            .join(format!("hdr10plus_metadata_{}.json", Uuid::new_v4()));
 
         match tool.extract_metadata(input_path, &metadata_file).await {
-            Ok(_) => {
-                // Load and parse the extracted metadata
-                match Hdr10PlusMetadata::from_json_file(&metadata_file).await {
-                    Ok(metadata) => {
-                        // Validate the metadata
-                        if let Err(e) = metadata.validate() {
-                            warn!("HDR10+ metadata validation failed: {}", e);
-                            return Ok(None);
-                        }
-
-                        let file_size = tokio::fs::metadata(&metadata_file)
-                            .await
-                            .map(|m| m.len())
-                            .ok();
-
-                        let result = Hdr10PlusProcessingResult {
-                            metadata_file,
-                            metadata,
-                            extraction_successful: true,
-                            file_size,
-                            curve_count: 0, // Will be calculated in constructor
-                            scene_count: 0, // Will be calculated in constructor
-                        };
-
-                        info!(
-                            "Successfully extracted HDR10+ metadata: {} frames, {} scenes",
-                            result.metadata.get_frame_count(),
-                            result.metadata.get_scene_count()
-                        );
-
-                        Ok(Some(result))
-                    }
-                    Err(e) => {
-                        warn!("Failed to parse extracted HDR10+ metadata: {}", e);
-                        // Clean up the file
-                        let _ = tokio::fs::remove_file(&metadata_file).await;
-                        Ok(None)
-                    }
+            Ok(_) => match self.read_metadata(&metadata_file).await {
+                Ok(result) => {
+                    info!(
+                        "Successfully extracted HDR10+ metadata: {} frames, {} scenes",
+                        result.summary.frame_count, result.summary.scene_count
+                    );
+                    Ok(Some(result))
                 }
-            }
+                Err(e) => {
+                    warn!("Failed to read extracted HDR10+ metadata: {}", e);
+                    // Clean up the file
+                    let _ = tokio::fs::remove_file(&metadata_file).await;
+                    Ok(None)
+                }
+            },
+            Err(e @ Error::Cancelled(_)) => Err(e),
             Err(e) => {
                 // Check if this is the expected "no dynamic metadata" case
                 let error_message = e.to_string();
@@ -136,6 +151,8 @@ impl Hdr10PlusManager {
                 {
                     debug!("No HDR10+ dynamic metadata found in file - this is normal for HDR10 content");
                     info!("No HDR10+ dynamic metadata detected (standard HDR10 content)");
+                } else if !self.fallback_to_hdr10 {
+                    return Err(e);
                 } else {
                     warn!("HDR10+ metadata extraction failed: {}", e);
                 }
@@ -144,6 +161,26 @@ impl Hdr10PlusManager {
         }
     }
 
+    /// Loads and validates extracted metadata; files over the summary
+    /// threshold are only summarized, since x265 reads the file itself.
+    async fn read_metadata(&self, metadata_file: &Path) -> Result<Hdr10PlusProcessingResult> {
+        let file_size = tokio::fs::metadata(metadata_file).await?.len();
+        let mut result = if file_size > self.summary_threshold_bytes {
+            debug!(
+                "HDR10+ metadata is {} MB, reading a summary only",
+                file_size / (1024 * 1024)
+            );
+            let summary = Hdr10PlusSummary::from_json_file(metadata_file).await?;
+            Hdr10PlusProcessingResult::from_summary(metadata_file.to_path_buf(), summary, true)
+        } else {
+            let metadata = Hdr10PlusMetadata::from_json_file(metadata_file).await?;
+            metadata.validate()?;
+            Hdr10PlusProcessingResult::new(metadata_file.to_path_buf(), metadata, true)
+        };
+        result.file_size = Some(file_size);
+        Ok(result)
+    }
+
     /// Process dual Dolby Vision + HDR10+ content
     pub async fn process_dual_format<P: AsRef<Path>>(
         &self,
@@ -175,7 +212,7 @@ impl Hdr10PlusManager {
                 info!(
                     "Dual format processing complete: DV Profile {} + HDR10+ ({} frames)",
                     dv_info.profile.as_str(),
-                    result.metadata.get_frame_count()
+                    result.summary.frame_count
                 );
 
                 Ok(Some(result))
@@ -199,14 +236,12 @@ impl Hdr10PlusManager {
             ));
         }
 
-        let params = vec![
+        let mut params = vec![
             // Core HDR10+ parameter - path to the JSON metadata file
             (
                 "dhdr10-info".to_string(),
                 hdr10plus_result.metadata_file.to_string_lossy().to_string(),
             ),
-            // HDR10+ specific optimizations
-            ("hdr10plus-opt".to_string(), "1".to_string()),
             // Enhanced rate control for dynamic metadata
             ("rc-lookahead".to_string(), "60".to_string()), // Longer lookahead
             ("bframes".to_string(), "8".to_string()),       // More B-frames for better compression
@@ -228,6 +263,13 @@ impl Hdr10PlusManager {
             ("weightb".to_string(), "".to_string()),
             ("weightp".to_string(), "2".to_string()),
         ];
+        if hdr10plus_result
+            .summary
+            .detected_profile()
+            .needs_dhdr10_opt()
+        {
+            params.push(("dhdr10-opt".to_string(), "".to_string()));
+        }
 
         info!("Generated {} HDR10+ x265 parameters", params.len());
         Ok(params)
@@ -1,7 +1,11 @@
 use crate::utils::{Error, Result};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Frames of the metadata kept in a [`Hdr10PlusSummary`] for logging
+const SUMMARY_FRAMES: usize = 3;
+
 /// HDR10+ dynamic metadata structure based on hdr10plus_tool JSON output
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
@@ -106,6 +110,216 @@ pub struct LuminanceDistributions {
     pub distribution_values: Vec<u32>,
 }
 
+/// Counts and brightness figures of HDR10+ metadata, without the per-frame
+/// data: what logging needs, while x265 reads the JSON file itself.
+#[derive(Debug, Clone, Default)]
+pub struct Hdr10PlusSummary {
+    pub profile: String,
+    pub version: String,
+    pub tool_info: Option<ToolInfo>,
+    pub frame_count: u32,
+    pub scene_count: u32,
+    /// Frames with a tone mapping curve
+    pub tone_mapping_frames: u32,
+    /// Highest MaxSCL component over all frames
+    pub peak_brightness: Option<u32>,
+    /// Mean of the frames' average RGB
+    pub average_brightness: Option<f64>,
+    /// The first few frames
+    pub first_frames: Vec<SceneMetadata>,
+}
+
+/// HDR10+ application profile: A carries the luminance statistics only, B
+/// adds a Bezier tone mapping curve for the target display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Hdr10PlusProfile {
+    A,
+    B,
+}
+
+impl Hdr10PlusProfile {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::A => "A",
+            Self::B => "B",
+        }
+    }
+
+    /// Whether x265 gets `--dhdr10-opt`. Profile B metadata repeats each
+    /// scene's curve on every frame; with the option x265 only writes the SEI
+    /// on IDR frames and where the metadata changes. Profile A is written for
+    /// every frame, as some players expect.
+    pub fn needs_dhdr10_opt(self) -> bool {
+        self == Self::B
+    }
+}
+
+impl std::fmt::Display for Hdr10PlusProfile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "profile {}", self.as_str())
+    }
+}
+
+impl Hdr10PlusSummary {
+    /// The profile the JSON declares (`HDR10plusProfile`), if it names A or B.
+    pub fn declared_profile(&self) -> Option<Hdr10PlusProfile> {
+        match self.profile.trim().to_ascii_uppercase().as_str() {
+            "A" => Some(Hdr10PlusProfile::A),
+            "B" => Some(Hdr10PlusProfile::B),
+            _ => None,
+        }
+    }
+
+    /// The profile the frames actually carry: B as soon as one frame has a
+    /// tone mapping curve. Extractions do not always declare it correctly.
+    pub fn detected_profile(&self) -> Hdr10PlusProfile {
+        if self.tone_mapping_frames > 0 {
+            Hdr10PlusProfile::B
+        } else {
+            Hdr10PlusProfile::A
+        }
+    }
+
+    /// Reads the summary of a hdr10plus_tool JSON file frame by frame, so
+    /// memory use does not grow with the length of the content. The frames
+    /// are checked like [`Hdr10PlusMetadata::validate`] does.
+    pub async fn from_json_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let document = tokio::task::spawn_blocking(move || -> Result<SummaryDocument> {
+            let reader = std::io::BufReader::new(std::fs::File::open(&path)?);
+            serde_json::from_reader(reader).map_err(|e| Error::Parse {
+                message: format!("Failed to parse HDR10+ metadata JSON: {}", e),
+            })
+        })
+        .await
+        .map_err(|e| Error::parse(format!("HDR10+ metadata reader failed: {}", e)))??;
+
+        if document.scene_info.frames == 0 {
+            return Err(Error::Validation {
+                message: "HDR10+ metadata contains no scene data".to_string(),
+            });
+        }
+        Ok(document
+            .scene_info
+            .into_summary(document.json_info, document.tool_info))
+    }
+}
+
+/// A hdr10plus_tool JSON file with the frames folded into [`FrameStats`].
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SummaryDocument {
+    #[serde(rename = "JSONInfo")]
+    json_info: JsonInfo,
+    scene_info: FrameStats,
+    tool_info: Option<ToolInfo>,
+}
+
+#[derive(Default)]
+struct FrameStats {
+    frames: u32,
+    max_scene_id: Option<u32>,
+    tone_mapping_frames: u32,
+    peak_brightness: Option<u32>,
+    average_rgb_sum: u64,
+    first_frames: Vec<SceneMetadata>,
+}
+
+impl FrameStats {
+    fn record(&mut self, scene: &SceneMetadata) {
+        self.frames += 1;
+        self.max_scene_id = self.max_scene_id.max(Some(scene.scene_id));
+        if !scene.bezier_curve_data.anchors.is_empty() {
+            self.tone_mapping_frames += 1;
+        }
+        let max_scl = scene.luminance_parameters.max_scl.iter().max().copied();
+        self.peak_brightness = self.peak_brightness.max(max_scl);
+        self.average_rgb_sum += scene.luminance_parameters.average_rgb as u64;
+        if self.first_frames.len() < SUMMARY_FRAMES {
+            self.first_frames.push(scene.clone());
+        }
+    }
+
+    fn into_summary(self, json_info: JsonInfo, tool_info: Option<ToolInfo>) -> Hdr10PlusSummary {
+        Hdr10PlusSummary {
+            profile: json_info.hdr10plus_profile,
+            version: json_info.version,
+            tool_info,
+            frame_count: self.frames,
+            scene_count: self.max_scene_id.map_or(0, |id| id + 1),
+            tone_mapping_frames: self.tone_mapping_frames,
+            peak_brightness: self.peak_brightness,
+            average_brightness: (self.frames > 0)
+                .then(|| self.average_rgb_sum as f64 / self.frames as f64),
+            first_frames: self.first_frames,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FrameStats {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct FrameVisitor;
+
+        impl<'de> Visitor<'de> for FrameVisitor {
+            type Value = FrameStats;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a list of HDR10+ frames")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<FrameStats, A::Error> {
+                let mut stats = FrameStats::default();
+                while let Some(scene) = seq.next_element::<SceneMetadata>()? {
+                    validate_frame(stats.frames as usize, &scene).map_err(de::Error::custom)?;
+                    stats.record(&scene);
+                }
+                Ok(stats)
+            }
+        }
+
+        deserializer.deserialize_seq(FrameVisitor)
+    }
+}
+
+/// Checks frame `index` of the metadata: sequential frame index, an RGB
+/// MaxSCL and a complete bezier curve, if any.
+fn validate_frame(index: usize, scene: &SceneMetadata) -> Result<()> {
+    if scene.sequence_frame_index != index as u32 {
+        return Err(Error::Validation {
+            message: format!(
+                "Scene {} has sequence_frame_index {}, expected {}",
+                index, scene.sequence_frame_index, index
+            ),
+        });
+    }
+
+    // Validate MaxSCL has 3 components (R, G, B)
+    if scene.luminance_parameters.max_scl.len() != 3 {
+        return Err(Error::Validation {
+            message: format!(
+                "Frame {} MaxSCL should have 3 components, found {}",
+                index,
+                scene.luminance_parameters.max_scl.len()
+            ),
+        });
+    }
+
+    // Validate bezier curve has exactly 9 anchors
+    let anchor_count = scene.bezier_curve_data.anchors.len();
+    if anchor_count != 0 && anchor_count != 9 {
+        return Err(Error::Validation {
+            message: format!(
+                "Frame {} bezier curve should have 0 or 9 anchors, found {}",
+                index, anchor_count
+            ),
+        });
+    }
+
+    Ok(())
+}
 
 /// HDR10+ metadata processing result
 #[derive(Debug, Clone)]
@@ -113,8 +327,12 @@ pub struct Hdr10PlusProcessingResult {
     /// Path to extracted metadata JSON file
     pub metadata_file: PathBuf,
 
-    /// Parsed metadata structure
-    pub metadata: Hdr10PlusMetadata,
+    /// Parsed metadata structure; `None` when the file was too large to
+    /// load and only its summary was read
+    pub metadata: Option<Hdr10PlusMetadata>,
+
+    /// Counts and brightness of the metadata
+    pub summary: Hdr10PlusSummary,
 
     /// Processing success flag
     pub extraction_successful: bool,
@@ -218,41 +436,44 @@ impl Hdr10PlusMetadata {
             });
         }
 
-        // Validate that sequence frame indices are sequential
         for (i, scene) in self.scene_info.iter().enumerate() {
-            if scene.sequence_frame_index != i as u32 {
-                return Err(Error::Validation {
-                    message: format!(
-                        "Scene {} has sequence_frame_index {}, expected {}",
-                        i, scene.sequence_frame_index, i
-                    ),
-                });
-            }
+            validate_frame(i, scene)?;
+        }
 
-            // Validate MaxSCL has 3 components (R, G, B)
-            if scene.luminance_parameters.max_scl.len() != 3 {
-                return Err(Error::Validation {
-                    message: format!(
-                        "Frame {} MaxSCL should have 3 components, found {}",
-                        i,
-                        scene.luminance_parameters.max_scl.len()
-                    ),
-                });
-            }
+        Ok(())
+    }
 
-            // Validate bezier curve has exactly 9 anchors
-            let anchor_count = scene.bezier_curve_data.anchors.len();
-            if anchor_count != 0 && anchor_count != 9 {
-                return Err(Error::Validation {
-                    message: format!(
-                        "Frame {} bezier curve should have 0 or 9 anchors, found {}",
-                        i, anchor_count
-                    ),
-                });
+    /// Keeps the metadata of `frames` (sequence frame indices) for an encode
+    /// of only that range: frame indices start over at 0, the scene the range
+    /// starts in begins at its first kept frame and scene ids are renumbered
+    /// from 0.
+    pub fn trim(&mut self, frames: std::ops::Range<u32>) {
+        self.scene_info
+            .retain(|scene| frames.contains(&scene.sequence_frame_index));
+        let mut previous_id = None;
+        let mut scene_id = 0;
+        let mut scene_start = 0;
+        for scene in &mut self.scene_info {
+            scene.sequence_frame_index -= frames.start;
+            if previous_id != Some(scene.scene_id) {
+                if previous_id.is_some() {
+                    scene_id += 1;
+                }
+                previous_id = Some(scene.scene_id);
+                scene_start = scene.sequence_frame_index;
             }
+            scene.scene_id = scene_id;
+            scene.scene_frame_index = scene.sequence_frame_index - scene_start;
         }
+    }
 
-        Ok(())
+    /// Counts and brightness of the metadata, see [`Hdr10PlusSummary`].
+    pub fn summary(&self) -> Hdr10PlusSummary {
+        let mut stats = FrameStats::default();
+        for scene in &self.scene_info {
+            stats.record(scene);
+        }
+        stats.into_summary(self.json_info.clone(), self.tool_info.clone())
     }
 }
 
@@ -276,16 +497,26 @@ impl Hdr10PlusProcessingResult {
         metadata: Hdr10PlusMetadata,
         extraction_successful: bool,
     ) -> Self {
-        let curve_count = metadata.get_tone_mapping_frame_count();
-        let scene_count = metadata.get_scene_count();
+        let summary = metadata.summary();
+        let mut result = Self::from_summary(metadata_file, summary, extraction_successful);
+        result.metadata = Some(metadata);
+        result
+    }
 
+    /// A result for metadata of which only the summary was read.
+    pub fn from_summary(
+        metadata_file: PathBuf,
+        summary: Hdr10PlusSummary,
+        extraction_successful: bool,
+    ) -> Self {
         Self {
             metadata_file,
-            metadata,
+            metadata: None,
+            curve_count: summary.tone_mapping_frames,
+            scene_count: summary.scene_count,
+            summary,
             extraction_successful,
             file_size: None,
-            curve_count,
-            scene_count,
         }
     }
 
@@ -313,3 +544,87 @@ impl Hdr10PlusProcessingResult {
         base_overhead + curve_overhead + scene_overhead
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsignal::hdr10plus_metadata;
+
+    #[tokio::test]
+    async fn test_summary_matches_full_parse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hdr10plus.json");
+        let mut json = hdr10plus_metadata(240);
+        json["SceneInfo"][100]["SceneId"] = 1.into();
+        std::fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+
+        let metadata = Hdr10PlusMetadata::from_json_file(&path).await.unwrap();
+        let full = metadata.summary();
+        let streamed = Hdr10PlusSummary::from_json_file(&path).await.unwrap();
+        for summary in [&full, &streamed] {
+            assert_eq!(summary.frame_count, 240);
+            assert_eq!(summary.scene_count, 2);
+            assert_eq!(summary.tone_mapping_frames, 240);
+            assert_eq!(summary.profile, "B");
+            assert_eq!(summary.first_frames.len(), SUMMARY_FRAMES);
+        }
+        assert_eq!(streamed.peak_brightness, metadata.get_peak_brightness());
+        assert_eq!(
+            streamed.average_brightness,
+            metadata.get_average_brightness()
+        );
+
+        assert_eq!(streamed.declared_profile(), Some(Hdr10PlusProfile::B));
+        assert_eq!(streamed.detected_profile(), Hdr10PlusProfile::B);
+        assert!(streamed.detected_profile().needs_dhdr10_opt());
+        // Curves make profile B whatever the JSON declares
+        let mismatched = Hdr10PlusSummary {
+            profile: "a".to_string(),
+            ..streamed.clone()
+        };
+        assert_eq!(mismatched.declared_profile(), Some(Hdr10PlusProfile::A));
+        assert_eq!(mismatched.detected_profile(), Hdr10PlusProfile::B);
+        let luminance_only = Hdr10PlusSummary {
+            tone_mapping_frames: 0,
+            ..Hdr10PlusSummary::default()
+        };
+        assert_eq!(luminance_only.declared_profile(), None);
+        assert!(!luminance_only.detected_profile().needs_dhdr10_opt());
+
+        let result = Hdr10PlusProcessingResult::from_summary(path.clone(), streamed, true);
+        assert!(result.metadata.is_none());
+        assert_eq!((result.curve_count, result.scene_count), (240, 2));
+
+        // Frames are validated while streaming
+        json["SceneInfo"][7]["SequenceFrameIndex"] = 9.into();
+        std::fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+        assert!(Hdr10PlusSummary::from_json_file(&path).await.is_err());
+        json["SceneInfo"] = serde_json::json!([]);
+        std::fs::write(&path, serde_json::to_string(&json).unwrap()).unwrap();
+        assert!(Hdr10PlusSummary::from_json_file(&path).await.is_err());
+    }
+
+    #[test]
+    fn test_trim_metadata() {
+        let mut json = hdr10plus_metadata(240);
+        for frame in 100..240 {
+            json["SceneInfo"][frame]["SceneId"] = 1.into();
+            json["SceneInfo"][frame]["SceneFrameIndex"] = (frame - 100).into();
+        }
+        let mut metadata: Hdr10PlusMetadata = serde_json::from_value(json).unwrap();
+
+        // Frames 60-159: the last 40 of scene 0, the first 60 of scene 1
+        metadata.trim(60..160);
+        assert_eq!(metadata.get_frame_count(), 100);
+        assert_eq!(metadata.get_scene_count(), 2);
+        metadata.validate().unwrap();
+        let frame = |index: usize| {
+            let scene = &metadata.scene_info[index];
+            (scene.scene_id, scene.scene_frame_index)
+        };
+        assert_eq!(frame(0), (0, 0));
+        assert_eq!(frame(39), (0, 39));
+        assert_eq!(frame(40), (1, 0));
+        assert_eq!(frame(99), (1, 59));
+    }
+}
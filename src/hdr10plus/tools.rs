@@ -1,4 +1,8 @@
-use crate::utils::{Result, ToolConfig, ToolRunner};
+use crate::log_step;
+use crate::tools::ExternalTool;
+use crate::utils::{
+    CancelToken, FfmpegWrapper, Result, ToolConfig, ToolInput, ToolProgress, ToolRunner,
+};
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
@@ -6,22 +10,51 @@ pub type Hdr10PlusToolConfig = ToolConfig;
 
 pub struct Hdr10PlusTool {
     tool: ToolRunner,
+    ffmpeg_pipe: Option<FfmpegWrapper>,
 }
 
 impl Hdr10PlusTool {
     pub fn new(config: Hdr10PlusToolConfig) -> Self {
         Self {
             tool: ToolRunner::new(config),
+            ffmpeg_pipe: None,
         }
     }
 
-    pub async fn check_availability(&self) -> Result<bool> {
-        match self.tool.check_availability("--help", "extract").await {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
+    /// Feeds extraction from `ffmpeg` (the HEVC elementary stream of the video
+    /// stream it selects, on stdin) instead of letting the tool open the
+    /// source, which it can only do for raw HEVC files.
+    pub fn with_ffmpeg_pipe(mut self, ffmpeg: FfmpegWrapper) -> Self {
+        self.ffmpeg_pipe = Some(ffmpeg);
+        self
+    }
+
+    fn source<'a>(&'a self, path: &'a Path) -> ToolInput<'a> {
+        match &self.ffmpeg_pipe {
+            Some(ffmpeg) => ToolInput::HevcPipe {
+                ffmpeg,
+                source: path,
+            },
+            None => ToolInput::File(path),
         }
     }
 
+    /// Lets `cancel` stop a running metadata extraction.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.tool.set_cancel_token(cancel);
+        self
+    }
+
+    /// Reports the tool's progress through `progress`.
+    pub fn with_progress(mut self, progress: ToolProgress) -> Self {
+        self.tool.set_progress(progress);
+        self
+    }
+
+    pub async fn check_availability(&self) -> Result<bool> {
+        Ok(crate::tools::probe(self).await.is_usable())
+    }
+
     pub async fn extract_metadata<P1: AsRef<Path>, P2: AsRef<Path>>(
         &self,
         input_video: P1,
@@ -30,26 +63,33 @@ impl Hdr10PlusTool {
         let input_path = input_video.as_ref().to_string_lossy();
         let output_path = output_json.as_ref().to_string_lossy();
 
-        info!(
+        log_step!(
             "Extracting HDR10+ metadata: {} -> {}",
-            input_path, output_path
+            input_path,
+            output_path
         );
         debug!("Running hdr10plus_tool (this may take a moment)...");
 
-        let base_args = vec![
+        let input = self.source(input_video.as_ref());
+        let mut args = vec![
             "extract".to_string(),
-            input_path.to_string(),
+            input.arg(),
             "-o".to_string(),
             output_path.to_string(),
         ];
+        if let Some(custom) = &self.tool.config().extract_args {
+            args.extend(custom.iter().cloned());
+        }
 
-        // Use silent method to avoid scary ERROR logs for expected failures
+        // Run silently to avoid scary ERROR logs for expected failures
         match self
             .tool
-            .run_with_custom_args_silent(
-                &base_args,
-                &self.tool.config().extract_args,
-                Some(output_json),
+            .run_monitored(
+                &args,
+                input,
+                output_json.as_ref(),
+                "Extracting HDR10+ metadata",
+                false,
             )
             .await
         {
@@ -178,3 +218,13 @@ impl Hdr10PlusTool {
         }
     }
 }
+
+impl ExternalTool for Hdr10PlusTool {
+    fn name(&self) -> &str {
+        "hdr10plus_tool"
+    }
+
+    fn path(&self) -> &str {
+        &self.tool.config().path
+    }
+}
@@ -0,0 +1,355 @@
+//! Blu-ray folder structures (`BDMV/PLAYLIST`, `BDMV/STREAM`) as inputs.
+//!
+//! A disc is given by its root folder (a copied disc or a mounted ISO), or the
+//! `BDMV` folder itself. The title to encode is a playlist (`.mpls`): the one
+//! named with `--playlist`, otherwise the longest one that does not repeat a
+//! segment (discs add looping playlists to confuse rippers). Its `.m2ts`
+//! segments are concatenated into a single transport stream for the pipeline.
+
+use crate::progress::format_duration;
+use crate::utils::{Error, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, info};
+
+/// MPLS timestamps are in 45 kHz ticks
+const MPLS_TICKS_PER_SECOND: f64 = 45_000.0;
+
+/// One playlist of a disc.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playlist {
+    /// File name, e.g. `00800.mpls`
+    pub name: String,
+    /// Clip names in play order (`00055` for `STREAM/00055.m2ts`)
+    pub clips: Vec<String>,
+    pub duration: Duration,
+}
+
+impl Playlist {
+    /// Parses the play items of an MPLS file; multi-angle items count with
+    /// their first angle.
+    pub fn parse(name: &str, data: &[u8]) -> Result<Self> {
+        let invalid = |reason: &str| Error::parse(format!("Invalid playlist {}: {}", name, reason));
+        let u16_at = |offset: usize| -> Result<usize> {
+            data.get(offset..offset + 2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]) as usize)
+                .ok_or_else(|| invalid("truncated"))
+        };
+        let u32_at = |offset: usize| -> Result<u32> {
+            data.get(offset..offset + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or_else(|| invalid("truncated"))
+        };
+
+        if !data.starts_with(b"MPLS") {
+            return Err(invalid("missing MPLS header"));
+        }
+        let playlist_start = u32_at(8)? as usize;
+        let item_count = u16_at(playlist_start + 6)?;
+
+        let mut clips = Vec::with_capacity(item_count);
+        let mut ticks = 0u64;
+        let mut offset = playlist_start + 10;
+        for _ in 0..item_count {
+            let length = u16_at(offset)?;
+            let item = data
+                .get(offset + 2..offset + 2 + length)
+                .filter(|item| item.len() >= 20)
+                .ok_or_else(|| invalid("truncated play item"))?;
+            clips.push(String::from_utf8_lossy(&item[0..5]).into_owned());
+            let in_time = u32::from_be_bytes([item[12], item[13], item[14], item[15]]);
+            let out_time = u32::from_be_bytes([item[16], item[17], item[18], item[19]]);
+            ticks += out_time.saturating_sub(in_time) as u64;
+            offset += 2 + length;
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            clips,
+            duration: Duration::from_secs_f64(ticks as f64 / MPLS_TICKS_PER_SECOND),
+        })
+    }
+
+    /// Whether a clip is played more than once, as in obfuscation playlists.
+    pub fn repeats_clips(&self) -> bool {
+        self.clips
+            .iter()
+            .enumerate()
+            .any(|(i, clip)| self.clips[..i].contains(clip))
+    }
+
+    /// `00800.mpls` and `00800` name the same playlist.
+    fn matches(&self, name: &str) -> bool {
+        let stem = |name: &str| {
+            let name = name.trim();
+            name.strip_suffix(".mpls").unwrap_or(name).to_lowercase()
+        };
+        stem(&self.name) == stem(name)
+    }
+}
+
+/// A Blu-ray folder structure with its playlists.
+#[derive(Debug, Clone)]
+pub struct BlurayDisc {
+    /// Folder containing `BDMV`
+    root: PathBuf,
+    bdmv: PathBuf,
+    playlists: Vec<Playlist>,
+}
+
+impl BlurayDisc {
+    /// Opens `path` if it is a disc root or a `BDMV` folder; `Ok(None)` for
+    /// anything else. ISO images have to be mounted first.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Option<Self>> {
+        let path = path.as_ref();
+        let is_iso = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("iso"));
+        if path.is_file() && is_iso {
+            return Err(Error::validation(format!(
+                "ISO images cannot be read directly; mount it (e.g. mount -o loop,ro {} /mnt/disc) and pass the mount point",
+                path.display()
+            )));
+        }
+
+        let bdmv = if path.join("BDMV").join("PLAYLIST").is_dir() {
+            path.join("BDMV")
+        } else if path.join("PLAYLIST").is_dir() && path.join("STREAM").is_dir() {
+            path.to_path_buf()
+        } else {
+            return Ok(None);
+        };
+        let root = bdmv.parent().unwrap_or(path).to_path_buf();
+
+        let mut playlists = Vec::new();
+        for entry in std::fs::read_dir(bdmv.join("PLAYLIST"))? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.to_lowercase().ends_with(".mpls") {
+                continue;
+            }
+            match Playlist::parse(&name, &std::fs::read(entry.path())?) {
+                Ok(playlist) => playlists.push(playlist),
+                Err(e) => debug!("Skipping playlist: {}", e),
+            }
+        }
+        playlists.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Some(Self {
+            root,
+            bdmv,
+            playlists,
+        }))
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn playlists(&self) -> &[Playlist] {
+        &self.playlists
+    }
+
+    /// Name of the disc folder, e.g. `MOVIE_TITLE`.
+    pub fn name(&self) -> String {
+        self.root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "bluray".to_string())
+    }
+
+    fn segment_path(&self, clip: &str) -> PathBuf {
+        self.bdmv.join("STREAM").join(format!("{}.m2ts", clip))
+    }
+
+    fn has_all_segments(&self, playlist: &Playlist) -> bool {
+        !playlist.clips.is_empty()
+            && playlist
+                .clips
+                .iter()
+                .all(|clip| self.segment_path(clip).is_file())
+    }
+
+    /// The playlist named `name`, or the main one: the longest with all
+    /// segments present, preferring playlists that play no segment twice.
+    pub fn select_playlist(&self, name: Option<&str>) -> Result<BlurayTitle> {
+        let playlist = match name {
+            Some(name) => self
+                .playlists
+                .iter()
+                .find(|playlist| playlist.matches(name))
+                .ok_or_else(|| {
+                    let available: Vec<&str> =
+                        self.playlists.iter().map(|p| p.name.as_str()).collect();
+                    Error::validation(format!(
+                        "Playlist {} not found in {} (available: {})",
+                        name,
+                        self.root.display(),
+                        available.join(", ")
+                    ))
+                })?,
+            None => self
+                .playlists
+                .iter()
+                .filter(|playlist| self.has_all_segments(playlist))
+                .max_by_key(|playlist| (!playlist.repeats_clips(), playlist.duration))
+                .ok_or_else(|| {
+                    Error::validation(format!(
+                        "No playable playlist found in {}",
+                        self.root.display()
+                    ))
+                })?,
+        };
+
+        let segments: Vec<PathBuf> = playlist
+            .clips
+            .iter()
+            .map(|clip| self.segment_path(clip))
+            .collect();
+        if let Some(missing) = segments.iter().find(|segment| !segment.is_file()) {
+            return Err(Error::validation(format!(
+                "Segment of playlist {} is missing: {}",
+                playlist.name,
+                missing.display()
+            )));
+        }
+
+        info!(
+            "Blu-ray {}: playlist {} ({}, {} segment(s))",
+            self.name(),
+            playlist.name,
+            format_duration(playlist.duration),
+            segments.len()
+        );
+        Ok(BlurayTitle {
+            disc_root: self.root.clone(),
+            disc_name: self.name(),
+            playlist: playlist.clone(),
+            segments,
+        })
+    }
+}
+
+/// The playlist of a disc that gets encoded.
+#[derive(Debug, Clone)]
+pub struct BlurayTitle {
+    pub disc_root: PathBuf,
+    pub disc_name: String,
+    pub playlist: Playlist,
+    pub segments: Vec<PathBuf>,
+}
+
+impl BlurayTitle {
+    /// Total size of the segments.
+    pub fn size(&self) -> Option<u64> {
+        self.segments
+            .iter()
+            .map(|segment| std::fs::metadata(segment).ok().map(|m| m.len()))
+            .sum()
+    }
+
+    /// Name of the transport stream the segments are joined into.
+    pub fn concat_file_name(&self) -> String {
+        let playlist = self.playlist.name.trim_end_matches(".mpls");
+        format!("bluray_{}_{}.m2ts", self.disc_name, playlist)
+    }
+
+    /// Joins the segments into `<temp_dir>/bluray_<disc>_<playlist>.m2ts`.
+    /// Transport streams can be concatenated byte by byte.
+    pub async fn concatenate(&self, temp_dir: &Path) -> Result<PathBuf> {
+        tokio::fs::create_dir_all(temp_dir).await?;
+        let output = temp_dir.join(self.concat_file_name());
+        info!(
+            "Joining {} segments of playlist {} into {}",
+            self.segments.len(),
+            self.playlist.name,
+            output.display()
+        );
+
+        let mut file = tokio::fs::File::create(&output).await?;
+        for segment in &self.segments {
+            let mut input = tokio::fs::File::open(segment).await?;
+            tokio::io::copy(&mut input, &mut file).await?;
+        }
+        file.sync_all().await?;
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Minimal MPLS file with one play item per `(clip, in, out)`.
+    fn mpls(items: &[(&str, u32, u32)]) -> Vec<u8> {
+        let mut data = b"MPLS0200".to_vec();
+        data.extend(20u32.to_be_bytes()); // PlayList start
+        data.extend([0u8; 8]); // PlayListMark, ExtensionData
+        data.extend([0u8; 4 + 2]); // length, reserved
+        data.extend((items.len() as u16).to_be_bytes());
+        data.extend(0u16.to_be_bytes()); // SubPaths
+        for (clip, in_time, out_time) in items {
+            data.extend(20u16.to_be_bytes());
+            data.extend(clip.as_bytes());
+            data.extend(b"M2TS");
+            data.extend([0u8; 3]); // flags, STC id
+            data.extend(in_time.to_be_bytes());
+            data.extend(out_time.to_be_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_select_main_playlist() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("MOVIE");
+        let bdmv = root.join("BDMV");
+        std::fs::create_dir_all(bdmv.join("PLAYLIST")).unwrap();
+        std::fs::create_dir_all(bdmv.join("STREAM")).unwrap();
+        for clip in ["00001", "00002", "00003"] {
+            std::fs::write(bdmv.join("STREAM").join(format!("{}.m2ts", clip)), b"ts").unwrap();
+        }
+        let hour = 3600 * 45_000;
+        let playlists = [
+            // Trailer, the feature in two segments, a looping fake
+            ("00001.mpls", mpls(&[("00003", 0, 120 * 45_000)])),
+            (
+                "00800.mpls",
+                mpls(&[("00001", 0, hour), ("00002", 0, hour)]),
+            ),
+            (
+                "00801.mpls",
+                mpls(&[("00001", 0, hour), ("00002", 0, hour), ("00001", 0, hour)]),
+            ),
+        ];
+        for (name, data) in &playlists {
+            std::fs::write(bdmv.join("PLAYLIST").join(name), data).unwrap();
+        }
+
+        let parsed = Playlist::parse("00800.mpls", &playlists[1].1).unwrap();
+        assert_eq!(parsed.clips, vec!["00001", "00002"]);
+        assert_eq!(parsed.duration, Duration::from_secs(7200));
+        assert!(Playlist::parse("bad.mpls", b"MPLS").is_err());
+
+        let disc = BlurayDisc::open(&root).unwrap().unwrap();
+        assert_eq!(disc.playlists().len(), 3);
+        let title = disc.select_playlist(None).unwrap();
+        assert_eq!(title.playlist.name, "00800.mpls");
+        assert_eq!(title.segments.len(), 2);
+        assert_eq!(title.size(), Some(4));
+        assert_eq!(title.concat_file_name(), "bluray_MOVIE_00800.m2ts");
+
+        let trailer = disc.select_playlist(Some("00001")).unwrap();
+        assert_eq!(
+            trailer.segments,
+            vec![bdmv.join("STREAM").join("00003.m2ts")]
+        );
+        assert!(disc.select_playlist(Some("00999.mpls")).is_err());
+
+        // The BDMV folder itself works too; other folders are not discs
+        assert!(BlurayDisc::open(&bdmv).unwrap().is_some());
+        assert!(BlurayDisc::open(disc.root().join("BDMV").join("STREAM"))
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,135 @@
+//! Discovery of what an `encode` run processes: video files, found directly or
+//! in folders, and Blu-ray folder structures.
+
+pub mod bluray;
+
+pub use bluray::{BlurayDisc, BlurayTitle, Playlist};
+
+use crate::encoding::Provenance;
+use crate::utils::{FfmpegWrapper, FileDiscovery, Result};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// One input to encode.
+#[derive(Debug, Clone)]
+pub enum InputSource {
+    File(PathBuf),
+    Bluray(BlurayTitle),
+}
+
+impl InputSource {
+    /// The file, or the disc folder.
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::File(path) => path,
+            Self::Bluray(title) => &title.disc_root,
+        }
+    }
+
+    /// Path an auto-generated output name is derived from; for discs
+    /// `<disc>.mkv` next to the disc folder, as its segments may be read-only.
+    pub fn output_name_path(&self) -> PathBuf {
+        match self {
+            Self::File(path) => path.clone(),
+            Self::Bluray(title) => title
+                .disc_root
+                .with_file_name(format!("{}.mkv", title.disc_name)),
+        }
+    }
+
+    /// Size of the source: the file, or the playlist's segments.
+    pub fn size(&self) -> Option<u64> {
+        match self {
+            Self::File(path) => std::fs::metadata(path).ok().map(|m| m.len()),
+            Self::Bluray(title) => title.size(),
+        }
+    }
+
+    /// The file the pipeline reads. A single-segment playlist is read in
+    /// place; longer ones are joined into `temp_dir` for the encode.
+    pub async fn prepare(&self, temp_dir: &Path) -> Result<PreparedInput> {
+        match self {
+            Self::File(path) => Ok(PreparedInput::existing(path.clone())),
+            Self::Bluray(title) if title.segments.len() == 1 => {
+                Ok(PreparedInput::existing(title.segments[0].clone()))
+            }
+            Self::Bluray(title) => Ok(PreparedInput {
+                path: title.concatenate(temp_dir).await?,
+                temporary: true,
+            }),
+        }
+    }
+}
+
+/// The input file of one encode; removed on drop if it was created for it.
+#[derive(Debug)]
+pub struct PreparedInput {
+    path: PathBuf,
+    temporary: bool,
+}
+
+impl PreparedInput {
+    fn existing(path: PathBuf) -> Self {
+        Self {
+            path,
+            temporary: false,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PreparedInput {
+    fn drop(&mut self) {
+        if !self.temporary {
+            return;
+        }
+        match std::fs::remove_file(&self.path) {
+            Ok(()) => debug!("Removed joined input: {}", self.path.display()),
+            Err(e) => warn!("Failed to remove {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+/// The inputs found at `path`: the main (or `playlist`) title of a Blu-ray
+/// folder structure, otherwise the video files `discovery` finds at or under
+/// `path`.
+pub fn discover_inputs<P: AsRef<Path>>(
+    path: P,
+    playlist: Option<&str>,
+    discovery: &mut FileDiscovery,
+) -> Result<Vec<InputSource>> {
+    let path = path.as_ref();
+    if let Some(disc) = BlurayDisc::open(path)? {
+        return Ok(vec![InputSource::Bluray(disc.select_playlist(playlist)?)]);
+    }
+    Ok(discovery
+        .find(path)?
+        .into_iter()
+        .map(InputSource::File)
+        .collect())
+}
+
+/// Splits off the files VEN itself produced, recognized by the provenance
+/// tags it writes into every output, so a batch over a folder that already
+/// holds some of its outputs does not encode them a second time.
+pub async fn split_own_outputs(
+    ffmpeg: &FfmpegWrapper,
+    sources: Vec<InputSource>,
+) -> (Vec<InputSource>, Vec<(PathBuf, Provenance)>) {
+    let mut inputs = Vec::with_capacity(sources.len());
+    let mut own_outputs = Vec::new();
+    for source in sources {
+        let provenance = match &source {
+            InputSource::File(path) => Provenance::read(ffmpeg, path).await,
+            InputSource::Bluray(_) => None,
+        };
+        match provenance {
+            Some(provenance) => own_outputs.push((source.path().to_path_buf(), provenance)),
+            None => inputs.push(source),
+        }
+    }
+    (inputs, own_outputs)
+}
@@ -12,6 +12,8 @@
 #![allow(clippy::match_same_arms)]
 
 pub mod analysis;
+pub mod audio;
+pub mod batch;
 pub mod cli;
 pub mod color;
 pub mod config;
@@ -19,17 +21,33 @@ pub mod content_manager;
 pub mod dolby_vision;
 pub mod dolby_vision_integration_test;
 pub mod encoding;
+pub mod engine;
+pub mod events;
+pub mod explain;
 pub mod hdr;
 pub mod hdr10plus;
+pub mod input;
 pub mod metadata_workflow;
 pub mod mkvmerge;
+pub mod planning;
 pub mod preview;
 pub mod processing;
 pub mod progress;
+pub mod quality;
+pub mod queue;
+pub mod reports;
+pub mod schedule;
+pub mod selftest;
+pub mod shootout;
 pub mod stream;
+pub mod testsignal;
+pub mod throttle;
+pub mod tools;
 pub mod utils;
 
-pub use analysis::{ContentClassification, DolbyVisionInfo, DolbyVisionProfile, VideoAnalysis};
+pub use analysis::{
+    ContentClassification, ContentClassifier, DolbyVisionInfo, DolbyVisionProfile, VideoAnalysis,
+};
 pub use color::ColorManager;
 pub use config::{Config, ContentType, DolbyVisionConfig, EncodingProfile, UnifiedHdrConfig};
 pub use content_manager::{
@@ -37,9 +55,12 @@ pub use content_manager::{
 };
 pub use dolby_vision::{DoviTool, DoviToolConfig, RpuManager, RpuMetadata};
 pub use encoding::{EncodingMode, EncodingOptions};
+pub use engine::{AnalysisReport, EncodeOutcome, RenditionResult, VenEngine};
+pub use events::{CommandHook, EncodeEvent, EncodeProgress, EventBus, EventSink, JsonReporter};
 pub use hdr::{ColorSpace, HdrFormat, HdrManager, HdrMetadata, TransferFunction};
 pub use hdr10plus::{
-    Hdr10PlusManager, Hdr10PlusMetadata, Hdr10PlusProcessingResult, Hdr10PlusToolConfig,
+    Hdr10PlusManager, Hdr10PlusMetadata, Hdr10PlusProcessingResult, Hdr10PlusProfile,
+    Hdr10PlusToolConfig,
 };
 pub use metadata_workflow::{ExtractedMetadata, MetadataWorkflowManager, ToolAvailability};
 pub use mkvmerge::{MkvMergeConfig, MkvMergeTool};
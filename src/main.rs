@@ -1,38 +1,46 @@
 use clap::Parser;
-use tracing::info;
+use std::sync::Arc;
+use tracing::{info, warn};
 
 use ven::{
-    cli::{handle_commands, CliArgs},
+    analysis::CachedAnalysis,
+    batch::BatchSummary,
+    cli::{handle_commands, CliArgs, Command, EncodeArgs, PreviewArgs},
     config::{Config, PreviewProfileManager, ProfileManager},
-    preview::{PreviewConfig, PreviewMode, PreviewProcessor},
-    processing::VideoProcessor,
-    stream::preservation::StreamPreservation,
+    input::{discover_inputs, split_own_outputs, InputSource},
+    log_root,
+    planning::{plan_time_budget, EncodeHistory, HistoryRecord},
+    preview::{PreviewConfig, PreviewMode, PreviewProcessor, PreviewSeek},
+    processing::ladder,
+    progress::BatchProgress,
+    reports::{EncodeReport, ReportLog},
     utils::{
-        find_video_files, generate_uuid_filename, setup_logging, Error, FfmpegWrapper, Result,
+        generate_uuid_filename, setup_logging, sweep_on_startup, write_debug_bundle, CancelToken,
+        Error, FfmpegWrapper, Result,
     },
+    JsonReporter, VenEngine,
 };
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = CliArgs::parse();
 
-    if !args.is_info_command() && args.input.is_empty() {
+    let Some((command, deprecated_hint)) = args.resolve_command()? else {
         use clap::CommandFactory;
         let mut cmd = CliArgs::command();
         cmd.print_help().unwrap();
         println!();
         return Ok(());
-    }
+    };
 
-    args.validate()?;
+    command.validate()?;
 
-    let config = Config::load_with_discovery(args.config.as_deref())?;
+    let config = Config::load_with_discovery(args.global.config.as_deref())?;
 
-    setup_logging(
-        args.get_log_level(&config.logging.level),
-        config.logging.show_timestamps,
-        config.logging.colored_output,
-    )?;
+    setup_logging(args.get_log_level(&config.logging.level), &config.logging)?;
+    for warning in config.warnings() {
+        warn!("{}", warning);
+    }
 
     // Display application banner
     info!(
@@ -41,28 +49,38 @@ async fn main() -> Result<()> {
         env!("CARGO_PKG_VERSION")
     );
 
-    if handle_commands(&args, &config).await? {
+    if let Some(hint) = deprecated_hint {
+        warn!(
+            "Flat option flags are deprecated; use '{} {}' instead",
+            env!("CARGO_BIN_NAME"),
+            hint
+        );
+    }
+
+    if handle_commands(&command, &args, &config).await? {
         return Ok(());
     }
 
-    if args.should_encode() {
-        handle_encoding(&args, &config).await
-    } else if args.should_preview() {
-        handle_preview(&args, &config).await
-    } else {
-        Ok(())
+    match &command {
+        Command::Encode(encode_args) => handle_encoding(encode_args, &config).await,
+        Command::Preview(preview_args) => handle_preview(preview_args, &config).await,
+        _ => Ok(()),
     }
 }
 
-async fn handle_encoding(args: &CliArgs, config: &Config) -> Result<()> {
-    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
-
-    ffmpeg
-        .check_availability()
-        .await
-        .map_err(|e| Error::ffmpeg(format!("FFmpeg tools not available: {}", e)))?;
+async fn handle_encoding(args: &EncodeArgs, config: &Config) -> Result<()> {
+    if args.error_resilience {
+        warn!("Error resilience enabled: corrupt frames will be discarded instead of aborting the encode");
+    }
 
-    let stream_preservation = StreamPreservation::new(ffmpeg.clone());
+    let cancel = cancel_on_ctrl_c();
+    let mut engine = VenEngine::new(config.clone()).with_cancel_token(cancel.clone());
+    if let Some(path) = &args.events_json {
+        let reporter = JsonReporter::append_to(path).map_err(|e| {
+            Error::validation(format!("Cannot write events to {}: {}", path.display(), e))
+        })?;
+        engine = engine.with_event_sink(Arc::new(reporter));
+    }
 
     if args.input.is_empty() {
         return Err(Error::validation(
@@ -70,14 +88,37 @@ async fn handle_encoding(args: &CliArgs, config: &Config) -> Result<()> {
         ));
     }
 
-    let mut all_video_files: Vec<std::path::PathBuf> = Vec::new();
+    let mut discovery = args.file_discovery();
+    let mut all_video_files: Vec<InputSource> = Vec::new();
     for input_path in &args.input {
-        let mut files = find_video_files(input_path)?;
+        let mut files = discover_inputs(input_path, args.playlist.as_deref(), &mut discovery)?;
         all_video_files.append(&mut files);
     }
 
-    let video_files = all_video_files;
-    info!("Found {} video file(s) to process", video_files.len());
+    let mut summary = BatchSummary::new();
+
+    let video_files = if args.re_encode_own_output {
+        all_video_files
+    } else {
+        let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+        let (inputs, own_outputs) = split_own_outputs(&ffmpeg, all_video_files).await;
+        for (path, provenance) in &own_outputs {
+            info!(
+                "Skipping {}: already encoded ({})",
+                path.display(),
+                provenance
+            );
+            summary.record_skip(path, provenance);
+        }
+        if !own_outputs.is_empty() {
+            log_root!(
+                "Skipped {} file(s) VEN already produced; pass --re-encode-own-output to encode them",
+                own_outputs.len()
+            );
+        }
+        inputs
+    };
+    log_root!("Found {} video file(s) to process", video_files.len());
 
     let mut profile_manager = ProfileManager::new();
     profile_manager.load_profiles(config.profiles.clone())?;
@@ -99,117 +140,302 @@ async fn handle_encoding(args: &CliArgs, config: &Config) -> Result<()> {
         )));
     }
 
-    let mut successful_files = 0;
-    let mut failed_files = Vec::new();
+    if let Some(ladder) = &args.ladder {
+        ladder::find_ladder(config, ladder)?;
+    }
+
+    sweep_on_startup(config);
+
+    let schedule = args.schedule_window()?;
+
+    let mut reports = match ReportLog::open_default() {
+        Ok(reports) => Some(reports),
+        Err(e) => {
+            warn!("Encode reports are not recorded: {}", e);
+            None
+        }
+    };
+
+    // Below each file's bar: files done and the time left for the batch
+    let report_profile = (args.profile != "auto").then_some(args.profile.as_str());
+    let batch_progress = (video_files.len() > 1).then(|| {
+        BatchProgress::new(
+            video_files.iter().map(InputSource::size).collect(),
+            reports
+                .as_ref()
+                .and_then(|reports| reports.seconds_per_byte(report_profile)),
+        )
+    });
+    let engine = match &batch_progress {
+        Some(batch) => engine.with_batch_progress(batch.clone()),
+        None => engine,
+    };
+
+    let mut history = None;
+    let plan = match args.time_budget()? {
+        Some(budget) => {
+            log_root!(
+                "Planning presets for a time budget of {}...",
+                args.time_budget.as_deref().unwrap_or_default()
+            );
+            let encode_history = EncodeHistory::open_default()?;
+            let files: Vec<&std::path::Path> = video_files
+                .iter()
+                .filter_map(|source| match source {
+                    InputSource::File(path) => Some(path.as_path()),
+                    InputSource::Bluray(_) => {
+                        warn!(
+                            "Not planning {} (keeps its profile's preset): disc titles are not probed",
+                            source.path().display()
+                        );
+                        None
+                    }
+                })
+                .collect();
+            let ffmpeg =
+                FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone())
+                    .with_video_stream(args.video_stream)
+                    .with_error_resilience(args.error_resilience);
+            let plan = plan_time_budget(config, &ffmpeg, &files, budget, &encode_history).await;
+            plan.log();
+            history = Some(encode_history);
+            Some(plan)
+        }
+        None => None,
+    };
 
-    for (index, input_path) in video_files.iter().enumerate() {
-        info!(
+    // With --pipeline: the next file's input path and its analysis, run during this encode
+    let mut analyzed_ahead: Option<(std::path::PathBuf, Result<CachedAnalysis>)> = None;
+    for (index, source) in video_files.iter().enumerate() {
+        let input_path = source.path();
+        let ahead = analyzed_ahead.take();
+        if cancel.is_cancelled() {
+            warn!(
+                "Skipping the remaining {} file(s)",
+                video_files.len() - index
+            );
+            break;
+        }
+        if let Some(window) = &schedule {
+            window.wait_until_open().await;
+        }
+
+        log_root!(
             "Processing file {}/{}: {}",
             index + 1,
             video_files.len(),
             input_path.display()
         );
+        if let Some(batch) = &batch_progress {
+            batch.start_file(index);
+        }
 
         if !input_path.exists() {
-            let error_msg = format!("File not found: {}", input_path.display());
-            tracing::warn!("{}", error_msg);
-            failed_files.push((input_path.clone(), error_msg));
+            let error = Error::validation(format!("File not found: {}", input_path.display()));
+            tracing::warn!("{}", error);
+            summary.record_failure(input_path, &error, std::time::Duration::ZERO);
             continue;
         }
 
         let output_path = if let Some(output) = &args.output {
             if video_files.len() > 1 {
                 let parent = output.parent().unwrap_or(output);
-                generate_uuid_filename(input_path, Some(parent))
+                generate_uuid_filename(source.output_name_path(), Some(parent))
             } else {
                 output.clone()
             }
         } else {
-            generate_uuid_filename(input_path, None::<&std::path::Path>)
+            generate_uuid_filename(source.output_name_path(), None::<&std::path::Path>)
         };
 
-        match process_single_file(
-            &ffmpeg,
-            &stream_preservation,
-            args,
-            config,
-            &mut profile_manager,
-            input_path,
-            &output_path,
-        )
-        .await
-        {
-            Ok(()) => {
-                successful_files += 1;
-                info!("✓ Successfully processed: {}", input_path.display());
-            }
+        let started = std::time::Instant::now();
+        let temp_dir = std::path::Path::new(&config.app.temp_dir);
+        let prepared = match source.prepare(temp_dir).await {
+            Ok(prepared) => prepared,
             Err(e) => {
-                let error_msg = format!("Failed to process {}: {}", input_path.display(), e);
-                tracing::error!("{}", error_msg);
-                failed_files.push((input_path.clone(), error_msg));
+                tracing::error!("Failed to read {}: {}", input_path.display(), e);
+                summary.record_failure(input_path, &e, started.elapsed());
+                continue;
+            }
+        };
+        let mut options = args.to_encoding_options(prepared.path(), &output_path);
+        let planned = plan.as_ref().and_then(|plan| plan.get(input_path));
+        if let Some(planned) = planned {
+            options.preset = Some(planned.preset.clone());
+        }
+        let analysis = match ahead {
+            Some((path, Ok(analysis))) if path == options.input_path => analysis,
+            Some((path, Err(e))) if path == options.input_path => {
+                warn!(
+                    "Analysis ahead of the encode failed, analyzing again: {}",
+                    e
+                );
+                CachedAnalysis::default()
+            }
+            _ => CachedAnalysis::default(),
+        };
+        // Ladders pick the analysis up from the analysis cache
+        let next_options = video_files
+            .get(index + 1)
+            .filter(|_| args.pipeline && args.ladder.is_none())
+            .and_then(|next| match next {
+                InputSource::File(path) if path.exists() => {
+                    Some(args.to_encoding_options(path, &output_path))
+                }
+                _ => None,
+            });
+
+        // One result per output: a single encode, or every rendition of the ladder
+        let encode = async {
+            match &args.ladder {
+                Some(ladder) => match engine.encode_ladder(&options, ladder).await {
+                    Ok(renditions) => renditions
+                        .into_iter()
+                        .map(|rendition| (rendition.output_path, rendition.result))
+                        .collect(),
+                    Err(e) => vec![(output_path, Err(e))],
+                },
+                None => vec![(
+                    output_path,
+                    engine.encode_with_analysis(&options, analysis).await,
+                )],
+            }
+        };
+        let results = match &next_options {
+            Some(next) => {
+                let (results, next_analysis) = tokio::join!(encode, engine.analyze_ahead(next));
+                analyzed_ahead = Some((next.input_path.clone(), next_analysis));
+                results
+            }
+            None => encode.await,
+        };
+        for (output_path, result) in results {
+            let (quality, decisions, hdr10plus_profile, fingerprint, log) = match &result {
+                Ok(outcome) => (
+                    outcome.quality.clone(),
+                    outcome.decisions.clone(),
+                    outcome.hdr10plus_profile,
+                    outcome.fingerprint.clone(),
+                    outcome.output_path.with_extension("log"),
+                ),
+                Err(_) => (
+                    Vec::new(),
+                    Vec::new(),
+                    None,
+                    None,
+                    output_path.with_extension("log"),
+                ),
+            };
+            match result {
+                Ok(outcome) => {
+                    info!(
+                        "✓ Successfully processed: {} -> {}",
+                        input_path.display(),
+                        outcome.output_path.display()
+                    );
+                    summary.record_success(input_path, source.size(), &outcome);
+                    if !outcome.decisions.is_empty() {
+                        info!("Decisions (--explain):");
+                        for decision in &outcome.decisions {
+                            info!("  {}", decision);
+                        }
+                    }
+                    if let (Some(history), Some(planned), Some(fps)) =
+                        (history.as_mut(), planned, outcome.average_fps())
+                    {
+                        let record = HistoryRecord::new(&planned.preset, planned.probe_fps, fps);
+                        if let Err(e) = history.record(record) {
+                            warn!("Failed to update the encode history: {}", e);
+                        }
+                    }
+                }
+                Err(e @ Error::Inefficient(_)) => {
+                    warn!("✗ Inefficient, source kept: {}", input_path.display());
+                    summary.record_failure(input_path, &e, started.elapsed());
+                }
+                Err(e @ Error::Duplicate(_)) => {
+                    warn!("- Skipped as a duplicate: {}", input_path.display());
+                    summary.record_failure(input_path, &e, started.elapsed());
+                }
+                Err(e) => {
+                    tracing::error!("Failed to process {}: {}", input_path.display(), e);
+                    if args.debug_bundle {
+                        match write_debug_bundle(config, input_path, &output_path, &e).await {
+                            Ok(bundle) => info!("Debug bundle written to: {}", bundle.display()),
+                            Err(bundle_error) => {
+                                warn!("Failed to write debug bundle: {}", bundle_error)
+                            }
+                        }
+                    }
+                    summary.record_failure(input_path, &e, started.elapsed());
+                }
+            }
+            if let (Some(reports), Some(file)) = (reports.as_mut(), summary.files().last()) {
+                let report = EncodeReport::new(file.clone(), quality, log.is_file().then_some(log))
+                    .with_decisions(decisions)
+                    .with_hdr10plus_profile(hdr10plus_profile)
+                    .with_fingerprint(fingerprint);
+                if let Err(e) = reports.append(report) {
+                    warn!("Failed to record the encode report: {}", e);
+                }
+                if let Some(batch) = &batch_progress {
+                    batch.set_seconds_per_byte(reports.seconds_per_byte(report_profile));
+                }
             }
         }
     }
+    if let Some(batch) = &batch_progress {
+        batch.finish();
+    }
 
     if video_files.len() > 1 {
-        info!(
-            "Processing complete: {} successful, {} failed",
-            successful_files,
-            failed_files.len()
-        );
+        summary.log();
+    }
+    if let Some(path) = &args.summary {
+        summary.write(path)?;
+    }
 
-        if !failed_files.is_empty() {
-            info!("Failed files:");
-            for (path, error) in &failed_files {
-                info!("  - {}: {}", path.display(), error);
-            }
-        }
+    let totals = summary.totals();
+
+    if cancel.is_cancelled() {
+        return Err(Error::cancelled("interrupted by Ctrl+C"));
     }
 
-    if successful_files == 0 && !failed_files.is_empty() {
+    if totals.encoded == 0 && totals.failed > 0 {
         return Err(Error::encoding("All files failed to process".to_string()));
     }
 
     Ok(())
 }
 
-async fn process_single_file(
-    ffmpeg: &FfmpegWrapper,
-    stream_preservation: &StreamPreservation,
-    args: &CliArgs,
-    config: &Config,
-    profile_manager: &mut ProfileManager,
-    input_path: &std::path::Path,
-    output_path: &std::path::Path,
-) -> Result<()> {
-    let mut processor = VideoProcessor::new(
-        ffmpeg,
-        stream_preservation,
-        args,
-        config,
-        profile_manager,
-        input_path,
-        output_path,
-    )?;
-    processor.run().await
+/// The first Ctrl+C stops the current file (killing running metadata tools and
+/// removing their partial output) and skips the rest; a second one exits at once.
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        warn!("Interrupted, stopping (press Ctrl+C again to exit immediately)");
+        token.cancel();
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+    cancel
 }
 
-async fn handle_preview(args: &CliArgs, config: &Config) -> Result<()> {
-    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+async fn handle_preview(args: &PreviewArgs, config: &Config) -> Result<()> {
+    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone())
+        .with_video_stream(args.video_stream);
 
     ffmpeg
         .check_availability()
         .await
         .map_err(|e| Error::ffmpeg(format!("FFmpeg tools not available: {}", e)))?;
 
-    if args.input.is_empty() {
-        return Err(Error::validation(
-            "At least one input path is required for preview mode".to_string(),
-        ));
-    }
-
-    // Get the first input file (preview mode only supports single file)
-    let input_path = &args.input[0];
+    let input_path = &args.input;
     if !input_path.is_file() {
         return Err(Error::validation(format!(
             "Preview mode requires a single video file as input, not a directory: {}",
@@ -227,13 +453,13 @@ async fn handle_preview(args: &CliArgs, config: &Config) -> Result<()> {
     info!("Preview mode enabled - testing {} profile(s)", profile_names.len());
 
     // Determine preview mode
-    let preview_mode = if let Some(timestamp) = args.preview_time {
+    let preview_mode = if let Some(&timestamp) = args.time.first() {
         PreviewMode::Image { timestamp }
     } else if let Some((start, end)) = args.parse_preview_range() {
         PreviewMode::VideoSegment { start, end }
     } else {
         return Err(Error::validation(
-            "Preview mode requires either --preview-time or --preview-range".to_string(),
+            "Preview requires either --time or --range".to_string(),
         ));
     };
 
@@ -241,6 +467,7 @@ async fn handle_preview(args: &CliArgs, config: &Config) -> Result<()> {
     let preview_config = PreviewConfig {
         mode: preview_mode,
         profile_names,
+        seek: PreviewSeek::from_string(&args.seek).unwrap_or_default(),
     };
 
     // Determine output directory from -o parameter if provided
@@ -253,18 +480,29 @@ async fn handle_preview(args: &CliArgs, config: &Config) -> Result<()> {
     });
 
     // Create preview processor and generate previews
-    let processor = PreviewProcessor::new(&ffmpeg, config, &profile_manager, input_path, output_dir, preview_config);
-    let _results = processor.generate_previews().await?;
+    let mut processor = PreviewProcessor::new(
+        &ffmpeg,
+        config,
+        &profile_manager,
+        input_path,
+        output_dir,
+        preview_config,
+    );
+    if args.time.len() > 1 {
+        processor.generate_montage(&args.time).await?;
+    } else {
+        let _results = processor.generate_previews().await?;
+    }
 
     Ok(())
 }
 
 fn get_preview_profile_names(
-    args: &CliArgs,
+    args: &PreviewArgs,
     config: &Config,
     profile_manager: &ProfileManager,
 ) -> Result<Vec<String>> {
-    if let Some(preview_profile_name) = &args.preview_profile {
+    if let Some(preview_profile_name) = &args.group {
         // Use custom preview profile group from config
         let preview_manager = PreviewProfileManager::new(config.preview_profiles.clone())?;
         let preview_profile = preview_manager.get_profile(preview_profile_name)?;
@@ -6,18 +6,19 @@
 /// 3. Provide metadata paths for x265 encoding (--dhdr10-info, etc.)
 /// 4. Inject metadata after encoding (dovi_tool inject-rpu, hdr10plus_tool inject)
 /// 5. Clean up temporary files
-use crate::analysis::dolby_vision::DolbyVisionInfo;
-use crate::config::Config;
-use crate::dolby_vision::{
-    rpu::RpuManager,
-    tools::{DoviTool, DoviToolConfig},
-    RpuMetadata,
-};
+use crate::analysis::dolby_vision::{DolbyVisionInfo, DolbyVisionProfile};
+use crate::config::{Config, MetadataFallback};
+use crate::dolby_vision::{rpu::RpuManager, tools::DoviTool, RpuMetadata};
 use crate::hdr::types::HdrAnalysisResult;
-use crate::hdr10plus::{manager::Hdr10PlusManager, Hdr10PlusProcessingResult};
+use crate::hdr10plus::{
+    manager::{Hdr10PlusManager, DEFAULT_SUMMARY_THRESHOLD_MB},
+    Hdr10PlusMetadata, Hdr10PlusProcessingResult,
+};
 use crate::mkvmerge::MkvMergeTool;
-use crate::utils::Result;
+use crate::utils::{CancelToken, Error, FfmpegWrapper, JobTempDir, Result, ToolProgress};
 use crate::ContentEncodingApproach;
+use crate::{log_stage, log_step};
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 use tracing::{debug, info, warn};
 
@@ -26,6 +27,9 @@ pub struct ExtractedMetadata {
     pub dolby_vision: Option<RpuMetadata>,
     pub hdr10_plus: Option<Hdr10PlusProcessingResult>,
     pub temp_dir: PathBuf,
+    /// Step of `analysis.fallback_chain` the output was degraded to, if the
+    /// source's metadata could not be carried over
+    pub fallback: Option<MetadataFallback>,
 }
 
 impl ExtractedMetadata {
@@ -34,6 +38,7 @@ impl ExtractedMetadata {
             dolby_vision: None,
             hdr10_plus: None,
             temp_dir,
+            fallback: None,
         }
     }
 
@@ -64,7 +69,16 @@ impl ExtractedMetadata {
 pub struct MetadataWorkflowManager {
     rpu_manager: Option<RpuManager>,
     hdr10plus_manager: Option<Hdr10PlusManager>,
+    ffmpeg_path: String,
+    convert_dv_to_hdr10plus: bool,
+    dv_fallback_to_hdr10: bool,
+    hdr10plus_fallback_to_hdr10: bool,
+    fallback_chain: Vec<MetadataFallback>,
+    /// `--strict-metadata`: fail instead of writing an output without the
+    /// source's Dolby Vision/HDR10+ metadata
+    strict: bool,
     temp_dir: PathBuf,
+    job_id: String,
     tools_available: ToolAvailability,
 }
 
@@ -75,8 +89,18 @@ pub struct ToolAvailability {
 }
 
 impl MetadataWorkflowManager {
-    pub async fn new(config: &Config) -> Result<Self> {
-        let temp_dir = PathBuf::from(&config.app.temp_dir);
+    /// Sets up the managers for the enabled metadata formats, extracting from
+    /// the video stream `ffmpeg` selects; `cancel` stops running RPU/HDR10+
+    /// extractions, and the tools report their progress through `progress`.
+    /// Extracted metadata is written to `job_dir`.
+    pub async fn new(
+        config: &Config,
+        ffmpeg: &FfmpegWrapper,
+        cancel: &CancelToken,
+        progress: &ToolProgress,
+        job_dir: &JobTempDir,
+    ) -> Result<Self> {
+        let temp_dir = job_dir.path().to_path_buf();
 
         // Initialize RPU manager if Dolby Vision is enabled
         let rpu_manager = if config
@@ -85,26 +109,40 @@ impl MetadataWorkflowManager {
             .as_ref()
             .is_some_and(|dv| dv.enabled)
         {
-            let dovi_tool = if let Some(ref dv_config) = config.tools.dovi_tool {
-                // Convert from config::types::DoviToolConfig to dolby_vision::tools::DoviToolConfig
-                let tool_config = DoviToolConfig {
-                    path: dv_config.path.clone(),
-                    timeout_seconds: dv_config.timeout_seconds,
-                    extract_args: dv_config.extract_args.clone(),
-                    inject_args: dv_config.inject_args.clone(),
-                };
-                Some(DoviTool::new(tool_config))
-            } else {
-                None
-            };
+            let dovi_tool = config.tools.dovi_tool.as_ref().map(|dv_config| {
+                DoviTool::new(dv_config.to_tool_config())
+                    .with_cancel_token(cancel.clone())
+                    .with_progress(progress.clone())
+                    .with_ffmpeg_pipe(ffmpeg.clone())
+            });
 
-            let mkvmerge_tool = config.tools.mkvmerge.as_ref().map(|mkv_config| MkvMergeTool::new(mkv_config.clone()));
+            let mkvmerge_tool = config.tools.mkvmerge.as_ref().map(|mkv_config| {
+                MkvMergeTool::new(mkv_config.clone()).with_progress(progress.clone())
+            });
 
             Some(RpuManager::new(temp_dir.clone(), dovi_tool, mkvmerge_tool))
         } else {
             None
         };
 
+        // A failed extraction degrades to HDR10 unless the format's config says otherwise
+        let dv_fallback_to_hdr10 = config
+            .analysis
+            .dolby_vision
+            .as_ref()
+            .is_none_or(|dv| dv.fallback_to_hdr10);
+        let hdr10plus_fallback_to_hdr10 = config
+            .analysis
+            .hdr10_plus
+            .as_ref()
+            .is_none_or(|h| h.fallback_to_hdr10);
+
+        let hdr10plus_summary_threshold = config
+            .analysis
+            .hdr10_plus
+            .as_ref()
+            .map_or(DEFAULT_SUMMARY_THRESHOLD_MB, |h| h.summary_threshold_mb);
+
         // Initialize HDR10+ manager if HDR10+ is enabled
         let hdr10plus_manager = if config
             .analysis
@@ -112,10 +150,14 @@ impl MetadataWorkflowManager {
             .as_ref()
             .is_some_and(|h| h.enabled)
         {
-            Some(Hdr10PlusManager::new(
-                temp_dir.clone(),
-                config.tools.hdr10plus_tool.clone(),
-            ))
+            Some(
+                Hdr10PlusManager::new(temp_dir.clone(), config.tools.hdr10plus_tool.clone())
+                    .with_cancel_token(cancel.clone())
+                    .with_progress(progress.clone())
+                    .with_ffmpeg_pipe(ffmpeg)
+                    .with_hdr10_fallback(hdr10plus_fallback_to_hdr10)
+                    .with_summary_threshold(hdr10plus_summary_threshold),
+            )
         } else {
             None
         };
@@ -123,7 +165,18 @@ impl MetadataWorkflowManager {
         let mut workflow_manager = Self {
             rpu_manager,
             hdr10plus_manager,
+            ffmpeg_path: config.tools.ffmpeg.clone(),
+            convert_dv_to_hdr10plus: config
+                .analysis
+                .dolby_vision
+                .as_ref()
+                .is_some_and(|dv| dv.convert_to_hdr10plus),
+            dv_fallback_to_hdr10,
+            hdr10plus_fallback_to_hdr10,
+            fallback_chain: config.analysis.fallback_chain.clone(),
+            strict: false,
             temp_dir,
+            job_id: job_dir.id().to_string(),
             tools_available: ToolAvailability {
                 dovi_tool: false,
                 hdr10plus_tool: false,
@@ -136,6 +189,24 @@ impl MetadataWorkflowManager {
         Ok(workflow_manager)
     }
 
+    /// Fails the job on any metadata workflow failure (missing tool, failed
+    /// extraction, conversion or injection) instead of falling back to an
+    /// output without the Dolby Vision RPU or HDR10+ metadata.
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        if strict {
+            self.dv_fallback_to_hdr10 = false;
+            self.hdr10plus_fallback_to_hdr10 = false;
+        }
+        self.strict = strict;
+        self
+    }
+
+    /// Leaves the intermediate files of RPU injection in place (`--keep-temp`).
+    pub fn with_keep_temp(mut self, keep: bool) -> Self {
+        self.rpu_manager = self.rpu_manager.map(|manager| manager.with_keep_temp(keep));
+        self
+    }
+
     async fn check_and_log_tool_availability(&mut self) -> Result<()> {
         debug!("Checking external metadata tool availability...");
 
@@ -189,9 +260,9 @@ impl MetadataWorkflowManager {
 
         // Summary
         if self.tools_available.dovi_tool || self.tools_available.hdr10plus_tool {
-            info!("HDR/DV metadata tools ready");
+            log_step!("HDR/DV metadata tools ready");
         } else {
-            info!("No external tools available - using x265 built-in HDR support only");
+            log_step!("No external tools available - using x265 built-in HDR support only");
         }
 
         Ok(())
@@ -205,47 +276,74 @@ impl MetadataWorkflowManager {
         dv_info: &DolbyVisionInfo,
         hdr_analysis: &HdrAnalysisResult,
     ) -> Result<ExtractedMetadata> {
-        info!("Starting pre-encoding metadata extraction phase");
+        log_stage!("Starting pre-encoding metadata extraction phase");
 
         let mut extracted = ExtractedMetadata::none(self.temp_dir.clone());
 
         match approach {
             ContentEncodingApproach::DolbyVision(_) => {
-                info!("Processing Dolby Vision content");
+                log_step!("Processing Dolby Vision content");
                 extracted.dolby_vision = self
                     .extract_dolby_vision_metadata(&input_path, dv_info)
                     .await?;
+                self.require_dolby_vision(dv_info, &extracted)?;
+                if self.convert_dv_to_hdr10plus {
+                    self.convert_dolby_vision_to_hdr10plus(&mut extracted).await;
+                }
+            }
+            ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _)
+                if self.convert_dv_to_hdr10plus =>
+            {
+                log_step!(
+                    "Processing dual format content as HDR10+ (dolby_vision.convert_to_hdr10plus)"
+                );
+                info!("   Source HDR10+ metadata is used as-is; the Dolby Vision RPU is dropped");
+                extracted.hdr10_plus = self
+                    .extract_hdr10plus_metadata(&input_path, hdr_analysis)
+                    .await?;
+                self.require_hdr10plus(hdr_analysis, &extracted)?;
             }
             ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => {
-                info!("Processing dual format content (Dolby Vision + HDR10+)");
+                log_step!("Processing dual format content (Dolby Vision + HDR10+)");
                 // Extract both DV and HDR10+ metadata for dual format
                 extracted.dolby_vision = self
                     .extract_dolby_vision_metadata(&input_path, dv_info)
                     .await?;
+                self.require_dolby_vision(dv_info, &extracted)?;
                 extracted.hdr10_plus = self
                     .extract_hdr10plus_metadata(&input_path, hdr_analysis)
                     .await?;
+                self.require_hdr10plus(hdr_analysis, &extracted)?;
             }
             ContentEncodingApproach::HDR(hdr_result) => {
                 // Check if this is HDR10+ content
                 if hdr_result.metadata.format == crate::hdr::types::HdrFormat::HDR10Plus {
-                    info!("Processing HDR10+ content");
+                    log_step!("Processing HDR10+ content");
                     extracted.hdr10_plus = self
                         .extract_hdr10plus_metadata(&input_path, hdr_analysis)
                         .await?;
+                    self.require_hdr10plus(hdr_analysis, &extracted)?;
                 } else {
-                    info!("Processing standard HDR10 content (no external tools needed)");
+                    log_step!("Processing standard HDR10 content (no external tools needed)");
                 }
             }
             ContentEncodingApproach::SDR => {
-                info!("Processing SDR content (no metadata extraction needed)");
+                log_step!("Processing SDR content (no metadata extraction needed)");
+            }
+        }
+
+        if let Some(wanted) = self.wanted_metadata(approach, dv_info) {
+            if !self.delivers(wanted, &extracted) {
+                self.degrade(wanted, &mut extracted)?;
             }
         }
 
         if extracted.has_metadata() {
-            info!("Metadata extraction phase completed - external metadata ready for encoding");
+            log_stage!(
+                "Metadata extraction phase completed - external metadata ready for encoding"
+            );
         } else {
-            info!(
+            log_step!(
                 "No external metadata extracted - encoding will use x265 built-in parameters only"
             );
         }
@@ -253,13 +351,121 @@ impl MetadataWorkflowManager {
         Ok(extracted)
     }
 
+    /// The dynamic metadata an encode with `approach` is meant to carry over.
+    fn wanted_metadata(
+        &self,
+        approach: &ContentEncodingApproach,
+        dv_info: &DolbyVisionInfo,
+    ) -> Option<MetadataFallback> {
+        match approach {
+            ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _)
+                if self.convert_dv_to_hdr10plus =>
+            {
+                Some(MetadataFallback::Hdr10Plus)
+            }
+            ContentEncodingApproach::DolbyVision(_)
+            | ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => {
+                dv_info.rpu_present.then_some(MetadataFallback::DolbyVision)
+            }
+            ContentEncodingApproach::HDR(hdr_result)
+                if hdr_result.metadata.format == crate::hdr::types::HdrFormat::HDR10Plus =>
+            {
+                Some(MetadataFallback::Hdr10Plus)
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `extracted` carries the `wanted` metadata (for Dolby Vision
+    /// with `convert_to_hdr10plus`, the HDR10+ derived from the RPU).
+    fn delivers(&self, wanted: MetadataFallback, extracted: &ExtractedMetadata) -> bool {
+        match wanted {
+            MetadataFallback::DolbyVision => {
+                extracted.dolby_vision.is_some()
+                    || (self.convert_dv_to_hdr10plus && extracted.hdr10_plus.is_some())
+            }
+            MetadataFallback::Hdr10Plus => extracted.hdr10_plus.is_some(),
+            _ => true,
+        }
+    }
+
+    /// Degrades `extracted` to the next deliverable step of
+    /// `analysis.fallback_chain` after the `lost` metadata could not be
+    /// carried over. Fails the job when the chain says so or runs out.
+    fn degrade(&self, lost: MetadataFallback, extracted: &mut ExtractedMetadata) -> Result<()> {
+        let Some(step) = next_fallback(
+            &self.fallback_chain,
+            lost,
+            extracted.hdr10_plus.is_some(),
+            false,
+        ) else {
+            let message = format!(
+                "{} metadata of the source could not be carried over and \
+                 analysis.fallback_chain allows no fallback",
+                lost
+            );
+            return Err(match lost {
+                MetadataFallback::DolbyVision => Error::dolby_vision(message),
+                _ => Error::tool(message),
+            });
+        };
+        match step {
+            MetadataFallback::Hdr10 => extracted.hdr10_plus = None,
+            MetadataFallback::Sdr => {
+                self.discard_dolby_vision(extracted);
+                extracted.hdr10_plus = None;
+            }
+            _ => {}
+        }
+        log_step!(
+            "{} not available, falling back to {} (analysis.fallback_chain)",
+            lost,
+            step
+        );
+        extracted.fallback = Some(step);
+        Ok(())
+    }
+
+    /// In strict mode, a Dolby Vision source whose RPU was not extracted
+    /// (tool missing or failed) fails the job.
+    fn require_dolby_vision(
+        &self,
+        dv_info: &DolbyVisionInfo,
+        extracted: &ExtractedMetadata,
+    ) -> Result<()> {
+        if self.strict && dv_info.rpu_present && extracted.dolby_vision.is_none() {
+            return Err(Error::dolby_vision(
+                "the source's RPU was not extracted (--strict-metadata)",
+            ));
+        }
+        Ok(())
+    }
+
+    /// In strict mode, an HDR10+ source whose dynamic metadata was not
+    /// extracted fails the job.
+    fn require_hdr10plus(
+        &self,
+        hdr_analysis: &HdrAnalysisResult,
+        extracted: &ExtractedMetadata,
+    ) -> Result<()> {
+        if self.strict
+            && hdr_analysis.metadata.format == crate::hdr::types::HdrFormat::HDR10Plus
+            && extracted.hdr10_plus.is_none()
+        {
+            return Err(Error::tool(
+                "HDR10+ dynamic metadata of the source was not extracted (--strict-metadata)",
+            ));
+        }
+        Ok(())
+    }
+
     async fn extract_dolby_vision_metadata<P: AsRef<Path>>(
         &self,
         input_path: P,
         dv_info: &DolbyVisionInfo,
     ) -> Result<Option<RpuMetadata>> {
         if !self.tools_available.dovi_tool {
-            info!("Skipping Dolby Vision RPU extraction - dovi_tool not available");
+            log_step!("Skipping Dolby Vision RPU extraction - dovi_tool not available");
             info!("   Encoding will continue with HDR10 fallback parameters");
             return Ok(None);
         }
@@ -274,13 +480,13 @@ impl MetadataWorkflowManager {
             return Ok(None);
         };
 
-        info!("Extracting Dolby Vision RPU metadata using dovi_tool...");
+        log_step!("Extracting Dolby Vision RPU metadata using dovi_tool...");
         info!("   Profile: {}", dv_info.profile.as_str());
 
         match manager.extract_rpu(&input_path, dv_info).await {
             Ok(metadata) => {
                 if let Some(ref meta) = metadata {
-                    info!("Dolby Vision RPU extraction successful!");
+                    log_step!("Dolby Vision RPU extraction successful!");
                     info!(
                         "   Profile: {}, File: {}, Size: {} bytes",
                         meta.profile.as_str(),
@@ -290,8 +496,16 @@ impl MetadataWorkflowManager {
                 }
                 Ok(metadata)
             }
+            Err(e @ Error::Cancelled(_)) => Err(e),
+            Err(e) if !self.dv_fallback_to_hdr10 => Err(Error::dolby_vision(format!(
+                "RPU extraction failed and analysis.dolby_vision.fallback_to_hdr10 is off: {}",
+                e
+            ))),
             Err(e) => {
-                warn!("Dolby Vision RPU extraction failed:");
+                match e.tool_failure_kind() {
+                    Some(kind) => warn!("Dolby Vision RPU extraction failed (dovi_tool {}):", kind),
+                    None => warn!("Dolby Vision RPU extraction failed:"),
+                }
 
                 // Log the detailed error message with tool-specific information
                 let error_details = e.to_string();
@@ -306,13 +520,163 @@ impl MetadataWorkflowManager {
         }
     }
 
+    /// Drops the extracted RPU so the output is plain HDR10 from the base layer.
+    pub fn discard_dolby_vision(&self, extracted: &mut ExtractedMetadata) {
+        if let (Some(rpu), Some(manager)) = (extracted.dolby_vision.take(), &self.rpu_manager) {
+            manager.cleanup_rpu(&rpu);
+        }
+    }
+
+    /// Converts an extracted profile 5 RPU to profile 8.1 to go with a base
+    /// layer reshaped to HDR10. If that fails the RPU is dropped, as a profile 5
+    /// RPU would describe the IPTPQc2 signal the encode no longer has; in
+    /// strict mode the failure is returned instead.
+    pub async fn convert_profile5_rpu(&self, extracted: &mut ExtractedMetadata) -> Result<()> {
+        let (Some(rpu), Some(manager)) = (extracted.dolby_vision.as_mut(), &self.rpu_manager)
+        else {
+            return Ok(());
+        };
+        if rpu.profile != DolbyVisionProfile::Profile5 {
+            return Ok(());
+        }
+
+        if let Err(e) = manager.convert_profile5_to_81(rpu).await {
+            if self.strict {
+                return Err(Error::dolby_vision(format!(
+                    "profile 5 RPU conversion failed (--strict-metadata): {}",
+                    e
+                )));
+            }
+            warn!("Could not convert the Dolby Vision profile 5 RPU: {}", e);
+            self.discard_dolby_vision(extracted);
+            self.degrade(MetadataFallback::DolbyVision, extracted)?;
+        }
+        Ok(())
+    }
+
+    /// Re-windows the extracted dynamic metadata to the frames `frames` of a
+    /// `source_frames`-frame source, for an encode of only that range: the
+    /// RPU loses the frames outside it (`dovi_tool editor`) and the HDR10+
+    /// metadata is cut and renumbered. Metadata that cannot be trimmed is
+    /// dropped through `analysis.fallback_chain` instead of being injected
+    /// out of sync; in strict mode the failure is returned.
+    pub async fn trim_metadata(
+        &self,
+        extracted: &mut ExtractedMetadata,
+        frames: Range<u32>,
+        source_frames: u32,
+    ) -> Result<()> {
+        if frames.start == 0 && frames.end >= source_frames {
+            return Ok(());
+        }
+        if frames.start >= frames.end.min(source_frames) {
+            return Err(Error::validation(format!(
+                "Frame range {}-{} is outside the source's {} frames",
+                frames.start, frames.end, source_frames
+            )));
+        }
+
+        if let (Some(rpu), Some(manager)) = (extracted.dolby_vision.as_mut(), &self.rpu_manager) {
+            let rpu_frames = u64::from(frames.start)..u64::from(frames.end);
+            if let Err(e) = manager
+                .trim(rpu, rpu_frames, u64::from(source_frames))
+                .await
+            {
+                if self.strict {
+                    return Err(Error::dolby_vision(format!(
+                        "RPU trimming failed (--strict-metadata): {}",
+                        e
+                    )));
+                }
+                warn!("Could not trim the Dolby Vision RPU: {}", e);
+                self.discard_dolby_vision(extracted);
+                self.degrade(MetadataFallback::DolbyVision, extracted)?;
+            }
+        }
+
+        if let Some(hdr10plus) = extracted.hdr10_plus.as_mut() {
+            if let Err(e) = trim_hdr10plus(hdr10plus, frames).await {
+                if self.strict {
+                    return Err(Error::tool(format!(
+                        "HDR10+ metadata trimming failed (--strict-metadata): {}",
+                        e
+                    )));
+                }
+                warn!("Could not trim the HDR10+ metadata: {}", e);
+                extracted.hdr10_plus = None;
+                self.degrade(MetadataFallback::Hdr10Plus, extracted)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adjusts the extracted RPU to a cropped picture. Returns `false` if the RPU
+    /// could not be edited, in which case the caller must not crop.
+    pub async fn adjust_rpu_for_crop(&self, extracted: &mut ExtractedMetadata) -> bool {
+        let (Some(rpu), Some(manager)) = (extracted.dolby_vision.as_mut(), &self.rpu_manager)
+        else {
+            return true;
+        };
+
+        match manager.reset_active_area(rpu).await {
+            Ok(()) => true,
+            Err(e) => {
+                warn!(
+                    "Could not update Dolby Vision L5 metadata for cropping: {}",
+                    e
+                );
+                false
+            }
+        }
+    }
+
+    /// Replaces an extracted Profile 8.1 RPU with HDR10+ metadata derived from its
+    /// L1 values. On failure the RPU is kept and the normal DV path continues.
+    async fn convert_dolby_vision_to_hdr10plus(&self, extracted: &mut ExtractedMetadata) {
+        let Some(rpu) = extracted.dolby_vision.as_ref() else {
+            return;
+        };
+        if rpu.profile != DolbyVisionProfile::Profile81 {
+            warn!(
+                "HDR10+ conversion only supports Dolby Vision Profile 8.1 (source is {}), keeping Dolby Vision",
+                rpu.profile.as_str()
+            );
+            return;
+        }
+        let Some(ref manager) = self.rpu_manager else {
+            return;
+        };
+
+        info!("Converting Dolby Vision to HDR10+ (dolby_vision.convert_to_hdr10plus)");
+        warn!("   HDR10+ derived from Dolby Vision is an approximation:");
+        warn!("   - only L1 (min/avg/max luminance per shot) is carried over");
+        warn!("   - L2/L8 trims and the DV display mapping are lost");
+        warn!("   - luminance distributions are interpolated, no Bezier tone-mapping curves");
+        warn!("   Tone mapping on HDR10+ displays will differ from the Dolby Vision grade");
+
+        match manager.convert_to_hdr10plus(rpu).await {
+            Ok(result) => {
+                manager.cleanup_rpu(rpu);
+                extracted.dolby_vision = None;
+                extracted.hdr10_plus = Some(result);
+                info!(
+                    "   Output will be HDR10+ (via x265 --dhdr10-info), without Dolby Vision RPU"
+                );
+            }
+            Err(e) => {
+                warn!("Dolby Vision to HDR10+ conversion failed: {}", e);
+                warn!("   Keeping Dolby Vision RPU injection instead");
+            }
+        }
+    }
+
     async fn extract_hdr10plus_metadata<P: AsRef<Path>>(
         &self,
         input_path: P,
         hdr_analysis: &HdrAnalysisResult,
     ) -> Result<Option<Hdr10PlusProcessingResult>> {
         if !self.tools_available.hdr10plus_tool {
-            info!("Skipping HDR10+ metadata extraction - hdr10plus_tool not available");
+            log_step!("Skipping HDR10+ metadata extraction - hdr10plus_tool not available");
             info!("   Encoding will continue with HDR10 fallback parameters");
             return Ok(None);
         }
@@ -322,7 +686,7 @@ impl MetadataWorkflowManager {
             return Ok(None);
         };
 
-        info!("Extracting HDR10+ dynamic metadata using hdr10plus_tool...");
+        log_step!("Extracting HDR10+ dynamic metadata using hdr10plus_tool...");
 
         match manager
             .extract_hdr10plus_metadata(&input_path, hdr_analysis)
@@ -330,18 +694,29 @@ impl MetadataWorkflowManager {
         {
             Ok(metadata) => {
                 if let Some(ref meta) = metadata {
-                    info!("HDR10+ metadata extraction successful!");
+                    log_step!("HDR10+ metadata extraction successful!");
                     info!(
                         "   Frames: {}, Curves: {}, File: {}",
-                        meta.metadata.get_frame_count(),
+                        meta.summary.frame_count,
                         meta.curve_count,
                         meta.metadata_file.display()
                     );
                 }
                 Ok(metadata)
             }
+            Err(e @ Error::Cancelled(_)) => Err(e),
+            Err(e) if !self.hdr10plus_fallback_to_hdr10 => Err(Error::tool(format!(
+                "HDR10+ extraction failed and analysis.hdr10_plus.fallback_to_hdr10 is off: {}",
+                e
+            ))),
             Err(e) => {
-                warn!("HDR10+ metadata extraction failed:");
+                match e.tool_failure_kind() {
+                    Some(kind) => warn!(
+                        "HDR10+ metadata extraction failed (hdr10plus_tool {}):",
+                        kind
+                    ),
+                    None => warn!("HDR10+ metadata extraction failed:"),
+                }
 
                 // Log the detailed error message with tool-specific information
                 let error_details = e.to_string();
@@ -390,15 +765,30 @@ impl MetadataWorkflowManager {
                     "Added HDR10+ metadata parameter for x265: --dhdr10-info {}",
                     hdr10plus_meta.metadata_file.display()
                 );
+                let summary = &hdr10plus_meta.summary;
+                let profile = summary.detected_profile();
+                if let Some(declared) = summary.declared_profile().filter(|d| *d != profile) {
+                    warn!(
+                        "HDR10+ metadata declares {} but {} of {} frames carry tone mapping curves; treating it as {}",
+                        declared, summary.tone_mapping_frames, summary.frame_count, profile
+                    );
+                }
+                if profile.needs_dhdr10_opt() {
+                    params.push(("dhdr10-opt".to_string(), String::new()));
+                    info!(
+                        "HDR10+ {}: added --dhdr10-opt (SEI on IDR frames and metadata changes only)",
+                        profile
+                    );
+                }
                 debug!(
                     "   Frames with metadata: {}",
-                    hdr10plus_meta.metadata.get_frame_count()
+                    hdr10plus_meta.summary.frame_count
                 );
             }
         }
 
         if !params.is_empty() {
-            info!("External metadata parameters ready for x265 encoding");
+            log_step!("External metadata parameters ready for x265 encoding");
         } else {
             debug!("No external metadata parameters to add - using x265 built-in HDR support only");
         }
@@ -411,61 +801,116 @@ impl MetadataWorkflowManager {
     ///
     /// # Parameters
     /// * `fps` - Framerate of the video, required for proper RPU injection timing
+    /// * `hdr10_fallback_path` - Also keep the encode without the RPU at this path
+    ///   (HDR10-only copy for players that mishandle Dolby Vision)
+    ///
+    /// Returns the Dolby Vision profile of the injected RPU, if one was injected.
     pub async fn inject_metadata<P: AsRef<Path>>(
         &self,
         encoded_path: P,
         final_output_path: P,
         extracted: &ExtractedMetadata,
         fps: f32,
-    ) -> Result<()> {
+        hdr10_fallback_path: Option<&Path>,
+    ) -> Result<Option<DolbyVisionProfile>> {
         // If no metadata was extracted, just rename/move the file
         if !extracted.has_metadata() {
             if encoded_path.as_ref() != final_output_path.as_ref() {
                 tokio::fs::rename(&encoded_path, &final_output_path).await?;
                 debug!("Moved encoded file to final location (no metadata injection needed)");
             }
-            return Ok(());
+            return Ok(None);
         }
 
-        info!("Starting post-encoding metadata injection phase");
+        log_stage!("Starting post-encoding metadata injection phase");
 
         // Handle Dolby Vision RPU injection first (most critical)
         if let Some(ref dv_meta) = extracted.dolby_vision {
             if dv_meta.extracted_successfully && self.tools_available.dovi_tool {
-                info!("Injecting Dolby Vision RPU metadata using dovi_tool...");
+                log_step!("Injecting Dolby Vision RPU metadata using dovi_tool...");
                 info!("   Video framerate: {} fps (required for timing synchronization)", fps);
                 if let Some(ref manager) = self.rpu_manager {
+                    // RPU injection consumes the encoded file, so split off the HDR10 copy first
+                    if let Some(fallback_path) = hdr10_fallback_path {
+                        if let Err(e) = self
+                            .write_hdr10_fallback(encoded_path.as_ref(), fallback_path)
+                            .await
+                        {
+                            if self.strict {
+                                return Err(e);
+                            }
+                            warn!("HDR10 fallback not written: {}", e);
+                        }
+                    }
+
                     match manager
                         .inject_rpu(&encoded_path, dv_meta, &final_output_path, fps)
                         .await
                     {
                         Ok(_) => {
-                            info!("Dolby Vision RPU injection successful!");
+                            log_step!("Dolby Vision RPU injection successful!");
                             info!("   Final file: {}", final_output_path.as_ref().display());
                             info!("   Profile: {}", dv_meta.profile.as_str());
 
                             // If we also had HDR10+ metadata, log that it was included during encoding
                             if let Some(ref hdr10plus_meta) = extracted.hdr10_plus {
                                 if hdr10plus_meta.extraction_successful {
-                                    info!("HDR10+ metadata was included during x265 encoding (--dhdr10-info)");
+                                    log_step!("HDR10+ metadata was included during x265 encoding (--dhdr10-info)");
                                     info!("   This is a dual-format Dolby Vision + HDR10+ file!");
                                 }
                             }
-                            return Ok(());
+                            return Ok(Some(dv_meta.profile));
+                        }
+                        Err(e)
+                            if self.strict
+                                || next_fallback(
+                                    &self.fallback_chain,
+                                    MetadataFallback::DolbyVision,
+                                    extracted.hdr10_plus.is_some(),
+                                    true,
+                                )
+                                .is_none() =>
+                        {
+                            for partial in [Some(final_output_path.as_ref()), hdr10_fallback_path]
+                                .into_iter()
+                                .flatten()
+                                .filter(|path| *path != encoded_path.as_ref())
+                            {
+                                let _ = tokio::fs::remove_file(partial).await;
+                            }
+                            let reason = if self.strict {
+                                "--strict-metadata"
+                            } else {
+                                "no fallback in analysis.fallback_chain"
+                            };
+                            return Err(Error::dolby_vision(format!(
+                                "RPU injection failed ({}): {}",
+                                reason, e
+                            )));
                         }
                         Err(e) => {
                             warn!("Dolby Vision RPU injection failed: {}", e);
                             warn!("   Falling back to encoded file without RPU injection");
+                            // The main output is HDR10 now; a second identical file is pointless
+                            if let Some(fallback_path) = hdr10_fallback_path {
+                                let _ = tokio::fs::remove_file(fallback_path).await;
+                            }
                         }
                     }
                 }
             }
         }
 
+        if self.strict && extracted.dolby_vision.is_some() {
+            return Err(Error::dolby_vision(
+                "the extracted RPU could not be injected (--strict-metadata)",
+            ));
+        }
+
         // If we reach here, either DV injection failed or there was only HDR10+ metadata
         if let Some(ref hdr10plus_meta) = extracted.hdr10_plus {
             if hdr10plus_meta.extraction_successful {
-                info!("HDR10+ metadata was successfully included during x265 encoding");
+                log_step!("HDR10+ metadata was successfully included during x265 encoding");
                 info!("   No post-encoding injection needed for HDR10+ (handled by --dhdr10-info)");
             }
         }
@@ -479,6 +924,33 @@ impl MetadataWorkflowManager {
             );
         }
 
+        Ok(None)
+    }
+
+    /// Stream-copies the encode (which has no RPU yet) into the HDR10 fallback file.
+    async fn write_hdr10_fallback(&self, encoded_path: &Path, fallback_path: &Path) -> Result<()> {
+        info!(
+            "Writing HDR10 fallback copy (no Dolby Vision RPU): {}",
+            fallback_path.display()
+        );
+
+        let output = tokio::process::Command::new(&self.ffmpeg_path)
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+            .arg(encoded_path)
+            .args(["-map", "0", "-c", "copy"])
+            .arg(fallback_path)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let _ = tokio::fs::remove_file(fallback_path).await;
+            return Err(Error::ffmpeg(format!(
+                "Failed to write HDR10 fallback {}: {}",
+                fallback_path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
         Ok(())
     }
 
@@ -496,12 +968,19 @@ impl MetadataWorkflowManager {
             .is_some_and(|dv| dv.extracted_successfully && self.tools_available.dovi_tool)
     }
 
-    /// Generate a temporary output path for post-processing alongside the source file
+    /// Path of the HDR10-only companion file: `movie.mkv` -> `movie.hdr10.mkv`
+    pub fn get_hdr10_fallback_path<P: AsRef<Path>>(&self, final_path: P) -> PathBuf {
+        hdr10_fallback_path(final_path.as_ref())
+    }
+
+    /// Temporary output path for post-processing next to the final file,
+    /// namespaced with the job id: `temp_encode_<job id>_<filename>`
     pub fn get_temp_output_path<P: AsRef<Path>>(&self, final_path: P) -> PathBuf {
         let final_path = final_path.as_ref();
 
         if let Some(filename) = final_path.file_name() {
-            let temp_filename = format!("temp_encode_{}", filename.to_string_lossy());
+            let temp_filename =
+                format!("temp_encode_{}_{}", self.job_id, filename.to_string_lossy());
             if let Some(parent) = final_path.parent() {
                 parent.join(temp_filename)
             } else {
@@ -511,7 +990,7 @@ impl MetadataWorkflowManager {
             final_path
                 .parent()
                 .unwrap_or(Path::new("."))
-                .join("temp_encode_output.mkv")
+                .join(format!("temp_encode_{}_output.mkv", self.job_id))
         }
     }
 
@@ -534,3 +1013,168 @@ impl MetadataWorkflowManager {
         Ok(())
     }
 }
+
+/// Rewrites the HDR10+ JSON of `result` with only the frames `frames`.
+async fn trim_hdr10plus(result: &mut Hdr10PlusProcessingResult, frames: Range<u32>) -> Result<()> {
+    let mut metadata = match result.metadata.take() {
+        Some(metadata) => metadata,
+        None => Hdr10PlusMetadata::from_json_file(&result.metadata_file).await?,
+    };
+    metadata.trim(frames.clone());
+    metadata.validate()?;
+    metadata.to_json_file(&result.metadata_file).await?;
+
+    let mut trimmed = Hdr10PlusProcessingResult::new(
+        result.metadata_file.clone(),
+        metadata,
+        result.extraction_successful,
+    );
+    trimmed.file_size = std::fs::metadata(&result.metadata_file)
+        .ok()
+        .map(|file| file.len());
+    info!(
+        "Trimmed the HDR10+ metadata to frames {}-{} ({} frames)",
+        frames.start,
+        frames.end.saturating_sub(1),
+        trimmed.summary.frame_count
+    );
+    *result = trimmed;
+    Ok(())
+}
+
+/// The first step of `chain` after the `lost` metadata that can be
+/// delivered: only steps ranked below `lost` count, HDR10+ needs extracted
+/// HDR10+ metadata and tone mapping to SDR is no option once the encode
+/// has run. `None` means the job fails.
+fn next_fallback(
+    chain: &[MetadataFallback],
+    lost: MetadataFallback,
+    has_hdr10plus: bool,
+    encoded: bool,
+) -> Option<MetadataFallback> {
+    chain
+        .iter()
+        .copied()
+        .filter(|step| step.rank() > lost.rank())
+        .find(|step| match step {
+            MetadataFallback::Hdr10Plus => has_hdr10plus,
+            MetadataFallback::Sdr => !encoded,
+            _ => true,
+        })
+        .filter(|step| *step != MetadataFallback::Fail)
+}
+
+fn hdr10_fallback_path(final_path: &Path) -> PathBuf {
+    let stem = final_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    let extension = final_path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mkv".to_string());
+    final_path.with_file_name(format!("{}.hdr10.{}", stem, extension))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hdr10_fallback_path() {
+        assert_eq!(
+            hdr10_fallback_path(Path::new("/media/out/movie.mkv")),
+            PathBuf::from("/media/out/movie.hdr10.mkv")
+        );
+        assert_eq!(
+            hdr10_fallback_path(Path::new("movie")),
+            PathBuf::from("movie.hdr10.mkv")
+        );
+    }
+
+    #[test]
+    fn test_fallback_chain() {
+        use MetadataFallback::*;
+        let default_chain = [DolbyVision, Hdr10Plus, Hdr10];
+        assert_eq!(
+            next_fallback(&default_chain, DolbyVision, true, false),
+            Some(Hdr10Plus)
+        );
+        assert_eq!(
+            next_fallback(&default_chain, DolbyVision, false, false),
+            Some(Hdr10)
+        );
+        assert_eq!(
+            next_fallback(&default_chain, Hdr10Plus, true, false),
+            Some(Hdr10)
+        );
+
+        // Steps above the lost metadata are skipped, SDR only before the encode
+        let chain = [Hdr10Plus, Sdr, Fail];
+        assert_eq!(next_fallback(&chain, DolbyVision, false, false), Some(Sdr));
+        assert_eq!(next_fallback(&chain, DolbyVision, false, true), None);
+        assert_eq!(
+            next_fallback(&[Hdr10, DolbyVision], Hdr10Plus, false, false),
+            Some(Hdr10)
+        );
+        assert_eq!(
+            next_fallback(&[DolbyVision, Fail], DolbyVision, true, false),
+            None
+        );
+        assert_eq!(next_fallback(&[], Hdr10Plus, true, false), None);
+    }
+
+    #[test]
+    fn test_strict_mode_requires_metadata() {
+        let workflow = |strict: bool| MetadataWorkflowManager {
+            rpu_manager: None,
+            hdr10plus_manager: None,
+            ffmpeg_path: "ffmpeg".to_string(),
+            convert_dv_to_hdr10plus: false,
+            dv_fallback_to_hdr10: false,
+            hdr10plus_fallback_to_hdr10: false,
+            fallback_chain: Vec::new(),
+            strict,
+            temp_dir: PathBuf::from("/tmp"),
+            job_id: "test".to_string(),
+            tools_available: ToolAvailability {
+                dovi_tool: false,
+                hdr10plus_tool: false,
+            },
+        };
+        let extracted = ExtractedMetadata::none(PathBuf::from("/tmp"));
+        let dv_info = DolbyVisionInfo {
+            rpu_present: true,
+            ..Default::default()
+        };
+        let format = crate::hdr::types::HdrFormat::HDR10Plus;
+        let hdr_analysis = HdrAnalysisResult {
+            metadata: crate::hdr::HdrMetadata {
+                format,
+                ..crate::hdr::HdrMetadataExtractor::get_default_metadata_for_format(format)
+            },
+            confidence_score: 1.0,
+            requires_tone_mapping: false,
+            encoding_complexity: 1.0,
+        };
+
+        assert!(workflow(false)
+            .require_dolby_vision(&dv_info, &extracted)
+            .is_ok());
+        assert!(workflow(false)
+            .require_hdr10plus(&hdr_analysis, &extracted)
+            .is_ok());
+        assert!(workflow(true)
+            .require_dolby_vision(&dv_info, &extracted)
+            .is_err());
+        assert!(workflow(true)
+            .require_hdr10plus(&hdr_analysis, &extracted)
+            .is_err());
+        assert!(workflow(true)
+            .require_dolby_vision(&DolbyVisionInfo::none(), &extracted)
+            .is_ok());
+
+        let lenient = workflow(false).with_strict(true);
+        assert!(!lenient.dv_fallback_to_hdr10 && !lenient.hdr10plus_fallback_to_hdr10);
+    }
+}
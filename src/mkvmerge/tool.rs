@@ -1,5 +1,7 @@
 use crate::config::types::MkvMergeConfig;
-use crate::utils::{Result, ToolRunner};
+use crate::tools::ExternalTool;
+use crate::utils::filesystem::ensure_not_source;
+use crate::utils::{CancelToken, Result, ToolInput, ToolProgress, ToolRunner};
 use std::path::Path;
 use tracing::{debug, info};
 
@@ -15,15 +17,24 @@ impl MkvMergeTool {
                 timeout_seconds: config.timeout_seconds,
                 extract_args: None,
                 inject_args: None,
+                ..Default::default()
             }),
         }
     }
 
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.tool.set_cancel_token(cancel);
+        self
+    }
+
+    /// Reports mkvmerge's progress through `progress`.
+    pub fn with_progress(mut self, progress: ToolProgress) -> Self {
+        self.tool.set_progress(progress);
+        self
+    }
+
     pub async fn check_availability(&self) -> Result<bool> {
-        match self.tool.check_availability("--version", "mkvmerge").await {
-            Ok(_) => Ok(true),
-            Err(_) => Ok(false),
-        }
+        Ok(crate::tools::probe(self).await.is_usable())
     }
 
     /// Remux raw HEVC+RPU bitstream with streams from original MKV
@@ -43,6 +54,7 @@ impl MkvMergeTool {
         output_mkv: P3,
         fps: f32,
     ) -> Result<()> {
+        ensure_not_source(&source_mkv, &output_mkv)?;
         let hevc_path = hevc_file.as_ref().to_string_lossy();
         let source_path = source_mkv.as_ref().to_string_lossy();
         let output_path = output_mkv.as_ref().to_string_lossy();
@@ -78,10 +90,74 @@ impl MkvMergeTool {
         debug!("  mkvmerge command: {} {}", self.tool.config().path, args.join(" "));
 
         self.tool
-            .run_with_custom_args(&args, &None, Some(output_mkv))
+            .run_monitored(
+                &args,
+                ToolInput::File(hevc_file.as_ref()),
+                output_mkv.as_ref(),
+                "Remuxing with mkvmerge",
+                true,
+            )
             .await?;
 
         info!("Successfully remuxed HEVC+RPU with all streams!");
         Ok(())
     }
+
+    /// Mux FFmpeg's raw HEVC encode with the file holding the other streams,
+    /// as the final mux step for `streams.muxer: mkvmerge`
+    ///
+    /// The video comes first, followed by every track, chapter, tag and
+    /// attachment of `streams_mkv` as is; mkvmerge writes fresh track
+    /// statistics tags and keeps the default/forced flags.
+    pub async fn mux_hevc_with_streams<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
+        &self,
+        hevc_file: P1,
+        streams_mkv: P2,
+        output_mkv: P3,
+        fps: f32,
+    ) -> Result<()> {
+        let hevc_path = hevc_file.as_ref().to_string_lossy();
+        let streams_path = streams_mkv.as_ref().to_string_lossy();
+        let output_path = output_mkv.as_ref().to_string_lossy();
+
+        info!(
+            "Muxing with mkvmerge: {} + {} -> {}",
+            hevc_path, streams_path, output_path
+        );
+
+        let args = vec![
+            "-o".to_string(),
+            output_path.to_string(),
+            "--default-duration".to_string(),
+            format!("0:{}fps", fps),
+            hevc_path.to_string(),
+            streams_path.to_string(),
+        ];
+        debug!(
+            "  mkvmerge command: {} {}",
+            self.tool.config().path,
+            args.join(" ")
+        );
+
+        self.tool
+            .run_monitored(
+                &args,
+                ToolInput::File(hevc_file.as_ref()),
+                output_mkv.as_ref(),
+                "Muxing with mkvmerge",
+                true,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl ExternalTool for MkvMergeTool {
+    fn name(&self) -> &str {
+        "mkvmerge"
+    }
+
+    fn path(&self) -> &str {
+        &self.tool.config().path
+    }
 }
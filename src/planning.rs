@@ -0,0 +1,452 @@
+//! Time-budgeted batches (`encode --time-budget 8h`): every file gets the
+//! slowest x265 preset of `planning.presets` that still lets the whole batch
+//! finish within the budget.
+//!
+//! A file's speed is estimated from a short probe encode at [`PROBE_PRESET`],
+//! scaled per preset by how fast earlier planned encodes ran compared with
+//! their probe (the encode history). Presets without history fall back to
+//! x265's typical relative speeds.
+
+use crate::analysis::BlackFrameScanner;
+use crate::config::{BlackFrameConfig, Config, PlanningConfig};
+use crate::progress::format_duration;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// Preset of the probe encode.
+pub const PROBE_PRESET: &str = "superfast";
+
+/// x265 presets with their typical speed relative to `medium`.
+const PRESET_SPEEDS: [(&str, f64); 10] = [
+    ("ultrafast", 8.0),
+    ("superfast", 6.0),
+    ("veryfast", 3.5),
+    ("faster", 2.2),
+    ("fast", 1.6),
+    ("medium", 1.0),
+    ("slow", 0.5),
+    ("slower", 0.18),
+    ("veryslow", 0.08),
+    ("placebo", 0.03),
+];
+
+/// Speed of `preset` relative to `medium`; `None` for unknown presets.
+pub fn typical_speed(preset: &str) -> Option<f64> {
+    PRESET_SPEEDS
+        .iter()
+        .find(|(name, _)| *name == preset)
+        .map(|(_, speed)| *speed)
+}
+
+/// Parses a budget such as `8h`, `90m` or `1h30m`.
+pub fn parse_budget(text: &str) -> Result<Duration> {
+    let invalid = || {
+        Error::validation(format!(
+            "Invalid time budget '{}' (expected e.g. 8h, 90m or 1h30m)",
+            text
+        ))
+    };
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in text.trim().chars() {
+        match c {
+            '0'..='9' | '.' => number.push(c),
+            'h' | 'm' | 's' => {
+                let value: f64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                seconds += value
+                    * match c {
+                        'h' => 3600.0,
+                        'm' => 60.0,
+                        _ => 1.0,
+                    };
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    if !number.is_empty() || seconds <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// One planned encode as it turned out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub preset: String,
+    /// Frames per second of the probe encode
+    pub probe_fps: f64,
+    /// Frames per second of the whole run
+    pub fps: f64,
+    /// Unix time of the encode
+    pub recorded_at: u64,
+}
+
+impl HistoryRecord {
+    pub fn new(preset: &str, probe_fps: f64, fps: f64) -> Self {
+        Self {
+            preset: preset.to_string(),
+            probe_fps,
+            fps,
+            recorded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Past planned encodes, one JSON record per line.
+#[derive(Debug)]
+pub struct EncodeHistory {
+    path: PathBuf,
+    records: Vec<HistoryRecord>,
+}
+
+impl EncodeHistory {
+    /// Default location: `<data dir>/ven/encode_history.jsonl`.
+    pub fn default_path() -> Result<PathBuf> {
+        dirs::data_dir()
+            .map(|dir| dir.join("ven").join("encode_history.jsonl"))
+            .ok_or_else(|| Error::validation("No data directory available for the encode history"))
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    /// Reads the history at `path`; unreadable lines are skipped.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let records = match std::fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(record) => Some(record),
+                    Err(e) => {
+                        debug!("Skipping unreadable history line: {}", e);
+                        None
+                    }
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, records })
+    }
+
+    pub fn records(&self) -> &[HistoryRecord] {
+        &self.records
+    }
+
+    /// Appends `record` to the file.
+    pub fn record(&mut self, record: HistoryRecord) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&record)?)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// How fast `preset` ran compared with the probe: the median over the
+    /// history, or else the typical preset speeds.
+    fn speed_ratio(&self, preset: &str) -> f64 {
+        let mut ratios: Vec<f64> = self
+            .records
+            .iter()
+            .filter(|record| record.preset == preset && record.probe_fps > 0.0)
+            .map(|record| record.fps / record.probe_fps)
+            .collect();
+        if ratios.is_empty() {
+            let probe = typical_speed(PROBE_PRESET).unwrap_or(1.0);
+            return typical_speed(preset).unwrap_or(1.0) / probe;
+        }
+        ratios.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        ratios[ratios.len() / 2]
+    }
+}
+
+/// A file to plan: how much there is to encode and how fast the probe ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanInput {
+    pub path: PathBuf,
+    pub frames: f64,
+    pub probe_fps: f64,
+}
+
+/// The preset chosen for one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub preset: String,
+    pub estimate: Duration,
+    pub probe_fps: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetPlan {
+    pub budget: Duration,
+    pub files: Vec<PlannedFile>,
+}
+
+impl BudgetPlan {
+    /// Estimated time of all planned encodes.
+    pub fn total(&self) -> Duration {
+        self.files.iter().map(|file| file.estimate).sum()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&PlannedFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    /// Logs the preset of every file and the estimated total.
+    pub fn log(&self) {
+        for file in &self.files {
+            info!(
+                "Planned {}: preset {} (about {})",
+                file.path.display(),
+                file.preset,
+                format_duration(file.estimate)
+            );
+        }
+        let total = self.total();
+        if total > self.budget {
+            warn!(
+                "Even the fastest presets need about {}, over the {} budget",
+                format_duration(total),
+                format_duration(self.budget)
+            );
+        } else {
+            info!(
+                "Time budget {}: about {} planned",
+                format_duration(self.budget),
+                format_duration(total)
+            );
+        }
+    }
+}
+
+/// Picks a preset out of `presets` (slowest first) for every input. All
+/// start at the fastest; then, as long as the budget allows, the file on the
+/// fastest preset moves one preset slower (the one costing the least extra
+/// time first), so the batch slows down evenly instead of spending the
+/// budget on one file.
+pub fn plan_presets(
+    inputs: &[PlanInput],
+    presets: &[String],
+    history: &EncodeHistory,
+    budget: Duration,
+) -> BudgetPlan {
+    let ratios: Vec<f64> = presets
+        .iter()
+        .map(|preset| history.speed_ratio(preset))
+        .collect();
+    let seconds = |input: &PlanInput, level: usize| {
+        input.frames / (input.probe_fps * ratios[level]).max(f64::MIN_POSITIVE)
+    };
+
+    let fastest = presets.len().saturating_sub(1);
+    let mut levels = vec![fastest; inputs.len()];
+    let mut total: f64 = inputs.iter().map(|input| seconds(input, fastest)).sum();
+    let budget_seconds = budget.as_secs_f64();
+
+    loop {
+        let mut candidates: Vec<(usize, usize, f64)> = levels
+            .iter()
+            .enumerate()
+            .filter(|(_, &level)| level > 0)
+            .map(|(index, &level)| {
+                let input = &inputs[index];
+                let extra = seconds(input, level - 1) - seconds(input, level);
+                (index, level, extra)
+            })
+            .collect();
+        candidates.sort_by(|a, b| {
+            b.1.cmp(&a.1)
+                .then(a.2.partial_cmp(&b.2).unwrap_or(std::cmp::Ordering::Equal))
+        });
+        let Some(&(index, _, extra)) = candidates
+            .iter()
+            .find(|(_, _, extra)| total + extra <= budget_seconds)
+        else {
+            break;
+        };
+        levels[index] -= 1;
+        total += extra;
+    }
+
+    let files = inputs
+        .iter()
+        .zip(&levels)
+        .map(|(input, &level)| PlannedFile {
+            path: input.path.clone(),
+            preset: presets[level].clone(),
+            estimate: Duration::from_secs_f64(seconds(input, level)),
+            probe_fps: input.probe_fps,
+        })
+        .collect();
+    BudgetPlan { budget, files }
+}
+
+/// FFmpeg arguments encoding `frames` frames of the video stream `ffmpeg`
+/// selects from `start` at [`PROBE_PRESET`], discarding the output.
+fn probe_args(ffmpeg: &FfmpegWrapper, input: &Path, start: f64, frames: u64) -> Vec<String> {
+    let mut args: Vec<String> = ["-loglevel", "error", "-hide_banner", "-nostdin"]
+        .map(String::from)
+        .to_vec();
+    args.extend(ffmpeg.input_options().iter().cloned());
+    args.extend(["-ss".to_string(), start.to_string(), "-i".to_string()]);
+    args.push(input.to_string_lossy().to_string());
+    args.extend(["-map".to_string(), ffmpeg.video_map_specifier()]);
+    args.extend(["-an", "-sn", "-dn", "-frames:v"].map(String::from));
+    args.push(frames.to_string());
+    args.extend(["-c:v", "libx265", "-preset", PROBE_PRESET].map(String::from));
+    args.extend(["-pix_fmt", "yuv420p10le", "-x265-params", "log-level=error"].map(String::from));
+    args.extend(["-f", "null", "-"].map(String::from));
+    args
+}
+
+/// Encodes `planning.probe_seconds` from the middle of `input` (moved off
+/// black frames, which encode unrealistically fast) at [`PROBE_PRESET`] and
+/// returns the frames per second.
+async fn probe_speed(
+    ffmpeg: &FfmpegWrapper,
+    black_frames: &BlackFrameConfig,
+    input: &Path,
+    duration: f64,
+    fps: f64,
+    seconds: f64,
+) -> Result<f64> {
+    let seconds = seconds.min(duration);
+    let middle = ((duration - seconds) / 2.0).max(0.0);
+    let start = BlackFrameScanner::new(ffmpeg, black_frames.clone())
+        .avoid_black(input, &[middle], duration - seconds)
+        .await[0];
+    let frames = ((seconds * fps).ceil() as u64).max(1);
+
+    let started = Instant::now();
+    let output = Command::new(ffmpeg.get_ffmpeg_path())
+        .args(probe_args(ffmpeg, input, start, frames))
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(Error::ffmpeg(format!(
+            "Speed probe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(frames as f64 / started.elapsed().as_secs_f64().max(0.001))
+}
+
+/// Probes every input through `ffmpeg` (which carries `--video-stream` and
+/// `--error-resilience`) and plans presets for them within `budget`. Files
+/// that cannot be probed are left out of the plan and keep their profile's
+/// preset.
+pub async fn plan_time_budget(
+    config: &Config,
+    ffmpeg: &FfmpegWrapper,
+    inputs: &[&Path],
+    budget: Duration,
+    history: &EncodeHistory,
+) -> BudgetPlan {
+    let settings: &PlanningConfig = &config.planning;
+
+    let mut planned = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        info!("Probing encode speed: {}", input.display());
+        let probe = match ffmpeg.get_video_metadata(input).await {
+            Ok(metadata) => {
+                let fps = metadata.fps as f64;
+                probe_speed(
+                    ffmpeg,
+                    &config.analysis.black_frames,
+                    input,
+                    metadata.duration,
+                    fps,
+                    settings.probe_seconds,
+                )
+                .await
+                .map(|probe_fps| PlanInput {
+                    path: input.to_path_buf(),
+                    frames: metadata.duration * fps,
+                    probe_fps,
+                })
+            }
+            Err(e) => Err(e),
+        };
+        match probe {
+            Ok(probe) => planned.push(probe),
+            Err(e) => warn!(
+                "Not planning {} (keeps its profile's preset): {}",
+                input.display(),
+                e
+            ),
+        }
+    }
+    plan_presets(&planned, &settings.presets, history, budget)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_presets_within_budget() {
+        assert_eq!(parse_budget("8h").unwrap(), Duration::from_secs(8 * 3600));
+        assert_eq!(parse_budget("1h30m").unwrap(), Duration::from_secs(5400));
+        assert!(parse_budget("90").is_err());
+        assert!(parse_budget("0m").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut history = EncodeHistory::open(dir.path().join("history.jsonl")).unwrap();
+        // `slow` really ran at a tenth of the probe speed; `medium` has no history
+        history
+            .record(HistoryRecord::new("slow", 60.0, 6.0))
+            .unwrap();
+        let history = EncodeHistory::open(dir.path().join("history.jsonl")).unwrap();
+        assert_eq!(history.records().len(), 1);
+        assert_eq!(history.speed_ratio("slow"), 0.1);
+        assert_eq!(history.speed_ratio("medium"), 1.0 / 6.0);
+
+        let presets: Vec<String> = ["slow", "medium"].iter().map(|p| p.to_string()).collect();
+        // 6000 frames each, probed at 60 fps: 600s at medium, 1000s at slow
+        let inputs: Vec<PlanInput> = ["a.mkv", "b.mkv"]
+            .iter()
+            .map(|name| PlanInput {
+                path: PathBuf::from(name),
+                frames: 6000.0,
+                probe_fps: 60.0,
+            })
+            .collect();
+        let plan = plan_presets(&inputs, &presets, &history, Duration::from_secs(1700));
+        let chosen: Vec<&str> = plan.files.iter().map(|f| f.preset.as_str()).collect();
+        assert_eq!(chosen, ["slow", "medium"]);
+        assert_eq!(plan.total().as_secs_f64().round(), 1600.0);
+
+        let tight = plan_presets(&inputs, &presets, &history, Duration::from_secs(60));
+        assert!(tight.files.iter().all(|file| file.preset == "medium"));
+        assert!(tight.total() > tight.budget);
+        assert_eq!(
+            plan.get(Path::new("b.mkv")).map(|f| f.preset.as_str()),
+            Some("medium")
+        );
+
+        // The probe encodes the stream --video-stream picked
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string())
+            .with_video_stream(Some(1))
+            .with_error_resilience(true);
+        let args = probe_args(&ffmpeg, Path::new("in.mkv"), 600.0, 240).join(" ");
+        assert!(args.contains("-fflags +genpts+discardcorrupt -ss 600 -i in.mkv -map 0:v:1"));
+        assert!(args.contains("-frames:v 240 -c:v libx265"));
+    }
+}
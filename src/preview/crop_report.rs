@@ -0,0 +1,217 @@
+//! Crop report for spot-checking automated crops, written next to the log
+//! when a detected crop is applied: `<output>.crop.jpg` with one row per
+//! crop detection sample (the source frame with the crop outlined, then the
+//! cropped frame) and `<output>.crop.html` showing it along with the
+//! cropdetect values of every sample.
+
+use crate::analysis::{CropAnalysisResult, CropValues};
+use crate::config::Config;
+use crate::encoding::FilterBuilder;
+use crate::utils::{ffmpeg::VideoMetadata, Error, Result};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Width of each half of a report row.
+const TILE_WIDTH: u32 = 640;
+
+/// Size of one half of a report row for a `width`x`height` source.
+fn tile_size(width: u32, height: u32) -> (u32, u32) {
+    let aspect = if width > 0 {
+        height as f64 / width as f64
+    } else {
+        9.0 / 16.0
+    };
+    let tile_height = (((TILE_WIDTH as f64 * aspect) / 2.0).round() as u32).max(1) * 2;
+    (TILE_WIDTH, tile_height)
+}
+
+/// Filtergraph turning every input into a before/after row and stacking the
+/// rows. `prefix` is applied to each input first (tone mapping).
+fn report_filter(inputs: usize, crop: &CropValues, prefix: &str, tile: (u32, u32)) -> String {
+    let (width, height) = tile;
+    let mut graph = String::new();
+    for index in 0..inputs {
+        let _ = write!(
+            graph,
+            "[{i}:v]{prefix}split[b{i}][a{i}];\
+             [b{i}]drawbox=x={x}:y={y}:w={cw}:h={ch}:color=red:t=6,scale={w}:{h}[l{i}];\
+             [a{i}]crop={crop},scale={w}:{h}:force_original_aspect_ratio=decrease:force_divisible_by=2,\
+             pad={w}:{h}:(ow-iw)/2:(oh-ih)/2[r{i}];\
+             [l{i}][r{i}]hstack[row{i}];",
+            i = index,
+            prefix = prefix,
+            x = crop.x,
+            y = crop.y,
+            cw = crop.width,
+            ch = crop.height,
+            crop = crop.to_ffmpeg_string(),
+            w = width,
+            h = height,
+        );
+    }
+    for index in 0..inputs {
+        let _ = write!(graph, "[row{}]", index);
+    }
+    let _ = write!(graph, "concat=n={}:v=1:a=0,tile=1x{}", inputs, inputs);
+    graph
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The HTML page of the report, showing `image_name` below a table of the
+/// samples in the order of the image's rows.
+fn report_html(title: &str, analysis: &CropAnalysisResult, image_name: &str) -> String {
+    let applied = analysis.crop_values.as_ref();
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Crop report: {title}</title>\n\
+         <style>body{{font-family:sans-serif}}table{{border-collapse:collapse}}\
+         td,th{{border:1px solid #999;padding:2px 8px}}.differs{{color:#b00}}</style></head><body>\n\
+         <h1>{title}</h1>\n<p>Applied crop <b>{crop}</b>: {method}, {pixels:.1}% of the picture removed</p>\n\
+         <table>\n<tr><th>Sample</th><th>Time</th><th>cropdetect</th></tr>\n",
+        title = escape_html(title),
+        crop = applied.map_or_else(|| "none".to_string(), CropValues::to_ffmpeg_string),
+        method = escape_html(&analysis.detection_method),
+        pixels = analysis.pixel_change_percent,
+    );
+    for sample in &analysis.sample_results {
+        let detected = sample
+            .crop_values
+            .as_ref()
+            .map_or_else(|| "none".to_string(), CropValues::to_ffmpeg_string);
+        let class = if sample.crop_values.as_ref() == applied {
+            ""
+        } else {
+            " class=\"differs\""
+        };
+        let _ = writeln!(
+            html,
+            "<tr{}><td>{}</td><td>{:.1}s</td><td>{}</td></tr>",
+            class,
+            escape_html(&sample.sample_point),
+            sample.timestamp,
+            detected
+        );
+    }
+    let _ = write!(
+        html,
+        "</table>\n<p>Left: source with the crop outlined. Right: cropped.</p>\n\
+         <img src=\"{}\" alt=\"Source and cropped frame per sample\">\n</body></html>\n",
+        escape_html(image_name)
+    );
+    html
+}
+
+/// Writes the crop report of `analysis` for the encode of `source` to
+/// `output` and returns the files written; nothing when no crop is applied.
+pub async fn write_crop_report(
+    config: &Config,
+    source: &Path,
+    metadata: &VideoMetadata,
+    analysis: &CropAnalysisResult,
+    output: &Path,
+) -> Result<Vec<PathBuf>> {
+    let Some(crop) = &analysis.crop_values else {
+        return Ok(Vec::new());
+    };
+    if analysis.sample_results.is_empty() {
+        return Ok(Vec::new());
+    }
+    let image = output.with_extension("crop.jpg");
+    let page = output.with_extension("crop.html");
+
+    let tonemap = FilterBuilder::new(config)
+        .with_tonemap(metadata.is_hdr)
+        .build()
+        .to_string();
+    let prefix = if tonemap.is_empty() {
+        String::new()
+    } else {
+        format!("{},", tonemap)
+    };
+
+    let mut cmd = Command::new(&config.tools.ffmpeg);
+    cmd.args(["-hide_banner", "-loglevel", "error", "-y"]);
+    for sample in &analysis.sample_results {
+        cmd.args(["-ss", &sample.timestamp.to_string()])
+            .arg("-i")
+            .arg(source);
+    }
+    let result = cmd
+        .arg("-filter_complex")
+        .arg(report_filter(
+            analysis.sample_results.len(),
+            crop,
+            &prefix,
+            tile_size(metadata.width, metadata.height),
+        ))
+        .args(["-frames:v", "1", "-q:v", "4"])
+        .arg(&image)
+        .output()
+        .await?;
+    if !result.status.success() {
+        let _ = tokio::fs::remove_file(&image).await;
+        return Err(Error::ffmpeg(format!(
+            "FFmpeg failed to write the crop report: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+
+    let image_name = image
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let title = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    tokio::fs::write(&page, report_html(&title, analysis, &image_name)).await?;
+    Ok(vec![page, image])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::crop::CropSampleResult;
+
+    #[test]
+    fn test_crop_report() {
+        let crop = CropValues::new(1920, 800, 0, 140);
+        let graph = report_filter(2, &crop, "", tile_size(1920, 1080));
+        assert!(graph.starts_with(
+            "[0:v]split[b0][a0];[b0]drawbox=x=0:y=140:w=1920:h=800:color=red:t=6,scale=640:360[l0];"
+        ));
+        assert!(graph.contains("[a1]crop=1920:800:0:140,scale=640:360:"));
+        assert!(graph.ends_with("[row0][row1]concat=n=2:v=1:a=0,tile=1x2"));
+
+        let sample = |timestamp: f64, crop_values: Option<CropValues>| CropSampleResult {
+            sample_point: format!("{}s", timestamp),
+            timestamp,
+            crop_values,
+            raw_output: String::new(),
+        };
+        let analysis = CropAnalysisResult {
+            crop_values: Some(crop.clone()),
+            detection_method: "frequency_analysis_50%_agreement".to_string(),
+            confidence: 50.0,
+            pixel_change_percent: 25.9,
+            sample_results: vec![
+                sample(60.0, Some(crop)),
+                sample(120.0, Some(CropValues::new(1920, 1040, 0, 20))),
+            ],
+            aspect_variation: 3.8,
+        };
+        let html = report_html("Movie <2024>.mkv", &analysis, "movie.crop.jpg");
+        assert!(html.contains("<h1>Movie &lt;2024&gt;.mkv</h1>"));
+        assert!(html.contains("<tr><td>60s</td><td>60.0s</td><td>1920:800:0:140</td></tr>"));
+        assert!(html.contains(
+            "<tr class=\"differs\"><td>120s</td><td>120.0s</td><td>1920:1040:0:20</td></tr>"
+        ));
+        assert!(html.contains("<img src=\"movie.crop.jpg\""));
+    }
+}
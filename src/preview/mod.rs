@@ -1,12 +1,203 @@
+pub mod crop_report;
+pub mod montage;
+pub mod screenshots;
+pub mod thumbnails;
+
 use crate::{
+    analysis::black::{black_seconds, BlackFrameScanner},
     config::{Config, EncodingProfile, ProfileManager},
+    encoding::FilterBuilder,
     utils::{ffmpeg::VideoMetadata, Error, FfmpegWrapper, Result},
 };
 use std::path::{Path, PathBuf};
 use std::time::Duration;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
+pub use crop_report::write_crop_report;
+pub use montage::{write_montage, MontageRow};
+pub use screenshots::{generate_screenshots, ScreenshotPosition};
+pub use thumbnails::generate_thumbnails;
+
+/// Seconds before the preview start searched for keyframes in accurate seek mode.
+const KEYFRAME_LOOKBACK: f64 = 30.0;
+
+/// Seconds after an image preview timestamp searched for the frame shown there.
+const FRAME_LOOKAHEAD: f64 = 1.0;
+
+/// Slack when selecting a frame by its timestamp; well under one frame
+/// interval, so only rounding of the timestamp is absorbed.
+const FRAME_TOLERANCE: f64 = 0.001;
+
+/// How the preview start is located in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewSeek {
+    /// Seek the input to an earlier keyframe and trim to the start after decoding,
+    /// so open-GOP leading pictures have their references and the preview starts clean
+    #[default]
+    Accurate,
+    /// Seek the input straight to the start; quicker, but open-GOP sources can
+    /// show corrupted frames at the beginning
+    Fast,
+}
+
+impl PreviewSeek {
+    pub fn from_string(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "accurate" => Some(Self::Accurate),
+            "fast" => Some(Self::Fast),
+            _ => None,
+        }
+    }
+}
+
+/// Input seek position and the decoded time discarded after it before the preview starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SeekPlan {
+    input: f64,
+    trim: f64,
+    /// Source timestamp of the exact frame of an image preview. When known,
+    /// timestamps are kept (`-copyts`) and the frame is selected by it, so
+    /// every profile and the source export show the same frame.
+    frame: Option<f64>,
+}
+
+impl SeekPlan {
+    /// Seeks to the keyframe before the last one at or ahead of `start`: that
+    /// GOP's leading pictures then decode from their references, and anything
+    /// shown before `start` is trimmed.
+    fn from_keyframes(keyframes: &[f64], start: f64) -> Self {
+        let mut before: Vec<f64> = keyframes.iter().copied().filter(|&k| k <= start).collect();
+        before.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let input = match before.len() {
+            0 => (start - KEYFRAME_LOOKBACK).max(0.0),
+            1 => before[0],
+            n => before[n - 2],
+        };
+        Self {
+            input,
+            trim: start - input,
+            frame: None,
+        }
+    }
+
+    fn input_args(&self) -> Vec<String> {
+        let mut args = vec!["-ss".to_string(), self.input.to_string()];
+        if self.frame.is_some() {
+            args.push("-copyts".to_string());
+        }
+        args
+    }
+
+    fn output_args(&self) -> Vec<String> {
+        if self.trim > 0.0 && self.frame.is_none() {
+            vec!["-ss".to_string(), self.trim.to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Filter passing only the image preview's frame (and those after it).
+    fn frame_filter(&self) -> Option<String> {
+        self.frame
+            .map(|frame| format!("select=gte(t\\,{:.6})", frame - FRAME_TOLERANCE))
+    }
+}
+
+/// The first of `frames` (timestamps) at or after `timestamp`.
+fn first_frame_at(frames: &[f64], timestamp: f64) -> Option<f64> {
+    frames
+        .iter()
+        .copied()
+        .filter(|&frame| frame >= timestamp - FRAME_TOLERANCE)
+        .min_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// Timestamp of the source frame shown at `timestamp`: ffprobe decodes from
+/// the keyframe before it and lists the frames. `None` if that fails.
+async fn resolve_frame(ffmpeg: &FfmpegWrapper, input: &Path, timestamp: f64) -> Option<f64> {
+    let interval = format!("{}%+{}", timestamp, FRAME_LOOKAHEAD);
+    let output = ffmpeg
+        .run_ffprobe(&[
+            "-v",
+            "error",
+            "-select_streams",
+            &ffmpeg.video_stream_specifier(),
+            "-read_intervals",
+            &interval,
+            "-show_entries",
+            "frame=pts_time",
+            "-of",
+            "csv=p=0",
+            &input.to_string_lossy(),
+        ])
+        .await
+        .map_err(|e| warn!("Frame lookup failed, selecting by seek only: {}", e))
+        .ok()?;
+    let frames: Vec<f64> = output
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').parse::<f64>().ok())
+        .collect();
+    first_frame_at(&frames, timestamp)
+}
+
+/// Decides where FFmpeg seeks `input` to start at `start`. Accurate mode
+/// reads the keyframe timestamps shortly before the start (keyframes only,
+/// so this is cheap) and falls back to a fixed lookback if that fails.
+async fn plan_seek(
+    ffmpeg: &FfmpegWrapper,
+    input: &Path,
+    start: f64,
+    seek: PreviewSeek,
+) -> SeekPlan {
+    if seek == PreviewSeek::Fast || start <= 0.0 {
+        return SeekPlan {
+            input: start,
+            trim: 0.0,
+            frame: None,
+        };
+    }
+
+    let interval = format!("{}%{}", (start - KEYFRAME_LOOKBACK).max(0.0), start + 0.001);
+    let keyframes = match ffmpeg
+        .run_ffprobe(&[
+            "-v",
+            "error",
+            "-select_streams",
+            &ffmpeg.video_stream_specifier(),
+            "-skip_frame",
+            "nokey",
+            "-read_intervals",
+            &interval,
+            "-show_entries",
+            "frame=pts_time",
+            "-of",
+            "csv=p=0",
+            &input.to_string_lossy(),
+        ])
+        .await
+    {
+        Ok(output) => output
+            .lines()
+            .filter_map(|line| line.trim().trim_end_matches(',').parse::<f64>().ok())
+            .collect(),
+        Err(e) => {
+            warn!(
+                "Keyframe lookup failed, seeking {}s back instead: {}",
+                KEYFRAME_LOOKBACK, e
+            );
+            Vec::new()
+        }
+    };
+
+    let plan = SeekPlan::from_keyframes(&keyframes, start);
+    debug!(
+        "Accurate seek: input at {:.3}s, trimming {:.3}s after decoding",
+        plan.input, plan.trim
+    );
+    plan
+}
+
 #[derive(Debug, Clone)]
 pub enum PreviewMode {
     Image { timestamp: f64 },
@@ -17,6 +208,7 @@ pub enum PreviewMode {
 pub struct PreviewConfig {
     pub mode: PreviewMode,
     pub profile_names: Vec<String>,
+    pub seek: PreviewSeek,
 }
 
 #[derive(Debug)]
@@ -35,6 +227,8 @@ pub struct PreviewProcessor<'a> {
     output_dir: PathBuf,
     preview_config: PreviewConfig,
     uuid: String,
+    /// The unencoded source frame of an image preview, for reference
+    source_frame: Option<PathBuf>,
 }
 
 impl<'a> PreviewProcessor<'a> {
@@ -65,10 +259,11 @@ impl<'a> PreviewProcessor<'a> {
             output_dir,
             preview_config,
             uuid,
+            source_frame: None,
         }
     }
 
-    pub async fn generate_previews(&self) -> Result<Vec<PreviewResult>> {
+    pub async fn generate_previews(&mut self) -> Result<Vec<PreviewResult>> {
         info!("Starting preview generation for: {}", self.input_path.display());
         info!("Using UUID: {}", self.uuid);
 
@@ -83,6 +278,18 @@ impl<'a> PreviewProcessor<'a> {
 
         let metadata = self.ffmpeg.get_video_metadata(self.input_path).await?;
         self.validate_preview_parameters(&metadata)?;
+        self.check_black_frames().await;
+        let seek_plan = self.plan_seek().await;
+
+        if matches!(self.preview_config.mode, PreviewMode::Image { .. }) {
+            match self.export_source_frame(&seek_plan, &metadata).await {
+                Ok(path) => {
+                    info!("✓ Source frame: {}", path.display());
+                    self.source_frame = Some(path);
+                }
+                Err(e) => warn!("✗ Failed to export the source frame: {}", e),
+            }
+        }
 
         let mut results = Vec::new();
 
@@ -90,7 +297,10 @@ impl<'a> PreviewProcessor<'a> {
             match self.profile_manager.get_profile(profile_name) {
                 Some(profile) => {
                     info!("Generating preview with profile: {}", profile_name);
-                    match self.generate_single_preview(profile, &metadata).await {
+                    match self
+                        .generate_single_preview(profile, &metadata, &seek_plan)
+                        .await
+                    {
                         Ok(result) => {
                             info!(
                                 "✓ Profile '{}': {} ({:.2} MB) - took {:.2}s",
@@ -121,6 +331,108 @@ impl<'a> PreviewProcessor<'a> {
         Ok(results)
     }
 
+    /// Image previews at each of `timestamps`, then a montage of them all
+    /// (source frames and every profile) in one PNG. Returns the montage.
+    pub async fn generate_montage(&mut self, timestamps: &[f64]) -> Result<PathBuf> {
+        let mut source_frames = Vec::new();
+        let mut results = Vec::new();
+        for &timestamp in timestamps {
+            self.preview_config.mode = PreviewMode::Image { timestamp };
+            self.source_frame = None;
+            results.push(self.generate_previews().await?);
+            source_frames.push(self.source_frame.clone());
+        }
+
+        let mut rows = Vec::new();
+        if let Some(frames) = source_frames.into_iter().collect::<Option<Vec<_>>>() {
+            rows.push(MontageRow {
+                label: "source".to_string(),
+                frames,
+            });
+        }
+        for profile_name in &self.preview_config.profile_names {
+            let frames: Option<Vec<PathBuf>> = results
+                .iter()
+                .map(|at_timestamp| {
+                    at_timestamp
+                        .iter()
+                        .find(|result| &result.profile_name == profile_name)
+                        .map(|result| result.output_path.clone())
+                })
+                .collect();
+            match frames {
+                Some(frames) => rows.push(MontageRow {
+                    label: profile_name.clone(),
+                    frames,
+                }),
+                None => warn!(
+                    "Profile '{}' is missing frames, left out of the montage",
+                    profile_name
+                ),
+            }
+        }
+
+        let stem = self
+            .input_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("preview");
+        let montage = self
+            .output_dir
+            .join(format!("{}_preview_montage_uuid-{}.png", stem, self.uuid));
+        write_montage(&self.config.tools.ffmpeg, &rows, timestamps, &montage).await?;
+        info!("Montage: {}", montage.display());
+        Ok(montage)
+    }
+
+    /// Warns when the preview frame or most of the preview segment is black
+    /// (fade, slate), where every profile looks alike. The requested time is
+    /// kept as it is: it was chosen on purpose.
+    async fn check_black_frames(&self) {
+        let scanner =
+            BlackFrameScanner::new(self.ffmpeg, self.config.analysis.black_frames.clone());
+        let black = match self.preview_config.mode {
+            PreviewMode::Image { timestamp } => scanner
+                .scan_around(self.input_path, &[timestamp])
+                .await
+                .map(|ranges| ranges.iter().any(|range| range.contains(timestamp))),
+            PreviewMode::VideoSegment { start, end } => scanner
+                .scan(self.input_path, start, end - start)
+                .await
+                .map(|ranges| black_seconds(&ranges, start, end) > (end - start) / 2.0),
+        };
+        match black {
+            Ok(true) => {
+                warn!("The preview is on black frames, pick another time to compare picture")
+            }
+            Ok(false) => {}
+            Err(e) => debug!("Black frame scan failed: {}", e),
+        }
+    }
+
+    /// For an image preview, the exact source frame is looked up once, so
+    /// all profiles encode the same frame.
+    async fn plan_seek(&self) -> SeekPlan {
+        let (start, image) = match self.preview_config.mode {
+            PreviewMode::Image { timestamp } => (timestamp, true),
+            PreviewMode::VideoSegment { start, .. } => (start, false),
+        };
+        let mut plan = plan_seek(
+            self.ffmpeg,
+            self.input_path,
+            start,
+            self.preview_config.seek,
+        )
+        .await;
+        if image {
+            plan.frame = resolve_frame(self.ffmpeg, self.input_path, start).await;
+            if let Some(frame) = plan.frame {
+                info!("Preview frame: source frame at {:.3}s", frame);
+            }
+        }
+        plan
+    }
+
     fn validate_preview_parameters(&self, metadata: &VideoMetadata) -> Result<()> {
         match &self.preview_config.mode {
             PreviewMode::Image { timestamp } => {
@@ -153,19 +465,26 @@ impl<'a> PreviewProcessor<'a> {
         &self,
         profile: &EncodingProfile,
         metadata: &VideoMetadata,
+        seek_plan: &SeekPlan,
     ) -> Result<PreviewResult> {
         let start_time = std::time::Instant::now();
 
         let output_path = self.generate_preview_filename(&profile.name);
 
         match &self.preview_config.mode {
-            PreviewMode::Image { timestamp } => {
-                self.generate_image_preview(profile, *timestamp, &output_path, metadata)
+            PreviewMode::Image { .. } => {
+                self.generate_image_preview(profile, seek_plan, &output_path, metadata)
                     .await?;
             }
             PreviewMode::VideoSegment { start, end } => {
-                self.generate_video_preview(profile, *start, *end, &output_path, metadata)
-                    .await?;
+                self.generate_video_preview(
+                    profile,
+                    seek_plan,
+                    end - start,
+                    &output_path,
+                    metadata,
+                )
+                .await?;
             }
         }
 
@@ -208,7 +527,7 @@ impl<'a> PreviewProcessor<'a> {
     async fn generate_image_preview(
         &self,
         profile: &EncodingProfile,
-        timestamp: f64,
+        seek_plan: &SeekPlan,
         output_path: &Path,
         metadata: &VideoMetadata,
     ) -> Result<()> {
@@ -231,12 +550,17 @@ impl<'a> PreviewProcessor<'a> {
             false,
         );
 
-        let mut cmd = tokio::process::Command::new(&self.config.tools.ffmpeg);
-        cmd.arg("-ss")
-            .arg(timestamp.to_string())
+        let mut cmd = tokio::process::Command::new(self.ffmpeg.get_ffmpeg_path());
+        cmd.args(seek_plan.input_args())
             .arg("-i")
             .arg(self.input_path)
-            .arg("-vframes")
+            .args(seek_plan.output_args())
+            .arg("-map")
+            .arg(self.ffmpeg.video_map_specifier());
+        if let Some(select) = seek_plan.frame_filter() {
+            cmd.arg("-vf").arg(select);
+        }
+        cmd.arg("-vframes")
             .arg("1")
             .arg("-c:v")
             .arg("libx265")
@@ -262,11 +586,18 @@ impl<'a> PreviewProcessor<'a> {
             )));
         }
 
-        // Step 2: Extract encoded frame to PNG
+        // Step 2: Extract encoded frame to PNG. The temp MKV carries no colour
+        // tags, so HDR frames are tone mapped with the source's transfer given
+        // explicitly; a PQ frame written as-is looks washed out.
         let mut cmd2 = tokio::process::Command::new(&self.config.tools.ffmpeg);
-        cmd2.arg("-i")
-            .arg(&temp_mkv)
-            .arg("-vframes")
+        cmd2.arg("-i").arg(&temp_mkv);
+        if metadata.is_hdr {
+            let tonemap = FilterBuilder::new(self.config)
+                .with_tonemap_from(metadata.transfer_function.as_deref())
+                .build();
+            cmd2.arg("-vf").arg(tonemap.to_string());
+        }
+        cmd2.arg("-vframes")
             .arg("1")
             .arg("-c:v")
             .arg("png")
@@ -289,11 +620,51 @@ impl<'a> PreviewProcessor<'a> {
         Ok(())
     }
 
+    /// Writes the source frame the image previews encode, untouched apart
+    /// from the tone mapping HDR previews get as well.
+    async fn export_source_frame(
+        &self,
+        seek_plan: &SeekPlan,
+        metadata: &VideoMetadata,
+    ) -> Result<PathBuf> {
+        let png = self.generate_preview_filename("source");
+        let mut filters: Vec<String> = seek_plan.frame_filter().into_iter().collect();
+        if metadata.is_hdr {
+            let tonemap = FilterBuilder::new(self.config)
+                .with_tonemap_from(metadata.transfer_function.as_deref())
+                .build();
+            filters.push(tonemap.to_string());
+        }
+
+        let mut cmd = tokio::process::Command::new(self.ffmpeg.get_ffmpeg_path());
+        cmd.args(seek_plan.input_args())
+            .arg("-i")
+            .arg(self.input_path)
+            .args(seek_plan.output_args());
+        if !filters.is_empty() {
+            cmd.arg("-vf").arg(filters.join(","));
+        }
+        cmd.arg("-map")
+            .arg(self.ffmpeg.video_map_specifier())
+            .args(["-vframes", "1", "-c:v", "png", "-y"])
+            .arg(&png);
+
+        let output = cmd.output().await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to extract the source frame: {}",
+                stderr
+            )));
+        }
+        Ok(png)
+    }
+
     async fn generate_video_preview(
         &self,
         profile: &EncodingProfile,
-        start: f64,
-        end: f64,
+        seek_plan: &SeekPlan,
+        duration: f64,
         output_path: &Path,
         metadata: &VideoMetadata,
     ) -> Result<()> {
@@ -308,13 +679,16 @@ impl<'a> PreviewProcessor<'a> {
             false,
         );
 
-        let mut cmd = tokio::process::Command::new(&self.config.tools.ffmpeg);
-        cmd.arg("-ss")
-            .arg(start.to_string())
-            .arg("-to")
-            .arg(end.to_string())
+        let mut cmd = tokio::process::Command::new(self.ffmpeg.get_ffmpeg_path());
+        cmd.args(seek_plan.input_args())
             .arg("-i")
             .arg(self.input_path)
+            .args(seek_plan.output_args())
+            .arg("-map")
+            .arg(self.ffmpeg.video_map_specifier())
+            .args(["-map", "0:a:0?"])
+            .arg("-t")
+            .arg(duration.to_string())
             .arg("-c:v")
             .arg("libx265")
             .arg("-x265-params")
@@ -364,6 +738,10 @@ impl<'a> PreviewProcessor<'a> {
             }
         }
 
+        if let Some(frame) = self.source_frame.as_ref().and_then(|path| path.file_name()) {
+            summary.push_str(&format!("Source frame: {}\n", frame.to_string_lossy()));
+        }
+
         summary.push_str(&"=".repeat(80));
         summary.push_str("\n\n");
         summary.push_str(&format!(
@@ -394,3 +772,44 @@ impl<'a> PreviewProcessor<'a> {
         summary
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seek_plan_from_keyframes() {
+        // Start in the GOP at 100s: seek to the keyframe before it
+        let plan = SeekPlan::from_keyframes(&[88.0, 94.0, 100.0], 103.5);
+        assert_eq!(
+            plan,
+            SeekPlan {
+                input: 94.0,
+                trim: 9.5,
+                frame: None,
+            }
+        );
+        assert_eq!(plan.output_args(), vec!["-ss", "9.5"]);
+
+        let plan = SeekPlan::from_keyframes(&[0.0], 4.0);
+        assert_eq!(plan.input, 0.0);
+
+        // No keyframes found: fixed lookback
+        assert_eq!(SeekPlan::from_keyframes(&[], 50.0).input, 20.0);
+    }
+
+    #[test]
+    fn test_image_preview_frame_selection() {
+        // 23.976 fps: 60s falls between two frames, the later one is shown
+        let frames = [59.976, 60.018, 60.059, 60.101];
+        assert_eq!(first_frame_at(&frames, 60.0), Some(60.018));
+        assert_eq!(first_frame_at(&frames, 60.059), Some(60.059));
+        assert_eq!(first_frame_at(&frames, 61.0), None);
+
+        let mut plan = SeekPlan::from_keyframes(&[55.0], 60.0);
+        plan.frame = Some(60.018);
+        assert_eq!(plan.input_args(), vec!["-ss", "55", "-copyts"]);
+        assert!(plan.output_args().is_empty());
+        assert_eq!(plan.frame_filter().unwrap(), "select=gte(t\\,60.017000)");
+    }
+}
@@ -0,0 +1,133 @@
+//! Montage of image previews taken at several timestamps: one row per
+//! profile (the source frames first), one column per timestamp, each tile
+//! labeled, tiled into a single PNG with FFmpeg's `tile` filter.
+
+use crate::utils::{Error, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::warn;
+
+/// Width every frame is scaled to in the montage.
+const TILE_WIDTH: u32 = 960;
+
+/// One row of the montage: a label and a frame per timestamp.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MontageRow {
+    pub label: String,
+    pub frames: Vec<PathBuf>,
+}
+
+/// `text` reduced to characters that need no escaping in a drawtext label.
+fn label_text(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || " _-.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Filtergraph scaling (and labeling) every input and tiling them, row by
+/// row, `columns` wide.
+fn montage_filter(labels: &[String], columns: usize, labeled: bool) -> String {
+    let mut graph = String::new();
+    for (index, label) in labels.iter().enumerate() {
+        graph.push_str(&format!(
+            "[{}:v]scale={}:-2,setsar=1,format=rgb24",
+            index, TILE_WIDTH
+        ));
+        if labeled {
+            graph.push_str(&format!(
+                ",drawtext=text='{}':x=12:y=12:fontsize=28:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=6",
+                label_text(label)
+            ));
+        }
+        graph.push_str(&format!("[t{}];", index));
+    }
+    for index in 0..labels.len() {
+        graph.push_str(&format!("[t{}]", index));
+    }
+    graph.push_str(&format!(
+        "concat=n={}:v=1:a=0,tile={}x{}",
+        labels.len(),
+        columns,
+        labels.len().div_ceil(columns.max(1))
+    ));
+    graph
+}
+
+/// Writes the montage of `rows` (each with one frame per entry of
+/// `timestamps`) to `output`. Labels need an FFmpeg with drawtext; without
+/// it the montage is written unlabeled.
+pub async fn write_montage(
+    ffmpeg_path: &str,
+    rows: &[MontageRow],
+    timestamps: &[f64],
+    output: &Path,
+) -> Result<()> {
+    let mut inputs = Vec::new();
+    let mut labels = Vec::new();
+    for row in rows {
+        for (frame, timestamp) in row.frames.iter().zip(timestamps) {
+            inputs.push(frame.clone());
+            labels.push(format!("{} {:.1}s", row.label, timestamp));
+        }
+    }
+    if inputs.is_empty() {
+        return Err(Error::validation("No preview frames for a montage"));
+    }
+
+    let run = |labeled: bool| {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.args(["-hide_banner", "-loglevel", "error", "-y"]);
+        for input in &inputs {
+            cmd.arg("-i").arg(input);
+        }
+        cmd.arg("-filter_complex")
+            .arg(montage_filter(&labels, timestamps.len(), labeled))
+            .args(["-frames:v", "1"])
+            .arg(output);
+        async move { cmd.output().await }
+    };
+
+    let mut result = run(true).await?;
+    if !result.status.success() {
+        warn!(
+            "Labeled montage failed (FFmpeg without drawtext?), writing it unlabeled: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        );
+        result = run(false).await?;
+    }
+    if !result.status.success() {
+        return Err(Error::ffmpeg(format!(
+            "FFmpeg failed to write the montage: {}",
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_montage_filter() {
+        let labels = vec![
+            "source 60.0s".to_string(),
+            "source 300.0s".to_string(),
+            "anime 60.0s".to_string(),
+            "anime 300.0s".to_string(),
+        ];
+        let graph = montage_filter(&labels, 2, false);
+        assert!(graph.starts_with("[0:v]scale=960:-2,setsar=1,format=rgb24[t0];"));
+        assert!(graph.ends_with("[t0][t1][t2][t3]concat=n=4:v=1:a=0,tile=2x2"));
+
+        let labeled = montage_filter(&labels[..1], 1, true);
+        assert!(labeled.contains("drawtext=text='source 60.0s'"));
+        assert_eq!(label_text("it's: a,b"), "it_s_ a_b");
+    }
+}
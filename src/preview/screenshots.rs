@@ -0,0 +1,186 @@
+//! Screenshot sets for sharing comparisons: lossless PNGs of the source at
+//! fixed positions (`10%,30%,50%,70%,90%` by default), tone mapped to SDR
+//! when the source is HDR so they display correctly anywhere.
+
+use super::{plan_seek, PreviewSeek};
+use crate::config::Config;
+use crate::encoding::FilterBuilder;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::info;
+
+/// Where in the source a screenshot is taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScreenshotPosition {
+    /// Percentage of the duration
+    Percent(f64),
+    Seconds(f64),
+}
+
+impl ScreenshotPosition {
+    /// Parses a comma-separated list such as `10%,50%,90%` or `30,95.5`.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>> {
+        let positions = list
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(Self::parse)
+            .collect::<Result<Vec<_>>>()?;
+        if positions.is_empty() {
+            return Err(Error::validation(
+                "At least one screenshot position is required".to_string(),
+            ));
+        }
+        Ok(positions)
+    }
+
+    fn parse(entry: &str) -> Result<Self> {
+        let invalid = || {
+            Error::validation(format!(
+                "Invalid screenshot position '{}' (expected a percentage like 50% or seconds)",
+                entry
+            ))
+        };
+        match entry.strip_suffix('%') {
+            Some(percent) => {
+                let percent: f64 = percent.trim().parse().map_err(|_| invalid())?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err(invalid());
+                }
+                Ok(Self::Percent(percent))
+            }
+            None => {
+                let seconds: f64 = entry.parse().map_err(|_| invalid())?;
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return Err(invalid());
+                }
+                Ok(Self::Seconds(seconds))
+            }
+        }
+    }
+
+    /// Seconds into a video of `duration` seconds, kept before its last frame.
+    fn timestamp(&self, duration: f64, fps: f64) -> f64 {
+        let last_frame = (duration - 1.0 / fps.max(1.0)).max(0.0);
+        match self {
+            Self::Percent(percent) => (duration * percent / 100.0).min(last_frame),
+            Self::Seconds(seconds) => seconds.min(last_frame),
+        }
+    }
+
+    /// File name part, e.g. `10pct` or `95.5s`.
+    fn label(&self) -> String {
+        match self {
+            Self::Percent(percent) => format!("{}pct", percent),
+            Self::Seconds(seconds) => format!("{}s", seconds),
+        }
+    }
+}
+
+/// `<stem>_screenshot_<label>.png` in `output_dir`.
+fn screenshot_path(input: &Path, output_dir: &Path, position: &ScreenshotPosition) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("screenshot");
+    output_dir.join(format!("{}_screenshot_{}.png", stem, position.label()))
+}
+
+/// Writes one PNG per position into `output_dir` (the input's directory when
+/// `None`) and returns their paths.
+pub async fn generate_screenshots(
+    ffmpeg: &FfmpegWrapper,
+    config: &Config,
+    input: &Path,
+    output_dir: Option<&Path>,
+    positions: &[ScreenshotPosition],
+    seek: PreviewSeek,
+) -> Result<Vec<PathBuf>> {
+    let metadata = ffmpeg.get_video_metadata(input).await?;
+    for position in positions {
+        if let ScreenshotPosition::Seconds(seconds) = position {
+            if *seconds > metadata.duration {
+                return Err(Error::validation(format!(
+                    "Screenshot position ({:.2}s) exceeds video duration ({:.2}s)",
+                    seconds, metadata.duration
+                )));
+            }
+        }
+    }
+
+    let output_dir = output_dir
+        .map(Path::to_path_buf)
+        .or_else(|| input.parent().map(Path::to_path_buf))
+        .unwrap_or_else(|| PathBuf::from("."));
+    std::fs::create_dir_all(&output_dir)?;
+
+    let mut written = Vec::new();
+    for position in positions {
+        let timestamp = position.timestamp(metadata.duration, metadata.fps as f64);
+        let plan = plan_seek(ffmpeg, input, timestamp, seek).await;
+        let png = screenshot_path(input, &output_dir, position);
+
+        let mut cmd = Command::new(&config.tools.ffmpeg);
+        cmd.args(["-loglevel", "error", "-hide_banner", "-y"])
+            .args(plan.input_args())
+            .arg("-i")
+            .arg(input)
+            .args(plan.output_args())
+            .args(["-an", "-sn", "-dn", "-frames:v", "1"]);
+        if metadata.is_hdr {
+            let tonemap = FilterBuilder::new(config)
+                .with_tonemap_from(metadata.transfer_function.as_deref())
+                .build();
+            cmd.arg("-vf").arg(tonemap.to_string());
+        }
+        let output = cmd.args(["-c:v", "png"]).arg(&png).output().await?;
+        if !output.status.success() {
+            let _ = tokio::fs::remove_file(&png).await;
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to write screenshot at {:.3}s: {}",
+                timestamp,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        info!("Screenshot at {:.3}s: {}", timestamp, png.display());
+        written.push(png);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_screenshot_positions() {
+        let positions = ScreenshotPosition::parse_list("10%, 50%,100%,95.5").unwrap();
+        assert_eq!(
+            positions,
+            [
+                ScreenshotPosition::Percent(10.0),
+                ScreenshotPosition::Percent(50.0),
+                ScreenshotPosition::Percent(100.0),
+                ScreenshotPosition::Seconds(95.5),
+            ]
+        );
+        assert!(ScreenshotPosition::parse_list("150%").is_err());
+        assert!(ScreenshotPosition::parse_list("-3").is_err());
+        assert!(ScreenshotPosition::parse_list(" , ").is_err());
+
+        assert_eq!(positions[0].timestamp(200.0, 25.0), 20.0);
+        assert_eq!(positions[2].timestamp(200.0, 25.0), 199.96);
+        assert_eq!(positions[3].timestamp(60.0, 24.0), 60.0 - 1.0 / 24.0);
+        assert_eq!(
+            screenshot_path(
+                Path::new("/in/movie.mkv"),
+                Path::new("/shots"),
+                &positions[3]
+            ),
+            PathBuf::from("/shots/movie_screenshot_95.5s.png")
+        );
+        assert_eq!(positions[1].label(), "50pct");
+    }
+}
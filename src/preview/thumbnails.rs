@@ -0,0 +1,219 @@
+//! Thumbnails for media servers and web players, written next to an output
+//! once it is encoded: a sprite sheet (`<output>.thumbnails.jpg`) with a
+//! WebVTT track (`<output>.thumbnails.vtt`) pointing each time range at its
+//! tile, or a single-row strip (`<output>.strip.jpg`).
+
+use crate::config::{Config, ThumbnailKind, ThumbnailsConfig};
+use crate::encoding::FilterBuilder;
+use crate::utils::{ffmpeg::VideoMetadata, Error, Result};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+
+/// Where the thumbnails go and how the frames are arranged.
+#[derive(Debug, Clone, PartialEq)]
+struct ThumbnailLayout {
+    /// Seconds of video each thumbnail stands for
+    interval: f64,
+    /// Seconds into the video of the first thumbnail
+    offset: f64,
+    count: u32,
+    columns: u32,
+    rows: u32,
+    tile_width: u32,
+    tile_height: u32,
+}
+
+impl ThumbnailLayout {
+    fn new(config: &ThumbnailsConfig, metadata: &VideoMetadata) -> Self {
+        let duration = metadata.duration.max(0.0);
+        let (interval, offset, count, columns) = match config.kind {
+            ThumbnailKind::Sprite => {
+                let count = ((duration / config.interval).ceil() as u32).max(1);
+                (config.interval, 0.0, count, config.columns.min(count))
+            }
+            // Frames from the middle of each slice, so the strip skips the first frame
+            ThumbnailKind::Strip => {
+                let interval = duration / config.count as f64;
+                (interval, interval / 2.0, config.count, config.count)
+            }
+        };
+
+        let tile_width = config.width.max(2) & !1;
+        let aspect = if metadata.width > 0 {
+            metadata.height as f64 / metadata.width as f64
+        } else {
+            9.0 / 16.0
+        };
+        let tile_height = (((tile_width as f64 * aspect) / 2.0).round() as u32).max(1) * 2;
+
+        Self {
+            interval,
+            offset,
+            count,
+            columns,
+            rows: count.div_ceil(columns),
+            tile_width,
+            tile_height,
+        }
+    }
+
+    /// Video filter picking one frame per interval and tiling them into one image.
+    fn filter(&self) -> String {
+        format!(
+            "fps=1/{},scale={}:{},tile={}x{}",
+            self.interval, self.tile_width, self.tile_height, self.columns, self.rows
+        )
+    }
+
+    /// WebVTT track mapping each interval to its tile in `image_name`.
+    fn webvtt(&self, image_name: &str, duration: f64) -> String {
+        let mut vtt = String::from("WEBVTT\n");
+        for index in 0..self.count {
+            let start = index as f64 * self.interval;
+            let end = (start + self.interval).min(duration.max(start));
+            let x = (index % self.columns) * self.tile_width;
+            let y = (index / self.columns) * self.tile_height;
+            let _ = write!(
+                vtt,
+                "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+                vtt_timestamp(start),
+                vtt_timestamp(end),
+                image_name,
+                x,
+                y,
+                self.tile_width,
+                self.tile_height
+            );
+        }
+        vtt
+    }
+}
+
+/// `HH:MM:SS.mmm`
+fn vtt_timestamp(seconds: f64) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// `<output>.<suffix>` next to `output`.
+fn sibling_path(output: &Path, suffix: &str) -> PathBuf {
+    output.with_extension(suffix)
+}
+
+/// Writes the thumbnails `config.thumbnails` describes for the encoded
+/// `video` and returns the files written. HDR outputs are tone mapped so the
+/// images do not look washed out.
+pub async fn generate_thumbnails(
+    config: &Config,
+    video: &Path,
+    metadata: &VideoMetadata,
+) -> Result<Vec<PathBuf>> {
+    let settings = &config.thumbnails;
+    let layout = ThumbnailLayout::new(settings, metadata);
+    let image = match settings.kind {
+        ThumbnailKind::Sprite => sibling_path(video, "thumbnails.jpg"),
+        ThumbnailKind::Strip => sibling_path(video, "strip.jpg"),
+    };
+
+    let mut filters = FilterBuilder::new(config)
+        .with_tonemap(metadata.is_hdr)
+        .build();
+    filters.add_filter(layout.filter());
+
+    let output = Command::new(&config.tools.ffmpeg)
+        .args(["-loglevel", "error", "-hide_banner", "-y"])
+        .args(["-ss", &layout.offset.to_string()])
+        .arg("-i")
+        .arg(video)
+        .args(["-an", "-sn", "-dn", "-vf", &filters.to_string()])
+        .args(["-frames:v", "1", "-update", "1", "-q:v", "4"])
+        .arg(&image)
+        .output()
+        .await?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&image).await;
+        return Err(Error::ffmpeg(format!(
+            "FFmpeg failed to write thumbnails: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let mut written = vec![image.clone()];
+    if settings.kind == ThumbnailKind::Sprite {
+        let image_name = image
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let vtt = sibling_path(video, "thumbnails.vtt");
+        std::fs::write(&vtt, layout.webvtt(&image_name, metadata.duration))?;
+        written.push(vtt);
+    }
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite_and_strip_layout() {
+        let metadata = VideoMetadata {
+            width: 3840,
+            height: 1606,
+            duration: 25.0,
+            fps: 24.0,
+            bitrate: None,
+            codec: Some("hevc".to_string()),
+            pix_fmt: None,
+            field_order: None,
+            is_hdr: false,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+        let config = ThumbnailsConfig {
+            columns: 2,
+            ..ThumbnailsConfig::default()
+        };
+
+        let sprite = ThumbnailLayout::new(&config, &metadata);
+        assert_eq!((sprite.count, sprite.columns, sprite.rows), (3, 2, 2));
+        assert_eq!((sprite.tile_width, sprite.tile_height), (320, 134));
+        assert_eq!(sprite.filter(), "fps=1/10,scale=320:134,tile=2x2");
+        assert_eq!(
+            sprite.webvtt("movie.thumbnails.jpg", metadata.duration),
+            "WEBVTT\n\
+             \n00:00:00.000 --> 00:00:10.000\nmovie.thumbnails.jpg#xywh=0,0,320,134\n\
+             \n00:00:10.000 --> 00:00:20.000\nmovie.thumbnails.jpg#xywh=320,0,320,134\n\
+             \n00:00:20.000 --> 00:00:25.000\nmovie.thumbnails.jpg#xywh=0,134,320,134\n"
+        );
+        assert_eq!(vtt_timestamp(3723.5), "01:02:03.500");
+
+        let strip = ThumbnailLayout::new(
+            &ThumbnailsConfig {
+                kind: ThumbnailKind::Strip,
+                count: 5,
+                ..config
+            },
+            &metadata,
+        );
+        assert_eq!((strip.count, strip.columns, strip.rows), (5, 5, 1));
+        assert_eq!((strip.interval, strip.offset), (5.0, 2.5));
+        assert_eq!(
+            sibling_path(Path::new("/out/movie.mkv"), "strip.jpg"),
+            PathBuf::from("/out/movie.strip.jpg")
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! Ladder encoding: several renditions of one source (e.g. 2160p HDR, 1080p
+//! SDR, 720p SDR) from a single run, as defined under `ladders:` in the
+//! configuration. [`VenEngine::encode_ladder`](crate::VenEngine::encode_ladder)
+//! runs them; this module turns a ladder into per-rendition encoding options.
+
+use crate::config::{Config, LadderConfig, Rendition};
+use crate::encoding::EncodingOptions;
+use crate::utils::{Error, Result};
+use std::path::PathBuf;
+
+/// The ladder `name` of the configuration.
+pub fn find_ladder<'a>(config: &'a Config, name: &str) -> Result<&'a LadderConfig> {
+    config.ladders.get(name).ok_or_else(|| {
+        let mut available: Vec<&str> = config.ladders.keys().map(String::as_str).collect();
+        available.sort_unstable();
+        Error::validation(format!(
+            "Unknown ladder '{}'. Available ladders: {}",
+            name,
+            available.join(", ")
+        ))
+    })
+}
+
+/// Options for one rendition: `base` (the options of a plain encode) with
+/// the rendition's profile, size, tone mapping and output name.
+pub fn rendition_options(
+    base: &EncodingOptions,
+    ladder: &LadderConfig,
+    rendition: &Rendition,
+) -> EncodingOptions {
+    let mut options = base.clone();
+    options.output_path = rendition_output(base, ladder, rendition);
+    if let Some(profile) = &rendition.profile {
+        options.profile_name = profile.clone();
+    }
+    options.scale_height = rendition.height;
+    options.tonemap = rendition.tonemap;
+    options
+}
+
+/// Fills in the ladder's output template next to the output `base` would
+/// write, keeping its extension.
+fn rendition_output(
+    base: &EncodingOptions,
+    ladder: &LadderConfig,
+    rendition: &Rendition,
+) -> PathBuf {
+    let stem = base
+        .output_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "output".to_string());
+    let height = rendition
+        .height
+        .map(|height| height.to_string())
+        .unwrap_or_else(|| "source".to_string());
+    let name = ladder
+        .output
+        .replace("{stem}", &stem)
+        .replace("{name}", &rendition.name)
+        .replace("{height}", &height);
+
+    let mut output = base.output_path.with_file_name(name);
+    if let Some(extension) = base.output_path.extension() {
+        let mut file_name = output.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".");
+        file_name.push(extension);
+        output.set_file_name(file_name);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rendition_options() {
+        let ladder: LadderConfig = serde_yaml::from_str(
+            r#"
+renditions:
+  - name: 2160p
+  - name: 1080p-sdr
+    profile: movie
+    height: 1080
+    tonemap: true
+"#,
+        )
+        .unwrap();
+        assert_eq!(ladder.output, "{stem}.{name}");
+        assert_eq!(ladder.parallel, 1);
+
+        let base = EncodingOptions::new("/in/movie.mkv", "/out/movie_1234.mkv");
+        let uhd = rendition_options(&base, &ladder, &ladder.renditions[0]);
+        assert_eq!(uhd.output_path, PathBuf::from("/out/movie_1234.2160p.mkv"));
+        assert_eq!(uhd.profile_name, "auto");
+        assert_eq!(uhd.scale_height, None);
+        assert!(!uhd.tonemap);
+
+        let sdr = rendition_options(&base, &ladder, &ladder.renditions[1]);
+        assert_eq!(
+            sdr.output_path,
+            PathBuf::from("/out/movie_1234.1080p-sdr.mkv")
+        );
+        assert_eq!(sdr.profile_name, "movie");
+        assert_eq!(sdr.scale_height, Some(1080));
+        assert!(sdr.tonemap);
+
+        let custom = LadderConfig {
+            output: "renditions/{height}/{stem}-{name}".to_string(),
+            ..ladder.clone()
+        };
+        assert_eq!(
+            rendition_options(&base, &custom, &custom.renditions[1]).output_path,
+            PathBuf::from("/out/renditions/1080/movie_1234-1080p-sdr.mkv")
+        );
+        assert_eq!(
+            rendition_options(&base, &custom, &custom.renditions[0]).output_path,
+            PathBuf::from("/out/renditions/source/movie_1234-2160p.mkv")
+        );
+    }
+}
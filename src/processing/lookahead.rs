@@ -0,0 +1,203 @@
+//! Analysis ahead of the encode. In a batch run with `--pipeline`, the next
+//! file is probed, its HDR/Dolby Vision content analyzed, its crop and scene
+//! cuts detected while the current file encodes;
+//! [`VenEngine::encode_with_analysis`](crate::VenEngine::encode_with_analysis)
+//! then starts its encode from these results instead of analyzing again.
+
+use crate::analysis::cache::CachedCrop;
+use crate::analysis::{
+    AnalysisCache, CachedAnalysis, CropAnalysisResult, CropDetector, SceneDetector,
+};
+use crate::config::{Config, CropDetectionConfig, DolbyVisionCropPolicy};
+use crate::encoding::EncodingOptions;
+use crate::hdr::HdrFormat;
+use crate::utils::ffmpeg::VideoMetadata;
+use crate::utils::{FfmpegWrapper, Result};
+use crate::UnifiedContentManager;
+use std::path::Path;
+use tracing::info;
+
+/// Runs the analysis phase of an encode with `options` on its own: HDR and
+/// Dolby Vision analysis, crop and scene detection, starting from the analysis
+/// cache. The result is stored in the cache as well.
+pub async fn analyze_ahead(
+    ffmpeg: &FfmpegWrapper,
+    config: &Config,
+    options: &EncodingOptions,
+) -> Result<CachedAnalysis> {
+    let input_path = options.input_path.as_path();
+    let cache = AnalysisCache::from_config(&config.analysis.cache, !options.use_analysis_cache)
+        .with_selection(options.video_stream, options.error_resilience);
+    let mut analysis = cache.load(input_path).unwrap_or_default();
+    if options.is_video_copy() {
+        return Ok(analysis);
+    }
+    info!("Analyzing ahead: {}", input_path.display());
+
+    let metadata = ffmpeg.get_video_metadata(input_path).await?;
+    let content_manager = UnifiedContentManager::new(
+        config.analysis.hdr.clone().unwrap_or_default(),
+        config.analysis.dolby_vision.clone(),
+        config.tools.hdr10plus_tool.clone(),
+    )
+    .with_temp_dir(Path::new(&config.app.temp_dir));
+    let hdr_analysis = match analysis.hdr.clone() {
+        Some(hdr_analysis) => hdr_analysis,
+        None => content_manager.analyze_hdr_only(ffmpeg, input_path).await?,
+    };
+    let content = content_manager
+        .analyze_content_with_reuse(
+            ffmpeg,
+            input_path,
+            Some(hdr_analysis),
+            analysis.dolby_vision.clone(),
+        )
+        .await?;
+    let is_advanced_content = content.hdr_analysis.metadata.format != HdrFormat::None;
+    analysis.hdr = Some(content.hdr_analysis);
+    analysis.dolby_vision = Some(content.dolby_vision.clone());
+
+    if detects_crop(config, options, content.dolby_vision.is_dolby_vision()) {
+        let crop_config = crop_config(config, options);
+        let settings = crop_settings(config, &crop_config, is_advanced_content)?;
+        if analysis.crop_for(&settings).is_none() {
+            let result = detect_crop(
+                ffmpeg,
+                config,
+                &crop_config,
+                input_path,
+                &metadata,
+                is_advanced_content,
+            )
+            .await?;
+            analysis.crop = Some(CachedCrop { settings, result });
+        }
+    }
+
+    let scenes = &config.analysis.scene_detection;
+    if scenes.enabled && analysis.scene_cuts_for(scenes.threshold).is_none() {
+        analysis.scene_cuts = Some(
+            SceneDetector::new(ffmpeg, scenes)
+                .detect(input_path)
+                .await?,
+        );
+    }
+
+    cache.store(input_path, &analysis);
+    Ok(analysis)
+}
+
+/// Whether an encode with `options` runs crop detection: no `--crop` was
+/// given, detection is enabled and not switched off for Dolby Vision.
+pub(crate) fn detects_crop(
+    config: &Config,
+    options: &EncodingOptions,
+    is_dolby_vision: bool,
+) -> bool {
+    let crop_config = &config.analysis.crop_detection;
+    options.crop.is_none()
+        && crop_config.enabled
+        && !(is_dolby_vision && crop_config.dolby_vision == DolbyVisionCropPolicy::Off)
+}
+
+/// Crop detection settings of an encode with `options`: the configured ones
+/// with the per-run overrides applied.
+pub(crate) fn crop_config(config: &Config, options: &EncodingOptions) -> CropDetectionConfig {
+    config
+        .analysis
+        .crop_detection
+        .with_overrides(&options.crop_detection)
+}
+
+/// Key of a cached crop result: cached crop values are only valid for the
+/// settings that produced them. Whether a report is written does not count.
+pub(crate) fn crop_settings(
+    config: &Config,
+    crop_config: &CropDetectionConfig,
+    is_advanced_content: bool,
+) -> Result<String> {
+    Ok(format!(
+        "{}:{}:{}",
+        serde_json::to_string(&CropDetectionConfig {
+            report: false,
+            ..crop_config.clone()
+        })?,
+        serde_json::to_string(&config.analysis.black_frames)?,
+        is_advanced_content
+    ))
+}
+
+/// Crop detection over the source, sampling around black frames.
+pub(crate) async fn detect_crop(
+    ffmpeg: &FfmpegWrapper,
+    config: &Config,
+    crop_config: &CropDetectionConfig,
+    input_path: &Path,
+    metadata: &VideoMetadata,
+    is_advanced_content: bool,
+) -> Result<CropAnalysisResult> {
+    CropDetector::new(ffmpeg, crop_config.clone())
+        .with_black_frames(config.analysis.black_frames.clone())
+        .detect_crop_values(
+            input_path,
+            metadata.duration,
+            metadata.width,
+            metadata.height,
+            is_advanced_content,
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CropDetectionOverrides;
+
+    #[test]
+    fn test_lookahead_crop_decision() {
+        let mut config = Config::default();
+        config.analysis.crop_detection.enabled = true;
+        config.analysis.crop_detection.dolby_vision = DolbyVisionCropPolicy::RpuAware;
+        let mut options = EncodingOptions::new("in.mkv", "out.mkv");
+        assert!(detects_crop(&config, &options, false));
+        assert!(detects_crop(&config, &options, true));
+
+        config.analysis.crop_detection.dolby_vision = DolbyVisionCropPolicy::Off;
+        assert!(detects_crop(&config, &options, false));
+        assert!(!detects_crop(&config, &options, true));
+
+        options.crop = Some("1920:800:0:140".to_string());
+        assert!(!detects_crop(&config, &options, false));
+
+        let crop_config = crop_config(&config, &options);
+        assert_ne!(
+            crop_settings(&config, &crop_config, true).unwrap(),
+            crop_settings(&config, &crop_config, false).unwrap()
+        );
+
+        // Per-run overrides apply to detection and invalidate cached results
+        let overridden = crop_config.with_overrides(&CropDetectionOverrides {
+            sample_count: Some(12),
+            limit: Some(18),
+            min_confidence: Some(80.0),
+            report: true,
+        });
+        assert_eq!(overridden.sample_count, 12);
+        assert_eq!(overridden.sdr_crop_limit, 18);
+        assert_eq!(overridden.hdr_crop_limit, 18);
+        assert_eq!(overridden.min_confidence, 80.0);
+        assert!(overridden.report);
+        assert_ne!(
+            crop_settings(&config, &crop_config, false).unwrap(),
+            crop_settings(&config, &overridden, false).unwrap()
+        );
+        let reported = CropDetectionConfig {
+            report: true,
+            ..crop_config.clone()
+        };
+        assert_eq!(
+            crop_settings(&config, &crop_config, false).unwrap(),
+            crop_settings(&config, &reported, false).unwrap()
+        );
+    }
+}
@@ -1,77 +1,459 @@
+pub mod ladder;
+pub mod lookahead;
+
 use crate::{
-    analysis::ContentAnalyzer,
-    cli::CliArgs,
-    config::{Config, EncodingProfile, ProfileManager, StreamSelectionProfileManager},
+    analysis::{
+        bitrate::measure_source_bitrate, dolby_vision::DolbyVisionProfile, scenes::keyframe_times,
+        AnalysisCache, BandingDetector, CachedAnalysis, ClassifierChain, Fingerprinter,
+        SceneDetector,
+    },
+    audio::{BitrateLadder, LoudnormNormalizer},
+    config::{
+        ClosedCaptionPolicy, Config, ContentType, DolbyVisionCropPolicy, DuplicateAction,
+        EncodingProfile, MetadataFallback, Muxer, OutputContainer, ProfileManager,
+        StarvedSourceAction, StreamSelectionProfileManager,
+    },
     encoding::{
-        modes::Encoder, AbrEncoder, CbrEncoder, CrfEncoder, EncodingMode, FilterBuilder,
-        FilterChain,
+        modes::Encoder, remux, x265_params, AbrEncoder, CbrEncoder, CrfEncoder, DeviceConstraints,
+        EncodingMode, EncodingOptions, FilterBuilder, FilterChain, FilterStage, Provenance,
+    },
+    events::{CommandHook, EncodeEvent, EventBus, EventSender, EventSink},
+    explain::{Decision, DecisionTopic},
+    hdr10plus::Hdr10PlusProfile,
+    log_stage, log_step,
+    metadata_workflow::{ExtractedMetadata, MetadataWorkflowManager},
+    mkvmerge::MkvMergeTool,
+    preview::{generate_thumbnails, write_crop_report},
+    progress::{BatchProgress, ProgressMonitor},
+    quality::{QualityComparison, QualityScore},
+    reports::ReportLog,
+    schedule::SchedulePause,
+    stream::{
+        captions::CaptionExtractor,
+        diff as stream_diff, forced,
+        ocr::{SubtitleOcr, PGS_CODEC},
+        preservation::{is_bitmap_subtitle, StreamInfo, StreamMapping, StreamPreservation},
+        verification::StreamVerifier,
     },
-    metadata_workflow::MetadataWorkflowManager,
-    progress::ProgressMonitor,
-    stream::preservation::StreamPreservation,
-    utils::{ffmpeg::VideoMetadata, Error, FfmpegWrapper, FileLogger, Result},
-    ContentEncodingApproach, UnifiedContentManager,
+    throttle::Throttle,
+    utils::{
+        disk::DiskSpaceGuard, ffmpeg::VideoMetadata, CancelToken, Error, FfmpegWrapper, FileLogger,
+        JobTempDir, Result, ToolProgress,
+    },
+    ContentEncodingApproach, EncodingAdjustments, UnifiedContentManager,
 };
-use std::path::Path;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use tracing::info;
 
 pub struct VideoProcessor<'a> {
     ffmpeg: &'a FfmpegWrapper,
     stream_preservation: &'a StreamPreservation,
-    args: &'a CliArgs,
+    options: &'a EncodingOptions,
     config: &'a Config,
     profile_manager: &'a mut ProfileManager,
     stream_profile_manager: StreamSelectionProfileManager,
     input_path: &'a Path,
-    output_path: &'a Path,
+    output_path: PathBuf,
+    analysis_cache: AnalysisCache,
+    cached_analysis: CachedAnalysis,
+    device: Option<DeviceConstraints>,
+    events: EventBus,
+    show_progress_bar: bool,
+    /// Overall bar of the batch this encode belongs to
+    batch_progress: Option<BatchProgress>,
+    cancel: CancelToken,
+    profile_used: Option<String>,
+    /// Stream selection profile the selected encoding profile binds
+    profile_stream_selection: Option<String>,
+    /// Classification for profile auto-selection
+    classifiers: ClassifierChain,
+    frames_encoded: Option<u32>,
+    /// Scores of the quality check, when one ran and succeeded
+    quality_scores: Vec<QualityScore>,
+    /// `--smart` chose to copy the video stream
+    passthrough: bool,
+    /// `--dv5-convert` applies to this source: reshape profile 5 to HDR10
+    dv5_reshape: bool,
+    /// `tonemap` applies to this source: it is HDR and encoded as SDR
+    tonemap: bool,
+    /// The banding check found this anime source likely to band
+    deband: bool,
+    /// Scene cuts the encode forces keyframes on (`analysis.scene_detection`)
+    keyframes: Vec<f64>,
+    /// `--explain`: the automated decisions so far
+    decisions: Vec<Decision>,
+    /// Profile of the HDR10+ metadata passed to x265, if any
+    hdr10plus_profile: Option<Hdr10PlusProfile>,
+    /// Files written next to the output (HDR10 fallback, crop report,
+    /// thumbnails), which get the `output_files` permissions too
+    sidecar_files: Vec<PathBuf>,
 }
 
 impl<'a> VideoProcessor<'a> {
     pub fn new(
         ffmpeg: &'a FfmpegWrapper,
         stream_preservation: &'a StreamPreservation,
-        args: &'a CliArgs,
+        options: &'a EncodingOptions,
         config: &'a Config,
         profile_manager: &'a mut ProfileManager,
-        input_path: &'a Path,
-        output_path: &'a Path,
     ) -> Result<Self> {
         let stream_profile_manager =
             StreamSelectionProfileManager::new(config.stream_selection_profiles.clone())?;
+        let device = options
+            .device
+            .as_deref()
+            .map(|name| DeviceConstraints::from_config(config, name))
+            .transpose()?;
+        let mut events = EventBus::new();
+        if config.notifications.is_enabled() {
+            events.subscribe(Arc::new(CommandHook::new(config.notifications.clone())));
+        }
 
         Ok(Self {
             ffmpeg,
             stream_preservation,
-            args,
+            options,
             config,
             profile_manager,
             stream_profile_manager,
-            input_path,
-            output_path,
+            input_path: &options.input_path,
+            output_path: options.output_path.clone(),
+            analysis_cache: AnalysisCache::from_config(
+                &config.analysis.cache,
+                !options.use_analysis_cache,
+            )
+            .with_selection(options.video_stream, options.error_resilience),
+            cached_analysis: CachedAnalysis::default(),
+            device,
+            events,
+            show_progress_bar: true,
+            batch_progress: None,
+            cancel: CancelToken::new(),
+            profile_used: None,
+            profile_stream_selection: None,
+            classifiers: ClassifierChain::new(),
+            frames_encoded: None,
+            quality_scores: Vec::new(),
+            passthrough: false,
+            dv5_reshape: false,
+            tonemap: false,
+            deband: false,
+            keyframes: Vec::new(),
+            decisions: Vec::new(),
+            hdr10plus_profile: None,
+            sidecar_files: Vec::new(),
         })
     }
 
+    /// Lets `cancel` stop metadata extraction and the encode itself.
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Reports progress as [`EncodeEvent`]s instead of drawing a progress bar.
+    pub fn with_events(mut self, sender: EventSender) -> Self {
+        self.events.subscribe(Arc::new(sender));
+        self.show_progress_bar = false;
+        self
+    }
+
+    /// Also hands every [`EncodeEvent`] to `sink`, next to the progress bar.
+    pub fn with_event_sink(mut self, sink: Arc<dyn EventSink>) -> Self {
+        self.events.subscribe(sink);
+        self
+    }
+
+    /// Leaves the terminal progress bar out, e.g. when several encodes run at once.
+    pub fn without_progress_bar(mut self) -> Self {
+        self.show_progress_bar = false;
+        self
+    }
+
+    /// Draws the progress bar above the overall bar of `batch`.
+    pub fn with_batch_progress(mut self, batch: BatchProgress) -> Self {
+        self.batch_progress = Some(batch);
+        self
+    }
+
+    /// Classifies sources for profile auto-selection with `classifiers`
+    /// instead of the built-in heuristic alone.
+    pub fn with_classifiers(mut self, classifiers: ClassifierChain) -> Self {
+        self.classifiers = classifiers;
+        self
+    }
+
+    /// Starts from analysis results another run computed for the same source
+    /// (ladder renditions) instead of the on-disk analysis cache.
+    pub fn with_cached_analysis(mut self, analysis: CachedAnalysis) -> Self {
+        self.cached_analysis = analysis;
+        self
+    }
+
+    /// Analysis results of the source, as far as the run computed them.
+    pub fn cached_analysis(&self) -> &CachedAnalysis {
+        &self.cached_analysis
+    }
+
+    /// Output path, after any retargeting to the profile's container.
+    pub fn output_path(&self) -> &Path {
+        &self.output_path
+    }
+
+    /// Whether the video stream is copied: `--video copy`, or `--smart` found
+    /// nothing to encode.
+    fn copies_video(&self) -> bool {
+        self.options.is_video_copy() || self.passthrough
+    }
+
+    /// Name of the profile the encode used; `None` for a stream copy.
+    pub fn profile_used(&self) -> Option<&str> {
+        self.profile_used.as_deref()
+    }
+
+    /// Frames FFmpeg reported for the finished encode or remux.
+    pub fn frames_encoded(&self) -> Option<u32> {
+        self.frames_encoded
+    }
+
+    /// Results of the quality check; empty when none ran or it failed.
+    pub fn quality_scores(&self) -> &[QualityScore] {
+        &self.quality_scores
+    }
+
+    /// Automated decisions of the encode, recorded with `--explain`.
+    pub fn decisions(&self) -> &[Decision] {
+        &self.decisions
+    }
+
+    /// Profile of the HDR10+ metadata the encode carried, if any.
+    pub fn hdr10plus_profile(&self) -> Option<Hdr10PlusProfile> {
+        self.hdr10plus_profile
+    }
+
+    fn emit(&self, event: EncodeEvent) {
+        self.events.emit(event);
+    }
+
+    /// Records `decision` when `--explain` is on.
+    fn explain(&mut self, decision: Decision) {
+        if self.options.explain {
+            self.decisions.push(decision);
+        }
+    }
+
+    /// Creates the per-file log, which also records the pipeline events from here on.
+    fn create_file_logger(&mut self) -> Result<FileLogger> {
+        let file_logger = FileLogger::new(&self.output_path)?;
+        self.events.subscribe(Arc::new(file_logger.clone()));
+        Ok(file_logger)
+    }
+
     pub async fn run(&mut self) -> Result<()> {
+        let started = std::time::Instant::now();
+        self.emit(EncodeEvent::Started {
+            input: self.input_path.to_path_buf(),
+            output: self.output_path.clone(),
+        });
+
         let metadata = self.get_metadata().await?;
+        // Removed with everything in it when the run ends, however it ends
+        let mut job_dir = JobTempDir::create(&self.config.app.temp_dir, &self.output_path)?;
+        if self.options.keep_temp {
+            job_dir.keep();
+        }
+        let result = if self.options.is_video_copy() {
+            self.run_remux(&metadata, &job_dir).await
+        } else {
+            self.run_encode(metadata, &job_dir).await
+        };
+        if let Err(e) = result {
+            // Ends the per-file log, so `logs tail` stops following it
+            self.emit(EncodeEvent::Failed {
+                output: self.output_path.clone(),
+                error: e.to_string(),
+                cancelled: matches!(e, Error::Cancelled(_)) || self.cancel.is_cancelled(),
+            });
+            return Err(e);
+        }
+        if self.options.thumbnails || self.config.thumbnails.enabled {
+            self.write_thumbnails().await;
+        }
+        self.apply_output_files();
+
+        self.emit(EncodeEvent::Finished {
+            output: self.output_path.clone(),
+            elapsed: started.elapsed(),
+        });
+        Ok(())
+    }
+
+    async fn run_encode(
+        &mut self,
+        mut metadata: VideoMetadata,
+        job_dir: &JobTempDir,
+    ) -> Result<()> {
+        if self.cached_analysis.hdr.is_some() {
+            info!("Reusing the analysis of this source from earlier in the run");
+        } else if let Some(cached) = self.analysis_cache.load(self.input_path) {
+            info!("Reusing cached analysis results for this source");
+            self.cached_analysis = cached;
+        }
+        self.check_duplicate(metadata.duration).await?;
 
         let content_manager = UnifiedContentManager::new(
             self.config.analysis.hdr.clone().unwrap_or_default(),
             self.config.analysis.dolby_vision.clone(),
             self.config.tools.hdr10plus_tool.clone(),
-        );
-        let hdr_analysis = content_manager
-            .analyze_hdr_only(self.ffmpeg, self.input_path)
-            .await?;
+        )
+        .with_temp_dir(job_dir.path());
+        let hdr_analysis = match self.cached_analysis.hdr.clone() {
+            Some(hdr_analysis) => hdr_analysis,
+            None => {
+                content_manager
+                    .analyze_hdr_only(self.ffmpeg, self.input_path)
+                    .await?
+            }
+        };
 
         let is_advanced_content = hdr_analysis.metadata.format != crate::hdr::HdrFormat::None;
-        let (crop_values, crop_sample_timestamps, crop_analysis_result) =
-            self.detect_crop(is_advanced_content, &metadata).await?;
 
-        let content_analysis = content_manager
-            .analyze_content_with_hdr_reuse(self.ffmpeg, self.input_path, Some(hdr_analysis))
+        let mut content_analysis = content_manager
+            .analyze_content_with_reuse(
+                self.ffmpeg,
+                self.input_path,
+                Some(hdr_analysis),
+                self.cached_analysis.dolby_vision.clone(),
+            )
+            .await?;
+        self.cached_analysis.hdr = Some(content_analysis.hdr_analysis.clone());
+        self.cached_analysis.dolby_vision = Some(content_analysis.dolby_vision.clone());
+
+        let is_dolby_vision = content_analysis.dolby_vision.is_dolby_vision();
+        let is_profile5 = content_analysis.dolby_vision.profile == DolbyVisionProfile::Profile5;
+        // Smart mode may still copy the video, which keeps profile 5 intact
+        if is_profile5 && !self.options.dv5_convert && !self.options.smart {
+            return Err(profile5_refusal());
+        }
+        self.dv5_reshape = is_profile5 && self.options.dv5_convert;
+        self.tonemap = self.options.tonemap
+            && !matches!(
+                content_analysis.recommended_approach,
+                ContentEncodingApproach::SDR
+            );
+        let (mut crop_values, crop_sample_timestamps, crop_analysis_result) = self
+            .detect_crop(is_advanced_content, is_dolby_vision, &metadata)
             .await?;
-        let metadata_workflow = self.initialize_metadata_workflow().await?;
-        let extracted_metadata = metadata_workflow
+        if self.tonemap {
+            log_step!("Tone mapping to SDR (BT.709): HDR metadata is not carried over");
+            metadata = tonemap_to_sdr(&mut content_analysis, metadata);
+            self.explain(
+                Decision::new(DecisionTopic::Filter, "tone map to SDR", "HDR source")
+                    .with_rule("--tonemap"),
+            );
+        }
+
+        let mut selected_profile = self.select_profile(&metadata).await?;
+        self.profile_stream_selection = selected_profile.stream_selection_profile.clone();
+        if let Some(device) = &self.device {
+            info!("Applying device constraints: {}", device.title());
+            device.apply_to_profile(&mut selected_profile);
+        }
+        self.deband = self.check_banding(&mut selected_profile, &metadata).await?;
+        if let Some(preset) = &self.options.preset {
+            log_step!("Using preset {} (planned for --time-budget)", preset);
+            selected_profile
+                .x265_params
+                .insert("preset".to_string(), preset.clone());
+        }
+
+        // Smart mode: decided before any metadata extraction or encoder setup
+        if self.options.smart {
+            let filter_chain = self.build_filter_chain(crop_values.as_deref(), &metadata)?;
+            let blockers =
+                passthrough_blockers(self.options, &metadata, &selected_profile, &filter_chain);
+            if blockers.is_empty() {
+                log_stage!(
+                    "Smart mode: source already meets profile '{}', copying the video stream",
+                    selected_profile.name
+                );
+                self.explain(
+                    Decision::new(
+                        DecisionTopic::Profile,
+                        "copy the video stream",
+                        format!("source already meets profile '{}'", selected_profile.name),
+                    )
+                    .with_rule("--smart"),
+                );
+                self.passthrough = true;
+                self.analysis_cache
+                    .store(self.input_path, &self.cached_analysis);
+                return self.run_remux(&metadata, job_dir).await;
+            }
+            log_step!("Smart mode: re-encoding ({})", blockers.join("; "));
+            self.explain(
+                Decision::new(DecisionTopic::Profile, "re-encode", blockers.join("; "))
+                    .with_rule("--smart"),
+            );
+            if is_profile5 && !self.options.dv5_convert {
+                return Err(profile5_refusal());
+            }
+        }
+        if !matches!(
+            content_analysis.recommended_approach,
+            ContentEncodingApproach::SDR
+        ) {
+            for message in x265_params::enforce_hdr_bit_depth(
+                &mut selected_profile.x265_params,
+                self.config.encoding.allow_8bit_hdr,
+            )? {
+                tracing::warn!("Profile '{}': {}", selected_profile.name, message);
+            }
+            if self.config.encoding.hdr_signaling {
+                let pq = self.dv5_reshape
+                    || !metadata
+                        .transfer_function
+                        .as_deref()
+                        .is_some_and(|transfer| transfer.contains("arib-std-b67"));
+                let (added, conflicts) =
+                    x265_params::add_hdr_signaling(&mut selected_profile.x265_params, pq);
+                if !added.is_empty() {
+                    info!("HDR signaling added to x265 params: {}", added.join(", "));
+                }
+                for conflict in conflicts {
+                    tracing::warn!("Profile '{}': {}", selected_profile.name, conflict);
+                }
+            }
+        }
+        if self.dv5_reshape {
+            log_step!("Dolby Vision profile 5: reshaping to HDR10 with libplacebo (--dv5-convert)");
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Filter,
+                    "reshape to HDR10",
+                    "Dolby Vision profile 5 source",
+                )
+                .with_rule("--dv5-convert"),
+            );
+            // The reshaped picture is BT.2020 PQ whatever the source signals
+            for (key, value) in [
+                ("colorprim", "bt2020"),
+                ("transfer", "smpte2084"),
+                ("colormatrix", "bt2020nc"),
+            ] {
+                selected_profile
+                    .x265_params
+                    .insert(key.to_string(), value.to_string());
+            }
+        }
+        if self.tonemap {
+            sdr_x265_params(&mut selected_profile);
+        }
+
+        let metadata_workflow = self.initialize_metadata_workflow(job_dir).await?;
+        let mut extracted_metadata = metadata_workflow
             .extract_metadata(
                 self.input_path,
                 &content_analysis.recommended_approach,
@@ -79,17 +461,108 @@ impl<'a> VideoProcessor<'a> {
                 &content_analysis.hdr_analysis,
             )
             .await?;
+        self.trim_dynamic_metadata(&metadata_workflow, &mut extracted_metadata, &metadata)
+            .await?;
+        if self.dv5_reshape {
+            metadata_workflow
+                .convert_profile5_rpu(&mut extracted_metadata)
+                .await?;
+        }
+        if let Some(step) = extracted_metadata.fallback {
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Metadata,
+                    format!("output {}", step),
+                    "the source's dynamic metadata could not be carried over",
+                )
+                .with_rule("analysis.fallback_chain"),
+            );
+        }
+        if extracted_metadata.fallback == Some(MetadataFallback::Sdr) && !self.tonemap {
+            log_step!("Tone mapping to SDR (BT.709) instead (analysis.fallback_chain)");
+            self.tonemap = true;
+            metadata = tonemap_to_sdr(&mut content_analysis, metadata);
+            sdr_x265_params(&mut selected_profile);
+        }
+        if crop_values.is_some()
+            && !metadata_workflow
+                .adjust_rpu_for_crop(&mut extracted_metadata)
+                .await
+        {
+            tracing::warn!("Not cropping: the Dolby Vision RPU would describe the uncropped frame");
+            crop_values = None;
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Crop,
+                    "no crop",
+                    "the Dolby Vision RPU's active area could not be updated",
+                )
+                .with_rule("analysis.crop_detection.dolby_vision"),
+            );
+        }
+        self.apply_device_dolby_vision(&metadata_workflow, &mut extracted_metadata)?;
+        if extracted_metadata
+            .dolby_vision
+            .as_ref()
+            .is_some_and(|rpu| rpu.extracted_successfully)
+        {
+            for warning in x265_params::enforce_dolby_vision_gop(
+                &mut selected_profile.x265_params,
+                metadata.fps,
+            ) {
+                tracing::warn!("Profile '{}': {}", selected_profile.name, warning);
+            }
+        }
 
         self.log_content_analysis(&metadata, &content_analysis);
 
-        let selected_profile = self.select_profile(&metadata).await?;
-        let file_logger = FileLogger::new(self.output_path)?;
+        let needs_post_processing = metadata_workflow.needs_post_processing(&extracted_metadata);
+
+        self.detect_scene_cuts().await;
+        let crf_reduction = self.check_starved_source(&metadata).await;
+        self.analysis_cache
+            .store(self.input_path, &self.cached_analysis);
+        self.apply_profile_container(&selected_profile, needs_post_processing)?;
+        let file_logger = self.create_file_logger()?;
 
-        let adaptive_crf =
-            selected_profile.base_crf + content_analysis.encoding_adjustments.crf_adjustment;
-        let adaptive_bitrate = ((selected_profile.bitrate as f32)
+        let adaptive_crf = (selected_profile.base_crf
+            + content_analysis.encoding_adjustments.crf_adjustment
+            - crf_reduction)
+            .max(0.0);
+        let uncapped_bitrate = ((selected_profile.bitrate as f32)
             * content_analysis.encoding_adjustments.bitrate_multiplier)
             as u32;
+        let adaptive_bitrate = selected_profile.cap_bitrate(uncapped_bitrate);
+        if adaptive_bitrate < uncapped_bitrate {
+            info!(
+                "Bitrate {}kbps capped to the profile's max_bitrate of {}kbps",
+                uncapped_bitrate, adaptive_bitrate
+            );
+        }
+        self.explain_rate(
+            &content_analysis,
+            &selected_profile,
+            (adaptive_crf, adaptive_bitrate),
+            uncapped_bitrate,
+        );
+        self.profile_used = Some(selected_profile.name.clone());
+        self.emit(EncodeEvent::ProfileSelected {
+            profile: selected_profile.name.clone(),
+            crf: adaptive_crf,
+            bitrate: adaptive_bitrate,
+        });
+
+        let encoding_mode = self.get_encoding_mode()?;
+        // CBR already pins vbv-maxrate to the (capped) target bitrate
+        if encoding_mode != EncodingMode::CBR {
+            selected_profile.apply_max_bitrate_vbv();
+        }
+        for warning in
+            x265_params::sanitize_rate_control(&mut selected_profile.x265_params, encoding_mode)
+        {
+            tracing::warn!("Profile '{}': {}", selected_profile.name, warning);
+            file_logger.log_encoding_progress(&warning)?;
+        }
 
         self.log_parameter_adjustments(
             &content_analysis,
@@ -106,9 +579,26 @@ impl<'a> VideoProcessor<'a> {
             self.build_x265_params_preview(&selected_profile, &metadata, is_advanced_content);
         self.log_x265_params(&content_analysis, &x265_params_preview, is_advanced_content);
 
-        let filter_chain = self.build_filter_chain(crop_values.as_deref())?;
-        let encoding_mode = self.get_encoding_mode()?;
-        let stream_mapping = self.analyze_streams().await?;
+        let filter_chain = self.build_filter_chain(crop_values.as_deref(), &metadata)?;
+        let mut stream_mapping = self.analyze_streams().await?;
+        self.adapt_streams_to_target(&mut stream_mapping, &file_logger, job_dir)
+            .await?;
+        self.explain_streams(&stream_mapping);
+        self.apply_device_audio(&mut stream_mapping, &file_logger)?;
+        self.apply_audio_titles(&mut stream_mapping, &file_logger)?;
+        stream_mapping.mapping_args.extend(
+            Provenance::for_encode(
+                self.input_path,
+                &selected_profile,
+                encoding_mode,
+                adaptive_crf,
+                adaptive_bitrate,
+            )
+            .metadata_args(),
+        );
+        let caption_file = self
+            .apply_closed_caption_policy(&mut stream_mapping, &file_logger, job_dir)
+            .await?;
 
         self.log_initial_settings(
             &file_logger,
@@ -126,27 +616,93 @@ impl<'a> VideoProcessor<'a> {
             is_advanced_content,
         )?;
 
-        let needs_post_processing = metadata_workflow.needs_post_processing(&extracted_metadata);
+        // RPU injection already remuxes through mkvmerge
+        let mkvmerge = if needs_post_processing {
+            None
+        } else {
+            self.final_muxer(Some("hevc")).await
+        };
+        let mux_paths = mkvmerge.as_ref().map(|_| self.mux_temp_paths(job_dir));
         let actual_output_path = if needs_post_processing {
-            metadata_workflow.get_temp_output_path(self.output_path)
+            metadata_workflow.get_temp_output_path(&self.output_path)
+        } else if let Some((_, streams_path)) = &mux_paths {
+            streams_path.clone()
         } else {
             self.output_path.to_path_buf()
         };
+        let video_output = mux_paths
+            .as_ref()
+            .map(|(video_path, _)| video_path.as_path());
 
         let external_metadata_params =
             metadata_workflow.build_external_metadata_params(&extracted_metadata);
+        if external_metadata_params
+            .iter()
+            .any(|(key, _)| key == "dhdr10-info")
+        {
+            self.record_hdr10plus_profile(&extracted_metadata);
+        }
         let external_params_ref = if external_metadata_params.is_empty() {
             None
         } else {
             Some(external_metadata_params.as_slice())
         };
 
+        let disk_guard = self.disk_space_guard(&actual_output_path);
+        if let Some(guard) = &disk_guard {
+            guard.check(None).await?;
+        }
+        let frame_count = match self.trimmed_frames(&metadata) {
+            Some((frames, _)) => Some(frames.len() as u32),
+            None => self.exact_frame_count(&stream_mapping).await,
+        };
+
+        if self.config.encoding.preflight_probe {
+            log_step!(
+                "Preflight probe: encoding {}s with the final arguments (encoding.preflight_probe)",
+                PREFLIGHT_PROBE_SECONDS
+            );
+            let probe_path = job_dir.path().join(format!(
+                "preflight_probe.{}",
+                actual_output_path
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .unwrap_or("mkv")
+            ));
+            let probe_ffmpeg = self
+                .ffmpeg
+                .clone()
+                .with_output_duration(Some(PREFLIGHT_PROBE_SECONDS));
+            let child = self
+                .start_encoding(
+                    &probe_ffmpeg,
+                    &probe_path,
+                    None,
+                    &selected_profile,
+                    &filter_chain,
+                    &stream_mapping,
+                    &metadata,
+                    adaptive_crf,
+                    adaptive_bitrate,
+                    encoding_mode,
+                    &file_logger,
+                    external_params_ref,
+                )
+                .await;
+            let result = check_preflight_probe(child).await;
+            let _ = tokio::fs::remove_file(&probe_path).await;
+            result?;
+            file_logger.log_encoding_progress("Preflight probe passed")?;
+        }
+
         // Start timer for encoding duration
         let encoding_start = std::time::Instant::now();
 
         let child = self
             .start_encoding(
+                self.ffmpeg,
                 &actual_output_path,
+                video_output,
                 &selected_profile,
                 &filter_chain,
                 &stream_mapping,
@@ -159,22 +715,58 @@ impl<'a> VideoProcessor<'a> {
             )
             .await?;
 
-        let mut progress_monitor = self.create_progress_monitor(&metadata, encoding_mode);
-        let status = progress_monitor.monitor_encoding(child).await?;
+        let mut progress_monitor =
+            self.create_progress_monitor(&metadata, encoding_mode, frame_count);
+        if let Some(guard) = disk_guard {
+            progress_monitor.add_abort_condition(Box::new(guard));
+        }
+        let status = match progress_monitor.monitor_encoding(child).await {
+            Ok(status) => status,
+            Err(e) => {
+                remove_partial_output(&actual_output_path).await;
+                if let Some(video_path) = video_output {
+                    remove_partial_output(video_path).await;
+                }
+                return Err(e);
+            }
+        };
+        self.report_discarded_frames(&progress_monitor, &file_logger)?;
+        self.frames_encoded = progress_monitor.frames_encoded();
+        let mut phases = vec![("encode", encoding_start.elapsed())];
+
+        let hdr10_fallback_path = self
+            .options
+            .dv_hdr10_fallback
+            .then(|| metadata_workflow.get_hdr10_fallback_path(&self.output_path));
+        if self.options.dv_hdr10_fallback && !needs_post_processing {
+            info!("--dv-hdr10-fallback: no Dolby Vision RPU to inject, writing a single output");
+        }
 
         if status.success() && needs_post_processing {
+            let injection_start = std::time::Instant::now();
             match metadata_workflow
                 .inject_metadata(
                     &actual_output_path,
-                    &self.output_path.to_path_buf(),
+                    &self.output_path,
                     &extracted_metadata,
                     metadata.fps,
+                    hdr10_fallback_path.as_deref(),
                 )
                 .await
             {
-                Ok(_) => {}
+                Ok(Some(profile)) => {
+                    self.emit(EncodeEvent::MetadataInjected { profile });
+                    self.sidecar_files
+                        .extend(hdr10_fallback_path.clone().filter(|path| path.exists()));
+                }
+                Ok(None) => {}
                 Err(e) => {
-                    if actual_output_path.exists() {
+                    if self.options.keep_temp {
+                        info!(
+                            "Kept the temporary encode: {}",
+                            actual_output_path.display()
+                        );
+                    } else if actual_output_path.exists() {
                         let _ = tokio::fs::remove_file(&actual_output_path).await;
                         tracing::debug!(
                             "Cleaned up temporary file after metadata injection failure: {}",
@@ -184,8 +776,17 @@ impl<'a> VideoProcessor<'a> {
                     return Err(e);
                 }
             }
-        } else if needs_post_processing && !status.success() && actual_output_path.exists() {
-            if let Err(e) = tokio::fs::remove_file(&actual_output_path).await {
+            phases.push(("metadata injection", injection_start.elapsed()));
+        } else if actual_output_path != self.output_path
+            && !status.success()
+            && actual_output_path.exists()
+        {
+            if self.options.keep_temp {
+                info!(
+                    "Kept the temporary encode: {}",
+                    actual_output_path.display()
+                );
+            } else if let Err(e) = tokio::fs::remove_file(&actual_output_path).await {
                 tracing::warn!(
                     "Failed to clean up temporary file after encoding failure: {}",
                     e
@@ -197,74 +798,710 @@ impl<'a> VideoProcessor<'a> {
                 );
             }
         }
+        if let (Some(mkvmerge), Some((video_path, streams_path))) = (&mkvmerge, &mux_paths) {
+            if status.success() {
+                let mux_start = std::time::Instant::now();
+                self.mux_with_mkvmerge(mkvmerge, video_path, streams_path, metadata.fps)
+                    .await?;
+                phases.push(("mkvmerge mux", mux_start.elapsed()));
+            } else if !self.options.keep_temp {
+                remove_partial_output(video_path).await;
+            }
+        }
 
         let encoding_duration = encoding_start.elapsed();
-        self.finalize_logging(&file_logger, status, encoding_duration)?;
+        self.finalize_logging(
+            &file_logger,
+            status,
+            progress_monitor.ffmpeg_errors(),
+            encoding_duration,
+            &phases,
+        )?;
+        self.report_stream_changes(&stream_mapping, &file_logger)
+            .await?;
+        self.verify_streams(&stream_mapping, &file_logger).await?;
+        let quality_check = self.options.quality_check || self.config.quality.enabled;
+        if quality_check && self.options.is_trimmed() {
+            info!("Skipping the quality check: the output covers only part of the source");
+        } else if quality_check {
+            self.measure_quality(
+                &metadata,
+                &filter_chain,
+                &stream_mapping,
+                &file_logger,
+                job_dir,
+            )
+            .await;
+        }
 
-        metadata_workflow.cleanup().await?;
-        extracted_metadata.cleanup();
+        // With --keep-temp the job directory keeps the RPU and HDR10+ metadata
+        if !self.options.keep_temp {
+            metadata_workflow.cleanup().await?;
+            extracted_metadata.cleanup();
+            if let Some(path) = caption_file {
+                let _ = tokio::fs::remove_file(path).await;
+            }
+        }
 
         Ok(())
     }
 
-    async fn get_metadata(&self) -> Result<VideoMetadata> {
-        info!("Getting video metadata for: {}", self.input_path.display());
-        self.ffmpeg.get_video_metadata(self.input_path).await
+    /// Stream-copy path for `--video copy`: no content analysis, crop detection or
+    /// x265, just the selected stream mapping with chapters, metadata and title.
+    async fn run_remux(&mut self, metadata: &VideoMetadata, job_dir: &JobTempDir) -> Result<()> {
+        if self.options.normalize_audio {
+            info!("Audio normalization mode: copying video, re-encoding audio");
+        } else if self.passthrough {
+            info!("Smart passthrough: remuxing with stream selection and metadata only");
+        } else {
+            info!("Video copy mode: remuxing without re-encoding");
+        }
+
+        // An explicit profile may still pin the output container
+        let mut profile_name = None;
+        if !self.options.is_auto_profile() {
+            let mut profile = self.select_profile(metadata).await?;
+            profile_name = Some(profile.name.clone());
+            self.profile_stream_selection = profile.stream_selection_profile.clone();
+            if let Some(device) = &self.device {
+                device.apply_to_profile(&mut profile);
+            }
+            self.apply_profile_container(&profile, false)?;
+        }
+
+        let file_logger = self.create_file_logger()?;
+        let mut stream_mapping = self.analyze_streams().await?;
+        self.adapt_streams_to_target(&mut stream_mapping, &file_logger, job_dir)
+            .await?;
+        self.explain_streams(&stream_mapping);
+
+        if self.options.normalize_audio {
+            info!("Audio normalization: measuring loudness (pass 1/2)...");
+            let bitrates = LoudnormNormalizer::new(
+                self.ffmpeg.clone(),
+                self.config.audio.normalization.clone(),
+            )
+            .with_bitrate_ladder(self.audio_bitrate_ladder()?)
+            .apply_to_mapping(self.input_path, &mut stream_mapping)
+            .await?;
+            for bitrate in &bitrates {
+                file_logger.log_encoding_progress(&format!("Audio transcode: {}", bitrate))?;
+            }
+        }
+        self.apply_device_audio(&mut stream_mapping, &file_logger)?;
+        self.apply_audio_titles(&mut stream_mapping, &file_logger)?;
+        stream_mapping.mapping_args.extend(
+            Provenance::for_remux(self.input_path, profile_name.as_deref()).metadata_args(),
+        );
+
+        let mkvmerge = self.final_muxer(metadata.codec.as_deref()).await;
+        let mux_paths = mkvmerge.as_ref().map(|_| self.mux_temp_paths(job_dir));
+        let target_path = match &mux_paths {
+            Some((_, streams_path)) => streams_path.clone(),
+            None => self.output_path.clone(),
+        };
+        let video_output = mux_paths
+            .as_ref()
+            .map(|(video_path, _)| video_path.as_path());
+
+        // A remux writes roughly the source size, so warn early if that will not fit
+        let disk_guard = self.disk_space_guard(&target_path);
+        if let Some(guard) = &disk_guard {
+            let source_size = std::fs::metadata(self.input_path).map(|m| m.len()).ok();
+            guard.check(source_size).await?;
+        }
+
+        let frame_count = match self.trimmed_frames(metadata) {
+            Some((frames, _)) => Some(frames.len() as u32),
+            None => self.exact_frame_count(&stream_mapping).await,
+        };
+        let remux_start = std::time::Instant::now();
+        let child = remux::start_remux(
+            self.ffmpeg,
+            self.input_path,
+            &target_path,
+            &stream_mapping,
+            self.options.title.as_deref(),
+            metadata.codec.as_deref(),
+            video_output,
+            Some(&file_logger),
+        )
+        .await?;
+
+        let mut progress_monitor =
+            self.create_progress_monitor(metadata, EncodingMode::CRF, frame_count);
+        if let Some(guard) = disk_guard {
+            progress_monitor.add_abort_condition(Box::new(guard));
+        }
+        let status = match progress_monitor.monitor_encoding(child).await {
+            Ok(status) => status,
+            Err(e) => {
+                remove_partial_output(&target_path).await;
+                if let Some(video_path) = video_output {
+                    remove_partial_output(video_path).await;
+                }
+                return Err(e);
+            }
+        };
+        self.report_discarded_frames(&progress_monitor, &file_logger)?;
+        self.frames_encoded = progress_monitor.frames_encoded();
+        let mut phases = vec![("remux", remux_start.elapsed())];
+
+        if let (Some(mkvmerge), Some((video_path, streams_path))) = (&mkvmerge, &mux_paths) {
+            if status.success() {
+                let mux_start = std::time::Instant::now();
+                self.mux_with_mkvmerge(mkvmerge, video_path, streams_path, metadata.fps)
+                    .await?;
+                phases.push(("mkvmerge mux", mux_start.elapsed()));
+            } else {
+                remove_partial_output(video_path).await;
+                remove_partial_output(streams_path).await;
+            }
+        }
+
+        self.finalize_logging(
+            &file_logger,
+            status,
+            progress_monitor.ffmpeg_errors(),
+            remux_start.elapsed(),
+            &phases,
+        )?;
+        self.report_stream_changes(&stream_mapping, &file_logger)
+            .await?;
+        self.verify_streams(&stream_mapping, &file_logger).await
+    }
+
+    /// Thumbnails of the finished output; failing to make them leaves the
+    /// encode intact.
+    /// Remembers the profile of the HDR10+ metadata given to x265, for the
+    /// encode report.
+    fn record_hdr10plus_profile(&mut self, extracted_metadata: &ExtractedMetadata) {
+        let Some(hdr10plus) = &extracted_metadata.hdr10_plus else {
+            return;
+        };
+        let profile = hdr10plus.summary.detected_profile();
+        self.hdr10plus_profile = Some(profile);
+        if profile.needs_dhdr10_opt() {
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Metadata,
+                    "--dhdr10-opt",
+                    format!(
+                        "HDR10+ {}: the SEI is written on IDR frames and metadata changes only",
+                        profile
+                    ),
+                )
+                .with_rule("analysis.hdr10_plus"),
+            );
+        }
     }
 
-    async fn initialize_metadata_workflow(&self) -> Result<MetadataWorkflowManager> {
-        info!("Initializing metadata workflow manager...");
-        MetadataWorkflowManager::new(self.config).await
+    /// `output_files`: gives the output, its log and sidecar files the
+    /// configured permissions and owner; failures only warn.
+    fn apply_output_files(&self) {
+        let settings = &self.config.output_files;
+        if *settings == crate::config::OutputFilesConfig::default() {
+            return;
+        }
+        let log = self.output_path.with_extension("log");
+        let files = std::iter::once(&self.output_path)
+            .chain(Some(&log).filter(|log| log.exists()))
+            .chain(&self.sidecar_files);
+        for file in files {
+            if let Err(e) = crate::utils::filesystem::apply_output_files_config(file, settings) {
+                tracing::warn!(
+                    "Failed to set permissions/owner of {} (output_files): {}",
+                    file.display(),
+                    e
+                );
+            }
+        }
     }
 
-    fn log_content_analysis(
-        &self,
+    /// Writes the crop report next to the log; failures only warn.
+    async fn save_crop_report(
+        &mut self,
         metadata: &VideoMetadata,
-        content_analysis: &crate::ContentAnalysisResult,
+        crop_analysis: &crate::analysis::CropAnalysisResult,
     ) {
-        match &content_analysis.recommended_approach {
-            ContentEncodingApproach::SDR => {
-                info!("SDR CONTENT DETECTED");
-            }
-            ContentEncodingApproach::HDR(hdr_result) => {
-                info!("HDR CONTENT DETECTED");
-                info!("  Format: {:?}", hdr_result.metadata.format);
-                if let Some(ref color_space) = metadata.color_space {
-                    info!("  Color Space: {}", color_space);
+        match write_crop_report(
+            self.config,
+            self.input_path,
+            metadata,
+            crop_analysis,
+            &self.output_path,
+        )
+        .await
+        {
+            Ok(files) => {
+                if let Some(page) = files.first() {
+                    info!("Crop report: {}", page.display());
                 }
+                self.sidecar_files.extend(files);
             }
-            ContentEncodingApproach::DolbyVision(dv_info) => {
-                info!("DOLBY VISION CONTENT DETECTED");
-                info!("  Profile: {}", dv_info.profile.as_str());
+            Err(e) => tracing::warn!("Failed to write the crop report: {}", e),
+        }
+    }
+
+    async fn write_thumbnails(&mut self) {
+        log_step!("Generating thumbnails...");
+        // The output's encoded stream is its first, whatever --video-stream picked
+        let ffmpeg = self.ffmpeg.clone().with_video_stream(None);
+        let result = match ffmpeg.get_video_metadata(&self.output_path).await {
+            Ok(metadata) => generate_thumbnails(self.config, &self.output_path, &metadata).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(files) => {
+                for file in &files {
+                    info!("Thumbnails written to: {}", file.display());
+                }
+                self.sidecar_files.extend(files);
             }
-            ContentEncodingApproach::DolbyVisionWithHDR10Plus(dv_info, _) => {
-                info!("DUAL FORMAT CONTENT DETECTED: DOLBY VISION + HDR10+");
-                info!("  Dolby Vision Profile: {}", dv_info.profile.as_str());
+            Err(e) => tracing::warn!("Failed to generate thumbnails: {}", e),
+        }
+    }
+
+    /// Scores the finished output against the source with the metrics of
+    /// `quality`; a failed measurement leaves the encode intact.
+    async fn measure_quality(
+        &mut self,
+        metadata: &VideoMetadata,
+        filter_chain: &FilterChain,
+        stream_mapping: &crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+        job_dir: &JobTempDir,
+    ) {
+        log_step!("Measuring quality against the source...");
+        let reference_stream = stream_mapping.primary_video_specifier();
+        let comparison = QualityComparison::new(self.config, &self.output_path, self.input_path)
+            .with_reference_stream(reference_stream.trim_start_matches("0:"))
+            .with_reference_filters((!filter_chain.is_empty()).then(|| filter_chain.to_string()))
+            .with_hdr_transfer(
+                metadata
+                    .is_hdr
+                    .then(|| metadata.transfer_function.clone().unwrap_or_default()),
+            );
+        match comparison
+            .measure(&self.config.quality.metrics, job_dir.path())
+            .await
+        {
+            Ok(scores) => {
+                let summary = scores
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let line = format!("Quality: {}", summary);
+                info!("{}", line);
+                if let Err(e) = file_logger.log_encoding_progress(&line) {
+                    tracing::warn!("Failed to log quality scores: {}", e);
+                }
+                self.quality_scores = scores;
             }
+            Err(e) => tracing::warn!("Failed to measure quality: {}", e),
         }
     }
 
-    async fn select_profile(&self, metadata: &VideoMetadata) -> Result<EncodingProfile> {
-        if self.args.profile == "auto" {
-            info!("Auto-selecting profile based on content analysis...");
+    /// mkvmerge for the final mux when `streams.muxer` selects it; `None`
+    /// leaves the mux to FFmpeg. mkvmerge takes the video as a raw HEVC
+    /// stream, so other `video_codec`s are muxed by FFmpeg as well.
+    async fn final_muxer(&self, video_codec: Option<&str>) -> Option<MkvMergeTool> {
+        if self.config.streams.muxer != Muxer::Mkvmerge {
+            return None;
+        }
+        if OutputContainer::from_path(&self.output_path) != Some(OutputContainer::Mkv) {
+            tracing::warn!(
+                "mkvmerge only writes Matroska; {} is muxed by FFmpeg",
+                self.output_path.display()
+            );
+            return None;
+        }
+        if video_codec != Some("hevc") {
+            tracing::warn!(
+                "mkvmerge muxes HEVC video only; {} video is muxed by FFmpeg",
+                video_codec.unwrap_or("unknown")
+            );
+            return None;
+        }
 
-            let content_analyzer = ContentAnalyzer::new();
-            let classification = content_analyzer.classify_content(metadata).await?;
-            let content_type = classification.content_type;
+        let mkvmerge = MkvMergeTool::new(self.config.tools.mkvmerge.clone().unwrap_or_default())
+            .with_cancel_token(self.cancel.clone())
+            .with_progress(self.tool_progress());
+        if !mkvmerge.check_availability().await.unwrap_or(false) {
+            tracing::warn!("mkvmerge is not available; the output is muxed by FFmpeg");
+            return None;
+        }
+        Some(mkvmerge)
+    }
+
+    /// Where FFmpeg writes the raw HEVC video and the other streams when
+    /// mkvmerge does the final mux
+    fn mux_temp_paths(&self, job_dir: &JobTempDir) -> (PathBuf, PathBuf) {
+        let filename = self
+            .output_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "output.mkv".to_string());
+        let video_name = Path::new(&filename).with_extension("hevc");
+        (
+            self.output_path
+                .with_file_name(job_dir.scoped_name("temp_mux", &video_name.to_string_lossy())),
+            self.output_path
+                .with_file_name(job_dir.scoped_name("temp_mux", &filename)),
+        )
+    }
+
+    /// Muxes FFmpeg's raw HEVC video and the file with the other streams into
+    /// the final output in one mkvmerge pass. The temporary files are removed
+    /// afterwards unless `--keep-temp` is set.
+    async fn mux_with_mkvmerge(
+        &self,
+        mkvmerge: &MkvMergeTool,
+        video_path: &Path,
+        streams_path: &Path,
+        fps: f32,
+    ) -> Result<()> {
+        log_step!("Muxing final output with mkvmerge...");
+        let result = mkvmerge
+            .mux_hevc_with_streams(video_path, streams_path, &self.output_path, fps)
+            .await;
+        if result.is_err() {
+            remove_partial_output(&self.output_path).await;
+        }
+        if self.options.keep_temp {
+            info!(
+                "Kept FFmpeg's output: {} + {}",
+                video_path.display(),
+                streams_path.display()
+            );
+        } else {
+            for path in [video_path, streams_path] {
+                if let Err(e) = tokio::fs::remove_file(path).await {
+                    tracing::warn!("Failed to remove temporary file {}: {}", path.display(), e);
+                }
+            }
+        }
+        result
+    }
+
+    fn disk_space_guard(&self, output_path: &Path) -> Option<DiskSpaceGuard> {
+        let disk_space = &self.config.disk_space;
+        // `output_path` may be a temporary file on another volume than the final output
+        disk_space
+            .enabled
+            .then(|| DiskSpaceGuard::new(disk_space, &[output_path, &self.output_path]))
+    }
+
+    /// With `--error-resilience`, corrupt input is skipped silently by FFmpeg, so the
+    /// frames that went missing are counted and reported explicitly.
+    fn report_discarded_frames(
+        &self,
+        progress_monitor: &ProgressMonitor,
+        file_logger: &FileLogger,
+    ) -> Result<()> {
+        if !self.options.error_resilience {
+            return Ok(());
+        }
+
+        let dropped = progress_monitor.dropped_frames().unwrap_or(0);
+        let missing = match (
+            progress_monitor.expected_frames(),
+            progress_monitor.frames_encoded(),
+        ) {
+            (Some(expected), Some(encoded)) => expected.saturating_sub(encoded),
+            _ => 0,
+        };
+        let discarded = dropped.max(missing);
+
+        let message = format!(
+            "Error resilience: {} frame(s) discarded ({} dropped by FFmpeg, {} missing vs. expected {}; encoded {})",
+            discarded,
+            dropped,
+            missing,
+            progress_monitor
+                .expected_frames()
+                .map_or("unknown".to_string(), |f| f.to_string()),
+            progress_monitor
+                .frames_encoded()
+                .map_or("unknown".to_string(), |f| f.to_string())
+        );
+        if discarded > 0 {
+            tracing::warn!("{}", message);
+            tracing::warn!("Output was salvaged from a damaged source; check it for glitches");
+        } else {
+            info!("{}", message);
+        }
+        file_logger.log_encoding_progress(&message)
+    }
+
+    /// Prints which source streams the output kept, transcoded or dropped.
+    async fn report_stream_changes(
+        &self,
+        stream_mapping: &crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+    ) -> Result<()> {
+        let probes = tokio::try_join!(
+            self.stream_preservation.get_stream_info(self.input_path),
+            self.stream_preservation.get_stream_info(&self.output_path)
+        );
+        let (source, output) = match probes {
+            Ok(streams) => streams,
+            Err(e) => {
+                tracing::warn!("Could not compare source and output streams: {}", e);
+                return Ok(());
+            }
+        };
+
+        let rows = stream_diff::diff_streams(&source, stream_mapping, &output);
+        info!("Streams (source -> output):");
+        for line in stream_diff::render(&rows, self.config.logging.colored_output) {
+            info!("  {}", line);
+        }
+        for line in stream_diff::render(&rows, false) {
+            file_logger.log_encoding_progress(&format!("Stream: {}", line))?;
+        }
+
+        let lost = rows
+            .iter()
+            .filter(|row| row.change == stream_diff::StreamChange::Lost)
+            .count();
+        if lost > 0 {
+            tracing::warn!(
+                "{} selected stream(s) are missing from the output, see the stream table above",
+                lost
+            );
+        }
+        Ok(())
+    }
+
+    async fn verify_streams(
+        &self,
+        stream_mapping: &crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+    ) -> Result<()> {
+        if !self.options.verify_streams {
+            return Ok(());
+        }
+
+        info!("Verifying copied streams against the source...");
+        let results = StreamVerifier::new(self.ffmpeg.clone())
+            .verify_copied_streams(self.input_path, self.output_path.as_path(), stream_mapping)
+            .await?;
+        file_logger.log_stream_verification(&results)
+    }
+
+    /// Retargets an auto-generated output path to the profile's container/extension.
+    /// Explicit `-o` paths are kept as given.
+    fn apply_profile_container(
+        &mut self,
+        profile: &EncodingProfile,
+        needs_post_processing: bool,
+    ) -> Result<()> {
+        let explicit_output = self.options.explicit_output;
+        let original_path = self.output_path.clone();
+
+        if let Some(extension) = profile.output_extension() {
+            if explicit_output {
+                if self.output_path.extension().and_then(|e| e.to_str()) != Some(extension) {
+                    tracing::warn!(
+                        "Profile '{}' prefers .{} outputs, keeping explicit output path {}",
+                        profile.name,
+                        extension,
+                        self.output_path.display()
+                    );
+                }
+            } else {
+                self.output_path.set_extension(extension);
+            }
+        }
+
+        // RPU injection remuxes through mkvmerge, which can only write Matroska
+        if needs_post_processing
+            && OutputContainer::from_path(&self.output_path) == Some(OutputContainer::Mp4)
+        {
+            if explicit_output {
+                return Err(Error::validation(format!(
+                    "Dolby Vision output requires a Matroska container, but {} is MP4",
+                    self.output_path.display()
+                )));
+            }
+            tracing::warn!(
+                "Dolby Vision RPU injection requires Matroska; writing .mkv instead of MP4"
+            );
+            self.output_path
+                .set_extension(OutputContainer::Mkv.extension());
+        }
+
+        if self.output_path != original_path {
+            info!("Output file: {}", self.output_path.display());
+        }
+
+        Ok(())
+    }
+
+    async fn get_metadata(&self) -> Result<VideoMetadata> {
+        self.emit(EncodeEvent::AnalysisStarted {
+            input: self.input_path.to_path_buf(),
+        });
+        self.ffmpeg.get_video_metadata(self.input_path).await
+    }
+
+    async fn initialize_metadata_workflow(
+        &self,
+        job_dir: &JobTempDir,
+    ) -> Result<MetadataWorkflowManager> {
+        log_stage!("Initializing metadata workflow manager...");
+        Ok(MetadataWorkflowManager::new(
+            self.config,
+            self.ffmpeg,
+            &self.cancel,
+            &self.tool_progress(),
+            job_dir,
+        )
+        .await?
+        .with_strict(self.options.strict_metadata)
+        .with_keep_temp(self.options.keep_temp))
+    }
+
+    /// Progress reporting for the tools run around the encode: on the events,
+    /// and on their own bar only when this encode draws the only one.
+    fn tool_progress(&self) -> ToolProgress {
+        ToolProgress {
+            events: self.events.clone(),
+            show_bar: self.show_progress_bar && self.batch_progress.is_none(),
+        }
+    }
+
+    /// Frames of the source a `--start`/`--end` encode covers, with the
+    /// source's frame count (counted with `analysis.count_frames`, or
+    /// estimated from the duration).
+    fn trimmed_frames(&self, metadata: &VideoMetadata) -> Option<(Range<u32>, u32)> {
+        let total = self
+            .cached_analysis
+            .frame_count
+            .unwrap_or_else(|| (metadata.duration * metadata.fps as f64).round() as u32);
+        let frames = self.options.trimmed_frames(metadata.fps, total)?;
+        Some((frames, total))
+    }
+
+    /// With `--start`/`--end`, cuts the extracted Dolby Vision RPU and HDR10+
+    /// metadata down to the encoded frames, so they stay in sync with the
+    /// output.
+    async fn trim_dynamic_metadata(
+        &self,
+        workflow: &MetadataWorkflowManager,
+        extracted: &mut ExtractedMetadata,
+        metadata: &VideoMetadata,
+    ) -> Result<()> {
+        let Some((frames, total)) = self.trimmed_frames(metadata) else {
+            return Ok(());
+        };
+        log_step!(
+            "Encoding frames {}-{} of {} (--start/--end)",
+            frames.start,
+            frames.end.saturating_sub(1),
+            total
+        );
+        workflow.trim_metadata(extracted, frames, total).await
+    }
+
+    fn log_content_analysis(
+        &self,
+        metadata: &VideoMetadata,
+        content_analysis: &crate::ContentAnalysisResult,
+    ) {
+        match &content_analysis.recommended_approach {
+            ContentEncodingApproach::SDR => {
+                log_stage!("SDR CONTENT DETECTED");
+            }
+            ContentEncodingApproach::HDR(hdr_result) => {
+                log_stage!("HDR CONTENT DETECTED");
+                info!("  Format: {:?}", hdr_result.metadata.format);
+                if let Some(ref color_space) = metadata.color_space {
+                    info!("  Color Space: {}", color_space);
+                }
+            }
+            ContentEncodingApproach::DolbyVision(dv_info) => {
+                log_stage!("DOLBY VISION CONTENT DETECTED");
+                info!("  Profile: {}", dv_info.profile.as_str());
+            }
+            ContentEncodingApproach::DolbyVisionWithHDR10Plus(dv_info, _) => {
+                log_stage!("DUAL FORMAT CONTENT DETECTED: DOLBY VISION + HDR10+");
+                info!("  Dolby Vision Profile: {}", dv_info.profile.as_str());
+            }
+        }
+    }
+
+    async fn select_profile(&mut self, metadata: &VideoMetadata) -> Result<EncodingProfile> {
+        if self.options.is_auto_profile() {
+            log_step!("Auto-selecting profile based on content analysis...");
+
+            let classification = match self.cached_analysis.classification.clone() {
+                Some(classification) => Some(classification),
+                None => {
+                    let classification = self.classifiers.classify(self.input_path, metadata).await;
+                    self.cached_analysis.classification = classification.clone();
+                    classification
+                }
+            };
+            let Some(classification) = classification else {
+                log_step!("No classifier classified the source, using default 'movie' profile");
+                self.explain(
+                    Decision::new(
+                        DecisionTopic::Profile,
+                        "profile 'movie'",
+                        "no classifier classified the source",
+                    )
+                    .with_rule("--profile auto"),
+                );
+                return self
+                    .profile_manager
+                    .get_profile("movie")
+                    .cloned()
+                    .ok_or_else(|| Error::profile("Default 'movie' profile not found"));
+            };
+            let content_type = classification.content_type;
 
             if let Some(profile) = self.profile_manager.recommend_profile_for_resolution(
                 metadata.width,
                 metadata.height,
                 content_type,
             ) {
-                info!(
+                log_step!(
                     "Selected profile based on content analysis: {} (confidence: {:.1}%)",
                     profile.name,
                     classification.confidence * 100.0
                 );
-                Ok(profile.clone())
+                let profile = profile.clone();
+                self.explain(
+                    Decision::new(
+                        DecisionTopic::Profile,
+                        format!("profile '{}'", profile.name),
+                        format!(
+                            "classified as {} by {} ({:.1}% confidence) at {}x{}",
+                            content_type.as_str(),
+                            classification.method,
+                            classification.confidence * 100.0,
+                            metadata.width,
+                            metadata.height
+                        ),
+                    )
+                    .with_rule("--profile auto"),
+                );
+                Ok(profile)
             } else {
-                info!("No specific profile found for content type, using default 'movie' profile");
+                log_step!(
+                    "No specific profile found for content type, using default 'movie' profile"
+                );
+                self.explain(
+                    Decision::new(
+                        DecisionTopic::Profile,
+                        "profile 'movie'",
+                        format!("no profile for {} content", content_type.as_str()),
+                    )
+                    .with_rule("--profile auto"),
+                );
                 self.profile_manager
                     .get_profile("movie")
                     .cloned()
@@ -272,158 +1509,910 @@ impl<'a> VideoProcessor<'a> {
             }
         } else {
             self.profile_manager
-                .get_profile(&self.args.profile)
-                .ok_or_else(|| Error::profile(format!("Profile '{}' not found", self.args.profile)))
+                .get_profile(&self.options.profile_name)
+                .ok_or_else(|| {
+                    Error::profile(format!("Profile '{}' not found", self.options.profile_name))
+                })
                 .cloned()
         }
     }
 
-    fn log_parameter_adjustments(
+    fn log_parameter_adjustments(
+        &self,
+        content_analysis: &crate::ContentAnalysisResult,
+        selected_profile: &EncodingProfile,
+        adaptive_crf: f32,
+        adaptive_bitrate: u32,
+    ) {
+        match &content_analysis.recommended_approach {
+            ContentEncodingApproach::SDR => {
+                log_stage!(
+                    "Using standard encoding parameters (SDR): CRF={:.1}, Bitrate={}kbps",
+                    adaptive_crf,
+                    adaptive_bitrate
+                );
+            }
+            _ => {
+                log_stage!("PARAMETER ADJUSTMENTS:");
+                info!(
+                    "  Base CRF: {} -> Adjusted CRF: {:.1} (+{:.1})",
+                    selected_profile.base_crf,
+                    adaptive_crf,
+                    content_analysis.encoding_adjustments.crf_adjustment
+                );
+                info!(
+                    "  Base Bitrate: {} -> Adjusted Bitrate: {} ({:.1}x multiplier)",
+                    selected_profile.bitrate,
+                    adaptive_bitrate,
+                    content_analysis.encoding_adjustments.bitrate_multiplier
+                );
+            }
+        }
+    }
+
+    /// Records how the CRF and bitrate came about.
+    fn explain_rate(
+        &mut self,
+        content_analysis: &crate::ContentAnalysisResult,
+        profile: &EncodingProfile,
+        (crf, bitrate): (f32, u32),
+        uncapped_bitrate: u32,
+    ) {
+        let adjustments = &content_analysis.encoding_adjustments;
+        let (content, section) = adjustment_source(&content_analysis.recommended_approach);
+        let rule = |key: &str| match section {
+            Some(section) => format!("{}.{}", section, key),
+            None => format!("profiles.{}", profile.name),
+        };
+        self.explain(
+            Decision::new(
+                DecisionTopic::Rate,
+                format!("CRF {:.1}", crf),
+                format!(
+                    "profile '{}' base CRF {:.1} {:+.1} for {} content",
+                    profile.name, profile.base_crf, adjustments.crf_adjustment, content
+                ),
+            )
+            .with_rule(rule("crf_adjustment")),
+        );
+        let bitrate_decision = if bitrate < uncapped_bitrate {
+            Decision::new(
+                DecisionTopic::Rate,
+                format!("{} kbps", bitrate),
+                format!(
+                    "{} kbps for {} content capped to the profile's maximum",
+                    uncapped_bitrate, content
+                ),
+            )
+            .with_rule(format!("profiles.{}.max_bitrate", profile.name))
+        } else {
+            Decision::new(
+                DecisionTopic::Rate,
+                format!("{} kbps", bitrate),
+                format!(
+                    "profile '{}' bitrate {} kbps x {:.2} for {} content",
+                    profile.name, profile.bitrate, adjustments.bitrate_multiplier, content
+                ),
+            )
+            .with_rule(rule("bitrate_multiplier"))
+        };
+        self.explain(bitrate_decision);
+    }
+
+    /// Records which audio and subtitle streams the output keeps and which
+    /// source streams were dropped.
+    fn explain_streams(&mut self, mapping: &StreamMapping) {
+        if !self.options.explain {
+            return;
+        }
+        let kept = match self.stream_selection_profile_name() {
+            Some(name) => format!("passes stream selection profile '{}'", name),
+            None => "no stream selection profile, every stream is kept".to_string(),
+        };
+        let mut decisions: Vec<Decision> = mapping
+            .audio_streams
+            .iter()
+            .chain(&mapping.subtitle_streams)
+            .map(|stream| {
+                Decision::new(
+                    DecisionTopic::Stream,
+                    format!(
+                        "keep {} #{} ({}, {})",
+                        stream.codec_type,
+                        stream.index,
+                        stream.codec_name,
+                        stream.language.as_deref().unwrap_or("und")
+                    ),
+                    kept.clone(),
+                )
+            })
+            .collect();
+        decisions.extend(mapping.dropped.iter().map(|stream| {
+            Decision::new(
+                DecisionTopic::Stream,
+                format!("drop {} #{}", stream.codec_type, stream.index),
+                "left out of the output",
+            )
+            .with_rule(stream.rule.clone())
+        }));
+        self.decisions.extend(decisions);
+    }
+
+    fn build_x265_params_preview(
+        &self,
+        selected_profile: &EncodingProfile,
+        metadata: &VideoMetadata,
+        is_advanced_content: bool,
+    ) -> String {
+        selected_profile.build_x265_params_string_with_hdr_passthrough(
+            None,
+            Some(is_advanced_content),
+            metadata.color_space.as_ref(),
+            metadata.transfer_function.as_ref(),
+            metadata.color_primaries.as_ref(),
+            metadata.master_display.as_ref(),
+            metadata.max_cll.as_ref(),
+            false, // Default to non-passthrough mode
+        )
+    }
+
+    fn log_x265_params(
+        &self,
+        content_analysis: &crate::ContentAnalysisResult,
+        x265_params_preview: &str,
+        is_advanced_content: bool,
+    ) {
+        if is_advanced_content {
+            match &content_analysis.recommended_approach {
+                ContentEncodingApproach::HDR(_) => log_step!("HDR x265 parameters injected:"),
+                ContentEncodingApproach::DolbyVision(_) => {
+                    log_step!("Dolby Vision x265 parameters injected:")
+                }
+                ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => {
+                    log_step!("Dual format (DV+HDR10+) x265 parameters injected:")
+                }
+                _ => {}
+            }
+            let params: Vec<&str> = x265_params_preview.split(':').collect();
+            let special_params: Vec<&str> = params
+                .iter()
+                .filter(|p| {
+                    p.contains("colormatrix")
+                        || p.contains("transfer")
+                        || p.contains("colorprim")
+                        || p.contains("master-display")
+                        || p.contains("max-cll")
+                })
+                .copied()
+                .collect();
+            if !special_params.is_empty() {
+                for param in special_params {
+                    info!("  -> {}", param);
+                }
+            }
+        }
+    }
+
+    async fn detect_crop(
+        &mut self,
+        is_advanced_content: bool,
+        is_dolby_vision: bool,
+        metadata: &VideoMetadata,
+    ) -> Result<(
+        Option<String>,
+        Vec<f64>,
+        Option<crate::analysis::CropAnalysisResult>,
+    )> {
+        if let Some(crop) = &self.options.crop {
+            self.emit(EncodeEvent::CropDetected {
+                crop: Some(crop.clone()),
+                method: "fixed".to_string(),
+            });
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Crop,
+                    crop.clone(),
+                    "given on the command line",
+                )
+                .with_rule("--crop"),
+            );
+            return Ok((Some(crop.clone()), vec![], None));
+        }
+
+        let crop_config = lookahead::crop_config(self.config, self.options);
+        if is_dolby_vision
+            && crop_config.enabled
+            && crop_config.dolby_vision == DolbyVisionCropPolicy::Off
+        {
+            info!(
+                "Crop detection skipped for Dolby Vision source (crop_detection.dolby_vision: off)"
+            );
+            self.explain(
+                Decision::new(DecisionTopic::Crop, "no crop", "Dolby Vision source")
+                    .with_rule("analysis.crop_detection.dolby_vision"),
+            );
+            return Ok((None, vec![], None));
+        }
+
+        if crop_config.enabled {
+            use crate::analysis::cache::CachedCrop;
+
+            let settings =
+                lookahead::crop_settings(self.config, &crop_config, is_advanced_content)?;
+            let crop_analysis = match self.cached_analysis.crop_for(&settings) {
+                Some(cached) => cached.clone(),
+                None => {
+                    let crop_analysis = lookahead::detect_crop(
+                        self.ffmpeg,
+                        self.config,
+                        &crop_config,
+                        self.input_path,
+                        metadata,
+                        is_advanced_content,
+                    )
+                    .await?;
+                    self.cached_analysis.crop = Some(CachedCrop {
+                        settings,
+                        result: crop_analysis.clone(),
+                    });
+                    crop_analysis
+                }
+            };
+            let sample_timestamps = crop_analysis
+                .sample_results
+                .iter()
+                .map(|sample| sample.timestamp)
+                .collect();
+            let crop_values = crop_analysis
+                .crop_values
+                .as_ref()
+                .map(|cv| cv.to_ffmpeg_string());
+            self.emit(EncodeEvent::CropDetected {
+                crop: crop_values.clone(),
+                method: crop_analysis.detection_method.clone(),
+            });
+            self.explain(
+                Decision::new(
+                    DecisionTopic::Crop,
+                    crop_values.as_deref().unwrap_or("no crop"),
+                    format!(
+                        "{}: {:.1}% of the picture is border ({:.0}% confidence)",
+                        crop_analysis.detection_method,
+                        crop_analysis.pixel_change_percent,
+                        crop_analysis.confidence
+                    ),
+                )
+                .with_rule("analysis.crop_detection.min_pixel_change_percent"),
+            );
+            if crop_config.report {
+                self.save_crop_report(metadata, &crop_analysis).await;
+            }
+            Ok((crop_values, sample_timestamps, Some(crop_analysis)))
+        } else {
+            self.explain(
+                Decision::new(DecisionTopic::Crop, "no crop", "crop detection is disabled")
+                    .with_rule("analysis.crop_detection.enabled"),
+            );
+            Ok((None, vec![], None))
+        }
+    }
+
+    fn build_filter_chain(
+        &self,
+        crop_values: Option<&str>,
+        metadata: &VideoMetadata,
+    ) -> Result<FilterChain> {
+        Ok(FilterBuilder::new(self.config)
+            .with_dv5_reshape(self.dv5_reshape)
+            .with_deinterlace(self.options.deinterlace)?
+            .with_denoise(self.options.denoise)
+            .with_deband(self.deband)
+            .with_custom(FilterStage::PreCrop)
+            .with_crop(crop_values)?
+            .with_scale(scale_height(
+                self.options.scale_height,
+                crop_values,
+                metadata,
+            ))
+            .with_custom(FilterStage::PostScale)
+            .with_tonemap(self.tonemap)
+            .with_custom(FilterStage::Final)
+            .build())
+    }
+
+    /// Runs the banding check for profiles made for anime when
+    /// `filters.deband` is enabled. A score at or above the threshold turns
+    /// the deband filter on and moves the profile to aq-mode 3 with at least
+    /// `min_bframes` B-frames.
+    async fn check_banding(
+        &mut self,
+        profile: &mut EncodingProfile,
+        metadata: &VideoMetadata,
+    ) -> Result<bool> {
+        let deband_config = &self.config.filters.deband;
+        if !deband_config.enabled
+            || !matches!(
+                profile.content_type,
+                ContentType::Anime | ContentType::ClassicAnime
+            )
+        {
+            return Ok(false);
+        }
+
+        let score = match self.cached_analysis.banding_score {
+            Some(score) => score,
+            None => {
+                let score = BandingDetector::new(self.ffmpeg, deband_config)
+                    .with_black_frames(self.config.analysis.black_frames.clone())
+                    .measure(self.input_path, metadata.duration)
+                    .await?;
+                self.cached_analysis.banding_score = Some(score);
+                score
+            }
+        };
+        if score < deband_config.threshold {
+            return Ok(false);
+        }
+
+        let bframes = profile
+            .x265_params
+            .get("bframes")
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(0)
+            .max(deband_config.min_bframes);
+        log_step!(
+            "Banding risk is high (score {:.3}): debanding with {}, aq-mode 3, bframes {}",
+            score,
+            deband_config.filter,
+            bframes
+        );
+        let decision = Decision::new(
+            DecisionTopic::Filter,
+            format!("deband, aq-mode 3, bframes {}", bframes),
+            format!(
+                "banding score {:.3} reaches the threshold of {:.3}",
+                score, deband_config.threshold
+            ),
+        )
+        .with_rule("filters.deband.threshold");
+        self.explain(decision);
+        profile
+            .x265_params
+            .insert("aq-mode".to_string(), "3".to_string());
+        profile
+            .x265_params
+            .insert("bframes".to_string(), bframes.to_string());
+        Ok(true)
+    }
+
+    /// Fingerprints the source (`analysis.fingerprint`) and looks for an
+    /// encode of the same content under another name in the encode reports.
+    /// A match is warned about, or ends the encode with
+    /// [`Error::Duplicate`] when `on_duplicate` is `skip`.
+    async fn check_duplicate(&mut self, duration: f64) -> Result<()> {
+        let config = &self.config.analysis.fingerprint;
+        if !config.enabled {
+            return Ok(());
+        }
+
+        let fingerprint = match self.cached_analysis.fingerprint.clone() {
+            Some(fingerprint) if fingerprint.hashes.len() == config.samples => fingerprint,
+            _ => match Fingerprinter::new(self.ffmpeg, config)
+                .compute(self.input_path, duration)
+                .await
+            {
+                Ok(fingerprint) => {
+                    self.cached_analysis.fingerprint = Some(fingerprint.clone());
+                    fingerprint
+                }
+                Err(e) => {
+                    tracing::warn!("Fingerprinting the source failed: {}", e);
+                    return Ok(());
+                }
+            },
+        };
+        let reports = match ReportLog::open_default() {
+            Ok(reports) => reports,
+            Err(e) => {
+                tracing::warn!("Cannot check for duplicates: {}", e);
+                return Ok(());
+            }
+        };
+        let Some((report, distance)) =
+            reports.find_duplicate(self.input_path, &fingerprint, config.max_distance)
+        else {
+            return Ok(());
+        };
+
+        let reason = format!(
+            "looks like {}, encoded {} ({:.1}% of the fingerprint differs)",
+            report.file.input.display(),
+            report.finished(),
+            distance * 100.0
+        );
+        match config.on_duplicate {
+            DuplicateAction::Skip => Err(Error::duplicate(reason)),
+            DuplicateAction::Warn => {
+                tracing::warn!("Possible duplicate: {}", reason);
+                self.explain(
+                    Decision::new(DecisionTopic::Profile, "encode anyway", reason)
+                        .with_rule("analysis.fingerprint.on_duplicate"),
+                );
+                Ok(())
+            }
+        }
+    }
+
+    /// Finds the source's scene cuts (`analysis.scene_detection`) for the
+    /// encode to force keyframes on. Without them x265 places keyframes on
+    /// its own, so a failed detection is only warned about.
+    /// Measures the source's bits per pixel (`analysis.starved_source`) and
+    /// warns about an already starved source. Returns the CRF points to take
+    /// off with `action: lower_crf`, 0 otherwise.
+    async fn check_starved_source(&mut self, metadata: &VideoMetadata) -> f32 {
+        let config = &self.config.analysis.starved_source;
+        if !config.enabled {
+            return 0.0;
+        }
+
+        let source = match self.cached_analysis.source_bitrate.clone() {
+            Some(source) => source,
+            None => match measure_source_bitrate(
+                self.ffmpeg,
+                self.input_path,
+                metadata.codec.as_deref().unwrap_or("unknown"),
+                (metadata.width, metadata.height, metadata.fps),
+            )
+            .await
+            {
+                Ok(source) => {
+                    self.cached_analysis.source_bitrate = Some(source.clone());
+                    source
+                }
+                Err(e) => {
+                    tracing::warn!("Measuring the source bitrate failed: {}", e);
+                    return 0.0;
+                }
+            },
+        };
+        if !source.is_starved(config.min_bits_per_pixel) {
+            return 0.0;
+        }
+
+        let measured = format!(
+            "{:.3} bits per pixel ({:.3} H.264 equivalent) is below {:.3}",
+            source.median_bpp,
+            source.h264_equivalent_bpp(),
+            config.min_bits_per_pixel
+        );
+        let (choice, reduction) = match config.action {
+            StarvedSourceAction::Warn => {
+                tracing::warn!(
+                    "Source is already heavily compressed ({}): re-encoding will amplify its artifacts",
+                    measured
+                );
+                ("keep the profile's CRF".to_string(), 0.0)
+            }
+            StarvedSourceAction::LowerCrf => {
+                log_step!(
+                    "Source is already heavily compressed ({}): lowering CRF by {:.1}",
+                    measured,
+                    config.crf_reduction
+                );
+                (
+                    format!("lower CRF by {:.1}", config.crf_reduction),
+                    config.crf_reduction,
+                )
+            }
+        };
+        let decision = Decision::new(DecisionTopic::Rate, choice, measured)
+            .with_rule("analysis.starved_source");
+        self.explain(decision);
+        reduction
+    }
+
+    async fn detect_scene_cuts(&mut self) {
+        let config = &self.config.analysis.scene_detection;
+        if !config.enabled {
+            return;
+        }
+
+        let cuts = match self.cached_analysis.scene_cuts_for(config.threshold) {
+            Some(cuts) => cuts.to_vec(),
+            None => match SceneDetector::new(self.ffmpeg, config)
+                .detect(self.input_path)
+                .await
+            {
+                Ok(cuts) => {
+                    let times = cuts.times.clone();
+                    self.cached_analysis.scene_cuts = Some(cuts);
+                    times
+                }
+                Err(e) => {
+                    tracing::warn!("Scene detection failed, keyframes left to x265: {}", e);
+                    return;
+                }
+            },
+        };
+        // The output of a trimmed encode starts at --start
+        let cuts: Vec<f64> = cuts
+            .iter()
+            .filter_map(|&cut| self.options.trimmed_time(cut))
+            .collect();
+        self.keyframes = keyframe_times(&cuts, config.min_interval);
+        if self.keyframes.is_empty() {
+            return;
+        }
+        log_step!(
+            "Forcing keyframes on {} of {} scene cuts",
+            self.keyframes.len(),
+            cuts.len()
+        );
+        let decision = Decision::new(
+            DecisionTopic::Rate,
+            format!("{} keyframes on scene cuts", self.keyframes.len()),
+            format!(
+                "{} cuts at scene score {}, at least {}s apart",
+                cuts.len(),
+                config.threshold,
+                config.min_interval
+            ),
+        )
+        .with_rule("analysis.scene_detection");
+        self.explain(decision);
+    }
+
+    fn get_encoding_mode(&self) -> Result<EncodingMode> {
+        EncodingMode::from_string(&self.options.mode)
+            .ok_or_else(|| Error::encoding(format!("Invalid encoding mode: {}", self.options.mode)))
+    }
+
+    /// `--stream-selection-profile`, or else the one bound by the encoding profile.
+    fn stream_selection_profile_name(&self) -> Option<&str> {
+        self.options
+            .stream_selection_profile
+            .as_deref()
+            .or(self.profile_stream_selection.as_deref())
+    }
+
+    async fn analyze_streams(&self) -> Result<crate::stream::preservation::StreamMapping> {
+        if self.options.stream_selection_profile.is_none() {
+            if let Some(profile_name) = &self.profile_stream_selection {
+                log_step!(
+                    "Using stream selection profile '{}' bound to the encoding profile",
+                    profile_name
+                );
+            }
+        }
+        if let Some(profile_name) = self.stream_selection_profile_name() {
+            let profile = self.stream_profile_manager.get_profile(profile_name)?;
+            self.stream_preservation
+                .analyze_streams_with_profile(self.input_path, profile)
+                .await
+        } else {
+            self.stream_preservation
+                .analyze_streams(self.input_path)
+                .await
+        }
+    }
+
+    /// Fits the selected streams to the output container and `--device`. PGS
+    /// subtitles that neither can show are OCR'd to SRT when the stream
+    /// selection profile sets `convert_pgs_to_text`; other bitmap subtitles
+    /// are dropped.
+    async fn adapt_streams_to_target(
         &self,
-        content_analysis: &crate::ContentAnalysisResult,
-        selected_profile: &EncodingProfile,
-        adaptive_crf: f32,
-        adaptive_bitrate: u32,
-    ) {
-        match &content_analysis.recommended_approach {
-            ContentEncodingApproach::SDR => {
-                info!(
-                    "Using standard encoding parameters (SDR): CRF={:.1}, Bitrate={}kbps",
-                    adaptive_crf, adaptive_bitrate
-                );
+        stream_mapping: &mut StreamMapping,
+        file_logger: &FileLogger,
+        job_dir: &JobTempDir,
+    ) -> Result<()> {
+        let container = OutputContainer::from_path(&self.output_path);
+        let device_text_only = self
+            .device
+            .as_ref()
+            .is_some_and(|device| device.text_subtitles_only());
+        let bitmap_subtitles: Vec<StreamInfo> =
+            if device_text_only || container == Some(OutputContainer::Mp4) {
+                stream_mapping
+                    .subtitle_streams
+                    .iter()
+                    .filter(|stream| is_bitmap_subtitle(&stream.codec_name))
+                    .cloned()
+                    .collect()
+            } else {
+                Vec::new()
+            };
+        let forced = self
+            .detect_forced_subtitle(stream_mapping, file_logger)
+            .await;
+        let converted = self
+            .convert_pgs_subtitles(&bitmap_subtitles, file_logger, job_dir)
+            .await?;
+
+        if let Some(container) = container {
+            stream_mapping.adapt_for_container(container);
+        }
+        for stream in &bitmap_subtitles {
+            // Still mapped when the container takes them but the device does not
+            if stream_mapping
+                .subtitle_streams
+                .iter()
+                .any(|mapped| mapped.index == stream.index)
+            {
+                if !converted.iter().any(|(done, _)| done.index == stream.index) {
+                    tracing::warn!(
+                        "Dropping {} subtitle stream #{} ({} only shows text subtitles)",
+                        stream.codec_name,
+                        stream.index,
+                        self.device.as_ref().map_or("the device", |d| d.title())
+                    );
+                }
+                stream_mapping.drop_subtitle(stream.index);
             }
-            _ => {
-                info!("PARAMETER ADJUSTMENTS:");
-                info!(
-                    "  Base CRF: {} -> Adjusted CRF: {:.1} (+{:.1})",
-                    selected_profile.base_crf,
-                    adaptive_crf,
-                    content_analysis.encoding_adjustments.crf_adjustment
-                );
-                info!(
-                    "  Base Bitrate: {} -> Adjusted Bitrate: {} ({:.1}x multiplier)",
-                    selected_profile.bitrate,
-                    adaptive_bitrate,
-                    content_analysis.encoding_adjustments.bitrate_multiplier
-                );
+        }
+        let set_default = self.config.streams.forced_subtitles.set_default;
+        if let Some(index) = forced {
+            stream_mapping.mark_forced_subtitle(index, set_default);
+        }
+        for (stream, srt) in &converted {
+            if forced == Some(stream.index) {
+                let mut stream = stream.clone();
+                stream.disposition.forced = true;
+                stream.disposition.default |= set_default;
+                stream_mapping.add_text_subtitle(&stream, srt, container);
+            } else {
+                stream_mapping.add_text_subtitle(stream, srt, container);
             }
         }
+        Ok(())
     }
 
-    fn build_x265_params_preview(
+    /// The selected subtitle stream that `streams.forced_subtitles` takes for
+    /// the forced one. Counting the events reads the whole source, so this
+    /// only runs with several subtitle streams and none flagged forced.
+    async fn detect_forced_subtitle(
         &self,
-        selected_profile: &EncodingProfile,
-        metadata: &VideoMetadata,
-        is_advanced_content: bool,
-    ) -> String {
-        selected_profile.build_x265_params_string_with_hdr_passthrough(
-            None,
-            Some(is_advanced_content),
-            metadata.color_space.as_ref(),
-            metadata.transfer_function.as_ref(),
-            metadata.color_primaries.as_ref(),
-            metadata.master_display.as_ref(),
-            metadata.max_cll.as_ref(),
-            false, // Default to non-passthrough mode
-        )
+        stream_mapping: &StreamMapping,
+        file_logger: &FileLogger,
+    ) -> Option<u32> {
+        let subtitles = &stream_mapping.subtitle_streams;
+        if !self.config.streams.forced_subtitles.enabled
+            || subtitles.len() < 2
+            || subtitles.iter().any(|stream| stream.disposition.forced)
+        {
+            return None;
+        }
+        let events = match forced::count_subtitle_events(self.ffmpeg, self.input_path).await {
+            Ok(events) => events,
+            Err(e) => {
+                tracing::warn!("Counting subtitle events failed: {}", e);
+                return None;
+            }
+        };
+        let (index, reason) = forced::detect_forced(
+            subtitles,
+            &events,
+            forced::main_audio_language(stream_mapping),
+            &self.config.streams.forced_subtitles,
+        )?;
+        log_step!(
+            "Subtitle stream #{} is the forced track ({})",
+            index,
+            reason
+        );
+        file_logger
+            .log_encoding_progress(&format!("Forced subtitles: stream #{} ({})", index, reason))
+            .ok();
+        Some(index)
     }
 
-    fn log_x265_params(
+    /// OCRs the PGS streams among `streams` with `tools.subtitle_ocr` when the
+    /// stream selection profile asks for it. Streams that fail are left out.
+    async fn convert_pgs_subtitles(
         &self,
-        content_analysis: &crate::ContentAnalysisResult,
-        x265_params_preview: &str,
-        is_advanced_content: bool,
-    ) {
-        if is_advanced_content {
-            match &content_analysis.recommended_approach {
-                ContentEncodingApproach::HDR(_) => info!("HDR x265 parameters injected:"),
-                ContentEncodingApproach::DolbyVision(_) => {
-                    info!("Dolby Vision x265 parameters injected:")
-                }
-                ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => {
-                    info!("Dual format (DV+HDR10+) x265 parameters injected:")
-                }
-                _ => {}
+        streams: &[StreamInfo],
+        file_logger: &FileLogger,
+        job_dir: &JobTempDir,
+    ) -> Result<Vec<(StreamInfo, PathBuf)>> {
+        let pgs: Vec<&StreamInfo> = streams
+            .iter()
+            .filter(|stream| stream.codec_name == PGS_CODEC)
+            .collect();
+        let requested = match self.stream_selection_profile_name() {
+            Some(name) => {
+                self.stream_profile_manager
+                    .get_profile(name)?
+                    .subtitle
+                    .convert_pgs_to_text
             }
-            let params: Vec<&str> = x265_params_preview.split(':').collect();
-            let special_params: Vec<&str> = params
-                .iter()
-                .filter(|p| {
-                    p.contains("colormatrix")
-                        || p.contains("transfer")
-                        || p.contains("colorprim")
-                        || p.contains("master-display")
-                        || p.contains("max-cll")
-                })
-                .copied()
-                .collect();
-            if !special_params.is_empty() {
-                for param in special_params {
-                    info!("  -> {}", param);
+            None => false,
+        };
+        if pgs.is_empty() || !requested {
+            return Ok(Vec::new());
+        }
+        let Some(ocr_config) = &self.config.tools.subtitle_ocr else {
+            tracing::warn!(
+                "convert_pgs_to_text is set but tools.subtitle_ocr is not configured; PGS subtitles are dropped"
+            );
+            return Ok(Vec::new());
+        };
+
+        let ocr = SubtitleOcr::new(self.ffmpeg.get_ffmpeg_path(), ocr_config);
+        let mut converted = Vec::new();
+        for stream in pgs {
+            match ocr.convert(self.input_path, stream, job_dir.path()).await {
+                Ok(srt) => {
+                    file_logger.log_encoding_progress(&format!(
+                        "Subtitle OCR: PGS stream #{} converted to text",
+                        stream.index
+                    ))?;
+                    converted.push((stream.clone(), srt));
                 }
+                Err(e) => tracing::warn!(
+                    "OCR of subtitle stream #{} failed, dropping it: {}",
+                    stream.index,
+                    e
+                ),
             }
         }
+        Ok(converted)
     }
 
-    async fn detect_crop(
-        &self,
-        is_advanced_content: bool,
-        metadata: &VideoMetadata,
-    ) -> Result<(
-        Option<String>,
-        Vec<f64>,
-        Option<crate::analysis::CropAnalysisResult>,
-    )> {
-        if self.config.analysis.crop_detection.enabled {
-            use crate::analysis::CropDetector;
-            let crop_detector = CropDetector::new(self.config.analysis.crop_detection.clone());
-            let crop_analysis = crop_detector
-                .detect_crop_values(
-                    self.input_path,
-                    metadata.duration,
-                    metadata.width,
-                    metadata.height,
-                    is_advanced_content,
-                )
-                .await?;
-            let sample_timestamps = self
-                .config
-                .analysis
-                .crop_detection
-                .get_sample_timestamps(metadata.duration);
-            let crop_values = crop_analysis
-                .crop_values
-                .as_ref()
-                .map(|cv| cv.to_ffmpeg_string());
-            Ok((crop_values, sample_timestamps, Some(crop_analysis)))
-        } else {
-            Ok((None, vec![], None))
+    /// Audio bitrate ladder, with overrides from the selected stream selection profile.
+    fn audio_bitrate_ladder(&self) -> Result<BitrateLadder> {
+        let overrides = match self.stream_selection_profile_name() {
+            Some(profile_name) => self
+                .stream_profile_manager
+                .get_profile(profile_name)?
+                .audio
+                .bitrate_per_channel
+                .clone(),
+            None => None,
+        };
+        Ok(BitrateLadder::with_overrides(overrides.as_ref()))
+    }
+
+    /// Drops a Dolby Vision RPU the `--device` cannot play, leaving HDR10. Profile 5
+    /// has no HDR10 base layer to fall back to, so it is refused instead.
+    fn apply_device_dolby_vision(
+        &mut self,
+        metadata_workflow: &MetadataWorkflowManager,
+        extracted_metadata: &mut crate::metadata_workflow::ExtractedMetadata,
+    ) -> Result<()> {
+        let (Some(device), Some(rpu)) = (&self.device, &extracted_metadata.dolby_vision) else {
+            return Ok(());
+        };
+        let profile = rpu.profile;
+        if device.supports_dolby_vision(profile) {
+            return Ok(());
+        }
+
+        metadata_workflow.discard_dolby_vision(extracted_metadata);
+        if profile == DolbyVisionProfile::Profile5 {
+            return Err(Error::validation(format!(
+                "Device '{}' does not play Dolby Vision profile 5, and profile 5 has no HDR10 base layer to fall back to",
+                device.name()
+            )));
         }
+        tracing::warn!(
+            "Device '{}' does not play Dolby Vision profile {}; encoding HDR10 without the RPU",
+            device.name(),
+            profile.as_str()
+        );
+        let decision = Decision::new(
+            DecisionTopic::Metadata,
+            "drop the Dolby Vision RPU",
+            format!(
+                "device '{}' does not play Dolby Vision profile {}",
+                device.name(),
+                profile.as_str()
+            ),
+        )
+        .with_rule("--device");
+        self.explain(decision);
+        Ok(())
     }
 
-    fn build_filter_chain(&self, crop_values: Option<&str>) -> Result<FilterChain> {
-        Ok(FilterBuilder::new(self.config)
-            .with_deinterlace(self.args.deinterlace)?
-            .with_denoise(self.args.denoise)
-            .with_crop(crop_values)?
-            .build())
+    /// Transcodes audio streams the `--device` cannot decode.
+    fn apply_device_audio(
+        &self,
+        stream_mapping: &mut crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+    ) -> Result<()> {
+        let Some(device) = &self.device else {
+            return Ok(());
+        };
+        let ladder = self.audio_bitrate_ladder()?;
+        for bitrate in device.apply_to_mapping(stream_mapping, &ladder) {
+            info!(
+                "Transcoding {} (unsupported by {})",
+                bitrate,
+                device.title()
+            );
+            file_logger.log_encoding_progress(&format!("Audio transcode: {}", bitrate))?;
+        }
+        Ok(())
     }
 
-    fn get_encoding_mode(&self) -> Result<EncodingMode> {
-        EncodingMode::from_string(&self.args.mode)
-            .ok_or_else(|| Error::encoding(format!("Invalid encoding mode: {}", self.args.mode)))
+    /// Retitles the output audio tracks from the `title_template` of the
+    /// selected stream selection profile, once their codecs are final.
+    fn apply_audio_titles(
+        &self,
+        stream_mapping: &mut crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+    ) -> Result<()> {
+        let Some(profile_name) = self.stream_selection_profile_name() else {
+            return Ok(());
+        };
+        let profile = self.stream_profile_manager.get_profile(profile_name)?;
+        let Some(template) = &profile.audio.title_template else {
+            return Ok(());
+        };
+        for (output_index, title) in stream_mapping.retitle_audio(template).iter().enumerate() {
+            info!("Audio track {} title: {}", output_index, title);
+            file_logger
+                .log_encoding_progress(&format!("Audio title {}: {}", output_index, title))?;
+        }
+        Ok(())
     }
 
-    async fn analyze_streams(&self) -> Result<crate::stream::preservation::StreamMapping> {
-        if let Some(profile_name) = &self.args.stream_selection_profile {
-            let profile = self.stream_profile_manager.get_profile(profile_name)?;
-            self.stream_preservation
-                .analyze_streams_with_profile(self.input_path, profile)
-                .await
-        } else {
-            self.stream_preservation
-                .analyze_streams(self.input_path)
-                .await
+    /// Applies `streams.closed_captions` to captions carried inside the video
+    /// stream, which the encode would otherwise lose. Returns the extracted
+    /// caption file to remove once the encode is done.
+    async fn apply_closed_caption_policy(
+        &self,
+        stream_mapping: &mut crate::stream::preservation::StreamMapping,
+        file_logger: &FileLogger,
+        job_dir: &JobTempDir,
+    ) -> Result<Option<PathBuf>> {
+        if !stream_mapping.has_closed_captions() {
+            return Ok(None);
+        }
+
+        match self.config.streams.closed_captions {
+            ClosedCaptionPolicy::Drop => {
+                tracing::debug!("Dropping closed captions (streams.closed_captions: drop)");
+                Ok(None)
+            }
+            ClosedCaptionPolicy::Warn => {
+                tracing::warn!(
+                    "Source has EIA-608/708 closed captions in the video stream; they are lost on re-encode (set streams.closed_captions: extract to keep them as a subtitle track)"
+                );
+                file_logger.log_encoding_progress("Closed captions: dropped")?;
+                Ok(None)
+            }
+            ClosedCaptionPolicy::Extract => {
+                let stem = self
+                    .input_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "input".to_string());
+                let caption_path = job_dir.path().join(format!("{}_captions.srt", stem));
+
+                let extractor = CaptionExtractor::new(self.ffmpeg.get_ffmpeg_path());
+                match extractor.extract(self.input_path, &caption_path).await {
+                    Ok(true) => {
+                        stream_mapping.add_caption_track(
+                            &caption_path,
+                            OutputContainer::from_path(&self.output_path),
+                        );
+                        info!("Closed captions extracted to a subtitle track");
+                        file_logger.log_encoding_progress("Closed captions: extracted")?;
+                        Ok(Some(caption_path))
+                    }
+                    Ok(false) => {
+                        info!("Closed caption stream holds no text, nothing to keep");
+                        let _ = tokio::fs::remove_file(&caption_path).await;
+                        Ok(None)
+                    }
+                    Err(e) => {
+                        tracing::warn!("{}; the captions will be dropped", e);
+                        file_logger.log_encoding_progress("Closed captions: dropped")?;
+                        let _ = tokio::fs::remove_file(&caption_path).await;
+                        Ok(None)
+                    }
+                }
+            }
         }
     }
 
@@ -446,10 +2435,10 @@ impl<'a> VideoProcessor<'a> {
     ) -> Result<()> {
         file_logger.log_encoding_settings(
             self.input_path,
-            self.output_path,
-            &self.args.profile,
+            &self.output_path,
+            &self.options.profile_name,
             selected_profile,
-            &self.args.mode,
+            &self.options.mode,
             adaptive_crf,
             adaptive_bitrate,
             Some(&filter_chain.to_string()),
@@ -457,21 +2446,22 @@ impl<'a> VideoProcessor<'a> {
         )?;
         file_logger.log_analysis_results(metadata, None, Some(content_analysis))?;
         file_logger.log_encoding_progress(&format!("x265 parameters: {}", x265_params_preview))?;
+        let crop_config = lookahead::crop_config(self.config, self.options);
         let detection_method = if let Some(analysis) = crop_analysis_result {
             &analysis.detection_method
-        } else if self.config.analysis.crop_detection.enabled {
+        } else if crop_config.enabled {
             "automatic_detection"
         } else {
             "disabled"
         };
         file_logger.log_crop_detection_results(
-            self.config.analysis.crop_detection.enabled,
-            self.config.analysis.crop_detection.sample_count,
+            crop_config.enabled,
+            crop_config.sample_count,
             crop_sample_timestamps,
             crop_values,
             detection_method,
-            self.config.analysis.crop_detection.sdr_crop_limit,
-            self.config.analysis.crop_detection.hdr_crop_limit,
+            crop_config.sdr_crop_limit,
+            crop_config.hdr_crop_limit,
             is_advanced_content,
         )?;
         if let Some(analysis) = crop_analysis_result {
@@ -481,6 +2471,19 @@ impl<'a> VideoProcessor<'a> {
                 analysis.pixel_change_percent,
                 analysis.sample_results.len()
             ))?;
+            if !analysis.sample_results.is_empty() {
+                file_logger.log_crop_samples(analysis)?;
+            }
+        }
+        if let Some(source) = &self.cached_analysis.source_bitrate {
+            let starved = source.is_starved(self.config.analysis.starved_source.min_bits_per_pixel);
+            file_logger.log_encoding_progress(&format!(
+                "Source bitrate: {:.3} bits per pixel (median, {:.3} H.264 equivalent), {:.3} at the 10th percentile: {}",
+                source.median_bpp,
+                source.h264_equivalent_bpp(),
+                source.low_bpp,
+                if starved { "starved" } else { "not starved" }
+            ))?;
         }
         Ok(())
     }
@@ -488,7 +2491,9 @@ impl<'a> VideoProcessor<'a> {
     #[allow(clippy::too_many_arguments)]
     async fn start_encoding(
         &self,
+        ffmpeg: &FfmpegWrapper,
         actual_output_path: &Path,
+        video_output: Option<&Path>,
         selected_profile: &EncodingProfile,
         filter_chain: &FilterChain,
         stream_mapping: &crate::stream::preservation::StreamMapping,
@@ -501,9 +2506,11 @@ impl<'a> VideoProcessor<'a> {
     ) -> Result<tokio::process::Child> {
         match encoding_mode {
             EncodingMode::CRF => {
-                CrfEncoder
+                CrfEncoder::default()
+                    .with_keyframes(self.keyframes.clone())
+                    .with_video_output(video_output.map(Path::to_path_buf))
                     .encode(
-                        self.ffmpeg,
+                        ffmpeg,
                         self.input_path,
                         actual_output_path,
                         selected_profile,
@@ -512,7 +2519,7 @@ impl<'a> VideoProcessor<'a> {
                         metadata,
                         adaptive_crf,
                         adaptive_bitrate,
-                        self.args.title.as_deref(),
+                        self.options.title.as_deref(),
                         Some(file_logger),
                         external_params_ref,
                         false, // Default to non-passthrough mode
@@ -520,9 +2527,12 @@ impl<'a> VideoProcessor<'a> {
                     .await
             }
             EncodingMode::ABR => {
-                AbrEncoder
+                AbrEncoder::default()
+                    .with_keep_stats(self.options.keep_temp)
+                    .with_keyframes(self.keyframes.clone())
+                    .with_video_output(video_output.map(Path::to_path_buf))
                     .encode(
-                        self.ffmpeg,
+                        ffmpeg,
                         self.input_path,
                         actual_output_path,
                         selected_profile,
@@ -531,7 +2541,7 @@ impl<'a> VideoProcessor<'a> {
                         metadata,
                         adaptive_crf,
                         adaptive_bitrate,
-                        self.args.title.as_deref(),
+                        self.options.title.as_deref(),
                         Some(file_logger),
                         external_params_ref,
                         false,
@@ -540,8 +2550,11 @@ impl<'a> VideoProcessor<'a> {
             }
             EncodingMode::CBR => {
                 CbrEncoder::new()
+                    .with_keep_stats(self.options.keep_temp)
+                    .with_keyframes(self.keyframes.clone())
+                    .with_video_output(video_output.map(Path::to_path_buf))
                     .encode(
-                        self.ffmpeg,
+                        ffmpeg,
                         self.input_path,
                         actual_output_path,
                         selected_profile,
@@ -550,7 +2563,7 @@ impl<'a> VideoProcessor<'a> {
                         metadata,
                         adaptive_crf,
                         adaptive_bitrate,
-                        self.args.title.as_deref(),
+                        self.options.title.as_deref(),
                         Some(file_logger),
                         external_params_ref,
                         false,
@@ -564,23 +2577,50 @@ impl<'a> VideoProcessor<'a> {
         &self,
         metadata: &VideoMetadata,
         encoding_mode: EncodingMode,
+        frame_count: Option<u32>,
     ) -> ProgressMonitor {
         let source_file_size = std::fs::metadata(self.input_path).map(|m| m.len()).ok();
 
-        let progress_monitor = ProgressMonitor::new(
-            metadata.duration,
+        let mut progress_monitor = ProgressMonitor::new(
+            self.options.trimmed_duration(metadata.duration),
             metadata.fps,
             self.ffmpeg.clone(),
             encoding_mode,
             source_file_size,
         );
-        let total_frames = if metadata.fps > 0.0 && metadata.duration > 0.0 {
-            (metadata.duration * metadata.fps as f64) as u32
+        if let Ok(Some(window)) = self.options.pause_window() {
+            progress_monitor.add_pause_condition(Box::new(SchedulePause::new(window)));
+        }
+        progress_monitor.add_abort_condition(Box::new(self.cancel.clone()));
+        progress_monitor = progress_monitor.with_events(self.events.clone());
+        if let Some(ratio) = self.config.encoding.abort_if_projected_ratio {
+            // A stream copy is as large as its source by design
+            if !self.copies_video() {
+                progress_monitor =
+                    progress_monitor.with_size_limit(ratio, self.config.encoding.abort_min_percent);
+            }
+        }
+        if !self.show_progress_bar {
+            progress_monitor = progress_monitor.without_progress_bar();
+        } else if let Some(batch) = &self.batch_progress {
+            progress_monitor = progress_monitor.with_batch(batch.clone());
+        }
+        if let Some(frames) = frame_count {
+            progress_monitor = progress_monitor.with_exact_frame_count(frames);
+        }
+        if self.config.throttle.enabled {
+            progress_monitor
+                .add_pause_condition(Box::new(Throttle::new(self.config.throttle.clone())));
+        }
+        let total_frames = progress_monitor.expected_frames().unwrap_or(0);
+        let action = if self.copies_video() {
+            "Remuxing"
         } else {
-            0
+            "Encoding"
         };
         progress_monitor.set_message(&format!(
-            "Encoding {} ({}x{}, {:.1}fps, {} frames)",
+            "{} {} ({}x{}, {:.1}fps, {} frames)",
+            action,
             self.input_path
                 .file_name()
                 .unwrap_or_default()
@@ -593,39 +2633,398 @@ impl<'a> VideoProcessor<'a> {
         progress_monitor
     }
 
+    /// The source's exact frame count with `analysis.count_frames`, from the
+    /// analysis cache or counted now. Skipped when deinterlacing, which may
+    /// double the frame count.
+    async fn exact_frame_count(
+        &self,
+        stream_mapping: &crate::stream::preservation::StreamMapping,
+    ) -> Option<u32> {
+        if !self.config.analysis.count_frames || self.options.deinterlace {
+            return None;
+        }
+        let mut cached = self
+            .analysis_cache
+            .load(self.input_path)
+            .unwrap_or_default();
+        if let Some(frames) = cached.frame_count {
+            return Some(frames);
+        }
+
+        log_step!("Counting source frames (analysis.count_frames)...");
+        let specifier = stream_mapping.primary_video_specifier();
+        match self
+            .ffmpeg
+            .count_video_frames(self.input_path, specifier.trim_start_matches("0:"))
+            .await
+        {
+            Ok(frames) => {
+                info!("Source has {} frames", frames);
+                cached.frame_count = Some(frames);
+                self.analysis_cache.store(self.input_path, &cached);
+                Some(frames)
+            }
+            Err(e) => {
+                tracing::warn!("Frame count failed, estimating from duration: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Logs the result; `phases` splits `duration` into the encode and the
+    /// steps after it (RPU injection, mkvmerge mux).
     fn finalize_logging(
         &self,
         file_logger: &FileLogger,
         status: std::process::ExitStatus,
+        ffmpeg_errors: &[String],
         duration: std::time::Duration,
+        phases: &[(&str, std::time::Duration)],
     ) -> Result<()> {
-        let output_size = std::fs::metadata(self.output_path).map(|m| m.len()).ok();
+        for line in ffmpeg_errors {
+            tracing::debug!("FFmpeg: {}", line);
+        }
+        let output_size = std::fs::metadata(&self.output_path).map(|m| m.len()).ok();
         let exit_code = status.code();
         if status.success() {
             if let Some(size) = output_size {
-                info!(
+                log_stage!(
                     "Encoding completed successfully in {:.2}s, output size: {:.2} MB",
                     duration.as_secs_f64(),
                     size as f64 / 1_048_576.0
                 );
             } else {
-                info!(
+                log_stage!(
                     "Encoding completed successfully in {:.2}s",
                     duration.as_secs_f64()
                 );
             }
-            file_logger.log_encoding_complete(true, duration, output_size, exit_code)?;
+            if phases.len() > 1 {
+                let breakdown: Vec<String> = phases
+                    .iter()
+                    .map(|(name, elapsed)| format!("{} {:.2}s", name, elapsed.as_secs_f64()))
+                    .collect();
+                log_step!("Time per phase: {}", breakdown.join(", "));
+            }
+            file_logger.log_encoding_complete(true, duration, phases, output_size, exit_code)?;
             info!(
                 "Encoding log saved to: {}",
                 file_logger.get_log_path().display()
             );
         } else {
-            file_logger.log_encoding_complete(false, duration, output_size, exit_code)?;
-            return Err(Error::encoding(format!(
+            if !ffmpeg_errors.is_empty() {
+                file_logger.log_ffmpeg_errors(ffmpeg_errors)?;
+            }
+            file_logger.log_encoding_complete(false, duration, phases, output_size, exit_code)?;
+            let mut message = format!(
                 "Encoding failed with exit code: {}",
                 exit_code.unwrap_or(-1)
-            )));
+            );
+            if let Some(error) = ffmpeg_errors.last() {
+                message.push_str(&format!(" ({})", error));
+            }
+            return Err(Error::encoding(message));
         }
         Ok(())
     }
 }
+
+/// Why `--smart` has to re-encode instead of copying the video stream; empty
+/// when the source is already HEVC 10-bit within the profile's bitrate ceiling
+/// (`max_bitrate`, else its target bitrate) and needs no filters.
+fn passthrough_blockers(
+    options: &EncodingOptions,
+    metadata: &VideoMetadata,
+    profile: &EncodingProfile,
+    filter_chain: &FilterChain,
+) -> Vec<String> {
+    let mut blockers = Vec::new();
+
+    let codec = metadata.codec.as_deref().unwrap_or("unknown");
+    if codec != "hevc" {
+        blockers.push(format!("source is {}, not HEVC", codec));
+    }
+    match metadata.bit_depth() {
+        Some(depth) if depth >= 10 => {}
+        Some(depth) => blockers.push(format!("source is {}-bit", depth)),
+        None => blockers.push("source bit depth unknown".to_string()),
+    }
+
+    // The container bitrate includes audio, so this errs on the side of encoding
+    let ceiling = profile.max_bitrate.unwrap_or(profile.bitrate);
+    match metadata.bitrate.map(|bps| bps / 1000) {
+        Some(kbps) if kbps <= ceiling => {}
+        Some(kbps) => blockers.push(format!(
+            "source bitrate {}kbps exceeds {}kbps",
+            kbps, ceiling
+        )),
+        None => blockers.push("source bitrate unknown".to_string()),
+    }
+
+    if !filter_chain.is_empty() {
+        blockers.push("crop or filters required".to_string());
+    }
+    if options.device.is_some() {
+        blockers.push("device constraints apply".to_string());
+    }
+    if options.dv_hdr10_fallback {
+        blockers.push("--dv-hdr10-fallback needs an encode".to_string());
+    }
+    if options.is_trimmed() {
+        blockers.push("--start/--end need a frame-accurate encode".to_string());
+    }
+    blockers
+}
+
+/// Height to scale to: `target` if the (cropped) picture is taller, since
+/// renditions never upscale.
+fn scale_height(target: Option<u32>, crop: Option<&str>, metadata: &VideoMetadata) -> Option<u32> {
+    let height = crop
+        .and_then(|crop| crop.split(':').nth(1))
+        .and_then(|height| height.parse().ok())
+        .unwrap_or(metadata.height);
+    target.filter(|&target| target < height)
+}
+
+/// What an encode tone mapped to SDR signals: BT.709 without HDR metadata.
+fn sdr_metadata(metadata: VideoMetadata) -> VideoMetadata {
+    VideoMetadata {
+        is_hdr: false,
+        hdr_analysis: None,
+        color_space: Some("bt709".to_string()),
+        transfer_function: Some("bt709".to_string()),
+        color_primaries: Some("bt709".to_string()),
+        master_display: None,
+        max_cll: None,
+        max_fall: None,
+        ..metadata
+    }
+}
+
+/// Switches an HDR encode to SDR for tone mapping.
+fn tonemap_to_sdr(
+    content_analysis: &mut crate::ContentAnalysisResult,
+    metadata: VideoMetadata,
+) -> VideoMetadata {
+    content_analysis.recommended_approach = ContentEncodingApproach::SDR;
+    content_analysis.encoding_adjustments = EncodingAdjustments::sdr_default();
+    sdr_metadata(metadata)
+}
+
+/// BT.709 signalling for a tone mapped encode.
+fn sdr_x265_params(profile: &mut EncodingProfile) {
+    // Profiles made for HDR sources may pin HDR signalling
+    for key in [
+        "hdr10",
+        "hdr10-opt",
+        "hdr10_opt",
+        "master-display",
+        "max-cll",
+    ] {
+        profile.x265_params.remove(key);
+    }
+    for (key, value) in [
+        ("colorprim", "bt709"),
+        ("transfer", "bt709"),
+        ("colormatrix", "bt709"),
+    ] {
+        profile
+            .x265_params
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+/// Content label and config section of the CRF/bitrate adjustments for
+/// `approach`; SDR encodes use the profile as it is.
+fn adjustment_source(approach: &ContentEncodingApproach) -> (&'static str, Option<&'static str>) {
+    match approach {
+        ContentEncodingApproach::SDR => ("SDR", None),
+        ContentEncodingApproach::HDR(_) => ("HDR", Some("analysis.hdr")),
+        ContentEncodingApproach::DolbyVision(_) => ("Dolby Vision", Some("analysis.dolby_vision")),
+        ContentEncodingApproach::DolbyVisionWithHDR10Plus(_, _) => {
+            ("Dolby Vision + HDR10+", Some("analysis.dolby_vision"))
+        }
+    }
+}
+
+/// Why a Dolby Vision profile 5 source is not encoded without `--dv5-convert`.
+fn profile5_refusal() -> Error {
+    Error::dolby_vision(
+        "Dolby Vision profile 5 source: its base layer is IPTPQc2, not BT.2020 PQ, so encoding it \
+         as-is gives wrong (green/purple) colors on anything but a Dolby Vision display. Pass \
+         --dv5-convert to reshape it to HDR10 (needs ffmpeg with libplacebo) and convert the RPU to \
+         profile 8.1, or --video copy to keep the stream as it is",
+    )
+}
+
+/// Length of the `encoding.preflight_probe` encode, in seconds
+const PREFLIGHT_PROBE_SECONDS: f64 = 5.0;
+
+/// Waits for the preflight probe encode and fails with FFmpeg's error when
+/// it did not succeed.
+async fn check_preflight_probe(child: Result<tokio::process::Child>) -> Result<()> {
+    let output = child?.wait_with_output().await?;
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let error = stderr
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or("no error output");
+    Err(Error::encoding(format!(
+        "Preflight probe failed, the encode would fail too: {}",
+        error
+    )))
+}
+
+/// Deletes an output left behind by an aborted encode; it is never a valid file.
+async fn remove_partial_output(path: &Path) {
+    if !path.exists() {
+        return;
+    }
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => info!("Removed partial output: {}", path.display()),
+        Err(e) => tracing::warn!("Failed to remove partial output {}: {}", path.display(), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_passthrough_blockers() {
+        let profile = EncodingProfile {
+            name: "movie".to_string(),
+            title: "Movie".to_string(),
+            base_crf: 22.0,
+            bitrate: 10000,
+            content_type: crate::config::ContentType::Film,
+            x265_params: BTreeMap::new(),
+            container: None,
+            extension: None,
+            max_bitrate: Some(12000),
+            stream_selection_profile: None,
+            tune: None,
+        };
+        let metadata = VideoMetadata {
+            width: 1920,
+            height: 1080,
+            duration: 60.0,
+            fps: 24.0,
+            bitrate: Some(11_500_000),
+            codec: Some("hevc".to_string()),
+            pix_fmt: Some("yuv420p10le".to_string()),
+            field_order: None,
+            is_hdr: false,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+        let options = EncodingOptions::default().with_smart(true);
+        let no_filters = FilterChain::new();
+        assert!(passthrough_blockers(&options, &metadata, &profile, &no_filters).is_empty());
+
+        let h264_8bit = VideoMetadata {
+            codec: Some("h264".to_string()),
+            pix_fmt: Some("yuv420p".to_string()),
+            bitrate: Some(20_000_000),
+            ..metadata.clone()
+        };
+        assert_eq!(
+            passthrough_blockers(&options, &h264_8bit, &profile, &no_filters),
+            vec![
+                "source is h264, not HEVC",
+                "source is 8-bit",
+                "source bitrate 20000kbps exceeds 12000kbps",
+            ]
+        );
+
+        let mut cropped = FilterChain::new();
+        cropped.add_filter("crop=1920:800:0:140".to_string());
+        let blockers = passthrough_blockers(&options, &metadata, &profile, &cropped);
+        assert_eq!(blockers, vec!["crop or filters required"]);
+
+        let p010 = VideoMetadata {
+            pix_fmt: Some("p010le".to_string()),
+            ..metadata
+        };
+        assert_eq!(p010.bit_depth(), Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_trimmed_encode_cuts_dynamic_metadata() {
+        use crate::hdr10plus::{Hdr10PlusMetadata, Hdr10PlusProcessingResult};
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::default();
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let preservation = StreamPreservation::new(ffmpeg.clone());
+        let options = EncodingOptions {
+            output_path: dir.path().join("out.mkv"),
+            ..EncodingOptions::default()
+        }
+        .with_trim(Some(10.0), Some(20.0));
+        let mut profiles = ProfileManager::new();
+        let processor =
+            VideoProcessor::new(&ffmpeg, &preservation, &options, &config, &mut profiles).unwrap();
+        let metadata = VideoMetadata {
+            width: 3840,
+            height: 2160,
+            duration: 100.0,
+            fps: 24.0,
+            bitrate: None,
+            codec: Some("hevc".to_string()),
+            pix_fmt: Some("yuv420p10le".to_string()),
+            field_order: None,
+            is_hdr: true,
+            hdr_analysis: None,
+            color_space: None,
+            transfer_function: None,
+            color_primaries: None,
+            master_display: None,
+            max_cll: None,
+            max_fall: None,
+            streams: Vec::new(),
+        };
+        assert_eq!(processor.trimmed_frames(&metadata), Some((240..480, 2400)));
+
+        let json = dir.path().join("hdr10plus.json");
+        std::fs::write(
+            &json,
+            serde_json::to_string(&crate::testsignal::hdr10plus_metadata(2400)).unwrap(),
+        )
+        .unwrap();
+        let source = Hdr10PlusMetadata::from_json_file(&json).await.unwrap();
+        let mut extracted = ExtractedMetadata::none(dir.path().to_path_buf());
+        extracted.hdr10_plus = Some(Hdr10PlusProcessingResult::new(json.clone(), source, true));
+
+        let job_dir = JobTempDir::create(dir.path(), &options.output_path).unwrap();
+        let workflow = MetadataWorkflowManager::new(
+            &config,
+            &ffmpeg,
+            &CancelToken::new(),
+            &ToolProgress::default(),
+            &job_dir,
+        )
+        .await
+        .unwrap();
+        processor
+            .trim_dynamic_metadata(&workflow, &mut extracted, &metadata)
+            .await
+            .unwrap();
+
+        // The metadata now covers the ten encoded seconds only
+        assert_eq!(extracted.hdr10_plus.unwrap().summary.frame_count, 240);
+        let trimmed = Hdr10PlusMetadata::from_json_file(&json).await.unwrap();
+        assert_eq!(trimmed.get_frame_count(), 240);
+    }
+}
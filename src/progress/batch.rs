@@ -0,0 +1,169 @@
+//! Overall progress of an `encode` run over several files, drawn as a second
+//! bar below the current file's (see [`ProgressMonitor::with_batch`](super::ProgressMonitor::with_batch)).
+//!
+//! The time left for the batch is the current file's ETA plus the files after
+//! it at the encode speed of earlier runs (seconds per source byte, from the
+//! encode reports), or at the speed of the current file without history.
+
+use super::format_duration;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Steps of the overall bar per file.
+const FILE_STEPS: u64 = 1000;
+
+#[derive(Debug, Clone, PartialEq)]
+struct BatchState {
+    /// Source size of every file of the batch
+    sizes: Vec<Option<u64>>,
+    /// Index of the file being encoded; the ones before it are done
+    current: usize,
+    /// Encode time per source byte of earlier encodes
+    seconds_per_byte: Option<f64>,
+}
+
+impl BatchState {
+    /// Time left for the batch with the current file `fraction` done after
+    /// `elapsed`, and `eta` left by its own estimate.
+    fn remaining(
+        &self,
+        fraction: f64,
+        elapsed: Duration,
+        eta: Option<Duration>,
+    ) -> Option<Duration> {
+        let current_size = self.sizes.get(self.current).copied().flatten();
+        let rate = self.seconds_per_byte.or_else(|| {
+            let size = current_size.filter(|&size| size > 0)?;
+            (fraction > 0.01).then(|| elapsed.as_secs_f64() / fraction / size as f64)
+        })?;
+        let current = match eta {
+            Some(eta) => eta.as_secs_f64(),
+            None => current_size? as f64 * (1.0 - fraction) * rate,
+        };
+        let later: u64 = self
+            .sizes
+            .iter()
+            .skip(self.current + 1)
+            .filter_map(|size| *size)
+            .sum();
+        Some(Duration::from_secs_f64(current + later as f64 * rate))
+    }
+
+    fn message(&self, remaining: Option<Duration>) -> String {
+        let done = format!("{}/{} files done", self.current, self.sizes.len());
+        match remaining {
+            Some(remaining) => format!("{} • batch ETA {}", done, format_duration(remaining)),
+            None => done,
+        }
+    }
+}
+
+/// The batch bar, shared by the progress monitors of the batch's encodes.
+#[derive(Clone)]
+pub struct BatchProgress {
+    multi: MultiProgress,
+    bar: ProgressBar,
+    state: Arc<Mutex<BatchState>>,
+}
+
+impl BatchProgress {
+    /// A bar over files of the given source sizes, estimating the time left
+    /// at `seconds_per_byte` when there is history for it.
+    pub fn new(sizes: Vec<Option<u64>>, seconds_per_byte: Option<f64>) -> Self {
+        let multi = MultiProgress::new();
+        let bar = multi.add(ProgressBar::new(sizes.len() as u64 * FILE_STEPS));
+        bar.set_style(
+            ProgressStyle::with_template("[{wide_bar:.green/white}] Batch | {msg}")
+                .unwrap()
+                .progress_chars("█▉▊▋▌▍▎▏ "),
+        );
+        let state = BatchState {
+            sizes,
+            current: 0,
+            seconds_per_byte,
+        };
+        bar.set_message(state.message(None));
+        Self {
+            multi,
+            bar,
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Moves on to file `index` (counting the files before it as done).
+    pub fn start_file(&self, index: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.current = index;
+        self.bar.set_position(index as u64 * FILE_STEPS);
+        self.bar.set_message(state.message(None));
+    }
+
+    /// Takes the encode speed of the reports, e.g. after another file finished.
+    pub fn set_seconds_per_byte(&self, seconds_per_byte: Option<f64>) {
+        let mut state = self.state.lock().unwrap();
+        if seconds_per_byte.is_some() {
+            state.seconds_per_byte = seconds_per_byte;
+        }
+    }
+
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+
+    /// Draws a file's bar above the batch bar.
+    pub(super) fn add_file_bar(&self, bar: ProgressBar) -> ProgressBar {
+        self.multi.insert_before(&self.bar, bar)
+    }
+
+    pub(super) fn remove_file_bar(&self, bar: &ProgressBar) {
+        self.multi.remove(bar);
+    }
+
+    /// Follows the current file, `fraction` done after `elapsed`.
+    pub(super) fn update_file(&self, fraction: f64, elapsed: Duration, eta: Option<Duration>) {
+        let state = self.state.lock().unwrap();
+        self.bar.set_position(
+            state.current as u64 * FILE_STEPS + (fraction * FILE_STEPS as f64) as u64,
+        );
+        self.bar
+            .set_message(state.message(state.remaining(fraction, elapsed, eta)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_remaining() {
+        const GB: u64 = 1_000_000_000;
+        let mut state = BatchState {
+            sizes: vec![Some(4 * GB), Some(2 * GB), None, Some(GB)],
+            current: 1,
+            seconds_per_byte: Some(1e-6),
+        };
+        // The current file's own ETA, then 1 GB at 1000 s/GB (the unknown size counts nothing)
+        let seconds = |remaining: Option<Duration>| remaining.map(|r| r.as_secs_f64().round());
+        assert_eq!(
+            seconds(state.remaining(
+                0.5,
+                Duration::from_secs(600),
+                Some(Duration::from_secs(700))
+            )),
+            Some(1700.0)
+        );
+        assert_eq!(
+            state.message(Some(Duration::from_secs(1700))),
+            "1/4 files done • batch ETA 28:20"
+        );
+
+        // Without history, at the speed of the current file: 2 GB half done in 600 s
+        state.seconds_per_byte = None;
+        assert_eq!(
+            seconds(state.remaining(0.5, Duration::from_secs(600), None)),
+            Some(1200.0)
+        );
+        assert_eq!(state.remaining(0.0, Duration::ZERO, None), None);
+    }
+}
@@ -1,20 +1,121 @@
+mod batch;
+
+pub use batch::BatchProgress;
+
 use crate::encoding::EncodingMode;
-use crate::utils::{FfmpegWrapper, Result};
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::events::{EncodeEvent, EncodeProgress, EventBus};
+use crate::utils::ffmpeg::ProgressParser;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use futures::future::BoxFuture;
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::VecDeque;
 use std::time::{Duration, Instant};
 use tokio::process::Child;
+use tracing::warn;
+
+/// Progress between two snapshots in the per-file log
+const SNAPSHOT_STEP: f64 = 0.1;
+
+/// Longest time between two snapshots, so a log followed with `logs tail`
+/// keeps moving on slow encodes
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Lines of FFmpeg's stderr kept for error reports
+const ERROR_TAIL_LINES: usize = 20;
+
+/// Final output size extrapolated from the bytes written so far.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SizeProjection {
+    pub projected: u64,
+    pub source: Option<u64>,
+}
+
+impl SizeProjection {
+    /// `None` until 1% is encoded; before that the estimate swings too much.
+    pub fn new(written: u64, progress: f64, source: Option<u64>) -> Option<Self> {
+        (progress > 0.01).then(|| Self {
+            projected: (written as f64 / progress.min(1.0)) as u64,
+            source,
+        })
+    }
+
+    /// Projected size relative to the source (above 1.0 means larger).
+    pub fn ratio(&self) -> Option<f64> {
+        self.source
+            .filter(|&source| source > 0)
+            .map(|source| self.projected as f64 / source as f64)
+    }
+
+    /// Space saved over the source in percent; negative when the output ends up larger.
+    pub fn savings_percent(&self) -> Option<f64> {
+        self.ratio().map(|ratio| (1.0 - ratio) * 100.0)
+    }
+}
+
+impl std::fmt::Display for SizeProjection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.source, self.savings_percent()) {
+            (Some(source), Some(savings)) if savings >= 0.0 => write!(
+                f,
+                "{} > ~{} (-{:.0}%)",
+                format_size(source),
+                format_size(self.projected),
+                savings
+            ),
+            (Some(source), Some(savings)) => write!(
+                f,
+                "{} > ~{} (+{:.0}%!)",
+                format_size(source),
+                format_size(self.projected),
+                -savings
+            ),
+            _ => write!(f, "~{}", format_size(self.projected)),
+        }
+    }
+}
+
+/// Something that can ask a running encode to pause, e.g. a schedule window or
+/// a thermal limit. Checked once per progress tick while FFmpeg runs, on the
+/// async runtime: readings must not block it.
+pub trait PauseCondition: Send {
+    /// Returns why the encode should be paused right now, or `None` to let it run.
+    fn pause_reason(&mut self) -> BoxFuture<'_, Option<String>>;
+}
+
+/// Something that must stop a running encode, e.g. the output disk filling up.
+/// Checked once per progress tick while FFmpeg runs, on the async runtime:
+/// readings must not block it.
+pub trait AbortCondition: Send {
+    /// Returns why the encode has to be aborted, or `None` to let it continue.
+    fn abort_reason(&mut self) -> BoxFuture<'_, Option<String>>;
+}
 
 pub struct ProgressMonitor {
     progress_bar: ProgressBar,
     start_time: Instant,
     total_duration: f64,
     total_frames: Option<u32>,
+    /// `total_frames` was counted, not estimated, so frames drive the progress
+    exact_frames: bool,
     is_two_pass: bool,
     last_progress: f64,
     source_fps: f32,
     last_time: f64,
     stall_counter: u32,
     source_file_size: Option<u64>,
+    next_snapshot: f64,
+    last_snapshot: Instant,
+    projection: Option<SizeProjection>,
+    /// Projected/source ratio above which the encode is abandoned, and the
+    /// percentage that has to be encoded before the projection counts
+    size_limit: Option<(f64, f64)>,
+    final_progress: Option<crate::utils::ffmpeg::ProgressInfo>,
+    ffmpeg_errors: Vec<String>,
+    pause_conditions: Vec<Box<dyn PauseCondition>>,
+    abort_conditions: Vec<Box<dyn AbortCondition>>,
+    events: EventBus,
+    /// Batch bar drawn below this file's bar
+    batch: Option<BatchProgress>,
 }
 
 impl ProgressMonitor {
@@ -59,15 +160,112 @@ impl ProgressMonitor {
             start_time: Instant::now(),
             total_duration,
             total_frames,
+            exact_frames: false,
             is_two_pass,
             last_progress: 0.0,
             source_fps: fps,
             last_time: 0.0,
             stall_counter: 0,
             source_file_size,
+            next_snapshot: SNAPSHOT_STEP,
+            last_snapshot: Instant::now(),
+            projection: None,
+            size_limit: None,
+            final_progress: None,
+            ffmpeg_errors: Vec::new(),
+            pause_conditions: Vec::new(),
+            abort_conditions: Vec::new(),
+            events: EventBus::new(),
+            batch: None,
         }
     }
 
+    /// Reports progress, pauses and resumes on `events`.
+    pub fn with_events(mut self, events: EventBus) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Hides the terminal progress bar, for callers that follow the events instead.
+    pub fn without_progress_bar(self) -> Self {
+        self.progress_bar
+            .set_draw_target(ProgressDrawTarget::hidden());
+        self
+    }
+
+    /// Draws the bar above the overall bar of `batch` and advances that one
+    /// along with this encode.
+    pub fn with_batch(mut self, batch: BatchProgress) -> Self {
+        self.progress_bar = batch.add_file_bar(self.progress_bar.clone());
+        self.batch = Some(batch);
+        self
+    }
+
+    /// Uses a counted frame count instead of duration × frame rate, and
+    /// follows frames rather than timestamps, which drift for VFR sources.
+    pub fn with_exact_frame_count(mut self, frames: u32) -> Self {
+        self.total_frames = Some(frames);
+        self.exact_frames = true;
+        self
+    }
+
+    /// Abandons the encode with [`Error::Inefficient`](crate::utils::Error::Inefficient)
+    /// once at least `min_percent` is encoded and the projected output exceeds
+    /// `ratio` times the source size.
+    pub fn with_size_limit(mut self, ratio: f64, min_percent: f64) -> Self {
+        self.size_limit = Some((ratio, min_percent));
+        self
+    }
+
+    fn inefficiency_reason(&self) -> Option<String> {
+        let (limit, min_percent) = self.size_limit?;
+        let projection = self.projection?;
+        let ratio = projection.ratio()?;
+        (self.last_progress * 100.0 >= min_percent && ratio > limit).then(|| {
+            format!(
+                "projected output {} is {:.2}x the source {} after {:.0}% (limit {:.2}x)",
+                format_size(projection.projected),
+                ratio,
+                format_size(projection.source.unwrap_or_default()),
+                self.last_progress * 100.0,
+                limit
+            )
+        })
+    }
+
+    fn emit(&self, event: EncodeEvent) {
+        self.events.emit(event);
+    }
+
+    /// Suspends FFmpeg (SIGSTOP) whenever `condition` reports a reason, and
+    /// resumes it (SIGCONT) once no condition does.
+    pub fn add_pause_condition(&mut self, condition: Box<dyn PauseCondition>) {
+        self.pause_conditions.push(condition);
+    }
+
+    /// Kills FFmpeg and fails the encode as soon as `condition` reports a reason.
+    pub fn add_abort_condition(&mut self, condition: Box<dyn AbortCondition>) {
+        self.abort_conditions.push(condition);
+    }
+
+    async fn current_pause_reason(&mut self) -> Option<String> {
+        for condition in &mut self.pause_conditions {
+            if let Some(reason) = condition.pause_reason().await {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
+    async fn current_abort_reason(&mut self) -> Option<String> {
+        for condition in &mut self.abort_conditions {
+            if let Some(reason) = condition.abort_reason().await {
+                return Some(reason);
+            }
+        }
+        None
+    }
+
     pub fn set_message(&self, message: &str) {
         self.progress_bar.set_message(message.to_string());
     }
@@ -87,7 +285,7 @@ impl ProgressMonitor {
     }
 
     pub async fn monitor_encoding(&mut self, mut child: Child) -> Result<std::process::ExitStatus> {
-        use std::path::Path;
+        use tokio::io::{AsyncBufReadExt, BufReader};
         use tokio::time::{interval, Duration};
 
         // For two-pass encoding, reset start time when monitoring begins (Pass 2)
@@ -96,108 +294,123 @@ impl ProgressMonitor {
             self.last_progress = 0.0; // Reset progress for Pass 2
             self.last_time = 0.0; // Reset time tracking for Pass 2
             self.stall_counter = 0; // Reset stall counter for Pass 2
+            self.next_snapshot = SNAPSHOT_STEP;
+            self.last_snapshot = Instant::now();
+            self.projection = None;
             self.start_pass_two();
         }
 
-        // Get progress file path (same format as in encoding)
-        let progress_file = format!("/tmp/ffmpeg_progress_{}.txt", std::process::id());
+        // Progress blocks arrive on stdout and errors on stderr; both are read
+        // as they come so FFmpeg never blocks on a full pipe
+        let (progress_tx, mut progress_rx) = tokio::sync::watch::channel(None);
+        let mut parser = ProgressParser::new(self.total_duration);
+        let progress_reader = child.stdout.take().map(|stdout| {
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stdout).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if let Some(block) = parser.feed(&line) {
+                        progress_tx.send_replace(Some(block));
+                    }
+                }
+            })
+        });
+        let error_reader = child.stderr.take().map(|stderr| {
+            tokio::spawn(async move {
+                let mut tail = VecDeque::with_capacity(ERROR_TAIL_LINES);
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if tail.len() == ERROR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+                Vec::from(tail)
+            })
+        });
 
-        // Monitor progress file for encoding updates
+        // Pick up the latest progress block once per tick
         let mut interval_timer = interval(Duration::from_millis(1000));
+        let mut paused: Option<String> = None;
+        // FFmpeg could not be stopped: not retried until the pause reason clears
+        let mut pause_failed = false;
 
         loop {
             interval_timer.tick().await;
 
-            // Check if process is still running
-            match child.try_wait()? {
-                Some(status) => {
-                    self.finish();
-                    return Ok(status);
-                }
-                None => {
-                    // Process still running, check progress file
-                    if Path::new(&progress_file).exists() {
-                        if let Ok(content) = tokio::fs::read_to_string(&progress_file).await {
-                            if let Some(progress_info) = self.parse_progress_file(&content) {
-                                self.update_progress(&progress_info);
-                            }
-                        }
-                    }
-                }
+            if let Some(reason) = self.current_abort_reason().await {
+                warn!("Aborting encode: {}", reason);
+                let _ = child.kill().await;
+                self.progress_bar
+                    .abandon_with_message(format!("Aborted: {}", reason));
+                return Err(crate::utils::Error::encoding(format!(
+                    "Encoding aborted: {}",
+                    reason
+                )));
             }
-        }
-    }
 
-    fn parse_progress_file(&self, content: &str) -> Option<crate::utils::ffmpeg::ProgressInfo> {
-        let mut progress = crate::utils::ffmpeg::ProgressInfo {
-            frame: None,
-            fps: None,
-            bitrate: None,
-            total_size: None,
-            time: 0.0,
-            speed: None,
-            progress_percentage: 0.0,
-        };
+            if let Some(reason) = self.inefficiency_reason() {
+                warn!("Abandoning encode: {}", reason);
+                let _ = child.kill().await;
+                self.progress_bar
+                    .abandon_with_message(format!("Inefficient: {}", reason));
+                return Err(crate::utils::Error::inefficient(reason));
+            }
 
-        // Parse key=value lines from FFmpeg progress output
-        let lines: Vec<&str> = content.lines().collect();
-        let last_lines: Vec<&str> = lines.iter().rev().take(20).cloned().collect();
-
-        for line in last_lines.iter().rev() {
-            let line = line.trim();
-            if line.contains('=') {
-                let parts: Vec<&str> = line.splitn(2, '=').collect();
-                if parts.len() == 2 {
-                    let key = parts[0].trim();
-                    let value = parts[1].trim();
-
-                    match key {
-                        "frame" => {
-                            if value.trim() != "N/A" {
-                                progress.frame = value.parse().ok();
-                            }
-                        }
-                        "fps" => {
-                            if value.trim() != "N/A" {
-                                progress.fps = value.parse().ok();
-                            }
-                        }
-                        "out_time_us" => {
-                            if value.trim() != "N/A" {
-                                if let Ok(time_us) = value.parse::<u64>() {
-                                    progress.time = time_us as f64 / 1_000_000.0; // Convert microseconds to seconds
-                                    if self.total_duration > 0.0 {
-                                        progress.progress_percentage =
-                                            ((progress.time / self.total_duration) * 100.0)
-                                                .min(100.0)
-                                                as f32;
-                                    }
+            if !self.pause_conditions.is_empty() {
+                let reason = self.current_pause_reason().await;
+                if let Some(pid) = child.id() {
+                    match (&paused, reason) {
+                        (None, Some(reason)) if !pause_failed => {
+                            match signal_process(pid, JobSignal::Stop) {
+                                Ok(()) => {
+                                    self.set_message(&format!("Paused: {}", reason));
+                                    self.emit(EncodeEvent::Paused {
+                                        reason: reason.clone(),
+                                    });
+                                    paused = Some(reason);
+                                }
+                                Err(e) => {
+                                    warn!("Cannot pause the encode ({}): {}", reason, e);
+                                    pause_failed = true;
                                 }
                             }
                         }
-                        "speed" => {
-                            if value.trim() != "N/A" {
-                                // Remove 'x' suffix if present
-                                let speed_str = value.trim_end_matches('x');
-                                progress.speed = speed_str.parse().ok();
-                            }
-                        }
-                        "total_size" => {
-                            if value.trim() != "N/A" {
-                                progress.total_size = value.parse().ok();
+                        (None, None) => pause_failed = false,
+                        (Some(_), None) => match signal_process(pid, JobSignal::Continue) {
+                            Ok(()) => {
+                                self.emit(EncodeEvent::Resumed);
+                                paused = None;
                             }
-                        }
-                        _ => {} // Ignore other keys
+                            Err(e) => warn!("Cannot resume the encode, retrying: {}", e),
+                        },
+                        _ => {}
                     }
                 }
             }
-        }
 
-        // Only return if we have meaningful progress data
-        if progress.frame.is_some() || progress.time > 0.0 {
-            Some(progress)
-        } else {
-            None
+            // Check if process is still running
+            match child.try_wait()? {
+                Some(status) => {
+                    // Keep the last progress block so callers can report final frame counts
+                    if let Some(reader) = progress_reader {
+                        let _ = reader.await;
+                    }
+                    self.final_progress = progress_rx.borrow().clone();
+                    if let Some(reader) = error_reader {
+                        self.ffmpeg_errors = reader.await.unwrap_or_default();
+                    }
+                    self.finish();
+                    return Ok(status);
+                }
+                None => {
+                    if paused.is_none() && progress_rx.has_changed().unwrap_or(false) {
+                        let progress_info = progress_rx.borrow_and_update().clone();
+                        if let Some(progress_info) = progress_info {
+                            self.update_progress(&progress_info);
+                        }
+                    }
+                }
+            }
         }
     }
 
@@ -218,7 +431,7 @@ impl ProgressMonitor {
 
         // Use frame-based progress as fallback when time-based fails, shows N/A, or stalls
         let mut frame_based_progress = None;
-        if current_progress <= 0.0 || info.time <= 0.0 || time_stalled {
+        if current_progress <= 0.0 || info.time <= 0.0 || time_stalled || self.exact_frames {
             if let (Some(current_frame), Some(total_frames)) = (info.frame, self.total_frames) {
                 if current_frame > 0 && total_frames > 0 {
                     let frame_progress = current_frame as f64 / total_frames as f64;
@@ -288,25 +501,17 @@ impl ProgressMonitor {
             message_parts.push(format!("{:.1}x", speed));
         }
 
-        // Add size estimation if we have enough data
-        if current_progress > 0.01 {
-            if let Some(current_size) = info.total_size {
-                let estimated_final_size = (current_size as f64 / current_progress) as u64;
-
-                // Format as "source > estimated output" if we have source size
-                if let Some(source_size) = self.source_file_size {
-                    message_parts.push(format!(
-                        "{} > ~{}",
-                        format_size(source_size),
-                        format_size(estimated_final_size)
-                    ));
-                } else {
-                    message_parts.push(format!("~{}", format_size(estimated_final_size)));
-                }
-            }
+        // "source > ~projected (savings)" once enough has been encoded
+        let projection = info.total_size.and_then(|written| {
+            SizeProjection::new(written, current_progress, self.source_file_size)
+        });
+        if let Some(projection) = &projection {
+            message_parts.push(projection.to_string());
         }
+        self.projection = projection;
 
         // Enhanced ETA calculation with multiple methods
+        let mut eta = None;
         if current_progress > 0.005 {
             let elapsed = self.start_time.elapsed().as_secs_f64();
 
@@ -347,14 +552,62 @@ impl ProgressMonitor {
             eta_seconds = eta_seconds.clamp(5.0, 24.0 * 3600.0);
 
             if eta_seconds > 0.0 {
-                let eta = Duration::from_secs_f64(eta_seconds);
-                message_parts.push(format!("ETA {}", format_duration(eta)));
+                let remaining = Duration::from_secs_f64(eta_seconds);
+                message_parts.push(format!("ETA {}", format_duration(remaining)));
+                eta = Some(remaining);
             }
         }
 
         if !message_parts.is_empty() {
             self.set_message(&message_parts.join(" • "));
         }
+        if let Some(batch) = &self.batch {
+            batch.update_file(current_progress, self.start_time.elapsed(), eta);
+        }
+
+        let progress = EncodeProgress {
+            percent: current_progress * 100.0,
+            frame: info.frame,
+            fps: info.fps,
+            speed: info.speed,
+            eta,
+            size: info.total_size,
+            projected_size: projection.map(|p| p.projected),
+            savings_percent: projection.and_then(|p| p.savings_percent()),
+        };
+        let step_reached = current_progress >= self.next_snapshot;
+        if (step_reached || self.last_snapshot.elapsed() >= SNAPSHOT_INTERVAL)
+            && current_progress < 1.0
+        {
+            while self.next_snapshot <= current_progress {
+                self.next_snapshot += SNAPSHOT_STEP;
+            }
+            self.last_snapshot = Instant::now();
+            self.emit(EncodeEvent::ProgressSnapshot(progress.clone()));
+        }
+        self.emit(EncodeEvent::Progress(progress));
+    }
+
+    /// Frames written by the finished encode, from FFmpeg's last progress report.
+    pub fn frames_encoded(&self) -> Option<u32> {
+        self.final_progress.as_ref().and_then(|p| p.frame)
+    }
+
+    /// Frames FFmpeg reported as dropped during the finished encode.
+    pub fn dropped_frames(&self) -> Option<u32> {
+        self.final_progress.as_ref().and_then(|p| p.dropped_frames)
+    }
+
+    /// The last lines FFmpeg wrote to stderr during the finished run; at
+    /// `-loglevel error` these are its error messages.
+    pub fn ffmpeg_errors(&self) -> &[String] {
+        &self.ffmpeg_errors
+    }
+
+    /// Expected frame count: counted if known, else derived from source
+    /// duration and frame rate.
+    pub fn expected_frames(&self) -> Option<u32> {
+        self.total_frames
     }
 
     fn finish(&self) {
@@ -362,14 +615,56 @@ impl ProgressMonitor {
         self.progress_bar.set_position(10000);
         self.progress_bar
             .finish_with_message(format!("Completed in {}", format_duration(duration)));
+    }
+}
 
-        // Cleanup progress file
-        let progress_file = format!("/tmp/ffmpeg_progress_{}.txt", std::process::id());
-        let _ = std::fs::remove_file(&progress_file);
+impl Drop for ProgressMonitor {
+    fn drop(&mut self) {
+        // The next file of the batch gets a bar of its own
+        if let Some(batch) = &self.batch {
+            batch.remove_file_bar(&self.progress_bar);
+        }
     }
 }
 
-fn format_duration(duration: Duration) -> String {
+/// Job-control signals that pause and resume FFmpeg.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JobSignal {
+    Stop,
+    Continue,
+}
+
+/// Sends `signal` to process `pid`.
+#[cfg(unix)]
+fn signal_process(pid: u32, signal: JobSignal) -> Result<()> {
+    use nix::sys::signal::{kill, Signal};
+    use nix::unistd::Pid;
+
+    let signal = match signal {
+        JobSignal::Stop => Signal::SIGSTOP,
+        JobSignal::Continue => Signal::SIGCONT,
+    };
+    let pid = i32::try_from(pid)
+        .map_err(|_| Error::encoding(format!("Process id {} out of range", pid)))?;
+    kill(Pid::from_raw(pid), signal).map_err(|e| {
+        Error::encoding(format!(
+            "Failed to send {} to process {}: {}",
+            signal, pid, e
+        ))
+    })
+}
+
+/// Without job-control signals an encode cannot be suspended.
+#[cfg(not(unix))]
+fn signal_process(_pid: u32, signal: JobSignal) -> Result<()> {
+    Err(Error::validation(format!(
+        "Sending {:?} to FFmpeg needs Unix job control; the schedule window and thermal \
+         throttle cannot pause encodes on this platform",
+        signal
+    )))
+}
+
+pub(crate) fn format_duration(duration: Duration) -> String {
     let total_secs = duration.as_secs();
     let hours = total_secs / 3600;
     let minutes = (total_secs % 3600) / 60;
@@ -398,3 +693,46 @@ fn format_size(bytes: u64) -> String {
         format!("{:.1} {}", size, UNITS[unit_index])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_size_projection() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        assert_eq!(SizeProjection::new(GB, 0.005, Some(10 * GB)), None);
+
+        let smaller = SizeProjection::new(GB, 0.25, Some(10 * GB)).unwrap();
+        assert_eq!(smaller.projected, 4 * GB);
+        assert_eq!(smaller.savings_percent(), Some(60.0));
+        assert_eq!(smaller.to_string(), "10.0 GB > ~4.0 GB (-60%)");
+
+        let larger = SizeProjection::new(5 * GB, 0.5, Some(8 * GB)).unwrap();
+        assert_eq!(larger.savings_percent(), Some(-25.0));
+        assert_eq!(larger.to_string(), "8.0 GB > ~10.0 GB (+25%!)");
+
+        assert_eq!(larger.ratio(), Some(1.25));
+
+        let unknown_source = SizeProjection::new(GB, 0.5, None).unwrap();
+        assert_eq!(unknown_source.savings_percent(), None);
+        assert_eq!(unknown_source.ratio(), None);
+        assert_eq!(unknown_source.to_string(), "~2.0 GB");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_signal_process() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        assert!(signal_process(child.id(), JobSignal::Stop).is_ok());
+        assert!(signal_process(child.id(), JobSignal::Continue).is_ok());
+        child.kill().unwrap();
+        child.wait().unwrap();
+
+        // A process that is gone reports the failure instead of pausing nothing
+        assert!(signal_process(child.id(), JobSignal::Stop).is_err());
+    }
+}
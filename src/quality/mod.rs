@@ -0,0 +1,403 @@
+//! Full-reference quality metrics: an encoded output scored against its
+//! source with VMAF, SSIM and XPSNR (FFmpeg filters) or butteraugli (libjxl's
+//! `butteraugli_main` on sampled frames), as selected by `quality.metrics`.
+//!
+//! The source goes through the encode's own filters first (crop, scaling,
+//! tone mapping, ...), so the frames line up and the scores measure what the
+//! encoder lost.
+//!
+//! HDR outputs are compared in PQ (or HLG) with an HDR-suited VMAF model, or
+//! with both sides tone mapped identically (`quality.hdr_mode`); the SDR
+//! models on untreated PQ code values would score the wrong thing.
+
+use crate::config::{Config, QualityHdrMode, QualityMetric};
+use crate::encoding::FilterBuilder;
+use crate::utils::{Error, FfmpegWrapper, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::debug;
+
+/// Brings both sides to the same timeline and pixel format before comparing.
+const PREPARE: &str = "setpts=PTS-STARTPTS,format=yuv420p10le";
+
+impl QualityMetric {
+    pub(crate) fn label(&self) -> &'static str {
+        match self {
+            Self::Vmaf => "VMAF",
+            Self::Ssim => "SSIM",
+            Self::Xpsnr => "XPSNR",
+            Self::Butteraugli => "butteraugli",
+        }
+    }
+
+    /// Whether a higher score means a closer match (all but butteraugli's distance).
+    pub fn higher_is_better(&self) -> bool {
+        *self != Self::Butteraugli
+    }
+}
+
+/// One metric's result for a whole output.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QualityScore {
+    pub metric: QualityMetric,
+    /// VMAF 0-100, SSIM 0-1, XPSNR in dB (luma), butteraugli mean distance
+    pub value: f64,
+}
+
+impl fmt::Display for QualityScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.metric {
+            QualityMetric::Vmaf => write!(f, "VMAF {:.2}", self.value),
+            QualityMetric::Ssim => write!(f, "SSIM {:.5}", self.value),
+            QualityMetric::Xpsnr => write!(f, "XPSNR {:.2} dB", self.value),
+            QualityMetric::Butteraugli => write!(f, "butteraugli {:.3}", self.value),
+        }
+    }
+}
+
+/// An output and the source it was encoded from.
+pub struct QualityComparison<'a> {
+    config: &'a Config,
+    distorted: &'a Path,
+    reference: &'a Path,
+    /// Video stream of the source that was encoded, e.g. `v:0`
+    reference_stream: String,
+    /// Filters the encode applied to the source
+    reference_filters: Option<String>,
+    /// Transfer of both sides when the output is HDR
+    hdr_transfer: Option<String>,
+}
+
+impl<'a> QualityComparison<'a> {
+    pub fn new(config: &'a Config, distorted: &'a Path, reference: &'a Path) -> Self {
+        Self {
+            config,
+            distorted,
+            reference,
+            reference_stream: "v:0".to_string(),
+            reference_filters: None,
+            hdr_transfer: None,
+        }
+    }
+
+    /// Compares against this video stream of the source (`v:N`).
+    pub fn with_reference_stream(mut self, stream: impl Into<String>) -> Self {
+        self.reference_stream = stream.into();
+        self
+    }
+
+    /// Runs the source through `filters` (an FFmpeg filter chain) before comparing.
+    pub fn with_reference_filters(mut self, filters: Option<String>) -> Self {
+        self.reference_filters = filters.filter(|filters| !filters.is_empty());
+        self
+    }
+
+    /// Marks the output as HDR with this transfer (`smpte2084`, `arib-std-b67`).
+    pub fn with_hdr_transfer(mut self, transfer: Option<String>) -> Self {
+        self.hdr_transfer = transfer;
+        self
+    }
+
+    /// Scores the output with each of `metrics`. Butteraugli writes its
+    /// sample frames to `work_dir`.
+    pub async fn measure(
+        &self,
+        metrics: &[QualityMetric],
+        work_dir: &Path,
+    ) -> Result<Vec<QualityScore>> {
+        let mut scores = Vec::with_capacity(metrics.len());
+        for &metric in metrics {
+            let value = match metric {
+                QualityMetric::Butteraugli => self.butteraugli(work_dir).await?,
+                _ => self.filter_metric(metric).await?,
+            };
+            scores.push(QualityScore { metric, value });
+        }
+        Ok(scores)
+    }
+
+    /// Tone mapping applied to both sides when `metric` compares HDR frames as SDR.
+    fn comparison_tonemap(&self, metric: QualityMetric) -> Option<String> {
+        let transfer = self.hdr_transfer.as_deref()?;
+        let tonemapped = metric == QualityMetric::Butteraugli
+            || self.config.quality.hdr_mode == QualityHdrMode::Tonemap;
+        tonemapped.then(|| {
+            FilterBuilder::new(self.config)
+                .with_tonemap_from(Some(transfer))
+                .build()
+                .to_string()
+        })
+    }
+
+    /// Filters for the output and for the source before `metric` compares them.
+    fn side_filters(&self, metric: QualityMetric) -> (Option<String>, Option<String>) {
+        let tonemap = self.comparison_tonemap(metric);
+        let reference = match (&self.reference_filters, &tonemap) {
+            (Some(filters), Some(tonemap)) => Some(format!("{},{}", filters, tonemap)),
+            (filters, tonemap) => filters.clone().or_else(|| tonemap.clone()),
+        };
+        (tonemap, reference)
+    }
+
+    fn filtergraph(&self, metric: QualityMetric) -> String {
+        let (distorted, reference) = self.side_filters(metric);
+        format!(
+            "[0:v:0]{}{prepare}[dist];[1:{}]{}{prepare}[ref];[dist][ref]{}",
+            leading(distorted),
+            self.reference_stream,
+            leading(reference),
+            self.comparison_filter(metric),
+            prepare = PREPARE
+        )
+    }
+
+    fn comparison_filter(&self, metric: QualityMetric) -> String {
+        match metric {
+            QualityMetric::Vmaf => {
+                let quality = &self.config.quality;
+                let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+                let model = match (&self.hdr_transfer, quality.hdr_mode) {
+                    (Some(_), QualityHdrMode::Pq) => {
+                        format!("model=version={}:", quality.hdr_vmaf_model)
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "libvmaf={}n_subsample={}:n_threads={}",
+                    model, quality.vmaf_subsample, threads
+                )
+            }
+            QualityMetric::Ssim => "ssim".to_string(),
+            QualityMetric::Xpsnr => "xpsnr".to_string(),
+            QualityMetric::Butteraugli => unreachable!("butteraugli runs outside FFmpeg"),
+        }
+    }
+
+    /// Runs one of FFmpeg's comparison filters over both videos and reads
+    /// the summary it logs at the end.
+    async fn filter_metric(&self, metric: QualityMetric) -> Result<f64> {
+        let graph = self.filtergraph(metric);
+        debug!("{} filtergraph: {}", metric.label(), graph);
+        let output = Command::new(&self.config.tools.ffmpeg)
+            .args(["-hide_banner", "-nostats", "-i"])
+            .arg(self.distorted)
+            .arg("-i")
+            .arg(self.reference)
+            .args(["-lavfi", &graph, "-f", "null", "-"])
+            .output()
+            .await?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to measure {}: {}",
+                metric.label(),
+                stderr.lines().last().unwrap_or_default()
+            )));
+        }
+        parse_filter_score(metric, &stderr)
+            .ok_or_else(|| Error::parse(format!("No {} score in FFmpeg's output", metric.label())))
+    }
+
+    /// Mean butteraugli distance over `quality.butteraugli_frames` frames
+    /// spread over the output.
+    async fn butteraugli(&self, work_dir: &Path) -> Result<f64> {
+        let tool = self.config.tools.butteraugli.as_deref().ok_or_else(|| {
+            Error::tool("The butteraugli metric needs tools.butteraugli".to_string())
+        })?;
+        let ffmpeg = FfmpegWrapper::new(
+            self.config.tools.ffmpeg.clone(),
+            self.config.tools.ffprobe.clone(),
+        );
+        let duration = ffmpeg.get_video_metadata(self.distorted).await?.duration;
+
+        let (distorted_filters, reference_filters) = self.side_filters(QualityMetric::Butteraugli);
+        let frames = self.config.quality.butteraugli_frames;
+        let mut total = 0.0;
+        for (index, timestamp) in sample_timestamps(duration, frames).into_iter().enumerate() {
+            let distorted = work_dir.join(format!("butteraugli_{}_distorted.png", index));
+            let reference = work_dir.join(format!("butteraugli_{}_reference.png", index));
+            self.extract_frame(
+                self.distorted,
+                "0:v:0",
+                distorted_filters.as_deref(),
+                timestamp,
+                &distorted,
+            )
+            .await?;
+            self.extract_frame(
+                self.reference,
+                &format!("0:{}", self.reference_stream),
+                reference_filters.as_deref(),
+                timestamp,
+                &reference,
+            )
+            .await?;
+
+            let output = Command::new(tool)
+                .arg(&reference)
+                .arg(&distorted)
+                .output()
+                .await
+                .map_err(|e| Error::tool(format!("Failed to run {}: {}", tool, e)))?;
+            let _ = std::fs::remove_file(&distorted);
+            let _ = std::fs::remove_file(&reference);
+            if !output.status.success() {
+                return Err(Error::tool(format!(
+                    "{} failed: {}",
+                    tool,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+            let distance = parse_butteraugli(&String::from_utf8_lossy(&output.stdout))
+                .ok_or_else(|| Error::parse(format!("No distance in the output of {}", tool)))?;
+            debug!("butteraugli at {:.3}s: {}", timestamp, distance);
+            total += distance;
+        }
+        Ok(total / frames as f64)
+    }
+
+    async fn extract_frame(
+        &self,
+        input: &Path,
+        stream: &str,
+        filters: Option<&str>,
+        timestamp: f64,
+        image: &Path,
+    ) -> Result<()> {
+        let mut cmd = Command::new(&self.config.tools.ffmpeg);
+        cmd.args(["-loglevel", "error", "-hide_banner", "-y"])
+            .args(["-ss", &timestamp.to_string()])
+            .arg("-i")
+            .arg(input)
+            .args(["-map", stream]);
+        if let Some(filters) = filters {
+            cmd.args(["-vf", filters]);
+        }
+        let output = cmd
+            .args(["-frames:v", "1", "-pix_fmt", "rgb24"])
+            .arg(image)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to extract a frame at {:.3}s from {}: {}",
+                timestamp,
+                input.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `filters` followed by a comma, to go in front of more filters.
+fn leading(filters: Option<String>) -> String {
+    filters.map(|filters| filters + ",").unwrap_or_default()
+}
+
+/// Middle of each of `count` equal slices of the video.
+fn sample_timestamps(duration: f64, count: u32) -> Vec<f64> {
+    let slice = duration.max(0.0) / count as f64;
+    (0..count)
+        .map(|index| (index as f64 + 0.5) * slice)
+        .collect()
+}
+
+/// Reads the summary line FFmpeg logs when a comparison filter finishes.
+fn parse_filter_score(metric: QualityMetric, stderr: &str) -> Option<f64> {
+    let (line_marker, value_marker) = match metric {
+        QualityMetric::Vmaf => ("VMAF score", "VMAF score:"),
+        QualityMetric::Ssim => ("SSIM Y:", "All:"),
+        QualityMetric::Xpsnr => ("XPSNR average", "y:"),
+        QualityMetric::Butteraugli => return None,
+    };
+    let line = stderr
+        .lines()
+        .rev()
+        .find(|line| line.contains(line_marker))?;
+    let (_, rest) = line.split_once(value_marker)?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+/// `butteraugli_main` prints the maximum distance on its first line.
+fn parse_butteraugli(stdout: &str) -> Option<f64> {
+    stdout.lines().next()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_scores_and_filtergraph() {
+        let vmaf = "[Parsed_libvmaf_4 @ 0x55d5c8a0] VMAF score: 95.342817\n";
+        assert_eq!(
+            parse_filter_score(QualityMetric::Vmaf, vmaf),
+            Some(95.342817)
+        );
+        let ssim = "[Parsed_ssim_4 @ 0x5581] SSIM Y:0.987091 (18.891) U:0.991 (20.5) \
+                    V:0.992 (21.0) All:0.988731 (19.480281)\n";
+        assert_eq!(
+            parse_filter_score(QualityMetric::Ssim, ssim),
+            Some(0.988731)
+        );
+        let xpsnr = "[Parsed_xpsnr_4 @ 0x5581] XPSNR average, 240 frames  y: 41.2316  \
+                     u: 44.1020  v: 45.0087\n";
+        assert_eq!(
+            parse_filter_score(QualityMetric::Xpsnr, xpsnr),
+            Some(41.2316)
+        );
+        assert_eq!(parse_filter_score(QualityMetric::Xpsnr, vmaf), None);
+        assert_eq!(
+            parse_butteraugli("1.8734051\n3-norm: 0.912\n"),
+            Some(1.8734051)
+        );
+        assert_eq!(sample_timestamps(100.0, 4), [12.5, 37.5, 62.5, 87.5]);
+
+        let config = Config::load_default().unwrap();
+        let comparison = QualityComparison::new(&config, Path::new("out.mkv"), Path::new("in.mkv"))
+            .with_reference_stream("v:1")
+            .with_reference_filters(Some("crop=1920:800:0:140".to_string()));
+        assert_eq!(
+            comparison.filtergraph(QualityMetric::Ssim),
+            "[0:v:0]setpts=PTS-STARTPTS,format=yuv420p10le[dist];\
+             [1:v:1]crop=1920:800:0:140,setpts=PTS-STARTPTS,format=yuv420p10le[ref];\
+             [dist][ref]ssim"
+        );
+        assert_eq!(
+            QualityScore {
+                metric: QualityMetric::Xpsnr,
+                value: 41.2316
+            }
+            .to_string(),
+            "XPSNR 41.23 dB"
+        );
+    }
+
+    #[test]
+    fn test_hdr_comparison() {
+        fn pq(config: &Config) -> QualityComparison<'_> {
+            QualityComparison::new(config, Path::new("out.mkv"), Path::new("in.mkv"))
+                .with_hdr_transfer(Some("smpte2084".to_string()))
+        }
+        let mut config = Config::load_default().unwrap();
+
+        let vmaf = pq(&config).filtergraph(QualityMetric::Vmaf);
+        assert!(vmaf.contains("[dist][ref]libvmaf=model=version=vmaf_v0.6.1neg:n_subsample=5:"));
+        assert!(!vmaf.contains("tonemap"));
+        let (distorted, reference) = pq(&config).side_filters(QualityMetric::Butteraugli);
+        assert!(distorted
+            .as_deref()
+            .is_some_and(|filters| filters.starts_with("zscale=tin=smpte2084:")));
+        assert_eq!(distorted, reference);
+
+        config.quality.hdr_mode = QualityHdrMode::Tonemap;
+        let ssim = pq(&config).filtergraph(QualityMetric::Ssim);
+        assert_eq!(ssim.matches("tonemap=tonemap=").count(), 2);
+        assert!(pq(&config)
+            .filtergraph(QualityMetric::Vmaf)
+            .contains("[dist][ref]libvmaf=n_subsample="));
+    }
+}
@@ -0,0 +1,403 @@
+use crate::cli::EncodeArgs;
+use crate::schedule::TimeWindow;
+use crate::utils::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Running => "running",
+            Self::Completed => "completed",
+            Self::Failed => "failed",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed | Self::Failed)
+    }
+}
+
+/// A single queued encode: one input file plus the options it was queued with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueJob {
+    pub id: String,
+    pub args: EncodeArgs,
+    pub status: JobStatus,
+    pub added_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl QueueJob {
+    pub fn new(args: EncodeArgs) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+        Self {
+            id,
+            args,
+            status: JobStatus::Pending,
+            added_at: chrono::Local::now().to_rfc3339(),
+            finished_at: None,
+            error: None,
+        }
+    }
+
+    /// The job's input file (queued jobs always carry exactly one).
+    pub fn input(&self) -> Option<&Path> {
+        self.args.input.first().map(PathBuf::as_path)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct QueueFile {
+    jobs: Vec<QueueJob>,
+}
+
+/// The on-disk encoding queue. Every mutation is written back immediately so
+/// `queue add` can be used while `queue run` is processing.
+#[derive(Debug)]
+pub struct EncodingQueue {
+    path: PathBuf,
+    jobs: Vec<QueueJob>,
+}
+
+impl EncodingQueue {
+    /// Default queue location: `<data dir>/ven/queue.json`.
+    pub fn default_path() -> Result<PathBuf> {
+        dirs::data_dir()
+            .map(|dir| dir.join("ven").join("queue.json"))
+            .ok_or_else(|| Error::validation("No data directory available for the queue"))
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let jobs = if path.exists() {
+            let content = std::fs::read_to_string(&path)?;
+            let file: QueueFile = serde_json::from_str(&content).map_err(|e| {
+                Error::parse(format!("Failed to read queue {}: {}", path.display(), e))
+            })?;
+            file.jobs
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self { path, jobs })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn jobs(&self) -> &[QueueJob] {
+        &self.jobs
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        // Write to a sibling file first so a crash never leaves a truncated queue
+        let temp_path = self.path.with_extension("json.tmp");
+        let file = QueueFile {
+            jobs: self.jobs.clone(),
+        };
+        std::fs::write(&temp_path, serde_json::to_string_pretty(&file)?)?;
+        std::fs::rename(&temp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Re-reads the queue from disk to pick up changes made by other commands.
+    pub fn reload(&mut self) -> Result<()> {
+        *self = Self::open(self.path.clone())?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, args: EncodeArgs) -> &QueueJob {
+        self.jobs.push(QueueJob::new(args));
+        self.jobs.last().expect("job was just pushed")
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<QueueJob> {
+        let index = self
+            .jobs
+            .iter()
+            .position(|job| job.id == id)
+            .ok_or_else(|| Error::validation(format!("No queued job with ID '{}'", id)))?;
+
+        if self.jobs[index].status == JobStatus::Running {
+            return Err(Error::validation(format!(
+                "Job '{}' is currently running and cannot be removed",
+                id
+            )));
+        }
+
+        Ok(self.jobs.remove(index))
+    }
+
+    /// Removes finished jobs, or every job that is not running. Returns the count removed.
+    pub fn clear(&mut self, finished_only: bool) -> usize {
+        let before = self.jobs.len();
+        self.jobs.retain(|job| {
+            job.status == JobStatus::Running || (finished_only && !job.status.is_finished())
+        });
+        before - self.jobs.len()
+    }
+
+    fn has_pending(&self) -> bool {
+        self.jobs.iter().any(|job| job.status == JobStatus::Pending)
+    }
+
+    fn next_pending(&mut self) -> Option<&mut QueueJob> {
+        self.jobs
+            .iter_mut()
+            .find(|job| job.status == JobStatus::Pending)
+    }
+
+    fn set_status(&mut self, id: &str, status: JobStatus, error: Option<String>) {
+        if let Some(job) = self.jobs.iter_mut().find(|job| job.id == id) {
+            job.status = status;
+            job.error = error;
+            job.finished_at = status
+                .is_finished()
+                .then(|| chrono::Local::now().to_rfc3339());
+        }
+    }
+
+    /// Jobs left in `running` by an interrupted `queue run` are re-queued.
+    fn requeue_interrupted(&mut self) -> usize {
+        let mut count = 0;
+        for job in &mut self.jobs {
+            if job.status == JobStatus::Running {
+                job.status = JobStatus::Pending;
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// Processes the queue by running each job as a separate `encode` worker
+/// process, so parallel jobs never share progress files or loggers.
+pub struct QueueRunner {
+    executable: PathBuf,
+    config_path: Option<PathBuf>,
+    jobs: usize,
+    schedule: Option<TimeWindow>,
+    pause_outside_schedule: bool,
+}
+
+impl QueueRunner {
+    pub fn new(config_path: Option<PathBuf>, jobs: usize) -> Result<Self> {
+        Ok(Self {
+            executable: std::env::current_exe()?,
+            config_path,
+            jobs: jobs.max(1),
+            schedule: None,
+            pause_outside_schedule: false,
+        })
+    }
+
+    /// Only start jobs inside `window`; with `pause_running`, workers also
+    /// suspend their encode while the window is closed.
+    pub fn with_schedule(mut self, window: TimeWindow, pause_running: bool) -> Self {
+        self.schedule = Some(window);
+        self.pause_outside_schedule = pause_running;
+        self
+    }
+
+    fn worker_args(&self, job: &QueueJob) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        if let Some(config) = &self.config_path {
+            args.push("--config".into());
+            args.push(config.into());
+        }
+        args.push("encode".into());
+
+        let mut job_args = job.args.clone();
+        if let (Some(window), true) = (self.schedule, self.pause_outside_schedule) {
+            job_args.schedule = Some(window.to_string());
+            job_args.pause_outside_schedule = true;
+        }
+        args.extend(job_args.to_cli_args());
+        args
+    }
+
+    /// Runs pending jobs until none are left. Returns `(completed, failed)`.
+    pub async fn run(&self, queue: &mut EncodingQueue) -> Result<(usize, usize)> {
+        let interrupted = queue.requeue_interrupted();
+        if interrupted > 0 {
+            warn!(
+                "Re-queued {} job(s) interrupted by a previous run",
+                interrupted
+            );
+        }
+        queue.save()?;
+
+        let mut workers = JoinSet::new();
+        let mut completed = 0;
+        let mut failed = 0;
+
+        loop {
+            while workers.len() < self.jobs {
+                queue.reload()?;
+                if !queue.has_pending() {
+                    break;
+                }
+
+                if let Some(window) = self.schedule {
+                    if !window.is_open_now() {
+                        // Let running jobs finish before idling until the window opens
+                        if !workers.is_empty() {
+                            break;
+                        }
+                        window.wait_until_open().await;
+                        queue.reload()?;
+                    }
+                }
+
+                let Some(job) = queue.next_pending() else {
+                    break;
+                };
+                job.status = JobStatus::Running;
+                let job = job.clone();
+                queue.save()?;
+
+                info!(
+                    "Starting job {}: {}",
+                    job.id,
+                    job.input()
+                        .map(|p| p.display().to_string())
+                        .unwrap_or_default()
+                );
+
+                let mut command = tokio::process::Command::new(&self.executable);
+                command.args(self.worker_args(&job)).stdin(Stdio::null());
+                if self.jobs > 1 {
+                    // Interleaved progress bars are unreadable; each job still writes its own .log
+                    command.stdout(Stdio::null()).stderr(Stdio::null());
+                }
+
+                let id = job.id.clone();
+                match command.spawn() {
+                    Ok(mut child) => {
+                        workers.spawn(async move { (id, child.wait().await) });
+                    }
+                    Err(e) => {
+                        queue.reload()?;
+                        queue.set_status(&id, JobStatus::Failed, Some(e.to_string()));
+                        queue.save()?;
+                        failed += 1;
+                    }
+                }
+            }
+
+            let Some(joined) = workers.join_next().await else {
+                break;
+            };
+            let (id, exit) =
+                joined.map_err(|e| Error::encoding(format!("Queue worker panicked: {}", e)))?;
+
+            let error = match exit {
+                Ok(status) if status.success() => None,
+                Ok(status) => Some(format!("encoder exited with {}", status)),
+                Err(e) => Some(e.to_string()),
+            };
+
+            queue.reload()?;
+            match error {
+                None => {
+                    info!("✓ Job {} completed", id);
+                    queue.set_status(&id, JobStatus::Completed, None);
+                    completed += 1;
+                }
+                Some(error) => {
+                    warn!("✗ Job {} failed: {}", id, error);
+                    queue.set_status(&id, JobStatus::Failed, Some(error));
+                    failed += 1;
+                }
+            }
+            queue.save()?;
+        }
+
+        Ok((completed, failed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_queue() -> EncodingQueue {
+        let path = std::env::temp_dir()
+            .join(format!("ven_queue_test_{}", uuid::Uuid::new_v4()))
+            .join("queue.json");
+        EncodingQueue::open(path).unwrap()
+    }
+
+    fn job_args(input: &str) -> EncodeArgs {
+        EncodeArgs {
+            input: vec![PathBuf::from(input)],
+            profile: "anime".to_string(),
+            ..EncodeArgs::default()
+        }
+    }
+
+    #[test]
+    fn test_queue_persists_jobs() {
+        let mut queue = temp_queue();
+        let id = queue.add(job_args("a.mkv")).id.clone();
+        queue.add(job_args("b.mkv"));
+        queue.save().unwrap();
+
+        let reopened = EncodingQueue::open(queue.path().to_path_buf()).unwrap();
+        assert_eq!(reopened.jobs().len(), 2);
+        assert_eq!(reopened.jobs()[0].id, id);
+        assert_eq!(reopened.jobs()[0].args.profile, "anime");
+        assert_eq!(reopened.jobs()[0].status, JobStatus::Pending);
+
+        std::fs::remove_dir_all(queue.path().parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_remove_and_clear() {
+        let mut queue = temp_queue();
+        let done = queue.add(job_args("a.mkv")).id.clone();
+        let running = queue.add(job_args("b.mkv")).id.clone();
+        queue.add(job_args("c.mkv"));
+
+        queue.set_status(&done, JobStatus::Completed, None);
+        queue.set_status(&running, JobStatus::Running, None);
+
+        assert!(queue.remove(&running).is_err());
+        assert!(queue.remove("missing").is_err());
+
+        assert_eq!(queue.clear(true), 1);
+        assert_eq!(queue.jobs().len(), 2);
+        assert_eq!(queue.clear(false), 1);
+        assert_eq!(queue.jobs().len(), 1);
+        assert_eq!(queue.jobs()[0].id, running);
+
+        assert_eq!(queue.requeue_interrupted(), 1);
+        assert_eq!(queue.jobs()[0].status, JobStatus::Pending);
+    }
+}
@@ -0,0 +1,706 @@
+//! Structured records of past encodes, for `logs list`, `logs show`,
+//! `logs grep` and `stats`.
+//!
+//! Every file an `encode` run attempts appends one JSON line to
+//! `<data dir>/ven/encode_reports.jsonl`: its [`FileSummary`], the quality
+//! scores and the log written next to the output. The `.log` files stay the
+//! detailed record; the reports make them searchable across runs.
+
+use crate::analysis::VideoFingerprint;
+use crate::batch::{FileStatus, FileSummary};
+use crate::config::QualityMetric;
+use crate::explain::Decision;
+use crate::hdr10plus::Hdr10PlusProfile;
+use crate::quality::QualityScore;
+use crate::utils::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// One attempted encode.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncodeReport {
+    /// RFC 3339 time the encode finished
+    pub finished_at: String,
+    #[serde(flatten)]
+    pub file: FileSummary,
+    #[serde(default)]
+    pub quality: Vec<QualityScore>,
+    /// Log written next to the output, if there is one
+    pub log: Option<PathBuf>,
+    /// Automated decisions of the encode (`--explain`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decisions: Vec<Decision>,
+    /// Profile of the HDR10+ metadata carried into the output
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdr10plus_profile: Option<Hdr10PlusProfile>,
+    /// Perceptual fingerprint of the source (`analysis.fingerprint`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<VideoFingerprint>,
+}
+
+impl EncodeReport {
+    pub fn new(file: FileSummary, quality: Vec<QualityScore>, log: Option<PathBuf>) -> Self {
+        Self {
+            finished_at: chrono::Local::now().to_rfc3339(),
+            file,
+            quality,
+            log,
+            decisions: Vec::new(),
+            hdr10plus_profile: None,
+            fingerprint: None,
+        }
+    }
+
+    pub fn with_decisions(mut self, decisions: Vec<Decision>) -> Self {
+        self.decisions = decisions;
+        self
+    }
+
+    pub fn with_hdr10plus_profile(mut self, profile: Option<Hdr10PlusProfile>) -> Self {
+        self.hdr10plus_profile = profile;
+        self
+    }
+
+    pub fn with_fingerprint(mut self, fingerprint: Option<VideoFingerprint>) -> Self {
+        self.fingerprint = fingerprint;
+        self
+    }
+
+    pub fn score(&self, metric: QualityMetric) -> Option<f64> {
+        self.quality
+            .iter()
+            .find(|score| score.metric == metric)
+            .map(|score| score.value)
+    }
+
+    /// `finished_at` as `YYYY-MM-DD HH:MM`.
+    pub fn finished(&self) -> String {
+        chrono::DateTime::parse_from_rfc3339(&self.finished_at)
+            .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| self.finished_at.clone())
+    }
+
+    /// Local date the encode finished.
+    pub fn finished_date(&self) -> Option<chrono::NaiveDate> {
+        chrono::DateTime::parse_from_rfc3339(&self.finished_at)
+            .ok()
+            .map(|time| time.with_timezone(&chrono::Local).date_naive())
+    }
+
+    /// The quality scores joined for display, e.g. `VMAF 94.20, SSIM 0.99012`.
+    pub fn quality_summary(&self) -> String {
+        self.quality
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn number(&self, field: Field) -> Option<f64> {
+        match field {
+            Field::Metric(metric) => self.score(metric),
+            Field::Savings => self.file.saved_percent,
+            Field::Fps => self.file.average_fps,
+            Field::Duration => Some(self.file.duration_seconds),
+            _ => None,
+        }
+    }
+
+    fn text(&self, field: Field) -> Option<String> {
+        let path = |path: &Option<PathBuf>| path.as_ref().map(|p| p.display().to_string());
+        match field {
+            Field::Status => Some(self.file.status.as_str().to_string()),
+            Field::Profile => self.file.profile.clone(),
+            Field::Input => Some(self.file.input.display().to_string()),
+            Field::Output => path(&self.file.output),
+            Field::Error => self.file.error.clone(),
+            _ => None,
+        }
+    }
+}
+
+/// The reports of earlier encodes, oldest first.
+#[derive(Debug)]
+pub struct ReportLog {
+    path: PathBuf,
+    reports: Vec<EncodeReport>,
+}
+
+impl ReportLog {
+    /// Default location: `<data dir>/ven/encode_reports.jsonl`.
+    pub fn default_path() -> Result<PathBuf> {
+        dirs::data_dir()
+            .map(|dir| dir.join("ven").join("encode_reports.jsonl"))
+            .ok_or_else(|| Error::validation("No data directory available for encode reports"))
+    }
+
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_path()?)
+    }
+
+    /// Reads the reports at `path`; unreadable lines are skipped.
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let reports = match std::fs::read_to_string(&path) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .filter_map(|line| match serde_json::from_str(line) {
+                    Ok(report) => Some(report),
+                    Err(e) => {
+                        debug!("Skipping unreadable report line: {}", e);
+                        None
+                    }
+                })
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { path, reports })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends `report` to the file.
+    pub fn append(&mut self, report: EncodeReport) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&report)?)?;
+        self.reports.push(report);
+        Ok(())
+    }
+
+    /// Reports newest first, numbered from 1 (the number `show` takes).
+    pub fn newest_first(&self) -> impl Iterator<Item = (usize, &EncodeReport)> {
+        self.reports
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(index, report)| (index + 1, report))
+    }
+
+    /// The report numbered `selector` by [`newest_first`](Self::newest_first),
+    /// or the latest one whose input or output is the path `selector`.
+    pub fn find(&self, selector: &str) -> Option<&EncodeReport> {
+        if let Ok(number) = selector.parse::<usize>() {
+            return number
+                .checked_sub(1)
+                .and_then(|index| self.reports.iter().rev().nth(index));
+        }
+        let path = Path::new(selector);
+        self.reports
+            .iter()
+            .rev()
+            .find(|report| report.file.input == path || report.file.output.as_deref() == Some(path))
+    }
+
+    /// Reports matching every condition, newest first.
+    pub fn grep<'a>(
+        &'a self,
+        conditions: &'a [ReportCondition],
+    ) -> impl Iterator<Item = (usize, &'a EncodeReport)> {
+        self.newest_first()
+            .filter(|(_, report)| conditions.iter().all(|c| c.matches(report)))
+    }
+}
+
+/// Totals of one profile's encodes (in one month with `stats --monthly`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileStats {
+    /// `-` for encodes without a profile
+    pub profile: String,
+    /// `YYYY-MM` when split by month
+    pub month: Option<String>,
+    /// Finished encodes, including those abandoned as inefficient
+    pub encodes: usize,
+    pub failed: usize,
+    /// Averages over the encodes that have the value
+    pub saved_percent: Option<f64>,
+    pub vmaf: Option<f64>,
+    pub fps: Option<f64>,
+    /// Time spent on all attempts, failed ones included
+    pub encoding_seconds: f64,
+}
+
+/// Parses a `YYYY-MM-DD` date such as `stats --since` takes.
+pub fn parse_date(text: &str) -> Result<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(text.trim(), "%Y-%m-%d")
+        .map_err(|_| Error::validation(format!("Invalid date '{}' (expected YYYY-MM-DD)", text)))
+}
+
+fn average(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    (count > 0).then(|| sum / count as f64)
+}
+
+impl ReportLog {
+    /// Totals per profile (and per month when `monthly`) of the encodes
+    /// finished on or after `since`, sorted by profile and month. Skipped
+    /// files are left out.
+    pub fn profile_stats(
+        &self,
+        since: Option<chrono::NaiveDate>,
+        monthly: bool,
+    ) -> Vec<ProfileStats> {
+        let mut groups: std::collections::BTreeMap<(String, Option<String>), Vec<&EncodeReport>> =
+            std::collections::BTreeMap::new();
+        for report in &self.reports {
+            if report.file.status == FileStatus::Skipped {
+                continue;
+            }
+            let date = report.finished_date();
+            if since.is_some_and(|since| date.is_none_or(|date| date < since)) {
+                continue;
+            }
+            let profile = report
+                .file
+                .profile
+                .clone()
+                .unwrap_or_else(|| "-".to_string());
+            let month = monthly.then(|| {
+                date.map_or_else(|| "?".to_string(), |date| date.format("%Y-%m").to_string())
+            });
+            groups.entry((profile, month)).or_default().push(report);
+        }
+
+        groups
+            .into_iter()
+            .map(|((profile, month), reports)| {
+                let finished: Vec<&&EncodeReport> = reports
+                    .iter()
+                    .filter(|report| report.file.status != FileStatus::Failed)
+                    .collect();
+                ProfileStats {
+                    profile,
+                    month,
+                    encodes: finished.len(),
+                    failed: reports.len() - finished.len(),
+                    saved_percent: average(
+                        finished
+                            .iter()
+                            .filter_map(|report| report.file.saved_percent),
+                    ),
+                    vmaf: average(
+                        finished
+                            .iter()
+                            .filter_map(|report| report.score(QualityMetric::Vmaf)),
+                    ),
+                    fps: average(finished.iter().filter_map(|report| report.file.average_fps)),
+                    encoding_seconds: reports
+                        .iter()
+                        .map(|report| report.file.duration_seconds)
+                        .sum(),
+                }
+            })
+            .collect()
+    }
+
+    /// Median encode time per source byte of the finished encodes, of
+    /// `profile` when it has any, to estimate how long a batch takes.
+    pub fn seconds_per_byte(&self, profile: Option<&str>) -> Option<f64> {
+        let rate = |report: &EncodeReport| {
+            let size = report.file.original_size.filter(|&size| size > 0)?;
+            (report.file.status == FileStatus::Encoded && report.file.duration_seconds > 0.0)
+                .then(|| report.file.duration_seconds / size as f64)
+        };
+        let mut rates: Vec<f64> = self
+            .reports
+            .iter()
+            .filter(|report| {
+                profile.is_some_and(|profile| report.file.profile.as_deref() == Some(profile))
+            })
+            .filter_map(rate)
+            .collect();
+        if rates.is_empty() {
+            rates = self.reports.iter().filter_map(rate).collect();
+        }
+        if rates.is_empty() {
+            return None;
+        }
+        rates.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        Some(rates[rates.len() / 2])
+    }
+
+    /// The latest encode of another source whose fingerprint is within
+    /// `max_distance` of `fingerprint`, with the distance. Earlier encodes of
+    /// `input` itself are not duplicates.
+    pub fn find_duplicate(
+        &self,
+        input: &Path,
+        fingerprint: &VideoFingerprint,
+        max_distance: f64,
+    ) -> Option<(&EncodeReport, f64)> {
+        let canonical = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let input = canonical(input);
+        self.reports.iter().rev().find_map(|report| {
+            if report.file.status != FileStatus::Encoded {
+                return None;
+            }
+            let distance = report.fingerprint.as_ref()?.distance(fingerprint)?;
+            (distance <= max_distance && canonical(&report.file.input) != input)
+                .then_some((report, distance))
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Metric(QualityMetric),
+    Savings,
+    Fps,
+    Duration,
+    Status,
+    Profile,
+    Input,
+    Output,
+    Error,
+}
+
+impl Field {
+    const NAMES: &'static str =
+        "vmaf, ssim, xpsnr, butteraugli, savings, fps, duration, status, profile, input, output, error";
+
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_lowercase().as_str() {
+            "vmaf" => Self::Metric(QualityMetric::Vmaf),
+            "ssim" => Self::Metric(QualityMetric::Ssim),
+            "xpsnr" => Self::Metric(QualityMetric::Xpsnr),
+            "butteraugli" => Self::Metric(QualityMetric::Butteraugli),
+            "savings" => Self::Savings,
+            "fps" => Self::Fps,
+            "duration" => Self::Duration,
+            "status" => Self::Status,
+            "profile" => Self::Profile,
+            "input" => Self::Input,
+            "output" => Self::Output,
+            "error" => Self::Error,
+            _ => return None,
+        })
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            Self::Metric(_) | Self::Savings | Self::Fps | Self::Duration
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operator {
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    /// Case-insensitive substring
+    Contains,
+}
+
+/// Operators longest first, so `<=` is not read as `<`.
+const OPERATORS: [(&str, Operator); 7] = [
+    ("<=", Operator::LessOrEqual),
+    (">=", Operator::GreaterOrEqual),
+    ("!=", Operator::NotEqual),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+    ("=", Operator::Equal),
+    ("~", Operator::Contains),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Number(f64),
+    Text(String),
+}
+
+/// A condition of `logs grep`, e.g. `vmaf<93`, `savings<20%` or
+/// `input~season1`. Reports without the field (no VMAF score, a failed
+/// encode without savings) never match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportCondition {
+    field: Field,
+    operator: Operator,
+    value: Value,
+}
+
+impl ReportCondition {
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid =
+            |reason: String| Error::validation(format!("Invalid condition '{}': {}", text, reason));
+        let start = text
+            .find(['<', '>', '=', '!', '~'])
+            .ok_or_else(|| invalid("expected e.g. vmaf<93 or profile=anime".to_string()))?;
+        let (name, rest) = text.split_at(start);
+        let (symbol, operator) = OPERATORS
+            .iter()
+            .find(|(symbol, _)| rest.starts_with(symbol))
+            .ok_or_else(|| invalid("unknown operator".to_string()))?;
+        let field = Field::parse(name.trim())
+            .ok_or_else(|| invalid(format!("unknown field (available: {})", Field::NAMES)))?;
+        let value = rest[symbol.len()..].trim();
+
+        let value = if field.is_numeric() {
+            if *operator == Operator::Contains {
+                return Err(invalid("~ only applies to text fields".to_string()));
+            }
+            let number = value.trim_end_matches('%').trim();
+            Value::Number(
+                number
+                    .parse()
+                    .map_err(|_| invalid(format!("'{}' is not a number", value)))?,
+            )
+        } else {
+            if matches!(
+                operator,
+                Operator::Less
+                    | Operator::LessOrEqual
+                    | Operator::Greater
+                    | Operator::GreaterOrEqual
+            ) {
+                return Err(invalid(format!("{} only applies to numbers", symbol)));
+            }
+            Value::Text(value.to_lowercase())
+        };
+        Ok(Self {
+            field,
+            operator: *operator,
+            value,
+        })
+    }
+
+    pub fn matches(&self, report: &EncodeReport) -> bool {
+        match &self.value {
+            Value::Number(expected) => {
+                let Some(actual) = report.number(self.field) else {
+                    return false;
+                };
+                match self.operator {
+                    Operator::Less => actual < *expected,
+                    Operator::LessOrEqual => actual <= *expected,
+                    Operator::Greater => actual > *expected,
+                    Operator::GreaterOrEqual => actual >= *expected,
+                    Operator::Equal => actual == *expected,
+                    Operator::NotEqual => actual != *expected,
+                    Operator::Contains => false,
+                }
+            }
+            Value::Text(expected) => {
+                let Some(actual) = report.text(self.field) else {
+                    return false;
+                };
+                let actual = actual.to_lowercase();
+                match self.operator {
+                    Operator::Equal => actual == *expected,
+                    Operator::NotEqual => actual != *expected,
+                    Operator::Contains => actual.contains(expected.as_str()),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::FileStatus;
+
+    fn report(input: &str, saved_percent: Option<f64>, vmaf: Option<f64>) -> EncodeReport {
+        let file = FileSummary {
+            input: PathBuf::from(input),
+            output: Some(PathBuf::from(input).with_extension("out.mkv")),
+            status: FileStatus::Encoded,
+            profile: Some("anime".to_string()),
+            original_size: Some(1000),
+            new_size: None,
+            saved_percent,
+            duration_seconds: 60.0,
+            average_fps: Some(24.0),
+            error: None,
+        };
+        let quality = vmaf
+            .map(|value| QualityScore {
+                metric: QualityMetric::Vmaf,
+                value,
+            })
+            .into_iter()
+            .collect();
+        EncodeReport::new(file, quality, None)
+    }
+
+    #[test]
+    fn test_report_log_grep() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("reports.jsonl");
+        let mut log = ReportLog::open(path.clone()).unwrap();
+        log.append(report("/tv/s1/e1.mkv", Some(45.0), Some(95.1)))
+            .unwrap();
+        log.append(report("/tv/s1/e2.mkv", Some(12.5), Some(92.4)))
+            .unwrap();
+        log.append(report("/movies/film.mkv", Some(30.0), None))
+            .unwrap();
+
+        let log = ReportLog::open(path).unwrap();
+        assert_eq!(log.newest_first().count(), 3);
+        assert_eq!(
+            log.find("1").map(|r| r.file.input.clone()),
+            Some(PathBuf::from("/movies/film.mkv"))
+        );
+        assert_eq!(
+            log.find("/tv/s1/e1.out.mkv")
+                .map(|r| r.score(QualityMetric::Vmaf)),
+            Some(Some(95.1))
+        );
+        assert!(log.find("4").is_none());
+        assert!(log.find("0").is_none());
+
+        let grep = |conditions: &[&str]| -> Vec<usize> {
+            let conditions: Vec<ReportCondition> = conditions
+                .iter()
+                .map(|c| ReportCondition::parse(c).unwrap())
+                .collect();
+            log.grep(&conditions).map(|(number, _)| number).collect()
+        };
+        // Reports without a VMAF score never match a VMAF condition
+        assert_eq!(grep(&["vmaf<93"]), [2]);
+        assert_eq!(grep(&["vmaf>=0"]), [2, 3]);
+        assert_eq!(grep(&["savings < 35%"]), [1, 2]);
+        assert_eq!(grep(&["input~S1", "savings>=40"]), [3]);
+        assert_eq!(grep(&["profile=ANIME", "status!=failed"]), [1, 2, 3]);
+
+        assert!(ReportCondition::parse("vmaf~93").is_err());
+        assert!(ReportCondition::parse("profile<3").is_err());
+        assert!(ReportCondition::parse("psnr>40").is_err());
+        assert!(ReportCondition::parse("vmaf<high").is_err());
+        assert!(ReportCondition::parse("vmaf").is_err());
+    }
+
+    #[test]
+    fn test_profile_stats() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = ReportLog::open(dir.path().join("reports.jsonl")).unwrap();
+        let mut add = |profile: &str,
+                       status: FileStatus,
+                       saved: Option<f64>,
+                       vmaf: Option<f64>,
+                       finished_at: &str| {
+            let mut report = report("/in.mkv", saved, vmaf);
+            report.file.profile = Some(profile.to_string());
+            report.file.status = status;
+            report.finished_at = finished_at.to_string();
+            log.append(report).unwrap();
+        };
+        add(
+            "anime",
+            FileStatus::Encoded,
+            Some(40.0),
+            Some(95.0),
+            "2026-01-10T12:00:00+00:00",
+        );
+        add(
+            "anime",
+            FileStatus::Encoded,
+            Some(20.0),
+            None,
+            "2026-02-10T12:00:00+00:00",
+        );
+        add(
+            "anime",
+            FileStatus::Failed,
+            None,
+            None,
+            "2026-02-11T12:00:00+00:00",
+        );
+        add(
+            "movie",
+            FileStatus::Skipped,
+            None,
+            None,
+            "2026-02-12T12:00:00+00:00",
+        );
+        add(
+            "movie",
+            FileStatus::Inefficient,
+            Some(-5.0),
+            None,
+            "2026-02-13T12:00:00+00:00",
+        );
+
+        let stats = log.profile_stats(None, false);
+        assert_eq!(stats.len(), 2);
+        let anime = &stats[0];
+        assert_eq!(
+            (anime.profile.as_str(), anime.encodes, anime.failed),
+            ("anime", 2, 1)
+        );
+        assert_eq!(anime.saved_percent, Some(30.0));
+        assert_eq!(anime.vmaf, Some(95.0));
+        assert_eq!(anime.fps, Some(24.0));
+        assert_eq!(anime.encoding_seconds, 180.0);
+        assert_eq!((stats[1].encodes, stats[1].saved_percent), (1, Some(-5.0)));
+
+        let since = chrono::NaiveDate::from_ymd_opt(2026, 2, 1);
+        let monthly = log.profile_stats(since, true);
+        assert_eq!(monthly.len(), 2);
+        assert_eq!(monthly[0].month.as_deref(), Some("2026-02"));
+        assert_eq!((monthly[0].encodes, monthly[0].vmaf), (1, None));
+
+        // 60 s over the 1000 bytes of the report() sources
+        assert_eq!(log.seconds_per_byte(Some("anime")), Some(0.06));
+        assert_eq!(log.seconds_per_byte(Some("movie")), Some(0.06));
+    }
+
+    #[test]
+    fn test_find_duplicate() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = ReportLog::open(dir.path().join("reports.jsonl")).unwrap();
+        let fingerprint = |hash: u64| VideoFingerprint {
+            duration: 5400.0,
+            hashes: vec![hash; 4],
+        };
+        log.append(
+            report("/movies/Film.2160p.mkv", Some(40.0), None)
+                .with_fingerprint(Some(fingerprint(u64::MAX))),
+        )
+        .unwrap();
+        let mut failed = report("/movies/Other.mkv", None, None)
+            .with_fingerprint(Some(fingerprint(u64::MAX >> 2)));
+        failed.file.status = FileStatus::Failed;
+        log.append(failed).unwrap();
+
+        // The same film at another resolution, 2 of 64 bits off per frame
+        let (duplicate, distance) = log
+            .find_duplicate(
+                Path::new("/downloads/film_1080p.mkv"),
+                &fingerprint(u64::MAX >> 2),
+                0.1,
+            )
+            .unwrap();
+        assert_eq!(duplicate.file.input, Path::new("/movies/Film.2160p.mkv"));
+        assert_eq!(distance, 2.0 / 64.0);
+        // Re-encoding the same file, and other content, are not duplicates
+        assert!(log
+            .find_duplicate(
+                Path::new("/movies/Film.2160p.mkv"),
+                &fingerprint(u64::MAX),
+                0.1
+            )
+            .is_none());
+        assert!(log
+            .find_duplicate(Path::new("/movies/new.mkv"), &fingerprint(0), 0.1)
+            .is_none());
+
+        let reopened = ReportLog::open(log.path().to_path_buf()).unwrap();
+        assert_eq!(reopened.reports, log.reports);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::progress::PauseCondition;
+use crate::utils::{Error, Result};
+use chrono::{Local, NaiveTime, Timelike};
+use futures::future::BoxFuture;
+use std::fmt;
+use std::time::Duration;
+use tracing::info;
+
+/// Longest single sleep while waiting for a window, so clock changes
+/// (suspend/resume, DST) are picked up reasonably quickly.
+const MAX_WAIT_STEP: Duration = Duration::from_secs(60);
+
+/// A daily time window such as `23:00-07:00`. Windows may wrap past midnight;
+/// identical start and end times mean "always open".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn parse(value: &str) -> Result<Self> {
+        let (start, end) = value.split_once('-').ok_or_else(|| {
+            Error::validation(format!(
+                "Invalid schedule '{}': expected HH:MM-HH:MM (e.g. 23:00-07:00)",
+                value
+            ))
+        })?;
+
+        let parse_time = |part: &str| {
+            NaiveTime::parse_from_str(part.trim(), "%H:%M").map_err(|_| {
+                Error::validation(format!(
+                    "Invalid time '{}' in schedule '{}': expected HH:MM",
+                    part.trim(),
+                    value
+                ))
+            })
+        };
+
+        Ok(Self {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start == self.end {
+            true
+        } else if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    pub fn is_open_now(&self) -> bool {
+        self.contains(Local::now().time())
+    }
+
+    /// Time from `now` until the window next opens (zero if it is open).
+    pub fn time_until_open(&self, now: NaiveTime) -> Duration {
+        if self.contains(now) {
+            return Duration::ZERO;
+        }
+
+        let now_secs = i64::from(now.num_seconds_from_midnight());
+        let start_secs = i64::from(self.start.num_seconds_from_midnight());
+        let wait = (start_secs - now_secs).rem_euclid(24 * 60 * 60);
+        Duration::from_secs(wait as u64)
+    }
+
+    /// Sleeps until the window is open, logging once how long the wait will be.
+    pub async fn wait_until_open(&self) {
+        let mut logged = false;
+        loop {
+            let wait = self.time_until_open(Local::now().time());
+            if wait.is_zero() {
+                if logged {
+                    info!("Schedule window {} opened, resuming batch", self);
+                }
+                return;
+            }
+
+            if !logged {
+                info!(
+                    "Outside schedule window {}: waiting {} until {}",
+                    self,
+                    format_wait(wait),
+                    self.start.format("%H:%M")
+                );
+                logged = true;
+            }
+
+            tokio::time::sleep(wait.min(MAX_WAIT_STEP)).await;
+        }
+    }
+}
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Pauses a running encode while the schedule window is closed.
+pub struct SchedulePause {
+    window: TimeWindow,
+}
+
+impl SchedulePause {
+    pub fn new(window: TimeWindow) -> Self {
+        Self { window }
+    }
+}
+
+impl PauseCondition for SchedulePause {
+    fn pause_reason(&mut self) -> BoxFuture<'_, Option<String>> {
+        let reason = (!self.window.is_open_now())
+            .then(|| format!("outside schedule window {}", self.window));
+        Box::pin(std::future::ready(reason))
+    }
+}
+
+fn format_wait(wait: Duration) -> String {
+    let minutes = wait.as_secs().div_ceil(60);
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let window = TimeWindow::parse("23:00-07:00").unwrap();
+        assert!(window.contains(time(23, 30)));
+        assert!(window.contains(time(3, 0)));
+        assert!(!window.contains(time(7, 0)));
+        assert!(!window.contains(time(12, 0)));
+
+        assert_eq!(window.time_until_open(time(2, 0)), Duration::ZERO);
+        assert_eq!(
+            window.time_until_open(time(22, 15)),
+            Duration::from_secs(45 * 60)
+        );
+        assert_eq!(
+            window.time_until_open(time(7, 0)),
+            Duration::from_secs(16 * 60 * 60)
+        );
+        assert_eq!(window.to_string(), "23:00-07:00");
+    }
+
+    #[test]
+    fn test_daytime_and_invalid_windows() {
+        let window = TimeWindow::parse("09:30-17:00").unwrap();
+        assert!(window.contains(time(9, 30)));
+        assert!(!window.contains(time(17, 0)));
+        assert!(!window.contains(time(8, 0)));
+
+        assert!(TimeWindow::parse("00:00-00:00")
+            .unwrap()
+            .contains(time(12, 0)));
+        assert!(TimeWindow::parse("23:00").is_err());
+        assert!(TimeWindow::parse("25:00-07:00").is_err());
+    }
+}
@@ -0,0 +1,540 @@
+/// Installation Self-Test
+///
+/// Synthesizes a short letterboxed clip (see [`crate::testsignal`], optionally
+/// HDR10, HDR10+ or Dolby Vision 8.1) and pushes it through the regular pipeline:
+/// 1. Check external tool availability
+/// 2. Generate the synthetic source
+/// 3. Run HDR/Dolby Vision detection and crop detection
+/// 4. Encode through `VideoProcessor` (including the metadata workflow)
+/// 5. Verify the output by probing it again
+///
+/// Each step is recorded as a check so users can see at a glance which
+/// subsystems work on their installation.
+use crate::analysis::CropDetector;
+use crate::config::{Config, ProfileManager};
+use crate::encoding::EncodingOptions;
+use crate::hdr::HdrFormat;
+use crate::processing::VideoProcessor;
+use crate::stream::preservation::StreamPreservation;
+use crate::testsignal::{
+    TestSignal, TestSignalGenerator, PICTURE_HEIGHT, SOURCE_HEIGHT, SOURCE_WIDTH,
+};
+use crate::tools::ToolStatus;
+use crate::utils::{FfmpegWrapper, Result};
+use crate::UnifiedContentManager;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+const CROP_TOLERANCE: u32 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Skipped,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pass => "PASS",
+            Self::Fail => "FAIL",
+            Self::Skipped => "SKIP",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SelfTestReport {
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfTestReport {
+    fn record(&mut self, name: &str, status: CheckStatus, detail: impl Into<String>) {
+        let detail = detail.into();
+        match status {
+            CheckStatus::Pass => info!("Self-test {}: {}", name, detail),
+            CheckStatus::Fail => warn!("Self-test {} failed: {}", name, detail),
+            CheckStatus::Skipped => debug!("Self-test {} skipped: {}", name, detail),
+        }
+        self.checks.push(CheckResult {
+            name: name.to_string(),
+            status,
+            detail,
+        });
+    }
+
+    pub fn all_passed(&self) -> bool {
+        self.checks.iter().all(|c| c.status != CheckStatus::Fail)
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|c| c.status == CheckStatus::Fail)
+            .count()
+    }
+
+    pub fn format_table(&self) -> String {
+        let mut table = format!("{:-<80}\n", "");
+        table.push_str(&format!("{:<24} {:<6} {}\n", "Check", "Status", "Detail"));
+        table.push_str(&format!("{:-<80}\n", ""));
+        for check in &self.checks {
+            table.push_str(&format!(
+                "{:<24} {:<6} {}\n",
+                check.name,
+                check.status.as_str(),
+                check.detail
+            ));
+        }
+        table.push_str(&format!("{:-<80}", ""));
+        table
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SelfTestOptions {
+    /// Tag the synthetic source as HDR10 (BT.2020/PQ with mastering metadata)
+    pub hdr: bool,
+    /// Add HDR10+ dynamic metadata (needs hdr10plus_tool)
+    pub hdr10plus: bool,
+    /// Add a Dolby Vision profile 8.1 RPU (needs dovi_tool)
+    pub dv81: bool,
+    /// Duration of the synthetic source in seconds
+    pub duration: f64,
+    /// Keep the working directory instead of removing it
+    pub keep_files: bool,
+    /// Encoding profile used for the encode step (`None` picks one from config)
+    pub profile: Option<String>,
+}
+
+impl Default for SelfTestOptions {
+    fn default() -> Self {
+        Self {
+            hdr: false,
+            hdr10plus: false,
+            dv81: false,
+            duration: 10.0,
+            keep_files: false,
+            profile: None,
+        }
+    }
+}
+
+pub struct SelfTest<'a> {
+    config: &'a Config,
+    options: SelfTestOptions,
+    work_dir: PathBuf,
+}
+
+impl<'a> SelfTest<'a> {
+    pub fn new(config: &'a Config, options: SelfTestOptions) -> Self {
+        let work_dir =
+            PathBuf::from(&config.app.temp_dir).join(format!("ven_selftest_{}", Uuid::new_v4()));
+        Self {
+            config,
+            options,
+            work_dir,
+        }
+    }
+
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+
+    fn signal(&self) -> TestSignal {
+        TestSignal {
+            hdr10: self.options.hdr,
+            hdr10plus: self.options.hdr10plus,
+            dv81: self.options.dv81,
+            duration: self.options.duration,
+        }
+    }
+
+    /// Run all checks. Individual failures are recorded in the report; only
+    /// problems with the self-test harness itself are returned as errors.
+    pub async fn run(&self) -> Result<SelfTestReport> {
+        let mut report = SelfTestReport::default();
+        tokio::fs::create_dir_all(&self.work_dir).await?;
+
+        let result = self.run_checks(&mut report).await;
+
+        if self.options.keep_files {
+            info!("Self-test files kept in: {}", self.work_dir.display());
+        } else if let Err(e) = tokio::fs::remove_dir_all(&self.work_dir).await {
+            warn!("Failed to remove self-test directory: {}", e);
+        }
+
+        result.map(|()| report)
+    }
+
+    async fn run_checks(&self, report: &mut SelfTestReport) -> Result<()> {
+        let ffmpeg = FfmpegWrapper::new(
+            self.config.tools.ffmpeg.clone(),
+            self.config.tools.ffprobe.clone(),
+        );
+
+        report.checks.extend(check_tools(self.config).await.checks);
+        if report
+            .checks
+            .iter()
+            .take(2)
+            .any(|c| c.status == CheckStatus::Fail)
+        {
+            report.record(
+                "pipeline",
+                CheckStatus::Skipped,
+                "ffmpeg is required for all remaining checks",
+            );
+            return Ok(());
+        }
+
+        let signal = self.signal();
+        let source = self.work_dir.join("selftest_source.mkv");
+        if let Err(e) = TestSignalGenerator::new(self.config)
+            .generate(&signal, &source, &self.work_dir)
+            .await
+        {
+            report.record("synthetic source", CheckStatus::Fail, e.to_string());
+            return Ok(());
+        }
+        report.record(
+            "synthetic source",
+            CheckStatus::Pass,
+            format!(
+                "{}x{} {:.0}s {}",
+                SOURCE_WIDTH,
+                SOURCE_HEIGHT,
+                self.options.duration,
+                signal.describe()
+            ),
+        );
+
+        self.check_detection(&ffmpeg, &source, report).await;
+        let crop_ok = self.check_crop(&ffmpeg, &source, report).await;
+
+        let output = self.work_dir.join("selftest_output.mkv");
+        if !self.check_encode(&ffmpeg, &source, &output, report).await {
+            report.record("verification", CheckStatus::Skipped, "no output to verify");
+            return Ok(());
+        }
+
+        self.check_output(&ffmpeg, &output, crop_ok, report).await;
+        Ok(())
+    }
+
+    async fn check_detection(
+        &self,
+        ffmpeg: &FfmpegWrapper,
+        source: &Path,
+        report: &mut SelfTestReport,
+    ) {
+        let content_manager = UnifiedContentManager::new(
+            self.config.analysis.hdr.clone().unwrap_or_default(),
+            self.config.analysis.dolby_vision.clone(),
+            self.config.tools.hdr10plus_tool.clone(),
+        );
+
+        match content_manager.analyze_content(ffmpeg, source).await {
+            Ok(analysis) => {
+                let signal = self.signal();
+                let format = analysis.hdr_analysis.metadata.format;
+                let expected = if signal.hdr10plus {
+                    HdrFormat::HDR10Plus
+                } else if signal.is_hdr() {
+                    HdrFormat::HDR10
+                } else {
+                    HdrFormat::None
+                };
+                let dolby_vision = analysis.dolby_vision.is_dolby_vision();
+                if format == expected && dolby_vision == signal.dv81 {
+                    report.record(
+                        "hdr detection",
+                        CheckStatus::Pass,
+                        format!(
+                            "detected {:?}{}",
+                            format,
+                            if dolby_vision { " + Dolby Vision" } else { "" }
+                        ),
+                    );
+                } else {
+                    report.record(
+                        "hdr detection",
+                        CheckStatus::Fail,
+                        format!(
+                            "expected {}, detected {:?}{}",
+                            signal.describe(),
+                            format,
+                            if dolby_vision { " + Dolby Vision" } else { "" }
+                        ),
+                    );
+                }
+            }
+            Err(e) => report.record("hdr detection", CheckStatus::Fail, e.to_string()),
+        }
+    }
+
+    async fn check_crop(
+        &self,
+        ffmpeg: &FfmpegWrapper,
+        source: &Path,
+        report: &mut SelfTestReport,
+    ) -> bool {
+        let mut crop_config = self.config.analysis.crop_detection.clone();
+        crop_config.enabled = true;
+        let detector = CropDetector::new(ffmpeg, crop_config);
+
+        let result = detector
+            .detect_crop_values(
+                source,
+                self.options.duration,
+                SOURCE_WIDTH,
+                SOURCE_HEIGHT,
+                self.signal().is_hdr(),
+            )
+            .await;
+
+        match result {
+            Ok(analysis) => match analysis.crop_values {
+                Some(crop) if crop.height.abs_diff(PICTURE_HEIGHT) <= CROP_TOLERANCE => {
+                    report.record(
+                        "crop detection",
+                        CheckStatus::Pass,
+                        format!("crop={}", crop.to_ffmpeg_string()),
+                    );
+                    true
+                }
+                Some(crop) => {
+                    report.record(
+                        "crop detection",
+                        CheckStatus::Fail,
+                        format!(
+                            "expected height ~{}, got crop={}",
+                            PICTURE_HEIGHT,
+                            crop.to_ffmpeg_string()
+                        ),
+                    );
+                    false
+                }
+                None => {
+                    report.record(
+                        "crop detection",
+                        CheckStatus::Fail,
+                        format!("no crop detected ({})", analysis.detection_method),
+                    );
+                    false
+                }
+            },
+            Err(e) => {
+                report.record("crop detection", CheckStatus::Fail, e.to_string());
+                false
+            }
+        }
+    }
+
+    async fn check_encode(
+        &self,
+        ffmpeg: &FfmpegWrapper,
+        source: &Path,
+        output: &Path,
+        report: &mut SelfTestReport,
+    ) -> bool {
+        let mut profile_manager = ProfileManager::new();
+        if let Err(e) = profile_manager.load_profiles(self.config.profiles.clone()) {
+            report.record("encode", CheckStatus::Fail, e.to_string());
+            return false;
+        }
+
+        let Some(profile_name) = self.resolve_profile(&profile_manager) else {
+            report.record(
+                "encode",
+                CheckStatus::Fail,
+                "no encoding profiles configured",
+            );
+            return false;
+        };
+
+        let options = EncodingOptions::new(source, output)
+            .with_profile(profile_name.clone())
+            .with_title("VEN self-test".to_string())
+            .with_mode("crf".to_string());
+
+        let stream_preservation = StreamPreservation::new(ffmpeg.clone())
+            .with_video_policy(self.config.streams.video_policy);
+        let start = std::time::Instant::now();
+        let result = match VideoProcessor::new(
+            ffmpeg,
+            &stream_preservation,
+            &options,
+            self.config,
+            &mut profile_manager,
+        ) {
+            Ok(mut processor) => processor.run().await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                report.record(
+                    "encode",
+                    CheckStatus::Pass,
+                    format!(
+                        "profile '{}' in {:.1}s",
+                        profile_name,
+                        start.elapsed().as_secs_f64()
+                    ),
+                );
+                true
+            }
+            Err(e) => {
+                report.record("encode", CheckStatus::Fail, e.to_string());
+                false
+            }
+        }
+    }
+
+    fn resolve_profile(&self, profile_manager: &ProfileManager) -> Option<String> {
+        if let Some(name) = &self.options.profile {
+            return Some(name.clone());
+        }
+        if profile_manager.get_profile("default").is_some() {
+            return Some("default".to_string());
+        }
+        let mut names: Vec<&String> = profile_manager.list_profiles();
+        names.sort();
+        names.first().map(|name| (*name).clone())
+    }
+
+    async fn check_output(
+        &self,
+        ffmpeg: &FfmpegWrapper,
+        output: &Path,
+        crop_applied: bool,
+        report: &mut SelfTestReport,
+    ) {
+        let metadata = match ffmpeg.get_video_metadata(output).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                report.record("verification", CheckStatus::Fail, e.to_string());
+                report.record(
+                    "metadata",
+                    CheckStatus::Skipped,
+                    "output could not be probed",
+                );
+                return;
+            }
+        };
+
+        let mut problems = Vec::new();
+        if metadata.codec.as_deref() != Some("hevc") {
+            problems.push(format!(
+                "video codec is {}",
+                metadata.codec.as_deref().unwrap_or("unknown")
+            ));
+        }
+        if (metadata.duration - self.options.duration).abs() > 1.0 {
+            problems.push(format!(
+                "duration {:.2}s differs from source {:.2}s",
+                metadata.duration, self.options.duration
+            ));
+        }
+        if !metadata.streams.iter().any(|s| s.codec_type == "audio") {
+            problems.push("audio stream missing".to_string());
+        }
+        if crop_applied && metadata.height.abs_diff(PICTURE_HEIGHT) > CROP_TOLERANCE {
+            problems.push(format!(
+                "output height {} does not reflect the detected crop",
+                metadata.height
+            ));
+        }
+
+        if problems.is_empty() {
+            report.record(
+                "verification",
+                CheckStatus::Pass,
+                format!(
+                    "hevc {}x{} {:.1}s",
+                    metadata.width, metadata.height, metadata.duration
+                ),
+            );
+        } else {
+            report.record("verification", CheckStatus::Fail, problems.join("; "));
+        }
+
+        if !self.signal().is_hdr() {
+            report.record(
+                "metadata",
+                CheckStatus::Skipped,
+                "SDR source (use --hdr to test HDR metadata)",
+            );
+        } else if metadata.transfer_function.as_deref() == Some("smpte2084")
+            && metadata
+                .color_primaries
+                .as_deref()
+                .is_some_and(|p| p.contains("bt2020"))
+        {
+            report.record(
+                "metadata",
+                CheckStatus::Pass,
+                "BT.2020/PQ signalling preserved",
+            );
+        } else {
+            report.record(
+                "metadata",
+                CheckStatus::Fail,
+                format!(
+                    "HDR signalling lost (transfer: {}, primaries: {})",
+                    metadata.transfer_function.as_deref().unwrap_or("unknown"),
+                    metadata.color_primaries.as_deref().unwrap_or("unknown")
+                ),
+            );
+        }
+    }
+}
+
+/// Check the external tools used by the pipeline.
+///
+/// The first two checks are always ffmpeg and ffprobe, which every other step
+/// depends on; optional tools that are not configured are reported as skipped.
+pub async fn check_tools(config: &Config) -> SelfTestReport {
+    let mut report = SelfTestReport::default();
+    for probe in crate::tools::check_all(&config.tools).await {
+        let status = match probe.status {
+            ToolStatus::Available(_) => CheckStatus::Pass,
+            ToolStatus::NotConfigured => CheckStatus::Skipped,
+            ToolStatus::TooOld { .. } | ToolStatus::Unavailable(_) => CheckStatus::Fail,
+        };
+        report.record(&probe.name, status, probe.detail());
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_status() {
+        let mut report = SelfTestReport::default();
+        report.record("ffmpeg/ffprobe", CheckStatus::Pass, "available");
+        report.record("dovi_tool", CheckStatus::Skipped, "not configured");
+        assert!(report.all_passed());
+
+        report.record("encode", CheckStatus::Fail, "boom");
+        assert!(!report.all_passed());
+        assert_eq!(report.failed_count(), 1);
+
+        let table = report.format_table();
+        assert!(table.contains("dovi_tool"));
+        assert!(table.contains("SKIP"));
+        assert!(table.contains("FAIL"));
+    }
+}
@@ -0,0 +1,533 @@
+//! Profile shootout (`shootout <file> --profiles a,b --segments 5`): every
+//! profile encodes the same representative segments of a source, and size,
+//! quality and encode time are compared per segment and over all of them.
+//!
+//! Each segment is cut once to a lossless FFV1 clip, which is both the input
+//! of the profiles' encodes and the reference they are scored against, so
+//! every profile sees exactly the same frames.
+
+use crate::config::{Config, EncodingProfile, QualityMetric};
+use crate::progress::format_duration;
+use crate::quality::{QualityComparison, QualityScore};
+use crate::utils::ffmpeg::VideoMetadata;
+use crate::utils::{Error, FfmpegWrapper, JobTempDir, Result};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tracing::info;
+
+/// Share of the runtime skipped at either end (openings, credits, fades).
+const EDGE_SHARE: f64 = 0.1;
+
+/// Start of each of `count` segments of `length` seconds: centred in equal
+/// slices of the runtime between 10% and 90%. A source shorter than two
+/// segments gets one segment from the start.
+pub fn segment_starts(duration: f64, count: usize, length: f64) -> Vec<f64> {
+    if duration < length * 2.0 || count == 0 {
+        return vec![0.0];
+    }
+    let first = duration * EDGE_SHARE;
+    let slice = duration * (1.0 - 2.0 * EDGE_SHARE) / count as f64;
+    (0..count)
+        .map(|index| {
+            let centre = first + (index as f64 + 0.5) * slice;
+            (centre - length / 2.0).clamp(0.0, duration - length)
+        })
+        .collect()
+}
+
+/// One profile's encode of one segment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SegmentResult {
+    pub profile: String,
+    pub segment: usize,
+    pub size: u64,
+    pub kbps: f64,
+    pub encode_time: Duration,
+    pub scores: Vec<QualityScore>,
+}
+
+impl SegmentResult {
+    fn score(&self, metric: QualityMetric) -> Option<f64> {
+        self.scores
+            .iter()
+            .find(|score| score.metric == metric)
+            .map(|score| score.value)
+    }
+}
+
+/// Mean and sample standard deviation.
+fn mean_and_deviation(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.0);
+    }
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    (mean, variance.sqrt())
+}
+
+/// A profile over all segments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileSummary {
+    pub profile: String,
+    /// Mean bitrate and its standard deviation over the segments, in kbps
+    pub kbps: (f64, f64),
+    /// Mean score of the leading metric and its standard deviation
+    pub score: Option<(f64, f64)>,
+    /// Worst segment score of the leading metric
+    pub worst_score: Option<f64>,
+    pub encode_time: Duration,
+    /// Segments won on size, quality and speed
+    pub wins: [usize; 3],
+}
+
+/// Winners of a set of results: smallest, best scoring, fastest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Winners {
+    pub size: String,
+    pub quality: Option<String>,
+    pub speed: String,
+}
+
+#[derive(Debug)]
+pub struct ShootoutReport {
+    pub input: PathBuf,
+    pub profiles: Vec<String>,
+    pub segment_starts: Vec<f64>,
+    pub segment_length: f64,
+    /// First of `quality.metrics`; decides the quality winner
+    pub metric: Option<QualityMetric>,
+    pub results: Vec<SegmentResult>,
+}
+
+impl ShootoutReport {
+    fn segment_results(&self, segment: usize) -> Vec<&SegmentResult> {
+        self.results
+            .iter()
+            .filter(|result| result.segment == segment)
+            .collect()
+    }
+
+    /// The best result by `key`, lowest first unless `highest`.
+    fn best_by<'r>(
+        results: &[&'r SegmentResult],
+        highest: bool,
+        key: impl Fn(&SegmentResult) -> Option<f64>,
+    ) -> Option<&'r SegmentResult> {
+        results
+            .iter()
+            .filter_map(|result| key(result).map(|value| (*result, value)))
+            .min_by(|(_, a), (_, b)| {
+                let order = a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal);
+                if highest {
+                    order.reverse()
+                } else {
+                    order
+                }
+            })
+            .map(|(result, _)| result)
+    }
+
+    pub fn segment_winners(&self, segment: usize) -> Option<Winners> {
+        let results = self.segment_results(segment);
+        let size = Self::best_by(&results, false, |r| Some(r.size as f64))?;
+        let speed = Self::best_by(&results, false, |r| Some(r.encode_time.as_secs_f64()))?;
+        let quality = self.metric.and_then(|metric| {
+            Self::best_by(&results, metric.higher_is_better(), |r| r.score(metric))
+        });
+        Some(Winners {
+            size: size.profile.clone(),
+            quality: quality.map(|result| result.profile.clone()),
+            speed: speed.profile.clone(),
+        })
+    }
+
+    pub fn summaries(&self) -> Vec<ProfileSummary> {
+        let winners: Vec<Winners> = (0..self.segment_starts.len())
+            .filter_map(|segment| self.segment_winners(segment))
+            .collect();
+        self.profiles
+            .iter()
+            .filter_map(|profile| {
+                let results: Vec<&SegmentResult> = self
+                    .results
+                    .iter()
+                    .filter(|result| &result.profile == profile)
+                    .collect();
+                if results.is_empty() {
+                    return None;
+                }
+                let kbps: Vec<f64> = results.iter().map(|result| result.kbps).collect();
+                let scores: Vec<f64> = self
+                    .metric
+                    .map(|metric| results.iter().filter_map(|r| r.score(metric)).collect())
+                    .unwrap_or_default();
+                let higher_is_better = self.metric.is_some_and(|m| m.higher_is_better());
+                let worst_score =
+                    scores.iter().copied().reduce(
+                        |a, b| {
+                            if (a < b) == higher_is_better {
+                                a
+                            } else {
+                                b
+                            }
+                        },
+                    );
+                let count = |pick: fn(&Winners) -> Option<&String>| {
+                    winners.iter().filter(|w| pick(w) == Some(profile)).count()
+                };
+                Some(ProfileSummary {
+                    profile: profile.clone(),
+                    kbps: mean_and_deviation(&kbps),
+                    score: (!scores.is_empty()).then(|| mean_and_deviation(&scores)),
+                    worst_score,
+                    encode_time: results.iter().map(|result| result.encode_time).sum(),
+                    wins: [
+                        count(|w| Some(&w.size)),
+                        count(|w| w.quality.as_ref()),
+                        count(|w| Some(&w.speed)),
+                    ],
+                })
+            })
+            .collect()
+    }
+
+    /// Winners over all segments: lowest mean bitrate, best mean score,
+    /// shortest total encode time.
+    pub fn overall_winners(&self) -> Option<Winners> {
+        let summaries = self.summaries();
+        let pick = |higher: bool, key: &dyn Fn(&ProfileSummary) -> Option<f64>| {
+            summaries
+                .iter()
+                .filter_map(|summary| key(summary).map(|value| (summary, value)))
+                .min_by(|(_, a), (_, b)| {
+                    let order = a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal);
+                    if higher {
+                        order.reverse()
+                    } else {
+                        order
+                    }
+                })
+                .map(|(summary, _)| summary.profile.clone())
+        };
+        let higher_is_better = self.metric.is_some_and(|m| m.higher_is_better());
+        Some(Winners {
+            size: pick(false, &|s| Some(s.kbps.0))?,
+            quality: pick(higher_is_better, &|s| s.score.map(|(mean, _)| mean)),
+            speed: pick(false, &|s| Some(s.encode_time.as_secs_f64()))?,
+        })
+    }
+
+    pub fn format_table(&self) -> String {
+        let metric = self.metric.map(|m| m.label()).unwrap_or("-");
+        let mut table = format!("Shootout: {}\n", self.input.display());
+        for (segment, start) in self.segment_starts.iter().enumerate() {
+            table.push_str(&format!("{:-<80}\n", ""));
+            table.push_str(&format!(
+                "Segment {} at {} ({:.0}s)\n",
+                segment + 1,
+                format_duration(Duration::from_secs_f64(*start)),
+                self.segment_length
+            ));
+            table.push_str(&format!(
+                "  {:<24} {:>10} {:>10} {:>12} {:>10}\n",
+                "Profile", "Size (MB)", "kbps", metric, "Time"
+            ));
+            for result in self.segment_results(segment) {
+                table.push_str(&format!(
+                    "  {:<24} {:>10.2} {:>10.0} {:>12} {:>10}\n",
+                    result.profile,
+                    result.size as f64 / 1_048_576.0,
+                    result.kbps,
+                    self.metric
+                        .and_then(|m| result.score(m))
+                        .map(|score| format!("{:.3}", score))
+                        .unwrap_or_else(|| "-".to_string()),
+                    format_duration(result.encode_time)
+                ));
+            }
+            if let Some(winners) = self.segment_winners(segment) {
+                table.push_str(&format!("  Winners: {}\n", winners));
+            }
+        }
+
+        table.push_str(&format!("{:=<80}\n", ""));
+        table.push_str(&format!(
+            "{:<24} {:>16} {:>18} {:>10} {:>10} {:>8}\n",
+            "Profile",
+            "kbps (± sd)",
+            format!("{} (± sd)", metric),
+            "worst",
+            "time",
+            "wins S/Q/T"
+        ));
+        for summary in self.summaries() {
+            table.push_str(&format!(
+                "{:<24} {:>16} {:>18} {:>10} {:>10} {:>8}\n",
+                summary.profile,
+                format!("{:.0} ± {:.0}", summary.kbps.0, summary.kbps.1),
+                summary
+                    .score
+                    .map(|(mean, sd)| format!("{:.3} ± {:.3}", mean, sd))
+                    .unwrap_or_else(|| "-".to_string()),
+                summary
+                    .worst_score
+                    .map(|score| format!("{:.3}", score))
+                    .unwrap_or_else(|| "-".to_string()),
+                format_duration(summary.encode_time),
+                format!(
+                    "{}/{}/{}",
+                    summary.wins[0], summary.wins[1], summary.wins[2]
+                )
+            ));
+        }
+        if let Some(winners) = self.overall_winners() {
+            table.push_str(&format!("Overall: {}\n", winners));
+        }
+        table.push_str(&format!("{:=<80}", ""));
+        table
+    }
+}
+
+impl std::fmt::Display for Winners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "smallest {}", self.size)?;
+        if let Some(quality) = &self.quality {
+            write!(f, ", best quality {}", quality)?;
+        }
+        write!(f, ", fastest {}", self.speed)
+    }
+}
+
+pub struct Shootout<'a> {
+    ffmpeg: &'a FfmpegWrapper,
+    config: &'a Config,
+    input: &'a Path,
+    profiles: Vec<&'a EncodingProfile>,
+    segments: usize,
+    segment_length: f64,
+}
+
+impl<'a> Shootout<'a> {
+    pub fn new(
+        ffmpeg: &'a FfmpegWrapper,
+        config: &'a Config,
+        input: &'a Path,
+        profiles: Vec<&'a EncodingProfile>,
+        segments: usize,
+        segment_length: f64,
+    ) -> Self {
+        Self {
+            ffmpeg,
+            config,
+            input,
+            profiles,
+            segments,
+            segment_length,
+        }
+    }
+
+    pub async fn run(&self) -> Result<ShootoutReport> {
+        let metadata = self.ffmpeg.get_video_metadata(self.input).await?;
+        let segment_length = self.segment_length.min(metadata.duration);
+        let starts = segment_starts(metadata.duration, self.segments, segment_length);
+        let job_dir = JobTempDir::create(&self.config.app.temp_dir, self.input)?;
+        let metric = self.config.quality.metrics.first().copied();
+        let hdr_transfer = metadata
+            .is_hdr
+            .then(|| metadata.transfer_function.clone())
+            .flatten();
+
+        let mut results = Vec::new();
+        for (segment, &start) in starts.iter().enumerate() {
+            info!(
+                "Segment {}/{} at {:.1}s: cutting the reference clip",
+                segment + 1,
+                starts.len(),
+                start
+            );
+            let reference = job_dir.path().join(format!("segment_{}.mkv", segment));
+            self.cut_reference(start, segment_length, &reference)
+                .await?;
+
+            for profile in &self.profiles {
+                let output = job_dir
+                    .path()
+                    .join(format!("segment_{}_{}.mkv", segment, profile.name));
+                let started = Instant::now();
+                self.encode(profile, &metadata, &reference, &output).await?;
+                let encode_time = started.elapsed();
+                let size = std::fs::metadata(&output)?.len();
+                let scores = QualityComparison::new(self.config, &output, &reference)
+                    .with_hdr_transfer(hdr_transfer.clone())
+                    .measure(&self.config.quality.metrics, job_dir.path())
+                    .await?;
+                info!(
+                    "  {}: {:.2} MB in {:.1}s{}",
+                    profile.name,
+                    size as f64 / 1_048_576.0,
+                    encode_time.as_secs_f64(),
+                    scores
+                        .iter()
+                        .map(|score| format!(", {}", score))
+                        .collect::<String>()
+                );
+                let _ = std::fs::remove_file(&output);
+                results.push(SegmentResult {
+                    profile: profile.name.clone(),
+                    segment,
+                    size,
+                    kbps: size as f64 * 8.0 / segment_length / 1000.0,
+                    encode_time,
+                    scores,
+                });
+            }
+            let _ = std::fs::remove_file(&reference);
+        }
+
+        Ok(ShootoutReport {
+            input: self.input.to_path_buf(),
+            profiles: self.profiles.iter().map(|p| p.name.clone()).collect(),
+            segment_starts: starts,
+            segment_length,
+            metric,
+            results,
+        })
+    }
+
+    /// Cuts `length` seconds from `start` of the source's main video stream
+    /// to lossless FFV1.
+    async fn cut_reference(&self, start: f64, length: f64, reference: &Path) -> Result<()> {
+        let output = Command::new(&self.config.tools.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-ss", &start.to_string()])
+            .arg("-i")
+            .arg(self.input)
+            .args(["-t", &length.to_string()])
+            .args(["-map", "0:v:0", "-c:v", "ffv1", "-an", "-sn", "-dn"])
+            .arg(reference)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to cut the segment at {:.1}s: {}",
+                start,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Encodes `reference` with `profile` at its base CRF, as a preview does.
+    async fn encode(
+        &self,
+        profile: &EncodingProfile,
+        metadata: &VideoMetadata,
+        reference: &Path,
+        output_path: &Path,
+    ) -> Result<()> {
+        let x265_params = profile.build_x265_params_string_with_hdr_passthrough(
+            None,
+            Some(false),
+            metadata.color_space.as_ref(),
+            metadata.transfer_function.as_ref(),
+            metadata.color_primaries.as_ref(),
+            metadata.master_display.as_ref(),
+            metadata.max_cll.as_ref(),
+            false,
+        );
+        let output = Command::new(&self.config.tools.ffmpeg)
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+            .arg(reference)
+            .args([
+                "-map",
+                "0:v:0",
+                "-c:v",
+                "libx265",
+                "-x265-params",
+                &x265_params,
+            ])
+            .args(["-crf", &profile.base_crf.to_string()])
+            .args([
+                "-preset",
+                profile
+                    .x265_params
+                    .get("preset")
+                    .map(String::as_str)
+                    .unwrap_or("medium"),
+            ])
+            .arg(output_path)
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "FFmpeg failed to encode the segment with profile '{}': {}",
+                profile.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(profile: &str, segment: usize, size: u64, secs: u64, vmaf: f64) -> SegmentResult {
+        SegmentResult {
+            profile: profile.to_string(),
+            segment,
+            size,
+            kbps: size as f64 * 8.0 / 20.0 / 1000.0,
+            encode_time: Duration::from_secs(secs),
+            scores: vec![QualityScore {
+                metric: QualityMetric::Vmaf,
+                value: vmaf,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_shootout_winners() {
+        let starts = segment_starts(1000.0, 4, 20.0);
+        assert_eq!(starts, [190.0, 390.0, 590.0, 790.0]);
+        assert_eq!(segment_starts(30.0, 4, 20.0), [0.0]);
+
+        let report = ShootoutReport {
+            input: PathBuf::from("movie.mkv"),
+            profiles: vec!["a".to_string(), "b".to_string()],
+            segment_starts: vec![100.0, 500.0],
+            segment_length: 20.0,
+            metric: Some(QualityMetric::Vmaf),
+            results: vec![
+                result("a", 0, 4_000_000, 30, 95.0),
+                result("b", 0, 3_000_000, 40, 94.0),
+                result("a", 1, 5_000_000, 35, 96.0),
+                result("b", 1, 6_500_000, 45, 96.5),
+            ],
+        };
+        assert_eq!(
+            report.segment_winners(0),
+            Some(Winners {
+                size: "b".to_string(),
+                quality: Some("a".to_string()),
+                speed: "a".to_string(),
+            })
+        );
+
+        let summaries = report.summaries();
+        assert_eq!(summaries[0].wins, [1, 1, 2]);
+        assert_eq!(summaries[1].wins, [1, 1, 0]);
+        assert_eq!(summaries[1].worst_score, Some(94.0));
+        let (mean, deviation) = summaries[0].kbps;
+        assert_eq!(mean, 1800.0);
+        assert!((deviation - 282.84).abs() < 0.01);
+
+        let overall = report.overall_winners().unwrap();
+        assert_eq!(overall.size, "a");
+        assert_eq!(overall.quality.as_deref(), Some("a"));
+        assert!(report.format_table().contains("Overall: smallest a"));
+    }
+}
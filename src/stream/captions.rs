@@ -0,0 +1,159 @@
+use super::preservation::StreamMapping;
+use crate::config::types::OutputContainer;
+use crate::utils::{Error, Result};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Title of the subtitle track holding extracted closed captions
+const CAPTION_TRACK_TITLE: &str = "Closed Captions";
+
+/// Backslash-escapes `special` characters (and the backslash itself).
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c == '\\' || special.contains(&c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// lavfi `movie` source that decodes the video together with its embedded
+/// captions (`+subcc`). The path is escaped twice: once as a filter option
+/// value and once for the filtergraph around it.
+fn movie_source(input_path: &Path) -> String {
+    let option_value = escape(&input_path.to_string_lossy(), &['\'', ':']);
+    let graph_value = escape(&option_value, &['\'', '[', ']', ',', ';']);
+    format!("movie={}[out0+subcc]", graph_value)
+}
+
+impl StreamMapping {
+    /// Whether an encoded video stream carries EIA-608/708 captions.
+    pub fn has_closed_captions(&self) -> bool {
+        self.video_streams
+            .iter()
+            .any(|s| s.closed_captions && !s.disposition.attached_pic)
+    }
+
+    /// Adds an extracted caption file as an extra input, mapped after the
+    /// other subtitle tracks (mov_text for MP4, SRT otherwise).
+    pub fn add_caption_track(&mut self, caption_path: &Path, container: Option<OutputContainer>) {
+        let input_index = 1 + self.input_args.iter().filter(|a| *a == "-i").count();
+        let output_index = self.subtitle_output_count();
+        let codec = match container {
+            Some(OutputContainer::Mp4) => "mov_text",
+            _ => "srt",
+        };
+
+        self.input_args
+            .extend(["-i".to_string(), caption_path.to_string_lossy().to_string()]);
+        self.mapping_args.extend([
+            "-map".to_string(),
+            format!("{}:s:0", input_index),
+            format!("-c:s:{}", output_index),
+            codec.to_string(),
+            format!("-metadata:s:s:{}", output_index),
+            format!("title={}", CAPTION_TRACK_TITLE),
+        ]);
+    }
+}
+
+/// Extracts closed captions from the video stream into an SRT file through
+/// FFmpeg's lavfi `movie` source.
+pub struct CaptionExtractor {
+    ffmpeg_path: String,
+}
+
+impl CaptionExtractor {
+    pub fn new(ffmpeg_path: impl Into<String>) -> Self {
+        Self {
+            ffmpeg_path: ffmpeg_path.into(),
+        }
+    }
+
+    /// Writes the captions to `output_path`. Returns `false` when the stream
+    /// turned out to hold no caption text. Decodes the whole video once.
+    pub async fn extract(&self, input_path: &Path, output_path: &Path) -> Result<bool> {
+        info!("Extracting closed captions (decodes the full video once)");
+        let source = movie_source(input_path);
+        debug!("Caption source: {}", source);
+
+        let output = Command::new(&self.ffmpeg_path)
+            .args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"])
+            .args(["-f", "lavfi", "-i", &source])
+            .args(["-map", "0:s:0", "-c:s", "srt"])
+            .arg(output_path)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "Closed caption extraction failed: {}",
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+
+        let size = tokio::fs::metadata(output_path).await?.len();
+        Ok(size > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::{StreamDisposition, StreamInfo};
+
+    #[test]
+    fn test_caption_track() {
+        assert_eq!(
+            movie_source(Path::new("/media/it's [live]: news.ts")),
+            r"movie=/media/it\\\'s \[live\]\\: news.ts[out0+subcc]"
+        );
+
+        let video = StreamInfo {
+            index: 0,
+            codec_type: "video".to_string(),
+            codec_name: "h264".to_string(),
+            channels: None,
+            language: None,
+            title: None,
+            disposition: StreamDisposition {
+                default: true,
+                forced: false,
+                comment: false,
+                lyrics: false,
+                karaoke: false,
+                original: false,
+                dub: false,
+                visual_impaired: false,
+                hearing_impaired: false,
+                attached_pic: false,
+            },
+            closed_captions: true,
+        };
+        let mut mapping = StreamMapping {
+            video_streams: vec![video],
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: vec!["-map".to_string(), "0:v:0".to_string()],
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        assert!(mapping.has_closed_captions());
+
+        mapping.add_caption_track(Path::new("/tmp/cc.srt"), Some(OutputContainer::Mkv));
+        assert_eq!(mapping.input_args, vec!["-i", "/tmp/cc.srt"]);
+        assert_eq!(
+            mapping.mapping_args.join(" "),
+            "-map 0:v:0 -map 1:s:0 -c:s:0 srt -metadata:s:s:0 title=Closed Captions"
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use super::preservation::StreamInfo;
+use crate::config::types::AudioRole;
+use regex::Regex;
+use std::sync::LazyLock;
+
+static KARAOKE_TITLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)karaoke|sing[- ]?along").unwrap());
+
+static DESCRIPTION_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)audio[- ]?descri|descriptive|described|visually impaired|\bDVS\b|\bAD\b|audiodeskription|audiodescription",
+    )
+    .unwrap()
+});
+
+static COMMENTARY_TITLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)commentary|director|kommentar|commentaire|comentario").unwrap()
+});
+
+/// Classifies an audio track. Dispositions are trusted as-is; title matches
+/// only count for mono/stereo tracks, since commentary and description tracks
+/// are rarely mixed wider and titles like "Director's Cut 5.1" name the main mix.
+pub fn classify_audio(stream: &StreamInfo) -> AudioRole {
+    let disposition = &stream.disposition;
+    if disposition.karaoke {
+        return AudioRole::Karaoke;
+    }
+    if disposition.visual_impaired {
+        return AudioRole::AudioDescription;
+    }
+    if disposition.comment {
+        return AudioRole::Commentary;
+    }
+
+    let Some(title) = stream.title.as_deref() else {
+        return AudioRole::Main;
+    };
+    if KARAOKE_TITLE.is_match(title) {
+        return AudioRole::Karaoke;
+    }
+
+    let narrow_mix = stream.channels.is_none_or(|channels| channels <= 2);
+    if narrow_mix && DESCRIPTION_TITLE.is_match(title) {
+        AudioRole::AudioDescription
+    } else if narrow_mix && COMMENTARY_TITLE.is_match(title) {
+        AudioRole::Commentary
+    } else {
+        AudioRole::Main
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    fn audio(title: Option<&str>, channels: u32) -> StreamInfo {
+        StreamInfo {
+            index: 1,
+            codec_type: "audio".to_string(),
+            codec_name: "ac3".to_string(),
+            channels: Some(channels),
+            language: Some("eng".to_string()),
+            title: title.map(str::to_string),
+            disposition: StreamDisposition {
+                default: false,
+                forced: false,
+                comment: false,
+                lyrics: false,
+                karaoke: false,
+                original: false,
+                dub: false,
+                visual_impaired: false,
+                hearing_impaired: false,
+                attached_pic: false,
+            },
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_classify_audio() {
+        assert_eq!(classify_audio(&audio(None, 6)), AudioRole::Main);
+        assert_eq!(
+            classify_audio(&audio(Some("Director's Cut 5.1"), 6)),
+            AudioRole::Main
+        );
+        assert_eq!(
+            classify_audio(&audio(Some("Commentary with the director"), 2)),
+            AudioRole::Commentary
+        );
+        assert_eq!(
+            classify_audio(&audio(Some("English AD"), 2)),
+            AudioRole::AudioDescription
+        );
+        assert_eq!(
+            classify_audio(&audio(Some("Karaoke"), 6)),
+            AudioRole::Karaoke
+        );
+
+        let mut described = audio(Some("English 5.1"), 6);
+        described.disposition.visual_impaired = true;
+        assert_eq!(classify_audio(&described), AudioRole::AudioDescription);
+    }
+}
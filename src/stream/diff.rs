@@ -0,0 +1,257 @@
+//! Before/after comparison of the source's streams and the output's.
+//!
+//! Streams of each type are paired in order: the n-th audio stream the mapping
+//! kept with the n-th audio stream of the output. Source streams the mapping
+//! left out show up as dropped; streams it kept that are missing from the
+//! output as lost.
+
+use crate::stream::preservation::{StreamInfo, StreamMapping};
+use console::style;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamChange {
+    Kept,
+    Transcoded,
+    Dropped,
+    /// Selected for the output but not in it
+    Lost,
+    /// In the output only, e.g. extracted closed captions
+    Added,
+}
+
+impl StreamChange {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Kept => "kept",
+            Self::Transcoded => "transcoded",
+            Self::Dropped => "dropped",
+            Self::Lost => "LOST",
+            Self::Added => "added",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamDiffRow {
+    pub change: StreamChange,
+    pub source: Option<StreamInfo>,
+    pub output: Option<StreamInfo>,
+}
+
+const STREAM_TYPES: &[&str] = &["video", "audio", "subtitle", "data", "attachment"];
+
+/// Pairs the source's streams (`source`, all of them) with the output's.
+pub fn diff_streams(
+    source: &[StreamInfo],
+    mapping: &StreamMapping,
+    output: &[StreamInfo],
+) -> Vec<StreamDiffRow> {
+    let kept_indices: Vec<u32> = mapping
+        .video_streams
+        .iter()
+        .chain(&mapping.audio_streams)
+        .chain(&mapping.subtitle_streams)
+        .chain(&mapping.data_streams)
+        .map(|s| s.index)
+        .collect();
+
+    let mut rows = Vec::new();
+    for &codec_type in STREAM_TYPES {
+        let mut kept: Vec<&StreamInfo> = source
+            .iter()
+            .filter(|s| s.codec_type == codec_type && kept_indices.contains(&s.index))
+            .collect();
+        // The encoded video stream comes first, cover art after it
+        kept.sort_by_key(|s| s.disposition.attached_pic);
+        let mut outputs = output.iter().filter(|s| s.codec_type == codec_type);
+
+        for stream in kept {
+            let row = match outputs.next() {
+                Some(out) => StreamDiffRow {
+                    change: if out.codec_name == stream.codec_name {
+                        StreamChange::Kept
+                    } else {
+                        StreamChange::Transcoded
+                    },
+                    source: Some(stream.clone()),
+                    output: Some(out.clone()),
+                },
+                None => StreamDiffRow {
+                    change: StreamChange::Lost,
+                    source: Some(stream.clone()),
+                    output: None,
+                },
+            };
+            rows.push(row);
+        }
+        rows.extend(outputs.map(|out| StreamDiffRow {
+            change: StreamChange::Added,
+            source: None,
+            output: Some(out.clone()),
+        }));
+        rows.extend(
+            source
+                .iter()
+                .filter(|s| s.codec_type == codec_type && !kept_indices.contains(&s.index))
+                .map(|s| StreamDiffRow {
+                    change: StreamChange::Dropped,
+                    source: Some(s.clone()),
+                    output: None,
+                }),
+        );
+    }
+    rows
+}
+
+/// `#2 ac3 eng (default, forced)`
+fn describe(stream: &StreamInfo) -> String {
+    let mut text = format!("#{} {}", stream.index, stream.codec_name);
+    if let Some(language) = &stream.language {
+        text.push(' ');
+        text.push_str(language);
+    }
+    let d = &stream.disposition;
+    let flags: Vec<&str> = [
+        (d.default, "default"),
+        (d.forced, "forced"),
+        (d.comment, "commentary"),
+        (d.hearing_impaired, "SDH"),
+        (d.attached_pic, "cover"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect();
+    if !flags.is_empty() {
+        text.push_str(&format!(" ({})", flags.join(", ")));
+    }
+    text
+}
+
+/// One aligned line per stream: type, source, output and the change, colored
+/// by change when `use_color` is set.
+pub fn render(rows: &[StreamDiffRow], use_color: bool) -> Vec<String> {
+    let cells: Vec<(String, String, String)> = rows
+        .iter()
+        .map(|row| {
+            let codec_type = row
+                .source
+                .as_ref()
+                .or(row.output.as_ref())
+                .map(|s| s.codec_type.clone())
+                .unwrap_or_default();
+            let describe_or_dash =
+                |stream: &Option<StreamInfo>| stream.as_ref().map_or("-".to_string(), describe);
+            (
+                codec_type,
+                describe_or_dash(&row.source),
+                describe_or_dash(&row.output),
+            )
+        })
+        .collect();
+    let type_width = cells.iter().map(|c| c.0.len()).max().unwrap_or(0);
+    let source_width = cells.iter().map(|c| c.1.chars().count()).max().unwrap_or(0);
+    let output_width = cells.iter().map(|c| c.2.chars().count()).max().unwrap_or(0);
+
+    rows.iter()
+        .zip(cells)
+        .map(|(row, (codec_type, source, output))| {
+            let line = format!(
+                "{:<type_width$}  {:<source_width$} -> {:<output_width$}  {}",
+                codec_type,
+                source,
+                output,
+                row.change.as_str(),
+            );
+            if !use_color {
+                return line;
+            }
+            match row.change {
+                StreamChange::Kept => line,
+                StreamChange::Transcoded => style(line).yellow().to_string(),
+                StreamChange::Dropped => style(line).red().to_string(),
+                StreamChange::Lost => style(line).red().bold().to_string(),
+                StreamChange::Added => style(line).cyan().to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    fn stream(
+        index: u32,
+        codec_type: &str,
+        codec_name: &str,
+        language: Option<&str>,
+    ) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: codec_type.to_string(),
+            codec_name: codec_name.to_string(),
+            channels: None,
+            language: language.map(str::to_string),
+            title: None,
+            disposition: StreamDisposition::default(),
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_diff_streams() {
+        let source = vec![
+            stream(0, "video", "h264", None),
+            stream(1, "audio", "dts", Some("eng")),
+            stream(2, "audio", "ac3", Some("ger")),
+            stream(3, "subtitle", "subrip", Some("eng")),
+            stream(4, "subtitle", "hdmv_pgs_subtitle", Some("eng")),
+        ];
+        let mapping = StreamMapping {
+            video_streams: vec![source[0].clone()],
+            audio_streams: vec![source[1].clone()],
+            subtitle_streams: vec![source[3].clone(), source[4].clone()],
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: Vec::new(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        let output = vec![
+            stream(0, "video", "hevc", None),
+            stream(1, "audio", "dts", Some("eng")),
+            stream(2, "subtitle", "subrip", Some("eng")),
+        ];
+        // The PGS track was selected but did not make it into the output
+
+        let changes: Vec<(StreamChange, Option<u32>)> = diff_streams(&source, &mapping, &output)
+            .iter()
+            .map(|row| (row.change, row.source.as_ref().map(|s| s.index)))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (StreamChange::Transcoded, Some(0)),
+                (StreamChange::Kept, Some(1)),
+                (StreamChange::Dropped, Some(2)),
+                (StreamChange::Kept, Some(3)),
+                (StreamChange::Lost, Some(4)),
+            ]
+        );
+
+        let lines = render(&diff_streams(&source, &mapping, &output), false);
+        assert_eq!(
+            lines[0],
+            "video     #0 h264                  -> #0 hevc        transcoded"
+        );
+        assert_eq!(
+            lines[4],
+            "subtitle  #4 hdmv_pgs_subtitle eng -> -              LOST"
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Finds the forced (narrative) subtitle track of sources that do not flag
+//! it: foreign dialogue and signs are subtitled in a track with only a
+//! fraction of the events of the full subtitles, usually in the language of
+//! the main audio, often titled "Forced".
+
+use super::preservation::{language_matches, StreamInfo, StreamMapping};
+use crate::config::types::ForcedSubtitleConfig;
+use crate::utils::{FfmpegWrapper, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use tracing::debug;
+
+/// Why a track was taken for the forced one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForcedReason {
+    /// Its title says so
+    Title,
+    /// It has few events next to another track in the same language
+    EventCount { events: u64, full_events: u64 },
+}
+
+impl std::fmt::Display for ForcedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Title => f.write_str("title mentions forced"),
+            Self::EventCount {
+                events,
+                full_events,
+            } => write!(
+                f,
+                "{} events against {} in the full track",
+                events, full_events
+            ),
+        }
+    }
+}
+
+/// Events (packets) of every subtitle stream of `input_path`, by stream
+/// index. Reads the whole file.
+pub async fn count_subtitle_events(
+    ffmpeg: &FfmpegWrapper,
+    input_path: &Path,
+) -> Result<HashMap<u32, u64>> {
+    let output = ffmpeg
+        .run_ffprobe(&[
+            "-v",
+            "error",
+            "-select_streams",
+            "s",
+            "-count_packets",
+            "-show_entries",
+            "stream=index,nb_read_packets",
+            "-of",
+            "csv=p=0",
+            &input_path.to_string_lossy(),
+        ])
+        .await?;
+    Ok(parse_event_counts(&output))
+}
+
+/// `index,count` lines as printed by ffprobe.
+fn parse_event_counts(output: &str) -> HashMap<u32, u64> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (index, count) = line.trim().split_once(',')?;
+            Some((index.parse().ok()?, count.trim().parse().ok()?))
+        })
+        .collect()
+}
+
+fn same_language(a: &str, b: &str) -> bool {
+    a.eq_ignore_ascii_case(b) || language_matches(Some(a), b) || language_matches(Some(b), a)
+}
+
+/// Language of the main audio: the default audio stream, or else the first.
+pub fn main_audio_language(mapping: &StreamMapping) -> Option<&str> {
+    mapping
+        .audio_streams
+        .iter()
+        .find(|stream| stream.disposition.default)
+        .or_else(|| mapping.audio_streams.first())
+        .and_then(|stream| stream.language.as_deref())
+}
+
+/// The forced track among `subtitles`, by stream index. `None` when a track
+/// is already flagged forced or none stands out. A track titled "forced"
+/// wins; otherwise, within the main audio's language (any language when it
+/// is unknown), the track with the fewest events is taken if it has at most
+/// `max_event_ratio` of the events of the fullest track.
+pub fn detect_forced(
+    subtitles: &[StreamInfo],
+    events: &HashMap<u32, u64>,
+    audio_language: Option<&str>,
+    config: &ForcedSubtitleConfig,
+) -> Option<(u32, ForcedReason)> {
+    if subtitles.iter().any(|stream| stream.disposition.forced) {
+        return None;
+    }
+
+    let in_language: Vec<&StreamInfo> = subtitles
+        .iter()
+        .filter(
+            |stream| match (audio_language, stream.language.as_deref()) {
+                (Some(audio), Some(language)) => same_language(audio, language),
+                _ => true,
+            },
+        )
+        .collect();
+
+    if let Some(titled) = in_language.iter().find(|stream| {
+        stream
+            .title
+            .as_ref()
+            .is_some_and(|title| title.to_lowercase().contains("forced"))
+    }) {
+        return Some((titled.index, ForcedReason::Title));
+    }
+
+    let counted: Vec<(u32, u64)> = in_language
+        .iter()
+        .filter_map(|stream| events.get(&stream.index).map(|&n| (stream.index, n)))
+        .filter(|&(_, n)| n > 0)
+        .collect();
+    if counted.len() < 2 {
+        return None;
+    }
+    let full_events = counted.iter().map(|&(_, n)| n).max()?;
+    let (index, fewest) = counted.iter().copied().min_by_key(|&(_, n)| n)?;
+    debug!(
+        "Subtitle events: fewest {} (#{}), most {}",
+        fewest, index, full_events
+    );
+    (fewest as f64 <= full_events as f64 * config.max_event_ratio).then_some((
+        index,
+        ForcedReason::EventCount {
+            events: fewest,
+            full_events,
+        },
+    ))
+}
+
+impl StreamMapping {
+    /// Flags the selected subtitle stream `index` forced in the output (and
+    /// default, taking the flag from the other subtitles, when `set_default`).
+    /// Returns `false` when the stream is not among the selected source
+    /// streams, e.g. replaced by its OCR result.
+    pub fn mark_forced_subtitle(&mut self, index: u32, set_default: bool) -> bool {
+        let position = self
+            .subtitle_streams
+            .iter()
+            .position(|stream| stream.index == index);
+        if set_default {
+            for (other, stream) in self.subtitle_streams.iter().enumerate() {
+                if Some(other) != position && stream.disposition.default {
+                    self.mapping_args
+                        .extend([format!("-disposition:s:{}", other), "-default".to_string()]);
+                }
+            }
+        }
+        let Some(position) = position else {
+            return false;
+        };
+        let flags = if set_default {
+            "+default+forced"
+        } else {
+            "+forced"
+        };
+        self.mapping_args
+            .extend([format!("-disposition:s:{}", position), flags.to_string()]);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    fn subtitle(index: u32, language: &str, title: Option<&str>, default: bool) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: "subtitle".to_string(),
+            codec_name: "subrip".to_string(),
+            channels: None,
+            language: Some(language.to_string()),
+            title: title.map(str::to_string),
+            disposition: StreamDisposition {
+                default,
+                ..Default::default()
+            },
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_detect_forced_subtitles() {
+        let config = ForcedSubtitleConfig::default();
+        let events = parse_event_counts("2,1450\n3,38\n4,1320\n5,12\n");
+        assert_eq!(events.get(&3), Some(&38));
+
+        let subtitles = vec![
+            subtitle(2, "eng", None, true),
+            subtitle(3, "eng", None, false),
+            subtitle(4, "ger", None, false),
+            subtitle(5, "ger", None, false),
+        ];
+        assert_eq!(
+            detect_forced(&subtitles, &events, Some("en"), &config),
+            Some((
+                3,
+                ForcedReason::EventCount {
+                    events: 38,
+                    full_events: 1450
+                }
+            ))
+        );
+        assert_eq!(
+            detect_forced(&subtitles, &events, Some("ger"), &config).map(|(i, _)| i),
+            Some(5)
+        );
+
+        // The title wins over the event count
+        let mut titled = subtitles.clone();
+        titled[0].title = Some("English (Forced)".to_string());
+        assert_eq!(
+            detect_forced(&titled, &events, Some("eng"), &config),
+            Some((2, ForcedReason::Title))
+        );
+
+        // Flagged sources and similar tracks are left alone
+        let mut flagged = subtitles.clone();
+        flagged[1].disposition.forced = true;
+        assert_eq!(detect_forced(&flagged, &events, Some("eng"), &config), None);
+        let similar = parse_event_counts("2,1450\n3,1200\n");
+        assert_eq!(
+            detect_forced(&subtitles[..2], &similar, Some("eng"), &config),
+            None
+        );
+
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: subtitles,
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: Vec::new(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        assert!(mapping.mark_forced_subtitle(3, true));
+        assert_eq!(
+            mapping.mapping_args,
+            [
+                "-disposition:s:0",
+                "-default",
+                "-disposition:s:1",
+                "+default+forced"
+            ]
+        );
+        assert!(!mapping.mark_forced_subtitle(9, false));
+        assert_eq!(mapping.mapping_args.len(), 4);
+    }
+}
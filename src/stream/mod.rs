@@ -1 +1,8 @@
+pub mod captions;
+pub mod classification;
+pub mod diff;
+pub mod forced;
+pub mod ocr;
 pub mod preservation;
+pub mod titles;
+pub mod verification;
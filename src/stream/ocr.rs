@@ -0,0 +1,285 @@
+use super::preservation::{StreamInfo, StreamMapping};
+use crate::config::types::{OutputContainer, SubtitleOcrConfig};
+use crate::utils::{Error, Result, ToolConfig, ToolRunner};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::{debug, info};
+
+/// Codec name ffprobe reports for Blu-ray (PGS) subtitles
+pub const PGS_CODEC: &str = "hdmv_pgs_subtitle";
+
+impl StreamMapping {
+    /// Subtitle streams the output gets: the selected source streams plus
+    /// tracks added from extra inputs (OCR results, extracted captions).
+    pub(crate) fn subtitle_output_count(&self) -> usize {
+        let added = self
+            .mapping_args
+            .windows(2)
+            .filter(|pair| {
+                pair[0] == "-map"
+                    && pair[1]
+                        .split_once(":s")
+                        .is_some_and(|(input, _)| input != "0" && input.parse::<u32>().is_ok())
+            })
+            .count();
+        self.subtitle_streams.len() + added
+    }
+
+    /// Leaves the source subtitle stream `index` out of the output.
+    pub fn drop_subtitle(&mut self, index: u32) {
+        self.mapping_args
+            .extend(["-map".to_string(), format!("-0:{}", index)]);
+        self.subtitle_streams.retain(|stream| stream.index != index);
+    }
+
+    /// Adds `srt_path`, a text version of the source subtitle `original`, as
+    /// an extra input after the other subtitle tracks, with the original's
+    /// language, title and default/forced flags (mov_text for MP4, SRT otherwise).
+    pub fn add_text_subtitle(
+        &mut self,
+        original: &StreamInfo,
+        srt_path: &Path,
+        container: Option<OutputContainer>,
+    ) {
+        let input_index = 1 + self.input_args.iter().filter(|a| *a == "-i").count();
+        let output_index = self.subtitle_output_count();
+        let codec = match container {
+            Some(OutputContainer::Mp4) => "mov_text",
+            _ => "srt",
+        };
+
+        self.input_args
+            .extend(["-i".to_string(), srt_path.to_string_lossy().to_string()]);
+        self.mapping_args.extend([
+            "-map".to_string(),
+            format!("{}:s:0", input_index),
+            format!("-c:s:{}", output_index),
+            codec.to_string(),
+            format!("-metadata:s:s:{}", output_index),
+            format!("language={}", original.language.as_deref().unwrap_or("und")),
+        ]);
+        if let Some(title) = &original.title {
+            self.mapping_args.extend([
+                format!("-metadata:s:s:{}", output_index),
+                format!("title={}", title),
+            ]);
+        }
+        let flags: Vec<&str> = [
+            (original.disposition.default, "default"),
+            (original.disposition.forced, "forced"),
+        ]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+        self.mapping_args.extend([
+            format!("-disposition:s:{}", output_index),
+            if flags.is_empty() {
+                "0".to_string()
+            } else {
+                flags.join("+")
+            },
+        ]);
+    }
+}
+
+/// Converts PGS subtitle streams to SRT with the OCR tool of
+/// `tools.subtitle_ocr`: the stream is copied out to a `.sup` file, which the
+/// tool reads.
+pub struct SubtitleOcr {
+    ffmpeg_path: String,
+    args: Vec<String>,
+    tool: ToolRunner,
+}
+
+impl SubtitleOcr {
+    pub fn new(ffmpeg_path: impl Into<String>, config: &SubtitleOcrConfig) -> Self {
+        Self {
+            ffmpeg_path: ffmpeg_path.into(),
+            args: config.args.clone(),
+            tool: ToolRunner::new(ToolConfig {
+                path: config.path.clone(),
+                timeout_seconds: config.timeout_seconds,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// The tool's arguments with the placeholders filled in.
+    fn tool_args(&self, sup: &Path, srt: &Path, language: &str) -> Vec<String> {
+        let output_dir = srt.parent().unwrap_or(Path::new("."));
+        self.args
+            .iter()
+            .map(|arg| {
+                arg.replace("{input}", &sup.to_string_lossy())
+                    .replace("{output}", &srt.to_string_lossy())
+                    .replace("{output_dir}", &output_dir.to_string_lossy())
+                    .replace("{language}", language)
+            })
+            .collect()
+    }
+
+    /// OCRs source stream `stream` of `input_path` in its own directory under
+    /// `work_dir` and returns the SRT file.
+    pub async fn convert(
+        &self,
+        input_path: &Path,
+        stream: &StreamInfo,
+        work_dir: &Path,
+    ) -> Result<PathBuf> {
+        let dir = work_dir.join(format!("ocr_{}", stream.index));
+        tokio::fs::create_dir_all(&dir).await?;
+        let sup = dir.join(format!("stream_{}.sup", stream.index));
+        let srt = dir.join(format!("stream_{}.srt", stream.index));
+
+        let output = Command::new(&self.ffmpeg_path)
+            .args(["-hide_banner", "-nostats", "-loglevel", "error", "-y", "-i"])
+            .arg(input_path)
+            .args(["-map", &format!("0:{}", stream.index), "-c:s", "copy"])
+            .arg(&sup)
+            .output()
+            .await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "Extracting PGS subtitle stream #{} failed: {}",
+                stream.index,
+                stderr.lines().last().unwrap_or("unknown error")
+            )));
+        }
+
+        let language = stream.language.as_deref().unwrap_or("und");
+        info!(
+            "OCR of PGS subtitle stream #{} ({})",
+            stream.index, language
+        );
+        self.tool
+            .run(&self.tool_args(&sup, &srt, language), None)
+            .await?;
+
+        let result = if srt.is_file() {
+            srt
+        } else {
+            // Tools that name their output themselves (e.g. `<input>.<lang>.srt`)
+            find_srt(&dir).ok_or_else(|| {
+                Error::tool(format!(
+                    "OCR tool wrote no SRT file for subtitle stream #{}",
+                    stream.index
+                ))
+            })?
+        };
+        if std::fs::metadata(&result)?.len() == 0 {
+            return Err(Error::tool(format!(
+                "OCR found no text in subtitle stream #{}",
+                stream.index
+            )));
+        }
+        debug!("OCR result: {}", result.display());
+        Ok(result)
+    }
+}
+
+/// The first `.srt` file in `dir`.
+fn find_srt(dir: &Path) -> Option<PathBuf> {
+    let mut found: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "srt"))
+        .collect();
+    found.sort();
+    found.into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    #[test]
+    fn test_pgs_replaced_by_ocr_result() {
+        let pgs = |index: u32, language: &str, forced: bool| StreamInfo {
+            index,
+            codec_type: "subtitle".to_string(),
+            codec_name: PGS_CODEC.to_string(),
+            channels: None,
+            language: Some(language.to_string()),
+            title: forced.then(|| "Forced".to_string()),
+            disposition: StreamDisposition {
+                forced,
+                ..Default::default()
+            },
+            closed_captions: false,
+        };
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: vec![pgs(2, "eng", false), pgs(3, "ger", true)],
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: [
+                "-map", "0:v:0", "-map", "0:2", "-map", "0:3", "-c:s", "copy",
+            ]
+            .iter()
+            .map(|a| a.to_string())
+            .collect(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        let forced = mapping.subtitle_streams[1].clone();
+        mapping.drop_subtitle(3);
+        mapping.add_text_subtitle(
+            &forced,
+            Path::new("/tmp/s3.srt"),
+            Some(OutputContainer::Mkv),
+        );
+        assert_eq!(mapping.input_args, ["-i", "/tmp/s3.srt"]);
+        assert_eq!(
+            mapping.mapping_args[8..],
+            [
+                "-map",
+                "-0:3",
+                "-map",
+                "1:s:0",
+                "-c:s:1",
+                "srt",
+                "-metadata:s:s:1",
+                "language=ger",
+                "-metadata:s:s:1",
+                "title=Forced",
+                "-disposition:s:1",
+                "forced",
+            ]
+        );
+        assert_eq!(mapping.subtitle_streams.len(), 1);
+        assert_eq!(mapping.subtitle_output_count(), 2);
+
+        let ocr = SubtitleOcr::new(
+            "ffmpeg",
+            &SubtitleOcrConfig {
+                path: "pgsrip".to_string(),
+                args: ["--language", "{language}", "{input}", "-o", "{output_dir}"]
+                    .iter()
+                    .map(|a| a.to_string())
+                    .collect(),
+                timeout_seconds: 600,
+            },
+        );
+        assert_eq!(
+            ocr.tool_args(
+                Path::new("/work/ocr_3/stream_3.sup"),
+                Path::new("/work/ocr_3/stream_3.srt"),
+                "ger"
+            ),
+            [
+                "--language",
+                "ger",
+                "/work/ocr_3/stream_3.sup",
+                "-o",
+                "/work/ocr_3"
+            ]
+        );
+    }
+}
@@ -1,4 +1,10 @@
-use crate::config::types::{AudioSelectionConfig, StreamSelectionProfile, SubtitleSelectionConfig};
+use super::classification::classify_audio;
+use crate::audio::language::{is_untagged, LanguageDetector};
+use crate::config::types::{
+    AudioRole, AudioSelectionConfig, LanguageSelectionMode, OutputContainer,
+    StreamSelectionProfile, SubtitleSelectionConfig, VideoStreamPolicy,
+};
+use crate::log_step;
 use crate::utils::{Error, FfmpegWrapper, Result};
 use regex::Regex;
 use serde_json::{from_str, Value};
@@ -10,12 +16,23 @@ pub struct StreamInfo {
     pub index: u32,
     pub codec_type: String,
     pub codec_name: String,
+    /// Audio channel count as reported by ffprobe
+    pub channels: Option<u32>,
     pub language: Option<String>,
     pub title: Option<String>,
     pub disposition: StreamDisposition,
+    /// Video stream carries EIA-608/708 closed captions (as reported by ffprobe)
+    pub closed_captions: bool,
 }
 
-#[derive(Debug, Clone)]
+impl StreamInfo {
+    /// Role of an audio track (main, commentary, ...); `None` for other stream types.
+    pub fn audio_role(&self) -> Option<AudioRole> {
+        (self.codec_type == "audio").then(|| classify_audio(self))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct StreamDisposition {
     pub default: bool,
     pub forced: bool,
@@ -26,6 +43,7 @@ pub struct StreamDisposition {
     pub dub: bool,
     pub visual_impaired: bool,
     pub hearing_impaired: bool,
+    pub attached_pic: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -48,23 +66,419 @@ pub struct StreamMapping {
     pub chapters: Vec<ChapterInfo>,
     pub metadata: Vec<(String, String)>,
     pub mapping_args: Vec<String>,
+    /// Codec/disposition overrides that copy the video streams besides the
+    /// encoded one: cover art (kept as attached pictures) and, under
+    /// `video_policy: all`, the other video streams. Must follow the global
+    /// `-c:v` in the FFmpeg command.
+    pub copied_video_args: Vec<String>,
+    /// Additional inputs (e.g. extracted captions), placed right after the source `-i`
+    pub input_args: Vec<String>,
+    /// Source streams left out of the output, and why
+    pub dropped: Vec<DroppedStream>,
+    /// Position among the video streams of the stream chosen with
+    /// `--video-stream`; `None` encodes the first one that is not cover art
+    pub primary_video: Option<usize>,
+}
+
+/// A source stream left out of the output
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroppedStream {
+    pub index: u32,
+    pub codec_type: String,
+    /// Config key or limit that removed it, e.g.
+    /// `stream_selection_profiles.english.audio.languages`
+    pub rule: String,
+}
+
+impl DroppedStream {
+    fn new(stream: &StreamInfo, rule: &str) -> Self {
+        Self {
+            index: stream.index,
+            codec_type: stream.codec_type.clone(),
+            rule: rule.to_string(),
+        }
+    }
+}
+
+/// `streams.retain(keep)`, noting the streams it removes as dropped by `rule`.
+fn retain_streams(
+    streams: &mut Vec<StreamInfo>,
+    dropped: &mut Vec<DroppedStream>,
+    rule: &str,
+    mut keep: impl FnMut(&StreamInfo) -> bool,
+) {
+    streams.retain(|stream| {
+        let kept = keep(stream);
+        if !kept {
+            dropped.push(DroppedStream::new(stream, rule));
+        }
+        kept
+    });
+}
+
+impl StreamMapping {
+    /// Stream specifier of the video stream that gets encoded (and filtered), e.g. `0:v:0`.
+    /// Cover art is skipped, so this is not always the first video stream.
+    pub fn primary_video_specifier(&self) -> String {
+        let position = self
+            .primary_video
+            .unwrap_or_else(|| primary_video_position(&self.video_streams));
+        format!("0:v:{}", position)
+    }
+
+    /// Adjusts the mapping for containers that cannot carry everything Matroska can.
+    /// MP4 has no attachments or arbitrary data streams and only supports text
+    /// subtitles (as mov_text), so bitmap subtitles are dropped with a warning.
+    pub fn adapt_for_container(&mut self, container: OutputContainer) {
+        if container != OutputContainer::Mp4 {
+            return;
+        }
+
+        let (text_subs, bitmap_subs): (Vec<StreamInfo>, Vec<StreamInfo>) = self
+            .subtitle_streams
+            .drain(..)
+            .partition(|s| !is_bitmap_subtitle(&s.codec_name));
+        for stream in &bitmap_subs {
+            warn!(
+                "Dropping {} subtitle stream #{} (bitmap subtitles are not supported in MP4)",
+                stream.codec_name, stream.index
+            );
+        }
+        if !self.data_streams.is_empty() {
+            warn!(
+                "Dropping {} data/attachment stream(s) (not supported in MP4)",
+                self.data_streams.len()
+            );
+        }
+        self.dropped.extend(
+            bitmap_subs
+                .iter()
+                .chain(&self.data_streams)
+                .map(|stream| DroppedStream::new(stream, "MP4 output")),
+        );
+
+        let subtitle_indices: Vec<String> = text_subs
+            .iter()
+            .chain(bitmap_subs.iter())
+            .map(|s| format!("0:{}", s.index))
+            .collect();
+
+        let mut args = Vec::new();
+        let mut iter = self.mapping_args.iter();
+        while let Some(arg) = iter.next() {
+            let value = match arg.as_str() {
+                "-map" | "-c:s" | "-c:d" | "-c:t" => iter.next(),
+                _ => None,
+            };
+            match (arg.as_str(), value) {
+                ("-map", Some(v)) if v == "0:s?" || v == "0:d?" || v == "0:t?" => {}
+                ("-map", Some(v)) if subtitle_indices.contains(v) => {}
+                ("-c:s" | "-c:d" | "-c:t", Some(_)) => {}
+                (_, Some(v)) => {
+                    args.push(arg.clone());
+                    args.push(v.clone());
+                }
+                (_, None) => args.push(arg.clone()),
+            }
+        }
+
+        for stream in &text_subs {
+            args.push("-map".to_string());
+            args.push(format!("0:{}", stream.index));
+        }
+        if !text_subs.is_empty() {
+            args.extend(vec!["-c:s".to_string(), "mov_text".to_string()]);
+        }
+
+        self.subtitle_streams = text_subs;
+        self.data_streams.clear();
+        self.mapping_args = args;
+    }
+}
+
+/// ISO 639-1 codes of common languages and their ISO 639-2 (B and T) forms,
+/// so profiles can say `de` for tracks tagged `ger` or `deu`.
+pub(crate) const LANGUAGE_ALIASES: &[(&str, &[&str])] = &[
+    ("de", &["ger", "deu"]),
+    ("en", &["eng"]),
+    ("es", &["spa"]),
+    ("fr", &["fre", "fra"]),
+    ("it", &["ita"]),
+    ("ja", &["jpn"]),
+    ("ko", &["kor"]),
+    ("nl", &["dut", "nld"]),
+    ("pl", &["pol"]),
+    ("pt", &["por"]),
+    ("ru", &["rus"]),
+    ("sv", &["swe"]),
+    ("zh", &["chi", "zho"]),
+];
+
+/// Whether a stream's language tag matches one entry of a profile's `languages`.
+/// `und` also matches streams without a language tag.
+pub(crate) fn language_matches(stream_language: Option<&str>, wanted: &str) -> bool {
+    let wanted = wanted.to_lowercase();
+    let Some(language) = stream_language.map(str::to_lowercase) else {
+        return wanted == "und";
+    };
+
+    language.contains(&wanted)
+        || LANGUAGE_ALIASES
+            .iter()
+            .find(|(short, _)| *short == wanted)
+            .is_some_and(|(_, codes)| codes.contains(&language.as_str()))
+}
+
+/// Applies a profile's `languages` list in the given selection mode.
+fn select_languages(
+    streams: Vec<StreamInfo>,
+    languages: &[String],
+    mode: LanguageSelectionMode,
+) -> Vec<StreamInfo> {
+    let matches =
+        |stream: &StreamInfo, wanted: &str| language_matches(stream.language.as_deref(), wanted);
+
+    match mode {
+        LanguageSelectionMode::AllMatches => streams
+            .into_iter()
+            .filter(|stream| languages.iter().any(|wanted| matches(stream, wanted)))
+            .collect(),
+        LanguageSelectionMode::FirstMatch => {
+            let Some(chosen) = languages
+                .iter()
+                .find(|wanted| streams.iter().any(|stream| matches(stream, wanted)))
+            else {
+                return Vec::new();
+            };
+            debug!("Language fallback chain resolved to '{}'", chosen);
+            streams
+                .into_iter()
+                .filter(|stream| matches(stream, chosen))
+                .collect()
+        }
+    }
+}
+
+pub(crate) fn is_bitmap_subtitle(codec_name: &str) -> bool {
+    matches!(
+        codec_name,
+        "hdmv_pgs_subtitle" | "dvd_subtitle" | "dvb_subtitle" | "xsub"
+    )
+}
+
+/// Whether `exclude_commentary` drops a stream: flagged as commentary, or
+/// titled so whatever its channel count.
+fn is_commentary(stream: &StreamInfo) -> bool {
+    stream.disposition.comment
+        || stream.title.as_ref().is_some_and(|title| {
+            let title = title.to_lowercase();
+            title.contains("commentary") || title.contains("director")
+        })
+}
+
+/// Position (among video streams) of the first stream that is not cover art.
+fn primary_video_position(video_streams: &[StreamInfo]) -> usize {
+    video_streams
+        .iter()
+        .position(|s| !s.disposition.attached_pic)
+        .unwrap_or(0)
+}
+
+/// `-metadata:s:a:N language=xxx` for the audio streams whose language was
+/// detected, N being the stream's position among the mapped audio streams.
+fn detected_language_args(audio_streams: &[StreamInfo], detected: &[u32]) -> Vec<String> {
+    audio_streams
+        .iter()
+        .enumerate()
+        .filter(|(_, stream)| detected.contains(&stream.index))
+        .filter_map(|(output_index, stream)| {
+            let language = stream.language.as_ref()?;
+            Some([
+                format!("-metadata:s:a:{}", output_index),
+                format!("language={}", language),
+            ])
+        })
+        .flatten()
+        .collect()
 }
 
 pub struct StreamPreservation {
     ffmpeg: FfmpegWrapper,
+    video_policy: VideoStreamPolicy,
+    video_stream: Option<usize>,
+    language_detector: Option<LanguageDetector>,
 }
 
 impl StreamPreservation {
     pub fn new(ffmpeg: FfmpegWrapper) -> Self {
-        Self { ffmpeg }
+        Self {
+            ffmpeg,
+            video_policy: VideoStreamPolicy::default(),
+            video_stream: None,
+            language_detector: None,
+        }
+    }
+
+    pub fn with_video_policy(mut self, policy: VideoStreamPolicy) -> Self {
+        self.video_policy = policy;
+        self
+    }
+
+    /// Encodes the video stream at `position` (`0:v:N`) instead of the first
+    /// one that is not cover art.
+    pub fn with_video_stream(mut self, position: Option<usize>) -> Self {
+        self.video_stream = position;
+        self
+    }
+
+    /// Position of the encoded video stream among `video_streams`.
+    fn primary_position(&self, video_streams: &[StreamInfo]) -> Result<usize> {
+        match self.video_stream {
+            Some(position) if position >= video_streams.len() => Err(Error::validation(format!(
+                "--video-stream {}: the source has {} video stream(s)",
+                position,
+                video_streams.len()
+            ))),
+            Some(position) => Ok(position),
+            None => Ok(primary_video_position(video_streams)),
+        }
+    }
+
+    /// Detects the language of untagged audio streams before they are selected.
+    pub fn with_language_detector(mut self, detector: LanguageDetector) -> Self {
+        self.language_detector = Some(detector);
+        self
+    }
+
+    /// Tags untagged audio streams with their detected language; returns the
+    /// indices of the streams that got one. A failed detection leaves the
+    /// stream untagged.
+    async fn detect_languages(&self, input_path: &Path, streams: &mut [StreamInfo]) -> Vec<u32> {
+        let Some(detector) = &self.language_detector else {
+            return Vec::new();
+        };
+
+        let mut detected = Vec::new();
+        for stream in streams
+            .iter_mut()
+            .filter(|s| s.codec_type == "audio" && is_untagged(s.language.as_deref()))
+        {
+            match detector.detect(input_path, stream.index).await {
+                Ok(Some(language)) => {
+                    log_step!(
+                        "Detected language of audio stream #{}: {}",
+                        stream.index,
+                        language
+                    );
+                    stream.language = Some(language);
+                    detected.push(stream.index);
+                }
+                Ok(None) => info!(
+                    "Language of audio stream #{} could not be determined",
+                    stream.index
+                ),
+                Err(e) => warn!(
+                    "Language detection failed for audio stream #{}: {}",
+                    stream.index, e
+                ),
+            }
+        }
+        detected
+    }
+
+    /// `-map` arguments for the video streams, applying the video stream policy.
+    /// The encoded stream(s) come first, followed by any cover art.
+    fn video_map_args(&self, video_streams: &[StreamInfo]) -> Result<Vec<String>> {
+        if video_streams.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let primary = self.primary_position(video_streams)?;
+        let (covers, others): (Vec<usize>, Vec<usize>) = (0..video_streams.len())
+            .filter(|&position| position != primary)
+            .partition(|&position| video_streams[position].disposition.attached_pic);
+
+        let mut encoded = vec![primary];
+        if !others.is_empty() {
+            match self.video_policy {
+                VideoStreamPolicy::First => warn!(
+                    "Source has {} video streams; only 0:v:{} is encoded, {} ignored (streams.video_policy: first)",
+                    others.len() + 1,
+                    primary,
+                    others.len()
+                ),
+                VideoStreamPolicy::All => {
+                    info!(
+                        "Keeping all {} video streams: 0:v:{} is encoded, {} copied (streams.video_policy: all)",
+                        others.len() + 1,
+                        primary,
+                        others.len()
+                    );
+                    encoded.extend(&others);
+                }
+                VideoStreamPolicy::Error => {
+                    return Err(Error::validation(format!(
+                        "Source has {} video streams (streams.video_policy: error)",
+                        others.len() + 1
+                    )))
+                }
+            }
+        }
+
+        Ok(encoded
+            .iter()
+            .chain(covers.iter())
+            .flat_map(|position| ["-map".to_string(), format!("0:v:{}", position)])
+            .collect())
+    }
+
+    /// Copies every mapped video stream but the encoded one (output `v:0`), so
+    /// the filters and x265 settings derived from the primary stream only
+    /// apply to it: under `video_policy: all` the other streams as they are,
+    /// and cover art marked `attached_pic`, which Matroska stores as
+    /// attachments and MP4 as cover art.
+    fn copied_video_codec_args(&self, video_streams: &[StreamInfo]) -> Vec<String> {
+        let primary = self
+            .video_stream
+            .unwrap_or_else(|| primary_video_position(video_streams));
+        let cover_count = video_streams
+            .iter()
+            .enumerate()
+            .filter(|(position, s)| *position != primary && s.disposition.attached_pic)
+            .count();
+        let copied_count = match self.video_policy {
+            VideoStreamPolicy::All => video_streams.len().saturating_sub(1 + cover_count),
+            _ => 0,
+        };
+
+        let mut args = Vec::new();
+        for output_index in 1..=copied_count {
+            args.extend([format!("-c:v:{}", output_index), "copy".to_string()]);
+        }
+        if cover_count > 0 {
+            info!(
+                "Preserving {} cover art stream(s) as attachments",
+                cover_count
+            );
+        }
+        for output_index in 1 + copied_count..1 + copied_count + cover_count {
+            args.extend([
+                format!("-c:v:{}", output_index),
+                "copy".to_string(),
+                format!("-disposition:v:{}", output_index),
+                "attached_pic".to_string(),
+            ]);
+        }
+        args
     }
 
     pub async fn analyze_streams<P: AsRef<Path>>(&self, input_path: P) -> Result<StreamMapping> {
         let input_path = input_path.as_ref();
 
-        info!("Analyzing stream structure: {}", input_path.display());
+        log_step!("Analyzing stream structure: {}", input_path.display());
 
-        let streams = self.get_stream_info(input_path).await?;
+        let mut streams = self.get_stream_info(input_path).await?;
+        let detected = self.detect_languages(input_path, &mut streams).await;
         let chapters = self.get_chapter_info(input_path).await?;
         let metadata = self.get_global_metadata(input_path).await?;
 
@@ -92,9 +506,11 @@ impl StreamPreservation {
             .cloned()
             .collect();
 
-        let mapping_args = self.build_mapping_arguments(&streams)?;
+        let mut mapping_args = self.build_mapping_arguments(&streams)?;
+        mapping_args.extend(detected_language_args(&audio_streams, &detected));
+        let copied_video_args = self.copied_video_codec_args(&video_streams);
 
-        info!(
+        log_step!(
             "Stream analysis complete: {} video, {} audio, {} subtitle, {} data, {} chapters",
             video_streams.len(),
             audio_streams.len(),
@@ -111,6 +527,10 @@ impl StreamPreservation {
             chapters,
             metadata,
             mapping_args,
+            copied_video_args,
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: self.video_stream,
         })
     }
 
@@ -121,13 +541,14 @@ impl StreamPreservation {
     ) -> Result<StreamMapping> {
         let input_path = input_path.as_ref();
 
-        info!(
+        log_step!(
             "Analyzing stream structure with profile '{}': {}",
             profile.name,
             input_path.display()
         );
 
-        let streams = self.get_stream_info(input_path).await?;
+        let mut streams = self.get_stream_info(input_path).await?;
+        let detected = self.detect_languages(input_path, &mut streams).await;
         let chapters = self.get_chapter_info(input_path).await?;
         let metadata = self.get_global_metadata(input_path).await?;
 
@@ -156,18 +577,25 @@ impl StreamPreservation {
             .collect();
 
         // Apply stream filtering using the profile
-        audio_streams = self.filter_audio_streams(audio_streams, &profile.audio)?;
-        subtitle_streams = self.filter_subtitle_streams(subtitle_streams, &profile.subtitle)?;
+        let mut dropped = Vec::new();
+        audio_streams = self.filter_audio_streams(audio_streams, &profile.audio, &mut dropped)?;
+        subtitle_streams =
+            self.filter_subtitle_streams(subtitle_streams, &profile.subtitle, &mut dropped)?;
+        for stream in &mut dropped {
+            stream.rule = format!("stream_selection_profiles.{}.{}", profile.name, stream.rule);
+        }
 
         // Build mapping arguments with filtered streams
-        let mapping_args = self.build_filtered_mapping_arguments(
+        let mut mapping_args = self.build_filtered_mapping_arguments(
             &video_streams,
             &audio_streams,
             &subtitle_streams,
             &data_streams,
         )?;
+        mapping_args.extend(detected_language_args(&audio_streams, &detected));
+        let copied_video_args = self.copied_video_codec_args(&video_streams);
 
-        info!(
+        log_step!(
             "Stream filtering with profile '{}' complete: {} video, {} audio (filtered from {}), {} subtitle (filtered from {}), {} data, {} chapters",
             profile.name,
             video_streams.len(),
@@ -187,10 +615,15 @@ impl StreamPreservation {
             chapters,
             metadata,
             mapping_args,
+            copied_video_args,
+            input_args: Vec::new(),
+            dropped,
+            primary_video: self.video_stream,
         })
     }
 
-    async fn get_stream_info<P: AsRef<Path>>(&self, input_path: P) -> Result<Vec<StreamInfo>> {
+    /// Every stream of `input_path`, in file order.
+    pub async fn get_stream_info<P: AsRef<Path>>(&self, input_path: P) -> Result<Vec<StreamInfo>> {
         let input_path = input_path.as_ref();
 
         // Use the integrated FFmpeg wrapper for better performance
@@ -230,8 +663,10 @@ impl StreamPreservation {
                     .as_str()
                     .unwrap_or("unknown")
                     .to_string();
+                let channels = stream["channels"].as_u64().map(|c| c as u32);
                 let language = stream["tags"]["language"].as_str().map(|s| s.to_string());
                 let title = stream["tags"]["title"].as_str().map(|s| s.to_string());
+                let closed_captions = stream["closed_captions"].as_i64().unwrap_or(0) == 1;
 
                 // Parse disposition
                 let disposition = if let Some(disp) = stream["disposition"].as_object() {
@@ -245,6 +680,7 @@ impl StreamPreservation {
                         dub: disp["dub"].as_i64().unwrap_or(0) == 1,
                         visual_impaired: disp["visual_impaired"].as_i64().unwrap_or(0) == 1,
                         hearing_impaired: disp["hearing_impaired"].as_i64().unwrap_or(0) == 1,
+                        attached_pic: disp["attached_pic"].as_i64().unwrap_or(0) == 1,
                     }
                 } else {
                     StreamDisposition {
@@ -257,6 +693,7 @@ impl StreamPreservation {
                         dub: false,
                         visual_impaired: false,
                         hearing_impaired: false,
+                        attached_pic: false,
                     }
                 };
 
@@ -264,9 +701,11 @@ impl StreamPreservation {
                     index: index as u32,
                     codec_type,
                     codec_name,
+                    channels,
                     language,
                     title,
                     disposition,
+                    closed_captions,
                 });
 
                 debug!(
@@ -410,12 +849,14 @@ impl StreamPreservation {
         let mut args = Vec::new();
 
         // Simple 1:1 mapping: copy everything from input to output
-        // Map video stream (first video stream only for encoding)
-        // Note: When using filter_complex, this will be overridden to map [v] instead
-        if streams.iter().any(|s| s.codec_type == "video") {
-            args.push("-map".to_string());
-            args.push("0:v:0".to_string()); // Use type-based mapping for first video stream
-        }
+        // Map video streams per the video stream policy (type-based specifiers)
+        // Note: When using filter_complex, the primary stream is overridden to map [v] instead
+        let video_streams: Vec<StreamInfo> = streams
+            .iter()
+            .filter(|s| s.codec_type == "video")
+            .cloned()
+            .collect();
+        args.extend(self.video_map_args(&video_streams)?);
 
         // Check if audio streams exist before mapping
         let has_audio = streams.iter().any(|s| s.codec_type == "audio");
@@ -465,11 +906,8 @@ impl StreamPreservation {
     ) -> Result<Vec<String>> {
         let mut args = Vec::new();
 
-        // Map video stream (first video stream only for encoding)
-        if !video_streams.is_empty() {
-            args.push("-map".to_string());
-            args.push("0:v:0".to_string());
-        }
+        // Map video streams per the video stream policy
+        args.extend(self.video_map_args(video_streams)?);
 
         // Map filtered audio streams by their original indices
         for stream in audio_streams {
@@ -518,28 +956,14 @@ impl StreamPreservation {
         &self,
         streams: Vec<StreamInfo>,
         config: &AudioSelectionConfig,
+        dropped: &mut Vec<DroppedStream>,
     ) -> Result<Vec<StreamInfo>> {
         let original_count = streams.len();
         let mut filtered_streams = streams;
 
-        // Filter by languages
-        if let Some(languages) = &config.languages {
-            filtered_streams.retain(|stream| {
-                if let Some(lang) = &stream.language {
-                    languages.iter().any(|pattern_lang| {
-                        lang.to_lowercase().contains(&pattern_lang.to_lowercase())
-                    })
-                } else {
-                    // If no language specified in stream, decide based on config
-                    // For now, include streams with no language info
-                    false
-                }
-            });
-        }
-
         // Filter by codecs
         if let Some(codecs) = &config.codecs {
-            filtered_streams.retain(|stream| {
+            retain_streams(&mut filtered_streams, dropped, "audio.codecs", |stream| {
                 codecs.iter().any(|pattern_codec| {
                     stream
                         .codec_name
@@ -551,61 +975,98 @@ impl StreamPreservation {
 
         // Filter by dispositions
         if let Some(dispositions) = &config.dispositions {
-            filtered_streams.retain(|stream| {
-                dispositions
-                    .iter()
-                    .any(|disposition| match disposition.to_lowercase().as_str() {
-                        "default" => stream.disposition.default,
-                        "forced" => stream.disposition.forced,
-                        "original" => stream.disposition.original,
-                        "dub" => stream.disposition.dub,
-                        "comment" => stream.disposition.comment,
-                        "lyrics" => stream.disposition.lyrics,
-                        "karaoke" => stream.disposition.karaoke,
-                        "visual_impaired" => stream.disposition.visual_impaired,
-                        "hearing_impaired" => stream.disposition.hearing_impaired,
-                        _ => false,
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "audio.dispositions",
+                |stream| {
+                    dispositions.iter().any(|disposition| {
+                        match disposition.to_lowercase().as_str() {
+                            "default" => stream.disposition.default,
+                            "forced" => stream.disposition.forced,
+                            "original" => stream.disposition.original,
+                            "dub" => stream.disposition.dub,
+                            "comment" => stream.disposition.comment,
+                            "lyrics" => stream.disposition.lyrics,
+                            "karaoke" => stream.disposition.karaoke,
+                            "visual_impaired" => stream.disposition.visual_impaired,
+                            "hearing_impaired" => stream.disposition.hearing_impaired,
+                            _ => false,
+                        }
                     })
-            });
+                },
+            );
         }
 
         // Filter by title patterns (regex)
         if let Some(title_patterns) = &config.title_patterns {
-            filtered_streams.retain(|stream| {
-                if let Some(title) = &stream.title {
-                    title_patterns.iter().any(|pattern| {
-                        match Regex::new(pattern) {
-                            Ok(regex) => regex.is_match(title),
-                            Err(_) => {
-                                warn!(
-                                    "Invalid regex pattern for audio title filtering: {}",
-                                    pattern
-                                );
-                                // Fall back to simple substring matching
-                                title.to_lowercase().contains(&pattern.to_lowercase())
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "audio.title_patterns",
+                |stream| {
+                    if let Some(title) = &stream.title {
+                        title_patterns.iter().any(|pattern| {
+                            match Regex::new(pattern) {
+                                Ok(regex) => regex.is_match(title),
+                                Err(_) => {
+                                    warn!(
+                                        "Invalid regex pattern for audio title filtering: {}",
+                                        pattern
+                                    );
+                                    // Fall back to simple substring matching
+                                    title.to_lowercase().contains(&pattern.to_lowercase())
+                                }
                             }
-                        }
-                    })
-                } else {
-                    false
-                }
+                        })
+                    } else {
+                        false
+                    }
+                },
+            );
+        }
+
+        // Filter by classified role
+        if let Some(roles) = &config.roles {
+            retain_streams(&mut filtered_streams, dropped, "audio.roles", |stream| {
+                roles.contains(&classify_audio(stream))
             });
         }
 
         // Exclude commentary tracks
         if config.exclude_commentary {
-            filtered_streams.retain(|stream| {
-                !stream.disposition.comment
-                    && stream.title.as_ref().is_none_or(|title| {
-                        !title.to_lowercase().contains("commentary")
-                            && !title.to_lowercase().contains("director")
-                    })
-            });
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "audio.exclude_commentary",
+                |stream| classify_audio(stream) != AudioRole::Commentary && !is_commentary(stream),
+            );
+        }
+
+        // Filter by languages last, so a fallback chain only falls through to the
+        // next language when no eligible stream of the preferred one is left
+        if let Some(languages) = &config.languages {
+            let selected =
+                select_languages(filtered_streams.clone(), languages, config.selection_mode);
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "audio.languages",
+                |stream| selected.iter().any(|kept| kept.index == stream.index),
+            );
         }
 
         // Limit number of streams
         if let Some(max_streams) = config.max_streams {
-            filtered_streams.truncate(max_streams);
+            if filtered_streams.len() > max_streams {
+                let rule = "audio.max_streams";
+                dropped.extend(
+                    filtered_streams
+                        .split_off(max_streams)
+                        .iter()
+                        .map(|stream| DroppedStream::new(stream, rule)),
+                );
+            }
         }
 
         debug!(
@@ -620,93 +1081,120 @@ impl StreamPreservation {
         &self,
         streams: Vec<StreamInfo>,
         config: &SubtitleSelectionConfig,
+        dropped: &mut Vec<DroppedStream>,
     ) -> Result<Vec<StreamInfo>> {
         let original_count = streams.len();
         let mut filtered_streams = streams;
 
-        // Filter by languages
-        if let Some(languages) = &config.languages {
-            filtered_streams.retain(|stream| {
-                if let Some(lang) = &stream.language {
-                    languages.iter().any(|pattern_lang| {
-                        lang.to_lowercase().contains(&pattern_lang.to_lowercase())
-                    })
-                } else {
-                    false
-                }
-            });
-        }
-
         // Filter by codecs
         if let Some(codecs) = &config.codecs {
-            filtered_streams.retain(|stream| {
-                codecs.iter().any(|pattern_codec| {
-                    stream
-                        .codec_name
-                        .to_lowercase()
-                        .contains(&pattern_codec.to_lowercase())
-                })
-            });
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.codecs",
+                |stream| {
+                    codecs.iter().any(|pattern_codec| {
+                        stream
+                            .codec_name
+                            .to_lowercase()
+                            .contains(&pattern_codec.to_lowercase())
+                    })
+                },
+            );
         }
 
         // Filter by dispositions
         if let Some(dispositions) = &config.dispositions {
-            filtered_streams.retain(|stream| {
-                dispositions
-                    .iter()
-                    .any(|disposition| match disposition.to_lowercase().as_str() {
-                        "default" => stream.disposition.default,
-                        "forced" => stream.disposition.forced,
-                        "original" => stream.disposition.original,
-                        "comment" => stream.disposition.comment,
-                        "hearing_impaired" => stream.disposition.hearing_impaired,
-                        "visual_impaired" => stream.disposition.visual_impaired,
-                        _ => false,
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.dispositions",
+                |stream| {
+                    dispositions.iter().any(|disposition| {
+                        match disposition.to_lowercase().as_str() {
+                            "default" => stream.disposition.default,
+                            "forced" => stream.disposition.forced,
+                            "original" => stream.disposition.original,
+                            "comment" => stream.disposition.comment,
+                            "hearing_impaired" => stream.disposition.hearing_impaired,
+                            "visual_impaired" => stream.disposition.visual_impaired,
+                            _ => false,
+                        }
                     })
-            });
+                },
+            );
         }
 
         // Include forced subtitles only
         if config.include_forced_only {
-            filtered_streams.retain(|stream| stream.disposition.forced);
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.include_forced_only",
+                |stream| stream.disposition.forced,
+            );
         }
 
         // Filter by title patterns (regex)
         if let Some(title_patterns) = &config.title_patterns {
-            filtered_streams.retain(|stream| {
-                if let Some(title) = &stream.title {
-                    title_patterns
-                        .iter()
-                        .any(|pattern| match Regex::new(pattern) {
-                            Ok(regex) => regex.is_match(title),
-                            Err(_) => {
-                                warn!(
-                                    "Invalid regex pattern for subtitle title filtering: {}",
-                                    pattern
-                                );
-                                title.to_lowercase().contains(&pattern.to_lowercase())
-                            }
-                        })
-                } else {
-                    false
-                }
-            });
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.title_patterns",
+                |stream| {
+                    if let Some(title) = &stream.title {
+                        title_patterns
+                            .iter()
+                            .any(|pattern| match Regex::new(pattern) {
+                                Ok(regex) => regex.is_match(title),
+                                Err(_) => {
+                                    warn!(
+                                        "Invalid regex pattern for subtitle title filtering: {}",
+                                        pattern
+                                    );
+                                    title.to_lowercase().contains(&pattern.to_lowercase())
+                                }
+                            })
+                    } else {
+                        false
+                    }
+                },
+            );
         }
 
         // Exclude commentary subtitles
         if config.exclude_commentary {
-            filtered_streams.retain(|stream| {
-                !stream.disposition.comment
-                    && stream.title.as_ref().is_none_or(|title| {
-                        !title.to_lowercase().contains("commentary")
-                            && !title.to_lowercase().contains("director")
-                    })
-            });
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.exclude_commentary",
+                |stream| !is_commentary(stream),
+            );
+        }
+
+        // Filter by languages (see filter_audio_streams for why this runs last)
+        if let Some(languages) = &config.languages {
+            let selected =
+                select_languages(filtered_streams.clone(), languages, config.selection_mode);
+            retain_streams(
+                &mut filtered_streams,
+                dropped,
+                "subtitle.languages",
+                |stream| selected.iter().any(|kept| kept.index == stream.index),
+            );
         }
 
         // Limit number of streams
         if let Some(max_streams) = config.max_streams {
-            filtered_streams.truncate(max_streams);
+            if filtered_streams.len() > max_streams {
+                let rule = "subtitle.max_streams";
+                dropped.extend(
+                    filtered_streams
+                        .split_off(max_streams)
+                        .iter()
+                        .map(|stream| DroppedStream::new(stream, rule)),
+                );
+            }
         }
 
         debug!(
@@ -811,6 +1299,7 @@ mod tests {
                 index: 0,
                 codec_type: "video".to_string(),
                 codec_name: "h264".to_string(),
+                channels: None,
                 language: None,
                 title: None,
                 disposition: StreamDisposition {
@@ -823,13 +1312,16 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
             // Sample audio stream
             StreamInfo {
                 index: 1,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("English Audio".to_string()),
                 disposition: StreamDisposition {
@@ -842,7 +1334,9 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
         ];
 
@@ -867,6 +1361,7 @@ mod tests {
                 index: 0,
                 codec_type: "video".to_string(),
                 codec_name: "h264".to_string(),
+                channels: None,
                 language: None,
                 title: None,
                 disposition: StreamDisposition {
@@ -879,7 +1374,9 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
         ];
 
@@ -902,6 +1399,7 @@ mod tests {
                 index: 1,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("English Audio".to_string()),
                 disposition: StreamDisposition {
@@ -914,12 +1412,15 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
             StreamInfo {
                 index: 2,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("jpn".to_string()),
                 title: Some("Japanese Audio".to_string()),
                 disposition: StreamDisposition {
@@ -932,12 +1433,15 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
             StreamInfo {
                 index: 3,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("ger".to_string()),
                 title: Some("German Audio".to_string()),
                 disposition: StreamDisposition {
@@ -950,7 +1454,9 @@ mod tests {
                     dub: true,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
         ];
 
@@ -959,7 +1465,9 @@ mod tests {
             ..Default::default()
         };
 
-        let filtered = preservation.filter_audio_streams(streams, &config).unwrap();
+        let filtered = preservation
+            .filter_audio_streams(streams, &config, &mut Vec::new())
+            .unwrap();
 
         assert_eq!(filtered.len(), 2);
         assert_eq!(filtered[0].language.as_ref().unwrap(), "eng");
@@ -976,6 +1484,7 @@ mod tests {
                 index: 1,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("English Audio".to_string()),
                 disposition: StreamDisposition {
@@ -988,12 +1497,15 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
             StreamInfo {
                 index: 2,
                 codec_type: "audio".to_string(),
                 codec_name: "aac".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("Director Commentary".to_string()),
                 disposition: StreamDisposition {
@@ -1006,7 +1518,9 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
         ];
 
@@ -1015,10 +1529,27 @@ mod tests {
             ..Default::default()
         };
 
-        let filtered = preservation.filter_audio_streams(streams, &config).unwrap();
+        let mut dropped = Vec::new();
+        let filtered = preservation
+            .filter_audio_streams(streams, &config, &mut dropped)
+            .unwrap();
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].title.as_ref().unwrap(), "English Audio");
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].index, 2);
+        assert_eq!(dropped[0].rule, "audio.exclude_commentary");
+
+        // A 5.1 commentary is dropped by its title alone
+        let mut surround = filtered[0].clone();
+        surround.index = 3;
+        surround.channels = Some(6);
+        surround.title = Some("Director's Commentary".to_string());
+        let filtered = preservation
+            .filter_audio_streams(vec![filtered[0].clone(), surround], &config, &mut dropped)
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(dropped[1].index, 3);
     }
 
     #[test]
@@ -1031,6 +1562,7 @@ mod tests {
                 index: 4,
                 codec_type: "subtitle".to_string(),
                 codec_name: "subrip".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("English Subtitles".to_string()),
                 disposition: StreamDisposition {
@@ -1043,12 +1575,15 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
             StreamInfo {
                 index: 5,
                 codec_type: "subtitle".to_string(),
                 codec_name: "subrip".to_string(),
+                channels: None,
                 language: Some("eng".to_string()),
                 title: Some("English Forced".to_string()),
                 disposition: StreamDisposition {
@@ -1061,7 +1596,9 @@ mod tests {
                     dub: false,
                     visual_impaired: false,
                     hearing_impaired: false,
+                    attached_pic: false,
                 },
+                closed_captions: false,
             },
         ];
 
@@ -1071,11 +1608,253 @@ mod tests {
         };
 
         let filtered = preservation
-            .filter_subtitle_streams(streams, &config)
+            .filter_subtitle_streams(streams, &config, &mut Vec::new())
             .unwrap();
 
         assert_eq!(filtered.len(), 1);
         assert_eq!(filtered[0].title.as_ref().unwrap(), "English Forced");
         assert!(filtered[0].disposition.forced);
     }
+
+    fn subtitle_stream(index: u32, codec_name: &str) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: "subtitle".to_string(),
+            codec_name: codec_name.to_string(),
+            channels: None,
+            language: Some("eng".to_string()),
+            title: None,
+            disposition: StreamDisposition {
+                default: false,
+                forced: false,
+                comment: false,
+                lyrics: false,
+                karaoke: false,
+                original: false,
+                dub: false,
+                visual_impaired: false,
+                hearing_impaired: false,
+                attached_pic: false,
+            },
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_adapt_for_mp4_drops_bitmap_subtitles_and_attachments() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let preservation = StreamPreservation::new(ffmpeg);
+
+        let subtitle_streams = vec![
+            subtitle_stream(2, "subrip"),
+            subtitle_stream(3, "hdmv_pgs_subtitle"),
+        ];
+        let mapping_args = preservation
+            .build_filtered_mapping_arguments(&[], &[], &subtitle_streams, &[])
+            .unwrap();
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams,
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args,
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        mapping.adapt_for_container(OutputContainer::Mp4);
+
+        assert_eq!(mapping.subtitle_streams.len(), 1);
+        assert_eq!(
+            mapping.mapping_args,
+            vec!["-map", "0:2", "-c:s", "mov_text"]
+        );
+        assert_eq!(mapping.dropped.len(), 1);
+        assert_eq!(mapping.dropped[0].index, 3);
+    }
+
+    #[test]
+    fn test_adapt_for_mkv_is_noop() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let preservation = StreamPreservation::new(ffmpeg);
+        let mapping_args = preservation
+            .build_mapping_arguments(&[subtitle_stream(0, "hdmv_pgs_subtitle")])
+            .unwrap();
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: vec![subtitle_stream(0, "hdmv_pgs_subtitle")],
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: mapping_args.clone(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        mapping.adapt_for_container(OutputContainer::Mkv);
+
+        assert_eq!(mapping.mapping_args, mapping_args);
+    }
+
+    fn video_stream(index: u32, attached_pic: bool) -> StreamInfo {
+        let mut stream = subtitle_stream(index, if attached_pic { "mjpeg" } else { "h264" });
+        stream.codec_type = "video".to_string();
+        stream.language = None;
+        stream.disposition.attached_pic = attached_pic;
+        stream
+    }
+
+    #[test]
+    fn test_video_policy_with_cover_art() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        // Cover first (as muxed by some taggers), then two real video streams
+        let videos = vec![
+            video_stream(0, true),
+            video_stream(1, false),
+            video_stream(2, false),
+        ];
+
+        let first = StreamPreservation::new(ffmpeg.clone());
+        assert_eq!(
+            first.video_map_args(&videos).unwrap(),
+            vec!["-map", "0:v:1", "-map", "0:v:0"]
+        );
+        assert_eq!(
+            first.copied_video_codec_args(&videos),
+            vec!["-c:v:1", "copy", "-disposition:v:1", "attached_pic"]
+        );
+
+        let all = StreamPreservation::new(ffmpeg.clone()).with_video_policy(VideoStreamPolicy::All);
+        assert_eq!(
+            all.video_map_args(&videos).unwrap(),
+            vec!["-map", "0:v:1", "-map", "0:v:2", "-map", "0:v:0"]
+        );
+        // Only the primary stream is encoded; the second one is copied as it is
+        assert_eq!(
+            all.copied_video_codec_args(&videos),
+            vec![
+                "-c:v:1",
+                "copy",
+                "-c:v:2",
+                "copy",
+                "-disposition:v:2",
+                "attached_pic"
+            ]
+        );
+        assert_eq!(
+            all.copied_video_codec_args(&videos[1..]),
+            vec!["-c:v:1", "copy"]
+        );
+
+        // --video-stream picks the encoded stream; cover art is still kept
+        let chosen = StreamPreservation::new(ffmpeg.clone()).with_video_stream(Some(2));
+        assert_eq!(
+            chosen.video_map_args(&videos).unwrap(),
+            vec!["-map", "0:v:2", "-map", "0:v:0"]
+        );
+        let missing = StreamPreservation::new(ffmpeg.clone()).with_video_stream(Some(3));
+        assert!(missing.video_map_args(&videos).is_err());
+
+        let error = StreamPreservation::new(ffmpeg).with_video_policy(VideoStreamPolicy::Error);
+        assert!(error.video_map_args(&videos).is_err());
+        // A cover alone does not count as a second video stream
+        assert!(error.video_map_args(&videos[..2]).is_ok());
+
+        let mapping = StreamMapping {
+            video_streams: videos,
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            mapping_args: Vec::new(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+        assert_eq!(mapping.primary_video_specifier(), "0:v:1");
+        let mapping = StreamMapping {
+            primary_video: Some(2),
+            ..mapping
+        };
+        assert_eq!(mapping.primary_video_specifier(), "0:v:2");
+    }
+
+    #[test]
+    fn test_detected_language_args() {
+        let mut first = subtitle_stream(1, "aac");
+        first.codec_type = "audio".to_string();
+        let mut detected = first.clone();
+        detected.index = 3;
+        detected.language = Some("jpn".to_string());
+
+        assert_eq!(
+            detected_language_args(&[first, detected], &[3]),
+            vec!["-metadata:s:a:1", "language=jpn"]
+        );
+    }
+
+    #[test]
+    fn test_language_fallback_chain() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let preservation = StreamPreservation::new(ffmpeg);
+        let with_language = |index: u32, language: Option<&str>| {
+            let mut stream = subtitle_stream(index, "aac");
+            stream.codec_type = "audio".to_string();
+            stream.language = language.map(str::to_string);
+            stream
+        };
+        let indices = |streams: &[StreamInfo]| streams.iter().map(|s| s.index).collect::<Vec<_>>();
+        let streams = vec![
+            with_language(1, Some("eng")),
+            with_language(2, None),
+            with_language(3, Some("eng")),
+            with_language(4, Some("jpn")),
+        ];
+
+        let mut config = AudioSelectionConfig {
+            languages: Some(vec!["de".to_string(), "en".to_string(), "und".to_string()]),
+            selection_mode: LanguageSelectionMode::FirstMatch,
+            ..Default::default()
+        };
+        let filtered = preservation
+            .filter_audio_streams(streams.clone(), &config, &mut Vec::new())
+            .unwrap();
+        assert_eq!(indices(&filtered), vec![1, 3]);
+
+        let german = vec![with_language(5, Some("ger")), streams[0].clone()];
+        let filtered = preservation
+            .filter_audio_streams(german, &config, &mut Vec::new())
+            .unwrap();
+        assert_eq!(indices(&filtered), vec![5]);
+
+        config.selection_mode = LanguageSelectionMode::AllMatches;
+        let filtered = preservation
+            .filter_audio_streams(streams, &config, &mut Vec::new())
+            .unwrap();
+        assert_eq!(indices(&filtered), vec![1, 2, 3]);
+
+        // Subtitles: nothing in German or English, fall back to untagged
+        let mut untagged = subtitle_stream(7, "subrip");
+        untagged.language = None;
+        let subtitles = vec![subtitle_stream(6, "subrip"), untagged];
+        let subtitle_config = SubtitleSelectionConfig {
+            languages: Some(vec!["de".to_string(), "und".to_string()]),
+            selection_mode: LanguageSelectionMode::FirstMatch,
+            ..Default::default()
+        };
+        let filtered = preservation
+            .filter_subtitle_streams(subtitles, &subtitle_config, &mut Vec::new())
+            .unwrap();
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].index, 7);
+    }
 }
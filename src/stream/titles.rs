@@ -0,0 +1,185 @@
+//! Consistent audio track titles (`audio.title_template` of a stream
+//! selection profile), e.g. "English – Opus 5.1", rendered from the
+//! language, output codec and channel count of each track once filtering
+//! and transcoding have settled what the output carries.
+
+use super::preservation::{StreamInfo, StreamMapping, LANGUAGE_ALIASES};
+
+/// English names of the languages in [`LANGUAGE_ALIASES`], by ISO 639-1 code.
+const LANGUAGE_NAMES: &[(&str, &str)] = &[
+    ("de", "German"),
+    ("en", "English"),
+    ("es", "Spanish"),
+    ("fr", "French"),
+    ("it", "Italian"),
+    ("ja", "Japanese"),
+    ("ko", "Korean"),
+    ("nl", "Dutch"),
+    ("pl", "Polish"),
+    ("pt", "Portuguese"),
+    ("ru", "Russian"),
+    ("sv", "Swedish"),
+    ("zh", "Chinese"),
+];
+
+/// Display name of a language tag; other tags are shown as they are, and
+/// untagged tracks as "Unknown".
+pub fn language_name(language: Option<&str>) -> String {
+    let Some(language) = language.filter(|l| !l.is_empty() && *l != "und") else {
+        return "Unknown".to_string();
+    };
+    let lower = language.to_lowercase();
+    LANGUAGE_ALIASES
+        .iter()
+        .find(|(short, codes)| *short == lower || codes.contains(&lower.as_str()))
+        .and_then(|(short, _)| LANGUAGE_NAMES.iter().find(|(code, _)| code == short))
+        .map_or_else(|| language.to_string(), |(_, name)| name.to_string())
+}
+
+/// Display name of an FFmpeg audio codec or encoder name.
+pub fn codec_name(codec: &str) -> String {
+    let name = match codec.to_lowercase().as_str() {
+        "opus" | "libopus" => "Opus",
+        "aac" | "libfdk_aac" => "AAC",
+        "ac3" => "AC3",
+        "eac3" => "E-AC3",
+        "dts" => "DTS",
+        "truehd" => "TrueHD",
+        "flac" => "FLAC",
+        "mp3" | "libmp3lame" => "MP3",
+        "vorbis" | "libvorbis" => "Vorbis",
+        "alac" => "ALAC",
+        codec if codec.starts_with("pcm_") => "PCM",
+        _ => return codec.to_uppercase(),
+    };
+    name.to_string()
+}
+
+/// Channel layout of a channel count: "2.0", "5.1", "7.1", ...
+pub fn channel_layout(channels: Option<u32>) -> String {
+    match channels {
+        Some(channels @ (6 | 8)) => format!("{}.1", channels - 1),
+        Some(channels) => format!("{}.0", channels),
+        None => String::new(),
+    }
+}
+
+/// Fills `{language}`, `{codec}`, `{channels}` and `{title}` (the source
+/// title) of `template` for `stream` encoded as `codec`.
+pub fn render_audio_title(template: &str, stream: &StreamInfo, codec: &str) -> String {
+    template
+        .replace("{language}", &language_name(stream.language.as_deref()))
+        .replace("{codec}", &codec_name(codec))
+        .replace("{channels}", &channel_layout(stream.channels))
+        .replace("{title}", stream.title.as_deref().unwrap_or(""))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl StreamMapping {
+    /// Codec of the audio stream at `output_index` in the output: the last
+    /// `-c:a:N` or `-c:a` set for it, or the source codec when copied.
+    pub fn output_audio_codec(&self, output_index: usize) -> Option<&str> {
+        let codec = self
+            .output_codec("a", output_index)
+            .filter(|codec| *codec != "copy");
+        codec.or_else(|| {
+            self.audio_streams
+                .get(output_index)
+                .map(|stream| stream.codec_name.as_str())
+        })
+    }
+
+    /// Titles every output audio stream from `template`. Returns the titles
+    /// set, in output order.
+    pub fn retitle_audio(&mut self, template: &str) -> Vec<String> {
+        let titles: Vec<String> = (0..self.audio_streams.len())
+            .filter_map(|output_index| {
+                let codec = self.output_audio_codec(output_index)?;
+                Some(render_audio_title(
+                    template,
+                    &self.audio_streams[output_index],
+                    codec,
+                ))
+            })
+            .collect();
+        for (output_index, title) in titles.iter().enumerate() {
+            self.mapping_args.extend([
+                format!("-metadata:s:a:{}", output_index),
+                format!("title={}", title),
+            ]);
+        }
+        titles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stream::preservation::StreamDisposition;
+
+    fn audio(index: u32, codec: &str, channels: u32, language: Option<&str>) -> StreamInfo {
+        StreamInfo {
+            index,
+            codec_type: "audio".to_string(),
+            codec_name: codec.to_string(),
+            channels: Some(channels),
+            language: language.map(str::to_string),
+            title: Some("Surround".to_string()),
+            disposition: StreamDisposition::default(),
+            closed_captions: false,
+        }
+    }
+
+    #[test]
+    fn test_retitle_audio() {
+        let template = "{language} – {codec} {channels}";
+        let mut mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: vec![
+                audio(1, "truehd", 8, Some("eng")),
+                audio(2, "ac3", 2, Some("jpn")),
+                audio(3, "dts", 6, None),
+            ],
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            // The first stream was transcoded for a device
+            mapping_args: ["-map", "0:a", "-c:a", "copy", "-c:a:0", "libopus"]
+                .map(String::from)
+                .to_vec(),
+            copied_video_args: Vec::new(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        assert_eq!(
+            mapping.retitle_audio(template),
+            [
+                "English – Opus 7.1",
+                "Japanese – AC3 2.0",
+                "Unknown – DTS 5.1"
+            ]
+        );
+        assert_eq!(
+            &mapping.mapping_args[6..8],
+            ["-metadata:s:a:0", "title=English – Opus 7.1"]
+        );
+        assert_eq!(mapping.mapping_args.len(), 12);
+
+        // Unknown languages are kept as tagged; empty placeholders leave no gaps
+        let mut stream = audio(4, "pcm_s24le", 1, Some("fin"));
+        assert_eq!(
+            render_audio_title("{language} {codec} {channels} ({title})", &stream, "aac"),
+            "fin AAC 1.0 (Surround)"
+        );
+        stream.channels = None;
+        assert_eq!(
+            render_audio_title("{language} {channels} {codec}", &stream, "pcm_s24le"),
+            "fin PCM"
+        );
+    }
+}
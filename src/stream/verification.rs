@@ -0,0 +1,207 @@
+use crate::stream::preservation::{StreamInfo, StreamMapping};
+use crate::utils::{Error, FfmpegWrapper, Result};
+use std::path::Path;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+/// MD5 comparison of one stream that was copied from the source into the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamChecksum {
+    pub stream_type: &'static str,
+    pub source_index: u32,
+    pub output_specifier: String,
+    pub source_md5: String,
+    pub output_md5: Option<String>,
+}
+
+impl StreamChecksum {
+    pub fn matches(&self) -> bool {
+        self.output_md5.as_deref() == Some(self.source_md5.as_str())
+    }
+}
+
+impl StreamMapping {
+    /// Codec set for the output stream `output_index` of a type (`type_specifier`,
+    /// e.g. `a`): the last `-c:a:N` or `-c:a` for it, as FFmpeg applies them.
+    pub fn output_codec(&self, type_specifier: &str, output_index: usize) -> Option<&str> {
+        let type_option = format!("-c:{}", type_specifier);
+        let stream_option = format!("{}:{}", type_option, output_index);
+        self.mapping_args
+            .windows(2)
+            .rev()
+            .find(|pair| pair[0] == type_option || pair[0] == stream_option)
+            .map(|pair| pair[1].as_str())
+    }
+
+    /// Whether that output stream is copied from the source unchanged.
+    pub fn copies_stream(&self, type_specifier: &str, output_index: usize) -> bool {
+        self.output_codec(type_specifier, output_index) == Some("copy")
+    }
+}
+
+/// Proves passthrough integrity by hashing copied audio/subtitle packets in the
+/// source and the output (`ffmpeg -c copy -f hash -hash md5`).
+pub struct StreamVerifier {
+    ffmpeg: FfmpegWrapper,
+}
+
+impl StreamVerifier {
+    pub fn new(ffmpeg: FfmpegWrapper) -> Self {
+        Self { ffmpeg }
+    }
+
+    pub async fn verify_copied_streams<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        output_path: P,
+        mapping: &StreamMapping,
+    ) -> Result<Vec<StreamChecksum>> {
+        let mut results = Vec::new();
+
+        let groups: [(&'static str, &str, &[StreamInfo]); 2] = [
+            ("audio", "a", &mapping.audio_streams),
+            ("subtitle", "s", &mapping.subtitle_streams),
+        ];
+
+        for (stream_type, type_specifier, streams) in groups {
+            // Output streams of a type appear in the same order as they were mapped
+            for (output_position, stream) in streams.iter().enumerate() {
+                let output_specifier = format!("0:{}:{}", type_specifier, output_position);
+                if !mapping.copies_stream(type_specifier, output_position) {
+                    debug!(
+                        "Skipping verification of {} stream {}: not copied",
+                        stream_type, output_specifier
+                    );
+                    continue;
+                }
+                let source_md5 = self
+                    .stream_md5(input_path.as_ref(), &format!("0:{}", stream.index))
+                    .await?;
+                let output_md5 = match self
+                    .stream_md5(output_path.as_ref(), &output_specifier)
+                    .await
+                {
+                    Ok(md5) => Some(md5),
+                    Err(e) => {
+                        warn!("Could not hash output stream {}: {}", output_specifier, e);
+                        None
+                    }
+                };
+
+                results.push(StreamChecksum {
+                    stream_type,
+                    source_index: stream.index,
+                    output_specifier,
+                    source_md5,
+                    output_md5,
+                });
+            }
+        }
+
+        let mismatches = results.iter().filter(|r| !r.matches()).count();
+        if mismatches == 0 {
+            info!(
+                "Stream verification: {} copied stream(s) bit-exact",
+                results.len()
+            );
+        } else {
+            warn!(
+                "Stream verification: {} of {} copied stream(s) differ from the source",
+                mismatches,
+                results.len()
+            );
+        }
+
+        Ok(results)
+    }
+
+    async fn stream_md5(&self, path: &Path, map_specifier: &str) -> Result<String> {
+        let output = Command::new(self.ffmpeg.get_ffmpeg_path())
+            .args(["-hide_banner", "-v", "error", "-i"])
+            .arg(path)
+            .args([
+                "-map",
+                map_specifier,
+                "-c",
+                "copy",
+                "-f",
+                "hash",
+                "-hash",
+                "md5",
+                "-",
+            ])
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            return Err(Error::ffmpeg(format!(
+                "Hashing {} of {} failed: {}",
+                map_specifier,
+                path.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        parse_hash_output(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+fn parse_hash_output(stdout: &str) -> Result<String> {
+    stdout
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("MD5="))
+        .map(str::to_string)
+        .ok_or_else(|| Error::parse(format!("Unexpected hash output: {}", stdout.trim())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_hash_output() {
+        assert_eq!(
+            parse_hash_output("MD5=9e107d9d372bb6826bd81d3542a419d6\n").unwrap(),
+            "9e107d9d372bb6826bd81d3542a419d6"
+        );
+        assert!(parse_hash_output("").is_err());
+    }
+
+    #[test]
+    fn test_copies_stream() {
+        let mapping = StreamMapping {
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            data_streams: Vec::new(),
+            chapters: Vec::new(),
+            metadata: Vec::new(),
+            copied_video_args: Vec::new(),
+            mapping_args: ["-map", "0:a", "-c:a", "aac", "-c:s", "copy"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            input_args: Vec::new(),
+            dropped: Vec::new(),
+            primary_video: None,
+        };
+
+        assert!(!mapping.copies_stream("a", 0));
+        assert!(mapping.copies_stream("s", 0));
+
+        // A later per-stream codec (device fallback, loudnorm) overrides the
+        // type-level copy for that stream only
+        let mapping = StreamMapping {
+            mapping_args: ["-map", "0:a", "-c:a", "copy", "-c:a:1", "eac3"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ..mapping
+        };
+        assert!(mapping.copies_stream("a", 0));
+        assert!(!mapping.copies_stream("a", 1));
+        assert_eq!(mapping.output_codec("a", 1), Some("eac3"));
+        assert!(mapping.copies_stream("a", 2));
+        assert!(!mapping.copies_stream("s", 0));
+    }
+}
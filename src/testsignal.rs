@@ -0,0 +1,417 @@
+/// Synthetic Test Sources
+///
+/// Generates short letterboxed clips (testsrc2 + sine) with chosen HDR
+/// characteristics, so the metadata pipeline can be exercised without
+/// copyrighted content:
+/// - HDR10: BT.2020/PQ signalling with mastering display and content light levels
+/// - HDR10+: per-frame ST 2094-40 metadata injected with hdr10plus_tool
+/// - Dolby Vision 8.1: an RPU generated and injected with dovi_tool
+///
+/// Sources with injected metadata are encoded as raw HEVC first and muxed
+/// with mkvmerge, which writes the Dolby Vision configuration record.
+use crate::config::Config;
+use crate::dolby_vision::DoviTool;
+use crate::hdr10plus::{
+    BezierCurveData, Hdr10PlusMetadata, Hdr10PlusTool, JsonInfo, LuminanceDistributions,
+    LuminanceParameters, SceneMetadata, ToolInfo,
+};
+use crate::mkvmerge::MkvMergeTool;
+use crate::utils::{Error, Result};
+use serde_json::json;
+use std::path::Path;
+use tokio::process::Command;
+use tracing::info;
+
+/// Source geometry: a 1280x720 frame with a 1280x536 picture letterboxed in
+/// the middle, so crop detection has something to find.
+pub const SOURCE_WIDTH: u32 = 1280;
+pub const SOURCE_HEIGHT: u32 = 720;
+pub const PICTURE_HEIGHT: u32 = 536;
+pub const FRAME_RATE: u32 = 24;
+
+/// Mastering display and content light levels signalled by HDR sources
+const MASTER_DISPLAY: &str = "G(13250,34500)B(7500,3000)R(34000,16000)WP(15635,16450)L(10000000,1)";
+const MAX_CLL: (u32, u32) = (1000, 400);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestSignal {
+    pub hdr10: bool,
+    pub hdr10plus: bool,
+    pub dv81: bool,
+    /// Seconds
+    pub duration: f64,
+}
+
+impl Default for TestSignal {
+    fn default() -> Self {
+        Self {
+            hdr10: false,
+            hdr10plus: false,
+            dv81: false,
+            duration: 10.0,
+        }
+    }
+}
+
+impl TestSignal {
+    /// HDR10+ and Dolby Vision 8.1 both sit on an HDR10 base layer.
+    pub fn is_hdr(&self) -> bool {
+        self.hdr10 || self.hdr10plus || self.dv81
+    }
+
+    fn needs_injection(&self) -> bool {
+        self.hdr10plus || self.dv81
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        (self.duration * f64::from(FRAME_RATE)).round() as u32
+    }
+
+    /// e.g. `HDR10 + HDR10+ + Dolby Vision 8.1`, or `SDR`
+    pub fn describe(&self) -> String {
+        if !self.is_hdr() {
+            return "SDR".to_string();
+        }
+        let mut parts = vec!["HDR10"];
+        if self.hdr10plus {
+            parts.push("HDR10+");
+        }
+        if self.dv81 {
+            parts.push("Dolby Vision 8.1");
+        }
+        parts.join(" + ")
+    }
+
+    /// File name for a generated source, e.g. `test_hdr10_dv81.mkv`
+    pub fn default_file_name(&self) -> String {
+        let mut name = "test".to_string();
+        if !self.is_hdr() {
+            name.push_str("_sdr");
+        } else {
+            name.push_str("_hdr10");
+            if self.hdr10plus {
+                name.push_str("_hdr10plus");
+            }
+            if self.dv81 {
+                name.push_str("_dv81");
+            }
+        }
+        name.push_str(".mkv");
+        name
+    }
+}
+
+pub struct TestSignalGenerator<'a> {
+    config: &'a Config,
+}
+
+impl<'a> TestSignalGenerator<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self { config }
+    }
+
+    /// Writes `signal` to `output`, keeping intermediate files in `work_dir`.
+    pub async fn generate(
+        &self,
+        signal: &TestSignal,
+        output: &Path,
+        work_dir: &Path,
+    ) -> Result<()> {
+        info!(
+            "Generating {:.0}s {} test source: {}",
+            signal.duration,
+            signal.describe(),
+            output.display()
+        );
+        if !signal.needs_injection() {
+            return self
+                .run_ffmpeg(&build_source_args(output, signal), "test source")
+                .await;
+        }
+
+        let mut video = work_dir.join("testsignal_base.hevc");
+        self.run_ffmpeg(&build_source_args(&video, signal), "test source")
+            .await?;
+
+        if signal.hdr10plus {
+            let tool_config = self.config.tools.hdr10plus_tool.clone().ok_or_else(|| {
+                Error::validation("HDR10+ test sources need tools.hdr10plus_tool")
+            })?;
+            let metadata_json = work_dir.join("testsignal_hdr10plus.json");
+            tokio::fs::write(
+                &metadata_json,
+                serde_json::to_string_pretty(&hdr10plus_metadata(signal.frame_count()))?,
+            )
+            .await?;
+            let injected = work_dir.join("testsignal_hdr10plus.hevc");
+            Hdr10PlusTool::new(tool_config)
+                .inject_metadata(&video, &metadata_json, &injected)
+                .await?;
+            video = injected;
+        }
+
+        if signal.dv81 {
+            let tool_config = self.config.tools.dovi_tool.clone().ok_or_else(|| {
+                Error::validation("Dolby Vision test sources need tools.dovi_tool")
+            })?;
+            let dovi_tool = DoviTool::new(tool_config.to_tool_config());
+            let generate_json = work_dir.join("testsignal_rpu.json");
+            tokio::fs::write(
+                &generate_json,
+                serde_json::to_string_pretty(&rpu_generate_config(signal.frame_count()))?,
+            )
+            .await?;
+            let rpu = work_dir.join("testsignal_rpu.bin");
+            dovi_tool.generate_rpu(&generate_json, &rpu).await?;
+            let injected = work_dir.join("testsignal_dv81.hevc");
+            dovi_tool.inject_rpu(&video, &rpu, &injected).await?;
+            video = injected;
+        }
+
+        let audio = work_dir.join("testsignal_audio.mka");
+        self.run_ffmpeg(&build_audio_args(&audio, signal.duration), "test audio")
+            .await?;
+        MkvMergeTool::new(self.config.tools.mkvmerge.clone().unwrap_or_default())
+            .remux_hevc_with_streams(&video, &audio, output, FRAME_RATE as f32)
+            .await
+    }
+
+    async fn run_ffmpeg(&self, args: &[String], what: &str) -> Result<()> {
+        let output = Command::new(&self.config.tools.ffmpeg)
+            .args(args)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::ffmpeg(format!(
+                "Failed to synthesize {}: {}",
+                what,
+                stderr.trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// FFmpeg arguments that synthesize the letterboxed source. Sources that get
+/// metadata injected are written as raw HEVC without audio.
+pub fn build_source_args(output: &Path, signal: &TestSignal) -> Vec<String> {
+    let pad_y = (SOURCE_HEIGHT - PICTURE_HEIGHT) / 2;
+    let mut args: Vec<String> = vec![
+        "-y".to_string(),
+        "-hide_banner".to_string(),
+        "-loglevel".to_string(),
+        "error".to_string(),
+        "-f".to_string(),
+        "lavfi".to_string(),
+        "-i".to_string(),
+        format!(
+            "testsrc2=size={}x{}:rate={}:duration={}",
+            SOURCE_WIDTH, PICTURE_HEIGHT, FRAME_RATE, signal.duration
+        ),
+    ];
+    if !signal.needs_injection() {
+        args.extend([
+            "-f".to_string(),
+            "lavfi".to_string(),
+            "-i".to_string(),
+            sine_source(signal.duration),
+        ]);
+    }
+    args.extend([
+        "-vf".to_string(),
+        format!("pad={}:{}:0:{}:black", SOURCE_WIDTH, SOURCE_HEIGHT, pad_y),
+        "-c:v".to_string(),
+        "libx265".to_string(),
+        "-preset".to_string(),
+        "ultrafast".to_string(),
+    ]);
+
+    if signal.is_hdr() {
+        args.extend(
+            [
+                "-pix_fmt",
+                "yuv420p10le",
+                "-color_primaries",
+                "bt2020",
+                "-color_trc",
+                "smpte2084",
+                "-colorspace",
+                "bt2020nc",
+                "-x265-params",
+            ]
+            .iter()
+            .map(|s| (*s).to_string()),
+        );
+        args.push(format!(
+            "log-level=error:hdr10=1:repeat-headers=1:colorprim=bt2020:transfer=smpte2084:\
+             colormatrix=bt2020nc:master-display={}:max-cll={},{}",
+            MASTER_DISPLAY, MAX_CLL.0, MAX_CLL.1
+        ));
+    } else {
+        args.extend(
+            ["-pix_fmt", "yuv420p", "-x265-params", "log-level=error"]
+                .iter()
+                .map(|s| (*s).to_string()),
+        );
+    }
+
+    if signal.needs_injection() {
+        args.extend(["-f".to_string(), "hevc".to_string()]);
+    } else {
+        args.extend(
+            ["-c:a", "aac", "-b:a", "128k"]
+                .iter()
+                .map(|s| (*s).to_string()),
+        );
+    }
+    args.push(output.to_string_lossy().to_string());
+    args
+}
+
+fn sine_source(duration: f64) -> String {
+    format!(
+        "sine=frequency=1000:sample_rate=48000:duration={}",
+        duration
+    )
+}
+
+fn build_audio_args(output: &Path, duration: f64) -> Vec<String> {
+    [
+        "-y",
+        "-hide_banner",
+        "-loglevel",
+        "error",
+        "-f",
+        "lavfi",
+        "-i",
+        &sine_source(duration),
+        "-c:a",
+        "aac",
+        "-b:a",
+        "128k",
+        &output.to_string_lossy(),
+    ]
+    .iter()
+    .map(|s| (*s).to_string())
+    .collect()
+}
+
+/// `dovi_tool generate` config for a profile 8.1 RPU per frame, with L6
+/// matching the HDR10 signalling of the base layer.
+pub fn rpu_generate_config(frames: u32) -> serde_json::Value {
+    json!({
+        "cm_version": "V40",
+        "length": frames,
+        "level6": {
+            "max_display_mastering_luminance": 1000,
+            "min_display_mastering_luminance": 1,
+            "max_content_light_level": MAX_CLL.0,
+            "max_frame_average_light_level": MAX_CLL.1,
+        },
+    })
+}
+
+/// HDR10+ metadata in hdr10plus_tool's JSON format: one scene, one entry per
+/// frame, with the scene peak slowly rising so the metadata is dynamic.
+pub fn hdr10plus_metadata(frames: u32) -> serde_json::Value {
+    let scene_info = (0..frames)
+        .map(|frame| {
+            let peak = 4000 + frame * 6000 / frames.max(1);
+            SceneMetadata {
+                scene_id: 0,
+                scene_frame_index: frame,
+                sequence_frame_index: frame,
+                number_of_windows: 1,
+                targeted_system_display_maximum_luminance: 400,
+                bezier_curve_data: BezierCurveData {
+                    knee_point_x: 0,
+                    knee_point_y: 0,
+                    anchors: vec![102, 205, 307, 410, 512, 614, 717, 819, 922],
+                },
+                luminance_parameters: LuminanceParameters {
+                    average_rgb: 1000,
+                    max_scl: vec![peak, peak, peak],
+                    luminance_distributions: Some(LuminanceDistributions {
+                        distribution_index: vec![1, 5, 10, 25, 50, 75, 90, 95, 99],
+                        distribution_values: vec![
+                            0,
+                            10,
+                            50,
+                            200,
+                            600,
+                            1500,
+                            peak * 7 / 10,
+                            peak * 9 / 10,
+                            peak,
+                        ],
+                    }),
+                },
+            }
+        })
+        .collect();
+    let metadata = Hdr10PlusMetadata {
+        json_info: JsonInfo {
+            hdr10plus_profile: "B".to_string(),
+            version: "1.0".to_string(),
+        },
+        scene_info,
+        tool_info: Some(ToolInfo {
+            tool: "ven".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }),
+    };
+
+    let mut value = serde_json::to_value(metadata).unwrap_or_default();
+    value["SceneInfoSummary"] = json!({
+        "SceneFirstFrameIndex": [0],
+        "SceneFrameNumbers": [frames],
+    });
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal(hdr10: bool, dv81: bool) -> TestSignal {
+        TestSignal {
+            hdr10,
+            dv81,
+            duration: 5.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_source_args_sdr() {
+        let args = build_source_args(Path::new("/tmp/src.mkv"), &signal(false, false));
+        assert!(args.contains(&"testsrc2=size=1280x536:rate=24:duration=5".to_string()));
+        assert!(args.contains(&"pad=1280:720:0:92:black".to_string()));
+        assert!(args.contains(&"yuv420p".to_string()));
+        assert!(!args.iter().any(|a| a.contains("smpte2084")));
+        assert_eq!(args.last().unwrap(), "/tmp/src.mkv");
+    }
+
+    #[test]
+    fn test_source_args_hdr() {
+        let args = build_source_args(Path::new("/tmp/src.mkv"), &signal(true, false));
+        assert!(args.contains(&"yuv420p10le".to_string()));
+        assert!(args.contains(&"smpte2084".to_string()));
+        assert!(args.iter().any(|a| a.contains("master-display=")));
+    }
+
+    #[test]
+    fn test_source_args_dolby_vision() {
+        // Injected metadata goes into raw HEVC, audio is muxed afterwards
+        let dv = signal(false, true);
+        assert_eq!(dv.describe(), "HDR10 + Dolby Vision 8.1");
+        assert_eq!(dv.default_file_name(), "test_hdr10_dv81.mkv");
+        assert_eq!(dv.frame_count(), 120);
+        let args = build_source_args(Path::new("/tmp/base.hevc"), &dv);
+        assert!(args.contains(&"smpte2084".to_string()));
+        assert!(!args.iter().any(|a| a.starts_with("sine=")));
+        assert_eq!(args[args.len() - 3..], ["-f", "hevc", "/tmp/base.hevc"]);
+    }
+}
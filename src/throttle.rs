@@ -0,0 +1,193 @@
+use crate::config::types::{ThrottleConfig, ThrottleSource};
+use crate::progress::PauseCondition;
+use crate::utils::{Error, Result};
+use futures::future::BoxFuture;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tracing::{debug, warn};
+
+/// Pauses encoding while a temperature or load reading is above
+/// `pause_above`, resuming once it drops below `resume_below`.
+pub struct Throttle {
+    config: ThrottleConfig,
+    check_interval: Duration,
+    last_check: Option<Instant>,
+    throttled: Option<String>,
+}
+
+impl Throttle {
+    pub fn new(config: ThrottleConfig) -> Self {
+        let check_interval = Duration::from_secs(config.check_interval.max(1));
+        Self {
+            config,
+            check_interval,
+            last_check: None,
+            throttled: None,
+        }
+    }
+
+    fn metric_name(&self) -> &'static str {
+        match self.config.source {
+            ThrottleSource::Temperature => "temperature",
+            ThrottleSource::Load => "load average",
+            ThrottleSource::Command => "throttle metric",
+        }
+    }
+
+    /// Takes one reading from the configured source, without blocking the
+    /// runtime the encode is monitored on.
+    pub async fn read_metric(&self) -> Result<f64> {
+        match self.config.source {
+            ThrottleSource::Temperature => {
+                let content = tokio::fs::read_to_string(&self.config.temperature_path).await?;
+                let millidegrees = parse_number(&content)?;
+                Ok(millidegrees / 1000.0)
+            }
+            ThrottleSource::Load => load_average().await,
+            ThrottleSource::Command => {
+                let command = self.config.command.as_deref().ok_or_else(|| {
+                    Error::validation("Throttle source 'command' requires throttle.command")
+                })?;
+                let output = Command::new("sh")
+                    .arg("-c")
+                    .arg(command)
+                    .kill_on_drop(true)
+                    .output()
+                    .await?;
+                if !output.status.success() {
+                    return Err(Error::tool(format!(
+                        "Throttle command '{}' exited with {}",
+                        command, output.status
+                    )));
+                }
+                parse_number(&String::from_utf8_lossy(&output.stdout))
+            }
+        }
+    }
+
+    /// Applies hysteresis to a reading; returns the pause reason if throttled.
+    fn evaluate(&mut self, value: f64) -> Option<String> {
+        let limit_exceeded = if self.throttled.is_some() {
+            value >= self.config.resume_below
+        } else {
+            value > self.config.pause_above
+        };
+
+        self.throttled = limit_exceeded.then(|| {
+            format!(
+                "{} {:.1} (pauses above {:.1}, resumes below {:.1})",
+                self.metric_name(),
+                value,
+                self.config.pause_above,
+                self.config.resume_below
+            )
+        });
+        self.throttled.clone()
+    }
+}
+
+impl PauseCondition for Throttle {
+    fn pause_reason(&mut self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move {
+            let due = self
+                .last_check
+                .is_none_or(|last| last.elapsed() >= self.check_interval);
+            if !due {
+                return self.throttled.clone();
+            }
+            self.last_check = Some(Instant::now());
+
+            match self.read_metric().await {
+                Ok(value) => {
+                    debug!("Throttle reading: {} = {:.1}", self.metric_name(), value);
+                    self.evaluate(value)
+                }
+                Err(e) => {
+                    // Never stall an encode because the sensor is unreadable
+                    warn!("Throttle reading failed, not throttling: {}", e);
+                    self.throttled = None;
+                    None
+                }
+            }
+        })
+    }
+}
+
+/// The one-minute load average.
+#[cfg(target_os = "linux")]
+async fn load_average() -> Result<f64> {
+    parse_number(&tokio::fs::read_to_string("/proc/loadavg").await?)
+}
+
+/// The one-minute load average; only read from `/proc/loadavg` on Linux.
+#[cfg(not(target_os = "linux"))]
+async fn load_average() -> Result<f64> {
+    Err(Error::validation(
+        "Throttle source 'load' reads /proc/loadavg, which only Linux has; \
+         use source 'command' with a command printing the load instead",
+    ))
+}
+
+/// Parses the first whitespace-separated number, e.g. `48000\n` or `0.52 0.58 0.59 1/389 12345`.
+fn parse_number(content: &str) -> Result<f64> {
+    content
+        .split_whitespace()
+        .next()
+        .and_then(|token| token.parse::<f64>().ok())
+        .ok_or_else(|| Error::parse(format!("Expected a number, got '{}'", content.trim())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hysteresis() {
+        let mut throttle = Throttle::new(ThrottleConfig {
+            enabled: true,
+            pause_above: 85.0,
+            resume_below: 75.0,
+            ..ThrottleConfig::default()
+        });
+
+        assert!(throttle.evaluate(80.0).is_none());
+        assert!(throttle.evaluate(86.0).is_some());
+        // Still above resume threshold: stay paused
+        assert!(throttle.evaluate(80.0).is_some());
+        assert!(throttle.evaluate(74.5).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sources() {
+        assert_eq!(parse_number("48000\n").unwrap(), 48000.0);
+        assert_eq!(parse_number("0.52 0.58 0.59 1/389 12345").unwrap(), 0.52);
+        assert!(parse_number("n/a").is_err());
+
+        let dir = std::env::temp_dir().join(format!("ven_throttle_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let sensor = dir.join("temp");
+        std::fs::write(&sensor, "91500\n").unwrap();
+
+        let mut throttle = Throttle::new(ThrottleConfig {
+            enabled: true,
+            temperature_path: sensor.display().to_string(),
+            ..ThrottleConfig::default()
+        });
+        assert_eq!(throttle.read_metric().await.unwrap(), 91.5);
+        assert!(throttle
+            .pause_reason()
+            .await
+            .unwrap()
+            .contains("temperature 91.5"));
+
+        let command = Throttle::new(ThrottleConfig {
+            enabled: true,
+            source: ThrottleSource::Command,
+            command: Some("echo 42.5".to_string()),
+            ..ThrottleConfig::default()
+        });
+        assert_eq!(command.read_metric().await.unwrap(), 42.5);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,312 @@
+//! External binaries the pipeline runs.
+//!
+//! Each tool implements [`ExternalTool`], which says how to ask it for its
+//! version and which version the pipeline needs. [`probe`] checks one tool and
+//! [`check_all`] every configured one; `tools check`, the self-test and the
+//! availability checks of the tool wrappers all go through here.
+
+mod version;
+
+pub use version::Version;
+
+use crate::config::ToolsConfig;
+use crate::dolby_vision::DoviTool;
+use crate::hdr10plus::Hdr10PlusTool;
+use crate::mkvmerge::MkvMergeTool;
+use crate::utils::{Error, Result};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::debug;
+
+/// How long a tool may take to print its version
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `-stats_period`, used for progress reporting, arrived in FFmpeg 4.4
+const MIN_FFMPEG: Version = Version::new(4, 4, 0);
+
+/// An external binary the pipeline depends on.
+pub trait ExternalTool: Send + Sync {
+    /// Name in reports and errors
+    fn name(&self) -> &str;
+
+    /// Binary to run
+    fn path(&self) -> &str;
+
+    /// Arguments that make the tool print its version
+    fn version_args(&self) -> &[&str] {
+        &["--version"]
+    }
+
+    /// Oldest version the pipeline works with; `None` accepts any
+    fn min_version(&self) -> Option<Version> {
+        None
+    }
+
+    /// Reads the version from the tool's version output.
+    fn parse_version(&self, output: &str) -> Option<Version> {
+        output
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .and_then(Version::find)
+    }
+}
+
+/// A tool the pipeline runs directly, without a wrapper of its own.
+#[derive(Debug, Clone)]
+pub struct Binary {
+    name: &'static str,
+    path: String,
+    version_args: &'static [&'static str],
+    min_version: Option<Version>,
+}
+
+impl Binary {
+    pub fn ffmpeg(path: impl Into<String>) -> Self {
+        Self {
+            name: "ffmpeg",
+            path: path.into(),
+            version_args: &["-version"],
+            min_version: Some(MIN_FFMPEG),
+        }
+    }
+
+    pub fn ffprobe(path: impl Into<String>) -> Self {
+        Self {
+            name: "ffprobe",
+            ..Self::ffmpeg(path)
+        }
+    }
+
+    pub fn mkvpropedit(path: impl Into<String>) -> Self {
+        Self {
+            name: "mkvpropedit",
+            path: path.into(),
+            version_args: &["--version"],
+            min_version: None,
+        }
+    }
+}
+
+impl ExternalTool for Binary {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn version_args(&self) -> &[&str] {
+        self.version_args
+    }
+
+    fn min_version(&self) -> Option<Version> {
+        self.min_version
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolStatus {
+    /// Runs and is new enough; the version is `None` when it could not be read
+    Available(Option<Version>),
+    TooOld {
+        found: Version,
+        required: Version,
+    },
+    /// Missing, not executable, or failing to report its version
+    Unavailable(String),
+    NotConfigured,
+}
+
+/// Result of checking one tool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolProbe {
+    pub name: String,
+    pub path: Option<String>,
+    pub status: ToolStatus,
+}
+
+impl ToolProbe {
+    fn not_configured(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            path: None,
+            status: ToolStatus::NotConfigured,
+        }
+    }
+
+    pub fn is_usable(&self) -> bool {
+        matches!(self.status, ToolStatus::Available(_))
+    }
+
+    /// One-line description for reports.
+    pub fn detail(&self) -> String {
+        let path = self.path.as_deref().unwrap_or_default();
+        match &self.status {
+            ToolStatus::Available(Some(version)) => format!("{} (version {})", path, version),
+            ToolStatus::Available(None) => format!("{} (version unknown)", path),
+            ToolStatus::TooOld { found, required } => format!(
+                "{}: version {} is older than the required {}",
+                path, found, required
+            ),
+            ToolStatus::Unavailable(reason) => format!("{}: {}", path, reason),
+            ToolStatus::NotConfigured => "not configured".to_string(),
+        }
+    }
+
+    /// The tool's version if it is usable, otherwise why not.
+    pub fn into_result(self) -> Result<Option<Version>> {
+        match self.status {
+            ToolStatus::Available(version) => Ok(version),
+            ToolStatus::TooOld { found, required } => Err(Error::tool(format!(
+                "{} {} is older than the required {}",
+                self.name, found, required
+            ))),
+            ToolStatus::Unavailable(reason) => Err(Error::tool(format!(
+                "{} not available: {}",
+                self.name, reason
+            ))),
+            ToolStatus::NotConfigured => {
+                Err(Error::tool(format!("{} is not configured", self.name)))
+            }
+        }
+    }
+}
+
+/// Runs the tool's version query and checks the version against its minimum.
+pub async fn probe<T: ExternalTool + ?Sized>(tool: &T) -> ToolProbe {
+    let args = tool.version_args();
+    debug!("Checking {} at: {}", tool.name(), tool.path());
+
+    let output = tokio::time::timeout(
+        PROBE_TIMEOUT,
+        Command::new(tool.path())
+            .args(args)
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await;
+
+    let status = match output {
+        Err(_) => ToolStatus::Unavailable(format!(
+            "no answer to '{}' within {} seconds",
+            args.join(" "),
+            PROBE_TIMEOUT.as_secs()
+        )),
+        Ok(Err(e)) => ToolStatus::Unavailable(format!("failed to run: {}", e)),
+        Ok(Ok(output)) if !output.status.success() => ToolStatus::Unavailable(format!(
+            "'{}' exited with {}",
+            args.join(" "),
+            output.status
+        )),
+        Ok(Ok(output)) => {
+            // Some tools print their version to stderr
+            let version = tool
+                .parse_version(&String::from_utf8_lossy(&output.stdout))
+                .or_else(|| tool.parse_version(&String::from_utf8_lossy(&output.stderr)));
+            match (version, tool.min_version()) {
+                (Some(found), Some(required)) if found < required => {
+                    ToolStatus::TooOld { found, required }
+                }
+                (version, _) => ToolStatus::Available(version),
+            }
+        }
+    };
+
+    ToolProbe {
+        name: tool.name().to_string(),
+        path: Some(tool.path().to_string()),
+        status,
+    }
+}
+
+/// Checks every tool the configuration knows about, ffmpeg and ffprobe first.
+pub async fn check_all(config: &ToolsConfig) -> Vec<ToolProbe> {
+    let tools: Vec<(&str, Option<Box<dyn ExternalTool>>)> =
+        vec![
+            ("ffmpeg", Some(Box::new(Binary::ffmpeg(&config.ffmpeg)))),
+            ("ffprobe", Some(Box::new(Binary::ffprobe(&config.ffprobe)))),
+            (
+                "dovi_tool",
+                config.dovi_tool.as_ref().map(|dv| {
+                    Box::new(DoviTool::new(dv.to_tool_config())) as Box<dyn ExternalTool>
+                }),
+            ),
+            (
+                "hdr10plus_tool",
+                config.hdr10plus_tool.as_ref().map(|tool| {
+                    Box::new(Hdr10PlusTool::new(tool.clone())) as Box<dyn ExternalTool>
+                }),
+            ),
+            (
+                "mkvmerge",
+                config
+                    .mkvmerge
+                    .as_ref()
+                    .map(|mkv| Box::new(MkvMergeTool::new(mkv.clone())) as Box<dyn ExternalTool>),
+            ),
+            (
+                "mkvpropedit",
+                config
+                    .mkvpropedit
+                    .as_ref()
+                    .map(|mkv| Box::new(Binary::mkvpropedit(&mkv.path)) as Box<dyn ExternalTool>),
+            ),
+        ];
+
+    futures::future::join_all(tools.iter().map(|(name, tool)| async move {
+        match tool {
+            Some(tool) => probe(tool.as_ref()).await,
+            None => ToolProbe::not_configured(name),
+        }
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sh -c <script>` posing as a tool that requires version 2.0
+    struct FakeTool([&'static str; 2]);
+
+    impl ExternalTool for FakeTool {
+        fn name(&self) -> &str {
+            "fake"
+        }
+
+        fn path(&self) -> &str {
+            "sh"
+        }
+
+        fn version_args(&self) -> &[&str] {
+            &self.0
+        }
+
+        fn min_version(&self) -> Option<Version> {
+            Some(Version::new(2, 0, 0))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_probe_enforces_minimum_version() {
+        let probe_result = probe(&FakeTool(["-c", "echo fake 2.1.0"])).await;
+        assert_eq!(
+            probe_result.status,
+            ToolStatus::Available(Some(Version::new(2, 1, 0)))
+        );
+
+        let probe_result = probe(&FakeTool(["-c", "echo fake 1.9 >&2"])).await;
+        assert_eq!(
+            probe_result.status,
+            ToolStatus::TooOld {
+                found: Version::new(1, 9, 0),
+                required: Version::new(2, 0, 0),
+            }
+        );
+        assert!(probe_result.into_result().is_err());
+
+        let probe_result = probe(&FakeTool(["-c", "exit 3"])).await;
+        assert!(matches!(probe_result.status, ToolStatus::Unavailable(_)));
+    }
+}
@@ -0,0 +1,71 @@
+use regex::Regex;
+use std::fmt;
+use std::sync::LazyLock;
+
+static VERSION_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d+)\.(\d+)(?:\.(\d+))?").expect("valid version pattern"));
+
+/// Release version of an external tool (`major.minor[.patch]`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+}
+
+impl Version {
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// First `major.minor[.patch]` in `text`, e.g. `6.1.1` in
+    /// `ffmpeg version 6.1.1-3ubuntu5` or `82.0` in `mkvmerge v82.0 (...)`.
+    pub fn find(text: &str) -> Option<Self> {
+        let captures = VERSION_PATTERN.captures(text)?;
+        let number = |index: usize| {
+            captures
+                .get(index)
+                .map_or(Some(0), |m| m.as_str().parse().ok())
+        };
+        Some(Self::new(number(1)?, number(2)?, number(3)?))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_version() {
+        assert_eq!(
+            Version::find("ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023"),
+            Some(Version::new(6, 1, 1))
+        );
+        assert_eq!(
+            Version::find("ffmpeg version n7.0"),
+            Some(Version::new(7, 0, 0))
+        );
+        assert_eq!(
+            Version::find("dovi_tool 2.1.2"),
+            Some(Version::new(2, 1, 2))
+        );
+        assert_eq!(
+            Version::find("mkvmerge v82.0 ('I'm The President') 64-bit"),
+            Some(Version::new(82, 0, 0))
+        );
+        assert_eq!(Version::find("ffmpeg version N-113017-g2a5b2e5c7f"), None);
+
+        assert!(Version::new(4, 4, 0) > Version::new(4, 3, 9));
+        assert!(Version::new(10, 0, 0) > Version::new(9, 12, 0));
+    }
+}
@@ -0,0 +1,82 @@
+use crate::progress::AbortCondition;
+use futures::future::BoxFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Shared flag that stops long-running work early: external tool runs are
+/// killed (and their partial output removed) and running encodes aborted.
+/// Clones observe the same state; a cancelled token stays cancelled, so
+/// restarting the work takes a new one.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    inner: Arc<CancelState>,
+}
+
+#[derive(Debug, Default)]
+struct CancelState {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes once the token is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            // Register before checking, so a cancel in between is not missed
+            notified.as_mut().enable();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl AbortCondition for CancelToken {
+    fn abort_reason(&mut self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(std::future::ready(
+            self.is_cancelled().then(|| "cancelled".to_string()),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_wakes_waiters() {
+        let token = CancelToken::new();
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!token.is_cancelled());
+        token.cancel();
+
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("waiter was not woken")
+            .unwrap();
+        assert!(token.clone().abort_reason().await.is_some());
+    }
+}
@@ -0,0 +1,126 @@
+//! Debug bundles for bug reports.
+//!
+//! With `--debug-bundle`, a file that fails to encode leaves
+//! `<output>.debug.tar.gz` next to its output: the per-file log (which ends
+//! with FFmpeg's stderr), the error, ffprobe's view of the source, the
+//! resolved configuration and the versions of the external tools.
+
+use crate::config::Config;
+use crate::tools;
+use crate::utils::{Error, FfmpegWrapper, JobTempDir, Result};
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use tracing::debug;
+
+const LOG_FILE: &str = "encode.log";
+const ERROR_FILE: &str = "error.txt";
+const PROBE_FILE: &str = "source.ffprobe.json";
+const CONFIG_FILE: &str = "config.yaml";
+const TOOLS_FILE: &str = "tools.txt";
+
+/// Where the bundle of a failed `output` goes.
+pub fn bundle_path(output: &Path) -> PathBuf {
+    output.with_extension("debug.tar.gz")
+}
+
+/// What failed, for `error.txt`.
+fn error_report(input: &Path, output: &Path, error: &Error) -> String {
+    format!(
+        "ven {}\nInput: {}\nOutput: {}\nError: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        input.display(),
+        output.display(),
+        error
+    )
+}
+
+/// Collects what is known about the failed encode of `input` into a tarball
+/// next to `output` and returns its path. Parts that cannot be gathered (no
+/// log yet, source unreadable) are left out.
+pub async fn write_debug_bundle(
+    config: &Config,
+    input: &Path,
+    output: &Path,
+    error: &Error,
+) -> Result<PathBuf> {
+    let staging = JobTempDir::create(&config.app.temp_dir, output)?;
+    let dir = staging.path();
+    let mut files = vec![ERROR_FILE, CONFIG_FILE, TOOLS_FILE];
+
+    std::fs::write(dir.join(ERROR_FILE), error_report(input, output, error))?;
+    std::fs::write(dir.join(CONFIG_FILE), serde_yaml::to_string(config)?)?;
+
+    let tool_lines: Vec<String> = tools::check_all(&config.tools)
+        .await
+        .iter()
+        .map(|probe| format!("{}: {}", probe.name, probe.detail()))
+        .collect();
+    std::fs::write(dir.join(TOOLS_FILE), tool_lines.join("\n") + "\n")?;
+
+    match std::fs::copy(output.with_extension("log"), dir.join(LOG_FILE)) {
+        Ok(_) => files.push(LOG_FILE),
+        Err(e) => debug!("Debug bundle without log: {}", e),
+    }
+
+    let ffmpeg = FfmpegWrapper::new(config.tools.ffmpeg.clone(), config.tools.ffprobe.clone());
+    let probe = ffmpeg
+        .run_ffprobe(&[
+            "-v",
+            "quiet",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+            "-show_chapters",
+            &input.to_string_lossy(),
+        ])
+        .await;
+    match probe {
+        Ok(json) => {
+            std::fs::write(dir.join(PROBE_FILE), json)?;
+            files.push(PROBE_FILE);
+        }
+        Err(e) => debug!("Debug bundle without ffprobe output: {}", e),
+    }
+
+    let bundle = bundle_path(output);
+    let result = Command::new("tar")
+        .arg("-czf")
+        .arg(&bundle)
+        .arg("-C")
+        .arg(dir)
+        .args(&files)
+        .output()
+        .await?;
+    if !result.status.success() {
+        return Err(Error::tool(format!(
+            "tar failed to write {}: {}",
+            bundle.display(),
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_path_and_error_report() {
+        let output = Path::new("/media/out/movie.mkv");
+        assert_eq!(
+            bundle_path(output),
+            PathBuf::from("/media/out/movie.debug.tar.gz")
+        );
+
+        let report = error_report(
+            Path::new("/media/in/movie.mkv"),
+            output,
+            &Error::encoding("Encoding failed with exit code: 1"),
+        );
+        assert!(report.starts_with(&format!("ven {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(report.contains("Input: /media/in/movie.mkv\n"));
+        assert!(report.ends_with("Error: Encoding error: Encoding failed with exit code: 1\n"));
+    }
+}
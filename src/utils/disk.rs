@@ -0,0 +1,185 @@
+use crate::config::types::DiskSpaceConfig;
+use crate::progress::AbortCondition;
+use crate::utils::{Error, Result};
+use futures::future::BoxFuture;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+const MB: u64 = 1024 * 1024;
+
+/// Free bytes on the volume holding `path` (or its nearest existing ancestor),
+/// as reported by `df -Pk`.
+#[cfg(unix)]
+pub async fn available_space(path: &Path) -> Result<u64> {
+    let existing = path
+        .ancestors()
+        .find(|p| !p.as_os_str().is_empty() && p.exists())
+        .unwrap_or_else(|| Path::new("."));
+
+    let output = tokio::process::Command::new("df")
+        .arg("-Pk")
+        .arg(existing)
+        .kill_on_drop(true)
+        .output()
+        .await
+        .map_err(|e| Error::tool(format!("Failed to run df: {}", e)))?;
+    if !output.status.success() {
+        return Err(Error::tool(format!(
+            "df failed for {}: {}",
+            existing.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Free space is read with POSIX `df`, which this platform does not have.
+#[cfg(not(unix))]
+pub async fn available_space(_path: &Path) -> Result<u64> {
+    Err(Error::tool(
+        "Free space checks (disk_space) need POSIX df and are not available on this platform",
+    ))
+}
+
+/// Parses the "Available" column (1K blocks) of POSIX `df -P` output.
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_df_available(output: &str) -> Result<u64> {
+    output
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|blocks| blocks.parse::<u64>().ok())
+        .map(|blocks| blocks * 1024)
+        .ok_or_else(|| Error::parse(format!("Unexpected df output: {}", output.trim())))
+}
+
+fn format_mb(bytes: u64) -> String {
+    format!("{} MB", bytes / MB)
+}
+
+/// Watches free space on the output (and temp) volumes during an encode.
+pub struct DiskSpaceGuard {
+    paths: Vec<PathBuf>,
+    min_free: u64,
+    check_interval: Duration,
+    last_check: Option<Instant>,
+}
+
+impl DiskSpaceGuard {
+    pub fn new(config: &DiskSpaceConfig, paths: &[&Path]) -> Self {
+        let mut unique: Vec<PathBuf> = Vec::new();
+        for path in paths {
+            let dir = path.parent().unwrap_or(path).to_path_buf();
+            if !unique.contains(&dir) {
+                unique.push(dir);
+            }
+        }
+
+        Self {
+            paths: unique,
+            min_free: config.min_free_mb * MB,
+            check_interval: Duration::from_secs(config.check_interval.max(1)),
+            last_check: None,
+        }
+    }
+
+    /// Preflight check: errors if any watched volume is already below the minimum,
+    /// and warns if it has less room than `expected_size` bytes.
+    pub async fn check(&self, expected_size: Option<u64>) -> Result<()> {
+        for path in &self.paths {
+            let available = match available_space(path).await {
+                Ok(available) => available,
+                Err(e) => {
+                    warn!(
+                        "Could not determine free space for {}: {}",
+                        path.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            if available < self.min_free {
+                return Err(Error::validation(format!(
+                    "Not enough free space on {}: {} available, {} required (disk_space.min_free_mb)",
+                    path.display(),
+                    format_mb(available),
+                    format_mb(self.min_free)
+                )));
+            }
+
+            if let Some(expected) = expected_size {
+                if available < self.min_free + expected {
+                    warn!(
+                        "Only {} free on {}; the output may need up to {} (source size)",
+                        format_mb(available),
+                        path.display(),
+                        format_mb(expected)
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn low_space_reason(&self) -> Option<String> {
+        for path in &self.paths {
+            let Ok(available) = available_space(path).await else {
+                continue;
+            };
+            if available < self.min_free {
+                return Some(format!(
+                    "free space on {} dropped to {} (minimum {})",
+                    path.display(),
+                    format_mb(available),
+                    format_mb(self.min_free)
+                ));
+            }
+        }
+        None
+    }
+}
+
+impl AbortCondition for DiskSpaceGuard {
+    fn abort_reason(&mut self) -> BoxFuture<'_, Option<String>> {
+        Box::pin(async move {
+            let due = self
+                .last_check
+                .is_none_or(|last| last.elapsed() >= self.check_interval);
+            if !due {
+                return None;
+            }
+            self.last_check = Some(Instant::now());
+            self.low_space_reason().await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_df_output() {
+        let output = "Filesystem     1024-blocks      Used Available Capacity Mounted on\n\
+                      /dev/nvme0n1p2   479596204 301234568 153944236      67% /\n";
+        assert_eq!(parse_df_available(output).unwrap(), 153_944_236 * 1024);
+        assert!(parse_df_available("garbage").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_guard_deduplicates_volumes() {
+        let config = DiskSpaceConfig {
+            min_free_mb: 0,
+            ..DiskSpaceConfig::default()
+        };
+        let guard = DiskSpaceGuard::new(
+            &config,
+            &[Path::new("/tmp/out.mkv"), Path::new("/tmp/out.tmp.mkv")],
+        );
+        assert_eq!(guard.paths, vec![PathBuf::from("/tmp")]);
+        assert!(guard.check(None).await.is_ok());
+    }
+}
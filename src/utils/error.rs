@@ -1,7 +1,36 @@
+use std::fmt;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Why an external tool run failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolFailureKind {
+    /// Ran past the configured timeout and was killed
+    Timeout,
+    /// Died from a signal or panicked, or exited without its output
+    Crashed,
+    /// Exited with an error code, i.e. rejected the input
+    InvalidInput,
+}
+
+impl ToolFailureKind {
+    /// Whether running the tool again may succeed.
+    pub fn is_transient(self) -> bool {
+        !matches!(self, ToolFailureKind::InvalidInput)
+    }
+}
+
+impl fmt::Display for ToolFailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolFailureKind::Timeout => write!(f, "timed out"),
+            ToolFailureKind::Crashed => write!(f, "crashed"),
+            ToolFailureKind::InvalidInput => write!(f, "rejected the input"),
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("Configuration error: {0}")]
@@ -37,8 +66,25 @@ pub enum Error {
     #[error("Tool error: {0}")]
     Tool(String),
 
+    #[error("Tool error: {message}")]
+    ToolFailed {
+        kind: ToolFailureKind,
+        message: String,
+    },
+
     #[error("Dolby Vision error: {0}")]
     DolbyVision(String),
+
+    #[error("Cancelled: {0}")]
+    Cancelled(String),
+
+    /// The encode was abandoned because it would not save space
+    #[error("Inefficient encode: {0}")]
+    Inefficient(String),
+
+    /// The source looks like one encoded before under another name
+    #[error("Duplicate source: {0}")]
+    Duplicate(String),
 }
 
 impl Error {
@@ -91,4 +137,31 @@ impl Error {
     pub fn tool<T: Into<String>>(message: T) -> Self {
         Self::Tool(message.into())
     }
+
+    pub fn cancelled<T: Into<String>>(message: T) -> Self {
+        Self::Cancelled(message.into())
+    }
+
+    pub fn inefficient<T: Into<String>>(message: T) -> Self {
+        Self::Inefficient(message.into())
+    }
+
+    pub fn duplicate<T: Into<String>>(message: T) -> Self {
+        Self::Duplicate(message.into())
+    }
+
+    pub fn tool_failed<T: Into<String>>(kind: ToolFailureKind, message: T) -> Self {
+        Self::ToolFailed {
+            kind,
+            message: message.into(),
+        }
+    }
+
+    /// How an external tool failed, for errors from a tool run.
+    pub fn tool_failure_kind(&self) -> Option<ToolFailureKind> {
+        match self {
+            Self::ToolFailed { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
 }
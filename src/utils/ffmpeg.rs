@@ -1,4 +1,5 @@
 use crate::hdr::HdrAnalysisResult;
+use crate::tools::Binary;
 use crate::utils::{Error, Result};
 use regex::Regex;
 use std::path::Path;
@@ -10,20 +11,9 @@ use tracing::debug;
 static DURATION_REGEX: LazyLock<Regex> =
     LazyLock::new(|| Regex::new(r"Duration: (\d{2}):(\d{2}):(\d{2})\.(\d{2})").unwrap());
 
-static PROGRESS_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"time=(\d{2}):(\d{2}):(\d{2})\.(\d{2})").unwrap());
-
-static FRAME_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"frame=\s*(\d+)").unwrap());
-
-static SPEED_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"speed=\s*([0-9.]+)x").unwrap());
-
-static SIZE_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"(?:size|Lsize)=\s*(\d+)k?iB").unwrap());
-
-static BITRATE_REGEX: LazyLock<Regex> =
-    LazyLock::new(|| Regex::new(r"bitrate=\s*([0-9.]+)kbits/s").unwrap());
-
-static FPS_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"fps=\s*([0-9.]+)").unwrap());
+/// Depth suffix of a pixel format: `yuv420p10le`, `p010le`, `gray12le`
+static PIX_FMT_DEPTH_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:p|gray)0?(\d+)(?:le|be)$").unwrap());
 
 fn filter_ffmpeg_stderr(stderr: &str) -> String {
     stderr
@@ -60,6 +50,11 @@ pub struct VideoMetadata {
     pub fps: f32,
     pub bitrate: Option<u32>,
     pub codec: Option<String>,
+    /// Pixel format of the video stream, e.g. `yuv420p10le`
+    pub pix_fmt: Option<String>,
+    /// Field order of the video stream: `progressive`, or `tt`/`bb`/`tb`/`bt`
+    /// for interlaced video
+    pub field_order: Option<String>,
     pub is_hdr: bool,
     pub hdr_analysis: Option<HdrAnalysisResult>,
     pub color_space: Option<String>,
@@ -71,6 +66,26 @@ pub struct VideoMetadata {
     pub streams: Vec<StreamInfo>,
 }
 
+impl VideoMetadata {
+    /// Bits per component from the pixel format (`yuv420p10le`, `p010le`: 10);
+    /// formats without a depth suffix (`yuv420p`, `nv12`) are 8-bit.
+    pub fn bit_depth(&self) -> Option<u8> {
+        let pix_fmt = self.pix_fmt.as_deref()?;
+        Some(
+            PIX_FMT_DEPTH_REGEX
+                .captures(pix_fmt)
+                .and_then(|captures| captures[1].parse().ok())
+                .unwrap_or(8),
+        )
+    }
+
+    /// Whether ffprobe reports the video as interlaced; streams without a
+    /// field order count as progressive.
+    pub fn is_interlaced(&self) -> bool {
+        matches!(self.field_order.as_deref(), Some("tt" | "bb" | "tb" | "bt"))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct StreamInfo {
     pub index: u32,
@@ -80,7 +95,7 @@ pub struct StreamInfo {
     pub title: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct ProgressInfo {
     pub frame: Option<u32>,
     pub fps: Option<f32>,
@@ -89,22 +104,155 @@ pub struct ProgressInfo {
     pub time: f64,
     pub speed: Option<f32>,
     pub progress_percentage: f32,
+    pub dropped_frames: Option<u32>,
+}
+
+/// Reads FFmpeg's `-progress` protocol: blocks of `key=value` lines, each
+/// closed by `progress=continue` (or `progress=end` for the last one). Unlike
+/// the stats line on stderr, the keys do not change with locale or version.
+#[derive(Debug, Clone, Default)]
+pub struct ProgressParser {
+    total_duration: f64,
+    current: ProgressInfo,
+}
+
+impl ProgressParser {
+    pub fn new(total_duration: f64) -> Self {
+        Self {
+            total_duration,
+            current: ProgressInfo::default(),
+        }
+    }
+
+    /// Feeds one line; returns the block it completes, if any.
+    pub fn feed(&mut self, line: &str) -> Option<ProgressInfo> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+        if key == "progress" {
+            let block = std::mem::take(&mut self.current);
+            return (block.frame.is_some() || block.time > 0.0).then_some(block);
+        }
+        if value == "N/A" {
+            return None;
+        }
+
+        let progress = &mut self.current;
+        match key {
+            "frame" => progress.frame = value.parse().ok(),
+            "fps" => progress.fps = value.parse().ok(),
+            "bitrate" => {
+                progress.bitrate = value
+                    .strip_suffix("kbits/s")
+                    .map(|kbps| format!("{}kbps", kbps))
+            }
+            "total_size" => progress.total_size = value.parse().ok(),
+            // Both are microseconds; out_time_ms is misnamed in FFmpeg
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(time_us) = value.parse::<i64>() {
+                    progress.time = time_us.max(0) as f64 / 1_000_000.0;
+                    if self.total_duration > 0.0 {
+                        progress.progress_percentage =
+                            ((progress.time / self.total_duration) * 100.0).min(100.0) as f32;
+                    }
+                }
+            }
+            "speed" => progress.speed = value.trim_end_matches('x').parse().ok(),
+            "drop_frames" => progress.dropped_frames = value.parse().ok(),
+            _ => {}
+        }
+        None
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct FfmpegWrapper {
     ffmpeg_path: String,
     ffprobe_path: String,
+    input_options: Vec<String>,
+    video_stream: Option<usize>,
+    input_seek: Option<f64>,
+    output_duration: Option<f64>,
 }
 
+/// Input options that let FFmpeg continue past corrupt packets/frames instead of aborting.
+pub const ERROR_RESILIENCE_INPUT_OPTIONS: [&str; 4] = [
+    "-err_detect",
+    "ignore_err",
+    "-fflags",
+    "+genpts+discardcorrupt",
+];
+
 impl FfmpegWrapper {
     pub fn new(ffmpeg_path: String, ffprobe_path: String) -> Self {
         Self {
             ffmpeg_path,
             ffprobe_path,
+            input_options: Vec::new(),
+            video_stream: None,
+            input_seek: None,
+            output_duration: None,
         }
     }
 
+    /// Options placed before `-i` for every encode started through this wrapper.
+    pub fn with_input_options<I, S>(mut self, options: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.input_options = options.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// `--error-resilience`: reads damaged sources with
+    /// [`ERROR_RESILIENCE_INPUT_OPTIONS`].
+    pub fn with_error_resilience(self, enabled: bool) -> Self {
+        if enabled {
+            self.with_input_options(ERROR_RESILIENCE_INPUT_OPTIONS)
+        } else {
+            self
+        }
+    }
+
+    pub fn input_options(&self) -> &[String] {
+        &self.input_options
+    }
+
+    /// Reads the properties of the video stream at `position` (`v:N`, as
+    /// chosen with `--video-stream`) instead of the first video stream.
+    pub fn with_video_stream(mut self, position: Option<usize>) -> Self {
+        self.video_stream = position;
+        self
+    }
+
+    /// Stops every encode started through this wrapper after `seconds` of
+    /// output (`-t`), e.g. for a probe encode.
+    pub fn with_output_duration(mut self, seconds: Option<f64>) -> Self {
+        self.output_duration = seconds;
+        self
+    }
+
+    /// `--start`/`--end`: every encode started through this wrapper reads its
+    /// inputs from `start` seconds (`-ss` before each `-i`, so extracted
+    /// captions stay in sync) and stops at `end`.
+    pub fn with_trim(mut self, start: Option<f64>, end: Option<f64>) -> Self {
+        self.input_seek = start.filter(|start| *start > 0.0);
+        self.output_duration = end.map(|end| end - start.unwrap_or(0.0));
+        self
+    }
+
+    /// ffprobe `-select_streams` specifier of the video stream that is
+    /// probed, e.g. `v:0`.
+    pub fn video_stream_specifier(&self) -> String {
+        format!("v:{}", self.video_stream.unwrap_or(0))
+    }
+
+    /// ffmpeg `-map` specifier of that video stream in the first input,
+    /// e.g. `0:v:0`.
+    pub fn video_map_specifier(&self) -> String {
+        format!("0:{}", self.video_stream_specifier())
+    }
+
     pub fn get_ffmpeg_path(&self) -> &str {
         &self.ffmpeg_path
     }
@@ -142,19 +290,26 @@ impl FfmpegWrapper {
         self.parse_video_metadata(probe_data, &input_path).await
     }
 
+    /// Output options that send progress blocks to stdout for
+    /// [`ProgressParser`] instead of printing stats to stderr.
+    pub fn progress_args() -> Vec<String> {
+        ["-progress", "pipe:1", "-nostats", "-stats_period", "1.0"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect()
+    }
+
+    /// Spawns FFmpeg with stdout (progress, see [`Self::progress_args`]) and
+    /// stderr (errors only, at `-loglevel error`) piped; both have to be
+    /// drained while it runs.
     pub async fn start_encoding<P: AsRef<Path>>(
         &self,
-        _input_path: P,
-        _output_path: P,
+        input_path: P,
+        output_path: P,
         args: Vec<String>,
     ) -> Result<Child> {
-        let mut cmd_args = vec![
-            "-y".to_string(),
-            "-loglevel".to_string(),
-            "error".to_string(),
-            "-hide_banner".to_string(),
-        ];
-        cmd_args.extend(args);
+        super::filesystem::ensure_not_source(&input_path, &output_path)?;
+        let cmd_args = self.encoding_args(args);
 
         tracing::debug!(
             "Executing FFmpeg command: {} {}",
@@ -166,70 +321,35 @@ impl FfmpegWrapper {
         command
             .args(&cmd_args)
             .stdin(Stdio::null())
-            .stdout(Stdio::inherit())
-            .stderr(Stdio::inherit());
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         let child = command.spawn()?;
         Ok(child)
     }
 
-    pub fn parse_progress_line(&self, line: &str, total_duration: f64) -> Option<ProgressInfo> {
-        if !line.contains("frame=") {
-            return None;
-        }
-
-        let mut progress = ProgressInfo {
-            frame: None,
-            fps: None,
-            bitrate: None,
-            total_size: None,
-            time: 0.0,
-            speed: None,
-            progress_percentage: 0.0,
-        };
-
-        if let Some(captures) = FRAME_REGEX.captures(line) {
-            progress.frame = captures[1].parse().ok();
-        }
-
-        if let Some(captures) = PROGRESS_REGEX.captures(line) {
-            let hours: u32 = captures[1].parse().ok()?;
-            let minutes: u32 = captures[2].parse().ok()?;
-            let seconds: u32 = captures[3].parse().ok()?;
-            let centiseconds: u32 = captures[4].parse().ok()?;
-
-            progress.time =
-                (hours * 3600 + minutes * 60 + seconds) as f64 + centiseconds as f64 / 100.0;
-            progress.progress_percentage = if total_duration > 0.0 {
-                ((progress.time / total_duration) * 100.0).min(100.0) as f32
-            } else {
-                0.0
-            };
-        }
-
-        if let Some(captures) = FPS_REGEX.captures(line) {
-            progress.fps = captures[1].parse().ok();
-        }
-
-        if let Some(captures) = SPEED_REGEX.captures(line) {
-            progress.speed = captures[1].parse().ok();
-        }
-
-        if let Some(captures) = SIZE_REGEX.captures(line) {
-            if let Ok(size_kb) = captures[1].parse::<u64>() {
-                progress.total_size = Some(size_kb * 1024);
+    /// Full FFmpeg arguments of an encode whose own arguments (ending with
+    /// the output path) are `args`.
+    fn encoding_args(&self, args: Vec<String>) -> Vec<String> {
+        let mut cmd_args = vec![
+            "-y".to_string(),
+            "-loglevel".to_string(),
+            "error".to_string(),
+            "-hide_banner".to_string(),
+        ];
+        cmd_args.extend(self.input_options.iter().cloned());
+        for arg in args {
+            if let Some(start) = self.input_seek.filter(|_| arg == "-i") {
+                cmd_args.extend(["-ss".to_string(), start.to_string()]);
             }
+            cmd_args.push(arg);
         }
-
-        if let Some(captures) = BITRATE_REGEX.captures(line) {
-            progress.bitrate = Some(format!("{}kbps", &captures[1]));
-        }
-
-        if progress.frame.is_some() || progress.time > 0.0 {
-            Some(progress)
-        } else {
-            None
+        if let Some(seconds) = self.output_duration {
+            // The output path comes last
+            let at = cmd_args.len().saturating_sub(1);
+            cmd_args.splice(at..at, ["-t".to_string(), seconds.to_string()]);
         }
+        cmd_args
     }
 
     async fn parse_video_metadata(
@@ -243,8 +363,15 @@ impl FfmpegWrapper {
 
         let video_stream = streams
             .iter()
-            .find(|s| s["codec_type"].as_str() == Some("video"))
-            .ok_or_else(|| Error::parse("No video stream found"))?;
+            .filter(|s| s["codec_type"].as_str() == Some("video"))
+            .nth(self.video_stream.unwrap_or(0))
+            .ok_or_else(|| match self.video_stream {
+                Some(position) => Error::validation(format!(
+                    "--video-stream {}: no such video stream in {}",
+                    position, input_path
+                )),
+                None => Error::parse("No video stream found"),
+            })?;
 
         let width = video_stream["width"]
             .as_u64()
@@ -276,6 +403,8 @@ impl FfmpegWrapper {
             .and_then(|b| b.parse::<u32>().ok());
 
         let codec = video_stream["codec_name"].as_str().map(|s| s.to_string());
+        let pix_fmt = video_stream["pix_fmt"].as_str().map(|s| s.to_string());
+        let field_order = video_stream["field_order"].as_str().map(|s| s.to_string());
 
         let color_space = video_stream["color_space"].as_str().map(|s| s.to_string());
         let transfer_function = video_stream["color_transfer"]
@@ -349,6 +478,8 @@ impl FfmpegWrapper {
             fps,
             bitrate,
             codec,
+            pix_fmt,
+            field_order,
             is_hdr,
             hdr_analysis: None, // Will be filled by HDR analysis
             color_space,
@@ -408,25 +539,17 @@ impl FfmpegWrapper {
         is_hdr
     }
 
+    /// Checks that ffmpeg and ffprobe run and meet the minimum version.
     pub async fn check_availability(&self) -> Result<()> {
-        let ffmpeg_check = TokioCommand::new(&self.ffmpeg_path)
-            .arg("-version")
-            .output()
-            .await?;
-
-        if !ffmpeg_check.status.success() {
-            return Err(Error::ffmpeg("FFmpeg is not available or not executable"));
+        for binary in [
+            Binary::ffmpeg(&self.ffmpeg_path),
+            Binary::ffprobe(&self.ffprobe_path),
+        ] {
+            crate::tools::probe(&binary)
+                .await
+                .into_result()
+                .map_err(|e| Error::ffmpeg(e.to_string()))?;
         }
-
-        let ffprobe_check = TokioCommand::new(&self.ffprobe_path)
-            .arg("-version")
-            .output()
-            .await?;
-
-        if !ffprobe_check.status.success() {
-            return Err(Error::ffmpeg("FFprobe is not available or not executable"));
-        }
-
         Ok(())
     }
 
@@ -448,6 +571,37 @@ impl FfmpegWrapper {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
+    /// Exact frame count of a video stream (`v:0`-style specifier), from
+    /// reading every packet with `ffprobe -count_packets`. Reads the whole
+    /// file, but unlike duration × frame rate it is right for VFR sources.
+    pub async fn count_video_frames<P: AsRef<Path>>(
+        &self,
+        input_path: P,
+        stream_specifier: &str,
+    ) -> Result<u32> {
+        let input_path = input_path.as_ref().to_string_lossy();
+        let output = self
+            .run_ffprobe(&[
+                "-v",
+                "error",
+                "-select_streams",
+                stream_specifier,
+                "-count_packets",
+                "-show_entries",
+                "stream=nb_read_packets",
+                "-of",
+                "csv=p=0",
+                &input_path,
+            ])
+            .await?;
+        parse_packet_count(&output).ok_or_else(|| {
+            Error::parse(format!(
+                "Unexpected ffprobe packet count: {}",
+                output.trim()
+            ))
+        })
+    }
+
     /// Run ffmpeg with custom arguments and return the child process
     pub async fn run_ffmpeg(&self, args: &[&str]) -> Result<Child> {
         debug!("Running ffmpeg with args: {:?}", args);
@@ -549,10 +703,136 @@ impl FfmpegWrapper {
     }
 }
 
+/// `nb_read_packets` as printed with `-of csv=p=0` (a trailing comma appears
+/// with some ffprobe versions).
+fn parse_packet_count(output: &str) -> Option<u32> {
+    output
+        .lines()
+        .map(|line| line.trim().trim_end_matches(','))
+        .find(|line| !line.is_empty())?
+        .parse()
+        .ok()
+        .filter(|&count| count > 0)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_packet_count() {
+        assert_eq!(parse_packet_count("34512\n"), Some(34512));
+        assert_eq!(parse_packet_count("\n1440,\n"), Some(1440));
+        assert_eq!(parse_packet_count("N/A\n"), None);
+        assert_eq!(parse_packet_count("0\n"), None);
+    }
+
+    #[test]
+    fn test_progress_parser() {
+        let mut parser = ProgressParser::new(100.0);
+        let first = "frame=240\nfps=23.98\nstream_0_0_q=28.0\nbitrate=N/A\n\
+                     total_size=1048576\nout_time_us=10000000\nout_time_ms=10000000\n\
+                     out_time=00:00:10.000000\ndup_frames=0\ndrop_frames=2\nspeed=1.5x\n";
+        for line in first.lines() {
+            assert_eq!(parser.feed(line), None);
+        }
+        let block = parser.feed("progress=continue").unwrap();
+        assert_eq!(block.frame, Some(240));
+        assert_eq!(block.fps, Some(23.98));
+        assert_eq!(block.bitrate, None);
+        assert_eq!(block.total_size, Some(1_048_576));
+        assert_eq!(block.time, 10.0);
+        assert_eq!(block.progress_percentage, 10.0);
+        assert_eq!(block.dropped_frames, Some(2));
+        assert_eq!(block.speed, Some(1.5));
+
+        // Values never carry over into the next block
+        parser.feed("frame=250");
+        parser.feed("bitrate=838.9kbits/s");
+        let block = parser.feed("progress=end").unwrap();
+        assert_eq!(block.bitrate.as_deref(), Some("838.9kbps"));
+        assert_eq!(block.dropped_frames, None);
+
+        // FFmpeg reports a negative time before the first packet is written
+        parser.feed("out_time_us=-9223372036854775807");
+        assert_eq!(parser.feed("progress=continue"), None);
+    }
+
+    #[test]
+    fn test_error_resilience_input_options() {
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        assert!(ffmpeg.input_options().is_empty());
+
+        let ffmpeg = ffmpeg.with_input_options(ERROR_RESILIENCE_INPUT_OPTIONS);
+        assert_eq!(
+            ffmpeg.input_options().join(" "),
+            "-err_detect ignore_err -fflags +genpts+discardcorrupt"
+        );
+    }
+
+    #[test]
+    fn test_output_duration() {
+        let args = || {
+            ["-i", "in.mkv", "-c:v", "libx265", "out.mkv"]
+                .map(String::from)
+                .to_vec()
+        };
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        assert_eq!(
+            ffmpeg.encoding_args(args()).join(" "),
+            "-y -loglevel error -hide_banner -i in.mkv -c:v libx265 out.mkv"
+        );
+
+        let probe = ffmpeg.with_output_duration(Some(5.0));
+        assert_eq!(
+            probe.encoding_args(args()).join(" "),
+            "-y -loglevel error -hide_banner -i in.mkv -c:v libx265 -t 5 out.mkv"
+        );
+
+        // --start/--end seek every input and stop the output
+        let args = || {
+            ["-i", "in.mkv", "-i", "cc.srt", "-c:v", "libx265", "out.mkv"]
+                .map(String::from)
+                .to_vec()
+        };
+        let trimmed = probe.with_trim(Some(60.0), Some(90.0));
+        assert_eq!(
+            trimmed.encoding_args(args()).join(" "),
+            "-y -loglevel error -hide_banner -ss 60 -i in.mkv -ss 60 -i cc.srt -c:v libx265 -t 30 out.mkv"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_video_stream_selection() {
+        let probe = serde_json::json!({
+            "format": {"duration": "5400.0"},
+            "streams": [
+                {"codec_type": "video", "codec_name": "mpeg2video", "width": 720, "height": 480, "r_frame_rate": "30000/1001"},
+                {"codec_type": "audio", "codec_name": "ac3"},
+                {"codec_type": "video", "codec_name": "h264", "width": 1920, "height": 1080, "r_frame_rate": "24000/1001"}
+            ]
+        });
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let first = ffmpeg
+            .parse_video_metadata(probe.clone(), "in.mkv")
+            .await
+            .unwrap();
+        assert_eq!((first.width, first.height), (720, 480));
+        assert_eq!(ffmpeg.video_stream_specifier(), "v:0");
+
+        let ffmpeg = ffmpeg.with_video_stream(Some(1));
+        assert_eq!(ffmpeg.video_stream_specifier(), "v:1");
+        let second = ffmpeg
+            .parse_video_metadata(probe.clone(), "in.mkv")
+            .await
+            .unwrap();
+        assert_eq!((second.width, second.height), (1920, 1080));
+        assert_eq!(second.codec.as_deref(), Some("h264"));
+
+        let missing = ffmpeg.with_video_stream(Some(2));
+        assert!(missing.parse_video_metadata(probe, "in.mkv").await.is_err());
+    }
+
     #[test]
     fn test_parse_fraction_to_float() {
         let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
@@ -1,54 +1,154 @@
+use crate::config::OutputFilesConfig;
 use crate::utils::{Error, Result};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use tracing::info;
 use uuid::Uuid;
 use walkdir::WalkDir;
 
 const VIDEO_EXTENSIONS: &[&str] = &[".mkv", ".mp4", ".mov", ".m4v", ".avi", ".webm", ".ts"];
 
 pub fn find_video_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
-    let path = path.as_ref();
+    FileDiscovery::new().find(path)
+}
 
-    if !path.exists() {
-        return Err(Error::validation(format!(
-            "Path does not exist: {}",
-            path.display()
-        )));
+/// Identity of the file behind a path: device and inode where available, so
+/// hardlinks and symlinks to one file compare equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum FileId {
+    #[cfg(unix)]
+    Inode(u64, u64),
+    #[cfg(not(unix))]
+    Path(PathBuf),
+}
+
+impl FileId {
+    fn of(path: &Path) -> Option<Self> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let metadata = std::fs::metadata(path).ok()?;
+            Some(Self::Inode(metadata.dev(), metadata.ino()))
+        }
+        #[cfg(not(unix))]
+        {
+            path.canonicalize().ok().map(Self::Path)
+        }
     }
+}
 
-    let mut video_files = Vec::new();
+/// Finds the video files of a batch. Symlinked directories are only entered
+/// with `follow_symlinks`; a file reached a second time (through a hardlink, a
+/// symlink or another input path) is skipped unless duplicates are allowed.
+/// Files seen by earlier [`find`](Self::find) calls count as well.
+#[derive(Debug)]
+pub struct FileDiscovery {
+    follow_symlinks: bool,
+    allow_duplicates: bool,
+    seen: HashMap<FileId, PathBuf>,
+    duplicates: usize,
+}
 
-    if path.is_file() {
-        if is_video_file(path) {
-            video_files.push(path.to_path_buf());
-        } else {
+impl Default for FileDiscovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileDiscovery {
+    pub fn new() -> Self {
+        Self {
+            follow_symlinks: false,
+            allow_duplicates: false,
+            seen: HashMap::new(),
+            duplicates: 0,
+        }
+    }
+
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+
+    pub fn with_allow_duplicates(mut self, allow: bool) -> Self {
+        self.allow_duplicates = allow;
+        self
+    }
+
+    /// Files skipped so far because they were already found.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    pub fn find<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<PathBuf>> {
+        let path = path.as_ref();
+
+        if !path.exists() {
             return Err(Error::validation(format!(
-                "File is not a supported video format: {}",
+                "Path does not exist: {}",
                 path.display()
             )));
         }
-    } else if path.is_dir() {
-        for entry in WalkDir::new(path)
-            .follow_links(false)
-            .into_iter()
-            .filter_map(|e| e.ok())
-        {
-            let path = entry.path();
-            if path.is_file() && is_video_file(path) {
+
+        let mut video_files = Vec::new();
+
+        if path.is_file() {
+            if is_video_file(path) {
                 video_files.push(path.to_path_buf());
+            } else {
+                return Err(Error::validation(format!(
+                    "File is not a supported video format: {}",
+                    path.display()
+                )));
+            }
+        } else if path.is_dir() {
+            for entry in WalkDir::new(path)
+                .follow_links(self.follow_symlinks)
+                .into_iter()
+                .filter_map(|e| e.ok())
+            {
+                let path = entry.path();
+                if path.is_file() && is_video_file(path) {
+                    video_files.push(path.to_path_buf());
+                }
             }
-        }
 
-        if video_files.is_empty() {
-            return Err(Error::validation(format!(
-                "No supported video files found in directory: {}",
-                path.display()
-            )));
+            if video_files.is_empty() {
+                return Err(Error::validation(format!(
+                    "No supported video files found in directory: {}",
+                    path.display()
+                )));
+            }
+
+            video_files.sort();
         }
 
-        video_files.sort();
+        if !self.allow_duplicates {
+            video_files.retain(|file| self.first_sighting(file));
+        }
+        Ok(video_files)
     }
 
-    Ok(video_files)
+    fn first_sighting(&mut self, file: &Path) -> bool {
+        let Some(id) = FileId::of(file) else {
+            return true;
+        };
+        match self.seen.get(&id) {
+            Some(first) => {
+                info!(
+                    "Skipping {} (same file as {})",
+                    file.display(),
+                    first.display()
+                );
+                self.duplicates += 1;
+                false
+            }
+            None => {
+                self.seen.insert(id, file.to_path_buf());
+                true
+            }
+        }
+    }
 }
 
 pub fn is_video_file<P: AsRef<Path>>(path: P) -> bool {
@@ -90,6 +190,112 @@ pub fn generate_uuid_filename<P: AsRef<Path>, Q: AsRef<Path>>(
     }
 }
 
+/// `path` with symlinks resolved; for a file that does not exist yet, its
+/// resolved parent directory joined with the file name.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    if let Ok(resolved) = path.canonicalize() {
+        return Some(resolved);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// Whether `a` and `b` name the same file, through symlinks, `..` or hard
+/// links.
+pub fn same_file<P: AsRef<Path>, Q: AsRef<Path>>(a: P, b: Q) -> bool {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (a.metadata(), b.metadata()) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+    }
+    match (resolve(a), resolve(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Refuses to write `output` when it is the source `input`: sources are only
+/// ever read.
+pub fn ensure_not_source<P: AsRef<Path>, Q: AsRef<Path>>(input: P, output: Q) -> Result<()> {
+    let (input, output) = (input.as_ref(), output.as_ref());
+    if same_file(input, output) {
+        return Err(Error::validation(format!(
+            "Refusing to write {}: it is the source being read",
+            output.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Permission bits of an octal mode such as `"0644"` or `"664"`.
+pub fn parse_mode(mode: &str) -> Result<u32> {
+    u32::from_str_radix(mode.trim(), 8)
+        .ok()
+        .filter(|&bits| bits <= 0o7777)
+        .ok_or_else(|| {
+            Error::validation(format!(
+                "Invalid output_files.mode: {} (expected octal permissions such as 0644)",
+                mode
+            ))
+        })
+}
+
+/// The id of `name` in an `/etc/passwd` or `/etc/group` style `database`
+/// (`name:password:id:...`); a number is taken as the id itself.
+fn lookup_id(database: &str, name: &str) -> Option<u32> {
+    if let Ok(id) = name.parse() {
+        return Some(id);
+    }
+    database.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
+/// Gives `path` the permissions and owner of `output_files`.
+#[cfg(unix)]
+pub fn apply_output_files_config(path: &Path, config: &OutputFilesConfig) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(mode) = &config.mode {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(parse_mode(mode)?))?;
+    }
+    let id = |name: &Option<String>, database: &str| -> Result<Option<u32>> {
+        let Some(name) = name else {
+            return Ok(None);
+        };
+        let contents = std::fs::read_to_string(database).unwrap_or_default();
+        lookup_id(&contents, name)
+            .map(Some)
+            .ok_or_else(|| Error::validation(format!("Unknown user or group: {}", name)))
+    };
+    let owner = id(&config.owner, "/etc/passwd")?;
+    let group = id(&config.group, "/etc/group")?;
+    if owner.is_some() || group.is_some() {
+        std::os::unix::fs::chown(path, owner, group)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn apply_output_files_config(_path: &Path, config: &OutputFilesConfig) -> Result<()> {
+    if *config == OutputFilesConfig::default() {
+        return Ok(());
+    }
+    Err(Error::validation(
+        "output_files (permissions and ownership) is only supported on Unix",
+    ))
+}
+
 pub fn ensure_output_dir<P: AsRef<Path>>(path: P) -> Result<()> {
     let path = path.as_ref();
 
@@ -135,6 +341,36 @@ mod tests {
         assert!(!is_video_file("test"));
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn test_discovery_symlinks_and_hardlinks() {
+        let temp = tempfile::tempdir().unwrap();
+        let movies = temp.path().join("movies");
+        let library = temp.path().join("library");
+        std::fs::create_dir_all(&movies).unwrap();
+        std::fs::create_dir_all(&library).unwrap();
+        std::fs::write(movies.join("a.mkv"), b"a").unwrap();
+        std::fs::write(movies.join("b.mkv"), b"b").unwrap();
+        std::fs::hard_link(movies.join("a.mkv"), movies.join("a-copy.mkv")).unwrap();
+        std::os::unix::fs::symlink(&movies, library.join("movies")).unwrap();
+
+        let mut discovery = FileDiscovery::new();
+        assert_eq!(
+            discovery.find(&movies).unwrap(),
+            vec![movies.join("a-copy.mkv"), movies.join("b.mkv")]
+        );
+        assert_eq!(discovery.duplicates(), 1);
+        // Symlinked directories are not entered by default
+        assert!(discovery.find(&library).is_err());
+
+        let mut following = FileDiscovery::new().with_follow_symlinks(true);
+        assert_eq!(following.find(&library).unwrap().len(), 2);
+        assert!(following.find(movies.join("b.mkv")).unwrap().is_empty());
+
+        let mut all = FileDiscovery::new().with_allow_duplicates(true);
+        assert_eq!(all.find(&movies).unwrap().len(), 3);
+    }
+
     #[test]
     fn test_generate_uuid_filename() {
         let input = Path::new("/path/to/movie.mkv");
@@ -153,4 +389,54 @@ mod tests {
         assert_eq!(format_file_size(1_048_576), "1.00 MB");
         assert_eq!(format_file_size(1_073_741_824), "1.00 GB");
     }
+
+    #[test]
+    fn test_ensure_not_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("movie.mkv");
+        std::fs::write(&source, b"source").unwrap();
+
+        assert!(ensure_not_source(&source, &source).is_err());
+        let dotted = dir.path().join("sub").join("..").join("movie.mkv");
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        assert!(ensure_not_source(&source, &dotted).is_err());
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.mkv");
+            std::fs::hard_link(&source, &link).unwrap();
+            assert!(same_file(&source, &link));
+        }
+
+        assert!(ensure_not_source(&source, dir.path().join("movie_x265.mkv")).is_ok());
+        assert!(ensure_not_source(&source, "/dev/null").is_ok());
+        assert_eq!(std::fs::read(&source).unwrap(), b"source");
+    }
+
+    #[test]
+    fn test_output_files_config() {
+        assert_eq!(parse_mode("0644").unwrap(), 0o644);
+        assert_eq!(parse_mode("2775").unwrap(), 0o2775);
+        assert!(parse_mode("0984").is_err());
+        assert!(parse_mode("rw-r--r--").is_err());
+
+        let passwd = "root:x:0:0:root:/root:/bin/sh\nmedia:x:1001:1001::/srv/media:/bin/sh\n";
+        assert_eq!(lookup_id(passwd, "media"), Some(1001));
+        assert_eq!(lookup_id(passwd, "1500"), Some(1500));
+        assert_eq!(lookup_id(passwd, "nobody"), None);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let dir = tempfile::tempdir().unwrap();
+            let output = dir.path().join("movie.mkv");
+            std::fs::write(&output, b"encoded").unwrap();
+            let config = OutputFilesConfig {
+                mode: Some("0640".to_string()),
+                ..Default::default()
+            };
+            apply_output_files_config(&output, &config).unwrap();
+            let mode = output.metadata().unwrap().permissions().mode();
+            assert_eq!(mode & 0o7777, 0o640);
+        }
+    }
 }
@@ -0,0 +1,163 @@
+use crate::utils::Result;
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// Prefix of job directories under `app.temp_dir`
+pub const JOB_DIR_PREFIX: &str = "ven_job_";
+
+/// File in a job directory naming the process that owns the job
+const MARKER_FILE: &str = "job.marker";
+
+/// Temporary directory of a single encode, `<base>/ven_job_<id>`.
+///
+/// Extracted RPUs, HDR10+ metadata and caption files go in here, so runs of
+/// the same source or parallel jobs never share a file. The directory and
+/// everything in it are removed when the value is dropped, whether the job
+/// finished or failed, unless [`keep`](Self::keep) was called. A
+/// [`JobMarker`] inside records the owning process, so the directory of a
+/// crashed run can be told apart from a running one.
+#[derive(Debug)]
+pub struct JobTempDir {
+    id: String,
+    path: PathBuf,
+    kept: bool,
+}
+
+impl JobTempDir {
+    /// Creates the directory for a job writing `output`.
+    pub fn create<P: AsRef<Path>>(base: P, output: &Path) -> Result<Self> {
+        let id = Uuid::new_v4().simple().to_string();
+        let path = base.as_ref().join(format!("{}{}", JOB_DIR_PREFIX, id));
+        std::fs::create_dir_all(&path)?;
+
+        let marker = JobMarker {
+            pid: std::process::id(),
+            output_dir: output.parent().map(Path::to_path_buf),
+        };
+        std::fs::write(path.join(MARKER_FILE), marker.to_string())?;
+
+        debug!("Created job temp directory: {}", path.display());
+        Ok(Self {
+            id,
+            path,
+            kept: false,
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Leaves the directory in place when dropped (`--keep-temp`). Its marker
+    /// is removed, so temp sweeps only take it once it is older than
+    /// `cleanup.min_age_hours`.
+    pub fn keep(&mut self) {
+        let _ = std::fs::remove_file(self.path.join(MARKER_FILE));
+        self.kept = true;
+    }
+
+    /// Name for a temporary file kept outside the job directory (e.g. next to
+    /// the output): `<prefix>_<id>_<name>`.
+    pub fn scoped_name(&self, prefix: &str, name: &str) -> String {
+        format!("{}_{}_{}", prefix, self.id, name)
+    }
+}
+
+impl Drop for JobTempDir {
+    fn drop(&mut self) {
+        if self.kept {
+            info!("Kept job temp directory: {}", self.path.display());
+            return;
+        }
+        match std::fs::remove_dir_all(&self.path) {
+            Ok(()) => debug!("Removed job temp directory: {}", self.path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(
+                "Failed to remove job temp directory {}: {}",
+                self.path.display(),
+                e
+            ),
+        }
+    }
+}
+
+/// Contents of a job directory's marker file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobMarker {
+    pub pid: u32,
+    /// Directory of the job's output, where its `temp_encode_<id>_*` files live
+    pub output_dir: Option<PathBuf>,
+}
+
+impl JobMarker {
+    /// Reads the marker of the job directory `dir`, if it has a valid one.
+    pub fn read(dir: &Path) -> Option<Self> {
+        let contents = std::fs::read_to_string(dir.join(MARKER_FILE)).ok()?;
+        let mut pid = None;
+        let mut output_dir = None;
+        for line in contents.lines() {
+            match line.split_once('=') {
+                Some(("pid", value)) => pid = value.trim().parse().ok(),
+                Some(("output_dir", value)) => output_dir = Some(PathBuf::from(value)),
+                _ => {}
+            }
+        }
+        Some(Self {
+            pid: pid?,
+            output_dir,
+        })
+    }
+}
+
+impl std::fmt::Display for JobMarker {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pid={}", self.pid)?;
+        if let Some(dir) = &self.output_dir {
+            writeln!(f, "output_dir={}", dir.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_job_dir_is_unique_and_removed_on_drop() {
+        let base = tempfile::tempdir().unwrap();
+        let output = base.path().join("out").join("movie.mkv");
+        let first = JobTempDir::create(base.path(), &output).unwrap();
+        let second = JobTempDir::create(base.path(), &output).unwrap();
+        assert_ne!(first.path(), second.path());
+        assert_eq!(
+            first.scoped_name("temp_encode", "movie.mkv"),
+            format!("temp_encode_{}_movie.mkv", first.id())
+        );
+        assert_eq!(
+            JobMarker::read(first.path()),
+            Some(JobMarker {
+                pid: std::process::id(),
+                output_dir: Some(base.path().join("out")),
+            })
+        );
+
+        let path = first.path().to_path_buf();
+        std::fs::write(path.join("rpu.bin"), b"rpu").unwrap();
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.path().exists());
+
+        let mut kept = second;
+        kept.keep();
+        let path = kept.path().to_path_buf();
+        drop(kept);
+        assert!(path.exists());
+        assert_eq!(JobMarker::read(&path), None);
+    }
+}
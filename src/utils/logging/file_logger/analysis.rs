@@ -346,30 +346,35 @@ fn log_hdr10plus_metadata<W: Write>(
         writeln!(writer, "  Metadata File Size: {} bytes", file_size)?;
     }
 
-    // Access metadata fields directly since it's not optional
-    let metadata = &hdr10plus_result.metadata;
-    writeln!(
-        writer,
-        "  Metadata Version: {}",
-        metadata.json_info.version
-    )?;
-    writeln!(
-        writer,
-        "  HDR10+ Profile: {}",
-        metadata.json_info.hdr10plus_profile
-    )?;
-    writeln!(
-        writer,
-        "  Frame Count: {}",
-        metadata.get_frame_count()
-    )?;
+    let summary = &hdr10plus_result.summary;
+    writeln!(writer, "  Metadata Version: {}", summary.version)?;
+    let profile = summary.detected_profile();
+    match summary.declared_profile() {
+        Some(declared) if declared != profile => writeln!(
+            writer,
+            "  HDR10+ Profile: {} (declared {}, but {} frames carry tone mapping curves)",
+            profile.as_str(),
+            declared.as_str(),
+            summary.tone_mapping_frames
+        )?,
+        _ => writeln!(writer, "  HDR10+ Profile: {}", profile.as_str())?,
+    }
     writeln!(
         writer,
-        "  Scene Count: {}",
-        metadata.get_scene_count()
+        "  x265 --dhdr10-opt: {}",
+        if profile.needs_dhdr10_opt() {
+            "Yes"
+        } else {
+            "No"
+        }
     )?;
+    writeln!(writer, "  Frame Count: {}", summary.frame_count)?;
+    writeln!(writer, "  Scene Count: {}", summary.scene_count)?;
+    if hdr10plus_result.metadata.is_none() {
+        writeln!(writer, "  Parsed: summary only (large metadata file)")?;
+    }
 
-    if let Some(ref tool_info) = metadata.tool_info {
+    if let Some(ref tool_info) = summary.tool_info {
         writeln!(
             writer,
             "  Extracted with: {} v{}",
@@ -377,41 +382,40 @@ fn log_hdr10plus_metadata<W: Write>(
         )?;
     }
 
-    // Scene information (first few scenes)
-    if !metadata.scene_info.is_empty() {
-        for (_i, scene) in metadata.scene_info.iter().enumerate().take(3) {
-            writeln!(
-                writer,
-                "    Scene {}: Frame {}, Avg RGB: {}, Target Lum: {} nits",
-                scene.scene_id,
-                scene.sequence_frame_index,
-                scene.luminance_parameters.average_rgb,
-                scene.targeted_system_display_maximum_luminance
-            )?;
-        }
-        if metadata.scene_info.len() > 3 {
-            writeln!(
-                writer,
-                "    ... and {} more frames",
-                metadata.scene_info.len() - 3
-            )?;
-        }
+    // Scene information (first few frames)
+    for scene in &summary.first_frames {
+        writeln!(
+            writer,
+            "    Scene {}: Frame {}, Avg RGB: {}, Target Lum: {} nits",
+            scene.scene_id,
+            scene.sequence_frame_index,
+            scene.luminance_parameters.average_rgb,
+            scene.targeted_system_display_maximum_luminance
+        )?;
+    }
+    let listed = summary.first_frames.len() as u32;
+    if summary.frame_count > listed {
+        writeln!(
+            writer,
+            "    ... and {} more frames",
+            summary.frame_count - listed
+        )?;
     }
 
     // Tone mapping summary
-    if metadata.has_tone_mapping_curves() {
+    if summary.tone_mapping_frames > 0 {
         writeln!(
             writer,
             "  Frames with tone mapping: {}",
-            metadata.get_tone_mapping_frame_count()
+            summary.tone_mapping_frames
         )?;
     }
 
     // Peak brightness info
-    if let Some(peak) = metadata.get_peak_brightness() {
+    if let Some(peak) = summary.peak_brightness {
         writeln!(writer, "  Peak MaxSCL: {}", peak)?;
     }
-    if let Some(avg) = metadata.get_average_brightness() {
+    if let Some(avg) = summary.average_brightness {
         writeln!(writer, "  Average RGB: {:.2}", avg)?;
     }
 
@@ -74,6 +74,42 @@ pub fn log_crop_detection_results<W: Write>(
     Ok(())
 }
 
+/// Logs the crop each sample returned with its aspect ratio, marking the
+/// samples that disagree with the applied crop
+pub fn log_crop_samples<W: Write>(
+    writer: &mut W,
+    analysis: &crate::analysis::CropAnalysisResult,
+) -> crate::utils::Result<()> {
+    writeln!(writer, "CROP SAMPLES:")?;
+    for sample in &analysis.sample_results {
+        match &sample.crop_values {
+            Some(crop) => {
+                let differs = analysis
+                    .crop_values
+                    .as_ref()
+                    .is_some_and(|applied| applied != crop);
+                writeln!(
+                    writer,
+                    "  {:>8.1}s: {} ({:.2}:1){}",
+                    sample.timestamp,
+                    crop.to_ffmpeg_string(),
+                    crop.aspect_ratio(),
+                    if differs { " differs" } else { "" }
+                )?;
+            }
+            None => writeln!(writer, "  {:>8.1}s: no crop detected", sample.timestamp)?,
+        }
+    }
+    writeln!(
+        writer,
+        "  Aspect Ratio Variation: {:.1}%",
+        analysis.aspect_variation
+    )?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
 /// Parses crop statistics from a crop string
 /// Returns (original_width, original_height, cropped_width, cropped_height, percent_removed)
 fn parse_crop_statistics(crop_str: &str) -> Option<(u32, u32, u32, u32, f32)> {
@@ -133,6 +169,46 @@ mod tests {
         assert_eq!(crop_h, 1600);
     }
 
+    #[test]
+    fn test_log_crop_samples() {
+        use crate::analysis::crop::CropSampleResult;
+        use crate::analysis::{CropAnalysisResult, CropValues};
+
+        let sample = |timestamp: f64, crop: CropValues| CropSampleResult {
+            sample_point: String::new(),
+            timestamp,
+            crop_values: Some(crop),
+            raw_output: String::new(),
+        };
+        let analysis = CropAnalysisResult {
+            crop_values: None,
+            detection_method: "varying_aspect_ratio_35%_variation".to_string(),
+            confidence: 50.0,
+            pixel_change_percent: 25.9,
+            sample_results: vec![
+                sample(600.0, CropValues::new(1920, 800, 0, 140)),
+                sample(1200.0, CropValues::new(1920, 1080, 0, 0)),
+            ],
+            aspect_variation: 35.0,
+        };
+        let mut buffer = Vec::new();
+        log_crop_samples(&mut buffer, &analysis).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("     600.0s: 1920:800:0:140 (2.40:1)\n"));
+        assert!(output.contains("    1200.0s: 1920:1080:0:0 (1.78:1)\n"));
+        assert!(output.contains("Aspect Ratio Variation: 35.0%"));
+
+        let applied = CropAnalysisResult {
+            crop_values: Some(CropValues::new(1920, 800, 0, 140)),
+            ..analysis
+        };
+        let mut buffer = Vec::new();
+        log_crop_samples(&mut buffer, &applied).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("(1.78:1) differs"));
+        assert!(!output.contains("(2.40:1) differs"));
+    }
+
     #[test]
     fn test_parse_crop_statistics_invalid() {
         assert!(parse_crop_statistics("invalid").is_none());
@@ -73,11 +73,24 @@ pub fn log_encoding_progress<W: Write>(writer: &mut W, message: &str) -> crate::
     Ok(())
 }
 
+/// Logs the last lines FFmpeg wrote to stderr
+pub fn log_ffmpeg_errors<W: Write>(writer: &mut W, lines: &[String]) -> crate::utils::Result<()> {
+    writeln!(writer, "FFMPEG STDERR:")?;
+    for line in lines {
+        writeln!(writer, "  {}", line)?;
+    }
+    writeln!(writer)?;
+
+    writer.flush()?;
+    Ok(())
+}
+
 /// Logs encoding completion status
 pub fn log_encoding_complete<W: Write>(
     writer: &mut W,
     success: bool,
     duration: std::time::Duration,
+    phases: &[(&str, std::time::Duration)],
     output_size: Option<u64>,
     exit_code: Option<i32>,
 ) -> crate::utils::Result<()> {
@@ -88,6 +101,11 @@ pub fn log_encoding_complete<W: Write>(
         if success { "SUCCESS" } else { "FAILED" }
     )?;
     writeln!(writer, "  Duration: {:.2}s", duration.as_secs_f64())?;
+    if phases.len() > 1 {
+        for (name, elapsed) in phases {
+            writeln!(writer, "    {}: {:.2}s", name, elapsed.as_secs_f64())?;
+        }
+    }
 
     if let Some(size) = output_size {
         writeln!(writer, "  Output Size: {:.2} MB", size as f64 / 1_048_576.0)?;
@@ -105,6 +123,30 @@ pub fn log_encoding_complete<W: Write>(
     Ok(())
 }
 
+/// Ends the log of an encode that stopped before FFmpeg finished (an error in
+/// analysis, muxing or metadata injection, or a cancel), so every log ends
+/// with a result block
+pub fn log_encoding_aborted<W: Write>(
+    writer: &mut W,
+    error: &str,
+    cancelled: bool,
+) -> crate::utils::Result<()> {
+    writeln!(writer, "ENCODING RESULT:")?;
+    writeln!(
+        writer,
+        "  Status: {}",
+        if cancelled { "CANCELLED" } else { "FAILED" }
+    )?;
+    writeln!(writer, "  Error: {}", error)?;
+
+    let timestamp = Utc::now().format("%Y-%m-%d %H:%M:%S UTC");
+    writeln!(writer, "  Completed: {}", timestamp)?;
+    writeln!(writer)?;
+
+    writer.flush()?;
+    Ok(())
+}
+
 /// Logs the raw FFmpeg command
 pub fn log_ffmpeg_command<W: Write>(
     writer: &mut W,
@@ -147,6 +189,10 @@ mod tests {
             &mut buffer,
             true,
             Duration::from_secs(120),
+            &[
+                ("encode", Duration::from_secs(100)),
+                ("metadata injection", Duration::from_secs(20)),
+            ],
             Some(104_857_600),
             Some(0),
         );
@@ -154,6 +200,7 @@ mod tests {
         let output = String::from_utf8(buffer).unwrap();
         assert!(output.contains("SUCCESS"));
         assert!(output.contains("120.00s"));
+        assert!(output.contains("    metadata injection: 20.00s"));
         assert!(output.contains("100.00 MB"));
         assert!(output.contains("Exit Code: 0"));
     }
@@ -165,6 +212,7 @@ mod tests {
             &mut buffer,
             false,
             Duration::from_secs(30),
+            &[],
             None,
             Some(1),
         );
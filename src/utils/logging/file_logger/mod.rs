@@ -3,15 +3,21 @@
 pub mod analysis;
 pub mod crop;
 pub mod encoding;
+pub mod verification;
 
+use crate::events::{EncodeEvent, EventSink};
 use std::fs::File;
 use std::io::BufWriter;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+#[derive(Clone)]
 pub struct FileLogger {
     writer: Arc<Mutex<BufWriter<File>>>,
     log_path: PathBuf,
+    /// Whether the result block has been written
+    completed: Arc<AtomicBool>,
 }
 
 impl FileLogger {
@@ -22,7 +28,11 @@ impl FileLogger {
         let file = File::create(&log_path)?;
         let writer = Arc::new(Mutex::new(BufWriter::new(file)));
 
-        Ok(Self { writer, log_path })
+        Ok(Self {
+            writer,
+            log_path,
+            completed: Arc::new(AtomicBool::new(false)),
+        })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -89,6 +99,14 @@ impl FileLogger {
         )
     }
 
+    pub fn log_crop_samples(
+        &self,
+        analysis: &crate::analysis::CropAnalysisResult,
+    ) -> crate::utils::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        crop::log_crop_samples(&mut *writer, analysis)
+    }
+
     pub fn log_encoding_progress(&self, message: &str) -> crate::utils::Result<()> {
         let mut writer = self.writer.lock().unwrap();
         encoding::log_encoding_progress(&mut *writer, message)
@@ -98,11 +116,30 @@ impl FileLogger {
         &self,
         success: bool,
         duration: std::time::Duration,
+        phases: &[(&str, std::time::Duration)],
         output_size: Option<u64>,
         exit_code: Option<i32>,
     ) -> crate::utils::Result<()> {
         let mut writer = self.writer.lock().unwrap();
-        encoding::log_encoding_complete(&mut *writer, success, duration, output_size, exit_code)
+        self.completed.store(true, Ordering::SeqCst);
+        encoding::log_encoding_complete(
+            &mut *writer,
+            success,
+            duration,
+            phases,
+            output_size,
+            exit_code,
+        )
+    }
+
+    /// Writes the result block of a failed or cancelled encode, unless the
+    /// FFmpeg result was already logged.
+    pub fn log_encoding_aborted(&self, error: &str, cancelled: bool) -> crate::utils::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if self.completed.swap(true, Ordering::SeqCst) {
+            return Ok(());
+        }
+        encoding::log_encoding_aborted(&mut *writer, error, cancelled)
     }
 
     pub fn log_ffmpeg_command(
@@ -114,11 +151,40 @@ impl FileLogger {
         encoding::log_ffmpeg_command(&mut *writer, ffmpeg_path, args)
     }
 
+    pub fn log_ffmpeg_errors(&self, lines: &[String]) -> crate::utils::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        encoding::log_ffmpeg_errors(&mut *writer, lines)
+    }
+
+    pub fn log_stream_verification(
+        &self,
+        results: &[crate::stream::verification::StreamChecksum],
+    ) -> crate::utils::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        verification::log_stream_verification(&mut *writer, results)
+    }
+
     pub fn get_log_path(&self) -> &Path {
         &self.log_path
     }
 }
 
+impl EventSink for FileLogger {
+    fn handle(&self, event: &EncodeEvent) {
+        if let EncodeEvent::Failed {
+            error, cancelled, ..
+        } = event
+        {
+            let _ = self.log_encoding_aborted(error, *cancelled);
+            return;
+        }
+        // Progress ticks would drown out the log; only the 10% snapshots are kept
+        if event.level().is_some() || matches!(event, EncodeEvent::ProgressSnapshot(_)) {
+            let _ = self.log_encoding_progress(&event.to_string());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -150,4 +216,25 @@ mod tests {
         // Clean up
         let _ = std::fs::remove_file(logger.get_log_path());
     }
+
+    #[test]
+    fn test_failed_encode_ends_with_result_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let logger = FileLogger::new(dir.path().join("movie.mkv")).unwrap();
+        logger.log_encoding_progress("Analyzing").unwrap();
+        let failed = EncodeEvent::Failed {
+            output: dir.path().join("movie.mkv"),
+            error: "Cancelled: interrupted".to_string(),
+            cancelled: true,
+        };
+        logger.handle(&failed);
+        // Only one result block, however often the failure is reported
+        logger.handle(&failed);
+
+        let log = std::fs::read_to_string(logger.get_log_path()).unwrap();
+        assert!(log.contains("  Status: CANCELLED\n  Error: Cancelled: interrupted\n"));
+        assert_eq!(log.matches("ENCODING RESULT:").count(), 1);
+        let last = log.trim_end().lines().last().unwrap();
+        assert!(last.starts_with("  Completed: "));
+    }
 }
@@ -0,0 +1,63 @@
+//! Stream verification logging functionality
+
+use crate::stream::verification::StreamChecksum;
+use std::io::Write;
+
+/// Logs per-stream checksum comparisons for copied streams
+pub fn log_stream_verification<W: Write>(
+    writer: &mut W,
+    results: &[StreamChecksum],
+) -> crate::utils::Result<()> {
+    writeln!(writer, "STREAM VERIFICATION:")?;
+
+    if results.is_empty() {
+        writeln!(writer, "  No copied audio/subtitle streams to verify")?;
+    }
+
+    for result in results {
+        writeln!(
+            writer,
+            "  {} #{} -> {}: {}",
+            result.stream_type,
+            result.source_index,
+            result.output_specifier,
+            if result.matches() {
+                "MATCH"
+            } else {
+                "MISMATCH"
+            }
+        )?;
+        writeln!(writer, "    Source MD5: {}", result.source_md5)?;
+        writeln!(
+            writer,
+            "    Output MD5: {}",
+            result.output_md5.as_deref().unwrap_or("unavailable")
+        )?;
+    }
+    writeln!(writer)?;
+
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_stream_verification() {
+        let results = vec![StreamChecksum {
+            stream_type: "audio",
+            source_index: 1,
+            output_specifier: "0:a:0".to_string(),
+            source_md5: "abc".to_string(),
+            output_md5: Some("def".to_string()),
+        }];
+
+        let mut buffer = Vec::new();
+        log_stream_verification(&mut buffer, &results).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+        assert!(output.contains("STREAM VERIFICATION:"));
+        assert!(output.contains("audio #1 -> 0:a:0: MISMATCH"));
+    }
+}
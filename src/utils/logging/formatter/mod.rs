@@ -12,7 +12,7 @@ use tracing_subscriber::fmt::{format::Writer, FmtContext, FormatEvent, FormatFie
 
 use crate::utils::logging::text_utils;
 use filters::should_show_message;
-use levels::{determine_processing_level, ProcessingLevel};
+use levels::{ProcessingLevel, TREE_FIELD};
 use styling::{format_level, get_tree_prefix, style_message};
 
 pub struct CleanFormatter {
@@ -28,8 +28,12 @@ impl CleanFormatter {
         }
     }
 
-    fn format_message(&self, message: &str, metadata_level: &Level) -> String {
-        let level = determine_processing_level(message);
+    fn format_message(
+        &self,
+        message: &str,
+        level: ProcessingLevel,
+        metadata_level: &Level,
+    ) -> String {
         let prefix = get_tree_prefix(level);
 
         // Get level indicator string (for WARN/ERROR)
@@ -77,8 +81,6 @@ impl CleanFormatter {
                         }
                     } else if message.contains("External metadata tools are ready") {
                         "HDR/DV metadata tools ready".to_string()
-                    } else if message.contains("Getting video metadata for:") {
-                        "Analyzing video metadata".to_string()
                     } else {
                         message.to_string()
                     };
@@ -134,11 +136,10 @@ where
         event: &tracing::Event<'_>,
     ) -> std_fmt::Result {
         let metadata = event.metadata();
-        let message = {
-            let mut visitor = MessageVisitor::default();
-            event.record(&mut visitor);
-            visitor.message
-        };
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        let message = visitor.message;
+        let level = ProcessingLevel::from_tree(visitor.tree.as_deref());
 
         // Filter out noisy messages
         if !should_show_message(&message) {
@@ -161,7 +162,7 @@ where
         }
 
         // Add formatted message (which now includes the level indicator in the appropriate position)
-        output.push_str(&self.format_message(&message, metadata.level()));
+        output.push_str(&self.format_message(&message, level, metadata.level()));
 
         writeln!(writer, "{}", output)
     }
@@ -170,9 +171,18 @@ where
 #[derive(Default)]
 struct MessageVisitor {
     message: String,
+    tree: Option<String>,
 }
 
 impl tracing::field::Visit for MessageVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == TREE_FIELD {
+            self.tree = Some(value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
     fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
         if field.name() == "message" {
             self.message = format!("{:?}", value).trim_matches('"').to_string();
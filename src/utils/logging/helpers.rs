@@ -2,7 +2,7 @@
 
 /// Logs the start of an encoding operation
 pub fn log_encoding_start(input: &str, output: &str, profile: &str, mode: &str) {
-    tracing::info!(
+    crate::log_stage!(
         "Starting encoding: {} -> {} (profile: {}, mode: {})",
         input,
         output,
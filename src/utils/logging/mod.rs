@@ -9,38 +9,65 @@
 mod file_logger;
 mod formatter;
 mod helpers;
+mod system_log;
+mod tail;
 mod text_utils;
 
 // Re-export public types and functions for backward compatibility
 pub use file_logger::FileLogger;
+pub use formatter::levels::ProcessingLevel;
 pub use helpers::{
     log_analysis_result, log_crop_detection, log_encoding_complete, log_encoding_start,
     log_profile_selection,
 };
+pub use system_log::SystemLogLayer;
+pub use tail::{find_log, newest_log, LogTail};
 
+use crate::config::types::{LogBackend, LoggingConfig};
 use tracing::Level;
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 use formatter::CleanFormatter;
 
+/// Logs an info message at the top of the console tree (one per input file).
+#[macro_export]
+macro_rules! log_root {
+    ($($arg:tt)+) => {
+        ::tracing::info!(tree = "root", $($arg)+)
+    };
+}
+
+/// Logs an info message for a major processing stage (`●` in the console tree).
+#[macro_export]
+macro_rules! log_stage {
+    ($($arg:tt)+) => {
+        ::tracing::info!(tree = "stage", $($arg)+)
+    };
+}
+
+/// Logs an info message for a step within a stage.
+#[macro_export]
+macro_rules! log_step {
+    ($($arg:tt)+) => {
+        ::tracing::info!(tree = "step", $($arg)+)
+    };
+}
+
 /// Sets up the logging system with the specified configuration
 ///
 /// # Arguments
-/// * `level` - Log level (trace, debug, info, warn, error)
-/// * `show_timestamps` - Whether to show timestamps in console output
-/// * `colored` - Whether to use colored output in console
+/// * `level` - Log level (trace, debug, info, warn, error), overriding `config.level`
+/// * `config` - Console formatting and the syslog/journald backend
 ///
 /// # Examples
 /// ```no_run
-/// use ffmpeg_autoencoder::utils::logging::setup_logging;
+/// use ven::config::Config;
+/// use ven::utils::logging::setup_logging;
 ///
-/// setup_logging("info", false, true).expect("Failed to setup logging");
+/// let config = Config::default();
+/// setup_logging("info", &config.logging).expect("Failed to setup logging");
 /// ```
-pub fn setup_logging(
-    level: &str,
-    show_timestamps: bool,
-    colored: bool,
-) -> crate::utils::Result<()> {
+pub fn setup_logging(level: &str, config: &LoggingConfig) -> crate::utils::Result<()> {
     let level = match level.to_lowercase().as_str() {
         "trace" => Level::TRACE,
         "debug" => Level::DEBUG,
@@ -54,16 +81,24 @@ pub fn setup_logging(
         .with_default_directive(level.into())
         .from_env_lossy();
 
+    let system_layer = match config.backend {
+        LogBackend::Stdout => None,
+        backend => Some(SystemLogLayer::connect(backend)?),
+    };
+
     // Use our clean formatter for better console output
-    let formatter = CleanFormatter::new(show_timestamps, colored);
-    let fmt_layer = fmt::layer()
-        .with_target(false)
-        .with_level(false) // We handle level formatting in our custom formatter
-        .event_format(formatter);
+    let formatter = CleanFormatter::new(config.show_timestamps, config.colored_output);
+    let fmt_layer = (system_layer.is_none() || config.console).then(|| {
+        fmt::layer()
+            .with_target(false)
+            .with_level(false) // We handle level formatting in our custom formatter
+            .event_format(formatter)
+    });
 
     tracing_subscriber::registry()
         .with(env_filter)
         .with(fmt_layer)
+        .with(system_layer)
         .init();
 
     Ok(())
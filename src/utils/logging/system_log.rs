@@ -0,0 +1,204 @@
+//! Sending log events to syslog or journald for daemon deployments
+//! (`logging.backend`). Both are written to their Unix socket directly:
+//! syslog gets RFC 3164 lines on `/dev/log` with the event's fields appended
+//! as `key=value`, journald its native protocol with every field as a
+//! journal field (`TREE=stage`, ...).
+
+use crate::config::types::LogBackend;
+use crate::utils::{Error, Result};
+use std::fmt::{Debug, Write};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+
+const SYSLOG_SOCKET: &str = "/dev/log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Syslog facility of the messages (daemon).
+const FACILITY_DAEMON: u8 = 3;
+
+/// Name the messages are logged under (`journalctl -t ven`).
+const IDENTIFIER: &str = env!("CARGO_PKG_NAME");
+
+/// Syslog severity of a tracing level.
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+/// The message and other fields of an event.
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+}
+
+/// RFC 3164 line without the timestamp, which the syslog daemon adds.
+fn syslog_message(level: &Level, pid: u32, event: &EventFields) -> String {
+    let mut line = format!(
+        "<{}>{}[{}]: {}",
+        FACILITY_DAEMON * 8 + severity(level),
+        IDENTIFIER,
+        pid,
+        event.message
+    );
+    for (name, value) in &event.fields {
+        let _ = write!(line, " {}={}", name, value);
+    }
+    line
+}
+
+/// Journal field name of a tracing field: upper case letters, digits and
+/// underscores, not starting with an underscore (reserved for journald).
+fn journal_field_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    name.trim_start_matches('_').to_string()
+}
+
+fn push_journal_field(datagram: &mut Vec<u8>, name: &str, value: &str) {
+    datagram.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        // Multi-line values are sent as their length and raw bytes
+        datagram.push(b'\n');
+        datagram.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        datagram.push(b'=');
+    }
+    datagram.extend_from_slice(value.as_bytes());
+    datagram.push(b'\n');
+}
+
+/// Datagram of the journald native protocol.
+fn journald_message(level: &Level, target: &str, event: &EventFields) -> Vec<u8> {
+    let mut datagram = Vec::new();
+    push_journal_field(&mut datagram, "MESSAGE", &event.message);
+    push_journal_field(&mut datagram, "PRIORITY", &severity(level).to_string());
+    push_journal_field(&mut datagram, "SYSLOG_IDENTIFIER", IDENTIFIER);
+    push_journal_field(&mut datagram, "TARGET", target);
+    for (name, value) in &event.fields {
+        let name = journal_field_name(name);
+        if !name.is_empty() {
+            push_journal_field(&mut datagram, &name, value);
+        }
+    }
+    datagram
+}
+
+/// Layer sending every event that passes the log level to syslog or journald.
+/// A message the daemon does not take is dropped.
+pub struct SystemLogLayer {
+    backend: LogBackend,
+    #[cfg(unix)]
+    socket: std::os::unix::net::UnixDatagram,
+}
+
+impl SystemLogLayer {
+    /// Connects to the socket of `backend` (syslog or journald).
+    #[cfg(unix)]
+    pub fn connect(backend: LogBackend) -> Result<Self> {
+        let path = match backend {
+            LogBackend::Syslog => SYSLOG_SOCKET,
+            LogBackend::Journald => JOURNALD_SOCKET,
+            LogBackend::Stdout => {
+                return Err(Error::validation("logging.backend stdout has no socket"))
+            }
+        };
+        let socket = std::os::unix::net::UnixDatagram::unbound()?;
+        socket.connect(path).map_err(|e| {
+            Error::validation(format!(
+                "logging.backend {}: cannot connect to {}: {}",
+                backend, path, e
+            ))
+        })?;
+        Ok(Self { backend, socket })
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect(backend: LogBackend) -> Result<Self> {
+        Err(Error::validation(format!(
+            "logging.backend {} is only available on Unix",
+            backend
+        )))
+    }
+}
+
+impl<S: Subscriber> Layer<S> for SystemLogLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        let metadata = event.metadata();
+        let datagram = match self.backend {
+            LogBackend::Syslog => {
+                syslog_message(metadata.level(), std::process::id(), &fields).into_bytes()
+            }
+            LogBackend::Journald => journald_message(metadata.level(), metadata.target(), &fields),
+            LogBackend::Stdout => return,
+        };
+        #[cfg(unix)]
+        let _ = self.socket.send(&datagram);
+        #[cfg(not(unix))]
+        let _ = datagram;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_log_messages() {
+        let event = EventFields {
+            message: "Encoding movie.mkv".to_string(),
+            fields: vec![("tree".to_string(), "stage".to_string())],
+        };
+        assert_eq!(
+            syslog_message(&Level::WARN, 42, &event),
+            "<28>ven[42]: Encoding movie.mkv tree=stage"
+        );
+
+        let datagram = journald_message(&Level::INFO, "ven::processing", &event);
+        assert_eq!(
+            String::from_utf8(datagram).unwrap(),
+            "MESSAGE=Encoding movie.mkv\nPRIORITY=6\nSYSLOG_IDENTIFIER=ven\n\
+             TARGET=ven::processing\nTREE=stage\n"
+        );
+
+        let mut multiline = Vec::new();
+        push_journal_field(&mut multiline, "MESSAGE", "a\nb");
+        assert_eq!(multiline, b"MESSAGE\n\x03\0\0\0\0\0\0\0a\nb\n");
+        assert_eq!(journal_field_name("_source.file"), "SOURCE_FILE");
+    }
+}
@@ -0,0 +1,170 @@
+//! Following the per-file log of a running encode (`logs tail`), e.g. from a
+//! second SSH session while the first one runs the batch.
+
+use crate::utils::{Error, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Line the result block of [`FileLogger`](super::FileLogger) ends with, for a
+/// finished encode as well as a failed or cancelled one
+const COMPLETED_PREFIX: &str = "  Completed: ";
+
+/// The log of `target`: a `.log` file itself, the log next to an output
+/// path, or for a directory the most recently written `.log` in it.
+pub fn find_log(target: &Path) -> Result<PathBuf> {
+    if target.is_dir() {
+        return newest_log(target)?
+            .ok_or_else(|| Error::validation(format!("No encode log in {}", target.display())));
+    }
+    let log = if target.extension().is_some_and(|ext| ext == "log") {
+        target.to_path_buf()
+    } else {
+        target.with_extension("log")
+    };
+    if !log.is_file() {
+        return Err(Error::validation(format!(
+            "No encode log at {} (has the encode started?)",
+            log.display()
+        )));
+    }
+    Ok(log)
+}
+
+/// The most recently modified `.log` directly in `dir`.
+pub fn newest_log(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut newest: Option<(SystemTime, PathBuf)> = None;
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "log") {
+            continue;
+        }
+        let Ok(modified) = path.metadata().and_then(|m| m.modified()) else {
+            continue;
+        };
+        if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+            newest = Some((modified, path));
+        }
+    }
+    Ok(newest.map(|(_, path)| path))
+}
+
+/// Reads the lines appended to a log since the last [`LogTail::poll`].
+pub struct LogTail {
+    path: PathBuf,
+    offset: u64,
+    partial: String,
+    finished: bool,
+    /// When the log last grew
+    grown: Instant,
+}
+
+impl LogTail {
+    /// Starts at the end of `path`, returning its last `lines` lines.
+    pub fn open(path: &Path, lines: usize) -> Result<(Self, Vec<String>)> {
+        let mut tail = Self {
+            path: path.to_path_buf(),
+            offset: 0,
+            partial: String::new(),
+            finished: false,
+            grown: Instant::now(),
+        };
+        let mut existing = tail.poll()?;
+        let skip = existing.len().saturating_sub(lines);
+        existing.drain(..skip);
+        Ok((tail, existing))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the log has reached the encode's result block.
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// How long the log has not grown.
+    pub fn idle(&self) -> Duration {
+        self.grown.elapsed()
+    }
+
+    /// Complete lines written since the last call. A log that shrank was
+    /// rewritten by a new encode of the same output and is read from the start.
+    pub fn poll(&mut self) -> Result<Vec<String>> {
+        let mut file = File::open(&self.path)?;
+        if file.metadata()?.len() < self.offset {
+            self.offset = 0;
+            self.partial.clear();
+            self.finished = false;
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        let mut bytes = Vec::new();
+        self.offset += file.read_to_end(&mut bytes)? as u64;
+        if !bytes.is_empty() {
+            self.grown = Instant::now();
+        }
+        self.partial.push_str(&String::from_utf8_lossy(&bytes));
+
+        let Some(end) = self.partial.rfind('\n') else {
+            return Ok(Vec::new());
+        };
+        let lines: Vec<String> = self.partial[..end].lines().map(str::to_string).collect();
+        self.partial.drain(..=end);
+        if lines.iter().any(|line| line.starts_with(COMPLETED_PREFIX)) {
+            self.finished = true;
+        }
+        Ok(lines)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_log_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("movie.mkv");
+        let log = dir.path().join("movie.log");
+        std::fs::write(&log, "one\ntwo\nthree\n[12:00:00] Progress: 10").unwrap();
+        assert_eq!(find_log(&output).unwrap(), log);
+        assert_eq!(find_log(dir.path()).unwrap(), log);
+        assert!(find_log(&dir.path().join("other.mkv")).is_err());
+
+        let (mut tail, last) = LogTail::open(&log, 2).unwrap();
+        assert_eq!(last, ["two", "three"]);
+        assert!(tail.poll().unwrap().is_empty());
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        write!(
+            file,
+            "%\nENCODING RESULT:\n{}2026-01-01\n",
+            COMPLETED_PREFIX
+        )
+        .unwrap();
+        let lines = tail.poll().unwrap();
+        assert_eq!(lines[0], "[12:00:00] Progress: 10%");
+        assert_eq!(lines.len(), 3);
+        assert!(tail.finished());
+
+        // A new encode of the same output starts the log over
+        std::fs::write(&log, "new\n").unwrap();
+        assert_eq!(tail.poll().unwrap(), ["new"]);
+        assert!(!tail.finished());
+        assert!(tail.idle() < Duration::from_secs(60));
+
+        // A failed or cancelled encode ends its log the same way
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+        write!(
+            file,
+            "ENCODING RESULT:\n  Status: CANCELLED\n{}2026-01-01\n",
+            COMPLETED_PREFIX
+        )
+        .unwrap();
+        assert_eq!(tail.poll().unwrap().len(), 3);
+        assert!(tail.finished());
+    }
+}
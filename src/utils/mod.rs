@@ -1,11 +1,20 @@
+pub mod cancel;
+pub mod debug_bundle;
+pub mod disk;
 pub mod error;
 pub mod ffmpeg;
 pub mod filesystem;
+pub mod job_dir;
 pub mod logging;
+pub mod temp_sweep;
 pub mod tool_runner;
 
-pub use error::{Error, Result};
+pub use cancel::CancelToken;
+pub use debug_bundle::write_debug_bundle;
+pub use error::{Error, Result, ToolFailureKind};
 pub use ffmpeg::FfmpegWrapper;
-pub use filesystem::{find_video_files, generate_uuid_filename};
+pub use filesystem::{find_video_files, generate_uuid_filename, FileDiscovery};
+pub use job_dir::JobTempDir;
 pub use logging::{setup_logging, FileLogger};
-pub use tool_runner::{ToolConfig, ToolRunner};
+pub use temp_sweep::{sweep_on_startup, TempSweep};
+pub use tool_runner::{ToolConfig, ToolInput, ToolProgress, ToolRunner};
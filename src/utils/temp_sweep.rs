@@ -0,0 +1,340 @@
+//! Removal of temporary files left behind by runs that crashed or were killed.
+//!
+//! Job directories (`ven_job_<id>` under `app.temp_dir`) carry a
+//! [`JobMarker`] naming the process that owns them; a directory whose process
+//! is gone is stale, together with the `temp_encode_<id>_*`/`temp_mux_<id>_*`
+//! files next to its output. Artifacts without a job id (RPU `.bin` files,
+//! HDR10+ metadata JSON, temporary HEVC streams, joined Blu-ray playlists,
+//! encodes of older versions) are only removed once they are older than
+//! `cleanup.min_age_hours`.
+
+use crate::config::Config;
+use crate::utils::filesystem::format_file_size;
+use crate::utils::job_dir::{JobMarker, JOB_DIR_PREFIX};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+/// Temporary output prefixes that may carry a job id
+const JOB_FILE_PREFIXES: &[&str] = &["temp_encode_", "temp_mux_"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Artifact<'a> {
+    /// Belongs to the job with this id
+    Job(&'a str),
+    /// No job id to check, so only its age counts
+    Orphan,
+}
+
+/// What a temporary file name left by the pipeline looks like, if it is one.
+fn classify(name: &str) -> Option<Artifact<'_>> {
+    if let Some(rest) = JOB_FILE_PREFIXES
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+    {
+        let job_id = rest
+            .split_once('_')
+            .map(|(id, _)| id)
+            .filter(|id| id.len() == 32 && id.bytes().all(|b| b.is_ascii_hexdigit()));
+        return Some(job_id.map_or(Artifact::Orphan, Artifact::Job));
+    }
+
+    let is_rpu = name.ends_with(".rpu.bin")
+        || (name.ends_with(".bin") && (name.starts_with("rpu_") || name.contains("_rpu_")));
+    let is_hdr10plus = name.ends_with(".json") && name.contains("hdr10plus_metadata_");
+    let is_hevc = name.starts_with("temp_hevc_") && name.ends_with(".hevc");
+    let is_bluray = name.starts_with("bluray_") && name.ends_with(".m2ts");
+    (is_rpu || is_hdr10plus || is_hevc || is_bluray).then_some(Artifact::Orphan)
+}
+
+/// Whether process `pid` is still running; `None` where that cannot be told.
+fn process_running(pid: u32) -> Option<bool> {
+    cfg!(target_os = "linux").then(|| Path::new("/proc").join(pid.to_string()).exists())
+}
+
+/// Outcome of a [`TempSweep`].
+#[derive(Debug, Default)]
+pub struct SweepReport {
+    pub removed: Vec<PathBuf>,
+    pub reclaimed_bytes: u64,
+    /// Job directories left alone because their process still runs
+    pub active_jobs: usize,
+    pub errors: Vec<String>,
+    pub dry_run: bool,
+}
+
+impl SweepReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} stale temporary item(s), {} {}",
+            if self.dry_run {
+                "Would remove"
+            } else {
+                "Removed"
+            },
+            self.removed.len(),
+            if self.dry_run {
+                "reclaiming"
+            } else {
+                "reclaimed"
+            },
+            format_file_size(self.reclaimed_bytes)
+        )
+    }
+}
+
+/// Finds and removes temporary files of earlier runs in `app.temp_dir` and
+/// any extra directories (e.g. output folders).
+pub struct TempSweep {
+    temp_dir: PathBuf,
+    dirs: Vec<PathBuf>,
+    min_age: Duration,
+    dry_run: bool,
+}
+
+impl TempSweep {
+    pub fn new(temp_dir: impl Into<PathBuf>, min_age: Duration) -> Self {
+        Self {
+            temp_dir: temp_dir.into(),
+            dirs: Vec::new(),
+            min_age,
+            dry_run: false,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(
+            &config.app.temp_dir,
+            Duration::from_secs(config.cleanup.min_age_hours * 3600),
+        )
+    }
+
+    /// Also looks for stale artifacts directly inside `dirs`.
+    pub fn with_dirs(mut self, dirs: impl IntoIterator<Item = PathBuf>) -> Self {
+        self.dirs.extend(dirs);
+        self
+    }
+
+    pub fn with_min_age(mut self, min_age: Duration) -> Self {
+        self.min_age = min_age;
+        self
+    }
+
+    /// Only reports what would be removed.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn run(&self) -> SweepReport {
+        let mut report = SweepReport {
+            dry_run: self.dry_run,
+            ..SweepReport::default()
+        };
+        let mut dirs = vec![self.temp_dir.clone()];
+        dirs.extend(self.dirs.iter().cloned());
+
+        let mut active_jobs = HashSet::new();
+        let mut dead_jobs = HashSet::new();
+        for (path, name) in entries(&self.temp_dir) {
+            let Some(id) = name.strip_prefix(JOB_DIR_PREFIX) else {
+                continue;
+            };
+            if !path.is_dir() {
+                continue;
+            }
+
+            let marker = JobMarker::read(&path);
+            let stale = match marker.as_ref().and_then(|m| process_running(m.pid)) {
+                Some(running) => !running,
+                None => self.is_old(&path),
+            };
+            if !stale {
+                debug!(
+                    "Keeping temp directory of a running job: {}",
+                    path.display()
+                );
+                active_jobs.insert(id.to_string());
+                continue;
+            }
+
+            if let Some(output_dir) = marker.and_then(|m| m.output_dir) {
+                if !dirs.contains(&output_dir) {
+                    dirs.push(output_dir);
+                }
+            }
+            dead_jobs.insert(id.to_string());
+            self.remove(&path, &mut report);
+        }
+        report.active_jobs = active_jobs.len();
+
+        for dir in &dirs {
+            for (path, name) in entries(dir) {
+                let stale = match classify(&name) {
+                    None => continue,
+                    Some(Artifact::Job(id)) if active_jobs.contains(id) => false,
+                    Some(Artifact::Job(id)) if dead_jobs.contains(id) => true,
+                    Some(_) => self.is_old(&path),
+                };
+                if stale && path.is_file() {
+                    self.remove(&path, &mut report);
+                }
+            }
+        }
+
+        report
+    }
+
+    fn is_old(&self, path: &Path) -> bool {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age >= self.min_age)
+    }
+
+    fn remove(&self, path: &Path, report: &mut SweepReport) {
+        let size: u64 = WalkDir::new(path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+
+        if !self.dry_run {
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            };
+            if let Err(e) = result {
+                warn!("Failed to remove {}: {}", path.display(), e);
+                report.errors.push(format!("{}: {}", path.display(), e));
+                return;
+            }
+        }
+
+        debug!("Stale temporary item: {}", path.display());
+        report.removed.push(path.to_path_buf());
+        report.reclaimed_bytes += size;
+    }
+}
+
+/// Entries directly inside `dir` with their file names; nothing if `dir`
+/// cannot be read.
+fn entries(dir: &Path) -> Vec<(PathBuf, String)> {
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    read_dir
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            (
+                entry.path(),
+                entry.file_name().to_string_lossy().into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Runs the sweep before encoding when `cleanup.on_startup` is set.
+pub fn sweep_on_startup(config: &Config) {
+    if !config.cleanup.on_startup {
+        return;
+    }
+    let report = TempSweep::from_config(config).run();
+    if !report.removed.is_empty() {
+        info!("{} from earlier runs", report.summary());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    #[test]
+    fn test_classify_artifacts() {
+        let id = "0123456789abcdef0123456789abcdef";
+        assert_eq!(
+            classify(&format!("temp_encode_{}_movie.mkv", id)),
+            Some(Artifact::Job(id))
+        );
+        assert_eq!(classify("temp_encode_movie.mkv"), Some(Artifact::Orphan));
+        assert_eq!(classify("temp_mux_short_movie.mkv"), Some(Artifact::Orphan));
+        assert_eq!(classify("movie.rpu.bin"), Some(Artifact::Orphan));
+        assert_eq!(classify("movie_rpu_1234.bin"), Some(Artifact::Orphan));
+        assert_eq!(
+            classify("hdr10plus_metadata_1234.json"),
+            Some(Artifact::Orphan)
+        );
+        assert_eq!(classify("temp_hevc_rpu_1234.hevc"), Some(Artifact::Orphan));
+        assert_eq!(classify("bluray_MOVIE_00800.m2ts"), Some(Artifact::Orphan));
+        assert_eq!(classify("movie.mkv"), None);
+        assert_eq!(classify("rpu_notes.txt"), None);
+    }
+
+    #[test]
+    fn test_sweep_uses_markers_and_age() {
+        let temp = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+        let touch = |path: &Path, age_hours: u64| {
+            std::fs::write(path, b"data").unwrap();
+            File::options()
+                .write(true)
+                .open(path)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(age_hours * 3600))
+                .unwrap();
+        };
+
+        // A job of this (running) process and one of a process that is gone
+        let running_id = "a".repeat(32);
+        let crashed_id = "b".repeat(32);
+        for (id, pid) in [(&running_id, std::process::id()), (&crashed_id, u32::MAX)] {
+            let dir = temp.path().join(format!("{}{}", JOB_DIR_PREFIX, id));
+            std::fs::create_dir(&dir).unwrap();
+            let marker = JobMarker {
+                pid,
+                output_dir: Some(output.path().to_path_buf()),
+            };
+            std::fs::write(dir.join("job.marker"), marker.to_string()).unwrap();
+            touch(&dir.join("rpu_1.bin"), 0);
+            touch(
+                &output.path().join(format!("temp_encode_{}_movie.mkv", id)),
+                0,
+            );
+        }
+        touch(&temp.path().join("movie_rpu_old.bin"), 48);
+        touch(&temp.path().join("movie_rpu_new.bin"), 0);
+        touch(&output.path().join("movie.mkv"), 48);
+
+        let report = TempSweep::new(temp.path(), Duration::from_secs(24 * 3600)).run();
+        assert_eq!(report.active_jobs, 1);
+        assert_eq!(report.removed.len(), 3);
+        assert!(report.errors.is_empty());
+
+        let remaining = |dir: &Path| {
+            let mut names: Vec<String> = entries(dir).into_iter().map(|(_, name)| name).collect();
+            names.sort();
+            names
+        };
+        assert_eq!(
+            remaining(temp.path()),
+            vec![
+                "movie_rpu_new.bin".to_string(),
+                format!("{}{}", JOB_DIR_PREFIX, running_id),
+            ]
+        );
+        assert_eq!(
+            remaining(output.path()),
+            vec![
+                "movie.mkv".to_string(),
+                format!("temp_encode_{}_movie.mkv", running_id),
+            ]
+        );
+    }
+}
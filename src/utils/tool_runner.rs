@@ -1,9 +1,87 @@
-use crate::utils::{Error, Result};
+use crate::events::{EncodeEvent, EventBus};
+use crate::utils::{CancelToken, Error, FfmpegWrapper, Result, ToolFailureKind};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
 use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::sync::LazyLock;
 use std::time::Duration;
-use tokio::process::Command;
-use tracing::{debug, error};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tracing::{debug, error, warn};
+
+/// How often a monitored tool's progress is sampled
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+/// Output lines of a monitored tool kept for the error message
+const OUTPUT_TAIL_LINES: usize = 20;
+
+static PERCENT_PATTERN: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(\d{1,3}(?:\.\d+)?)\s*%").expect("valid percent pattern"));
+
+/// Where a monitored tool reads the source video from.
+#[derive(Debug, Clone, Copy)]
+pub enum ToolInput<'a> {
+    /// The tool opens the file itself
+    File(&'a Path),
+    /// The tool reads stdin, fed by `ffmpeg` demuxing the selected video
+    /// stream of `source` to an HEVC Annex B elementary stream, so no
+    /// intermediate file is written
+    HevcPipe {
+        ffmpeg: &'a FfmpegWrapper,
+        source: &'a Path,
+    },
+}
+
+impl ToolInput<'_> {
+    /// Input argument for the tool: the file, or `-` for stdin.
+    pub fn arg(&self) -> String {
+        match self {
+            ToolInput::File(path) => path.to_string_lossy().to_string(),
+            ToolInput::HevcPipe { .. } => "-".to_string(),
+        }
+    }
+
+    pub fn source(&self) -> &Path {
+        match self {
+            ToolInput::File(path) => path,
+            ToolInput::HevcPipe { source, .. } => source,
+        }
+    }
+}
+
+/// FFmpeg arguments writing the HEVC elementary stream of the video stream
+/// `ffmpeg` selects (`--video-stream`) in `source` to stdout.
+fn hevc_pipe_args(ffmpeg: &FfmpegWrapper, source: &Path) -> Vec<String> {
+    let mut args: Vec<String> = ["-hide_banner", "-loglevel", "error", "-nostdin"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    args.extend(ffmpeg.input_options().iter().cloned());
+    args.extend([
+        "-i".to_string(),
+        source.to_string_lossy().to_string(),
+        "-map".to_string(),
+        ffmpeg.video_map_specifier(),
+    ]);
+    args.extend(
+        [
+            "-c:v",
+            "copy",
+            "-bsf:v",
+            "hevc_mp4toannexb",
+            "-f",
+            "hevc",
+            "-",
+        ]
+        .iter()
+        .map(|arg| arg.to_string()),
+    );
+    args
+}
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolConfig {
@@ -11,6 +89,16 @@ pub struct ToolConfig {
     pub timeout_seconds: u64,
     pub extract_args: Option<Vec<String>>,
     pub inject_args: Option<Vec<String>>,
+    /// Extra attempts after a timeout or crash (rejected input is not retried)
+    #[serde(default)]
+    pub retries: u32,
+    /// Pause before the first retry, doubled for each further one
+    #[serde(default = "default_retry_backoff_seconds")]
+    pub retry_backoff_seconds: u64,
+}
+
+pub fn default_retry_backoff_seconds() -> u64 {
+    5
 }
 
 impl Default for ToolConfig {
@@ -20,52 +108,67 @@ impl Default for ToolConfig {
             timeout_seconds: 300,
             extract_args: None,
             inject_args: None,
+            retries: 0,
+            retry_backoff_seconds: default_retry_backoff_seconds(),
+        }
+    }
+}
+
+/// Where [`ToolRunner::run_monitored`] reports progress: as
+/// [`EncodeEvent::ToolProgress`] on `events`, and on a terminal bar unless
+/// `show_bar` is off (events mode, parallel or batch encodes).
+#[derive(Clone)]
+pub struct ToolProgress {
+    pub events: EventBus,
+    pub show_bar: bool,
+}
+
+impl Default for ToolProgress {
+    fn default() -> Self {
+        Self {
+            events: EventBus::new(),
+            show_bar: true,
         }
     }
 }
 
+impl std::fmt::Debug for ToolProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolProgress")
+            .field("show_bar", &self.show_bar)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ToolRunner {
     config: ToolConfig,
     timeout: Duration,
+    cancel: CancelToken,
+    progress: ToolProgress,
 }
 
 impl ToolRunner {
     pub fn new(config: ToolConfig) -> Self {
         let timeout = Duration::from_secs(config.timeout_seconds);
-        Self { config, timeout }
-    }
-
-    pub async fn check_availability(
-        &self,
-        help_arg: &str,
-        expected_subcommand: &str,
-    ) -> Result<()> {
-        debug!("Checking tool availability at: {}", self.config.path);
-
-        let output = Command::new(&self.config.path)
-            .arg(help_arg)
-            .output()
-            .await
-            .map_err(|e| Error::Tool(format!("Failed to run tool: {}", e)))?;
-
-        if !output.status.success() {
-            return Err(Error::Tool(format!(
-                "Tool check failed with exit code: {}",
-                output.status
-            )));
+        Self {
+            config,
+            timeout,
+            cancel: CancelToken::new(),
+            progress: ToolProgress::default(),
         }
+    }
 
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        if !stdout.contains(expected_subcommand) {
-            return Err(Error::Tool(format!(
-                "Tool appears to be missing required subcommand: {}",
-                expected_subcommand
-            )));
-        }
+    /// Lets `cancel` stop runs started with [`run_monitored`](Self::run_monitored)
+    /// and any pause between retries.
+    pub fn set_cancel_token(&mut self, cancel: CancelToken) {
+        self.cancel = cancel;
+    }
 
-        debug!("Tool is available and functional");
-        Ok(())
+    /// Reports the progress of runs started with [`run_monitored`](Self::run_monitored)
+    /// through `progress`.
+    pub fn set_progress(&mut self, progress: ToolProgress) {
+        self.progress = progress;
     }
 
     pub async fn run(&self, args: &[String], output_file: Option<&Path>) -> Result<String> {
@@ -81,9 +184,58 @@ impl ToolRunner {
         args: &[String],
         output_file: Option<&Path>,
         log_errors: bool,
+    ) -> Result<String> {
+        self.retrying(|| self.run_once(args, output_file, log_errors))
+            .await
+    }
+
+    /// Repeats `attempt` after timeouts and crashes, up to the configured
+    /// number of retries, with exponential backoff in between.
+    async fn retrying<F, Fut>(&self, mut attempt: F) -> Result<String>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<String>>,
+    {
+        let mut retries_left = self.config.retries;
+        let mut backoff = Duration::from_secs(self.config.retry_backoff_seconds);
+        loop {
+            match attempt().await {
+                Err(e)
+                    if retries_left > 0
+                        && e.tool_failure_kind()
+                            .is_some_and(ToolFailureKind::is_transient) =>
+                {
+                    warn!(
+                        "{} failed ({}), retrying in {}s ({} attempt(s) left)",
+                        self.config.path,
+                        e,
+                        backoff.as_secs(),
+                        retries_left
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(backoff) => {}
+                        _ = self.cancel.cancelled() => {
+                            return Err(Error::cancelled(format!("{} not retried", self.config.path)));
+                        }
+                    }
+                    retries_left -= 1;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn run_once(
+        &self,
+        args: &[String],
+        output_file: Option<&Path>,
+        log_errors: bool,
     ) -> Result<String> {
         let mut command = Command::new(&self.config.path);
         command.args(args);
+        // A timed out tool must not keep running next to its retry
+        command.kill_on_drop(true);
 
         // Always capture stdout/stderr to prevent external tool messages from showing
         // without timestamps, which breaks log formatting
@@ -98,34 +250,16 @@ impl ToolRunner {
 
         let output = tokio::time::timeout(self.timeout, child.wait_with_output())
             .await
-            .map_err(|_| {
-                Error::Tool(format!(
-                    "Tool timed out after {} seconds",
-                    self.timeout.as_secs()
-                ))
-            })?
+            .map_err(|_| self.timed_out())?
             .map_err(|e| Error::Tool(format!("Tool failed: {}", e)))?;
 
         if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let stdout = String::from_utf8_lossy(&output.stdout);
-
-            if log_errors {
-                error!("Tool failed:");
-                error!("Exit code: {}", output.status);
-                error!("Stdout: {}", stdout);
-                error!("Stderr: {}", stderr);
-            } else {
-                debug!("Tool failed (expected):");
-                debug!("Exit code: {}", output.status);
-                debug!("Stdout: {}", stdout);
-                debug!("Stderr: {}", stderr);
-            }
-
-            return Err(Error::Tool(format!(
-                "Tool failed with exit code {}: {}",
-                output.status, stderr
-            )));
+            return Err(Self::failure(
+                output.status,
+                &String::from_utf8_lossy(&output.stdout),
+                &String::from_utf8_lossy(&output.stderr),
+                log_errors,
+            ));
         }
 
         // Log non-empty stdout/stderr from successful runs
@@ -152,15 +286,269 @@ impl ToolRunner {
 
         if let Some(file) = output_file {
             if !file.exists() {
-                return Err(Error::Tool(
-                    "Tool completed but output file not found".to_string(),
-                ));
+                return Err(Self::missing_output());
             }
         }
 
         Ok(stdout.to_string())
     }
 
+    fn timed_out(&self) -> Error {
+        Error::tool_failed(
+            ToolFailureKind::Timeout,
+            format!("Tool timed out after {} seconds", self.timeout.as_secs()),
+        )
+    }
+
+    fn missing_output() -> Error {
+        Error::tool_failed(
+            ToolFailureKind::Crashed,
+            "Tool completed but output file not found",
+        )
+    }
+
+    fn failure(status: ExitStatus, stdout: &str, stderr: &str, log_errors: bool) -> Error {
+        if log_errors {
+            error!("Tool failed:");
+            error!("Exit code: {}", status);
+            error!("Stdout: {}", stdout);
+            error!("Stderr: {}", stderr);
+        } else {
+            debug!("Tool failed (expected):");
+            debug!("Exit code: {}", status);
+            debug!("Stdout: {}", stdout);
+            debug!("Stderr: {}", stderr);
+        }
+
+        // Killed by a signal, or a Rust panic (exit code 101)
+        let kind = match status.code() {
+            None | Some(101) => ToolFailureKind::Crashed,
+            Some(_) => ToolFailureKind::InvalidInput,
+        };
+        Error::tool_failed(
+            kind,
+            format!("Tool failed with exit code {}: {}", status, stderr),
+        )
+    }
+
+    /// Runs a long extraction, injection or remux over `input`, streaming the
+    /// tool's output to the debug log and showing its progress on a bar: the
+    /// percentages the tool prints (`Progress: 42%` from mkvmerge, dovi_tool's
+    /// bar), or else how far it has read `input`. Cancelling the runner's
+    /// token, or hitting the timeout, kills the tool (and the FFmpeg feeding
+    /// it) and removes the partial `output_file`.
+    pub async fn run_monitored(
+        &self,
+        args: &[String],
+        input: ToolInput<'_>,
+        output_file: &Path,
+        label: &str,
+        log_errors: bool,
+    ) -> Result<String> {
+        self.retrying(|| self.run_monitored_once(args, input, output_file, label, log_errors))
+            .await
+    }
+
+    async fn run_monitored_once(
+        &self,
+        args: &[String],
+        input: ToolInput<'_>,
+        output_file: &Path,
+        label: &str,
+        log_errors: bool,
+    ) -> Result<String> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::cancelled(format!("{} not started", label)));
+        }
+        super::filesystem::ensure_not_source(input.source(), output_file)?;
+
+        let mut feeder = match input {
+            ToolInput::HevcPipe { ffmpeg, source } => {
+                let args = hevc_pipe_args(ffmpeg, source);
+                let ffmpeg = ffmpeg.get_ffmpeg_path();
+                debug!("Piping from: {} {}", ffmpeg, args.join(" "));
+                let feeder = Command::new(ffmpeg)
+                    .args(&args)
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .kill_on_drop(true)
+                    .spawn()
+                    .map_err(|e| Error::ffmpeg(format!("Failed to spawn FFmpeg: {}", e)))?;
+                Some(feeder)
+            }
+            ToolInput::File(_) => None,
+        };
+
+        debug!("Running: {} {}", self.config.path, args.join(" "));
+
+        let mut command = Command::new(&self.config.path);
+        command
+            .args(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut feeder_stderr = None;
+        if let Some(feeder) = feeder.as_mut() {
+            let stdin: Stdio = feeder
+                .stdout
+                .take()
+                .expect("stdout is piped")
+                .try_into()
+                .map_err(|e| Error::Tool(format!("Failed to connect FFmpeg pipe: {}", e)))?;
+            command.stdin(stdin);
+
+            let mut stderr = feeder.stderr.take().expect("stderr is piped");
+            feeder_stderr = Some(tokio::spawn(async move {
+                let mut buffer = Vec::new();
+                let _ = stderr.read_to_end(&mut buffer).await;
+                String::from_utf8_lossy(&buffer).into_owned()
+            }));
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| Error::Tool(format!("Failed to spawn tool: {}", e)))?;
+        // With a pipe, FFmpeg is the process reading the source
+        let pid = feeder.as_ref().map_or(child.id(), |feeder| feeder.id());
+
+        let (percent_sender, percent) = watch::channel(None);
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stdout_task = tokio::spawn(read_tool_output(stdout, percent_sender.clone()));
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stderr_task = tokio::spawn(read_tool_output(stderr, percent_sender));
+
+        let progress_bar = Self::progress_bar(input.source(), label);
+        if !self.progress.show_bar {
+            progress_bar.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        let mut reported = None;
+        let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+        let deadline = tokio::time::sleep(self.timeout);
+        tokio::pin!(deadline);
+
+        let status = loop {
+            tokio::select! {
+                status = child.wait() => {
+                    break status.map_err(|e| Error::Tool(format!("Tool failed: {}", e)))?;
+                }
+                _ = ticker.tick() => {
+                    if let Some(percent) = *percent.borrow() {
+                        match progress_bar.length() {
+                            Some(total) => {
+                                progress_bar.set_position((total as f64 * percent / 100.0) as u64)
+                            }
+                            None => progress_bar.set_message(format!("{} {:.0}%", label, percent)),
+                        }
+                    } else if let Some(read) =
+                        pid.and_then(bytes_read).or_else(|| bytes_written(output_file))
+                    {
+                        // rchar also counts reads besides the input
+                        let total = progress_bar.length().unwrap_or(read);
+                        progress_bar.set_position(read.min(total));
+                    }
+                    let done = match progress_bar.length() {
+                        Some(total) if total > 0 => {
+                            Some(progress_bar.position() as f64 * 100.0 / total as f64)
+                        }
+                        _ => *percent.borrow(),
+                    };
+                    if let Some(done) = done.map(f64::floor).filter(|d| reported != Some(*d)) {
+                        reported = Some(done);
+                        self.progress.events.emit(EncodeEvent::ToolProgress {
+                            tool: label.to_string(),
+                            percent: done,
+                        });
+                    }
+                }
+                _ = &mut deadline => {
+                    Self::abort(&mut child, feeder.as_mut(), output_file, &progress_bar).await;
+                    return Err(self.timed_out());
+                }
+                _ = self.cancel.cancelled() => {
+                    Self::abort(&mut child, feeder.as_mut(), output_file, &progress_bar).await;
+                    return Err(Error::cancelled(format!("{} stopped", label)));
+                }
+            }
+        };
+        progress_bar.finish_and_clear();
+
+        let stdout = stdout_task.await.unwrap_or_default();
+        let stderr = stderr_task.await.unwrap_or_default();
+        if !status.success() {
+            // The tool's error explains more than FFmpeg's broken pipe
+            if let Some(feeder) = feeder.as_mut() {
+                let _ = feeder.kill().await;
+            }
+            return Err(Self::failure(status, &stdout, &stderr, log_errors));
+        }
+
+        if let Some(feeder) = feeder.as_mut() {
+            let feeder_status = feeder
+                .wait()
+                .await
+                .map_err(|e| Error::ffmpeg(format!("FFmpeg pipe failed: {}", e)))?;
+            if !feeder_status.success() {
+                let _ = tokio::fs::remove_file(output_file).await;
+                let stderr = match feeder_stderr {
+                    Some(task) => task.await.unwrap_or_default(),
+                    None => String::new(),
+                };
+                return Err(Error::ffmpeg(format!(
+                    "FFmpeg pipe exited with {}, the tool saw a truncated stream: {}",
+                    feeder_status,
+                    stderr.trim()
+                )));
+            }
+        }
+
+        if !output_file.exists() {
+            return Err(Self::missing_output());
+        }
+
+        Ok(stdout)
+    }
+
+    fn progress_bar(input: &Path, label: &str) -> ProgressBar {
+        let progress_bar = match std::fs::metadata(input) {
+            Ok(metadata) => ProgressBar::new(metadata.len()).with_style(
+                ProgressStyle::with_template(
+                    "{msg} [{bar:30.cyan/blue}] {bytes}/{total_bytes} ({bytes_per_sec})",
+                )
+                .unwrap_or_else(|_| ProgressStyle::default_bar())
+                .progress_chars("#>-"),
+            ),
+            Err(_) => ProgressBar::new_spinner(),
+        };
+        progress_bar.set_message(label.to_string());
+        progress_bar
+    }
+
+    async fn abort(
+        child: &mut Child,
+        feeder: Option<&mut Child>,
+        output_file: &Path,
+        progress_bar: &ProgressBar,
+    ) {
+        progress_bar.abandon();
+        if let Err(e) = child.kill().await {
+            debug!("Failed to kill tool: {}", e);
+        }
+        if let Some(feeder) = feeder {
+            if let Err(e) = feeder.kill().await {
+                debug!("Failed to kill FFmpeg pipe: {}", e);
+            }
+        }
+        if output_file.exists() {
+            match tokio::fs::remove_file(output_file).await {
+                Ok(()) => debug!("Removed partial output: {}", output_file.display()),
+                Err(e) => debug!(
+                    "Failed to remove partial output {}: {}",
+                    output_file.display(),
+                    e
+                ),
+            }
+        }
+    }
+
     pub async fn run_with_custom_args<P: AsRef<Path>>(
         &self,
         base_args: &[String],
@@ -199,3 +587,236 @@ impl ToolRunner {
         &self.config
     }
 }
+
+/// Percentage on a tool's progress line, e.g. 42 in `Progress: 42%`.
+fn parse_percent(line: &str) -> Option<f64> {
+    PERCENT_PATTERN
+        .captures_iter(line)
+        .last()
+        .and_then(|captures| captures[1].parse::<f64>().ok())
+        .filter(|percent| *percent <= 100.0)
+}
+
+/// Reads a monitored tool's stdout or stderr until it closes. Lines are
+/// split at `\r` as well, since progress output redraws a single line;
+/// percentages go to `percent`, everything else to the debug log. Returns the
+/// last lines for the error message.
+async fn read_tool_output<R: AsyncRead + Unpin>(
+    mut reader: R,
+    percent: watch::Sender<Option<f64>>,
+) -> String {
+    let mut tail = VecDeque::with_capacity(OUTPUT_TAIL_LINES);
+    let mut handle_line = |line: &[u8]| {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+        if let Some(value) = parse_percent(line) {
+            percent.send_replace(Some(value));
+            return;
+        }
+        debug!("  {}", line);
+        if tail.len() == OUTPUT_TAIL_LINES {
+            tail.pop_front();
+        }
+        tail.push_back(line.to_string());
+    };
+
+    let mut pending = Vec::new();
+    let mut chunk = [0u8; 4096];
+    while let Ok(read) = reader.read(&mut chunk).await {
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&chunk[..read]);
+        while let Some(end) = pending.iter().position(|&b| b == b'\n' || b == b'\r') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            handle_line(&line[..end]);
+        }
+    }
+    handle_line(&pending);
+
+    Vec::from(tail).join("\n")
+}
+
+/// Bytes a running process has read so far (`rchar` in `/proc/<pid>/io`);
+/// `None` where procfs is unavailable.
+#[cfg(target_os = "linux")]
+fn bytes_read(pid: u32) -> Option<u64> {
+    let io = std::fs::read_to_string(format!("/proc/{}/io", pid)).ok()?;
+    io.lines()
+        .find_map(|line| line.strip_prefix("rchar:"))
+        .and_then(|value| value.trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn bytes_read(_pid: u32) -> Option<u64> {
+    None
+}
+
+/// Size of the output written so far: the fallback without procfs, close to
+/// the input read for remuxes and RPU injection.
+fn bytes_written(output_file: &Path) -> Option<u64> {
+    std::fs::metadata(output_file)
+        .ok()
+        .map(|metadata| metadata.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cancelled_run_removes_partial_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("partial.bin");
+        let mut runner = ToolRunner::new(ToolConfig {
+            path: "sh".to_string(),
+            timeout_seconds: 30,
+            ..ToolConfig::default()
+        });
+        let cancel = CancelToken::new();
+        runner.set_cancel_token(cancel.clone());
+
+        let args = vec![
+            "-c".to_string(),
+            format!("echo partial > '{}'; sleep 30", output.display()),
+        ];
+        let run = runner.run_monitored(&args, ToolInput::File(dir.path()), &output, "test", false);
+        let canceller = async {
+            while !output.exists() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            cancel.cancel();
+        };
+
+        let (result, _) = tokio::join!(run, canceller);
+        assert!(matches!(result, Err(Error::Cancelled(_))));
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn test_hevc_pipe_input() {
+        let source = Path::new("/media/movie.mkv");
+        let ffmpeg = FfmpegWrapper::new("ffmpeg".to_string(), "ffprobe".to_string());
+        let input = ToolInput::HevcPipe {
+            ffmpeg: &ffmpeg,
+            source,
+        };
+        assert_eq!(input.arg(), "-");
+        assert_eq!(input.source(), source);
+        assert_eq!(ToolInput::File(source).arg(), "/media/movie.mkv");
+
+        let args = hevc_pipe_args(&ffmpeg, source).join(" ");
+        assert!(args.contains("-i /media/movie.mkv -map 0:v:0 -c:v copy"));
+        assert!(args.ends_with("-bsf:v hevc_mp4toannexb -f hevc -"));
+
+        // --video-stream picks the stream, with the wrapper's input options
+        let ffmpeg = ffmpeg
+            .with_video_stream(Some(1))
+            .with_input_options(["-err_detect", "ignore_err"]);
+        let args = hevc_pipe_args(&ffmpeg, source).join(" ");
+        assert!(args.contains("-err_detect ignore_err -i /media/movie.mkv -map 0:v:1 -c:v copy"));
+    }
+
+    #[tokio::test]
+    async fn test_progress_output() {
+        assert_eq!(parse_percent("Progress: 42%"), Some(42.0));
+        assert_eq!(parse_percent("#GUI#progress 7%"), Some(7.0));
+        assert_eq!(
+            parse_percent("[00:00:03] ####---- 12.5 % (ETA 20s)"),
+            Some(12.5)
+        );
+        assert_eq!(
+            parse_percent("Rewriting file with interleaved RPU NALs.."),
+            None
+        );
+
+        let (sender, percent) = watch::channel(None);
+        let output: &[u8] = b"mkvmerge v82.0\nProgress: 10%\rProgress: 55%\rProgress: 100%\nDone\n";
+        let tail = read_tool_output(output, sender).await;
+        assert_eq!(*percent.borrow(), Some(100.0));
+        assert_eq!(tail, "mkvmerge v82.0\nDone");
+
+        // Without its own bar, the run reports progress as events only
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("input.hevc");
+        let output = dir.path().join("output.mkv");
+        std::fs::write(&input, [0u8; 1000]).unwrap();
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut events = EventBus::new();
+        events.subscribe(std::sync::Arc::new(sender));
+        let mut runner = ToolRunner::new(ToolConfig {
+            path: "sh".to_string(),
+            ..ToolConfig::default()
+        });
+        runner.set_progress(ToolProgress {
+            events,
+            show_bar: false,
+        });
+        let args = vec![
+            "-c".to_string(),
+            format!(
+                "echo 'Progress: 50%'; sleep 1; touch '{}'",
+                output.display()
+            ),
+        ];
+        runner
+            .run_monitored(&args, ToolInput::File(&input), &output, "Muxing", false)
+            .await
+            .unwrap();
+        let mut reported = Vec::new();
+        while let Ok(EncodeEvent::ToolProgress { tool, percent }) = receiver.try_recv() {
+            assert_eq!(tool, "Muxing");
+            reported.push(percent);
+        }
+        assert_eq!(reported.last(), Some(&50.0));
+    }
+
+    #[tokio::test]
+    async fn test_retries_crashes_but_not_rejected_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let attempts = dir.path().join("attempts");
+        let runner = ToolRunner::new(ToolConfig {
+            path: "sh".to_string(),
+            retries: 2,
+            retry_backoff_seconds: 0,
+            ..ToolConfig::default()
+        });
+        let script = |on_first: &str| {
+            vec![
+                "-c".to_string(),
+                format!(
+                    "echo x >> '{0}'; [ $(wc -l < '{0}') -ge 2 ] || {1}",
+                    attempts.display(),
+                    on_first
+                ),
+            ]
+        };
+
+        // Killed on the first attempt, succeeds on the retry
+        runner
+            .run_silent(&script("kill -KILL $$"), None)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&attempts).unwrap().lines().count(),
+            2
+        );
+
+        std::fs::remove_file(&attempts).unwrap();
+        let error = runner
+            .run_silent(&script("exit 2"), None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            error.tool_failure_kind(),
+            Some(ToolFailureKind::InvalidInput)
+        );
+        assert_eq!(
+            std::fs::read_to_string(&attempts).unwrap().lines().count(),
+            1
+        );
+    }
+}